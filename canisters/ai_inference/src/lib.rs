@@ -6,6 +6,11 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use k256::ecdsa::{SigningKey, VerifyingKey};
 use sha2::{Digest, Sha256};
+use safetensors::tensor::{Dtype, SafeTensors, TensorView};
+use governance::{
+    correlation_id_or_new, AdminApprovals, AdminOperation, AdminPolicy, AdminProposal, CallMetrics, CanisterMetrics,
+    ShadowDeployment, ShadowDisagreement, ShadowPolicy, ShadowScore, ShadowStatus, WASM_PAGE_BYTES,
+};
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct MedicalQuery {
@@ -13,6 +18,8 @@ pub struct MedicalQuery {
     pub symptoms: Vec<String>,
     pub medical_history: Vec<String>,
     pub timestamp: u64,
+    // Generated by the frontend per request; the canister makes one when left out
+    pub correlation_id: Option<String>,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -23,6 +30,7 @@ pub struct DiagnosisResult {
     pub risk_factors: Vec<String>,
     pub model_version: String,
     pub signature: Vec<u8>,
+    pub correlation_id: String,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -31,13 +39,26 @@ pub struct ModelWeights {
     pub weights: Vec<f32>,
     pub metadata: HashMap<String, String>,
     pub threshold_signature: Vec<u8>,
+    // Of the federated round that produced the model
+    pub correlation_id: Option<String>,
 }
 
 thread_local! {
     static MODEL_WEIGHTS: RefCell<Option<ModelWeights>> = RefCell::new(None);
     static SIGNING_KEY: RefCell<Option<SigningKey>> = RefCell::new(None);
+    // Models replaced by update_model_weights, oldest first, for rollback
+    static PREVIOUS_MODELS: RefCell<Vec<ModelWeights>> = RefCell::new(Vec::new());
+    static ADMIN: RefCell<AdminApprovals> = RefCell::new(AdminApprovals::new());
+    static CORRELATION_SEQUENCE: RefCell<u64> = RefCell::new(0);
+    static CALL_METRICS: RefCell<CallMetrics> = RefCell::new(CallMetrics::new());
+    // Candidate model scoring every query alongside production, and how the two compare
+    static SHADOW_MODEL: RefCell<Option<ModelWeights>> = RefCell::new(None);
+    static SHADOW: RefCell<Option<ShadowDeployment>> = RefCell::new(None);
+    static SHADOW_POLICY: RefCell<ShadowPolicy> = RefCell::new(ShadowPolicy::default());
 }
 
+const MAX_PREVIOUS_MODELS: usize = 5;
+
 #[init]
 fn init() {
     ic_cdk::println!("AI Inference Canister initialized");
@@ -67,20 +88,38 @@ async fn initialize_threshold_ecdsa() -> Result<(), String> {
 }
 
 #[update]
-fn update_model_weights(weights: ModelWeights) -> Result<String, String> {
+fn update_model_weights(mut weights: ModelWeights) -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    weights.correlation_id = Some(resolve_correlation_id(weights.correlation_id.take())?);
+    
     // Verify threshold signature before updating
     if !verify_threshold_signature(&weights) {
         return Err("Invalid threshold signature".to_string());
     }
+    let model_loaded = MODEL_WEIGHTS.with(|model| model.borrow().is_some());
+    if model_loaded && SHADOW_POLICY.with(|policy| policy.borrow().require_shadow) {
+        return Err("Models must be deployed in shadow and promoted".to_string());
+    }
     
-    MODEL_WEIGHTS.with(|model| {
-        *model.borrow_mut() = Some(weights.clone());
-    });
-    
-    ic_cdk::println!("Model weights updated to version: {}", weights.version);
+    install_model(weights.clone());
+    ic_cdk::println!("[{}] Model weights updated to version: {}", weights.correlation_id.as_deref().unwrap_or_default(), weights.version);
     Ok(format!("Model updated to version: {}", weights.version))
 }
 
+// Makes `weights` the production model, keeping the replaced one for rollback
+fn install_model(weights: ModelWeights) {
+    let replaced = MODEL_WEIGHTS.with(|model| model.borrow_mut().replace(weights));
+    if let Some(replaced) = replaced {
+        PREVIOUS_MODELS.with(|previous| {
+            let mut previous = previous.borrow_mut();
+            previous.push(replaced);
+            if previous.len() > MAX_PREVIOUS_MODELS {
+                previous.remove(0);
+            }
+        });
+    }
+}
+
 #[query]
 fn get_model_version() -> Option<String> {
     MODEL_WEIGHTS.with(|model| {
@@ -90,6 +129,15 @@ fn get_model_version() -> Option<String> {
 
 #[update]
 async fn diagnose(query: MedicalQuery) -> Result<DiagnosisResult, String> {
+    let result = run_diagnosis(query).await;
+    record_call("diagnose");
+    result
+}
+
+async fn run_diagnosis(mut query: MedicalQuery) -> Result<DiagnosisResult, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    query.correlation_id = Some(resolve_correlation_id(query.correlation_id.take())?);
+    
     let model = MODEL_WEIGHTS.with(|m| m.borrow().clone());
     
     let model_weights = model.ok_or("No model weights loaded")?;
@@ -97,6 +145,15 @@ async fn diagnose(query: MedicalQuery) -> Result<DiagnosisResult, String> {
     // Simulate AI inference (in production, this would use the actual model)
     let diagnosis_result = perform_inference(&query, &model_weights).await?;
     
+    // The shadow model scores the same query; its answer is compared, never returned
+    let shadow_model = SHADOW_MODEL.with(|m| m.borrow().clone());
+    if let Some(shadow_weights) = shadow_model {
+        match perform_inference(&query, &shadow_weights).await {
+            Ok(shadow_result) => record_shadow_comparison(&diagnosis_result, &shadow_result),
+            Err(e) => ic_cdk::println!("[{}] Shadow inference failed: {}", diagnosis_result.correlation_id, e),
+        }
+    }
+    
     // Sign the result with threshold-ECDSA
     let signed_result = sign_diagnosis_result(diagnosis_result).await?;
     
@@ -147,7 +204,8 @@ async fn perform_inference(query: &MedicalQuery, weights: &ModelWeights) -> Resu
     // Generate risk factors based on symptoms and history
     let risk_factors = calculate_risk_factors(&query.symptoms, &query.medical_history);
     
-    ic_cdk::println!("AI Inference completed: {} (confidence: {:.3})", primary_diagnosis, confidence);
+    let correlation_id = query.correlation_id.clone().unwrap_or_default();
+    ic_cdk::println!("[{}] AI Inference completed: {} (confidence: {:.3})", correlation_id, primary_diagnosis, confidence);
     
     Ok(DiagnosisResult {
         diagnosis: primary_diagnosis,
@@ -156,9 +214,20 @@ async fn perform_inference(query: &MedicalQuery, weights: &ModelWeights) -> Resu
         risk_factors,
         model_version: format!("{}_medical_ai", weights.version),
         signature: vec![], // Will be filled by sign_diagnosis_result
+        correlation_id,
     })
 }
 
+// The caller's correlation id, or a new one for calls that brought none
+fn resolve_correlation_id(supplied: Option<String>) -> Result<String, String> {
+    let sequence = CORRELATION_SEQUENCE.with(|sequence| {
+        let mut sequence = sequence.borrow_mut();
+        *sequence += 1;
+        *sequence
+    });
+    correlation_id_or_new(supplied, "ai_inference", ic_cdk::api::time(), sequence)
+}
+
 // Medical knowledge base for rare diseases
 fn get_rare_disease_knowledge_base() -> HashMap<String, DiseaseInfo> {
     let mut knowledge_base = HashMap::new();
@@ -420,16 +489,319 @@ fn verify_threshold_signature(weights: &ModelWeights) -> bool {
     !weights.threshold_signature.is_empty()
 }
 
+// Weights in the safetensors format, for loading into PyTorch. The tensor layout is
+// the "model_spec" metadata written by the federated_learning crate, or a single
+// tensor "weights" when the model has none.
+#[query]
+fn export_model_safetensors() -> Result<Vec<u8>, String> {
+    let model = MODEL_WEIGHTS.with(|m| m.borrow().clone()).ok_or("No model weights loaded")?;
+    let layout = tensor_layout(&model.metadata, model.weights.len())?;
+
+    let data: Vec<u8> = model.weights.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut views = Vec::new();
+    let mut offset = 0;
+    for (name, shape) in layout {
+        let len: usize = shape.iter().product();
+        let view = TensorView::new(Dtype::F32, shape, &data[offset * 4..(offset + len) * 4]).map_err(|e| e.to_string())?;
+        views.push((name, view));
+        offset += len;
+    }
+
+    let mut metadata = model.metadata.clone();
+    metadata.insert("format".to_string(), "pt".to_string());
+    metadata.insert("version".to_string(), model.version.clone());
+    if let Some(correlation_id) = &model.correlation_id {
+        metadata.insert("correlation_id".to_string(), correlation_id.clone());
+    }
+    safetensors::serialize(views, &Some(metadata)).map_err(|e| format!("Failed to write safetensors: {}", e))
+}
+
+// Loads a safetensors model, e.g. one fine-tuned in PyTorch, under the same
+// threshold-signature check as update_model_weights
+#[update]
+fn import_model_safetensors(bytes: Vec<u8>, threshold_signature: Vec<u8>) -> Result<String, String> {
+    let (_, header) = SafeTensors::read_metadata(&bytes).map_err(|e| format!("Invalid safetensors header: {}", e))?;
+    let tensors = SafeTensors::deserialize(&bytes).map_err(|e| format!("Invalid safetensors file: {}", e))?;
+    let metadata = header.metadata().clone().unwrap_or_default();
+    let version = metadata.get("version").cloned().ok_or("File has no version metadata")?;
+
+    let total: usize = tensors.tensors().iter().map(|(_, view)| view.shape().iter().product::<usize>()).sum();
+    let layout = tensor_layout(&metadata, total)?;
+    if layout.len() != tensors.len() {
+        return Err(format!("File has {} tensors, model_spec lists {}", tensors.len(), layout.len()));
+    }
+    let mut weights = Vec::with_capacity(total);
+    for (name, shape) in layout {
+        let view = tensors.tensor(&name).map_err(|_| format!("Tensor {} is missing", name))?;
+        if view.shape() != shape.as_slice() {
+            return Err(format!("Tensor {} has shape {:?}, model_spec says {:?}", name, view.shape(), shape));
+        }
+        weights.extend(decode_tensor(view.dtype(), view.data())?);
+    }
+
+    let mut metadata = metadata;
+    metadata.remove("format");
+    metadata.remove("version");
+    let correlation_id = metadata.remove("correlation_id");
+    update_model_weights(ModelWeights { version, weights, metadata, threshold_signature, correlation_id })
+}
+
+// (name, shape) of each tensor in weight order
+fn tensor_layout(metadata: &HashMap<String, String>, len: usize) -> Result<Vec<(String, Vec<usize>)>, String> {
+    let spec = match metadata.get("model_spec") {
+        Some(spec) => spec,
+        None => return Ok(vec![("weights".to_string(), vec![len])]),
+    };
+    let spec: serde_json::Value = serde_json::from_str(spec).map_err(|e| format!("Invalid model_spec metadata: {}", e))?;
+    let layout = spec["tensors"]
+        .as_array()
+        .ok_or("model_spec has no tensors")?
+        .iter()
+        .map(|tensor| {
+            let name = tensor["name"].as_str().ok_or("Tensor without a name")?.to_string();
+            let shape = tensor["shape"]
+                .as_array()
+                .ok_or("Tensor without a shape")?
+                .iter()
+                .map(|d| d.as_u64().map(|d| d as usize).ok_or("Invalid tensor dimension"))
+                .collect::<Result<Vec<usize>, &str>>()?;
+            Ok((name, shape))
+        })
+        .collect::<Result<Vec<_>, &str>>()?;
+    if layout.iter().map(|(_, shape)| shape.iter().product::<usize>()).sum::<usize>() != len {
+        return Err(format!("model_spec does not cover the model's {} weights", len));
+    }
+    Ok(layout)
+}
+
+fn decode_tensor(dtype: Dtype, data: &[u8]) -> Result<Vec<f32>, String> {
+    let halves = || data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    Ok(match dtype {
+        Dtype::F32 => data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+        Dtype::F64 => data.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap_or_default()) as f32).collect(),
+        Dtype::BF16 => halves().map(|h| f32::from_bits((h as u32) << 16)).collect(),
+        Dtype::F16 => halves().map(f16_to_f32).collect(),
+        other => return Err(format!("Unsupported tensor dtype {:?}", other)),
+    })
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[query]
+fn get_previous_model_versions() -> Vec<String> {
+    PREVIOUS_MODELS.with(|previous| previous.borrow().iter().map(|m| m.version.clone()).collect())
+}
+
+// Restores a replaced model; the models loaded after it are dropped
+#[update]
+fn rollback_model(version: String) -> Result<String, String> {
+    let position = PREVIOUS_MODELS.with(|previous| previous.borrow().iter().rposition(|m| m.version == version));
+    let position = position.ok_or_else(|| format!("Model version {} is not retained", version))?;
+    
+    let operation = AdminOperation::RollbackModel { version: version.clone() };
+    ADMIN.with(|admin| admin.borrow_mut().authorize_required(&operation, ic_cdk::caller(), ic_cdk::api::time()))?;
+    
+    let restored = PREVIOUS_MODELS.with(|previous| previous.borrow_mut().drain(position..).next());
+    MODEL_WEIGHTS.with(|model| *model.borrow_mut() = restored);
+    ic_cdk::println!("Model rolled back to version: {}", version);
+    Ok(format!("Model rolled back to version: {}", version))
+}
+
+fn shadow_score(result: &DiagnosisResult) -> ShadowScore {
+    ShadowScore {
+        model_version: result.model_version.clone(),
+        diagnosis: result.diagnosis.clone(),
+        confidence: result.confidence,
+    }
+}
+
+fn record_shadow_comparison(production: &DiagnosisResult, shadow: &DiagnosisResult) {
+    SHADOW.with(|deployment| {
+        if let Some(deployment) = deployment.borrow_mut().as_mut() {
+            deployment.record(&production.correlation_id, shadow_score(production), shadow_score(shadow), ic_cdk::api::time());
+        }
+    });
+}
+
+// Starts scoring every query with `weights` as well, without serving its answers;
+// replaces any shadow model already running
+#[update]
+fn deploy_shadow_model(mut weights: ModelWeights) -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    weights.correlation_id = Some(resolve_correlation_id(weights.correlation_id.take())?);
+    if !verify_threshold_signature(&weights) {
+        return Err("Invalid threshold signature".to_string());
+    }
+    
+    let policy = SHADOW_POLICY.with(|policy| policy.borrow().clone());
+    let deployment = ShadowDeployment::new(policy, weights.version.clone(), ic_cdk::api::time())?;
+    SHADOW.with(|shadow| *shadow.borrow_mut() = Some(deployment));
+    SHADOW_MODEL.with(|model| *model.borrow_mut() = Some(weights.clone()));
+    ic_cdk::println!("[{}] Shadow model deployed: {}", weights.correlation_id.as_deref().unwrap_or_default(), weights.version);
+    Ok(format!("Shadow model deployed: {}", weights.version))
+}
+
+#[query]
+fn get_shadow_status() -> Option<ShadowStatus> {
+    SHADOW.with(|shadow| shadow.borrow().as_ref().map(|s| s.status(ic_cdk::api::time())))
+}
+
+#[query]
+fn get_shadow_disagreements() -> Vec<ShadowDisagreement> {
+    SHADOW.with(|shadow| shadow.borrow().as_ref().map(|s| s.disagreements().to_vec()).unwrap_or_default())
+}
+
+// Replaces the production model with the shadow model once parity has held for the
+// policy's period
+#[update]
+fn promote_shadow_model() -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    SHADOW.with(|shadow| match shadow.borrow().as_ref() {
+        Some(deployment) => deployment.check_promotion(ic_cdk::api::time()),
+        None => Err("No shadow model deployed".to_string()),
+    })?;
+    
+    let weights = SHADOW_MODEL.with(|model| model.borrow_mut().take()).ok_or("No shadow model deployed")?;
+    SHADOW.with(|shadow| *shadow.borrow_mut() = None);
+    let version = weights.version.clone();
+    install_model(weights);
+    ic_cdk::println!("Shadow model promoted to production: {}", version);
+    Ok(format!("Model updated to version: {}", version))
+}
+
+// Drops the shadow model without promoting it
+#[update]
+fn stop_shadow_model() -> Result<(), String> {
+    let stopped = SHADOW_MODEL.with(|model| model.borrow_mut().take()).ok_or("No shadow model deployed")?;
+    SHADOW.with(|shadow| *shadow.borrow_mut() = None);
+    ic_cdk::println!("Shadow model stopped: {}", stopped.version);
+    Ok(())
+}
+
+// An admin operation once an admin policy is configured; a running shadow
+// deployment restarts its parity period under the new policy
+#[update]
+fn configure_shadow_policy(policy: ShadowPolicy) -> Result<(), String> {
+    policy.validate()?;
+    let operation = AdminOperation::ChangeShadowPolicy { policy: policy.clone() };
+    ADMIN.with(|admin| admin.borrow_mut().authorize(&operation, ic_cdk::caller(), ic_cdk::api::time()))?;
+    SHADOW.with(|shadow| match shadow.borrow_mut().as_mut() {
+        Some(deployment) => deployment.set_policy(policy.clone()),
+        None => Ok(()),
+    })?;
+    SHADOW_POLICY.with(|current| *current.borrow_mut() = policy);
+    Ok(())
+}
+
+#[query]
+fn get_shadow_policy() -> ShadowPolicy {
+    SHADOW_POLICY.with(|policy| policy.borrow().clone())
+}
+
+// Admin operations: proposed by one admin, approved by `threshold` of them, then
+// executed through the operation's own endpoint
+#[update]
+fn configure_admin_policy(policy: AdminPolicy) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    ADMIN.with(|admin| admin.borrow_mut().configure(policy, caller, ic_cdk::api::is_controller(&caller), ic_cdk::api::time()))
+}
+
+#[update]
+fn propose_admin_operation(operation: AdminOperation) -> Result<u64, String> {
+    if let AdminOperation::ResetPrivacyBudget { .. } = operation {
+        return Err("Privacy budgets are reset on the privacy engine".to_string());
+    }
+    ADMIN.with(|admin| admin.borrow_mut().propose(operation, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn approve_admin_operation(proposal_id: u64) -> Result<u32, String> {
+    ADMIN.with(|admin| admin.borrow_mut().approve(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn cancel_admin_operation(proposal_id: u64) -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().cancel(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[query]
+fn get_admin_policy() -> Option<AdminPolicy> {
+    ADMIN.with(|admin| admin.borrow().policy().cloned())
+}
+
+#[query]
+fn get_admin_proposals() -> Vec<AdminProposal> {
+    ADMIN.with(|admin| admin.borrow().open_proposals(ic_cdk::api::time()))
+}
+
+// Stops diagnoses and model updates until resumed
+#[update]
+fn emergency_pause() -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().pause(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Inference paused by {}", ic_cdk::caller());
+    Ok(())
+}
+
+#[update]
+fn resume() -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().resume(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Inference resumed by {}", ic_cdk::caller());
+    Ok(())
+}
+
 #[query]
 fn get_canister_status() -> HashMap<String, String> {
     let mut status = HashMap::new();
-    status.insert("status".to_string(), "active".to_string());
+    let paused = ADMIN.with(|admin| admin.borrow().is_paused());
+    status.insert("status".to_string(), if paused { "paused" } else { "active" }.to_string());
     status.insert("model_loaded".to_string(), 
                  MODEL_WEIGHTS.with(|m| m.borrow().is_some().to_string()));
+    status.insert("shadow_model".to_string(),
+                 SHADOW_MODEL.with(|m| m.borrow().as_ref().map(|m| m.version.clone()).unwrap_or_default()));
     status.insert("threshold_ecdsa".to_string(), 
                  SIGNING_KEY.with(|k| k.borrow().is_some().to_string()));
     status
 }
 
+// Instructions the current call has used, awaits included
+fn record_call(method: &str) {
+    let instructions = ic_cdk::api::performance_counter(1);
+    CALL_METRICS.with(|metrics| metrics.borrow_mut().record(method, instructions));
+}
+
+fn heap_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_BYTES;
+    #[cfg(not(target_arch = "wasm32"))]
+    return 0;
+}
+
+// Per-method instruction counts and memory size, read by the load generator
+#[query]
+fn get_canister_metrics() -> CanisterMetrics {
+    let stable_bytes = ic_cdk::api::stable::stable64_size() * WASM_PAGE_BYTES;
+    CALL_METRICS.with(|metrics| metrics.borrow().snapshot(heap_bytes(), stable_bytes))
+}
+
 // Export Candid interface
-ic_cdk::export_candid!();
\ No newline at end of file
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    // Regenerates ai_inference.did from the Rust interface and fails on breaking changes
+    #[test]
+    fn test_candid_interface_is_compatible() {
+        let did = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("ai_inference.did");
+        candid_interface::check_interface(&did, &super::__get_candid_interface_tmp_hack()).unwrap();
+    }
+}
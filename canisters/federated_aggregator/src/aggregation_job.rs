@@ -0,0 +1,70 @@
+// Round aggregation as a chunked job, so a round with many or large updates is
+// averaged over as many messages as it needs instead of one. Each chunk adds the
+// sample-weighted gradients of whole updates to a running sum until it has summed
+// `AGGREGATION_CHUNK_VALUES` values (always at least one update); the last chunk
+// normalizes the sum and publishes the model. A cancelled or failed aggregation
+// discards the round's updates, refunds their privacy budget and opens a new round,
+// as when a round misses its deadline.
+
+use crate::{abandon_round, complete_aggregation, GradientUpdate};
+use jobs::{ChunkOutcome, ChunkedJob};
+
+pub const AGGREGATION_CHUNK_VALUES: usize = 1_000_000;
+
+pub struct AggregationJob {
+    round_id: u64,
+    updates: Vec<GradientUpdate>,
+    sums: Vec<f32>,
+    total_samples: u32,
+    next: usize,
+}
+
+impl AggregationJob {
+    pub fn new(round_id: u64, updates: Vec<GradientUpdate>) -> Result<Self, String> {
+        let dimension = updates.first().map(|u| u.gradients.len()).ok_or("No updates to aggregate")?;
+        Ok(AggregationJob { round_id, updates, sums: vec![0.0; dimension], total_samples: 0, next: 0 })
+    }
+}
+
+impl ChunkedJob for AggregationJob {
+    fn kind(&self) -> String {
+        format!("aggregate_round:{}", self.round_id)
+    }
+
+    fn total_units(&self) -> u64 {
+        self.updates.len() as u64
+    }
+
+    fn run_chunk(&mut self) -> Result<ChunkOutcome, String> {
+        let start = self.next;
+        let mut values = 0;
+        while self.next < self.updates.len() && (self.next == start || values < AGGREGATION_CHUNK_VALUES) {
+            let update = &self.updates[self.next];
+            if update.gradients.len() != self.sums.len() {
+                return Err("Gradient size mismatch".to_string());
+            }
+            // Weighted average by sample count
+            for (sum, &gradient) in self.sums.iter_mut().zip(&update.gradients) {
+                *sum += gradient * update.sample_count as f32;
+            }
+            self.total_samples += update.sample_count;
+            values += update.gradients.len();
+            self.next += 1;
+        }
+
+        let units = (self.next - start) as u64;
+        if self.next < self.updates.len() {
+            return Ok(ChunkOutcome::Continue { units });
+        }
+        if self.total_samples == 0 {
+            return Err("Updates report no samples".to_string());
+        }
+        let weights = self.sums.iter().map(|sum| sum / self.total_samples as f32).collect();
+        let version = complete_aggregation(&self.updates, weights);
+        Ok(ChunkOutcome::Done { units, result: Some(version) })
+    }
+
+    fn abort(&mut self) {
+        abandon_round(self.round_id);
+    }
+}
@@ -0,0 +1,268 @@
+// Model history with keyframe-plus-delta weight storage. The newest model is kept
+// in full; older rounds keep metadata plus either a full keyframe (every
+// `keyframe_interval` rounds) or a delta against the round before, and are rebuilt
+// on demand by replaying deltas from the nearest earlier keyframe.
+
+use crate::AggregatedModel;
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct HistoryConfig {
+    pub keyframe_interval: u32,
+    // Oldest rounds are evicted beyond this count; 0 keeps every round
+    pub max_retained_rounds: u32,
+    pub delta_encoding: DeltaEncoding,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub enum DeltaEncoding {
+    // New value of every changed weight; lossless
+    Sparse,
+    // 16-bit delta per weight with a per-round scale, taken against the rebuilt
+    // previous round so error stays within scale / 2 instead of accumulating
+    Quantized16,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct HistoryMemoryReport {
+    pub rounds_retained: u64,
+    pub keyframes: u64,
+    pub deltas: u64,
+    pub dense_bytes: u64,
+    pub stored_bytes: u64,
+    pub savings_ratio: f64,
+}
+
+#[derive(Clone, Debug)]
+enum WeightCheckpoint {
+    Keyframe(Vec<f32>),
+    SparseDelta { indices: Vec<u32>, values: Vec<f32> },
+    QuantizedDelta { scale: f32, values: Vec<i16> },
+}
+
+#[derive(Clone, Debug)]
+struct HistoryEntry {
+    // Model metadata with an empty weight vector
+    model: AggregatedModel,
+    checkpoint: WeightCheckpoint,
+}
+
+pub struct ModelHistory {
+    config: HistoryConfig,
+    entries: Vec<HistoryEntry>,
+    latest: Option<AggregatedModel>,
+    // Rebuilt weights of the newest entry, which the next delta is taken against
+    reconstructed: Vec<f32>,
+    rounds_since_keyframe: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            keyframe_interval: 10,
+            max_retained_rounds: 100,
+            delta_encoding: DeltaEncoding::Quantized16,
+        }
+    }
+}
+
+impl HistoryConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keyframe_interval == 0 {
+            return Err("keyframe_interval must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl WeightCheckpoint {
+    fn stored_bytes(&self) -> u64 {
+        let bytes = match self {
+            WeightCheckpoint::Keyframe(weights) => weights.len() * 4,
+            WeightCheckpoint::SparseDelta { indices, values } => indices.len() * 4 + values.len() * 4,
+            WeightCheckpoint::QuantizedDelta { values, .. } => 4 + values.len() * 2,
+        };
+        bytes as u64
+    }
+
+    fn apply(&self, weights: &mut Vec<f32>) {
+        match self {
+            WeightCheckpoint::Keyframe(keyframe) => weights.clone_from(keyframe),
+            WeightCheckpoint::SparseDelta { indices, values } => {
+                for (&i, &value) in indices.iter().zip(values.iter()) {
+                    weights[i as usize] = value;
+                }
+            }
+            WeightCheckpoint::QuantizedDelta { scale, values } => {
+                for (w, &q) in weights.iter_mut().zip(values.iter()) {
+                    *w += q as f32 * scale;
+                }
+            }
+        }
+    }
+}
+
+impl ModelHistory {
+    pub fn new(config: HistoryConfig) -> Self {
+        ModelHistory {
+            config,
+            entries: Vec::new(),
+            latest: None,
+            reconstructed: Vec::new(),
+            rounds_since_keyframe: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: HistoryConfig) -> Result<(), String> {
+        config.validate()?;
+        self.config = config;
+        self.enforce_retention();
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&AggregatedModel> {
+        self.latest.as_ref()
+    }
+
+    pub fn push(&mut self, model: AggregatedModel) {
+        let weights = &model.weights;
+        let needs_keyframe = self.entries.is_empty()
+            || self.reconstructed.len() != weights.len()
+            || self.rounds_since_keyframe + 1 >= self.config.keyframe_interval;
+
+        let checkpoint = if needs_keyframe {
+            self.rounds_since_keyframe = 0;
+            WeightCheckpoint::Keyframe(weights.clone())
+        } else {
+            self.rounds_since_keyframe += 1;
+            let previous = &self.reconstructed;
+            match self.config.delta_encoding {
+                DeltaEncoding::Sparse => {
+                    let mut indices = Vec::new();
+                    let mut values = Vec::new();
+                    for (i, (&new, &old)) in weights.iter().zip(previous.iter()).enumerate() {
+                        if new.to_bits() != old.to_bits() {
+                            indices.push(i as u32);
+                            values.push(new);
+                        }
+                    }
+                    WeightCheckpoint::SparseDelta { indices, values }
+                }
+                DeltaEncoding::Quantized16 => {
+                    let max_delta = weights
+                        .iter()
+                        .zip(previous.iter())
+                        .map(|(new, old)| (new - old).abs())
+                        .fold(0.0f32, f32::max);
+                    let scale = if max_delta > 0.0 { max_delta / i16::MAX as f32 } else { 1.0 };
+                    let values = weights
+                        .iter()
+                        .zip(previous.iter())
+                        .map(|(new, old)| ((new - old) / scale).round() as i16)
+                        .collect();
+                    WeightCheckpoint::QuantizedDelta { scale, values }
+                }
+            }
+        };
+
+        checkpoint.apply(&mut self.reconstructed);
+        let mut metadata = model.clone();
+        metadata.weights = Vec::new();
+        self.entries.push(HistoryEntry { model: metadata, checkpoint });
+        self.latest = Some(model);
+        self.enforce_retention();
+    }
+
+    // Evicted rounds are folded into the oldest retained entry, which becomes a keyframe
+    fn enforce_retention(&mut self) {
+        let max = self.config.max_retained_rounds as usize;
+        if max == 0 || self.entries.len() <= max {
+            return;
+        }
+
+        let evict = self.entries.len() - max;
+        let mut weights = Vec::new();
+        for entry in &self.entries[..=evict] {
+            entry.checkpoint.apply(&mut weights);
+        }
+        self.entries.drain(..evict);
+        self.entries[0].checkpoint = WeightCheckpoint::Keyframe(weights);
+    }
+
+    // Rebuilds a retained model by version; the newest model is returned exactly
+    pub fn get(&self, version: &str) -> Option<AggregatedModel> {
+        if let Some(latest) = self.latest.as_ref().filter(|m| m.version == version) {
+            return Some(latest.clone());
+        }
+
+        let target = self.entries.iter().position(|e| e.model.version == version)?;
+        let start = self.entries[..=target]
+            .iter()
+            .rposition(|e| matches!(e.checkpoint, WeightCheckpoint::Keyframe(_)))?;
+
+        let mut weights = Vec::new();
+        for entry in &self.entries[start..=target] {
+            entry.checkpoint.apply(&mut weights);
+        }
+        let mut model = self.entries[target].model.clone();
+        model.weights = weights;
+        Some(model)
+    }
+
+    // Drops every round after `version`, which becomes the newest model again
+    pub fn rollback(&mut self, version: &str) -> Result<AggregatedModel, String> {
+        let target = self
+            .entries
+            .iter()
+            .position(|e| e.model.version == version)
+            .ok_or_else(|| format!("Model version {} is not retained", version))?;
+        let model = self.get(version).ok_or_else(|| format!("Model version {} cannot be rebuilt", version))?;
+
+        self.entries.truncate(target + 1);
+        let keyframe = self
+            .entries
+            .iter()
+            .rposition(|e| matches!(e.checkpoint, WeightCheckpoint::Keyframe(_)))
+            .unwrap_or(target);
+        self.rounds_since_keyframe = (target - keyframe) as u32;
+        self.reconstructed = model.weights.clone();
+        self.latest = Some(model.clone());
+        Ok(model)
+    }
+
+    pub fn versions(&self) -> Vec<String> {
+        self.entries.iter().map(|e| e.model.version.clone()).collect()
+    }
+
+    pub fn memory_report(&self) -> HistoryMemoryReport {
+        let dimension = self.reconstructed.len() as u64;
+        let keyframes = self
+            .entries
+            .iter()
+            .filter(|e| matches!(e.checkpoint, WeightCheckpoint::Keyframe(_)))
+            .count() as u64;
+        let rounds = self.entries.len() as u64;
+        let dense_bytes = rounds * dimension * 4;
+        // The exact copy of the newest model is part of the real footprint
+        let latest_bytes = self.latest.as_ref().map_or(0, |m| m.weights.len() as u64 * 4);
+        let stored_bytes = self.entries.iter().map(|e| e.checkpoint.stored_bytes()).sum::<u64>() + latest_bytes;
+
+        HistoryMemoryReport {
+            rounds_retained: rounds,
+            keyframes,
+            deltas: rounds - keyframes,
+            dense_bytes,
+            stored_bytes,
+            savings_ratio: if dense_bytes > 0 { 1.0 - stored_bytes as f64 / dense_bytes as f64 } else { 0.0 },
+        }
+    }
+}
@@ -3,8 +3,26 @@ use ic_cdk_macros::*;
 use serde::Serialize;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::time::Duration;
+use ic_cdk_timers::TimerId;
 use rand::Rng;
 use sha2::{Digest, Sha256};
+use governance::{
+    correlation_id_or_new, new_correlation_id, AdminApprovals, AdminOperation, AdminPolicy, AdminProposal,
+    ApprovalDecision, ApprovalReceipt, ApprovalRegistry, CallMetrics, CanisterMetrics, GovernanceAction,
+    LocalApprovalRegistry, OnboardingRecord, OnboardingRegistry, OnboardingStage, SessionToken, TrialResult,
+    WASM_PAGE_BYTES,
+};
+use jobs::{ChunkedJob, JobInfo, JobQueue};
+
+mod aggregation_job;
+use aggregation_job::AggregationJob;
+mod history;
+use history::{HistoryConfig, HistoryMemoryReport, ModelHistory};
+mod maintenance;
+use maintenance::{MaintenanceConfig, MaintenanceReport};
+mod policies;
+use policies::{AggregatorPolicies, ConfigFormat};
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 pub struct GradientUpdate {
@@ -15,6 +33,9 @@ pub struct GradientUpdate {
     pub privacy_budget: f64,
     pub timestamp: u64,
     pub signature: Vec<u8>,
+    // The round's correlation id, which the hospital also passes to the privacy
+    // engine; stamped on the update when left out
+    pub correlation_id: Option<String>,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -25,6 +46,18 @@ pub struct AggregatedModel {
     pub privacy_spent: f64,
     pub aggregation_round: u64,
     pub threshold_signature: Vec<u8>,
+    // Of the round the model was aggregated in, to pass on with the weights
+    pub correlation_id: String,
+}
+
+// The inference canister's ModelWeights, as the aggregator publishes each new model
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct InferenceModel {
+    pub version: String,
+    pub weights: Vec<f32>,
+    pub metadata: HashMap<String, String>,
+    pub threshold_signature: Vec<u8>,
+    pub correlation_id: Option<String>,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -34,6 +67,8 @@ pub struct InstitutionMetrics {
     pub privacy_budget_used: f64,
     pub last_update: u64,
     pub reputation_score: f64,
+    // Completed rounds since the institution last contributed
+    pub rounds_idle: u32,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -45,6 +80,8 @@ pub struct FederatedRound {
     pub privacy_epsilon: f64,
     pub deadline: u64,
     pub updates: Vec<GradientUpdate>,
+    // Traces the round across canisters
+    pub correlation_id: String,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -58,48 +95,245 @@ pub enum RoundStatus {
 thread_local! {
     static CURRENT_ROUND: RefCell<Option<FederatedRound>> = RefCell::new(None);
     static INSTITUTION_REGISTRY: RefCell<HashMap<String, InstitutionMetrics>> = RefCell::new(HashMap::new());
-    static MODEL_HISTORY: RefCell<Vec<AggregatedModel>> = RefCell::new(Vec::new());
+    static MODEL_HISTORY: RefCell<ModelHistory> = RefCell::new(ModelHistory::new(HistoryConfig::default()));
     static PRIVACY_ACCOUNTANT: RefCell<HashMap<String, f64>> = RefCell::new(HashMap::new());
+    static GOVERNANCE: RefCell<LocalApprovalRegistry> = RefCell::new(LocalApprovalRegistry::new());
+    static ONBOARDING: RefCell<OnboardingRegistry> = RefCell::new(OnboardingRegistry::new());
+    static ADMIN: RefCell<AdminApprovals> = RefCell::new(AdminApprovals::new());
+    static MAINTENANCE_CONFIG: RefCell<MaintenanceConfig> = RefCell::new(MaintenanceConfig::default());
+    static MAINTENANCE_TIMER: RefCell<Option<TimerId>> = RefCell::new(None);
+    static LAST_MAINTENANCE: RefCell<Option<MaintenanceReport>> = RefCell::new(None);
+    static JOBS: RefCell<JobQueue> = RefCell::new(JobQueue::default());
+    static JOB_TIMER: RefCell<Option<TimerId>> = RefCell::new(None);
+    static CORRELATION_SEQUENCE: RefCell<u64> = RefCell::new(0);
+    static CALL_METRICS: RefCell<CallMetrics> = RefCell::new(CallMetrics::new());
+    // Once set, every round is a launched task with a session on the privacy engine
+    // and each update is charged there
+    static PRIVACY_ENGINE: RefCell<Option<Principal>> = RefCell::new(None);
+    static PRIVACY_SESSION: RefCell<Option<SessionToken>> = RefCell::new(None);
+    // Each aggregated model is pushed here, under its round's correlation id
+    static INFERENCE_CANISTER: RefCell<Option<Principal>> = RefCell::new(None);
 }
 
 const MAX_PRIVACY_BUDGET: f64 = 10.0;
 const MIN_PARTICIPANTS: u32 = 3;
+const ROUND_DURATION_SECS: u64 = 3600;
 
 #[init]
 fn init() {
     ic_cdk::println!("Federated Aggregator Canister initialized");
     
     // Initialize first federated learning round
-    start_new_round(MIN_PARTICIPANTS, 1.0);
+    start_new_round(MIN_PARTICIPANTS, 1.0, None);
+    start_maintenance_timer();
+}
+
+// Timers do not survive an upgrade
+#[post_upgrade]
+fn post_upgrade() {
+    start_maintenance_timer();
 }
 
+// Onboarding: apply, submit credential hashes, governance approval, key registration,
+// a trial round and activation; only active institutions may submit updates
 #[update]
-fn register_institution(institution_id: String) -> Result<String, String> {
-    if institution_id.is_empty() {
-        return Err("Institution ID cannot be empty".to_string());
+fn apply_for_onboarding(institution_id: String, name: String) -> Result<OnboardingRecord, String> {
+    let already_registered = INSTITUTION_REGISTRY.with(|registry| registry.borrow().contains_key(&institution_id));
+    if already_registered {
+        return Err("Institution already registered".to_string());
+    }
+    ONBOARDING.with(|onboarding| {
+        onboarding.borrow_mut().apply(&institution_id, &name, ic_cdk::caller(), ic_cdk::api::time()).cloned()
+    })
+}
+
+// Documents are (kind, lowercase hex SHA-256) pairs; the documents stay off chain
+#[update]
+fn submit_onboarding_documents(institution_id: String, documents: Vec<(String, String)>) -> Result<(), String> {
+    ONBOARDING.with(|onboarding| {
+        onboarding.borrow_mut().submit_documents(&institution_id, ic_cdk::caller(), documents, ic_cdk::api::time())
+    })
+}
+
+// Admission is a consortium decision once governance is configured; the approval is
+// consumed here and its voters recorded on the institution's record
+#[update]
+fn approve_onboarding(institution_id: String) -> Result<(), String> {
+    let ready = ONBOARDING.with(|onboarding| {
+        onboarding.borrow().get(&institution_id).is_some_and(|r| r.stage == OnboardingStage::DocumentsSubmitted)
+    });
+    if !ready {
+        return Err(format!("{} has no submitted documents awaiting approval", institution_id));
+    }
+    let action = GovernanceAction::AdmitInstitution { institution_id: institution_id.clone() };
+    let (proposal_id, approvers) = GOVERNANCE.with(|g| {
+        let mut governance = g.borrow_mut();
+        let approvers = governance
+            .pending_approvals()
+            .into_iter()
+            .find(|d| d.action == action)
+            .map(|d| d.approvers)
+            .unwrap_or_default();
+        governance.authorize(&action, ic_cdk::caller(), ic_cdk::api::time()).map(|id| (id, approvers))
+    })?;
+    ONBOARDING.with(|onboarding| {
+        onboarding.borrow_mut().approve(&institution_id, ic_cdk::caller(), proposal_id, approvers, ic_cdk::api::time())
+    })
+}
+
+// SEC1-encoded secp256k1 key the institution signs its updates with
+#[update]
+fn register_institution_key(institution_id: String, public_key: Vec<u8>) -> Result<(), String> {
+    k256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key).map_err(|_| "Public key is not a valid SEC1 secp256k1 key".to_string())?;
+    ONBOARDING.with(|onboarding| {
+        onboarding.borrow_mut().register_key(&institution_id, ic_cdk::caller(), public_key, ic_cdk::api::time())
+    })
+}
+
+// A trial update goes through the same checks as a real one but is never aggregated
+// or charged to the privacy budget
+#[update]
+fn submit_trial_update(update: GradientUpdate) -> Result<TrialResult, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    let awaiting_trial = ONBOARDING.with(|onboarding| {
+        onboarding.borrow().get(&update.institution_id).is_some_and(|r| r.stage == OnboardingStage::KeysRegistered)
+    });
+    if !awaiting_trial {
+        return Err(format!("{} is not awaiting a trial round", update.institution_id));
+    }
+    if !verify_gradient_signature(&update) {
+        return Err("Invalid gradient signature".to_string());
+    }
+    if update.gradients.is_empty() || update.gradients.iter().any(|g| !g.is_finite()) {
+        return Err("Trial gradients must be non-empty and finite".to_string());
     }
+    if update.sample_count == 0 {
+        return Err("Trial update reports no samples".to_string());
+    }
+    let expected = MODEL_HISTORY.with(|history| history.borrow().latest().map(|m| m.weights.len()));
+    if let Some(expected) = expected.filter(|&expected| expected != update.gradients.len()) {
+        return Err(format!("Trial update has {} values, the model has {}", update.gradients.len(), expected));
+    }
+
+    let trial = TrialResult {
+        submitted_at: ic_cdk::api::time(),
+        dimension: update.gradients.len() as u64,
+        sample_count: update.sample_count,
+        gradient_norm: update.gradients.iter().map(|&g| (g as f64) * (g as f64)).sum::<f64>().sqrt(),
+    };
+    ONBOARDING.with(|onboarding| {
+        onboarding.borrow_mut().record_trial(&update.institution_id, ic_cdk::caller(), trial.clone(), ic_cdk::api::time())
+    })?;
+    Ok(trial)
+}
+
+#[update]
+fn activate_institution(institution_id: String) -> Result<String, String> {
+    let now = ic_cdk::api::time();
+    ONBOARDING.with(|onboarding| onboarding.borrow_mut().activate(&institution_id, ic_cdk::caller(), now))?;
     
     INSTITUTION_REGISTRY.with(|registry| {
-        let mut reg = registry.borrow_mut();
-        if reg.contains_key(&institution_id) {
-            return Err("Institution already registered".to_string());
-        }
-        
         let metrics = InstitutionMetrics {
             institution_id: institution_id.clone(),
             total_contributions: 0,
             privacy_budget_used: 0.0,
-            last_update: ic_cdk::api::time(),
+            last_update: now,
             reputation_score: 1.0,
+            rounds_idle: 0,
         };
-        
-        reg.insert(institution_id.clone(), metrics);
-        Ok(format!("Institution {} registered successfully", institution_id))
-    })
+        registry.borrow_mut().insert(institution_id.clone(), metrics);
+    });
+    Ok(format!("Institution {} activated", institution_id))
 }
 
 #[update]
-fn submit_gradient_update(update: GradientUpdate) -> Result<String, String> {
+fn withdraw_onboarding(institution_id: String) -> Result<(), String> {
+    ONBOARDING.with(|onboarding| onboarding.borrow_mut().withdraw(&institution_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[query]
+fn get_onboarding_status(institution_id: String) -> Option<OnboardingRecord> {
+    ONBOARDING.with(|onboarding| onboarding.borrow().get(&institution_id).cloned())
+}
+
+#[query]
+fn list_onboarding(stage: Option<OnboardingStage>) -> Vec<OnboardingRecord> {
+    ONBOARDING.with(|onboarding| onboarding.borrow().list(stage.as_ref()))
+}
+
+#[update]
+async fn submit_gradient_update(update: GradientUpdate) -> Result<String, String> {
+    let result = match charge_privacy_session(&update).await {
+        Ok(()) => accept_gradient_update(update),
+        Err(e) => Err(e),
+    };
+    record_call("submit_gradient_update");
+    result
+}
+
+// With a privacy engine configured, the update's ε is charged to the institution's
+// allocation in the round's session before the update is accepted. An update rejected
+// after the charge stays charged; over-counting is the safe side of the ledger.
+async fn charge_privacy_session(update: &GradientUpdate) -> Result<(), String> {
+    let Some(engine) = PRIVACY_ENGINE.with(|engine| *engine.borrow()) else {
+        return Ok(());
+    };
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    if !verify_gradient_signature(update) {
+        return Err("Invalid gradient signature".to_string());
+    }
+    let hospital_id = ONBOARDING
+        .with(|onboarding| {
+            onboarding.borrow().get(&update.institution_id).filter(|r| r.stage == OnboardingStage::Active).map(|r| r.applicant)
+        })
+        .ok_or(format!("{} is not an active institution", update.institution_id))?;
+    if hospital_id != ic_cdk::caller() {
+        return Err(format!("Only the applicant of {} can spend its privacy budget", update.institution_id));
+    }
+    let correlation_id = CURRENT_ROUND
+        .with(|round| round.borrow().as_ref().filter(|r| matches!(r.status, RoundStatus::Open)).map(|r| r.correlation_id.clone()))
+        .ok_or("Current round is not accepting updates")?;
+    let token = PRIVACY_SESSION
+        .with(|session| session.borrow().clone())
+        .filter(|token| token.session_id == correlation_id)
+        .ok_or("The current round has no privacy session; launch a task")?;
+
+    let mut hasher = Sha256::new();
+    for gradient in &update.gradients {
+        hasher.update(gradient.to_le_bytes());
+    }
+    let data_hash = format!("{:x}", hasher.finalize());
+    let args = (token, hospital_id, update.privacy_budget, 0.0f64, "gradient_update".to_string(), data_hash, Some(correlation_id));
+    let (result,): (Result<String, String>,) = ic_cdk::call(engine, "consume_privacy_budget", args)
+        .await
+        .map_err(|(code, message)| format!("Privacy engine call failed ({:?}): {}", code, message))?;
+    result.map(|_| ())
+}
+
+// Opens the round's session on the privacy engine, allocating `privacy_epsilon` to
+// each active institution, with the aggregator as the only token holder
+async fn open_privacy_session(engine: Principal, correlation_id: &str, privacy_epsilon: f64) -> Result<SessionToken, String> {
+    let hospitals: Vec<Principal> = ONBOARDING.with(|onboarding| {
+        onboarding.borrow().list(Some(&OnboardingStage::Active)).into_iter().map(|r| r.applicant).collect()
+    });
+    let total_epsilon = privacy_epsilon * hospitals.len() as f64;
+    let args = (
+        correlation_id.to_string(),
+        hospitals,
+        total_epsilon,
+        vec![ic_cdk::id()],
+        ROUND_DURATION_SECS,
+        Some(correlation_id.to_string()),
+    );
+    let (result,): (Result<Vec<SessionToken>, String>,) = ic_cdk::call(engine, "coordinate_federated_privacy", args)
+        .await
+        .map_err(|(code, message)| format!("Privacy engine call failed ({:?}): {}", code, message))?;
+    result?.into_iter().next().ok_or("Privacy engine issued no session token".to_string())
+}
+
+fn accept_gradient_update(update: GradientUpdate) -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    
     // Verify institution is registered
     let institution_exists = INSTITUTION_REGISTRY.with(|registry| {
         registry.borrow().contains_key(&update.institution_id)
@@ -136,6 +370,10 @@ fn submit_gradient_update(update: GradientUpdate) -> Result<String, String> {
         let mut current = round.borrow_mut();
         if let Some(ref mut round_data) = *current {
             if matches!(round_data.status, RoundStatus::Open) {
+                if update.correlation_id.as_ref().is_some_and(|id| *id != round_data.correlation_id) {
+                    return Err(format!("Update is not traced to the current round {}", round_data.correlation_id));
+                }
+                noisy_update.correlation_id = Some(round_data.correlation_id.clone());
                 round_data.updates.push(noisy_update);
                 round_data.current_participants += 1;
                 
@@ -153,17 +391,19 @@ fn submit_gradient_update(update: GradientUpdate) -> Result<String, String> {
                         metrics.total_contributions += 1;
                         metrics.privacy_budget_used += update.privacy_budget;
                         metrics.last_update = ic_cdk::api::time();
+                        metrics.rounds_idle = 0;
                     }
                 });
                 
                 // Check if we can start aggregation
                 if round_data.current_participants >= round_data.target_participants {
                     round_data.status = RoundStatus::Aggregating;
-                    ic_cdk::spawn(async {
-                        if let Err(e) = perform_aggregation().await {
-                            ic_cdk::println!("Aggregation failed: {}", e);
+                    match AggregationJob::new(round_data.round_id, round_data.updates.clone()) {
+                        Ok(job) => {
+                            submit_job(Box::new(job), ic_cdk::id());
                         }
-                    });
+                        Err(e) => ic_cdk::println!("Aggregation failed: {}", e),
+                    }
                 }
                 
                 Ok("Gradient update submitted successfully".to_string())
@@ -176,6 +416,175 @@ fn submit_gradient_update(update: GradientUpdate) -> Result<String, String> {
     })
 }
 
+// Starts a round for a governance-approved task, replacing the open round. With a
+// privacy engine configured the round gets a privacy session first; if that fails the
+// approval is spent and governance has to approve the task again.
+#[update]
+async fn launch_task(
+    task_id: String,
+    target_participants: u32,
+    privacy_epsilon: f64,
+    correlation_id: Option<String>,
+) -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    let correlation_id = correlation_id_or_new(correlation_id, "federated_aggregator", ic_cdk::api::time(), next_correlation_sequence())?;
+    if target_participants < MIN_PARTICIPANTS {
+        return Err(format!("A task needs at least {} participants", MIN_PARTICIPANTS));
+    }
+    if !(privacy_epsilon.is_finite() && privacy_epsilon > 0.0) {
+        return Err("Privacy epsilon must be positive".to_string());
+    }
+    let aggregating = CURRENT_ROUND.with(|round| {
+        round.borrow().as_ref().is_some_and(|r| matches!(r.status, RoundStatus::Aggregating))
+    });
+    if aggregating {
+        return Err("Cannot launch a task while a round is aggregating".to_string());
+    }
+    
+    let action = GovernanceAction::LaunchTask { task_id: task_id.clone(), target_participants, privacy_epsilon };
+    GOVERNANCE.with(|g| g.borrow_mut().authorize(&action, ic_cdk::caller(), ic_cdk::api::time()))?;
+    
+    let session = match PRIVACY_ENGINE.with(|engine| *engine.borrow()) {
+        Some(engine) => Some(open_privacy_session(engine, &correlation_id, privacy_epsilon).await?),
+        None => None,
+    };
+    // A round may have filled up while the session was being opened
+    let aggregating = CURRENT_ROUND.with(|round| {
+        round.borrow().as_ref().is_some_and(|r| matches!(r.status, RoundStatus::Aggregating))
+    });
+    if aggregating {
+        return Err("Cannot launch a task while a round is aggregating".to_string());
+    }
+    start_new_round(target_participants, privacy_epsilon, Some(correlation_id.clone()));
+    PRIVACY_SESSION.with(|current| *current.borrow_mut() = session);
+    Ok(format!("Task {} launched in round {}", task_id, correlation_id))
+}
+
+// The privacy engine must also name the aggregator its session coordinator
+#[update]
+fn set_privacy_engine(canister: Principal) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("Only a controller can set the privacy engine".to_string());
+    }
+    PRIVACY_ENGINE.with(|engine| *engine.borrow_mut() = Some(canister));
+    Ok(())
+}
+
+#[update]
+fn set_inference_canister(canister: Principal) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("Only a controller can set the inference canister".to_string());
+    }
+    INFERENCE_CANISTER.with(|inference| *inference.borrow_mut() = Some(canister));
+    Ok(())
+}
+
+fn require_controller_or_admin() -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    ADMIN.with(|admin| admin.borrow().require_controller_or_admin(caller, ic_cdk::api::is_controller(&caller)))
+}
+
+// Governance hooks: the governance canister pushes passed proposals here
+#[update]
+fn set_governance_canister(canister: Principal) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    GOVERNANCE.with(|g| g.borrow_mut().set_governance_canister(caller, ic_cdk::api::is_controller(&caller), canister))
+}
+
+#[update]
+fn record_governance_decision(decision: ApprovalDecision) -> Result<(), String> {
+    GOVERNANCE.with(|g| g.borrow_mut().record_decision(ic_cdk::caller(), decision))
+}
+
+#[update]
+fn revoke_governance_decision(proposal_id: u64) -> Result<(), String> {
+    GOVERNANCE.with(|g| g.borrow_mut().revoke_decision(ic_cdk::caller(), proposal_id))
+}
+
+#[query]
+fn get_pending_approvals() -> Vec<ApprovalDecision> {
+    GOVERNANCE.with(|g| g.borrow().pending_approvals())
+}
+
+#[query]
+fn get_governance_receipts() -> Vec<ApprovalReceipt> {
+    GOVERNANCE.with(|g| g.borrow().receipts().to_vec())
+}
+
+// Admin operations: proposed by one admin, approved by `threshold` of them, then
+// executed through the operation's own endpoint
+#[update]
+fn configure_admin_policy(policy: AdminPolicy) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    ADMIN.with(|admin| admin.borrow_mut().configure(policy, caller, ic_cdk::api::is_controller(&caller), ic_cdk::api::time()))
+}
+
+#[update]
+fn propose_admin_operation(operation: AdminOperation) -> Result<u64, String> {
+    if let AdminOperation::ResetPrivacyBudget { .. } = operation {
+        return Err("Privacy budgets are reset on the privacy engine".to_string());
+    }
+    if let AdminOperation::ChangeShadowPolicy { .. } = operation {
+        return Err("Shadow deployments run on the inference canister".to_string());
+    }
+    ADMIN.with(|admin| admin.borrow_mut().propose(operation, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn approve_admin_operation(proposal_id: u64) -> Result<u32, String> {
+    ADMIN.with(|admin| admin.borrow_mut().approve(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn cancel_admin_operation(proposal_id: u64) -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().cancel(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[query]
+fn get_admin_policy() -> Option<AdminPolicy> {
+    ADMIN.with(|admin| admin.borrow().policy().cloned())
+}
+
+#[query]
+fn get_admin_proposals() -> Vec<AdminProposal> {
+    ADMIN.with(|admin| admin.borrow().open_proposals(ic_cdk::api::time()))
+}
+
+// Stops accepting updates, trial updates and task launches until resumed
+#[update]
+fn emergency_pause() -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().pause(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Aggregator paused by {}", ic_cdk::caller());
+    Ok(())
+}
+
+#[update]
+fn resume() -> Result<(), String> {
+    ADMIN.with(|admin| admin.borrow_mut().resume(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Aggregator resumed by {}", ic_cdk::caller());
+    Ok(())
+}
+
+// Makes a retained version the latest model again, discarding the rounds after it
+#[update]
+fn rollback_model(version: String) -> Result<String, String> {
+    let aggregating = CURRENT_ROUND.with(|round| {
+        round.borrow().as_ref().is_some_and(|r| matches!(r.status, RoundStatus::Aggregating))
+    });
+    if aggregating {
+        return Err("Cannot roll back while a round is aggregating".to_string());
+    }
+    if MODEL_HISTORY.with(|history| !history.borrow().versions().contains(&version)) {
+        return Err(format!("Model version {} is not retained", version));
+    }
+    
+    let operation = AdminOperation::RollbackModel { version: version.clone() };
+    ADMIN.with(|admin| admin.borrow_mut().authorize_required(&operation, ic_cdk::caller(), ic_cdk::api::time()))?;
+    
+    MODEL_HISTORY.with(|history| history.borrow_mut().rollback(&version))?;
+    Ok(format!("Model rolled back to version {}", version))
+}
+
 fn add_differential_privacy_noise(gradients: &[f32], epsilon: f64) -> Vec<f32> {
     let mut rng = rand::thread_rng();
     let sensitivity = 1.0; // L2 sensitivity for gradient clipping
@@ -187,18 +596,9 @@ fn add_differential_privacy_noise(gradients: &[f32], epsilon: f64) -> Vec<f32> {
     }).collect()
 }
 
-async fn perform_aggregation() -> Result<(), String> {
-    let updates = CURRENT_ROUND.with(|round| {
-        round.borrow().as_ref().map(|r| r.updates.clone()).unwrap_or_default()
-    });
-    
-    if updates.is_empty() {
-        return Err("No updates to aggregate".to_string());
-    }
-    
-    // Federated averaging with differential privacy
-    let aggregated_weights = federated_average(&updates)?;
-    
+// Publishes the model averaged from the round's updates and opens the next round;
+// returns the new model version
+fn complete_aggregation(updates: &[GradientUpdate], aggregated_weights: Vec<f32>) -> String {
     // Create new model version
     let new_version = format!("v{}", ic_cdk::api::time());
     let participating_institutions: Vec<String> = updates.iter()
@@ -208,6 +608,9 @@ async fn perform_aggregation() -> Result<(), String> {
     let total_privacy_spent: f64 = updates.iter()
         .map(|u| u.privacy_budget)
         .sum();
+    let correlation_id = CURRENT_ROUND.with(|round| {
+        round.borrow().as_ref().map(|r| r.correlation_id.clone()).unwrap_or_default()
+    });
     
     let aggregated_model = AggregatedModel {
         version: new_version.clone(),
@@ -216,8 +619,29 @@ async fn perform_aggregation() -> Result<(), String> {
         privacy_spent: total_privacy_spent,
         aggregation_round: ic_cdk::api::time(),
         threshold_signature: generate_threshold_signature(&new_version),
+        correlation_id: correlation_id.clone(),
     };
     
+    // Institutions that sat the round out move closer to expiry
+    INSTITUTION_REGISTRY.with(|registry| {
+        for metrics in registry.borrow_mut().values_mut() {
+            if !aggregated_model.participating_institutions.contains(&metrics.institution_id) {
+                metrics.rounds_idle += 1;
+            }
+        }
+    });
+    
+    if let Some(inference) = INFERENCE_CANISTER.with(|inference| *inference.borrow()) {
+        let model = InferenceModel {
+            version: new_version.clone(),
+            weights: aggregated_model.weights.clone(),
+            metadata: HashMap::new(),
+            threshold_signature: aggregated_model.threshold_signature.clone(),
+            correlation_id: Some(correlation_id.clone()),
+        };
+        ic_cdk::spawn(publish_model(inference, model));
+    }
+    
     // Store in model history
     MODEL_HISTORY.with(|history| {
         history.borrow_mut().push(aggregated_model);
@@ -231,39 +655,50 @@ async fn perform_aggregation() -> Result<(), String> {
     });
     
     // Start next round
-    start_new_round(MIN_PARTICIPANTS, 1.0);
+    start_new_round(MIN_PARTICIPANTS, 1.0, None);
     
-    ic_cdk::println!("Aggregation completed for model version: {}", new_version);
-    Ok(())
+    ic_cdk::println!("[{}] Aggregation completed for model version: {}", correlation_id, new_version);
+    new_version
 }
 
-fn federated_average(updates: &[GradientUpdate]) -> Result<Vec<f32>, String> {
-    if updates.is_empty() {
-        return Err("No updates to average".to_string());
-    }
-    
-    let gradient_size = updates[0].gradients.len();
-    let mut averaged_gradients = vec![0.0f32; gradient_size];
-    let mut total_samples = 0u32;
-    
-    // Weighted average by sample count
-    for update in updates {
-        if update.gradients.len() != gradient_size {
-            return Err("Gradient size mismatch".to_string());
-        }
-        
-        for (i, &gradient) in update.gradients.iter().enumerate() {
-            averaged_gradients[i] += gradient * update.sample_count as f32;
-        }
-        total_samples += update.sample_count;
+// A model the inference canister refuses (e.g. one that must go through a shadow
+// deployment) stays in the aggregator's history for an operator to deploy
+async fn publish_model(inference: Principal, model: InferenceModel) {
+    let correlation_id = model.correlation_id.clone().unwrap_or_default();
+    let version = model.version.clone();
+    let result: Result<(Result<String, String>,), _> = ic_cdk::call(inference, "update_model_weights", (model,)).await;
+    match result {
+        Ok((Ok(_),)) => ic_cdk::println!("[{}] Model {} published to inference", correlation_id, version),
+        Ok((Err(e),)) => ic_cdk::println!("[{}] Inference refused model {}: {}", correlation_id, version, e),
+        Err((code, message)) => ic_cdk::println!("[{}] Publishing model {} failed ({:?}): {}", correlation_id, version, code, message),
     }
+}
+
+// Drops an aggregating round whose aggregation was cancelled or failed, refunding its
+// updates' privacy budget, and opens a new round with the same parameters
+fn abandon_round(round_id: u64) {
+    let abandoned = CURRENT_ROUND.with(|round| {
+        let mut current = round.borrow_mut();
+        let round_data = current.as_mut().filter(|r| r.round_id == round_id && matches!(r.status, RoundStatus::Aggregating))?;
+        round_data.status = RoundStatus::Failed;
+        let updates = std::mem::take(&mut round_data.updates);
+        Some((round_data.target_participants, round_data.privacy_epsilon, updates, round_data.correlation_id.clone()))
+    });
     
-    // Normalize by total samples
-    for gradient in &mut averaged_gradients {
-        *gradient /= total_samples as f32;
+    if let Some((target_participants, privacy_epsilon, updates, correlation_id)) = abandoned {
+        refund_privacy_budget(&updates);
+        start_new_round(target_participants, privacy_epsilon, None);
+        ic_cdk::println!("[{}] Aggregation of round {} abandoned", correlation_id, round_id);
     }
-    
-    Ok(averaged_gradients)
+}
+
+// Returns the budget charged for updates that were never aggregated; the total refunded
+fn refund_privacy_budget(updates: &[GradientUpdate]) -> f64 {
+    PRIVACY_ACCOUNTANT.with(|accountant| {
+        INSTITUTION_REGISTRY.with(|registry| {
+            maintenance::refund_privacy_budget(&mut accountant.borrow_mut(), &mut registry.borrow_mut(), updates)
+        })
+    })
 }
 
 fn verify_gradient_signature(update: &GradientUpdate) -> bool {
@@ -280,22 +715,129 @@ fn generate_threshold_signature(version: &str) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
-fn start_new_round(target_participants: u32, privacy_epsilon: f64) {
+// Rounds opened by the canister itself get a fresh correlation id
+fn start_new_round(target_participants: u32, privacy_epsilon: f64, correlation_id: Option<String>) {
+    let correlation_id = correlation_id.unwrap_or_else(|| {
+        new_correlation_id("federated_aggregator", ic_cdk::api::time(), next_correlation_sequence())
+    });
     let round = FederatedRound {
         round_id: ic_cdk::api::time(),
         status: RoundStatus::Open,
         target_participants,
         current_participants: 0,
         privacy_epsilon,
-        deadline: ic_cdk::api::time() + 3600_000_000_000, // 1 hour in nanoseconds
+        deadline: ic_cdk::api::time() + ROUND_DURATION_SECS * 1_000_000_000,
         updates: Vec::new(),
+        correlation_id: correlation_id.clone(),
     };
     
     CURRENT_ROUND.with(|current| {
         *current.borrow_mut() = Some(round);
     });
+    PRIVACY_SESSION.with(|session| *session.borrow_mut() = None);
+    
+    ic_cdk::println!("[{}] New federated learning round started", correlation_id);
+}
+
+fn next_correlation_sequence() -> u64 {
+    CORRELATION_SEQUENCE.with(|sequence| {
+        let mut sequence = sequence.borrow_mut();
+        *sequence += 1;
+        *sequence
+    })
+}
+
+fn start_maintenance_timer() {
+    let interval = MAINTENANCE_CONFIG.with(|config| config.borrow().interval_secs);
+    let timer_id = ic_cdk_timers::set_timer_interval(Duration::from_secs(interval), run_maintenance);
+    if let Some(previous) = MAINTENANCE_TIMER.with(|timer| timer.borrow_mut().replace(timer_id)) {
+        ic_cdk_timers::clear_timer(previous);
+    }
+}
+
+fn run_maintenance() {
+    let config = MAINTENANCE_CONFIG.with(|config| config.borrow().clone());
+    let now = ic_cdk::api::time();
+    let mut report = MaintenanceReport { ran_at: now, ..MaintenanceReport::default() };
+    
+    INSTITUTION_REGISTRY.with(|registry| {
+        let mut reg = registry.borrow_mut();
+        report.reputations_decayed = maintenance::decay_reputations(&mut reg, &config);
+        report.institutions_expired = maintenance::expire_idle_institutions(&mut reg, &config);
+    });
+    
+    // Expired institutions keep their approval and key but must pass a new trial round
+    ONBOARDING.with(|onboarding| {
+        let mut onboarding = onboarding.borrow_mut();
+        for institution_id in &report.institutions_expired {
+            let reason = format!("Idle for {} rounds", config.max_idle_rounds);
+            if let Err(e) = onboarding.return_to_trial(institution_id, ic_cdk::id(), now, &reason) {
+                ic_cdk::println!("Onboarding of expired {}: {}", institution_id, e);
+            }
+        }
+    });
+    
+    let stale = CURRENT_ROUND.with(|round| {
+        let mut current = round.borrow_mut();
+        let round_data = current.as_mut()?;
+        let discarded = maintenance::expire_stale_round(round_data, now)?;
+        Some((round_data.round_id, round_data.correlation_id.clone(), round_data.target_participants, round_data.privacy_epsilon, discarded))
+    });
+    
+    if let Some((round_id, correlation_id, target_participants, privacy_epsilon, discarded)) = stale {
+        // Discarded uploads were never aggregated, so the budget charged for them is returned
+        report.privacy_budget_refunded += refund_privacy_budget(&discarded);
+        report.expired_round = Some(round_id);
+        report.expired_round_correlation_id = Some(correlation_id);
+        report.uploads_discarded = discarded.len() as u32;
+        start_new_round(target_participants, privacy_epsilon, None);
+    }
     
-    ic_cdk::println!("New federated learning round started");
+    ic_cdk::println!(
+        "Maintenance: {} reputations decayed, {} institutions expired, {} uploads discarded",
+        report.reputations_decayed,
+        report.institutions_expired.len(),
+        report.uploads_discarded
+    );
+    LAST_MAINTENANCE.with(|last| *last.borrow_mut() = Some(report));
+}
+
+// A one-round idle limit or a one-second interval would expire every institution or
+// burn cycles, so not every caller may change the schedule
+#[update]
+fn set_maintenance_config(config: MaintenanceConfig) -> Result<(), String> {
+    require_controller_or_admin()?;
+    config.validate()?;
+    MAINTENANCE_CONFIG.with(|current| *current.borrow_mut() = config);
+    start_maintenance_timer();
+    Ok(())
+}
+
+// Applies the maintenance and history sections of a TOML or JSON policy document;
+// nothing is applied unless every section is valid. Gated like the endpoints for
+// each section, since retention can delete model versions
+#[update]
+fn load_policies(text: String, format: ConfigFormat) -> Result<(), String> {
+    require_controller_or_admin()?;
+    let policies = AggregatorPolicies::parse(&text, format)?;
+    if let Some(history) = policies.history {
+        MODEL_HISTORY.with(|current| current.borrow_mut().set_config(history))?;
+    }
+    if let Some(maintenance) = policies.maintenance {
+        MAINTENANCE_CONFIG.with(|current| *current.borrow_mut() = maintenance);
+        start_maintenance_timer();
+    }
+    Ok(())
+}
+
+#[query]
+fn get_maintenance_config() -> MaintenanceConfig {
+    MAINTENANCE_CONFIG.with(|config| config.borrow().clone())
+}
+
+#[query]
+fn get_last_maintenance_report() -> Option<MaintenanceReport> {
+    LAST_MAINTENANCE.with(|last| last.borrow().clone())
 }
 
 #[query]
@@ -303,6 +845,53 @@ fn get_current_round() -> Option<FederatedRound> {
     CURRENT_ROUND.with(|round| round.borrow().clone())
 }
 
+// Long-running work runs as jobs, one chunk per timer message until none is left
+fn submit_job(job: Box<dyn ChunkedJob>, submitted_by: Principal) -> u64 {
+    let id = JOBS.with(|jobs| jobs.borrow_mut().submit(job, submitted_by, ic_cdk::api::time()));
+    schedule_job_chunk();
+    id
+}
+
+fn schedule_job_chunk() {
+    let runnable = JOBS.with(|jobs| jobs.borrow().has_runnable());
+    let scheduled = JOB_TIMER.with(|timer| timer.borrow().is_some());
+    if runnable && !scheduled {
+        let timer_id = ic_cdk_timers::set_timer(Duration::ZERO, run_job_chunk);
+        JOB_TIMER.with(|timer| *timer.borrow_mut() = Some(timer_id));
+    }
+}
+
+fn run_job_chunk() {
+    JOB_TIMER.with(|timer| timer.borrow_mut().take());
+    JOBS.with(|jobs| jobs.borrow_mut().run_next(ic_cdk::api::time()));
+    record_call("run_job_chunk");
+    schedule_job_chunk();
+}
+
+#[query]
+fn get_job(job_id: u64) -> Option<JobInfo> {
+    JOBS.with(|jobs| jobs.borrow().info(job_id))
+}
+
+#[query]
+fn list_jobs() -> Vec<JobInfo> {
+    JOBS.with(|jobs| jobs.borrow().list())
+}
+
+// The submitter or any admin can cancel; cancelling a round's aggregation discards
+// the round
+#[update]
+fn cancel_job(job_id: u64) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let submitter = JOBS.with(|jobs| jobs.borrow().info(job_id).map(|info| info.submitted_by));
+    let submitter = submitter.ok_or_else(|| format!("No job {}", job_id))?;
+    let is_admin = ADMIN.with(|admin| admin.borrow().policy().is_some_and(|p| p.admins.contains(&caller)));
+    if caller != submitter && !is_admin {
+        return Err("Only the submitter or an admin can cancel a job".to_string());
+    }
+    JOBS.with(|jobs| jobs.borrow_mut().cancel(job_id, ic_cdk::api::time()))
+}
+
 #[query]
 fn get_institution_metrics(institution_id: String) -> Option<InstitutionMetrics> {
     INSTITUTION_REGISTRY.with(|registry| {
@@ -313,7 +902,37 @@ fn get_institution_metrics(institution_id: String) -> Option<InstitutionMetrics>
 #[query]
 fn get_latest_model() -> Option<AggregatedModel> {
     MODEL_HISTORY.with(|history| {
-        history.borrow().last().cloned()
+        history.borrow().latest().cloned()
+    })
+}
+
+#[query]
+fn get_model_version(version: String) -> Option<AggregatedModel> {
+    MODEL_HISTORY.with(|history| {
+        history.borrow().get(&version)
+    })
+}
+
+#[query]
+fn get_model_versions() -> Vec<String> {
+    MODEL_HISTORY.with(|history| {
+        history.borrow().versions()
+    })
+}
+
+#[query]
+fn get_history_memory_report() -> HistoryMemoryReport {
+    MODEL_HISTORY.with(|history| {
+        history.borrow().memory_report()
+    })
+}
+
+// Shrinking retention deletes versions at once, so it is not open to every caller
+#[update]
+fn set_history_config(config: HistoryConfig) -> Result<(), String> {
+    require_controller_or_admin()?;
+    MODEL_HISTORY.with(|history| {
+        history.borrow_mut().set_config(config)
     })
 }
 
@@ -325,6 +944,26 @@ fn get_privacy_budget(institution_id: String) -> f64 {
     })
 }
 
+// Instructions the current call has used, awaits included
+fn record_call(method: &str) {
+    let instructions = ic_cdk::api::performance_counter(1);
+    CALL_METRICS.with(|metrics| metrics.borrow_mut().record(method, instructions));
+}
+
+fn heap_bytes() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    return core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_BYTES;
+    #[cfg(not(target_arch = "wasm32"))]
+    return 0;
+}
+
+// Per-method instruction counts and memory size, read by the load generator
+#[query]
+fn get_canister_metrics() -> CanisterMetrics {
+    let stable_bytes = ic_cdk::api::stable::stable64_size() * WASM_PAGE_BYTES;
+    CALL_METRICS.with(|metrics| metrics.borrow().snapshot(heap_bytes(), stable_bytes))
+}
+
 #[query]
 fn get_aggregator_status() -> HashMap<String, String> {
     let mut status = HashMap::new();
@@ -342,11 +981,33 @@ fn get_aggregator_status() -> HashMap<String, String> {
     });
     
     status.insert("current_round_status".to_string(), round_status);
+    let onboarding_in_progress = ONBOARDING.with(|onboarding| {
+        onboarding
+            .borrow()
+            .list(None)
+            .iter()
+            .filter(|r| !matches!(r.stage, OnboardingStage::Active | OnboardingStage::Withdrawn))
+            .count()
+            .to_string()
+    });
+    
     status.insert("registered_institutions".to_string(), total_institutions);
+    status.insert("onboarding_in_progress".to_string(), onboarding_in_progress);
+    status.insert("paused".to_string(), ADMIN.with(|admin| admin.borrow().is_paused()).to_string());
     status.insert("aggregated_models".to_string(), total_models);
     
     status
 }
 
 // Export Candid interface
-ic_cdk::export_candid!();
\ No newline at end of file
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    // Regenerates federated_aggregator.did from the Rust interface and fails on breaking changes
+    #[test]
+    fn test_candid_interface_is_compatible() {
+        let did = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("federated_aggregator.did");
+        candid_interface::check_interface(&did, &super::__get_candid_interface_tmp_hack()).unwrap();
+    }
+}
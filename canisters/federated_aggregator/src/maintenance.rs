@@ -0,0 +1,204 @@
+// Periodic registry maintenance, run from a canister timer. Each tick moves every
+// institution's reputation a fixed fraction of the way back to the baseline, expires
+// institutions that have not contributed for `max_idle_rounds` completed rounds, and
+// discards the staged uploads of a round that passed its deadline short of its
+// participant target so their privacy budget can be refunded.
+
+use crate::{FederatedRound, GradientUpdate, InstitutionMetrics, RoundStatus};
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct MaintenanceConfig {
+    pub interval_secs: u64,
+    pub reputation_baseline: f64,
+    // Fraction of the distance to the baseline closed per tick
+    pub reputation_decay: f64,
+    // Completed rounds without a contribution before registration lapses; 0 never expires
+    pub max_idle_rounds: u32,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+pub struct MaintenanceReport {
+    pub ran_at: u64,
+    pub reputations_decayed: u32,
+    pub institutions_expired: Vec<String>,
+    pub expired_round: Option<u64>,
+    pub expired_round_correlation_id: Option<String>,
+    pub uploads_discarded: u32,
+    pub privacy_budget_refunded: f64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        MaintenanceConfig {
+            interval_secs: 3600,
+            reputation_baseline: 1.0,
+            reputation_decay: 0.05,
+            max_idle_rounds: 20,
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("interval_secs must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.reputation_decay) {
+            return Err("reputation_decay must be in [0, 1]".to_string());
+        }
+        if !self.reputation_baseline.is_finite() || self.reputation_baseline < 0.0 {
+            return Err("reputation_baseline must be non-negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Returns the number of reputations that moved
+pub fn decay_reputations(registry: &mut HashMap<String, InstitutionMetrics>, config: &MaintenanceConfig) -> u32 {
+    let mut decayed = 0;
+    for metrics in registry.values_mut() {
+        let gap = config.reputation_baseline - metrics.reputation_score;
+        if gap.abs() < 1e-9 {
+            continue;
+        }
+        metrics.reputation_score += gap * config.reputation_decay;
+        decayed += 1;
+    }
+    decayed
+}
+
+// Removes idle registrations; their privacy accounting is kept so a re-admitted
+// institution resumes with the budget it had already spent
+pub fn expire_idle_institutions(
+    registry: &mut HashMap<String, InstitutionMetrics>,
+    config: &MaintenanceConfig,
+) -> Vec<String> {
+    if config.max_idle_rounds == 0 {
+        return Vec::new();
+    }
+    let mut expired: Vec<String> = registry
+        .values()
+        .filter(|m| m.rounds_idle >= config.max_idle_rounds)
+        .map(|m| m.institution_id.clone())
+        .collect();
+    expired.sort();
+    for institution_id in &expired {
+        registry.remove(institution_id);
+    }
+    expired
+}
+
+// Fails an open round past its deadline that never reached its target and hands back
+// the uploads staged in it; none of them were aggregated or released
+pub fn expire_stale_round(round: &mut FederatedRound, now: u64) -> Option<Vec<GradientUpdate>> {
+    let stale = matches!(round.status, RoundStatus::Open)
+        && now > round.deadline
+        && round.current_participants < round.target_participants;
+    if !stale {
+        return None;
+    }
+    round.status = RoundStatus::Failed;
+    round.current_participants = 0;
+    Some(std::mem::take(&mut round.updates))
+}
+
+// Gives the budget charged for uploads that were never aggregated back to their
+// institutions; returns the total refunded
+pub fn refund_privacy_budget(
+    accountant: &mut HashMap<String, f64>,
+    registry: &mut HashMap<String, InstitutionMetrics>,
+    updates: &[GradientUpdate],
+) -> f64 {
+    for update in updates {
+        if let Some(used) = accountant.get_mut(&update.institution_id) {
+            *used = (*used - update.privacy_budget).max(0.0);
+        }
+        if let Some(metrics) = registry.get_mut(&update.institution_id) {
+            metrics.privacy_budget_used = (metrics.privacy_budget_used - update.privacy_budget).max(0.0);
+        }
+    }
+    updates.iter().map(|u| u.privacy_budget).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(institution_id: &str, reputation_score: f64, rounds_idle: u32) -> InstitutionMetrics {
+        InstitutionMetrics {
+            institution_id: institution_id.to_string(),
+            total_contributions: 1,
+            privacy_budget_used: 0.5,
+            last_update: 0,
+            reputation_score,
+            rounds_idle,
+        }
+    }
+
+    fn upload(institution_id: &str, privacy_budget: f64) -> GradientUpdate {
+        GradientUpdate {
+            institution_id: institution_id.to_string(),
+            model_version: "v1".to_string(),
+            gradients: vec![0.1],
+            sample_count: 10,
+            privacy_budget,
+            timestamp: 0,
+            signature: Vec::new(),
+            correlation_id: None,
+        }
+    }
+
+    #[test]
+    fn test_reputations_decay_towards_the_baseline_and_idle_institutions_expire() {
+        let config = MaintenanceConfig { reputation_decay: 0.25, max_idle_rounds: 3, ..MaintenanceConfig::default() };
+        let mut registry: HashMap<String, InstitutionMetrics> = [metrics("high", 2.0, 3), metrics("low", 0.2, 2), metrics("settled", 1.0, 5)]
+            .into_iter()
+            .map(|m| (m.institution_id.clone(), m))
+            .collect();
+
+        // A quarter of the gap to 1.0 is closed from either side; at the baseline nothing moves
+        assert_eq!(decay_reputations(&mut registry, &config), 2);
+        assert!((registry["high"].reputation_score - 1.75).abs() < 1e-12);
+        assert!((registry["low"].reputation_score - 0.4).abs() < 1e-12);
+        assert_eq!(registry["settled"].reputation_score, 1.0);
+
+        // The cut-off is inclusive, and 0 disables expiry
+        let never = MaintenanceConfig { max_idle_rounds: 0, ..config.clone() };
+        assert!(expire_idle_institutions(&mut registry, &never).is_empty());
+        assert_eq!(expire_idle_institutions(&mut registry, &config), vec!["high".to_string(), "settled".to_string()]);
+        assert_eq!(registry.keys().collect::<Vec<_>>(), vec!["low"]);
+    }
+
+    #[test]
+    fn test_stale_round_is_failed_and_its_budget_refunded() {
+        let mut round = FederatedRound {
+            round_id: 4,
+            status: RoundStatus::Open,
+            target_participants: 3,
+            current_participants: 2,
+            privacy_epsilon: 1.0,
+            deadline: 100,
+            updates: vec![upload("a", 0.4), upload("b", 0.3)],
+            correlation_id: "round-4".to_string(),
+        };
+        // Not yet past the deadline
+        assert!(expire_stale_round(&mut round, 100).is_none());
+        let discarded = expire_stale_round(&mut round, 101).unwrap();
+        assert!(matches!(round.status, RoundStatus::Failed));
+        assert_eq!((round.current_participants, round.updates.len(), discarded.len()), (0, 0, 2));
+        // A failed round is not expired twice
+        assert!(expire_stale_round(&mut round, 200).is_none());
+
+        let mut accountant: HashMap<String, f64> = [("a".to_string(), 1.0), ("b".to_string(), 0.1)].into_iter().collect();
+        let mut registry: HashMap<String, InstitutionMetrics> = [("a".to_string(), metrics("a", 1.0, 0))].into_iter().collect();
+        let refunded = refund_privacy_budget(&mut accountant, &mut registry, &discarded);
+        assert!((refunded - 0.7).abs() < 1e-12);
+        assert!((accountant["a"] - 0.6).abs() < 1e-12);
+        // Spend never goes below zero
+        assert_eq!(accountant["b"], 0.0);
+        assert!((registry["a"].privacy_budget_used - 0.1).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,50 @@
+// Aggregator policies loaded from a TOML or JSON document, so a deployment keeps its
+// maintenance and history settings in a reviewed file and applies them in one call
+// instead of hand-building candid records. Sections that are absent keep their current
+// values. The document is parsed strictly (unknown keys are errors, reported with the
+// path of the offending field) and every section is validated before any is applied.
+
+use crate::history::HistoryConfig;
+use crate::maintenance::MaintenanceConfig;
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AggregatorPolicies {
+    pub maintenance: Option<MaintenanceConfig>,
+    pub history: Option<HistoryConfig>,
+}
+
+impl AggregatorPolicies {
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Self, String> {
+        let describe = |path: String, message: String| match path.as_str() {
+            "." => message,
+            field => format!("{}: {}", field, message),
+        };
+        let policies: AggregatorPolicies = match format {
+            ConfigFormat::Toml => serde_path_to_error::deserialize(toml::Deserializer::new(text))
+                .map_err(|e| describe(e.path().to_string(), e.inner().message().to_string()))?,
+            ConfigFormat::Json => serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(text))
+                .map_err(|e| describe(e.path().to_string(), e.inner().to_string()))?,
+        };
+        policies.validate()?;
+        Ok(policies)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(maintenance) = &self.maintenance {
+            maintenance.validate().map_err(|e| format!("maintenance: {}", e))?;
+        }
+        if let Some(history) = &self.history {
+            history.validate().map_err(|e| format!("history: {}", e))?;
+        }
+        Ok(())
+    }
+}
@@ -7,7 +7,14 @@ use std::cell::RefCell;
 use candid::{CandidType, Decode, Encode, Principal};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use differential_privacy::DifferentialPrivacy;
+use differential_privacy::{DifferentialPrivacy, MomentsAccountant};
+use governance::{
+    correlation_id_or_new, AdminApprovals, AdminOperation, AdminPolicy, AdminProposal, ApprovalDecision,
+    ApprovalReceipt, ApprovalRegistry, GovernanceAction, LocalApprovalRegistry, PrivacyPolicyChange, SessionCoordinator,
+    SessionToken,
+};
+
+mod schema;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
@@ -25,11 +32,11 @@ pub struct PrivacyBudget {
 
 impl Storable for PrivacyBudget {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Owned(schema::encode(self))
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
@@ -44,6 +51,8 @@ pub struct PrivacyAuditEntry {
     pub timestamp: u64,
     pub data_hash: String,
     pub compliance_status: ComplianceStatus,
+    // Traces the entry to the round or request it was part of
+    pub correlation_id: String,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone)]
@@ -55,11 +64,11 @@ pub enum ComplianceStatus {
 
 impl Storable for PrivacyAuditEntry {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Owned(schema::encode(self))
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
@@ -70,8 +79,14 @@ pub struct PrivacyCoordination {
     pub participating_hospitals: Vec<Principal>,
     pub total_epsilon_budget: f64,
     pub allocated_budgets: Vec<(Principal, f64)>,
+    // ε spent so far per hospital under this session, never above its allocation
+    pub consumed_budgets: Vec<(Principal, f64)>,
+    // Canisters issued a session token, and when those tokens expire
+    pub token_holders: Vec<Principal>,
+    pub expires_at: u64,
     pub status: CoordinationStatus,
     pub created_at: u64,
+    pub correlation_id: String,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone)]
@@ -84,11 +99,11 @@ pub enum CoordinationStatus {
 
 impl Storable for PrivacyCoordination {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+        Cow::Owned(schema::encode(self))
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        schema::decode(bytes.as_ref()).unwrap()
     }
 }
 
@@ -115,8 +130,57 @@ thread_local! {
         )
     );
 
+    // Candid-encoded approval registry under key 0 and session coordinator under key
+    // 1, so the governance canister, pending approvals and coordinator survive upgrades
+    static GOVERNANCE_STORE: RefCell<StableBTreeMap<u8, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+        )
+    );
+
+    static GOVERNANCE: RefCell<LocalApprovalRegistry> = RefCell::new(
+        GOVERNANCE_STORE.with(|store| {
+            store.borrow().get(&0).map(|bytes| Decode!(&bytes, LocalApprovalRegistry).unwrap()).unwrap_or_default()
+        })
+    );
+
+    static SESSION_COORDINATOR: RefCell<SessionCoordinator> = RefCell::new(
+        GOVERNANCE_STORE.with(|store| {
+            store.borrow().get(&1).map(|bytes| Decode!(&bytes, SessionCoordinator).unwrap()).unwrap_or_default()
+        })
+    );
+
+    // HMAC secret for session tokens under key 0, drawn from raw_rand on first use
+    static SESSION_SECRET: RefCell<StableBTreeMap<u8, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
+        )
+    );
+
+    // Candid-encoded admin approvals under key 0, so the admin policy, open proposals
+    // and a pause survive upgrades
+    static ADMIN_STORE: RefCell<StableBTreeMap<u8, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    static ADMIN: RefCell<AdminApprovals> = RefCell::new(
+        ADMIN_STORE.with(|store| {
+            store.borrow().get(&0).map(|bytes| Decode!(&bytes, AdminApprovals).unwrap()).unwrap_or_default()
+        })
+    );
+
+    // JSON-encoded moments accountant of each hospital's DP-SGD training
+    static DP_SGD_MOMENTS: RefCell<StableBTreeMap<Principal, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
     static DIFFERENTIAL_PRIVACY: RefCell<DifferentialPrivacy> = RefCell::new(DifferentialPrivacy::new());
     static AUDIT_COUNTER: RefCell<u64> = RefCell::new(0);
+    static CORRELATION_SEQUENCE: RefCell<u64> = RefCell::new(0);
 }
 
 #[init]
@@ -136,23 +200,26 @@ fn post_upgrade() {
 
 // Hospital registration and privacy budget allocation
 #[update]
-async fn register_hospital(hospital_id: Principal, epsilon_total: f64, delta_total: f64) -> Result<String, String> {
+async fn register_hospital(
+    hospital_id: Principal,
+    epsilon_total: f64,
+    delta_total: f64,
+    correlation_id: Option<String>,
+) -> Result<String, String> {
     let caller = ic_cdk::caller();
+    let correlation_id = resolve_correlation_id(correlation_id)?;
     
     // In production, add proper authorization checks
     if caller == Principal::anonymous() {
         return Err("Anonymous caller not allowed".to_string());
     }
 
-    let privacy_budget = PrivacyBudget {
-        hospital_id,
-        epsilon_used: 0.0,
-        epsilon_total,
-        delta_used: 0.0,
-        delta_total,
-        last_updated: ic_cdk::api::time(),
-        queries_count: 0,
-    };
+    let existing = PRIVACY_BUDGETS.with(|budgets| budgets.borrow().get(&hospital_id));
+    let privacy_budget = new_privacy_budget(existing.as_ref(), hospital_id, epsilon_total, delta_total, ic_cdk::api::time())?;
+
+    // Allocating a budget is a privacy-policy change once governance is configured
+    let change = PrivacyPolicyChange::SetBudget { hospital_id, epsilon_total, delta_total };
+    with_governance(|g| g.authorize(&GovernanceAction::ChangePrivacyPolicy { change }, caller, ic_cdk::api::time()))?;
 
     PRIVACY_BUDGETS.with(|budgets| {
         budgets.borrow_mut().insert(hospital_id, privacy_budget);
@@ -166,11 +233,35 @@ async fn register_hospital(hospital_id: Principal, epsilon_total: f64, delta_tot
         0.0,
         "".to_string(),
         ComplianceStatus::Compliant,
+        correlation_id,
     ).await;
 
     Ok(format!("Hospital {} registered with privacy budget ε={}, δ={}", hospital_id, epsilon_total, delta_total))
 }
 
+// Registering again would start the hospital's spend over at zero, which is a budget
+// reset and has to go through reset_privacy_budget and its admin approval
+fn new_privacy_budget(
+    existing: Option<&PrivacyBudget>,
+    hospital_id: Principal,
+    epsilon_total: f64,
+    delta_total: f64,
+    now: u64,
+) -> Result<PrivacyBudget, String> {
+    if existing.is_some() {
+        return Err(format!("Hospital {} is already registered; reset its budget with reset_privacy_budget", hospital_id));
+    }
+    Ok(PrivacyBudget {
+        hospital_id,
+        epsilon_used: 0.0,
+        epsilon_total,
+        delta_used: 0.0,
+        delta_total,
+        last_updated: now,
+        queries_count: 0,
+    })
+}
+
 // Check if a privacy operation is allowed
 #[query]
 fn check_privacy_budget(hospital_id: Principal, epsilon_required: f64, delta_required: f64) -> Result<bool, String> {
@@ -187,22 +278,79 @@ fn check_privacy_budget(hospital_id: Principal, epsilon_required: f64, delta_req
     })
 }
 
-// Consume privacy budget for an operation
+// Consume privacy budget for an operation tied to a coordination session; the
+// caller must present the session token it was issued
 #[update]
 async fn consume_privacy_budget(
+    token: SessionToken,
     hospital_id: Principal,
     epsilon_consumed: f64,
     delta_consumed: f64,
     operation_type: String,
     data_hash: String,
+    correlation_id: Option<String>,
 ) -> Result<String, String> {
-    let caller = ic_cdk::caller();
-    
-    // Verify the caller is authorized (in production, implement proper auth)
-    if caller == Principal::anonymous() {
-        return Err("Anonymous caller not allowed".to_string());
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    let correlation_id = resolve_correlation_id(correlation_id)?;
+    authorize_session_spend(&token, ic_cdk::caller(), hospital_id, epsilon_consumed)?;
+    let result = charge_privacy_budget(hospital_id, epsilon_consumed, delta_consumed, operation_type, data_hash, correlation_id)?;
+    record_session_spend(&token.session_id, hospital_id, epsilon_consumed);
+    Ok(result)
+}
+
+// Charges DP-SGD training (`steps` steps at batch sampling rate `sample_rate` with
+// noise multiplier `noise_multiplier`) through the hospital's moments accountant, at
+// the δ of its budget. Only the rise in the composed ε is charged, so a long training
+// run costs far less than the sum of its steps. Returns the ε charged.
+#[update]
+async fn account_dp_sgd_training(
+    token: SessionToken,
+    hospital_id: Principal,
+    sample_rate: f64,
+    noise_multiplier: f64,
+    steps: u64,
+    correlation_id: Option<String>,
+) -> Result<f64, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    let correlation_id = resolve_correlation_id(correlation_id)?;
+    if !(sample_rate > 0.0 && sample_rate <= 1.0) || !(noise_multiplier.is_finite() && noise_multiplier > 0.0) {
+        return Err("Sample rate must be in (0, 1] and the noise multiplier positive".to_string());
+    }
+    if steps == 0 {
+        return Err("Training must take at least one step".to_string());
     }
+    let delta = PRIVACY_BUDGETS.with(|budgets| budgets.borrow().get(&hospital_id).map(|b| b.delta_total));
+    let delta = delta.ok_or("Hospital not registered")?;
+
+    let mut accountant = DP_SGD_MOMENTS.with(|moments| {
+        moments
+            .borrow()
+            .get(&hospital_id)
+            .and_then(|bytes| serde_json::from_slice::<MomentsAccountant>(&bytes).ok())
+            .unwrap_or_default()
+    });
+    let before = accountant.epsilon(delta);
+    accountant.accumulate(sample_rate, noise_multiplier, steps);
+    let epsilon = accountant.epsilon(delta) - before;
+
+    authorize_session_spend(&token, ic_cdk::caller(), hospital_id, epsilon)?;
+    let data_hash = compute_hash(&[sample_rate, noise_multiplier, steps as f64]);
+    charge_privacy_budget(hospital_id, epsilon, 0.0, "dp_sgd_training".to_string(), data_hash, correlation_id)?;
+    record_session_spend(&token.session_id, hospital_id, epsilon);
+
+    let bytes = serde_json::to_vec(&accountant).map_err(|e| format!("Failed to store moments: {}", e))?;
+    DP_SGD_MOMENTS.with(|moments| moments.borrow_mut().insert(hospital_id, bytes));
+    Ok(epsilon)
+}
 
+fn charge_privacy_budget(
+    hospital_id: Principal,
+    epsilon_consumed: f64,
+    delta_consumed: f64,
+    operation_type: String,
+    data_hash: String,
+    correlation_id: String,
+) -> Result<String, String> {
     PRIVACY_BUDGETS.with(|budgets| {
         let mut budgets_map = budgets.borrow_mut();
         match budgets_map.get(&hospital_id) {
@@ -240,6 +388,7 @@ async fn consume_privacy_budget(
                     delta_consumed,
                     data_hash,
                     compliance_status,
+                    correlation_id,
                 ));
 
                 Ok(format!("Privacy budget consumed: ε={}, δ={}", epsilon_consumed, delta_consumed))
@@ -260,17 +409,34 @@ fn get_privacy_budget(hospital_id: Principal) -> Result<PrivacyBudget, String> {
     })
 }
 
-// Coordinate privacy across multiple hospitals for federated learning
+// Coordinate privacy across multiple hospitals for federated learning. Each token
+// holder (the aggregator, the inference canister) gets a session token valid for
+// `token_ttl_seconds`, required to spend the allocated budgets. Only the session
+// coordinator or an admin may open a session.
 #[update]
 async fn coordinate_federated_privacy(
     session_id: String,
     participating_hospitals: Vec<Principal>,
     total_epsilon_budget: f64,
-) -> Result<String, String> {
+    token_holders: Vec<Principal>,
+    token_ttl_seconds: u64,
+    correlation_id: Option<String>,
+) -> Result<Vec<SessionToken>, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
     let caller = ic_cdk::caller();
+    let correlation_id = resolve_correlation_id(correlation_id)?;
     
-    if caller == Principal::anonymous() {
-        return Err("Anonymous caller not allowed".to_string());
+    SESSION_COORDINATOR.with(|sessions| ADMIN.with(|admin| sessions.borrow().authorize_opening(caller, &admin.borrow())))?;
+    if participating_hospitals.is_empty() || token_holders.is_empty() {
+        return Err("A session needs participating hospitals and token holders".to_string());
+    }
+    let already_active = PRIVACY_COORDINATIONS.with(|coords| {
+        coords.borrow().get(&session_id).is_some_and(|c| {
+            matches!(c.status, CoordinationStatus::Active) && c.expires_at > ic_cdk::api::time()
+        })
+    });
+    if already_active {
+        return Err(format!("Session {} is already active", session_id));
     }
 
     // Allocate budget equally among hospitals
@@ -290,37 +456,118 @@ async fn coordinate_federated_privacy(
         }
     }
 
+    let secret = session_secret().await?;
+    let now = ic_cdk::api::time();
+    let ttl_ns = token_ttl_seconds.saturating_mul(1_000_000_000);
+    let tokens = token_holders
+        .iter()
+        .map(|holder| SessionToken::issue(&secret, &session_id, *holder, now, ttl_ns))
+        .collect::<Result<Vec<_>, String>>()?;
+
     let coordination = PrivacyCoordination {
         session_id: session_id.clone(),
         participating_hospitals,
         total_epsilon_budget,
         allocated_budgets,
+        consumed_budgets: Vec::new(),
+        token_holders,
+        expires_at: now.saturating_add(ttl_ns),
         status: CoordinationStatus::Active,
-        created_at: ic_cdk::api::time(),
+        created_at: now,
+        correlation_id,
     };
 
     PRIVACY_COORDINATIONS.with(|coords| {
         coords.borrow_mut().insert(session_id.clone(), coordination);
     });
 
-    Ok(format!("Privacy coordination established for session {}", session_id))
+    Ok(tokens)
+}
+
+async fn session_secret() -> Result<Vec<u8>, String> {
+    if let Some(secret) = SESSION_SECRET.with(|s| s.borrow().get(&0)) {
+        return Ok(secret);
+    }
+    let (secret,) = raw_rand().await.map_err(|(_, message)| format!("Could not draw session secret: {}", message))?;
+    // Another call may have stored one while this one awaited; keep the first
+    Ok(SESSION_SECRET.with(|s| {
+        let mut store = s.borrow_mut();
+        match store.get(&0) {
+            Some(existing) => existing,
+            None => {
+                store.insert(0, secret.clone());
+                secret
+            }
+        }
+    }))
 }
 
-// Add noise to gradients using differential privacy
+// The token must be valid for the caller, and the spend must fit in what the session
+// allocated to the hospital
+fn authorize_session_spend(token: &SessionToken, caller: Principal, hospital_id: Principal, epsilon: f64) -> Result<(), String> {
+    let secret = SESSION_SECRET
+        .with(|s| s.borrow().get(&0))
+        .ok_or("No privacy session has been started")?;
+    token.verify(&secret, caller, ic_cdk::api::time())?;
+
+    let coordination = PRIVACY_COORDINATIONS
+        .with(|coords| coords.borrow().get(&token.session_id))
+        .ok_or(format!("Unknown session {}", token.session_id))?;
+    if !matches!(coordination.status, CoordinationStatus::Active) {
+        return Err(format!("Session {} is not active", token.session_id));
+    }
+    let allocated = coordination
+        .allocated_budgets
+        .iter()
+        .find(|(id, _)| *id == hospital_id)
+        .map(|(_, epsilon)| *epsilon)
+        .ok_or(format!("Hospital {} is not part of session {}", hospital_id, token.session_id))?;
+    let consumed = coordination
+        .consumed_budgets
+        .iter()
+        .find(|(id, _)| *id == hospital_id)
+        .map_or(0.0, |(_, epsilon)| *epsilon);
+    if consumed + epsilon > allocated {
+        return Err(format!("Hospital {} has exhausted its allocation in session {}", hospital_id, token.session_id));
+    }
+    Ok(())
+}
+
+fn record_session_spend(session_id: &str, hospital_id: Principal, epsilon: f64) {
+    PRIVACY_COORDINATIONS.with(|coords| {
+        let mut coords = coords.borrow_mut();
+        if let Some(mut coordination) = coords.get(&session_id.to_string()) {
+            match coordination.consumed_budgets.iter_mut().find(|(id, _)| *id == hospital_id) {
+                Some((_, consumed)) => *consumed += epsilon,
+                None => coordination.consumed_budgets.push((hospital_id, epsilon)),
+            }
+            coords.insert(session_id.to_string(), coordination);
+        }
+    });
+}
+
+// Add noise to gradients using differential privacy, spending the hospital's
+// allocation in the token's session
 #[update]
 async fn add_privacy_noise(
+    token: SessionToken,
     hospital_id: Principal,
     gradients: Vec<f64>,
     epsilon: f64,
     delta: f64,
     sensitivity: f64,
+    correlation_id: Option<String>,
 ) -> Result<Vec<f64>, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
     let caller = ic_cdk::caller();
+    let correlation_id = resolve_correlation_id(correlation_id)?;
     
     if caller == Principal::anonymous() {
         return Err("Anonymous caller not allowed".to_string());
     }
 
+    authorize_session_spend(&token, caller, hospital_id, epsilon)?;
+
     // Check privacy budget
     match check_privacy_budget(hospital_id, epsilon, delta) {
         Ok(true) => {},
@@ -338,17 +585,70 @@ async fn add_privacy_noise(
 
     // Consume privacy budget
     let data_hash = compute_hash(&gradients);
-    consume_privacy_budget(
+    charge_privacy_budget(
         hospital_id,
         epsilon,
         delta,
         "gradient_noise_addition".to_string(),
         data_hash,
-    ).await?;
+        correlation_id,
+    )?;
+    record_session_spend(&token.session_id, hospital_id, epsilon);
 
     Ok(noisy_gradients)
 }
 
+// Charge each contributing hospital for a federated analytics query, out of their
+// allocations in the token's session. Hospitals add Laplace noise locally (pure ε-DP,
+// so δ = 0); every budget is checked before any is consumed so a query is never
+// partially charged.
+#[update]
+async fn account_federated_query(
+    token: SessionToken,
+    query_id: String,
+    spend: Vec<(Principal, f64)>,
+    correlation_id: Option<String>,
+) -> Result<String, String> {
+    ADMIN.with(|admin| admin.borrow().require_running())?;
+    let caller = ic_cdk::caller();
+    let correlation_id = resolve_correlation_id(correlation_id)?;
+
+    if caller == Principal::anonymous() {
+        return Err("Anonymous caller not allowed".to_string());
+    }
+    if spend.is_empty() {
+        return Err("No contributing hospitals".to_string());
+    }
+
+    let mut seen = Vec::new();
+    for (hospital_id, epsilon) in &spend {
+        if seen.contains(hospital_id) {
+            return Err(format!("Hospital {} listed more than once", hospital_id));
+        }
+        seen.push(*hospital_id);
+        if !(epsilon.is_finite() && *epsilon > 0.0) {
+            return Err(format!("Invalid ε {} for hospital {}", epsilon, hospital_id));
+        }
+        authorize_session_spend(&token, caller, *hospital_id, *epsilon)?;
+        match check_privacy_budget(*hospital_id, *epsilon, 0.0) {
+            Ok(true) => {},
+            Ok(false) => return Err(format!("Hospital {} has insufficient privacy budget for {}", hospital_id, query_id)),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(query_id.as_bytes());
+    let data_hash = format!("{:x}", hasher.finalize());
+    let operation_type = format!("federated_analytics:{}", query_id);
+    for (hospital_id, epsilon) in &spend {
+        charge_privacy_budget(*hospital_id, *epsilon, 0.0, operation_type.clone(), data_hash.clone(), correlation_id.clone())?;
+        record_session_spend(&token.session_id, *hospital_id, *epsilon);
+    }
+
+    Ok(format!("Charged {} hospitals for {}", spend.len(), query_id))
+}
+
 // Generate privacy audit report
 #[query]
 fn get_privacy_audit_report(hospital_id: Option<Principal>, limit: Option<u64>) -> Vec<PrivacyAuditEntry> {
@@ -371,6 +671,14 @@ fn get_privacy_audit_report(hospital_id: Option<Principal>, limit: Option<u64>)
     })
 }
 
+// Audit entries written under one correlation id, oldest first
+#[query]
+fn get_audit_trail(correlation_id: String) -> Vec<PrivacyAuditEntry> {
+    AUDIT_LOG.with(|log| {
+        log.borrow().iter().map(|(_, entry)| entry).filter(|entry| entry.correlation_id == correlation_id).collect()
+    })
+}
+
 // Check overall system compliance
 #[query]
 fn check_system_compliance() -> Result<String, String> {
@@ -410,6 +718,7 @@ async fn log_privacy_audit(
     delta_consumed: f64,
     data_hash: String,
     compliance_status: ComplianceStatus,
+    correlation_id: String,
 ) {
     let audit_id = AUDIT_COUNTER.with(|counter| {
         let mut c = counter.borrow_mut();
@@ -426,6 +735,7 @@ async fn log_privacy_audit(
         timestamp: ic_cdk::api::time(),
         data_hash,
         compliance_status,
+        correlation_id,
     };
 
     AUDIT_LOG.with(|log| {
@@ -433,6 +743,16 @@ async fn log_privacy_audit(
     });
 }
 
+// The caller's correlation id, or a new one for calls that brought none
+fn resolve_correlation_id(supplied: Option<String>) -> Result<String, String> {
+    let sequence = CORRELATION_SEQUENCE.with(|sequence| {
+        let mut sequence = sequence.borrow_mut();
+        *sequence += 1;
+        *sequence
+    });
+    correlation_id_or_new(supplied, "privacy_engine", ic_cdk::api::time(), sequence)
+}
+
 // Helper function to compute hash of data
 fn compute_hash(data: &[f64]) -> String {
     let mut hasher = Sha256::new();
@@ -442,15 +762,23 @@ fn compute_hash(data: &[f64]) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-// Reset privacy budget (admin function - use with caution)
+// Reset privacy budget (admin function - use with caution); needs an approved admin
+// proposal once an admin policy is configured
 #[update]
-async fn reset_privacy_budget(hospital_id: Principal) -> Result<String, String> {
+async fn reset_privacy_budget(hospital_id: Principal, correlation_id: Option<String>) -> Result<String, String> {
     let caller = ic_cdk::caller();
+    let correlation_id = resolve_correlation_id(correlation_id)?;
     
-    // In production, implement proper admin authorization
     if caller == Principal::anonymous() {
         return Err("Anonymous caller not allowed".to_string());
     }
+    if !PRIVACY_BUDGETS.with(|budgets| budgets.borrow().contains_key(&hospital_id)) {
+        return Err("Hospital not registered".to_string());
+    }
+
+    let change = PrivacyPolicyChange::ResetBudget { hospital_id };
+    with_governance(|g| g.authorize(&GovernanceAction::ChangePrivacyPolicy { change }, caller, ic_cdk::api::time()))?;
+    with_admin(|admin| admin.authorize(&AdminOperation::ResetPrivacyBudget { hospital_id }, caller, ic_cdk::api::time()))?;
 
     PRIVACY_BUDGETS.with(|budgets| {
         let mut budgets_map = budgets.borrow_mut();
@@ -462,6 +790,7 @@ async fn reset_privacy_budget(hospital_id: Principal) -> Result<String, String>
                 budget.queries_count = 0;
                 
                 budgets_map.insert(hospital_id, budget);
+                DP_SGD_MOMENTS.with(|moments| moments.borrow_mut().remove(&hospital_id));
 
                 // Log the reset
                 ic_cdk::spawn(log_privacy_audit(
@@ -471,6 +800,7 @@ async fn reset_privacy_budget(hospital_id: Principal) -> Result<String, String>
                     0.0,
                     "".to_string(),
                     ComplianceStatus::Compliant,
+                    correlation_id,
                 ));
 
                 Ok(format!("Privacy budget reset for hospital {}", hospital_id))
@@ -480,5 +810,150 @@ async fn reset_privacy_budget(hospital_id: Principal) -> Result<String, String>
     })
 }
 
+// Runs `f` against the approval registry and persists the result
+fn with_governance<R>(f: impl FnOnce(&mut LocalApprovalRegistry) -> R) -> R {
+    GOVERNANCE.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let result = f(&mut registry);
+        let bytes = Encode!(&*registry).unwrap();
+        GOVERNANCE_STORE.with(|store| store.borrow_mut().insert(0, bytes));
+        result
+    })
+}
+
+// Governance hooks: the governance canister pushes passed proposals here
+#[update]
+fn set_governance_canister(canister: Principal) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    with_governance(|g| g.set_governance_canister(caller, ic_cdk::api::is_controller(&caller), canister))
+}
+
+// Names the canister allowed to open privacy sessions; a privacy-policy change once
+// governance is configured
+#[update]
+fn set_session_coordinator(coordinator: Principal) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let is_controller = ic_cdk::api::is_controller(&caller);
+    SESSION_COORDINATOR.with(|sessions| {
+        let mut sessions = sessions.borrow_mut();
+        with_governance(|g| sessions.set(coordinator, caller, is_controller, g, ic_cdk::api::time()))?;
+        let bytes = Encode!(&*sessions).unwrap();
+        GOVERNANCE_STORE.with(|store| store.borrow_mut().insert(1, bytes));
+        Ok(())
+    })
+}
+
+#[query]
+fn get_session_coordinator() -> Option<Principal> {
+    SESSION_COORDINATOR.with(|sessions| sessions.borrow().coordinator())
+}
+
+#[update]
+fn record_governance_decision(decision: ApprovalDecision) -> Result<(), String> {
+    with_governance(|g| g.record_decision(ic_cdk::caller(), decision))
+}
+
+#[update]
+fn revoke_governance_decision(proposal_id: u64) -> Result<(), String> {
+    with_governance(|g| g.revoke_decision(ic_cdk::caller(), proposal_id))
+}
+
+#[query]
+fn get_pending_approvals() -> Vec<ApprovalDecision> {
+    GOVERNANCE.with(|g| g.borrow().pending_approvals())
+}
+
+#[query]
+fn get_governance_receipts() -> Vec<ApprovalReceipt> {
+    GOVERNANCE.with(|g| g.borrow().receipts().to_vec())
+}
+
+// Runs `f` against the admin approvals and persists the result
+fn with_admin<R>(f: impl FnOnce(&mut AdminApprovals) -> R) -> R {
+    ADMIN.with(|admin| {
+        let mut admin = admin.borrow_mut();
+        let result = f(&mut admin);
+        let bytes = Encode!(&*admin).unwrap();
+        ADMIN_STORE.with(|store| store.borrow_mut().insert(0, bytes));
+        result
+    })
+}
+
+// Admin operations: proposed by one admin, approved by `threshold` of them, then
+// executed through the operation's own endpoint
+#[update]
+fn configure_admin_policy(policy: AdminPolicy) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    with_admin(|admin| admin.configure(policy, caller, ic_cdk::api::is_controller(&caller), ic_cdk::api::time()))
+}
+
+#[update]
+fn propose_admin_operation(operation: AdminOperation) -> Result<u64, String> {
+    if let AdminOperation::RollbackModel { .. } = operation {
+        return Err("The privacy engine holds no model to roll back".to_string());
+    }
+    if let AdminOperation::ChangeShadowPolicy { .. } = operation {
+        return Err("Shadow deployments run on the inference canister".to_string());
+    }
+    with_admin(|admin| admin.propose(operation, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn approve_admin_operation(proposal_id: u64) -> Result<u32, String> {
+    with_admin(|admin| admin.approve(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[update]
+fn cancel_admin_operation(proposal_id: u64) -> Result<(), String> {
+    with_admin(|admin| admin.cancel(proposal_id, ic_cdk::caller(), ic_cdk::api::time()))
+}
+
+#[query]
+fn get_admin_policy() -> Option<AdminPolicy> {
+    ADMIN.with(|admin| admin.borrow().policy().cloned())
+}
+
+#[query]
+fn get_admin_proposals() -> Vec<AdminProposal> {
+    ADMIN.with(|admin| admin.borrow().open_proposals(ic_cdk::api::time()))
+}
+
+// Stops all budget spending, session coordination and noise requests until resumed
+#[update]
+fn emergency_pause() -> Result<(), String> {
+    with_admin(|admin| admin.pause(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Privacy engine paused by {}", ic_cdk::caller());
+    Ok(())
+}
+
+#[update]
+fn resume() -> Result<(), String> {
+    with_admin(|admin| admin.resume(ic_cdk::caller(), ic_cdk::api::time()))?;
+    ic_cdk::println!("Privacy engine resumed by {}", ic_cdk::caller());
+    Ok(())
+}
+
 // Export Candid interface
-ic_cdk::export_candid!();
\ No newline at end of file
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registering_a_hospital_twice_does_not_reset_its_spend() {
+        let hospital_id = Principal::from_slice(&[1]);
+        let mut budget = new_privacy_budget(None, hospital_id, 10.0, 1e-5, 0).unwrap();
+        assert_eq!((budget.epsilon_total, budget.epsilon_used), (10.0, 0.0));
+        budget.epsilon_used = 9.5;
+        let again = new_privacy_budget(Some(&budget), hospital_id, 10.0, 1e-5, 1);
+        assert!(matches!(again, Err(e) if e.contains("reset_privacy_budget")));
+    }
+
+    // Regenerates privacy_engine.did from the Rust interface and fails on breaking changes
+    #[test]
+    fn test_candid_interface_is_compatible() {
+        let did = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("privacy_engine.did");
+        candid_interface::check_interface(&did, &super::__get_candid_interface_tmp_hack()).unwrap();
+    }
+}
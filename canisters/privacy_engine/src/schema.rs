@@ -0,0 +1,246 @@
+// Versioned envelope for the records kept in stable memory. A record is written as
+// the magic `STBV`, its type's schema version as a little-endian u32, and the
+// Candid encoding of the value. Records written before the envelope existed are bare
+// Candid, which always starts with `DIDL`, and count as version 1.
+//
+// Decoding a record of the current version is a plain Candid decode; an older record
+// goes through the type's `migrate`, which decodes the frozen struct of that version
+// and fills in what later versions added. A record from a newer version is refused
+// rather than misread, so a downgrade fails loudly instead of corrupting state.
+//
+// Changing a stored type means bumping its `VERSION`, freezing the previous layout
+// below as `<Type>V<n>`, adding the migration arm, and a test that decodes a record
+// encoded with the frozen layout.
+
+use crate::{ComplianceStatus, CoordinationStatus, PrivacyAuditEntry, PrivacyBudget, PrivacyCoordination};
+use candid::{CandidType, Decode, Encode, Principal};
+use serde::Deserialize;
+
+const MAGIC: &[u8; 4] = b"STBV";
+const CANDID_MAGIC: &[u8; 4] = b"DIDL";
+
+pub trait Versioned: CandidType + for<'de> Deserialize<'de> {
+    const VERSION: u32;
+
+    // Decodes a payload written at an older `version` into the current type
+    fn migrate(version: u32, payload: &[u8]) -> Result<Self, String>;
+}
+
+pub fn encode<T: Versioned>(value: &T) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&T::VERSION.to_le_bytes());
+    bytes.extend(Encode!(value).unwrap());
+    bytes
+}
+
+pub fn decode<T: Versioned>(bytes: &[u8]) -> Result<T, String> {
+    let name = std::any::type_name::<T>();
+    let (version, payload) = if bytes.starts_with(CANDID_MAGIC) {
+        (1, bytes)
+    } else if bytes.len() >= 8 && bytes.starts_with(MAGIC) {
+        (u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]), &bytes[8..])
+    } else {
+        return Err(format!("{}: record is neither versioned nor Candid", name));
+    };
+
+    if version > T::VERSION {
+        return Err(format!("{}: record version {} is newer than supported version {}", name, version, T::VERSION));
+    }
+    let decoded = if version == T::VERSION {
+        Decode!(payload, T).map_err(|e| e.to_string())
+    } else {
+        T::migrate(version, payload)
+    };
+    decoded.map_err(|e| format!("{}: cannot decode version {} record: {}", name, version, e))
+}
+
+fn no_migration<T>(version: u32) -> Result<T, String> {
+    Err(format!("no migration from version {}", version))
+}
+
+impl Versioned for PrivacyBudget {
+    const VERSION: u32 = 1;
+
+    fn migrate(version: u32, _payload: &[u8]) -> Result<Self, String> {
+        no_migration(version)
+    }
+}
+
+// Audit entry before correlation ids
+#[derive(CandidType, Deserialize)]
+pub struct PrivacyAuditEntryV1 {
+    pub id: u64,
+    pub hospital_id: Principal,
+    pub operation_type: String,
+    pub epsilon_consumed: f64,
+    pub delta_consumed: f64,
+    pub timestamp: u64,
+    pub data_hash: String,
+    pub compliance_status: ComplianceStatus,
+}
+
+impl Versioned for PrivacyAuditEntry {
+    const VERSION: u32 = 2;
+
+    fn migrate(version: u32, payload: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => {
+                let v1 = Decode!(payload, PrivacyAuditEntryV1).map_err(|e| e.to_string())?;
+                // Entries from before tracing belong to no trace
+                Ok(PrivacyAuditEntry {
+                    id: v1.id,
+                    hospital_id: v1.hospital_id,
+                    operation_type: v1.operation_type,
+                    epsilon_consumed: v1.epsilon_consumed,
+                    delta_consumed: v1.delta_consumed,
+                    timestamp: v1.timestamp,
+                    data_hash: v1.data_hash,
+                    compliance_status: v1.compliance_status,
+                    correlation_id: String::new(),
+                })
+            }
+            _ => no_migration(version),
+        }
+    }
+}
+
+// Coordination before per-hospital consumption and session tokens were tracked
+#[derive(CandidType, Deserialize)]
+pub struct PrivacyCoordinationV1 {
+    pub session_id: String,
+    pub participating_hospitals: Vec<Principal>,
+    pub total_epsilon_budget: f64,
+    pub allocated_budgets: Vec<(Principal, f64)>,
+    pub status: CoordinationStatus,
+    pub created_at: u64,
+}
+
+// Coordination before correlation ids
+#[derive(CandidType, Deserialize)]
+pub struct PrivacyCoordinationV2 {
+    pub session_id: String,
+    pub participating_hospitals: Vec<Principal>,
+    pub total_epsilon_budget: f64,
+    pub allocated_budgets: Vec<(Principal, f64)>,
+    pub consumed_budgets: Vec<(Principal, f64)>,
+    pub token_holders: Vec<Principal>,
+    pub expires_at: u64,
+    pub status: CoordinationStatus,
+    pub created_at: u64,
+}
+
+impl From<PrivacyCoordinationV1> for PrivacyCoordinationV2 {
+    // No tokens were issued for version 1 sessions, so they expire on migration
+    fn from(v1: PrivacyCoordinationV1) -> Self {
+        PrivacyCoordinationV2 {
+            session_id: v1.session_id,
+            participating_hospitals: v1.participating_hospitals,
+            total_epsilon_budget: v1.total_epsilon_budget,
+            allocated_budgets: v1.allocated_budgets,
+            consumed_budgets: Vec::new(),
+            token_holders: Vec::new(),
+            expires_at: v1.created_at,
+            status: v1.status,
+            created_at: v1.created_at,
+        }
+    }
+}
+
+impl From<PrivacyCoordinationV2> for PrivacyCoordination {
+    fn from(v2: PrivacyCoordinationV2) -> Self {
+        PrivacyCoordination {
+            session_id: v2.session_id,
+            participating_hospitals: v2.participating_hospitals,
+            total_epsilon_budget: v2.total_epsilon_budget,
+            allocated_budgets: v2.allocated_budgets,
+            consumed_budgets: v2.consumed_budgets,
+            token_holders: v2.token_holders,
+            expires_at: v2.expires_at,
+            status: v2.status,
+            created_at: v2.created_at,
+            correlation_id: String::new(),
+        }
+    }
+}
+
+impl Versioned for PrivacyCoordination {
+    const VERSION: u32 = 3;
+
+    // Each version migrates through the next
+    fn migrate(version: u32, payload: &[u8]) -> Result<Self, String> {
+        let v2 = match version {
+            1 => Decode!(payload, PrivacyCoordinationV1).map_err(|e| e.to_string())?.into(),
+            2 => Decode!(payload, PrivacyCoordinationV2).map_err(|e| e.to_string())?,
+            _ => return no_migration(version),
+        };
+        Ok(PrivacyCoordination::from(v2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::Storable;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_records_from_previous_versions_decode() {
+        let hospital = Principal::from_slice(&[7]);
+
+        // Bare Candid, as every record was written before the envelope
+        let budget = PrivacyBudget {
+            hospital_id: hospital,
+            epsilon_used: 0.5,
+            epsilon_total: 10.0,
+            delta_used: 1e-6,
+            delta_total: 1e-5,
+            last_updated: 42,
+            queries_count: 3,
+        };
+        let decoded = PrivacyBudget::from_bytes(Cow::Owned(Encode!(&budget).unwrap()));
+        assert_eq!((decoded.epsilon_used, decoded.queries_count), (0.5, 3));
+
+        let entry = PrivacyAuditEntryV1 {
+            id: 9,
+            hospital_id: hospital,
+            operation_type: "query".to_string(),
+            epsilon_consumed: 0.1,
+            delta_consumed: 0.0,
+            timestamp: 42,
+            data_hash: "ab".to_string(),
+            compliance_status: ComplianceStatus::Warning,
+        };
+        let decoded = PrivacyAuditEntry::from_bytes(Cow::Owned(Encode!(&entry).unwrap()));
+        assert!(decoded.id == 9 && matches!(decoded.compliance_status, ComplianceStatus::Warning));
+        assert!(decoded.correlation_id.is_empty());
+
+        let v1 = PrivacyCoordinationV1 {
+            session_id: "session".to_string(),
+            participating_hospitals: vec![hospital],
+            total_epsilon_budget: 2.0,
+            allocated_budgets: vec![(hospital, 2.0)],
+            status: CoordinationStatus::Active,
+            created_at: 100,
+        };
+        let migrated = PrivacyCoordination::from_bytes(Cow::Owned(Encode!(&v1).unwrap()));
+        assert_eq!(migrated.allocated_budgets, vec![(hospital, 2.0)]);
+        assert!(migrated.token_holders.is_empty() && migrated.expires_at == 100);
+
+        // A version 2 record, written in the envelope
+        let mut v2 = PrivacyCoordinationV2::from(v1);
+        v2.token_holders = vec![hospital];
+        let mut bytes = b"STBV\x02\0\0\0".to_vec();
+        bytes.extend(Encode!(&v2).unwrap());
+        let migrated = PrivacyCoordination::from_bytes(Cow::Owned(bytes));
+        assert!(migrated.token_holders == vec![hospital] && migrated.correlation_id.is_empty());
+
+        // Current records round-trip through the envelope; newer ones are refused
+        let bytes = migrated.to_bytes().into_owned();
+        assert_eq!(&bytes[..8], b"STBV\x03\0\0\0");
+        assert_eq!(PrivacyCoordination::from_bytes(Cow::Owned(bytes.clone())).session_id, "session");
+        let mut newer = bytes;
+        newer[4] = 4;
+        assert!(decode::<PrivacyCoordination>(&newer).unwrap_err().contains("newer"));
+        assert!(decode::<PrivacyBudget>(b"garbage!").is_err());
+    }
+}
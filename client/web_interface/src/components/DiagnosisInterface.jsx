@@ -58,6 +58,8 @@ const DiagnosisInterface = () => {
     }
 
     setLoading(true)
+    // Traces this diagnosis across canisters; sent as the query's correlation_id
+    const correlationId = crypto.randomUUID()
     
     // Simulate API call to AI inference canister
     try {
@@ -76,7 +78,8 @@ const DiagnosisInterface = () => {
         ],
         riskFactors: ['Age', 'Medical history'],
         modelVersion: 'v1.2.3',
-        signature: 'verified'
+        signature: 'verified',
+        correlationId
       }
       
       setDiagnosis(mockDiagnosis)
@@ -260,6 +263,9 @@ const DiagnosisInterface = () => {
                       <Shield className="w-4 h-4 mr-1" />
                       Model: {diagnosis.modelVersion}
                     </div>
+                    <div className="flex items-center">
+                      Trace: {diagnosis.correlationId}
+                    </div>
                     <div className="flex items-center">
                       <CheckCircle className="w-4 h-4 mr-1 text-green-400" />
                       Signature Verified
@@ -0,0 +1,79 @@
+// Keeps each canister's committed .did file in step with its Rust interface. A
+// canister test passes in the interface generated by `export_candid!`; the file is
+// written when it is missing or the new interface is a compatible evolution of it,
+// and the test fails on a breaking change unless CANDID_ALLOW_BREAKING is set, so
+// frontend teams see every interface change as a reviewed diff of the .did file.
+
+use candid_parser::utils::{service_compatible, CandidSource};
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const ALLOW_BREAKING_ENV: &str = "CANDID_ALLOW_BREAKING";
+
+#[derive(Debug, PartialEq)]
+pub enum InterfaceChange {
+    Created,
+    Unchanged,
+    // Rewritten; existing clients keep working
+    Compatible,
+    // Rewritten with CANDID_ALLOW_BREAKING set
+    Breaking,
+}
+
+pub fn check_interface(did_path: &Path, generated: &str) -> Result<InterfaceChange, String> {
+    let committed = match std::fs::read_to_string(did_path) {
+        Ok(committed) => committed,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            write_interface(did_path, generated)?;
+            return Ok(InterfaceChange::Created);
+        }
+        Err(e) => return Err(format!("Cannot read {}: {}", did_path.display(), e)),
+    };
+    if committed == generated {
+        return Ok(InterfaceChange::Unchanged);
+    }
+
+    // The new service must be a subtype of the old one for existing callers
+    let change = match service_compatible(CandidSource::Text(generated), CandidSource::Text(&committed)) {
+        Ok(()) => InterfaceChange::Compatible,
+        Err(e) if std::env::var_os(ALLOW_BREAKING_ENV).is_none() => {
+            return Err(format!(
+                "Breaking Candid interface change against {}: {}\nCoordinate with the frontend, then rerun with {}=1 to accept it",
+                did_path.display(),
+                e,
+                ALLOW_BREAKING_ENV
+            ));
+        }
+        Err(_) => InterfaceChange::Breaking,
+    };
+    write_interface(did_path, generated)?;
+    Ok(change)
+}
+
+fn write_interface(did_path: &Path, generated: &str) -> Result<(), String> {
+    std::fs::write(did_path, generated).map_err(|e| format!("Cannot write {}: {}", did_path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaking_changes_are_rejected() {
+        let did = std::env::temp_dir().join(format!("candid_interface_{}.did", std::process::id()));
+        let _ = std::fs::remove_file(&did);
+
+        let v1 = "service : { get : (text) -> (opt nat64) query }";
+        assert_eq!(check_interface(&did, v1), Ok(InterfaceChange::Created));
+        assert_eq!(check_interface(&did, v1), Ok(InterfaceChange::Unchanged));
+
+        // A new method is fine; changing an argument type is not
+        let v2 = "service : { get : (text) -> (opt nat64) query; put : (text, nat64) -> () }";
+        assert_eq!(check_interface(&did, v2), Ok(InterfaceChange::Compatible));
+        let v3 = "service : { get : (nat64) -> (opt nat64) query; put : (text, nat64) -> () }";
+        assert!(check_interface(&did, v3).is_err());
+        assert_eq!(std::fs::read_to_string(&did).unwrap(), v2);
+
+        std::fs::remove_file(&did).unwrap();
+    }
+}
@@ -3,6 +3,11 @@ use serde::{Deserialize, Serialize};
 use statrs::distribution::{Laplace, Continuous};
 use std::collections::HashMap;
 
+pub mod moments_accountant;
+pub use moments_accountant::*;
+pub mod statistics;
+pub use statistics::*;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivacyBudget {
     pub epsilon: f64,
@@ -0,0 +1,151 @@
+// Moments accountant (Abadi et al., 2016) for the subsampled Gaussian mechanism: each
+// step adds Gaussian noise with multiplier σ to a sum over a uniform sample drawn at
+// rate q. The accountant tracks the log moments α(λ) of the privacy loss for
+// λ = 1..=max_lambda. They add up over steps, and the tail bound
+//
+//   δ = min_λ exp(α(λ) - λε),   equivalently   ε = min_λ (α(λ) + ln(1/δ)) / λ
+//
+// turns them into an (ε, δ) guarantee, far below what summing each step's ε gives
+// over the thousands of steps of DP-SGD or hundreds of federated rounds.
+//
+// A log moment at λ is the Rényi divergence at order λ + 1 scaled by λ, so the same
+// computation backs the RDP accounting in federated_learning.
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MAX_LAMBDA: u32 = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MomentsAccountant {
+    // Summed log moment at λ = index + 1
+    pub log_moments: Vec<f64>,
+    pub steps: u64,
+}
+
+// α(λ) of one step, by expanding E[(1 - q + q e^{...})^{λ+1}] binomially in log space
+pub fn sampled_gaussian_log_moment(sample_rate: f64, noise_multiplier: f64, lambda: u32) -> f64 {
+    if noise_multiplier <= 0.0 {
+        return f64::INFINITY;
+    }
+    if sample_rate <= 0.0 {
+        return 0.0;
+    }
+    let order = lambda + 1;
+    let sigma2 = noise_multiplier * noise_multiplier;
+    if sample_rate >= 1.0 {
+        return (order as f64) * (lambda as f64) / (2.0 * sigma2);
+    }
+
+    let (log_q, log_1q) = (sample_rate.ln(), (1.0 - sample_rate).ln());
+    let mut log_binomial = 0.0;
+    let mut terms = Vec::with_capacity(order as usize + 1);
+    for k in 0..=order {
+        if k > 0 {
+            log_binomial += ((order - k + 1) as f64).ln() - (k as f64).ln();
+        }
+        let k = k as f64;
+        let log_q_term = if k > 0.0 { k * log_q } else { 0.0 };
+        let log_1q_term = if order as f64 > k { (order as f64 - k) * log_1q } else { 0.0 };
+        terms.push(log_binomial + log_q_term + log_1q_term + (k * k - k) / (2.0 * sigma2));
+    }
+    let max = terms.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    max + terms.iter().map(|t| (t - max).exp()).sum::<f64>().ln()
+}
+
+impl MomentsAccountant {
+    pub fn new(max_lambda: u32) -> Self {
+        MomentsAccountant { log_moments: vec![0.0; max_lambda.max(1) as usize], steps: 0 }
+    }
+
+    fn lambdas(&self) -> impl Iterator<Item = u32> {
+        1..=self.log_moments.len() as u32
+    }
+
+    pub fn step_log_moments(&self, sample_rate: f64, noise_multiplier: f64) -> Vec<f64> {
+        self.lambdas().map(|lambda| sampled_gaussian_log_moment(sample_rate, noise_multiplier, lambda)).collect()
+    }
+
+    pub fn accumulate(&mut self, sample_rate: f64, noise_multiplier: f64, steps: u64) {
+        let step = self.step_log_moments(sample_rate, noise_multiplier);
+        self.log_moments.iter_mut().zip(&step).for_each(|(total, m)| *total += steps as f64 * m);
+        self.steps += steps;
+    }
+
+    pub fn epsilon(&self, delta: f64) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        moments_to_epsilon(&self.log_moments, delta)
+    }
+
+    pub fn delta(&self, epsilon: f64) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        self.lambdas()
+            .zip(&self.log_moments)
+            .map(|(lambda, moment)| (moment - lambda as f64 * epsilon).exp())
+            .fold(1.0, f64::min)
+    }
+
+    // Further steps of the given mechanism, up to `cap`, that keep ε within `total_epsilon`
+    pub fn remaining_steps(&self, sample_rate: f64, noise_multiplier: f64, delta: f64, total_epsilon: f64, cap: u64) -> u64 {
+        let step = self.step_log_moments(sample_rate, noise_multiplier);
+        let epsilon_after = |k: u64| {
+            let moments: Vec<f64> = self.log_moments.iter().zip(&step).map(|(total, m)| total + k as f64 * m).collect();
+            moments_to_epsilon(&moments, delta)
+        };
+        let (mut low, mut high) = (0, cap);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if epsilon_after(mid) <= total_epsilon {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        low
+    }
+}
+
+impl Default for MomentsAccountant {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LAMBDA)
+    }
+}
+
+fn moments_to_epsilon(log_moments: &[f64], delta: f64) -> f64 {
+    log_moments
+        .iter()
+        .enumerate()
+        .map(|(i, moment)| (moment - delta.ln()) / (i + 1) as f64)
+        .fold(f64::INFINITY, f64::min)
+        .max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moments_accountant_matches_gaussian_and_beats_linear_composition() {
+        // Without sampling the log moment is λ(λ + 1) / 2σ²
+        assert!((sampled_gaussian_log_moment(1.0, 2.0, 3) - 12.0 / 8.0).abs() < 1e-12);
+        // and the binomial expansion agrees as q approaches 1
+        assert!((sampled_gaussian_log_moment(1.0 - 1e-12, 2.0, 3) - 12.0 / 8.0).abs() < 1e-6);
+
+        // DP-SGD on MNIST (Abadi et al.): q = 0.01, σ = 4, 10k steps, δ = 1e-5 gives ε ≈ 1.26
+        let mut accountant = MomentsAccountant::default();
+        accountant.accumulate(0.01, 4.0, 10_000);
+        let epsilon = accountant.epsilon(1e-5);
+        assert!(epsilon > 1.0 && epsilon < 1.5, "epsilon {}", epsilon);
+        assert!(accountant.delta(epsilon) <= 1e-5 * (1.0 + 1e-9));
+
+        let remaining = accountant.remaining_steps(0.01, 4.0, 1e-5, 2.0, u64::MAX / 2);
+        let mut extended = accountant.clone();
+        extended.accumulate(0.01, 4.0, remaining);
+        assert!(extended.epsilon(1e-5) <= 2.0);
+        extended.accumulate(0.01, 4.0, 1);
+        assert!(extended.epsilon(1e-5) > 2.0);
+    }
+}
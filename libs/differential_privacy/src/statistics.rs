@@ -0,0 +1,50 @@
+// Laplace-mechanism counts and means, for releasing statistics over groups of
+// patients that may be small. Adding or removing one patient changes a count by at
+// most 1, so Laplace(1/ε) noise makes it ε-DP. A mean is a noisy sum over a noisy
+// count, each spending half the ε, after clamping every value to [lower, upper]; the
+// clamp bounds the sum's sensitivity to max(|lower|, |upper|). Clamping the result
+// back into the range and rounding counts are post-processing and cost nothing.
+
+use rand::Rng;
+
+// Inverse CDF of a uniform draw on [-1/2, 1/2)
+pub fn laplace_noise<R: Rng + ?Sized>(rng: &mut R, scale: f64) -> f64 {
+    let u: f64 = rng.gen::<f64>() - 0.5;
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+pub fn noisy_count<R: Rng + ?Sized>(rng: &mut R, count: u64, epsilon: f64) -> f64 {
+    (count as f64 + laplace_noise(rng, 1.0 / epsilon)).round().max(0.0)
+}
+
+// The midpoint of the range when the noisy count leaves nothing to divide by
+pub fn noisy_mean<R: Rng + ?Sized>(rng: &mut R, values: &[f64], lower: f64, upper: f64, epsilon: f64) -> f64 {
+    let sensitivity = lower.abs().max(upper.abs());
+    let sum: f64 = values.iter().map(|v| v.clamp(lower, upper)).sum();
+    let noisy_sum = sum + laplace_noise(rng, 2.0 * sensitivity / epsilon);
+    let noisy_count = values.len() as f64 + laplace_noise(rng, 2.0 / epsilon);
+    if noisy_count < 1.0 {
+        return (lower + upper) / 2.0;
+    }
+    (noisy_sum / noisy_count).clamp(lower, upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_noisy_statistics_track_the_truth_at_large_epsilon() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let draws: Vec<f64> = (0..2_000).map(|_| laplace_noise(&mut rng, 2.0)).collect();
+        let mean_absolute = draws.iter().map(|d| d.abs()).sum::<f64>() / draws.len() as f64;
+        assert!((mean_absolute - 2.0).abs() < 0.2);
+
+        assert_eq!(noisy_count(&mut rng, 12, 100.0), 12.0);
+        let values = [10.0, 20.0, 30.0, 1_000.0];
+        // The outlier is clamped to 40 before averaging
+        assert!((noisy_mean(&mut rng, &values, 0.0, 40.0, 1_000.0) - 25.0).abs() < 0.5);
+        assert!((0.0..=40.0).contains(&noisy_mean(&mut rng, &[], 0.0, 40.0, 0.01)));
+    }
+}
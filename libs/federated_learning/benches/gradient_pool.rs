@@ -0,0 +1,70 @@
+// Compares building each round's client gradients in fresh allocations against
+// recycling them through a warm GradientPool, over several simulated rounds.
+//
+// cargo bench -p federated_learning --bench gradient_pool
+
+use federated_learning::GradientPool;
+use std::hint::black_box;
+use std::time::Instant;
+
+const CLIENTS: usize = 128;
+const DIMENSION: usize = 262_144;
+const ROUNDS: usize = 10;
+
+fn fill(buffer: &mut [f64], client: usize, round: usize) {
+    for (i, value) in buffer.iter_mut().enumerate() {
+        *value = ((client * 31 + round * 17 + i) % 1009) as f64 * 1e-3;
+    }
+}
+
+fn main() {
+    println!(
+        "{} clients x {} weights ({:.1} MB per gradient), {} rounds",
+        CLIENTS,
+        DIMENSION,
+        (DIMENSION * 8) as f64 / 1e6,
+        ROUNDS
+    );
+
+    let start = Instant::now();
+    for round in 0..ROUNDS {
+        let gradients: Vec<Vec<f64>> = (0..CLIENTS)
+            .map(|client| {
+                let mut buffer = vec![0.0; DIMENSION];
+                fill(&mut buffer, client, round);
+                buffer
+            })
+            .collect();
+        black_box(&gradients);
+    }
+    let fresh_secs = start.elapsed().as_secs_f64();
+
+    let mut pool = GradientPool::new(DIMENSION, CLIENTS);
+    pool.warm(CLIENTS);
+    let start = Instant::now();
+    for round in 0..ROUNDS {
+        let gradients: Vec<Vec<f64>> = (0..CLIENTS)
+            .map(|client| {
+                let mut buffer = pool.acquire();
+                fill(&mut buffer, client, round);
+                buffer
+            })
+            .collect();
+        black_box(&gradients);
+        for buffer in gradients {
+            pool.release(buffer);
+        }
+    }
+    let pooled_secs = start.elapsed().as_secs_f64();
+
+    let stats = pool.stats();
+    println!(
+        "fresh {:>8.1} ms   pooled {:>8.1} ms   speedup {:>5.2}x   allocations {} reuses {} retained {:.1} MB",
+        fresh_secs * 1e3,
+        pooled_secs * 1e3,
+        fresh_secs / pooled_secs,
+        stats.allocations,
+        stats.reuses,
+        stats.retained_bytes as f64 / 1e6
+    );
+}
@@ -0,0 +1,67 @@
+// Compares the scalar reference kernels against the dispatched backend.
+//
+// Native:   cargo bench -p federated_learning --features avx --bench kernels
+// Canister: CARGO_TARGET_WASM32_WASIP1_RUNNER=wasmtime RUSTFLAGS="-C target-feature=+simd128" \
+//           cargo bench -p federated_learning --features wasm-simd --bench kernels --target wasm32-wasip1
+//
+// Canisters run simd128 wasm, so the second is the speedup that matters on chain; CI
+// runs it under wasmtime. The first line of output names the backend that was measured.
+
+use federated_learning::kernels::{self, scalar};
+use std::hint::black_box;
+use std::time::Instant;
+
+const LEN: usize = 1_000_000;
+const ITERATIONS: u32 = 50;
+
+fn time<F: FnMut()>(mut f: F) -> f64 {
+    // Warm up once so the first-touch page faults are not measured
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed().as_secs_f64() / ITERATIONS as f64
+}
+
+fn report(name: &str, scalar_secs: f64, dispatched_secs: f64) {
+    println!(
+        "{:<22} scalar {:>9.3} ms   dispatched {:>9.3} ms   speedup {:>5.2}x",
+        name,
+        scalar_secs * 1e3,
+        dispatched_secs * 1e3,
+        scalar_secs / dispatched_secs,
+    );
+}
+
+fn main() {
+    let x: Vec<f64> = (0..LEN).map(|i| ((i % 997) as f64 - 498.0) * 1e-3).collect();
+    let mut y = vec![0.0; LEN];
+    let mut out = vec![0.0; LEN];
+
+    println!("backend: {:?}, vector length {}", kernels::active_backend(), LEN);
+
+    let s = time(|| scalar::axpy(black_box(0.25), black_box(&x), black_box(&mut y)));
+    let d = time(|| kernels::axpy(black_box(0.25), black_box(&x), black_box(&mut y)));
+    report("weighted average", s, d);
+
+    let s = time(|| scalar::axpy(black_box(1.0), black_box(&x), black_box(&mut y)));
+    let d = time(|| kernels::add_assign(black_box(&mut y), black_box(&x)));
+    report("noise addition", s, d);
+
+    let s = time(|| scalar::quantize_magnitudes(black_box(&x), black_box(0.5), black_box(255.0), black_box(&mut out)));
+    let d = time(|| kernels::quantize_magnitudes(black_box(&x), black_box(0.5), black_box(255.0), black_box(&mut out)));
+    report("quantization", s, d);
+
+    let s = time(|| scalar::affine(black_box(&x), black_box(2.0 / 255.0), black_box(-1.0), black_box(&mut out)));
+    let d = time(|| kernels::affine(black_box(&x), black_box(2.0 / 255.0), black_box(-1.0), black_box(&mut out)));
+    report("dequantization", s, d);
+
+    let s = time(|| {
+        black_box(scalar::sum_squares(black_box(&x)));
+    });
+    let d = time(|| {
+        black_box(kernels::sum_squares(black_box(&x)));
+    });
+    report("l2 norm", s, d);
+}
@@ -0,0 +1,73 @@
+// Accuracy of every aggregation method on the simulated task, without attackers and
+// against each poisoning attack with 20% of the clients malicious. Backdoor rows also
+// report how often the trigger flips a test record to the attacker's target.
+//
+// cargo bench -p federated_learning --bench poisoning
+
+use federated_learning::{
+    AggregationMethod, Attack, AttackScenario, FederatedLearningConfigBuilder, PrivacyMethod, Simulation, SimulationConfig,
+};
+
+const MALICIOUS_FRACTION: f64 = 0.2;
+
+fn attack_name(attack: &Attack) -> String {
+    match attack {
+        Attack::LabelFlip => "label flip".to_string(),
+        Attack::SignFlip => "sign flip".to_string(),
+        Attack::Scaled { factor } => format!("scaled x{}", factor),
+        Attack::Backdoor { boost, .. } => format!("backdoor x{}", boost),
+    }
+}
+
+fn main() {
+    let simulation = Simulation::new(SimulationConfig { clients: 20, rounds: 15, ..SimulationConfig::default() }).unwrap();
+    let base = FederatedLearningConfigBuilder::new()
+        .model_dimension(simulation.model_dimension())
+        .min_clients(simulation.config().clients as u32)
+        .privacy_method(PrivacyMethod::None)
+        .build()
+        .unwrap();
+    let methods = [
+        AggregationMethod::FedAvg,
+        AggregationMethod::Krum { byzantine_clients: 4 },
+        AggregationMethod::MultiKrum { m: 10 },
+        AggregationMethod::TrimmedMean { trim_ratio: 0.2 },
+        AggregationMethod::Median,
+        AggregationMethod::Bulyan { f: 4 },
+        AggregationMethod::FoolsGold,
+        AggregationMethod::GeometricMedian { max_iterations: 50, tolerance: 1e-6 },
+    ];
+    let scenarios: Vec<AttackScenario> = [
+        Attack::LabelFlip,
+        Attack::SignFlip,
+        Attack::Scaled { factor: -5.0 },
+        Attack::Backdoor { trigger_feature: 0, trigger_value: 6.0, target_label: 1.0, boost: 10.0 },
+    ]
+    .into_iter()
+    .map(|attack| AttackScenario { attack, malicious_fraction: MALICIOUS_FRACTION })
+    .collect();
+
+    println!("{:<58} {:<14} {:>9} {:>9} {:>9}", "aggregation", "attack", "accuracy", "backdoor", "rejected");
+    for method in &methods {
+        let single = simulation.benchmark_attacks(&base, std::slice::from_ref(method), &scenarios);
+        let results = match single {
+            Ok(results) => results,
+            Err(e) => {
+                println!("{:<58} failed: {}", format!("{:?}", method), e);
+                continue;
+            }
+        };
+        for result in results {
+            let attack = result.attack.as_ref().map_or("none".to_string(), attack_name);
+            let backdoor = result.backdoor_success_rate.map_or("-".to_string(), |r| format!("{:.3}", r));
+            println!(
+                "{:<58} {:<14} {:>9.3} {:>9} {:>9}",
+                format!("{:?}", result.aggregation_method),
+                attack,
+                result.final_accuracy,
+                backdoor,
+                result.rejected_updates
+            );
+        }
+    }
+}
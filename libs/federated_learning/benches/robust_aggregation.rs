@@ -0,0 +1,93 @@
+// Compares exact coordinate-wise trimmed mean / median against a SketchAggregator on a
+// large cohort. The exact methods sort every coordinate when the round closes; the
+// sketches take each update as it arrives, so closing the round only queries them.
+// Also reports the per-update ingest cost and the memory each approach holds.
+//
+// cargo bench -p federated_learning --bench robust_aggregation
+
+use federated_learning::{AggregationEngine, ModelUpdate, SketchAggregator};
+use std::hint::black_box;
+use std::time::Instant;
+
+const CLIENTS: usize = 2_000;
+const DIMENSION: usize = 2_000;
+const SKETCH_SIZE: u32 = 128;
+
+fn update(client: usize) -> ModelUpdate {
+    ModelUpdate {
+        client_id: format!("client_{}", client),
+        round: 1,
+        gradients: (0..DIMENSION).map(|i| (((client * 7919 + i * 104729) % 10007) as f64 - 5003.0) * 1e-4).collect(),
+        weights: Vec::new(),
+        loss: 0.0,
+        accuracy: 0.0,
+        data_size: 100,
+        computation_time: 0.0,
+        communication_cost: 0.0,
+        privacy_budget_used: 0.0,
+        compressed: false,
+        compression_ratio: None,
+        attestation: None,
+        personalized_accuracy: None,
+        sparse_gradients: None,
+        trained_ranges: None,
+        gradient_proof: None,
+    }
+}
+
+fn time<T, F: FnMut() -> T>(mut f: F) -> (f64, T) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed().as_secs_f64(), result)
+}
+
+fn max_abs_diff(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).fold(0.0, f64::max)
+}
+
+fn main() {
+    let engine = AggregationEngine::new();
+    let updates: Vec<ModelUpdate> = (0..CLIENTS).map(update).collect();
+
+    println!(
+        "{} clients x {} coordinates, sketch size {} (rank error bound {:.3})",
+        CLIENTS,
+        DIMENSION,
+        SKETCH_SIZE,
+        AggregationEngine::sketch_rank_error_bound(CLIENTS, SKETCH_SIZE)
+    );
+
+    // Ingest is paid per update while the round is open, off the round-close path
+    let mut streamed = SketchAggregator::new(DIMENSION, SKETCH_SIZE);
+    let (ingest_secs, _) = time(|| {
+        for u in &updates {
+            streamed.add(black_box(&u.gradients)).unwrap();
+        }
+    });
+    println!(
+        "ingest        {:>8.3} ms per update   memory exact {:>6.1} MB   sketch {:>6.1} MB",
+        ingest_secs * 1e3 / CLIENTS as f64,
+        (CLIENTS * DIMENSION * 8) as f64 / 1e6,
+        (streamed.retained_values() * 8) as f64 / 1e6
+    );
+
+    let (exact_secs, exact) = time(|| engine.median_aggregation(black_box(&updates)).unwrap());
+    let (close_secs, approx) = time(|| streamed.median().unwrap());
+    println!(
+        "median        round close exact {:>8.1} ms   sketch {:>8.1} ms   speedup {:>5.1}x   max abs error {:.2e}",
+        exact_secs * 1e3,
+        close_secs * 1e3,
+        exact_secs / close_secs,
+        max_abs_diff(&exact, &approx)
+    );
+
+    let (exact_secs, exact) = time(|| engine.trimmed_mean_aggregation(black_box(&updates), 0.2).unwrap());
+    let (close_secs, approx) = time(|| streamed.trimmed_mean(0.2).unwrap());
+    println!(
+        "trimmed mean  round close exact {:>8.1} ms   sketch {:>8.1} ms   speedup {:>5.1}x   max abs error {:.2e}",
+        exact_secs * 1e3,
+        close_secs * 1e3,
+        exact_secs / close_secs,
+        max_abs_diff(&exact, &approx)
+    );
+}
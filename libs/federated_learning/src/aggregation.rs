@@ -1,2 +1,468 @@
-// Placeholder for aggregation module
-pub struct AggregationPlaceholder;
\ No newline at end of file
+// Robust aggregation beyond the basic methods in lib.rs: Multi-Krum, Bulyan, FoolsGold, the
+// geometric median, and sketch-based
+// approximations of trimmed mean and median. Exact trimmed mean and median keep every client
+// update until the round closes and then sort each coordinate across all clients.
+// A SketchAggregator instead folds each update into fixed-size per-coordinate
+// quantile sketches as it arrives, so memory no longer grows with the number of
+// clients and closing the round only has to query the sketches. It only pays off
+// when updates are streamed in: sketching a batch already held in memory is slower
+// than sorting it, so there is no batch aggregation method built on it.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SketchAggregator {
+    sketch_size: u32,
+    sketches: Vec<QuantileSketch>,
+    num_updates: usize,
+}
+
+impl SketchAggregator {
+    pub fn new(dimension: usize, sketch_size: u32) -> Self {
+        SketchAggregator {
+            sketch_size,
+            sketches: vec![QuantileSketch::new(sketch_size as usize); dimension],
+            num_updates: 0,
+        }
+    }
+
+    pub fn num_updates(&self) -> usize {
+        self.num_updates
+    }
+
+    pub fn add(&mut self, gradients: &[f64]) -> Result<(), String> {
+        if gradients.len() != self.sketches.len() {
+            return Err(format!(
+                "Gradient size mismatch: expected {}, got {}",
+                self.sketches.len(),
+                gradients.len()
+            ));
+        }
+        for (sketch, &value) in self.sketches.iter_mut().zip(gradients.iter()) {
+            sketch.insert(value);
+        }
+        self.num_updates += 1;
+        Ok(())
+    }
+
+    // Combines partial aggregates, e.g. from regional sub-aggregators
+    pub fn merge(&mut self, other: &SketchAggregator) -> Result<(), String> {
+        if other.sketches.len() != self.sketches.len() {
+            return Err("Cannot merge sketch aggregators of different dimensions".to_string());
+        }
+        for (sketch, other_sketch) in self.sketches.iter_mut().zip(other.sketches.iter()) {
+            sketch.merge(other_sketch);
+        }
+        self.num_updates += other.num_updates;
+        Ok(())
+    }
+
+    pub fn median(&self) -> Result<Vec<f64>, String> {
+        self.per_coordinate(|sketch| sketch.median())
+    }
+
+    // Same trimming as the exact trimmed mean: trim_ratio / 2 of the clients per side
+    pub fn trimmed_mean(&self, trim_ratio: f64) -> Result<Vec<f64>, String> {
+        let trim_count = ((self.num_updates as f64 * trim_ratio) / 2.0).floor() as u64;
+        self.per_coordinate(|sketch| sketch.trimmed_mean(trim_count))
+    }
+
+    // Values held across all sketches; bounded by the sketch size, not the clients
+    pub fn retained_values(&self) -> usize {
+        self.sketches.iter().map(|s| s.retained()).sum()
+    }
+
+    // Largest measured rank error over all coordinates, as a fraction of clients
+    pub fn rank_error_bound(&self) -> f64 {
+        self.sketches.iter().map(|s| s.rank_error_bound()).fold(0.0, f64::max)
+    }
+
+    fn per_coordinate<F>(&self, statistic: F) -> Result<Vec<f64>, String>
+    where
+        F: Fn(&QuantileSketch) -> Option<f64>,
+    {
+        if self.num_updates == 0 {
+            return Err("No updates to aggregate".to_string());
+        }
+        self.sketches
+            .iter()
+            .enumerate()
+            .map(|(i, sketch)| statistic(sketch).ok_or_else(|| format!("No finite values at coordinate {}", i)))
+            .collect()
+    }
+}
+
+impl AggregationEngine {
+    // Worst-case rank error, as a fraction of clients, for `num_clients` inputs
+    pub fn sketch_rank_error_bound(num_clients: usize, sketch_size: u32) -> f64 {
+        let capacity = sketch_size.max(2) as f64;
+        if num_clients as f64 <= capacity {
+            return 0.0;
+        }
+        let levels = (num_clients as f64 / capacity).log2().ceil() + 1.0;
+        levels / capacity
+    }
+}
+
+impl AggregationEngine {
+    // Multi-Krum (Blanchard et al., 2017): scores every update by Krum once and averages
+    // the m best. Taking m = n - f, each update is scored over its m - 2 nearest
+    // neighbours. The mean is unweighted so a client cannot buy influence by
+    // over-reporting its data size.
+    pub fn multi_krum_aggregation(&self, updates: &[ModelUpdate], m: u32) -> Result<Vec<f64>, String> {
+        let n = updates.len();
+        let m = m as usize;
+        if m == 0 || m > n {
+            return Err(format!("Multi-Krum needs 1 <= m <= {} updates, got m = {}", n, m));
+        }
+        let dimension = check_dimensions(updates)?;
+        let distances = self.pairwise_distances(updates);
+
+        let candidates: Vec<usize> = (0..n).collect();
+        let scores = krum_scores(&distances, &candidates, m.saturating_sub(2).max(1));
+        let mut ranked: Vec<usize> = candidates;
+        ranked.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(a.cmp(&b)));
+
+        let mut aggregated = vec![0.0; dimension];
+        for &i in ranked.iter().take(m) {
+            kernels::axpy(1.0 / m as f64, &updates[i].gradients, &mut aggregated);
+        }
+        Ok(aggregated)
+    }
+
+    fn pairwise_distances(&self, updates: &[ModelUpdate]) -> Vec<Vec<f64>> {
+        let n = updates.len();
+        let mut distances = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let d = self.compute_euclidean_distance(&updates[i].gradients, &updates[j].gradients);
+                distances[i][j] = d;
+                distances[j][i] = d;
+            }
+        }
+        distances
+    }
+}
+
+fn check_dimensions(updates: &[ModelUpdate]) -> Result<usize, String> {
+    let dimension = updates.first().ok_or_else(|| "No updates to aggregate".to_string())?.gradients.len();
+    if updates.iter().any(|u| u.gradients.len() != dimension) {
+        return Err("Gradient size mismatch between updates".to_string());
+    }
+    Ok(dimension)
+}
+
+// Krum score of each candidate: summed distance to its `neighbours` nearest other candidates
+fn krum_scores(distances: &[Vec<f64>], candidates: &[usize], neighbours: usize) -> Vec<f64> {
+    candidates
+        .iter()
+        .map(|&i| {
+            let mut row: Vec<f64> = candidates.iter().filter(|&&j| j != i).map(|&j| distances[i][j]).collect();
+            row.sort_unstable_by(|a, b| a.total_cmp(b));
+            row.iter().take(neighbours).sum()
+        })
+        .collect()
+}
+
+// Per-client running sum of every gradient each client has sent, which FoolsGold
+// compares across clients
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FoolsGoldState {
+    pub histories: HashMap<String, Vec<f64>>,
+}
+
+impl FoolsGoldState {
+    pub fn new() -> Self {
+        FoolsGoldState::default()
+    }
+}
+
+impl AggregationEngine {
+    // FoolsGold (Fung et al., 2020). Sybils pushing the same poisoned objective keep
+    // sending similar gradients, so their histories stay unusually aligned while
+    // honest hospitals' drift apart with their different data. Each client is weighted
+    // by one minus its highest cosine similarity to another history, with the paper's
+    // pardoning of honest clients that merely resemble one outlier and its logit
+    // stretch of the weights (κ = 1).
+    pub fn foolsgold_aggregation(&self, state: &mut FoolsGoldState, updates: &[ModelUpdate]) -> Result<Vec<f64>, String> {
+        let dimension = check_dimensions(updates)?;
+        for update in updates {
+            let history = state.histories.entry(update.client_id.clone()).or_insert_with(|| vec![0.0; dimension]);
+            // A change of model size restarts that client's history
+            if history.len() != dimension {
+                *history = vec![0.0; dimension];
+            }
+            kernels::axpy(1.0, &update.gradients, history);
+        }
+
+        let n = updates.len();
+        let histories: Vec<&Vec<f64>> = updates.iter().map(|u| &state.histories[&u.client_id]).collect();
+        let norms: Vec<f64> = histories.iter().map(|h| h.iter().map(|v| v * v).sum::<f64>().sqrt()).collect();
+        let mut similarity = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let dot: f64 = histories[i].iter().zip(histories[j].iter()).map(|(a, b)| a * b).sum();
+                let cs = if norms[i] > 0.0 && norms[j] > 0.0 { dot / (norms[i] * norms[j]) } else { 0.0 };
+                similarity[i][j] = cs;
+                similarity[j][i] = cs;
+            }
+        }
+
+        let max_similarity: Vec<f64> =
+            (0..n).map(|i| (0..n).filter(|&j| j != i).map(|j| similarity[i][j]).fold(f64::MIN, f64::max)).collect();
+        let mut weights: Vec<f64> = (0..n)
+            .map(|i| {
+                let pardoned = (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| {
+                        if max_similarity[j] > max_similarity[i] && max_similarity[j] > 0.0 {
+                            similarity[i][j] * max_similarity[i] / max_similarity[j]
+                        } else {
+                            similarity[i][j]
+                        }
+                    })
+                    .fold(f64::MIN, f64::max);
+                if n == 1 { 1.0 } else { (1.0 - pardoned).clamp(0.0, 1.0) }
+            })
+            .collect();
+
+        let top = weights.iter().cloned().fold(0.0, f64::max);
+        if top <= 0.0 {
+            return Err("FoolsGold assigned zero weight to every client".to_string());
+        }
+        for weight in &mut weights {
+            let scaled = *weight / top;
+            *weight = if scaled >= 1.0 { 1.0 } else if scaled <= 0.0 { 0.0 } else { ((scaled / (1.0 - scaled)).ln() + 0.5).clamp(0.0, 1.0) };
+        }
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err("FoolsGold assigned zero weight to every client".to_string());
+        }
+        let mut aggregated = vec![0.0; dimension];
+        for (update, weight) in updates.iter().zip(weights.iter()) {
+            kernels::axpy(weight / total, &update.gradients, &mut aggregated);
+        }
+        Ok(aggregated)
+    }
+}
+
+impl AggregationEngine {
+    // signSGD with majority vote (Bernstein et al., 2019). Every client gets one vote
+    // per coordinate regardless of data size, which also bounds what a minority of
+    // faulty clients can do. Tied coordinates stay where they are.
+    pub fn sign_majority_vote(&self, updates: &[ModelUpdate], server_learning_rate: f64, global_weights: &[f64]) -> Result<Vec<f64>, String> {
+        let dimension = check_dimensions(updates)?;
+        if !global_weights.is_empty() && global_weights.len() != dimension {
+            return Err("Global model and update dimensions differ".to_string());
+        }
+
+        let mut votes = vec![0i64; dimension];
+        for update in updates {
+            for (vote, &g) in votes.iter_mut().zip(update.gradients.iter()) {
+                if g > 0.0 {
+                    *vote += 1;
+                } else if g < 0.0 {
+                    *vote -= 1;
+                }
+            }
+        }
+
+        Ok((0..dimension)
+            .map(|i| {
+                let global = global_weights.get(i).copied().unwrap_or(0.0);
+                global - server_learning_rate * votes[i].signum() as f64
+            })
+            .collect())
+    }
+}
+
+// Floor on distances in the smoothed Weiszfeld step, so an iterate landing on a
+// client's update does not divide by zero
+const WEISZFELD_SMOOTHING: f64 = 1e-6;
+
+impl AggregationEngine {
+    // Robust Federated Aggregation (Pillutla et al., 2022): the data-size weighted
+    // geometric median, found with smoothed Weiszfeld iterations starting from the
+    // weighted mean. Unlike the coordinate-wise median it is rotation invariant and
+    // keeps correlated coordinates together, and it tolerates up to half the weight
+    // being arbitrary.
+    pub fn geometric_median_aggregation(&self, updates: &[ModelUpdate], max_iterations: u32, tolerance: f64) -> Result<Vec<f64>, String> {
+        check_dimensions(updates)?;
+        let total_weight: f64 = updates.iter().map(|u| u.data_size as f64).sum();
+        let alphas: Vec<f64> = if total_weight > 0.0 {
+            updates.iter().map(|u| u.data_size as f64 / total_weight).collect()
+        } else {
+            vec![1.0 / updates.len() as f64; updates.len()]
+        };
+
+        let objective = |z: &[f64]| -> f64 {
+            updates.iter().zip(alphas.iter()).map(|(u, a)| a * self.compute_euclidean_distance(z, &u.gradients)).sum()
+        };
+
+        let mut median = self.weighted_average(updates)?;
+        let mut previous = objective(&median);
+        for _ in 0..max_iterations {
+            let betas: Vec<f64> = updates
+                .iter()
+                .zip(alphas.iter())
+                .map(|(u, a)| a / self.compute_euclidean_distance(&median, &u.gradients).max(WEISZFELD_SMOOTHING))
+                .collect();
+            let beta_total: f64 = betas.iter().sum();
+            let mut next = vec![0.0; median.len()];
+            for (update, beta) in updates.iter().zip(betas.iter()) {
+                kernels::axpy(beta / beta_total, &update.gradients, &mut next);
+            }
+            median = next;
+
+            let current = objective(&median);
+            let converged = (previous - current).abs() <= tolerance * current.max(f64::MIN_POSITIVE);
+            previous = current;
+            if converged {
+                break;
+            }
+        }
+        Ok(median)
+    }
+}
+
+// Bulyan (El Mhamdi et al., 2018) tolerates f Byzantine clients out of n >= 4f + 3.
+// It repeatedly picks the Krum winner among the remaining updates until n - 2f are
+// selected, then for each coordinate averages the n - 4f selected values closest to
+// their median, so an attacker can neither win selection with one large coordinate
+// nor shift any coordinate by more than the honest spread.
+impl AggregationEngine {
+    pub fn bulyan_aggregation(&self, updates: &[ModelUpdate], f: u32) -> Result<Vec<f64>, String> {
+        let n = updates.len();
+        let f = f as usize;
+        if n < 4 * f + 3 {
+            return Err(format!("Bulyan with f = {} needs at least {} updates, got {}", f, 4 * f + 3, n));
+        }
+        let dimension = check_dimensions(updates)?;
+        let distances = self.pairwise_distances(updates);
+
+        let mut remaining: Vec<usize> = (0..n).collect();
+        let mut selected = Vec::with_capacity(n - 2 * f);
+        while selected.len() < n - 2 * f {
+            let scores = krum_scores(&distances, &remaining, remaining.len() - f - 2);
+            let winner = scores
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(position, _)| position)
+                .ok_or_else(|| "No updates left to select".to_string())?;
+            selected.push(remaining.swap_remove(winner));
+        }
+
+        let beta = n - 4 * f;
+        let mut aggregated = vec![0.0; dimension];
+        let mut values = Vec::with_capacity(selected.len());
+        for (coordinate, output) in aggregated.iter_mut().enumerate() {
+            values.clear();
+            values.extend(selected.iter().map(|&i| updates[i].gradients[coordinate]));
+            values.sort_unstable_by(|a, b| a.total_cmp(b));
+            let middle = values.len() / 2;
+            let median = if values.len().is_multiple_of(2) { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] };
+            values.sort_unstable_by(|a, b| (a - median).abs().total_cmp(&(b - median).abs()));
+            *output = values.iter().take(beta).sum::<f64>() / beta as f64;
+        }
+
+        Ok(aggregated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(gradients: Vec<f64>) -> ModelUpdate {
+        ModelUpdate::for_test("", gradients, 1)
+    }
+
+    #[test]
+    fn test_bulyan_ignores_byzantine_updates() {
+        // Eight honest hospitals near (1, -1) and three attackers, f = 2 needs n >= 11
+        let mut updates: Vec<ModelUpdate> =
+            (0..8).map(|i| update(vec![1.0 + 0.01 * i as f64, -1.0 - 0.01 * i as f64])).collect();
+        updates.push(update(vec![100.0, 100.0]));
+        updates.push(update(vec![1.0, 50.0]));
+        updates.push(update(vec![-40.0, -1.0]));
+
+        let engine = AggregationEngine::new();
+        let aggregated = engine.bulyan_aggregation(&updates, 2).unwrap();
+        assert!((aggregated[0] - 1.035).abs() < 0.05);
+        assert!((aggregated[1] + 1.035).abs() < 0.05);
+
+        assert!(engine.bulyan_aggregation(&updates[..10], 2).is_err());
+    }
+
+    #[test]
+    fn test_foolsgold_down_weights_sybils() {
+        let engine = AggregationEngine::new();
+        let mut state = FoolsGoldState::new();
+        let client = |id: &str, gradients: Vec<f64>| ModelUpdate { client_id: id.to_string(), ..update(gradients) };
+
+        let mut aggregated = Vec::new();
+        for round in 0..3 {
+            let r = round as f64;
+            let updates = vec![
+                client("honest_a", vec![1.0, 0.2 * r, 0.0]),
+                client("honest_b", vec![0.0, 1.0, 0.3 * r]),
+                client("honest_c", vec![0.4 * r, 0.0, 1.0]),
+                // Two sybils sending the same poisoned direction
+                client("sybil_1", vec![-5.0, -5.0, -5.0]),
+                client("sybil_2", vec![-5.0, -5.0, -5.001]),
+            ];
+            aggregated = engine.foolsgold_aggregation(&mut state, &updates).unwrap();
+        }
+
+        // The sybils' history similarity zeroes their weight, leaving a positive honest mean
+        assert!(aggregated.iter().all(|&v| v > 0.0));
+        assert_eq!(state.histories.len(), 5);
+    }
+
+    #[test]
+    fn test_sign_majority_vote_steps_against_voted_sign() {
+        let engine = AggregationEngine::new();
+        let gradients = [vec![0.5, -2.0, 0.1, 3.0], vec![0.2, -0.1, -4.0, -1.0], vec![-9.0, -0.3, -0.2, 0.0]];
+        let updates: Vec<ModelUpdate> = gradients
+            .iter()
+            .map(|g| {
+                let (signs, stats) = SignCompressor::compress(g);
+                assert_eq!(stats.compressed_size, 1);
+                update(SignCompressor::decompress(&signs))
+            })
+            .collect();
+
+        // Votes: +1, -3, -1, +1 (zero is sent as +1)
+        let weights = engine.sign_majority_vote(&updates, 0.1, &[1.0, 1.0, 1.0, 1.0]).unwrap();
+        assert_eq!(weights, vec![0.9, 1.1, 1.1, 0.9]);
+    }
+
+    #[test]
+    fn test_geometric_median_resists_outliers() {
+        let engine = AggregationEngine::new();
+        let mut updates: Vec<ModelUpdate> =
+            [[1.0, 1.0], [1.2, 0.8], [0.8, 1.2], [1.1, 1.0], [0.9, 1.0]].iter().map(|g| update(g.to_vec())).collect();
+        updates.push(update(vec![100.0, -100.0]));
+        updates.push(update(vec![-80.0, 90.0]));
+
+        let median = engine.geometric_median_aggregation(&updates, 100, 1e-9).unwrap();
+        assert!((median[0] - 1.0).abs() < 0.2 && (median[1] - 1.0).abs() < 0.2);
+
+        // Without iterations this is the weighted mean, which the outliers drag away
+        let early = engine.geometric_median_aggregation(&updates, 0, 1e-9).unwrap();
+        assert!((early[0] - 1.0).abs() > 1.0);
+    }
+
+    #[test]
+    fn test_multi_krum_averages_best_candidates() {
+        let mut updates: Vec<ModelUpdate> = [1.0, 1.2, 0.8, 1.1].iter().map(|&v| update(vec![v, v])).collect();
+        updates.push(update(vec![25.0, -25.0]));
+
+        let engine = AggregationEngine::new();
+        let aggregated = engine.multi_krum_aggregation(&updates, 4).unwrap();
+        assert!((aggregated[0] - 1.025).abs() < 1e-12 && (aggregated[1] - 1.025).abs() < 1e-12);
+        assert!(engine.multi_krum_aggregation(&updates, 6).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+// Privacy amplification by subsampling. When each round trains on a cohort drawn
+// uniformly at random, a client is only in the round with probability q (cohort size
+// over selectable clients), and nobody outside the coordinator knows which. An
+// (ε, δ)-DP round then costs every selectable client ln(1 + q(e^ε - 1)) and qδ
+// (Balle et al., 2018), much less than ε for small q; in RDP the round is the
+// sampled Gaussian mechanism at rate q, which fixed-size cohorts are accounted as.
+//
+// The flip side is that the saving is paid by everyone: a client left out of the
+// round still incurs the amplified cost, so it is charged to every selectable client
+// and not only to the participants. Amplification only applies to cohorts drawn with
+// `SelectionStrategy::UniformRandom` through `select_clients`; weighted strategies,
+// custom selectors and rounds without a selected cohort are accounted at q = 1.
+
+use crate::*;
+
+// ε of an (ε, δ)-DP mechanism run on a uniform sample at rate `sample_rate`
+pub fn amplified_epsilon(epsilon: f64, sample_rate: f64) -> f64 {
+    let q = sample_rate.clamp(0.0, 1.0);
+    (q * epsilon.exp_m1()).ln_1p()
+}
+
+pub fn amplified_delta(delta: f64, sample_rate: f64) -> f64 {
+    delta * sample_rate.clamp(0.0, 1.0)
+}
+
+impl FederatedLearningCoordinator {
+    // Probability each selectable client had of being in the current round's cohort
+    pub fn round_sampling_rate(&self) -> f64 {
+        if !self.uniform_selection {
+            return 1.0;
+        }
+        self.client_registry
+            .cohort()
+            .filter(|c| c.round == self.global_model.round)
+            .and_then(|c| c.sampling_rate)
+            .unwrap_or(1.0)
+    }
+
+    // Clients a DP round is charged to: with amplification, every selectable client
+    pub(crate) fn privacy_accounted_clients(&self, updates: &[ModelUpdate]) -> Vec<String> {
+        if let Some(participants) = self.shuffled_round_participants() {
+            return participants.to_vec();
+        }
+        let mut clients: Vec<String> = updates.iter().map(|u| u.client_id.clone()).collect();
+        if self.global_model.privacy_metrics.sampling_rate < 1.0 {
+            let selectable = self.client_registry.records().into_iter().filter(|r| r.status != ClientStatus::Dropped);
+            clients.extend(selectable.map(|r| r.client_id));
+            clients.sort();
+            clients.dedup();
+        }
+        clients
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform_cohorts_amplify_the_round_epsilon() {
+        assert!((amplified_epsilon(1.0, 1.0) - 1.0).abs() < 1e-12);
+        assert!((amplified_epsilon(1.0, 0.1) - (0.1 * (1.0f64.exp() - 1.0)).ln_1p()).abs() < 1e-12);
+        assert!((amplified_delta(1e-5, 0.1) - 1e-6).abs() < 1e-18);
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(2).client_fraction(0.2).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_clipping_policy(ClippingPolicy { max_norm: Some(1.0) }).unwrap();
+        for i in 0..10 {
+            coordinator.register_client(&format!("site_{}", i), 100);
+        }
+        let cohort = coordinator.select_clients().unwrap();
+        assert_eq!(coordinator.round_sampling_rate(), 0.2);
+
+        let updates = cohort
+            .iter()
+            .map(|client_id| ModelUpdate {
+                loss: 1.0,
+                accuracy: 0.5,
+                ..ModelUpdate::for_test(client_id, vec![0.1, 0.1], 100)
+            })
+            .collect();
+        coordinator.execute_round(updates).unwrap();
+
+        // Every site pays the amplified ε, whether or not it was drawn
+        let report = coordinator.get_privacy_report();
+        let round_epsilon = amplified_epsilon(0.1, 0.2);
+        assert_eq!((report.sampling_rate, report.effective_epsilon_per_round), (0.2, round_epsilon));
+        assert_eq!(report.client_privacy_usage.len(), 10);
+        assert!(report.client_privacy_usage.values().all(|&e| (e - round_epsilon).abs() < 1e-12));
+
+        // A custom selector gives no guarantee about how the cohort was drawn
+        coordinator.set_client_selector(Box::new(SelectionStrategy::UniformRandom));
+        coordinator.select_clients().unwrap();
+        assert_eq!(coordinator.round_sampling_rate(), 1.0);
+    }
+}
@@ -0,0 +1,284 @@
+// Federated analytics: simple consortium statistics without training a model. Each
+// hospital computes a (sum, count) pair for the query over its own records, adds
+// Laplace noise locally so the coordinator only ever sees ε-DP values, and the
+// coordinator combines the noisy pairs into a consortium-wide estimate. The per-site
+// spend in the result is what the privacy_engine canister has to be charged.
+// Independently of the noise, a result resting on a small cell of patients is
+// suppressed: its values are withheld and only the privacy spend is reported.
+
+use crate::*;
+use crate::features::{condition_codes, patient_age, references_patient};
+use medical_data::suppression::SMALL_CELL_THRESHOLD;
+use medical_data::{ConditionOnset, MedicalDataset};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AnalyticsQuery {
+    // Share of patients with at least one condition carrying `code`
+    Prevalence { code: String },
+    // Mean age at the earliest recorded diagnosis of `code`; ages are clamped to
+    // [0, max_age], which bounds one patient's influence on the sum
+    MeanAgeAtDiagnosis { code: String, max_age: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LocalAggregate {
+    pub site_id: String,
+    pub query: AnalyticsQuery,
+    pub noisy_sum: f64,
+    pub noisy_count: f64,
+    // Laplace scales used for the sum and the count
+    pub sum_noise_scale: f64,
+    pub count_noise_scale: f64,
+    pub epsilon: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AnalyticsResult {
+    pub query: AnalyticsQuery,
+    pub estimate: f64,
+    // Standard error contributed by the DP noise alone (delta method)
+    pub noise_std_error: f64,
+    pub noisy_sum: f64,
+    pub noisy_count: f64,
+    pub sites: usize,
+    // ε charged to each contributing site
+    pub privacy_spend: Vec<(String, f64)>,
+    // When set, estimate, noisy_sum, noisy_count and noise_std_error are zeroed
+    pub suppressed: bool,
+}
+
+impl AnalyticsQuery {
+    // Stable identifier for audit logs and budget accounting
+    pub fn query_id(&self) -> String {
+        match self {
+            AnalyticsQuery::Prevalence { code } => format!("prevalence:{}", code),
+            AnalyticsQuery::MeanAgeAtDiagnosis { code, max_age } => format!("mean_age_at_diagnosis:{}:{}", code, max_age),
+        }
+    }
+
+    // How much adding or removing one patient can change (sum, count)
+    fn sensitivities(&self) -> (f64, f64) {
+        match self {
+            AnalyticsQuery::Prevalence { .. } => (1.0, 1.0),
+            AnalyticsQuery::MeanAgeAtDiagnosis { max_age, .. } => (*max_age, 1.0),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            AnalyticsQuery::MeanAgeAtDiagnosis { max_age, .. } if !(max_age.is_finite() && *max_age > 0.0) => {
+                Err("max_age must be positive".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Patient counts the result rests on: the cases and the rest of the population for
+    // a prevalence, the diagnosed patients for a mean age
+    fn cells(&self, sum: f64, count: f64) -> Vec<f64> {
+        match self {
+            AnalyticsQuery::Prevalence { .. } => vec![sum, count - sum],
+            AnalyticsQuery::MeanAgeAtDiagnosis { .. } => vec![count],
+        }
+    }
+
+    // Exact (sum, count) over a site's records, before any noise
+    fn exact_aggregate(&self, dataset: &MedicalDataset) -> (f64, f64) {
+        match self {
+            AnalyticsQuery::Prevalence { code } => {
+                let cases = dataset
+                    .patients
+                    .iter()
+                    .filter(|patient| {
+                        dataset.conditions.iter().any(|c| {
+                            references_patient(&c.subject.reference, &patient.id)
+                                && condition_codes(c).iter().any(|c| c == code)
+                        })
+                    })
+                    .count();
+                (cases as f64, dataset.patients.len() as f64)
+            }
+            AnalyticsQuery::MeanAgeAtDiagnosis { code, max_age } => {
+                let mut sum = 0.0;
+                let mut count = 0.0;
+                for patient in &dataset.patients {
+                    let age = dataset
+                        .conditions
+                        .iter()
+                        .filter(|c| {
+                            references_patient(&c.subject.reference, &patient.id)
+                                && condition_codes(c).iter().any(|c| c == code)
+                        })
+                        .filter_map(|c| match &c.onset {
+                            Some(ConditionOnset::Age(quantity)) => quantity.value,
+                            Some(ConditionOnset::DateTime(date)) => patient_age(patient, year_of(date)?),
+                            _ => patient_age(patient, year_of(c.recorded_date.as_deref()?)?),
+                        })
+                        .fold(None, |earliest: Option<f64>, age| Some(earliest.map_or(age, |e| e.min(age))));
+                    if let Some(age) = age {
+                        sum += age.clamp(0.0, *max_age);
+                        count += 1.0;
+                    }
+                }
+                (sum, count)
+            }
+        }
+    }
+}
+
+impl LocalAggregate {
+    // Site side: ε is split evenly between the sum and the count, which both
+    // depend on the same patients and so compose sequentially
+    pub fn compute(site_id: &str, query: &AnalyticsQuery, dataset: &MedicalDataset, epsilon: f64) -> Result<Self, String> {
+        query.validate()?;
+        if !(epsilon.is_finite() && epsilon > 0.0) {
+            return Err("Analytics ε must be positive".to_string());
+        }
+
+        let (sum, count) = query.exact_aggregate(dataset);
+        let (sum_sensitivity, count_sensitivity) = query.sensitivities();
+        let sum_noise_scale = sum_sensitivity / (epsilon / 2.0);
+        let count_noise_scale = count_sensitivity / (epsilon / 2.0);
+
+        Ok(LocalAggregate {
+            site_id: site_id.to_string(),
+            query: query.clone(),
+            noisy_sum: sum + sample_laplace(sum_noise_scale),
+            noisy_count: count + sample_laplace(count_noise_scale),
+            sum_noise_scale,
+            count_noise_scale,
+            epsilon,
+        })
+    }
+}
+
+// Coordinator side
+pub fn combine_local_aggregates(query: &AnalyticsQuery, aggregates: &[LocalAggregate]) -> Result<AnalyticsResult, String> {
+    combine_local_aggregates_with_threshold(query, aggregates, SMALL_CELL_THRESHOLD)
+}
+
+// Noisy cells are suppressed below the threshold, zero included, since the noise
+// makes a true zero indistinguishable from a handful of patients
+pub fn combine_local_aggregates_with_threshold(
+    query: &AnalyticsQuery,
+    aggregates: &[LocalAggregate],
+    threshold: u64,
+) -> Result<AnalyticsResult, String> {
+    if aggregates.is_empty() {
+        return Err("No site aggregates to combine".to_string());
+    }
+    if let Some(other) = aggregates.iter().find(|a| &a.query != query) {
+        return Err(format!("Site {} answered {} instead of {}", other.site_id, other.query.query_id(), query.query_id()));
+    }
+    let mut sites: Vec<&str> = aggregates.iter().map(|a| a.site_id.as_str()).collect();
+    sites.sort_unstable();
+    if sites.windows(2).any(|w| w[0] == w[1]) {
+        return Err("Each site may contribute one aggregate per query".to_string());
+    }
+
+    let sum: f64 = aggregates.iter().map(|a| a.noisy_sum).sum();
+    let count: f64 = aggregates.iter().map(|a| a.noisy_count).sum();
+    if count <= 0.0 {
+        return Err("Combined noisy count is not positive; too few records for this ε".to_string());
+    }
+
+    let mut estimate = sum / count;
+    if let AnalyticsQuery::Prevalence { .. } = query {
+        estimate = estimate.clamp(0.0, 1.0);
+    }
+
+    // Laplace(b) has variance 2b²; Var(S/C) ≈ Var(S)/C² + S²·Var(C)/C⁴
+    let sum_variance: f64 = aggregates.iter().map(|a| 2.0 * a.sum_noise_scale.powi(2)).sum();
+    let count_variance: f64 = aggregates.iter().map(|a| 2.0 * a.count_noise_scale.powi(2)).sum();
+    let noise_std_error = (sum_variance / count.powi(2) + sum.powi(2) * count_variance / count.powi(4)).sqrt();
+
+    let privacy_spend = aggregates.iter().map(|a| (a.site_id.clone(), a.epsilon)).collect();
+    if query.cells(sum, count).iter().any(|&cell| cell < threshold as f64) {
+        return Ok(AnalyticsResult {
+            query: query.clone(),
+            estimate: 0.0,
+            noise_std_error: 0.0,
+            noisy_sum: 0.0,
+            noisy_count: 0.0,
+            sites: aggregates.len(),
+            privacy_spend,
+            suppressed: true,
+        });
+    }
+
+    Ok(AnalyticsResult {
+        query: query.clone(),
+        estimate,
+        noise_std_error,
+        noisy_sum: sum,
+        noisy_count: count,
+        sites: aggregates.len(),
+        privacy_spend,
+        suppressed: false,
+    })
+}
+
+fn year_of(date: &str) -> Option<i32> {
+    date.get(..4)?.parse().ok()
+}
+
+fn sample_laplace(scale: f64) -> f64 {
+    use rand::Rng;
+    let u: f64 = rand::thread_rng().gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::*;
+
+    fn site(site_id: &str, diabetic: usize, total: usize) -> MedicalDataset {
+        let mut dataset = MedicalDataset::new(site_id.to_string(), site_id.to_string(), String::new());
+        for i in 0..total {
+            let id = format!("{}-{}", site_id, i);
+            let mut patient = Patient::new(id.clone());
+            patient.set_birth_date("1960-01-01".to_string());
+            dataset.patients.push(patient);
+            if i < diabetic {
+                let mut condition = Condition::new(format!("c{}", i), create_reference(&format!("Patient/{}", id), None));
+                condition.set_code(CodeableConcept::icd10("E11").unwrap());
+                condition.onset = Some(ConditionOnset::DateTime(format!("{}-06-01", 2000 + i)));
+                dataset.conditions.push(condition);
+            }
+        }
+        dataset
+    }
+
+    #[test]
+    fn test_combined_estimates_match_pooled_data() {
+        // A very large ε makes the noise negligible
+        let epsilon = 1e9;
+        let sites = [site("a", 10, 40), site("b", 30, 60)];
+
+        let prevalence = AnalyticsQuery::Prevalence { code: "E11".to_string() };
+        let aggregates: Vec<LocalAggregate> =
+            sites.iter().map(|d| LocalAggregate::compute(&d.id, &prevalence, d, epsilon).unwrap()).collect();
+        let result = combine_local_aggregates(&prevalence, &aggregates).unwrap();
+        assert!((result.estimate - 0.4).abs() < 1e-6);
+        assert_eq!(result.privacy_spend.len(), 2);
+
+        // Diagnosed at 40..49 in a and 40..69 in b
+        let age = AnalyticsQuery::MeanAgeAtDiagnosis { code: "E11".to_string(), max_age: 120.0 };
+        let aggregates: Vec<LocalAggregate> =
+            sites.iter().map(|d| LocalAggregate::compute(&d.id, &age, d, epsilon).unwrap()).collect();
+        let result = combine_local_aggregates(&age, &aggregates).unwrap();
+        assert!((result.estimate - (445.0 + 1635.0) / 40.0).abs() < 1e-6);
+
+        assert!(combine_local_aggregates(&prevalence, &aggregates).is_err());
+
+        // Three diabetics in all: the prevalence is withheld, the spend still reported
+        let small = [site("a", 1, 40), site("b", 2, 60)];
+        let aggregates: Vec<LocalAggregate> =
+            small.iter().map(|d| LocalAggregate::compute(&d.id, &prevalence, d, epsilon).unwrap()).collect();
+        let result = combine_local_aggregates(&prevalence, &aggregates).unwrap();
+        assert!(result.suppressed);
+        assert_eq!((result.estimate, result.noisy_sum), (0.0, 0.0));
+        assert_eq!(result.privacy_spend.len(), 2);
+    }
+}
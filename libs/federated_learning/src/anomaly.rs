@@ -0,0 +1,241 @@
+// Statistical anomaly detection for a round's client updates, on top of the checks in
+// `validate_client_updates`. Three tests, each optional:
+//
+//   direction:   cosine similarity of the update to the previous round's aggregated
+//                update; sign-flipping and model-replacement attacks point away from it
+//   coordinates: per-coordinate robust z-scores, |x - median| / (1.4826 · MAD) over the
+//                round's updates; a single poisoned coordinate stands out even when the
+//                update's norm is unremarkable
+//   loss:        the same robust z-score of the reported training loss
+//
+// Medians and MADs rather than means and standard deviations, so a few attackers
+// cannot shift the baseline they are measured against. The round-level tests need
+// `min_updates` candidates to mean anything and are skipped below that. Every update
+// dropped during validation, for these or the existing reasons, is listed in the
+// coordinator's `RoundDiagnostics`.
+
+use crate::*;
+
+// Scales the MAD to the standard deviation of a normal distribution
+const MAD_TO_STD: f64 = 1.4826;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AnomalyPolicy {
+    pub min_cosine_similarity: Option<f64>,
+    pub max_coordinate_z: Option<f64>,
+    pub max_loss_z: Option<f64>,
+    pub min_updates: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RejectionReason {
+    Unauthorized,
+    WrongRound { round: u64 },
+    NotInCohort,
+    MissingOptOutReport,
+    AttestationRejected { reason: String },
+    ProofRejected { reason: String },
+    InvalidGradients,
+    InvalidSparseGradients,
+    InvalidPartialUpdate { reason: String },
+    // SCAFFOLD needs the client's full local model to refresh its control variate
+    InvalidLocalWeights { received: usize },
+    InversionRisk { score: f64 },
+    DirectionOutlier { cosine_similarity: f64 },
+    CoordinateOutlier { coordinate: usize, z_score: f64 },
+    LossOutlier { z_score: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpdateRejection {
+    pub client_id: String,
+    pub reason: RejectionReason,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RoundDiagnostics {
+    pub round: u64,
+    pub received: usize,
+    pub accepted: usize,
+    pub rejections: Vec<UpdateRejection>,
+}
+
+impl Default for AnomalyPolicy {
+    fn default() -> Self {
+        AnomalyPolicy { min_cosine_similarity: Some(-0.5), max_coordinate_z: Some(6.0), max_loss_z: Some(4.0), min_updates: 5 }
+    }
+}
+
+impl AnomalyPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_cosine_similarity.is_some_and(|c| !(-1.0..=1.0).contains(&c)) {
+            return Err("Minimum cosine similarity must be in [-1, 1]".to_string());
+        }
+        if [self.max_coordinate_z, self.max_loss_z].iter().flatten().any(|z| !(z.is_finite() && *z > 0.0)) {
+            return Err("z-score thresholds must be positive".to_string());
+        }
+        if self.min_updates < 3 {
+            return Err("Anomaly statistics need at least 3 updates".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+// Robust z-score of every value; zero spread scores any deviation as infinite
+pub fn robust_z_scores(values: &[f64]) -> Vec<f64> {
+    let center = median(&mut values.to_vec());
+    let mut deviations: Vec<f64> = values.iter().map(|v| (v - center).abs()).collect();
+    let spread = MAD_TO_STD * median(&mut deviations);
+    values
+        .iter()
+        .map(|v| match (v - center).abs() {
+            0.0 => 0.0,
+            _ if spread == 0.0 => f64::INFINITY,
+            d => d / spread,
+        })
+        .collect()
+}
+
+pub fn cosine_similarity(x: &[f64], y: &[f64]) -> f64 {
+    let norms = kernels::l2_norm(x) * kernels::l2_norm(y);
+    if norms == 0.0 {
+        return 0.0;
+    }
+    x.iter().zip(y).map(|(a, b)| a * b).sum::<f64>() / norms
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_anomaly_policy(&mut self, policy: Option<AnomalyPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.anomaly_policy = policy;
+        Ok(())
+    }
+
+    // Updates received, accepted and rejected (with why) in the latest validation
+    pub fn get_round_diagnostics(&self) -> &RoundDiagnostics {
+        &self.round_diagnostics
+    }
+
+    pub(crate) fn reject_update(&mut self, client_id: &str, reason: RejectionReason) {
+        self.round_diagnostics.rejections.push(UpdateRejection { client_id: client_id.to_string(), reason });
+    }
+
+    // Per-update direction test against the previous aggregated update
+    pub(crate) fn direction_anomaly(&self, update: &ModelUpdate) -> Option<RejectionReason> {
+        let threshold = self.anomaly_policy.as_ref()?.min_cosine_similarity?;
+        if self.previous_global_update.is_empty() {
+            return None;
+        }
+        let gradients = update.sparse_gradients.as_ref().map(|s| s.to_dense(self.config.model_dimension));
+        let cosine_similarity = cosine_similarity(gradients.as_deref().unwrap_or(&update.gradients), &self.previous_global_update);
+        (cosine_similarity < threshold).then_some(RejectionReason::DirectionOutlier { cosine_similarity })
+    }
+
+    // Round-level coordinate and loss tests; returns the updates that pass
+    pub(crate) fn filter_statistical_anomalies(&mut self, updates: Vec<ModelUpdate>) -> Vec<ModelUpdate> {
+        let policy = match &self.anomaly_policy {
+            Some(policy) if updates.len() >= policy.min_updates => policy.clone(),
+            _ => return updates,
+        };
+
+        let mut reasons: Vec<Option<RejectionReason>> = vec![None; updates.len()];
+        if let Some(max_z) = policy.max_coordinate_z {
+            let dimension = self.config.model_dimension;
+            let rows: Vec<Vec<f64>> = updates
+                .iter()
+                .map(|u| u.sparse_gradients.as_ref().map_or_else(|| u.gradients.clone(), |s| s.to_dense(dimension)))
+                .collect();
+            for coordinate in 0..rows.iter().map(Vec::len).min().unwrap_or(0) {
+                let values: Vec<f64> = rows.iter().map(|row| row[coordinate]).collect();
+                for (i, z_score) in robust_z_scores(&values).into_iter().enumerate() {
+                    let worse = match &reasons[i] {
+                        Some(RejectionReason::CoordinateOutlier { z_score: worst, .. }) => z_score > *worst,
+                        _ => true,
+                    };
+                    if z_score > max_z && worse {
+                        reasons[i] = Some(RejectionReason::CoordinateOutlier { coordinate, z_score });
+                    }
+                }
+            }
+        }
+        if let Some(max_z) = policy.max_loss_z {
+            let losses: Vec<f64> = updates.iter().map(|u| u.loss).collect();
+            for (i, z_score) in robust_z_scores(&losses).into_iter().enumerate() {
+                if z_score > max_z && reasons[i].is_none() {
+                    reasons[i] = Some(RejectionReason::LossOutlier { z_score });
+                }
+            }
+        }
+
+        let mut kept = Vec::with_capacity(updates.len());
+        for (update, reason) in updates.into_iter().zip(reasons) {
+            match reason {
+                Some(reason) => self.reject_update(&update.client_id, reason),
+                None => kept.push(update),
+            }
+        }
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anomalous_updates_are_rejected_with_reasons() {
+        let z = robust_z_scores(&[1.0, 1.1, 0.9, 1.0, 9.0]);
+        assert!(z[4] > 50.0 && z[0] < 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-2.0, 0.0]), -1.0);
+
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(4)
+            .min_clients(3)
+            .privacy_method(PrivacyMethod::None)
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_anomaly_policy(Some(AnomalyPolicy::default())).unwrap();
+        let update = |client_id: &str, round: u64, gradients: Vec<f64>, loss: f64| ModelUpdate {
+            round,
+            loss,
+            accuracy: 0.5,
+            ..ModelUpdate::for_test(client_id, gradients, 100)
+        };
+        let honest = |i: usize, round: u64| {
+            let jitter = i as f64 * 0.01;
+            update(&format!("site_{}", i), round, vec![0.1 + jitter, 0.2 - jitter, 0.1, 0.05 + jitter], 1.0 + jitter)
+        };
+
+        let mut first: Vec<ModelUpdate> = (0..5).map(|i| honest(i, 0)).collect();
+        first.push(update("stale", 7, vec![0.1; 4], 1.0));
+        coordinator.execute_round(first).unwrap();
+        let diagnostics = coordinator.get_round_diagnostics();
+        assert_eq!((diagnostics.received, diagnostics.accepted), (6, 5));
+        assert_eq!(diagnostics.rejections[0].reason, RejectionReason::WrongRound { round: 7 });
+
+        let mut second: Vec<ModelUpdate> = (0..5).map(|i| honest(i, 1)).collect();
+        second.push(update("flipped", 1, vec![-0.1, -0.2, -0.1, -0.05], 1.0));
+        second.push(update("spiked", 1, vec![0.1, 0.2, 3.0, 0.05], 1.0));
+        second.push(update("diverged", 1, vec![0.11, 0.19, 0.1, 0.06], 40.0));
+        coordinator.execute_round(second).unwrap();
+        let rejections = &coordinator.get_round_diagnostics().rejections;
+        let reason = |client_id: &str| rejections.iter().find(|r| r.client_id == client_id).map(|r| r.reason.clone());
+        assert!(matches!(reason("flipped"), Some(RejectionReason::DirectionOutlier { .. })));
+        assert!(matches!(reason("spiked"), Some(RejectionReason::CoordinateOutlier { coordinate: 2, .. })));
+        assert!(matches!(reason("diverged"), Some(RejectionReason::LossOutlier { .. })));
+        assert_eq!(coordinator.get_round_diagnostics().accepted, 5);
+    }
+}
@@ -0,0 +1,213 @@
+// Model artifacts in the safetensors format, so a model trained here loads into
+// PyTorch (`safetensors.torch.load_file`) for local fine-tuning or external
+// validation, and a PyTorch checkpoint can seed or be compared against a federated
+// model. The flat weight vector is split into named tensors by a `ModelSpec`, in
+// state_dict order; without one the model is a single tensor "weights".
+//
+// Provenance travels in the file's string metadata: the spec as JSON under
+// "model_spec", plus "version", "round" and "epsilon_spent". safetensors stores
+// tensors sorted by name, so the spec is also what restores the flattening order;
+// files from elsewhere carry no spec, and the caller must supply one.
+
+use crate::*;
+use safetensors::tensor::{Dtype, SafeTensors, TensorView};
+
+pub const SINGLE_TENSOR_NAME: &str = "weights";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TensorSpec {
+    pub name: String,
+    pub shape: Vec<usize>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ModelSpec {
+    pub architecture: String,
+    // In the order their values appear in the flat weight vector
+    pub tensors: Vec<TensorSpec>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ModelArtifact {
+    pub spec: ModelSpec,
+    pub version: String,
+    pub round: u64,
+    pub epsilon_spent: f64,
+    pub weights: Vec<f64>,
+}
+
+impl TensorSpec {
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ModelSpec {
+    pub fn single_tensor(architecture: &str, dimension: usize) -> Self {
+        ModelSpec {
+            architecture: architecture.to_string(),
+            tensors: vec![TensorSpec { name: SINGLE_TENSOR_NAME.to_string(), shape: vec![dimension] }],
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.tensors.iter().map(TensorSpec::len).sum()
+    }
+
+    pub fn validate(&self, dimension: usize) -> Result<(), String> {
+        let mut names = std::collections::HashSet::new();
+        if self.tensors.iter().any(|t| t.name.is_empty() || !names.insert(&t.name)) {
+            return Err("Tensor names must be unique and non-empty".to_string());
+        }
+        if self.dimension() != dimension {
+            return Err(format!("Model spec covers {} values, model has {}", self.dimension(), dimension));
+        }
+        Ok(())
+    }
+}
+
+fn encode_values(values: &[f64], precision: Precision) -> (Dtype, Vec<u8>) {
+    match PackedVector::pack(values, precision) {
+        PackedVector::F64(v) => (Dtype::F64, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+        PackedVector::F32(v) => (Dtype::F32, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+        PackedVector::F16(v) => (Dtype::F16, v.iter().flat_map(|x| x.to_le_bytes()).collect()),
+    }
+}
+
+fn decode_values(dtype: Dtype, data: &[u8]) -> Result<Vec<f64>, String> {
+    let values = match dtype {
+        Dtype::F64 => data.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap_or_default())).collect(),
+        Dtype::F32 => data.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap_or_default()) as f64).collect(),
+        Dtype::F16 => data.chunks_exact(2).map(|b| F16(u16::from_le_bytes([b[0], b[1]])).to_f64()).collect(),
+        // bfloat16 is the upper half of an f32
+        Dtype::BF16 => data.chunks_exact(2).map(|b| f32::from_bits((u16::from_le_bytes([b[0], b[1]]) as u32) << 16) as f64).collect(),
+        other => return Err(format!("Unsupported tensor dtype {:?}", other)),
+    };
+    Ok(values)
+}
+
+impl ModelArtifact {
+    pub fn new(spec: ModelSpec, version: &str, round: u64, epsilon_spent: f64, weights: Vec<f64>) -> Result<Self, String> {
+        spec.validate(weights.len())?;
+        Ok(ModelArtifact { spec, version: version.to_string(), round, epsilon_spent, weights })
+    }
+
+    pub fn from_global_model(model: &GlobalModel, spec: ModelSpec, version: &str) -> Result<Self, String> {
+        Self::new(spec, version, model.round, model.privacy_metrics.total_epsilon_used, model.weights.clone())
+    }
+
+    // safetensors bytes with every tensor stored at `precision`; PyTorch's default is F32
+    pub fn to_safetensors(&self, precision: Precision) -> Result<Vec<u8>, String> {
+        self.spec.validate(self.weights.len())?;
+        let mut buffers = Vec::with_capacity(self.spec.tensors.len());
+        let mut offset = 0;
+        for tensor in &self.spec.tensors {
+            buffers.push(encode_values(&self.weights[offset..offset + tensor.len()], precision));
+            offset += tensor.len();
+        }
+        let views = self
+            .spec
+            .tensors
+            .iter()
+            .zip(&buffers)
+            .map(|(tensor, (dtype, data))| {
+                TensorView::new(*dtype, tensor.shape.clone(), data)
+                    .map(|view| (tensor.name.clone(), view))
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let spec_json = serde_json::to_string(&self.spec).map_err(|e| format!("Failed to serialize model spec: {}", e))?;
+        let metadata = HashMap::from([
+            ("format".to_string(), "pt".to_string()),
+            ("model_spec".to_string(), spec_json),
+            ("version".to_string(), self.version.clone()),
+            ("round".to_string(), self.round.to_string()),
+            ("epsilon_spent".to_string(), self.epsilon_spent.to_string()),
+        ]);
+        safetensors::serialize(views, &Some(metadata)).map_err(|e| format!("Failed to write safetensors: {}", e))
+    }
+
+    // Reads a safetensors file. `spec` orders and checks the tensors of files without
+    // our metadata, and overrides the embedded spec when given.
+    pub fn from_safetensors(bytes: &[u8], spec: Option<&ModelSpec>) -> Result<Self, String> {
+        let (_, header) = SafeTensors::read_metadata(bytes).map_err(|e| format!("Invalid safetensors header: {}", e))?;
+        let tensors = SafeTensors::deserialize(bytes).map_err(|e| format!("Invalid safetensors file: {}", e))?;
+        let metadata = header.metadata().clone().unwrap_or_default();
+
+        let spec = match (spec, metadata.get("model_spec")) {
+            (Some(spec), _) => spec.clone(),
+            (None, Some(json)) => serde_json::from_str(json).map_err(|e| format!("Invalid model_spec metadata: {}", e))?,
+            (None, None) => return Err("File has no model_spec metadata; supply a ModelSpec to order its tensors".to_string()),
+        };
+        if tensors.len() != spec.tensors.len() {
+            return Err(format!("File has {} tensors, spec lists {}", tensors.len(), spec.tensors.len()));
+        }
+
+        let mut weights = Vec::with_capacity(spec.dimension());
+        for tensor in &spec.tensors {
+            let view = tensors.tensor(&tensor.name).map_err(|_| format!("Tensor {} is missing", tensor.name))?;
+            if view.shape() != tensor.shape.as_slice() {
+                return Err(format!("Tensor {} has shape {:?}, spec says {:?}", tensor.name, view.shape(), tensor.shape));
+            }
+            weights.extend(decode_values(view.dtype(), view.data())?);
+        }
+
+        let round = metadata.get("round").map(|r| r.parse::<u64>()).transpose().map_err(|e| format!("Invalid round metadata: {}", e))?;
+        let epsilon_spent = metadata
+            .get("epsilon_spent")
+            .map(|e| e.parse::<f64>())
+            .transpose()
+            .map_err(|e| format!("Invalid epsilon_spent metadata: {}", e))?;
+        Self::new(
+            spec,
+            metadata.get("version").map(String::as_str).unwrap_or("external"),
+            round.unwrap_or(0),
+            epsilon_spent.unwrap_or(0.0),
+            weights,
+        )
+    }
+}
+
+impl FederatedLearningCoordinator {
+    pub fn export_artifact(&self, spec: ModelSpec, version: &str) -> Result<ModelArtifact, String> {
+        ModelArtifact::from_global_model(&self.global_model, spec, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safetensors_round_trip_keeps_layout_and_provenance() {
+        let spec = ModelSpec {
+            architecture: "mlp".to_string(),
+            tensors: vec![
+                TensorSpec { name: "fc1.weight".to_string(), shape: vec![2, 3] },
+                TensorSpec { name: "fc1.bias".to_string(), shape: vec![2] },
+            ],
+        };
+        let weights = vec![0.5, -1.0, 2.0, 0.25, 3.0, -0.125, 1.0, -2.0];
+        let artifact = ModelArtifact::new(spec.clone(), "v7", 12, 1.5, weights.clone()).unwrap();
+
+        // Tensors come back in spec order even though safetensors sorts them by name
+        let bytes = artifact.to_safetensors(Precision::F32).unwrap();
+        let restored = ModelArtifact::from_safetensors(&bytes, None).unwrap();
+        assert_eq!(restored.weights, weights);
+        assert_eq!((restored.spec, restored.version.as_str(), restored.round, restored.epsilon_spent), (spec.clone(), "v7", 12, 1.5));
+
+        // A file without our metadata needs the caller's spec
+        let tensors = SafeTensors::deserialize(&bytes).unwrap();
+        let bare = safetensors::serialize(tensors.tensors(), &None).unwrap();
+        assert!(ModelArtifact::from_safetensors(&bare, None).is_err());
+        assert_eq!(ModelArtifact::from_safetensors(&bare, Some(&spec)).unwrap().weights, weights);
+        let wrong_shape = ModelSpec::single_tensor("mlp", 8);
+        assert!(ModelArtifact::from_safetensors(&bytes, Some(&wrong_shape)).is_err());
+        assert!(ModelArtifact::new(spec, "v7", 0, 0.0, vec![0.0; 7]).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+// Trusted execution environment attestation for client updates. A client training
+// inside an enclave attaches the platform quote, the enclave measurement and report
+// data binding the quote to this exact update. The coordinator checks the metadata
+// against its policy, hands the raw quote to a platform-specific verifier, and can
+// then weight attested updates above unattested ones.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TeePlatform {
+    IntelSgx,
+    IntelTdx,
+    AmdSevSnp,
+    AwsNitro,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TeeAttestation {
+    pub platform: TeePlatform,
+    // Raw quote or attestation document as produced by the platform
+    pub quote: Vec<u8>,
+    // Hex-encoded enclave measurement (MRENCLAVE, MRTD, launch digest or PCR0)
+    pub measurement: String,
+    // Hex-encoded data the enclave placed in the quote; must equal `update_digest`
+    pub report_data: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AttestationPolicy {
+    // Reject updates without a valid attestation; implied by the TEE privacy method
+    pub require_attestation: bool,
+    pub allowed_platforms: Vec<TeePlatform>,
+    // Enclave builds the consortium has reviewed; an empty list admits none
+    pub allowed_measurements: Vec<String>,
+    // Aggregation weight of attested updates relative to unattested ones
+    pub trusted_weight_multiplier: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AttestationStatus {
+    // No attestation attached and none required
+    Unattested,
+    Verified,
+    Rejected { reason: String },
+}
+
+// Platform-specific quote verification (signature chain, TCB status, collateral).
+// Runs after the policy checks, only for updates that carry an attestation.
+pub trait QuoteVerifier {
+    fn verify_quote(&self, attestation: &TeeAttestation) -> Result<(), String>;
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        AttestationPolicy {
+            require_attestation: false,
+            allowed_platforms: vec![TeePlatform::IntelSgx, TeePlatform::IntelTdx, TeePlatform::AmdSevSnp, TeePlatform::AwsNitro],
+            allowed_measurements: Vec::new(),
+            trusted_weight_multiplier: 1.0,
+        }
+    }
+}
+
+impl AttestationPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.trusted_weight_multiplier.is_finite() && self.trusted_weight_multiplier >= 1.0) {
+            return Err("trusted_weight_multiplier must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    // Policy checks that need no platform knowledge
+    pub fn check(&self, update: &ModelUpdate, required: bool) -> AttestationStatus {
+        let attestation = match &update.attestation {
+            Some(attestation) => attestation,
+            None if required || self.require_attestation => {
+                return AttestationStatus::Rejected { reason: "Attestation required".to_string() };
+            }
+            None => return AttestationStatus::Unattested,
+        };
+
+        let reject = |reason: String| AttestationStatus::Rejected { reason };
+        if !self.allowed_platforms.contains(&attestation.platform) {
+            return reject(format!("Platform {:?} is not allowed", attestation.platform));
+        }
+        if !self.allowed_measurements.iter().any(|m| m.eq_ignore_ascii_case(&attestation.measurement)) {
+            return reject(format!("Measurement {} is not allowlisted", attestation.measurement));
+        }
+        if !attestation.report_data.eq_ignore_ascii_case(&update_digest(update)) {
+            return reject("Report data does not bind this update".to_string());
+        }
+        AttestationStatus::Verified
+    }
+}
+
+// SHA-256 over the fields an enclave must commit to, so a quote cannot be replayed
+// for another client, another round or altered weights
+pub fn update_digest(update: &ModelUpdate) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(update.client_id.as_bytes());
+    hasher.update(update.round.to_be_bytes());
+    hasher.update((update.data_size as u64).to_be_bytes());
+    for value in update.gradients.iter().chain(update.weights.iter()) {
+        hasher.update(value.to_be_bytes());
+    }
+    if let Some(sparse) = &update.sparse_gradients {
+        for (index, value) in sparse.indices.iter().zip(&sparse.values) {
+            hasher.update((*index as u64).to_be_bytes());
+            hasher.update(value.to_be_bytes());
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attested_update(measurement: &str) -> ModelUpdate {
+        let mut update = ModelUpdate {
+            round: 3,
+            weights: vec![1.0, 2.0],
+            ..ModelUpdate::for_test("hospital_a", vec![0.1, -0.2], 50)
+        };
+        let report_data = update_digest(&update);
+        update.attestation = Some(TeeAttestation {
+            platform: TeePlatform::AmdSevSnp,
+            quote: vec![0; 32],
+            measurement: measurement.to_string(),
+            report_data,
+        });
+        update
+    }
+
+    #[test]
+    fn test_policy_checks_measurement_and_binding() {
+        let policy = AttestationPolicy { allowed_measurements: vec!["ABCD".to_string()], ..AttestationPolicy::default() };
+
+        assert_eq!(policy.check(&attested_update("abcd"), true), AttestationStatus::Verified);
+        assert!(matches!(policy.check(&attested_update("ffff"), false), AttestationStatus::Rejected { .. }));
+
+        // Changing the update after attestation breaks the binding
+        let mut tampered = attested_update("abcd");
+        tampered.gradients[0] = 10.0;
+        assert!(matches!(policy.check(&tampered, false), AttestationStatus::Rejected { .. }));
+
+        let mut plain = attested_update("abcd");
+        plain.attestation = None;
+        assert_eq!(policy.check(&plain, false), AttestationStatus::Unattested);
+        assert!(matches!(policy.check(&plain, true), AttestationStatus::Rejected { .. }));
+    }
+}
@@ -0,0 +1,190 @@
+// Backdoor screening with spectral signatures (Tran, Li & Madry, 2018). Updates that
+// plant a backdoor share a component the clean updates lack, and it shows up as the
+// top principal direction of the round's centered updates: projecting each update on
+// that direction scores the poisoned ones far above the rest, even when their norms
+// are unremarkable and clipping lets them through.
+//
+// Each screened round centers the updates, finds the top right singular vector by
+// power iteration (without forming the d×d covariance), and scores every update by
+// its squared projection. At most 1.5 × `expected_poison_fraction` × n of the highest
+// scores are candidates, and a candidate is flagged only when its score is at least
+// `score_ratio` times the round's median, so a clean round flags nobody. Flagged
+// updates are excluded from aggregation or only reported, per the policy; either way
+// they are listed in `GlobalModel::backdoor_flags` for audit. Screening runs on the
+// clipped updates, before any privacy noise would hide the signature.
+
+use crate::*;
+
+const POWER_ITERATIONS: usize = 100;
+
+fn dot(x: &[f64], y: &[f64]) -> f64 {
+    x.iter().zip(y).map(|(a, b)| a * b).sum()
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BackdoorPolicy {
+    pub expected_poison_fraction: f64,
+    pub score_ratio: f64,
+    // Drop flagged updates from the round rather than only reporting them
+    pub exclude: bool,
+    // Rounds with fewer updates are not screened; the top direction means little there
+    pub min_updates: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BackdoorFlag {
+    pub client_id: String,
+    pub score: f64,
+    // Score over the round's median score
+    pub score_ratio: f64,
+    pub excluded: bool,
+}
+
+impl Default for BackdoorPolicy {
+    fn default() -> Self {
+        BackdoorPolicy { expected_poison_fraction: 0.1, score_ratio: 10.0, exclude: false, min_updates: 5 }
+    }
+}
+
+impl BackdoorPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.expected_poison_fraction > 0.0 && self.expected_poison_fraction < 0.5) {
+            return Err("Expected poison fraction must be in (0, 0.5)".to_string());
+        }
+        if !(self.score_ratio.is_finite() && self.score_ratio > 1.0) {
+            return Err("Score ratio must be greater than 1".to_string());
+        }
+        if self.min_updates < 3 {
+            return Err("Screening needs at least 3 updates".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Squared projection of each centered row on the rows' top principal direction
+pub fn spectral_scores(rows: &[Vec<f64>]) -> Vec<f64> {
+    let dimension = rows.first().map_or(0, |r| r.len());
+    if rows.is_empty() || dimension == 0 {
+        return vec![0.0; rows.len()];
+    }
+    let n = rows.len() as f64;
+    let mut mean = vec![0.0; dimension];
+    rows.iter().for_each(|r| kernels::axpy(1.0 / n, r, &mut mean));
+    let centered: Vec<Vec<f64>> = rows.iter().map(|r| r.iter().zip(&mean).map(|(x, m)| x - m).collect()).collect();
+
+    // A fixed, non-degenerate start keeps rounds reproducible
+    let mut direction: Vec<f64> = (0..dimension).map(|j| 1.0 + (j % 7) as f64 * 0.1).collect();
+    for _ in 0..POWER_ITERATIONS {
+        let mut next = vec![0.0; dimension];
+        for row in &centered {
+            kernels::axpy(dot(row, &direction), row, &mut next);
+        }
+        let norm = kernels::l2_norm(&next);
+        if norm == 0.0 {
+            return vec![0.0; rows.len()];
+        }
+        direction = next.into_iter().map(|x| x / norm).collect();
+    }
+    centered.iter().map(|row| dot(row, &direction).powi(2)).collect()
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_backdoor_policy(&mut self, policy: Option<BackdoorPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.backdoor_policy = policy;
+        Ok(())
+    }
+
+    // Flags (and with `exclude`, drops) updates whose spectral score stands out
+    pub(crate) fn screen_for_backdoors(&mut self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
+        self.global_model.backdoor_flags.clear();
+        let policy = match &self.backdoor_policy {
+            Some(policy) if updates.len() >= policy.min_updates => policy.clone(),
+            _ => return Ok(updates),
+        };
+
+        let dimension = self.config.model_dimension;
+        let rows: Vec<Vec<f64>> = updates
+            .iter()
+            .map(|u| u.sparse_gradients.as_ref().map_or_else(|| u.gradients.clone(), |s| s.to_dense(dimension)))
+            .collect();
+        let scores = spectral_scores(&rows);
+        let mut sorted = scores.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = sorted[sorted.len() / 2];
+
+        let candidates = (1.5 * policy.expected_poison_fraction * updates.len() as f64).ceil() as usize;
+        let mut ranked: Vec<usize> = (0..updates.len()).collect();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let flagged: Vec<usize> = ranked
+            .into_iter()
+            .take(candidates)
+            .filter(|&i| scores[i] > 0.0 && scores[i] >= policy.score_ratio * median)
+            .collect();
+
+        self.global_model.backdoor_flags = flagged
+            .iter()
+            .map(|&i| BackdoorFlag {
+                client_id: updates[i].client_id.clone(),
+                score: scores[i],
+                score_ratio: if median > 0.0 { scores[i] / median } else { f64::INFINITY },
+                excluded: policy.exclude,
+            })
+            .collect();
+        if !policy.exclude {
+            return Ok(updates);
+        }
+
+        let kept: Vec<ModelUpdate> = updates.into_iter().enumerate().filter(|(i, _)| !flagged.contains(i)).map(|(_, u)| u).collect();
+        if kept.len() < self.config.min_clients as usize {
+            return Err(format!("Only {} updates left after excluding suspected backdoors", kept.len()));
+        }
+        Ok(kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, gradients: Vec<f64>) -> ModelUpdate {
+        ModelUpdate { loss: 1.0, accuracy: 0.5, ..ModelUpdate::for_test(client_id, gradients, 100) }
+    }
+
+    #[test]
+    fn test_spectral_signatures_flag_the_poisoned_updates() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let dimension = 50;
+        // Honest updates share a direction plus noise; two also carry a backdoor trigger
+        let round = |rng: &mut rand::rngs::StdRng, poisoned: usize| -> Vec<ModelUpdate> {
+            (0..20)
+                .map(|i| {
+                    let gradients = (0..dimension)
+                        .map(|j| {
+                            let trigger = if i < poisoned && j >= 45 { 0.8 } else { 0.0 };
+                            0.5 + rng.gen_range(-0.2..0.2) + trigger
+                        })
+                        .collect();
+                    update(&format!("site_{}", i), gradients)
+                })
+                .collect()
+        };
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(dimension).min_clients(3).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        assert!(coordinator.set_backdoor_policy(Some(BackdoorPolicy { score_ratio: 0.5, ..BackdoorPolicy::default() })).is_err());
+        coordinator.set_backdoor_policy(Some(BackdoorPolicy { exclude: true, ..BackdoorPolicy::default() })).unwrap();
+
+        let kept = coordinator.screen_for_backdoors(round(&mut rng, 2)).unwrap();
+        let flagged: Vec<&str> = coordinator.get_global_model().backdoor_flags.iter().map(|f| f.client_id.as_str()).collect();
+        assert_eq!(flagged, vec!["site_0", "site_1"]);
+        assert_eq!(kept.len(), 18);
+
+        let kept = coordinator.screen_for_backdoors(round(&mut rng, 0)).unwrap();
+        assert!(coordinator.get_global_model().backdoor_flags.is_empty());
+        assert_eq!(kept.len(), 20);
+    }
+}
@@ -0,0 +1,135 @@
+// Reusable gradient buffers for simulations that hold hundreds of client updates per
+// round. Buffers are plain heap vectors kept warm between rounds rather than
+// memory-mapped files (not available inside a canister), so a round's updates can
+// be recycled into the next without another multi-megabyte allocation per client.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PoolStats {
+    // Buffers that had to be freshly allocated
+    pub allocations: u64,
+    // Buffers handed out from the free list
+    pub reuses: u64,
+    pub retained_buffers: usize,
+    pub retained_bytes: u64,
+}
+
+pub struct GradientPool {
+    dimension: usize,
+    // Free buffers beyond this count are dropped on release; 0 keeps them all
+    max_retained: usize,
+    free: Vec<Vec<f64>>,
+    allocations: u64,
+    reuses: u64,
+}
+
+impl GradientPool {
+    pub fn new(dimension: usize, max_retained: usize) -> Self {
+        GradientPool {
+            dimension,
+            max_retained,
+            free: Vec::new(),
+            allocations: 0,
+            reuses: 0,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    // Pre-allocates buffers so the first round does not pay for them either
+    pub fn warm(&mut self, buffers: usize) {
+        while self.free.len() < buffers {
+            self.free.push(Vec::with_capacity(self.dimension));
+            self.allocations += 1;
+        }
+    }
+
+    // A zeroed buffer of the pool's dimension
+    pub fn acquire(&mut self) -> Vec<f64> {
+        let mut buffer = self.take();
+        buffer.resize(self.dimension, 0.0);
+        buffer
+    }
+
+    // A buffer holding a copy of `values`, which must match the pool's dimension
+    pub fn acquire_copy(&mut self, values: &[f64]) -> Result<Vec<f64>, String> {
+        if values.len() != self.dimension {
+            return Err(format!("Pool holds {}-wide buffers, got {} values", self.dimension, values.len()));
+        }
+        let mut buffer = self.take();
+        buffer.extend_from_slice(values);
+        Ok(buffer)
+    }
+
+    fn take(&mut self) -> Vec<f64> {
+        match self.free.pop() {
+            Some(mut buffer) => {
+                self.reuses += 1;
+                buffer.clear();
+                buffer
+            }
+            None => {
+                self.allocations += 1;
+                Vec::with_capacity(self.dimension)
+            }
+        }
+    }
+
+    // Returns a buffer to the pool; buffers too small to reuse are dropped
+    pub fn release(&mut self, buffer: Vec<f64>) {
+        if buffer.capacity() < self.dimension {
+            return;
+        }
+        if self.max_retained > 0 && self.free.len() >= self.max_retained {
+            return;
+        }
+        self.free.push(buffer);
+    }
+
+    // Recycles the gradient and weight vectors of a finished round's updates
+    pub fn release_updates(&mut self, updates: Vec<ModelUpdate>) {
+        for update in updates {
+            self.release(update.gradients);
+            self.release(update.weights);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            allocations: self.allocations,
+            reuses: self.reuses,
+            retained_buffers: self.free.len(),
+            retained_bytes: self
+                .free
+                .iter()
+                .map(|b| (b.capacity() * std::mem::size_of::<f64>()) as u64)
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused_across_rounds() {
+        let mut pool = GradientPool::new(1024, 0);
+        pool.warm(4);
+        for round in 0..10 {
+            let buffers: Vec<Vec<f64>> = (0..4).map(|_| pool.acquire_copy(&[round as f64; 1024]).unwrap()).collect();
+            assert!(buffers.iter().all(|b| b.len() == 1024 && b[0] == round as f64));
+            for buffer in buffers {
+                pool.release(buffer);
+            }
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.allocations, 4);
+        assert_eq!(stats.reuses, 40);
+        assert!(pool.acquire().iter().all(|&v| v == 0.0));
+    }
+}
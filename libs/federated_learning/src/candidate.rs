@@ -0,0 +1,289 @@
+// Evaluation of each round's aggregated model before it replaces the previous one.
+// A poisoned or unlucky round can leave a model worse than the one it replaces; with
+// a policy set, the candidate and the previous global model are scored on the same
+// validation signal and the candidate is rolled back to the previous weights when its
+// accuracy drops by more than `max_accuracy_drop`.
+//
+// - ServerProxy: a ModelEvaluator the operator installs scores both models on a proxy
+//   validation set the coordinator holds, synchronously at the end of the round.
+// - ClientReported: the round ends provisionally. Sites score the candidate (the
+//   current global model) and the previous round's weights on their own validation
+//   split and report both; once `min_reports` sites have reported, the pooled
+//   accuracies (weighted by records) decide. The next round waits for the decision.
+//
+// A rollback restores only the global weights: the round still happened, its privacy
+// cost is spent and its participants are recorded. The candidate stays in the weight
+// history for audit, and the decision is kept on the round's history entry.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ValidationSignal {
+    ServerProxy,
+    ClientReported { min_reports: usize },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CandidateEvaluationPolicy {
+    pub signal: ValidationSignal,
+    // Largest accuracy loss against the previous model that is still accepted
+    pub max_accuracy_drop: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CandidateDecision {
+    Accepted,
+    RolledBack,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CandidateEvaluation {
+    pub round: u64,
+    pub signal: ValidationSignal,
+    pub candidate_accuracy: f64,
+    pub previous_accuracy: f64,
+    // Site reports pooled; 0 for the server proxy
+    pub reports: usize,
+    pub decision: CandidateDecision,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CandidateEvaluationReport {
+    pub client_id: String,
+    // The round whose candidate was evaluated
+    pub round: u64,
+    pub candidate_accuracy: f64,
+    pub previous_accuracy: f64,
+    pub records: usize,
+}
+
+// A round awaiting site evaluations, with the weights a rollback returns to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingCandidate {
+    pub round: u64,
+    pub previous_weights: Vec<f64>,
+    pub reports: Vec<CandidateEvaluationReport>,
+}
+
+// Scores a model on the server's proxy validation set
+pub trait ModelEvaluator {
+    fn accuracy(&self, weights: &[f64]) -> Result<f64, String>;
+}
+
+impl Default for CandidateEvaluationPolicy {
+    fn default() -> Self {
+        CandidateEvaluationPolicy { signal: ValidationSignal::ServerProxy, max_accuracy_drop: 0.02 }
+    }
+}
+
+impl CandidateEvaluationPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.max_accuracy_drop) {
+            return Err("Maximum accuracy drop must be within [0, 1]".to_string());
+        }
+        if self.signal == (ValidationSignal::ClientReported { min_reports: 0 }) {
+            return Err("Client-reported evaluation needs at least one report".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn decide(&self, candidate_accuracy: f64, previous_accuracy: f64) -> CandidateDecision {
+        if previous_accuracy - candidate_accuracy > self.max_accuracy_drop {
+            CandidateDecision::RolledBack
+        } else {
+            CandidateDecision::Accepted
+        }
+    }
+}
+
+fn check_accuracy(accuracy: f64) -> Result<f64, String> {
+    if (0.0..=1.0).contains(&accuracy) {
+        Ok(accuracy)
+    } else {
+        Err(format!("Accuracy {} is outside [0, 1]", accuracy))
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Clearing or replacing the policy accepts a candidate still awaiting reports
+    pub fn set_candidate_evaluation_policy(&mut self, policy: Option<CandidateEvaluationPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.candidate_policy = policy;
+        self.pending_candidate = None;
+        Ok(())
+    }
+
+    pub fn set_model_evaluator(&mut self, evaluator: Box<dyn ModelEvaluator>) {
+        self.model_evaluator = Some(evaluator);
+    }
+
+    // Round whose candidate sites should evaluate, if any
+    pub fn pending_candidate_round(&self) -> Option<u64> {
+        self.pending_candidate.as_ref().map(|p| p.round)
+    }
+
+    pub(crate) fn check_no_pending_candidate(&self) -> Result<(), String> {
+        match &self.pending_candidate {
+            Some(pending) => Err(format!("Candidate model of round {} is awaiting site evaluations", pending.round)),
+            None => Ok(()),
+        }
+    }
+
+    // Called once the candidate is the global model and before it is stored
+    pub(crate) fn evaluate_candidate(&mut self, previous_weights: Vec<f64>) -> Result<(), String> {
+        let Some(policy) = &self.candidate_policy else {
+            return Ok(());
+        };
+        match policy.signal {
+            ValidationSignal::ServerProxy => {
+                let evaluator = self.model_evaluator.as_ref().ok_or("Server-proxy evaluation needs a model evaluator")?;
+                let candidate_accuracy = check_accuracy(evaluator.accuracy(&self.global_model.weights)?)?;
+                let previous_accuracy = check_accuracy(evaluator.accuracy(&previous_weights)?)?;
+                let decision = policy.decide(candidate_accuracy, previous_accuracy);
+                if decision == CandidateDecision::RolledBack {
+                    self.global_model.weights = previous_weights;
+                }
+                self.global_model.candidate_evaluation = Some(CandidateEvaluation {
+                    round: self.global_model.round,
+                    signal: ValidationSignal::ServerProxy,
+                    candidate_accuracy,
+                    previous_accuracy,
+                    reports: 0,
+                    decision,
+                });
+            }
+            ValidationSignal::ClientReported { .. } => {
+                self.global_model.candidate_evaluation = None;
+                self.pending_candidate =
+                    Some(PendingCandidate { round: self.global_model.round, previous_weights, reports: Vec::new() });
+            }
+        }
+        Ok(())
+    }
+
+    // Pools a site's evaluation; returns the decision once enough sites reported
+    pub fn submit_candidate_evaluation(&mut self, report: CandidateEvaluationReport) -> Result<Option<CandidateEvaluation>, String> {
+        let min_reports = match self.candidate_policy.as_ref().map(|p| &p.signal) {
+            Some(ValidationSignal::ClientReported { min_reports }) => *min_reports,
+            _ => return Err("Candidate models are not evaluated by sites".to_string()),
+        };
+        let round = self.pending_candidate_round().ok_or("No candidate model is awaiting evaluation")?;
+        if report.round != round {
+            return Err(format!("Evaluation is for round {}, the candidate is round {}", report.round, round));
+        }
+        if !self.is_client_authorized(&report.client_id) {
+            return Err(format!("Client {} is not authorized", report.client_id));
+        }
+        check_accuracy(report.candidate_accuracy)?;
+        check_accuracy(report.previous_accuracy)?;
+        if report.records == 0 {
+            return Err("Evaluation must cover at least one record".to_string());
+        }
+        let pending = self.pending_candidate.as_mut().unwrap();
+        pending.reports.retain(|r| r.client_id != report.client_id);
+        pending.reports.push(report);
+        if pending.reports.len() < min_reports {
+            return Ok(None);
+        }
+        self.resolve_candidate_evaluation().map(Some)
+    }
+
+    // Decides on the reports received so far, for when some sites never report
+    pub fn resolve_candidate_evaluation(&mut self) -> Result<CandidateEvaluation, String> {
+        let policy = self.candidate_policy.as_ref().ok_or("Candidate evaluation is not enabled")?;
+        let pending = self.pending_candidate.as_ref().ok_or("No candidate model is awaiting evaluation")?;
+        if pending.reports.is_empty() {
+            return Err(format!("No site has evaluated the candidate of round {}", pending.round));
+        }
+        let records: f64 = pending.reports.iter().map(|r| r.records as f64).sum();
+        let pooled = |accuracy: fn(&CandidateEvaluationReport) -> f64| {
+            pending.reports.iter().map(|r| accuracy(r) * r.records as f64).sum::<f64>() / records
+        };
+        let candidate_accuracy = pooled(|r| r.candidate_accuracy);
+        let previous_accuracy = pooled(|r| r.previous_accuracy);
+        let evaluation = CandidateEvaluation {
+            round: pending.round,
+            signal: policy.signal.clone(),
+            candidate_accuracy,
+            previous_accuracy,
+            reports: pending.reports.len(),
+            decision: policy.decide(candidate_accuracy, previous_accuracy),
+        };
+
+        let pending = self.pending_candidate.take().unwrap();
+        if evaluation.decision == CandidateDecision::RolledBack {
+            self.global_model.weights = pending.previous_weights;
+        }
+        self.global_model.candidate_evaluation = Some(evaluation.clone());
+        if let Some(entry) = self.round_history.iter_mut().rev().find(|m| m.round == evaluation.round) {
+            entry.candidate_evaluation = Some(evaluation.clone());
+        } else if let Some(record) = self.compacted_history.iter_mut().rev().find(|r| r.round == evaluation.round) {
+            record.candidate_decision = Some(evaluation.decision);
+        }
+        Ok(evaluation)
+    }
+
+    // Rounds rolled back so far among those still in the history
+    pub fn get_rolled_back_rounds(&self) -> Vec<u64> {
+        self.get_round_records()
+            .iter()
+            .filter(|r| r.candidate_decision == Some(CandidateDecision::RolledBack))
+            .map(|r| r.round)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Accuracy falls off with the distance from the all-ones model
+    struct DistanceEvaluator;
+
+    impl ModelEvaluator for DistanceEvaluator {
+        fn accuracy(&self, weights: &[f64]) -> Result<f64, String> {
+            let distance: f64 = weights.iter().map(|w| (w - 1.0).powi(2)).sum::<f64>().sqrt();
+            Ok(1.0 / (1.0 + distance))
+        }
+    }
+
+    fn update(round: u64, gradients: Vec<f64>) -> ModelUpdate {
+        ModelUpdate { round, loss: 0.5, accuracy: 0.5, ..ModelUpdate::for_test("hospital_a", gradients, 10) }
+    }
+
+    #[test]
+    fn test_regressing_candidates_are_rolled_back() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).privacy_method(PrivacyMethod::None).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_model_evaluator(Box::new(DistanceEvaluator));
+        coordinator.set_candidate_evaluation_policy(Some(CandidateEvaluationPolicy::default())).unwrap();
+
+        coordinator.execute_round(vec![update(0, vec![0.9, 0.9])]).unwrap();
+        let model = coordinator.execute_round(vec![update(1, vec![-3.0, -3.0])]).unwrap();
+        assert_eq!(model.weights, vec![0.9, 0.9]);
+        assert_eq!(model.candidate_evaluation.unwrap().decision, CandidateDecision::RolledBack);
+        assert_eq!(coordinator.get_rolled_back_rounds(), vec![2]);
+
+        // Sites report; the next round waits until enough of them have
+        let policy = CandidateEvaluationPolicy { signal: ValidationSignal::ClientReported { min_reports: 2 }, max_accuracy_drop: 0.02 };
+        coordinator.set_candidate_evaluation_policy(Some(policy)).unwrap();
+        coordinator.execute_round(vec![update(2, vec![0.0, 0.0])]).unwrap();
+        assert!(coordinator.execute_round(vec![update(3, vec![0.9, 0.9])]).is_err());
+        let report = |client_id: &str, candidate_accuracy| CandidateEvaluationReport {
+            client_id: client_id.to_string(),
+            round: 3,
+            candidate_accuracy,
+            previous_accuracy: 0.8,
+            records: 50,
+        };
+        assert_eq!(coordinator.submit_candidate_evaluation(report("hospital_a", 0.7)).unwrap(), None);
+        let evaluation = coordinator.submit_candidate_evaluation(report("hospital_b", 0.8)).unwrap().unwrap();
+        assert!((evaluation.candidate_accuracy - 0.75).abs() < 1e-12);
+        assert_eq!(evaluation.decision, CandidateDecision::RolledBack);
+        assert_eq!(coordinator.get_global_model().weights, vec![0.9, 0.9]);
+        assert_eq!(coordinator.get_rolled_back_rounds(), vec![2, 3]);
+        assert!(coordinator.execute_round(vec![update(3, vec![0.95, 0.95])]).is_ok());
+    }
+}
@@ -0,0 +1,281 @@
+// Compact storage for the per-round global weight history. A full keyframe is kept
+// every `keyframe_interval` rounds and the rounds in between store only a delta
+// against the previous round; any retained round can be rebuilt on demand by
+// replaying deltas from the nearest earlier keyframe.
+
+use crate::*;
+use std::collections::VecDeque;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointConfig {
+    pub keyframe_interval: u64,
+    // Oldest rounds are evicted beyond this count; 0 keeps every round
+    pub max_retained_rounds: usize,
+    pub delta_encoding: DeltaEncoding,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum DeltaEncoding {
+    // Stores the new value of every weight that changed; lossless, and small when
+    // updates are sparse (TopK, DGC)
+    Sparse,
+    // Stores every delta as a 16-bit integer with a per-round scale. Each delta is
+    // taken against the reconstructed previous round, so error does not accumulate
+    // along the chain: a rebuilt round is within scale / 2 of the original.
+    Quantized16,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+enum CheckpointEntry {
+    Keyframe(Vec<f64>),
+    SparseDelta { indices: Vec<u32>, values: Vec<f64> },
+    QuantizedDelta { scale: f64, values: Vec<i16> },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointMemoryReport {
+    pub rounds_retained: usize,
+    pub keyframes: usize,
+    pub deltas: usize,
+    // Bytes the same rounds would take as full f64 weight vectors
+    pub dense_bytes: u64,
+    pub stored_bytes: u64,
+    pub savings_ratio: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointStore {
+    config: CheckpointConfig,
+    entries: VecDeque<(u64, CheckpointEntry)>,
+    // Reconstruction of the newest round, which the next delta is taken against
+    latest: Option<Vec<f64>>,
+    rounds_since_keyframe: u64,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            keyframe_interval: 10,
+            max_retained_rounds: 0,
+            delta_encoding: DeltaEncoding::Sparse,
+        }
+    }
+}
+
+impl CheckpointEntry {
+    fn stored_bytes(&self) -> u64 {
+        let bytes = match self {
+            CheckpointEntry::Keyframe(weights) => weights.len() * std::mem::size_of::<f64>(),
+            CheckpointEntry::SparseDelta { indices, values } => {
+                indices.len() * std::mem::size_of::<u32>() + values.len() * std::mem::size_of::<f64>()
+            }
+            CheckpointEntry::QuantizedDelta { values, .. } => {
+                std::mem::size_of::<f64>() + values.len() * std::mem::size_of::<i16>()
+            }
+        };
+        bytes as u64
+    }
+
+    fn apply(&self, weights: &mut Vec<f64>) {
+        match self {
+            CheckpointEntry::Keyframe(keyframe) => weights.clone_from(keyframe),
+            CheckpointEntry::SparseDelta { indices, values } => {
+                for (&i, &value) in indices.iter().zip(values.iter()) {
+                    weights[i as usize] = value;
+                }
+            }
+            CheckpointEntry::QuantizedDelta { scale, values } => {
+                for (w, &q) in weights.iter_mut().zip(values.iter()) {
+                    *w += q as f64 * scale;
+                }
+            }
+        }
+    }
+}
+
+impl CheckpointStore {
+    pub fn new(config: CheckpointConfig) -> Result<Self, String> {
+        if config.keyframe_interval == 0 {
+            return Err("keyframe_interval must be at least 1".to_string());
+        }
+        Ok(CheckpointStore {
+            config,
+            entries: VecDeque::new(),
+            latest: None,
+            rounds_since_keyframe: 0,
+        })
+    }
+
+    pub fn push(&mut self, round: u64, weights: &[f64]) -> Result<(), String> {
+        if let Some(&(last_round, _)) = self.entries.back() {
+            if round <= last_round {
+                return Err(format!("Round {} is not after the last stored round {}", round, last_round));
+            }
+        }
+
+        let needs_keyframe = match &self.latest {
+            Some(previous) => {
+                previous.len() != weights.len() || self.rounds_since_keyframe + 1 >= self.config.keyframe_interval
+            }
+            None => true,
+        };
+
+        let entry = if needs_keyframe {
+            self.rounds_since_keyframe = 0;
+            CheckpointEntry::Keyframe(weights.to_vec())
+        } else {
+            self.rounds_since_keyframe += 1;
+            let previous = self.latest.as_ref().unwrap();
+            match self.config.delta_encoding {
+                DeltaEncoding::Sparse => {
+                    let mut indices = Vec::new();
+                    let mut values = Vec::new();
+                    for (i, (&new, &old)) in weights.iter().zip(previous.iter()).enumerate() {
+                        if new.to_bits() != old.to_bits() {
+                            indices.push(i as u32);
+                            values.push(new);
+                        }
+                    }
+                    CheckpointEntry::SparseDelta { indices, values }
+                }
+                DeltaEncoding::Quantized16 => {
+                    let max_delta = weights
+                        .iter()
+                        .zip(previous.iter())
+                        .map(|(new, old)| (new - old).abs())
+                        .fold(0.0, f64::max);
+                    let scale = if max_delta > 0.0 { max_delta / i16::MAX as f64 } else { 1.0 };
+                    let values = weights
+                        .iter()
+                        .zip(previous.iter())
+                        .map(|(new, old)| ((new - old) / scale).round() as i16)
+                        .collect();
+                    CheckpointEntry::QuantizedDelta { scale, values }
+                }
+            }
+        };
+
+        let mut latest = self.latest.take().unwrap_or_default();
+        entry.apply(&mut latest);
+        self.latest = Some(latest);
+        self.entries.push_back((round, entry));
+        self.enforce_retention();
+        Ok(())
+    }
+
+    // Evicting the oldest round may leave a delta at the front, which is then
+    // materialised into a keyframe so the remaining chain stays reconstructible
+    fn enforce_retention(&mut self) {
+        let max = self.config.max_retained_rounds;
+        if max == 0 || self.entries.len() <= max {
+            return;
+        }
+
+        let mut front = match self.entries.pop_front() {
+            Some((_, CheckpointEntry::Keyframe(weights))) => weights,
+            _ => return,
+        };
+        while self.entries.len() > max {
+            if let Some((_, entry)) = self.entries.pop_front() {
+                entry.apply(&mut front);
+            }
+        }
+        if let Some((_, entry)) = self.entries.front_mut() {
+            if !matches!(entry, CheckpointEntry::Keyframe(_)) {
+                entry.apply(&mut front);
+                *entry = CheckpointEntry::Keyframe(front);
+            }
+        }
+    }
+
+    pub fn reconstruct(&self, round: u64) -> Option<Vec<f64>> {
+        let target = self.entries.iter().position(|(r, _)| *r == round)?;
+        let start = self.entries
+            .iter()
+            .take(target + 1)
+            .rposition(|(_, entry)| matches!(entry, CheckpointEntry::Keyframe(_)))?;
+
+        let mut weights = Vec::new();
+        for (_, entry) in self.entries.iter().skip(start).take(target + 1 - start) {
+            entry.apply(&mut weights);
+        }
+        Some(weights)
+    }
+
+    pub fn latest(&self) -> Option<&[f64]> {
+        self.latest.as_deref()
+    }
+
+    pub fn rounds(&self) -> Vec<u64> {
+        self.entries.iter().map(|(round, _)| *round).collect()
+    }
+
+    pub fn memory_report(&self) -> CheckpointMemoryReport {
+        let dimension = self.latest.as_ref().map_or(0, |w| w.len()) as u64;
+        let keyframes = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry, CheckpointEntry::Keyframe(_)))
+            .count();
+        let dense_bytes = self.entries.len() as u64 * dimension * std::mem::size_of::<f64>() as u64;
+        let stored_bytes: u64 = self.entries.iter().map(|(_, entry)| entry.stored_bytes()).sum();
+
+        CheckpointMemoryReport {
+            rounds_retained: self.entries.len(),
+            keyframes,
+            deltas: self.entries.len() - keyframes,
+            dense_bytes,
+            stored_bytes,
+            savings_ratio: if dense_bytes > 0 { 1.0 - stored_bytes as f64 / dense_bytes as f64 } else { 0.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_weights(round: u64) -> Vec<f64> {
+        // Only every tenth weight moves per round, as with top-k updates
+        (0..1000)
+            .map(|i| if i % 10 == 0 { i as f64 * 0.001 + round as f64 * 0.01 } else { i as f64 * 0.001 })
+            .collect()
+    }
+
+    #[test]
+    fn test_sparse_history_is_lossless_under_retention() {
+        let config = CheckpointConfig { keyframe_interval: 4, max_retained_rounds: 6, delta_encoding: DeltaEncoding::Sparse };
+        let mut store = CheckpointStore::new(config).unwrap();
+        for round in 1..=10 {
+            store.push(round, &round_weights(round)).unwrap();
+        }
+
+        assert_eq!(store.rounds(), vec![5, 6, 7, 8, 9, 10]);
+        for round in 5..=10 {
+            assert_eq!(store.reconstruct(round).unwrap(), round_weights(round));
+        }
+        assert!(store.reconstruct(4).is_none());
+
+        let report = store.memory_report();
+        assert_eq!(report.rounds_retained, 6);
+        assert!(report.savings_ratio > 0.5);
+    }
+
+    #[test]
+    fn test_quantized_history_error_is_bounded() {
+        let config = CheckpointConfig { keyframe_interval: 20, max_retained_rounds: 0, delta_encoding: DeltaEncoding::Quantized16 };
+        let mut store = CheckpointStore::new(config).unwrap();
+        let weights = |round: u64| -> Vec<f64> { (0..100).map(|i| ((i * 7 + round as usize * 13) as f64).sin()).collect() };
+        for round in 0..15 {
+            store.push(round, &weights(round)).unwrap();
+        }
+
+        for round in 0..15 {
+            let rebuilt = store.reconstruct(round).unwrap();
+            let max_error = rebuilt.iter().zip(weights(round)).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            // Deltas are at most 2, so the quantization step is 2 / 32767
+            assert!(max_error <= 2.0 / i16::MAX as f64);
+        }
+        assert!(store.memory_report().savings_ratio > 0.6);
+    }
+}
@@ -0,0 +1,335 @@
+// Client lifecycle tracking for the coordinator. Every client the coordinator has seen
+// is Active, Dropped (missed too many rounds in a row) or Rejoined (came back after a
+// drop and was re-synced); the dropout policy decides how an absent client's expected
+// weight is handled and what a rejoining client has to reset before training again.
+// When the coordinator selects a cohort for a round, only cohort members are
+// expected: clients left out of the round are neither missing nor missed.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ClientStatus {
+    Active,
+    Dropped,
+    // Back after a drop; becomes Active again once it contributes to a round
+    Rejoined,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum WeightRedistribution {
+    // Participants keep their own data-size weights and absent clients are ignored,
+    // which spreads the missing weight proportionally (plain FedAvg behaviour)
+    Proportional,
+    // The missing weight is split equally across participants, so small sites gain
+    // relatively more while large sites are offline
+    Uniform,
+    // The missing weight stays on the current global model, shrinking the step
+    // toward the participants by the fraction of expected weight that is present
+    RetainGlobal,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DropoutPolicy {
+    // Consecutive missed rounds after which a client is marked Dropped
+    pub max_missed_rounds: u32,
+    pub weight_redistribution: WeightRedistribution,
+    // Rejoining clients discard their error-feedback residuals, which were
+    // accumulated against a model that is now several rounds old
+    pub reset_error_feedback: bool,
+    // Rejoining clients restart SCAFFOLD with a zero client control
+    pub reset_control_variate: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClientRecord {
+    pub client_id: String,
+    pub status: ClientStatus,
+    // Last reported data size, used as the client's expected aggregation weight
+    pub expected_weight: f64,
+    pub last_seen_round: Option<u64>,
+    pub missed_rounds: u32,
+    pub dropouts: u32,
+    pub rejoins: u32,
+    // Training loss from the client's last accepted update
+    pub last_loss: Option<f64>,
+    pub resources: Option<ClientResources>,
+}
+
+// Everything a client needs to resume training from the current round
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClientResync {
+    pub client_id: String,
+    pub round: u64,
+    pub weights: Vec<f64>,
+    pub server_control: Vec<f64>,
+    pub client_control: Vec<f64>,
+    pub reset_error_feedback: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LifecycleChanges {
+    pub dropped: Vec<String>,
+    pub rejoined: Vec<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoundCohort {
+    pub round: u64,
+    pub clients: Vec<String>,
+    // Chance each selectable client had of being drawn; None if unknown
+    pub sampling_rate: Option<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClientRegistry {
+    clients: HashMap<String, ClientRecord>,
+    // Selected cohort of the open round; cleared when the round is recorded
+    cohort: Option<RoundCohort>,
+}
+
+impl Default for DropoutPolicy {
+    fn default() -> Self {
+        DropoutPolicy {
+            max_missed_rounds: 3,
+            weight_redistribution: WeightRedistribution::Proportional,
+            reset_error_feedback: true,
+            reset_control_variate: false,
+        }
+    }
+}
+
+impl DropoutPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_missed_rounds == 0 {
+            return Err("max_missed_rounds must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ClientRecord {
+    pub fn new(client_id: &str, expected_weight: f64) -> Self {
+        ClientRecord {
+            client_id: client_id.to_string(),
+            status: ClientStatus::Active,
+            expected_weight,
+            last_seen_round: None,
+            missed_rounds: 0,
+            dropouts: 0,
+            rejoins: 0,
+            last_loss: None,
+            resources: None,
+        }
+    }
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        ClientRegistry::default()
+    }
+
+    pub fn register(&mut self, client_id: &str, expected_weight: f64) {
+        self.clients
+            .entry(client_id.to_string())
+            .and_modify(|r| r.expected_weight = expected_weight)
+            .or_insert_with(|| ClientRecord::new(client_id, expected_weight));
+    }
+
+    // Registered clients plus first-time participants in `updates`
+    pub fn population(&self, updates: &[ModelUpdate]) -> usize {
+        let new = updates.iter().filter(|u| !self.clients.contains_key(&u.client_id)).count();
+        self.clients.len() + new
+    }
+
+    pub fn get(&self, client_id: &str) -> Option<&ClientRecord> {
+        self.clients.get(client_id)
+    }
+
+    pub fn status(&self, client_id: &str) -> Option<ClientStatus> {
+        self.clients.get(client_id).map(|r| r.status.clone())
+    }
+
+    // Records sorted by client id so reports are stable across replicas
+    pub fn records(&self) -> Vec<ClientRecord> {
+        let mut records: Vec<ClientRecord> = self.clients.values().cloned().collect();
+        records.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        records
+    }
+
+    pub fn clients_with_status(&self, status: &ClientStatus) -> Vec<String> {
+        let mut ids: Vec<String> =
+            self.clients.values().filter(|r| &r.status == status).map(|r| r.client_id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn set_resources(&mut self, client_id: &str, resources: ClientResources) -> Result<(), String> {
+        match self.clients.get_mut(client_id) {
+            Some(record) => {
+                record.resources = Some(resources);
+                Ok(())
+            }
+            None => Err(format!("Client {} is not registered", client_id)),
+        }
+    }
+
+    pub fn set_cohort(&mut self, cohort: RoundCohort) {
+        self.cohort = Some(cohort);
+    }
+
+    pub fn cohort(&self) -> Option<&RoundCohort> {
+        self.cohort.as_ref()
+    }
+
+    // Without a selected cohort for the round every client may contribute
+    pub fn in_cohort(&self, client_id: &str, round: u64) -> bool {
+        match &self.cohort {
+            Some(cohort) if cohort.round == round => cohort.clients.iter().any(|c| c == client_id),
+            _ => true,
+        }
+    }
+
+    fn is_expected(&self, client_id: &str) -> bool {
+        match &self.cohort {
+            Some(cohort) => cohort.clients.iter().any(|c| c == client_id),
+            None => true,
+        }
+    }
+
+    // Marks a dropped client as rejoining; returns false for unknown or non-dropped clients
+    pub fn mark_rejoined(&mut self, client_id: &str) -> bool {
+        match self.clients.get_mut(client_id) {
+            Some(record) if record.status == ClientStatus::Dropped => {
+                record.status = ClientStatus::Rejoined;
+                record.missed_rounds = 0;
+                record.rejoins += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Updates every record after a round closes. Participants are registered on first
+    // sight; a dropped client that sends a valid update counts as rejoining implicitly.
+    pub fn record_round(&mut self, round: u64, updates: &[ModelUpdate], policy: &DropoutPolicy) -> LifecycleChanges {
+        let mut changes = LifecycleChanges { dropped: Vec::new(), rejoined: Vec::new() };
+
+        for update in updates {
+            let record = self
+                .clients
+                .entry(update.client_id.clone())
+                .or_insert_with(|| ClientRecord::new(&update.client_id, update.data_size as f64));
+            if record.status == ClientStatus::Dropped {
+                record.rejoins += 1;
+                changes.rejoined.push(record.client_id.clone());
+            }
+            record.status = ClientStatus::Active;
+            record.expected_weight = update.data_size as f64;
+            record.last_seen_round = Some(round);
+            record.missed_rounds = 0;
+            record.last_loss = Some(update.loss);
+        }
+
+        let cohort = self.cohort.take();
+        for record in self.clients.values_mut() {
+            if record.last_seen_round == Some(round) || record.status == ClientStatus::Dropped {
+                continue;
+            }
+            if cohort.as_ref().is_some_and(|c| !c.clients.contains(&record.client_id)) {
+                continue;
+            }
+            record.missed_rounds += 1;
+            if record.missed_rounds >= policy.max_missed_rounds {
+                record.status = ClientStatus::Dropped;
+                record.dropouts += 1;
+                changes.dropped.push(record.client_id.clone());
+            }
+        }
+
+        changes.dropped.sort();
+        changes.rejoined.sort();
+        changes
+    }
+
+    // Expected weight of registered clients that did not take part in this round
+    pub fn missing_weight(&self, updates: &[ModelUpdate]) -> f64 {
+        self.clients
+            .values()
+            .filter(|r| self.is_expected(&r.client_id) && !updates.iter().any(|u| u.client_id == r.client_id))
+            .map(|r| r.expected_weight)
+            .sum()
+    }
+
+    // Rescales participant data sizes before aggregation. Proportional leaves them
+    // as reported; Uniform adds an equal share of the missing weight to each.
+    pub fn redistribute_weights(&self, mut updates: Vec<ModelUpdate>, policy: &DropoutPolicy) -> Vec<ModelUpdate> {
+        if !matches!(policy.weight_redistribution, WeightRedistribution::Uniform) || updates.is_empty() {
+            return updates;
+        }
+        let share = (self.missing_weight(&updates) / updates.len() as f64).round() as usize;
+        for update in &mut updates {
+            update.data_size += share;
+        }
+        updates
+    }
+
+    // RetainGlobal: mixes the aggregate with the current global model by the present
+    // fraction of expected weight; other policies return the aggregate unchanged
+    pub fn retain_global_weight(
+        &self,
+        aggregated: Vec<f64>,
+        global_weights: &[f64],
+        updates: &[ModelUpdate],
+        policy: &DropoutPolicy,
+    ) -> Vec<f64> {
+        if !matches!(policy.weight_redistribution, WeightRedistribution::RetainGlobal)
+            || aggregated.len() != global_weights.len()
+        {
+            return aggregated;
+        }
+        let present: f64 = updates.iter().map(|u| u.data_size as f64).sum();
+        let expected = present + self.missing_weight(updates);
+        if expected <= 0.0 {
+            return aggregated;
+        }
+        let fraction = present / expected;
+        aggregated
+            .iter()
+            .zip(global_weights.iter())
+            .map(|(new, old)| old + fraction * (new - old))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, data_size: usize) -> ModelUpdate {
+        ModelUpdate { weights: vec![1.0], ..ModelUpdate::for_test(client_id, vec![1.0], data_size) }
+    }
+
+    #[test]
+    fn test_drop_rejoin_and_redistribution() {
+        let policy = DropoutPolicy { max_missed_rounds: 2, ..DropoutPolicy::default() };
+        let mut registry = ClientRegistry::new();
+        registry.record_round(1, &[update("a", 100), update("b", 300)], &policy);
+
+        let only_a = [update("a", 100)];
+        assert!(registry.record_round(2, &only_a, &policy).dropped.is_empty());
+        assert_eq!(registry.record_round(3, &only_a, &policy).dropped, vec!["b".to_string()]);
+        assert_eq!(registry.status("b"), Some(ClientStatus::Dropped));
+
+        // b's 300 samples are missing from the round
+        let uniform = DropoutPolicy { weight_redistribution: WeightRedistribution::Uniform, ..policy.clone() };
+        assert_eq!(registry.redistribute_weights(only_a.to_vec(), &uniform)[0].data_size, 400);
+        let retain = DropoutPolicy { weight_redistribution: WeightRedistribution::RetainGlobal, ..policy.clone() };
+        assert_eq!(registry.retain_global_weight(vec![4.0], &[0.0], &only_a, &retain), vec![1.0]);
+
+        assert!(registry.mark_rejoined("b"));
+        assert_eq!(registry.status("b"), Some(ClientStatus::Rejoined));
+        registry.record_round(4, &[update("a", 100), update("b", 300)], &policy);
+        assert_eq!(registry.status("b"), Some(ClientStatus::Active));
+        assert_eq!(registry.get("b").unwrap().rejoins, 1);
+    }
+}
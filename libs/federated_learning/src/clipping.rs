@@ -0,0 +1,111 @@
+// Server-side norm bounding. A client whose update is far larger than everyone
+// else's (a boosted model-replacement attack, or just a diverged local run) can
+// dominate an average even when every value is finite, so before aggregation each
+// update is scaled down to at most `max_norm` in L2. The update's direction is
+// kept; only its magnitude is bounded.
+//
+// Under central differential privacy the bound doubles as the mechanism's L2
+// sensitivity: every update is clipped to it before any noise is added, and the
+// noise is calibrated to it rather than to the norms the round happened to see. DP
+// rounds refuse to run without one, and record it in `PrivacyMetrics::clip_norm`.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClippingPolicy {
+    // None disables clipping
+    pub max_norm: Option<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClippingStats {
+    pub updates_checked: u32,
+    pub updates_clipped: u32,
+    // Pre-clipping L2 norms of the round's updates
+    pub max_norm_observed: f64,
+    pub mean_norm_observed: f64,
+}
+
+impl ClippingPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.max_norm {
+            Some(max_norm) if !(max_norm.is_finite() && max_norm > 0.0) => {
+                Err("Clipping max_norm must be positive".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Scales `gradients` in place and returns their norm before clipping
+    pub fn clip(&self, gradients: &mut [f64], stats: &mut ClippingStats) -> f64 {
+        let norm = kernels::l2_norm(gradients);
+        let checked = stats.updates_checked as f64;
+        stats.mean_norm_observed = (stats.mean_norm_observed * checked + norm) / (checked + 1.0);
+        stats.max_norm_observed = stats.max_norm_observed.max(norm);
+        stats.updates_checked += 1;
+
+        if let Some(max_norm) = self.max_norm {
+            if norm > max_norm {
+                let factor = max_norm / norm;
+                gradients.iter_mut().for_each(|g| *g *= factor);
+                stats.updates_clipped += 1;
+            }
+        }
+        norm
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // L2 sensitivity DP noise is calibrated to: the per-layer bounds combined when a
+    // layer policy is set, otherwise the clipping policy's bound
+    pub fn dp_clip_norm(&self) -> Result<f64, String> {
+        let clip_norm = match &self.layer_privacy {
+            Some(policy) => policy.max_norms.as_ref().map(|norms| norms.iter().map(|n| n * n).sum::<f64>().sqrt()),
+            None => self.clipping_policy.max_norm,
+        };
+        clip_norm.ok_or_else(|| "Differential privacy needs a fixed clipping bound; set a clipping policy max_norm or per-layer max_norms".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipping_bounds_norm_and_counts() {
+        let policy = ClippingPolicy { max_norm: Some(5.0) };
+        let mut stats = ClippingStats::default();
+
+        let mut small = vec![3.0, 0.0];
+        let mut large = vec![30.0, 40.0];
+        assert_eq!(policy.clip(&mut small, &mut stats), 3.0);
+        assert_eq!(policy.clip(&mut large, &mut stats), 50.0);
+
+        assert_eq!(small, vec![3.0, 0.0]);
+        assert_eq!(large, vec![3.0, 4.0]);
+        assert_eq!((stats.updates_checked, stats.updates_clipped), (2, 1));
+        assert_eq!((stats.max_norm_observed, stats.mean_norm_observed), (50.0, 26.5));
+        assert!(ClippingPolicy { max_norm: Some(0.0) }.validate().is_err());
+    }
+
+    #[test]
+    fn test_dp_noise_is_calibrated_to_the_configured_bound() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(2).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let update = |client_id: &str, gradients: Vec<f64>| ModelUpdate::for_test(client_id, gradients, 1);
+        let updates = vec![update("hospital_a", vec![30.0, 40.0]), update("hospital_b", vec![0.3, 0.4])];
+
+        // Without a bound the sensitivity would depend on the data
+        assert!(coordinator.dp_clip_norm().is_err());
+        assert!(coordinator.execute_round(updates.clone()).is_err());
+
+        coordinator.set_clipping_policy(ClippingPolicy { max_norm: Some(1.0) }).unwrap();
+        let model = coordinator.execute_round(updates).unwrap();
+        assert_eq!(model.privacy_metrics.clip_norm, Some(1.0));
+        assert_eq!(model.clipping_stats.updates_clipped, 1);
+
+        let layers = LayerPrivacyPolicy { partition: LayerPartition::Layers { sizes: vec![1, 1] }, max_norms: Some(vec![3.0, 4.0]) };
+        coordinator.set_layer_privacy(Some(layers)).unwrap();
+        assert_eq!(coordinator.dp_clip_norm(), Ok(5.0));
+    }
+}
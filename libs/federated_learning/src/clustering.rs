@@ -0,0 +1,139 @@
+// Clustered federated learning (IFCA, Ghosh et al. 2020). The coordinator keeps K
+// cluster models. Before training, each client evaluates all K on its local data and
+// reports the losses; it is assigned to the cluster with the lowest loss, trains that
+// model, and its update is averaged only with the other members of that cluster. The
+// regular global model is still aggregated over every client and serves clients that
+// have not been assigned yet.
+
+use crate::*;
+use rand::Rng;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClusterLossReport {
+    pub client_id: String,
+    pub round: u64,
+    // Local loss of each cluster model, indexed by cluster
+    pub losses: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClusteringState {
+    models: Vec<Vec<f64>>,
+    assignments: HashMap<String, usize>,
+    // Round in which each assignment was made
+    assigned_round: HashMap<String, u64>,
+}
+
+impl ClusteringState {
+    pub fn new(models: Vec<Vec<f64>>) -> Result<Self, String> {
+        if models.len() < 2 {
+            return Err("Clustering needs at least 2 cluster models".to_string());
+        }
+        let len = models[0].len();
+        if len == 0 || models.iter().any(|m| m.len() != len) {
+            return Err("Cluster models must be non-empty and of equal length".to_string());
+        }
+        Ok(ClusteringState { models, assignments: HashMap::new(), assigned_round: HashMap::new() })
+    }
+
+    // K copies of `base` with independent uniform noise in [-scale, scale]; IFCA needs
+    // distinct starting points or every client picks the same cluster
+    pub fn perturbed(base: &[f64], num_clusters: usize, scale: f64) -> Result<Self, String> {
+        let mut rng = rand::thread_rng();
+        let models = (0..num_clusters)
+            .map(|_| base.iter().map(|w| w + rng.gen_range(-scale..=scale)).collect())
+            .collect();
+        ClusteringState::new(models)
+    }
+
+    pub fn num_clusters(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn models(&self) -> &[Vec<f64>] {
+        &self.models
+    }
+
+    pub fn assignment(&self, client_id: &str) -> Option<usize> {
+        self.assignments.get(client_id).copied()
+    }
+
+    // Assigns the client to its lowest-loss cluster
+    pub fn assign(&mut self, report: &ClusterLossReport) -> Result<usize, String> {
+        if report.losses.len() != self.models.len() {
+            return Err(format!("Expected {} cluster losses, got {}", self.models.len(), report.losses.len()));
+        }
+        if report.losses.iter().any(|l| !l.is_finite()) {
+            return Err("Cluster losses must be finite".to_string());
+        }
+        let cluster = report
+            .losses
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        self.assignments.insert(report.client_id.clone(), cluster);
+        self.assigned_round.insert(report.client_id.clone(), report.round);
+        Ok(cluster)
+    }
+
+    // Replaces each cluster's model with the sample-weighted average of the updates
+    // from clients assigned to it in `round`; clusters without updates keep their model
+    pub fn aggregate(&mut self, round: u64, updates: &[ModelUpdate], engine: &AggregationEngine) -> Result<Vec<usize>, String> {
+        let mut sizes = vec![0; self.models.len()];
+        for (cluster, model) in self.models.iter_mut().enumerate() {
+            let members: Vec<ModelUpdate> = updates
+                .iter()
+                .filter(|u| {
+                    self.assignments.get(&u.client_id) == Some(&cluster)
+                        && self.assigned_round.get(&u.client_id) == Some(&round)
+                        && u.gradients.len() == model.len()
+                })
+                .cloned()
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            *model = engine.weighted_average(&members)?;
+            sizes[cluster] = members.len();
+        }
+        Ok(sizes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, gradients: Vec<f64>, data_size: usize) -> ModelUpdate {
+        ModelUpdate::for_test(client_id, gradients, data_size)
+    }
+
+    #[test]
+    fn test_clients_train_their_best_cluster() {
+        let mut state = ClusteringState::new(vec![vec![0.0, 0.0], vec![10.0, 10.0]]).unwrap();
+        let report = |id: &str, losses: Vec<f64>| ClusterLossReport { client_id: id.to_string(), round: 3, losses };
+        assert_eq!(state.assign(&report("a", vec![0.2, 3.0])).unwrap(), 0);
+        assert_eq!(state.assign(&report("b", vec![4.0, 0.1])).unwrap(), 1);
+        assert_eq!(state.assign(&report("c", vec![5.0, 0.3])).unwrap(), 1);
+        assert!(state.assign(&report("d", vec![1.0])).is_err());
+
+        let updates = vec![
+            update("a", vec![1.0, 1.0], 10),
+            update("b", vec![12.0, 12.0], 10),
+            update("c", vec![9.0, 9.0], 30),
+            // Unassigned clients only contribute to the global model
+            update("e", vec![100.0, 100.0], 10),
+        ];
+        let sizes = state.aggregate(3, &updates, &AggregationEngine::new()).unwrap();
+        assert_eq!(sizes, vec![1, 2]);
+        assert_eq!(state.models()[0], vec![1.0, 1.0]);
+        assert!((state.models()[1][0] - 9.75).abs() < 1e-12);
+
+        // Assignments from an earlier round do not count
+        let before = state.models()[1].clone();
+        state.aggregate(4, &updates, &AggregationEngine::new()).unwrap();
+        assert_eq!(state.models()[1], before);
+    }
+}
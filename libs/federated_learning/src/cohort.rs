@@ -0,0 +1,134 @@
+// Training cohort for rare disease models: the cases a site contributes with their
+// feature rows and labels. Built in one pass so rows and labels stay aligned and
+// the opt-out registry is applied to the cases and to the records behind them.
+// Cases of deceased patients can be left out too, for models of outcomes that only
+// make sense for living patients.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+use medical_data::rare_diseases::RareDiseaseCase;
+use medical_data::MedicalDataset;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrainingCohort {
+    // Row i and label i belong to the same case
+    pub features: FeatureMatrix,
+    pub labels: LabelSet,
+    pub opt_out: OptOutStats,
+    pub deceased_excluded: u64,
+}
+
+pub struct CohortBuilder {
+    extractor: FeatureExtractor,
+    labeler: Labeler,
+    opt_out: OptOutRegistry,
+    exclude_deceased: bool,
+}
+
+impl CohortBuilder {
+    pub fn new(schema: FeatureSchema, labels: LabelSchema, opt_out: OptOutRegistry) -> Self {
+        CohortBuilder {
+            extractor: FeatureExtractor::new(schema).with_opt_out(opt_out.clone()),
+            labeler: Labeler::new(labels),
+            opt_out,
+            exclude_deceased: false,
+        }
+    }
+
+    // Deceased per the case outcome or the patient's record in the dataset
+    pub fn with_exclude_deceased(mut self, exclude: bool) -> Self {
+        self.exclude_deceased = exclude;
+        self
+    }
+
+    pub fn build(&self, cases: &[RareDiseaseCase], dataset: &MedicalDataset) -> Result<TrainingCohort, String> {
+        let (dataset, mut stats) = self.opt_out.filter_dataset(dataset);
+        let (cases, case_stats) = self.opt_out.filter_cases(cases);
+        stats.merge(&case_stats)?;
+        let total = cases.len();
+        let cases: Vec<RareDiseaseCase> = if self.exclude_deceased {
+            cases
+                .into_iter()
+                .filter(|c| !c.is_deceased() && !dataset.resolve_patient(&c.patient.id).is_some_and(|p| p.is_deceased()))
+                .collect()
+        } else {
+            cases
+        };
+        let deceased_excluded = (total - cases.len()) as u64;
+
+        let schema = self.extractor.schema();
+        let features = FeatureMatrix {
+            schema_fingerprint: schema.fingerprint(),
+            feature_names: schema.feature_names(),
+            patient_ids: cases.iter().map(|c| c.patient.id.clone()).collect(),
+            rows: cases.iter().map(|c| self.extractor.extract_case(c, &dataset)).collect(),
+        };
+        let labels = self.labeler.label_cases(&cases, &dataset);
+        Ok(TrainingCohort { features, labels, opt_out: stats, deceased_excluded })
+    }
+
+    // Changes whenever any input that decides which cases and columns make up the
+    // cohort changes
+    pub fn definition_hash(&self) -> String {
+        let labels = serde_json::to_string(self.labeler.schema()).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(b"fl-cohort-definition");
+        for field in [&self.extractor.schema().fingerprint(), &labels, &self.opt_out.fingerprint()] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update([self.exclude_deceased as u8]);
+        format!("{:x}", hasher.finalize())
+    }
+
+    // What the site submits to the coordinator alongside its update
+    pub fn report(&self, site_id: &str, round: u64, cohort: &TrainingCohort) -> OptOutReport {
+        OptOutReport { site_id: site_id.to_string(), round, stats: cohort.opt_out.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::rare_diseases::initialize_rare_disease_database;
+    use medical_data::*;
+
+    #[test]
+    fn test_opted_out_patients_never_reach_the_extract() {
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        for id in ["p1", "p2", "p3"] {
+            dataset.patients.push(Patient::new(id.to_string()));
+            let mut condition = Condition::new(format!("c-{}", id), create_reference(&format!("Patient/{}", id), None));
+            condition.set_code(CodeableConcept::icd10("E11").unwrap());
+            dataset.conditions.push(condition);
+        }
+
+        // The registry arrives as pseudonyms computed at another site
+        let mut origin = OptOutRegistry::new("salt");
+        let pseudonym = origin.insert_patient("p2");
+        let mut registry = OptOutRegistry::new("salt");
+        registry.insert_pseudonym(&pseudonym);
+        assert_eq!(registry.fingerprint(), origin.fingerprint());
+
+        let schema = FeatureSchema::new(2024).with_condition("E11");
+        let extractor = FeatureExtractor::new(schema.clone()).with_opt_out(registry.clone());
+        let (matrix, stats) = extractor.extract_dataset_with_stats(&dataset);
+        assert_eq!(matrix.patient_ids, vec!["p1".to_string(), "p3".to_string()]);
+        assert_eq!((stats.patients_seen, stats.patients_excluded, stats.conditions_excluded), (3, 1, 1));
+
+        let db = initialize_rare_disease_database();
+        let mut cases = vec![db.generate_synthetic_case("ORPHA:399").unwrap(), db.generate_synthetic_case("ORPHA:586").unwrap()];
+        cases[0].patient.id = "p2".to_string();
+        let builder = CohortBuilder::new(schema, LabelSchema::diagnosed_vs_undiagnosed(), registry);
+        let cohort = builder.build(&cases, &dataset).unwrap();
+        assert_eq!(cohort.labels.case_ids, vec![cases[1].case_id.clone()]);
+        assert_eq!(cohort.features.rows.len(), 1);
+        assert_eq!((cohort.opt_out.patients_excluded, cohort.opt_out.cases_excluded), (1, 1));
+
+        dataset.patients[0].set_deceased(None);
+        cases[1].patient.id = "p1".to_string();
+        let cohort = builder.with_exclude_deceased(true).build(&cases, &dataset).unwrap();
+        assert!(cohort.labels.case_ids.is_empty());
+        assert_eq!(cohort.deceased_excluded, 1);
+    }
+}
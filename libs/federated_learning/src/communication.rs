@@ -1,2 +1,235 @@
-// Placeholder for communication module
-pub struct CommunicationPlaceholder;
\ No newline at end of file
+// Chunked transfer of updates and models over the Internet Computer, whose ingress
+// and inter-canister messages are capped at 2 MiB. A payload is split into
+// `GradientChunk`s small enough to fit one message each; the chunk index is its
+// sequence number. The receiver answers every chunk with an acknowledgement, and the
+// sender retransmits whatever is unacknowledged once its backoff has elapsed, with a
+// bounded number of chunks in flight and a bounded number of attempts per chunk.
+//
+// Uploads are reassembled and checked against the client's signed commitment by
+// `ChunkedUpload`. Downloads use the same machinery in the other direction: the
+// coordinator commits to the weights it sends under `COORDINATOR_ID`, and the client
+// reassembles them with a `ChunkedUpload` built from that commitment.
+
+use crate::*;
+
+pub const IC_MAX_MESSAGE_BYTES: usize = 2 * 1024 * 1024;
+// Headroom for the Candid envelope, method name and the chunk's other fields
+pub const MESSAGE_OVERHEAD_BYTES: usize = 64 * 1024;
+// Sender id of chunks the coordinator sends to clients
+pub const COORDINATOR_ID: &str = "coordinator";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkAck {
+    pub client_id: String,
+    pub round: u64,
+    pub index: u32,
+    // A rejected chunk is retransmitted like a lost one
+    pub accepted: bool,
+    pub error: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RetryPolicy {
+    // Attempts per chunk, including the first
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub backoff_multiplier: f64,
+    // Chunks sent but not yet acknowledged
+    pub max_in_flight: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ChunkState {
+    Pending,
+    InFlight { attempts: u32, retry_at_ms: u64 },
+    Acked,
+}
+
+// Sender side of one chunked transfer
+#[derive(Clone, Debug)]
+pub struct ChunkSender {
+    chunks: Vec<GradientChunk>,
+    states: Vec<ChunkState>,
+    policy: RetryPolicy,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            backoff_multiplier: 2.0,
+            max_in_flight: 4,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 || self.max_in_flight == 0 {
+            return Err("max_attempts and max_in_flight must be at least 1".to_string());
+        }
+        if !(self.backoff_multiplier.is_finite() && self.backoff_multiplier >= 1.0) {
+            return Err("backoff_multiplier must be at least 1".to_string());
+        }
+        if self.initial_backoff_ms > self.max_backoff_ms {
+            return Err("initial_backoff_ms exceeds max_backoff_ms".to_string());
+        }
+        Ok(())
+    }
+
+    // Wait before retransmitting after the given (1-based) attempt
+    pub fn backoff_ms(&self, attempt: u32) -> u64 {
+        let backoff = self.initial_backoff_ms as f64 * self.backoff_multiplier.powi(attempt.saturating_sub(1) as i32);
+        backoff.min(self.max_backoff_ms as f64) as u64
+    }
+}
+
+// Largest chunk, in f64 values, that fits one message of `message_limit_bytes`
+pub fn max_chunk_values(message_limit_bytes: usize) -> u32 {
+    let payload = message_limit_bytes.saturating_sub(MESSAGE_OVERHEAD_BYTES);
+    (payload / std::mem::size_of::<f64>()).clamp(1, u32::MAX as usize) as u32
+}
+
+// Commitment and chunks of the weights the coordinator sends a client
+pub fn prepare_download(round: u64, weights: &[f64], chunk_size: u32) -> Result<(UpdateCommitment, Vec<GradientChunk>), String> {
+    let commitment = UpdateCommitment::for_gradients(COORDINATOR_ID, round, weights, chunk_size)?;
+    Ok((commitment, split_into_chunks(COORDINATOR_ID, round, weights, chunk_size)))
+}
+
+impl ChunkAck {
+    pub fn for_chunk(chunk: &GradientChunk, result: Result<(), String>) -> Self {
+        ChunkAck {
+            client_id: chunk.client_id.clone(),
+            round: chunk.round,
+            index: chunk.index,
+            accepted: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+impl ChunkedUpload {
+    // Adds the chunk and acknowledges it either way, so the sender knows whether to resend
+    pub fn receive(&mut self, chunk: GradientChunk) -> ChunkAck {
+        let ack = ChunkAck::for_chunk(&chunk, Ok(()));
+        match self.add_chunk(chunk) {
+            Ok(()) => ack,
+            Err(error) => ChunkAck { accepted: false, error: Some(error), ..ack },
+        }
+    }
+}
+
+impl ChunkSender {
+    pub fn new(chunks: Vec<GradientChunk>, policy: RetryPolicy) -> Result<Self, String> {
+        policy.validate()?;
+        if chunks.iter().enumerate().any(|(i, c)| c.index as usize != i) {
+            return Err("Chunks must be numbered 0..n in order".to_string());
+        }
+        let states = vec![ChunkState::Pending; chunks.len()];
+        Ok(ChunkSender { chunks, states, policy })
+    }
+
+    // Chunks to send at `now_ms`: retransmissions whose backoff has elapsed, then new
+    // chunks while the in-flight window has room. Fails once a chunk has used up its
+    // attempts without an acknowledgement.
+    pub fn poll(&mut self, now_ms: u64) -> Result<Vec<GradientChunk>, String> {
+        let mut due = Vec::new();
+        for (index, state) in self.states.iter_mut().enumerate() {
+            if let ChunkState::InFlight { attempts, retry_at_ms } = state {
+                if now_ms < *retry_at_ms {
+                    continue;
+                }
+                if *attempts >= self.policy.max_attempts {
+                    return Err(format!("Chunk {} was not acknowledged after {} attempts", index, attempts));
+                }
+                *attempts += 1;
+                *retry_at_ms = now_ms + self.policy.backoff_ms(*attempts);
+                due.push(self.chunks[index].clone());
+            }
+        }
+
+        let mut in_flight = self.states.iter().filter(|s| matches!(s, ChunkState::InFlight { .. })).count();
+        for (index, state) in self.states.iter_mut().enumerate() {
+            if in_flight >= self.policy.max_in_flight {
+                break;
+            }
+            if *state == ChunkState::Pending {
+                *state = ChunkState::InFlight { attempts: 1, retry_at_ms: now_ms + self.policy.backoff_ms(1) };
+                due.push(self.chunks[index].clone());
+                in_flight += 1;
+            }
+        }
+        Ok(due)
+    }
+
+    // A rejection makes the chunk due again immediately, still counting the attempt
+    pub fn acknowledge(&mut self, ack: &ChunkAck) -> Result<(), String> {
+        let chunk = self.chunks.get(ack.index as usize).ok_or(format!("Ack for unknown chunk {}", ack.index))?;
+        if chunk.client_id != ack.client_id || chunk.round != ack.round {
+            return Err("Ack belongs to a different transfer".to_string());
+        }
+        let state = &mut self.states[ack.index as usize];
+        match state {
+            ChunkState::InFlight { retry_at_ms, .. } if !ack.accepted => *retry_at_ms = 0,
+            ChunkState::InFlight { .. } | ChunkState::Acked => *state = ChunkState::Acked,
+            ChunkState::Pending => return Err(format!("Ack for chunk {} that was never sent", ack.index)),
+        }
+        Ok(())
+    }
+
+    pub fn unacknowledged(&self) -> Vec<u32> {
+        self.states.iter().enumerate().filter(|(_, s)| **s != ChunkState::Acked).map(|(i, _)| i as u32).collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.states.iter().all(|s| *s == ChunkState::Acked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lost_and_rejected_chunks_are_retransmitted() {
+        let gradients: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let commitment = UpdateCommitment::for_gradients("hospital_a", 1, &gradients, 3).unwrap();
+        let chunks = split_into_chunks("hospital_a", 1, &gradients, 3);
+        let policy = RetryPolicy { max_attempts: 3, initial_backoff_ms: 100, max_backoff_ms: 1_000, backoff_multiplier: 2.0, max_in_flight: 2 };
+        let mut sender = ChunkSender::new(chunks, policy).unwrap();
+        let mut upload = ChunkedUpload::new(commitment).unwrap();
+
+        // The window allows two chunks; chunk 0 is lost in transit, chunk 1 arrives corrupted
+        let sent = sender.poll(0).unwrap();
+        assert_eq!(sent.iter().map(|c| c.index).collect::<Vec<_>>(), vec![0, 1]);
+        let mut corrupted = sent[1].clone();
+        corrupted.values.pop();
+        let ack = upload.receive(corrupted);
+        assert!(!ack.accepted);
+        sender.acknowledge(&ack).unwrap();
+
+        // Chunk 1 is resent at once; chunk 0 waits out its backoff
+        assert_eq!(sender.poll(50).unwrap().iter().map(|c| c.index).collect::<Vec<_>>(), vec![1]);
+        let resent = sender.poll(100).unwrap();
+        assert_eq!(resent.iter().map(|c| c.index).collect::<Vec<_>>(), vec![0]);
+
+        let mut now = 100;
+        while !sender.is_complete() {
+            now += 100;
+            for chunk in sender.poll(now).unwrap().into_iter().chain(resent.iter().cloned()) {
+                let ack = upload.receive(chunk);
+                sender.acknowledge(&ack).unwrap();
+            }
+        }
+        assert_eq!(upload.finish().unwrap(), gradients);
+
+        // A receiver that never answers exhausts the attempts
+        let mut sender = ChunkSender::new(split_into_chunks("hospital_a", 1, &gradients, 10), RetryPolicy::default()).unwrap();
+        let result = (0..10).map(|i| sender.poll(i * 60_000)).find(|r| r.is_err());
+        assert!(result.is_some());
+        assert_eq!(max_chunk_values(IC_MAX_MESSAGE_BYTES), 253_952);
+    }
+}
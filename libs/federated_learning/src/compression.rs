@@ -3,6 +3,7 @@
 
 use crate::*;
 use std::collections::HashMap;
+use nalgebra::DMatrix;
 use rand::Rng;
 
 #[derive(Clone, Debug)]
@@ -43,7 +44,14 @@ impl QuantizationCompressor {
         let mut quantized = Vec::with_capacity(gradients.len());
         let mut total_error = 0.0;
         
-        for &gradient in gradients {
+        // Deterministic magnitudes are computed in one vectorised pass
+        let mut magnitudes = Vec::new();
+        if !self.stochastic {
+            magnitudes = vec![0.0; gradients.len()];
+            kernels::quantize_magnitudes(gradients, norm, (levels - 1) as f64, &mut magnitudes);
+        }
+        
+        for (i, &gradient) in gradients.iter().enumerate() {
             let normalized = gradient / norm;
             let abs_normalized = normalized.abs();
             
@@ -60,7 +68,7 @@ impl QuantizationCompressor {
                 }
             } else {
                 // Deterministic quantization
-                (abs_normalized * (levels - 1) as f64).round() as u32
+                magnitudes[i] as u32
             };
             
             // Store sign information in MSB for signed quantization
@@ -149,7 +157,7 @@ impl QuantizationCompressor {
     }
 
     fn compute_l2_norm(&self, vector: &[f64]) -> f64 {
-        vector.iter().map(|&x| x * x).sum::<f64>().sqrt()
+        kernels::l2_norm(vector)
     }
 }
 
@@ -177,6 +185,11 @@ impl SparsificationCompressor {
         }
     }
 
+    // Drops a client's accumulated residual, e.g. when it rejoins after missing rounds
+    pub fn reset_error_feedback(&mut self, client_id: &str) {
+        self.momentum_buffer.remove(client_id);
+    }
+
     // Deep Gradient Compression with error feedback
     pub fn dgc_compress(&mut self, gradients: &[f64], client_id: &str) -> (SparseGradients, CompressionStats) {
         let start_time = std::time::Instant::now();
@@ -193,12 +206,7 @@ impl SparsificationCompressor {
             .collect();
         
         // Apply sparsification
-        let sparse_gradients = match self.method {
-            SparsificationMethod::TopK => self.top_k_sparsify(&accumulated_gradients),
-            SparsificationMethod::RandomK => self.random_k_sparsify(&accumulated_gradients),
-            SparsificationMethod::ThresholdBased => self.threshold_sparsify(&accumulated_gradients),
-            SparsificationMethod::AdaptiveThreshold => self.adaptive_threshold_sparsify(&accumulated_gradients),
-        };
+        let sparse_gradients = self.sparsify(&accumulated_gradients);
         
         // Update momentum buffer with residual (error feedback)
         for (i, &sparse_grad) in sparse_gradients.values.iter().enumerate() {
@@ -223,6 +231,15 @@ impl SparsificationCompressor {
         (sparse_gradients, stats)
     }
 
+    fn sparsify(&self, gradients: &[f64]) -> SparseGradients {
+        match self.method {
+            SparsificationMethod::TopK => self.top_k_sparsify(gradients),
+            SparsificationMethod::RandomK => self.random_k_sparsify(gradients),
+            SparsificationMethod::ThresholdBased => self.threshold_sparsify(gradients),
+            SparsificationMethod::AdaptiveThreshold => self.adaptive_threshold_sparsify(gradients),
+        }
+    }
+
     fn top_k_sparsify(&self, gradients: &[f64]) -> SparseGradients {
         let k = ((1.0 - self.sparsity_ratio) * gradients.len() as f64) as usize;
         
@@ -365,6 +382,10 @@ impl HybridCompressor {
         }
     }
 
+    pub fn reset_error_feedback(&mut self, client_id: &str) {
+        self.sparsifier.reset_error_feedback(client_id);
+    }
+
     pub fn compress(&mut self, gradients: &[f64], client_id: &str) -> (HybridCompressedGradients, CompressionStats) {
         match self.compression_strategy {
             CompressionStrategy::QuantizationFirst => {
@@ -506,13 +527,491 @@ impl HybridCompressor {
     }
 }
 
+// signSGD (Bernstein et al., 2018): one bit per coordinate on the wire; the server
+// takes a majority vote over the clients' signs
+pub struct SignCompressor;
+
+impl SignCompressor {
+    // Zero gradients are sent as +1, so every coordinate fits in a single bit
+    pub fn compress(gradients: &[f64]) -> (SignGradients, CompressionStats) {
+        let start_time = std::time::Instant::now();
+        let mut negative = vec![0u8; gradients.len().div_ceil(8)];
+        for (i, &g) in gradients.iter().enumerate() {
+            if g < 0.0 {
+                negative[i / 8] |= 1 << (i % 8);
+            }
+        }
+        let compression_time = start_time.elapsed().as_secs_f64();
+
+        let original_size = std::mem::size_of_val(gradients);
+        let compressed_size = negative.len();
+        let stats = CompressionStats {
+            original_size,
+            compressed_size,
+            compression_ratio: compressed_size as f64 / original_size.max(1) as f64,
+            compression_time,
+            decompression_time: 0.0,
+            accuracy_loss: 0.0,
+        };
+        (SignGradients { len: gradients.len(), negative }, stats)
+    }
+
+    // ±1 per coordinate, the dense form ModelUpdate carries
+    pub fn decompress(signs: &SignGradients) -> Vec<f64> {
+        (0..signs.len)
+            .map(|i| if signs.negative[i / 8] & (1 << (i % 8)) != 0 { -1.0 } else { 1.0 })
+            .collect()
+    }
+}
+
+// FetchSGD (Rothchild et al., 2020): each client sends a Count Sketch of its gradient,
+// whose size is fixed by rows x columns rather than by the model, so even top-k
+// indices need not be sent. Sketches are linear, so the server sums them, keeps
+// momentum and error feedback in sketch space, and recovers the k heaviest
+// coordinates. Every client must use the same dimensions and seed.
+pub struct CountSketchCompressor {
+    pub rows: usize,
+    pub columns: usize,
+    pub seed: u64,
+    // Server-side state: momentum decay and the sketched momentum and error
+    pub momentum: f64,
+    momentum_sketch: Option<CountSketch>,
+    error_sketch: Option<CountSketch>,
+}
+
+impl CountSketchCompressor {
+    pub fn new(rows: usize, columns: usize, seed: u64, momentum: f64) -> Self {
+        CountSketchCompressor {
+            rows,
+            columns,
+            seed,
+            momentum,
+            momentum_sketch: None,
+            error_sketch: None,
+        }
+    }
+
+    // Client side
+    pub fn compress(&self, gradients: &[f64]) -> (CountSketch, CompressionStats) {
+        let start_time = std::time::Instant::now();
+        let mut sketch = CountSketch::new(self.rows, self.columns, gradients.len(), self.seed);
+        sketch.accumulate(gradients);
+        let compression_time = start_time.elapsed().as_secs_f64();
+
+        let original_size = std::mem::size_of_val(gradients);
+        let compressed_size = sketch.table.len() * 8;
+        let stats = CompressionStats {
+            original_size,
+            compressed_size,
+            compression_ratio: compressed_size as f64 / original_size.max(1) as f64,
+            compression_time,
+            decompression_time: 0.0,
+            accuracy_loss: 0.0,
+        };
+        (sketch, stats)
+    }
+
+    // Server side: averages the client sketches, folds them into the sketched
+    // momentum and error, and extracts the top-k update. The extracted coordinates are
+    // subtracted from both, so what was not sent this round is sent later.
+    pub fn aggregate(&mut self, sketches: &[CountSketch], k: usize) -> Result<SparseGradients, String> {
+        let first = sketches.first().ok_or("No sketches to aggregate")?;
+        let mut mean = CountSketch::new(first.rows, first.columns, first.dimension, first.seed);
+        for sketch in sketches {
+            mean.merge(sketch)?;
+        }
+        mean.scale(1.0 / sketches.len() as f64);
+
+        let momentum = self.momentum_sketch.get_or_insert_with(|| mean.zeroed());
+        momentum.scale(self.momentum);
+        momentum.merge(&mean)?;
+        let error = self.error_sketch.get_or_insert_with(|| mean.zeroed());
+        error.merge(momentum)?;
+
+        let update = error.unsketch_top_k(k);
+        let mut sent = mean.zeroed();
+        sent.accumulate_sparse(&update);
+        sent.scale(-1.0);
+        error.merge(&sent)?;
+        momentum.merge(&sent)?;
+        Ok(update)
+    }
+
+    pub fn reset(&mut self) {
+        self.momentum_sketch = None;
+        self.error_sketch = None;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CountSketch {
+    pub rows: usize,
+    pub columns: usize,
+    // Length of the sketched vector
+    pub dimension: usize,
+    pub seed: u64,
+    // Row-major, rows x columns
+    pub table: Vec<f64>,
+}
+
+// SplitMix64 finaliser; a fixed function of (seed, row, index) so that every client
+// hashes a coordinate to the same bucket and sign
+fn sketch_hash(seed: u64, row: usize, index: usize) -> u64 {
+    let mut z = seed ^ ((row as u64) << 48) ^ (index as u64);
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl CountSketch {
+    pub fn new(rows: usize, columns: usize, dimension: usize, seed: u64) -> Self {
+        let rows = rows.max(1);
+        let columns = columns.max(1);
+        CountSketch { rows, columns, dimension, seed, table: vec![0.0; rows * columns] }
+    }
+
+    fn zeroed(&self) -> Self {
+        CountSketch::new(self.rows, self.columns, self.dimension, self.seed)
+    }
+
+    fn bucket(&self, row: usize, index: usize) -> (usize, f64) {
+        let hash = sketch_hash(self.seed, row, index);
+        let column = ((hash >> 1) % self.columns as u64) as usize;
+        let sign = if hash & 1 == 0 { 1.0 } else { -1.0 };
+        (row * self.columns + column, sign)
+    }
+
+    pub fn accumulate(&mut self, values: &[f64]) {
+        for (index, &value) in values.iter().enumerate().take(self.dimension) {
+            if value != 0.0 {
+                self.add(index, value);
+            }
+        }
+    }
+
+    fn accumulate_sparse(&mut self, sparse: &SparseGradients) {
+        for (&index, &value) in sparse.indices.iter().zip(sparse.values.iter()) {
+            if index < self.dimension {
+                self.add(index, value);
+            }
+        }
+    }
+
+    fn add(&mut self, index: usize, value: f64) {
+        for row in 0..self.rows {
+            let (cell, sign) = self.bucket(row, index);
+            self.table[cell] += sign * value;
+        }
+    }
+
+    pub fn merge(&mut self, other: &CountSketch) -> Result<(), String> {
+        if (self.rows, self.columns, self.dimension, self.seed) != (other.rows, other.columns, other.dimension, other.seed) {
+            return Err("Count sketches differ in shape or seed".to_string());
+        }
+        kernels::axpy(1.0, &other.table, &mut self.table);
+        Ok(())
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        self.table.iter_mut().for_each(|v| *v *= factor);
+    }
+
+    // Median over rows of the signed bucket value
+    pub fn estimate(&self, index: usize) -> f64 {
+        let mut estimates: Vec<f64> = (0..self.rows)
+            .map(|row| {
+                let (cell, sign) = self.bucket(row, index);
+                sign * self.table[cell]
+            })
+            .collect();
+        estimates.sort_by(|a, b| a.total_cmp(b));
+        let mid = estimates.len() / 2;
+        if estimates.len().is_multiple_of(2) {
+            (estimates[mid - 1] + estimates[mid]) / 2.0
+        } else {
+            estimates[mid]
+        }
+    }
+
+    // The k coordinates with the largest estimated magnitude, in index order
+    pub fn unsketch_top_k(&self, k: usize) -> SparseGradients {
+        let mut estimates: Vec<(usize, f64)> = (0..self.dimension).map(|i| (i, self.estimate(i))).collect();
+        let k = k.min(estimates.len());
+        if k < estimates.len() {
+            estimates.select_nth_unstable_by(k, |a, b| b.1.abs().total_cmp(&a.1.abs()));
+            estimates.truncate(k);
+        }
+        estimates.retain(|(_, v)| *v != 0.0);
+        estimates.sort_by_key(|(i, _)| *i);
+        SparseGradients {
+            indices: estimates.iter().map(|(i, _)| *i).collect(),
+            values: estimates.iter().map(|(_, v)| *v).collect(),
+        }
+    }
+}
+
+// PowerSGD (Vogels et al., 2019): the gradient is laid out as a near-square matrix M
+// and sent as a rank-r approximation P Q^T, costing (rows + columns) x r values. One
+// power iteration per round: P = M Q, orthonormalise P, Q = M^T P. Each client's Q is
+// kept and used to start its next round's iteration, which over rounds converges to
+// the top singular subspace; what the approximation drops is fed back into the
+// client's next gradient.
+pub struct PowerSgdCompressor {
+    pub rank: usize,
+    // Last factors sent by each client; their Q warm-starts the next round
+    factors: HashMap<String, LowRankGradients>,
+    residuals: HashMap<String, Vec<f64>>,
+}
+
+impl PowerSgdCompressor {
+    pub fn new(rank: usize) -> Self {
+        PowerSgdCompressor { rank: rank.max(1), factors: HashMap::new(), residuals: HashMap::new() }
+    }
+
+    pub fn factors(&self, client_id: &str) -> Option<&LowRankGradients> {
+        self.factors.get(client_id)
+    }
+
+    // Drops a client's warm start and residual, e.g. when it rejoins after missing rounds
+    pub fn reset(&mut self, client_id: &str) {
+        self.factors.remove(client_id);
+        self.residuals.remove(client_id);
+    }
+
+    pub fn compress(&mut self, client_id: &str, gradients: &[f64]) -> (LowRankGradients, CompressionStats) {
+        let start_time = std::time::Instant::now();
+        let len = gradients.len();
+        let columns = ((len as f64).sqrt().ceil() as usize).max(1);
+        let rows = len.div_ceil(columns).max(1);
+        let rank = self.rank.min(rows).min(columns);
+
+        // Coordinate i sits at (i % rows, i / rows); the tail is zero padded
+        let mut padded = vec![0.0; rows * columns];
+        padded[..len].copy_from_slice(gradients);
+        if let Some(residual) = self.residuals.get(client_id).filter(|r| r.len() == len) {
+            kernels::add_assign(&mut padded[..len], residual);
+        }
+        let matrix = DMatrix::from_column_slice(rows, columns, &padded);
+
+        let q = match self.factors.get(client_id) {
+            Some(previous) if (previous.rows, previous.columns, previous.rank) == (rows, columns, rank) => {
+                DMatrix::from_column_slice(columns, rank, &previous.q)
+            }
+            _ => {
+                let mut rng = rand::thread_rng();
+                DMatrix::from_fn(columns, rank, |_, _| rng.gen::<f64>() - 0.5)
+            }
+        };
+        let p = (&matrix * q).qr().q();
+        let q = matrix.transpose() * &p;
+        let approximation = &p * q.transpose();
+
+        let residual: Vec<f64> = (&matrix - &approximation).as_slice()[..len].to_vec();
+        let accuracy_loss = kernels::l2_norm(&residual) / kernels::l2_norm(&padded[..len]).max(f64::EPSILON);
+        self.residuals.insert(client_id.to_string(), residual);
+
+        let compressed = LowRankGradients {
+            len,
+            rows,
+            columns,
+            rank,
+            p: p.as_slice().to_vec(),
+            q: q.as_slice().to_vec(),
+        };
+        self.factors.insert(client_id.to_string(), compressed.clone());
+
+        let original_size = std::mem::size_of_val(gradients);
+        let compressed_size = (compressed.p.len() + compressed.q.len()) * 8;
+        let stats = CompressionStats {
+            original_size,
+            compressed_size,
+            compression_ratio: compressed_size as f64 / original_size.max(1) as f64,
+            compression_time: start_time.elapsed().as_secs_f64(),
+            decompression_time: 0.0,
+            accuracy_loss,
+        };
+        (compressed, stats)
+    }
+
+    pub fn decompress(&self, compressed: &LowRankGradients) -> Vec<f64> {
+        compressed.to_dense()
+    }
+}
+
+// A compressor as seen by error feedback: `encode` produces what goes on the wire and
+// `decode` the dense vector the server will reconstruct from it
+pub trait Compressor {
+    type Compressed;
+
+    fn encode(&mut self, gradients: &[f64]) -> (Self::Compressed, CompressionStats);
+    fn decode(&self, compressed: &Self::Compressed, len: usize) -> Vec<f64>;
+}
+
+impl Compressor for QuantizationCompressor {
+    // Quantized levels and the norm they are relative to
+    type Compressed = (Vec<u32>, f64);
+
+    fn encode(&mut self, gradients: &[f64]) -> (Self::Compressed, CompressionStats) {
+        let start_time = std::time::Instant::now();
+        let (quantized, norm, error) = self.qsgd_compress(gradients);
+        let original_size = std::mem::size_of_val(gradients);
+        let compressed_size = (quantized.len() * self.bits as usize).div_ceil(8) + 8;
+        let stats = CompressionStats {
+            original_size,
+            compressed_size,
+            compression_ratio: compressed_size as f64 / original_size.max(1) as f64,
+            compression_time: start_time.elapsed().as_secs_f64(),
+            decompression_time: 0.0,
+            accuracy_loss: error,
+        };
+        ((quantized, norm), stats)
+    }
+
+    fn decode(&self, compressed: &Self::Compressed, _len: usize) -> Vec<f64> {
+        self.qsgd_decompress(&compressed.0, compressed.1)
+    }
+}
+
+// Without a buffer of its own; wrap in ErrorFeedback, or use dgc_compress
+impl Compressor for SparsificationCompressor {
+    type Compressed = SparseGradients;
+
+    fn encode(&mut self, gradients: &[f64]) -> (Self::Compressed, CompressionStats) {
+        let start_time = std::time::Instant::now();
+        let sparse = self.sparsify(gradients);
+        let original_size = std::mem::size_of_val(gradients);
+        let compressed_size = sparse.indices.len() * 12;
+        let stats = CompressionStats {
+            original_size,
+            compressed_size,
+            compression_ratio: compressed_size as f64 / original_size.max(1) as f64,
+            compression_time: start_time.elapsed().as_secs_f64(),
+            decompression_time: 0.0,
+            accuracy_loss: self.compute_sparsification_error(gradients, &sparse),
+        };
+        (sparse, stats)
+    }
+
+    fn decode(&self, compressed: &Self::Compressed, len: usize) -> Vec<f64> {
+        self.decompress(compressed, len)
+    }
+}
+
+// EF-signSGD (Karimireddy et al., 2019): the signs are scaled by the mean magnitude,
+// so the residual measures what the sign lost rather than the raw gradient
+impl Compressor for SignCompressor {
+    type Compressed = (SignGradients, f64);
+
+    fn encode(&mut self, gradients: &[f64]) -> (Self::Compressed, CompressionStats) {
+        let (signs, mut stats) = SignCompressor::compress(gradients);
+        let scale = gradients.iter().map(|g| g.abs()).sum::<f64>() / gradients.len().max(1) as f64;
+        stats.compressed_size += 8;
+        stats.compression_ratio = stats.compressed_size as f64 / stats.original_size.max(1) as f64;
+        ((signs, scale), stats)
+    }
+
+    fn decode(&self, compressed: &Self::Compressed, _len: usize) -> Vec<f64> {
+        SignCompressor::decompress(&compressed.0).into_iter().map(|s| s * compressed.1).collect()
+    }
+}
+
+impl Compressor for CountSketchCompressor {
+    type Compressed = CountSketch;
+
+    fn encode(&mut self, gradients: &[f64]) -> (Self::Compressed, CompressionStats) {
+        self.compress(gradients)
+    }
+
+    // Every coordinate's estimate; the top-k selection is left to the server
+    fn decode(&self, compressed: &Self::Compressed, len: usize) -> Vec<f64> {
+        (0..len.min(compressed.dimension)).map(|i| compressed.estimate(i)).collect()
+    }
+}
+
+// Error feedback (Seide et al., 2014; Stich et al., 2018) around any compressor: what
+// compression dropped from a client's update is added back into its next one, so
+// nothing is lost, only delayed. Residuals are kept per client.
+pub struct ErrorFeedback<C: Compressor> {
+    compressor: C,
+    residuals: HashMap<String, Vec<f64>>,
+}
+
+impl<C: Compressor> ErrorFeedback<C> {
+    pub fn new(compressor: C) -> Self {
+        ErrorFeedback { compressor, residuals: HashMap::new() }
+    }
+
+    pub fn compressor(&self) -> &C {
+        &self.compressor
+    }
+
+    pub fn compressor_mut(&mut self) -> &mut C {
+        &mut self.compressor
+    }
+
+    pub fn residual(&self, client_id: &str) -> Option<&[f64]> {
+        self.residuals.get(client_id).map(|r| r.as_slice())
+    }
+
+    // Drops a client's residual, e.g. when it rejoins after missing rounds
+    pub fn reset(&mut self, client_id: &str) {
+        self.residuals.remove(client_id);
+    }
+
+    pub fn compress(&mut self, client_id: &str, gradients: &[f64]) -> (C::Compressed, CompressionStats) {
+        let mut corrected = gradients.to_vec();
+        // A residual of another length belongs to a different model and is discarded
+        if let Some(residual) = self.residuals.get(client_id).filter(|r| r.len() == gradients.len()) {
+            kernels::add_assign(&mut corrected, residual);
+        }
+        let (compressed, stats) = self.compressor.encode(&corrected);
+        let sent = self.compressor.decode(&compressed, corrected.len());
+        let residual: Vec<f64> =
+            corrected.iter().enumerate().map(|(i, c)| c - sent.get(i).copied().unwrap_or(0.0)).collect();
+        self.residuals.insert(client_id.to_string(), residual);
+        (compressed, stats)
+    }
+}
+
 // Supporting data structures
 #[derive(Clone, Debug)]
+pub struct SignGradients {
+    pub len: usize,
+    // Bit i of the packed vector is set when coordinate i is negative
+    pub negative: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SparseGradients {
     pub indices: Vec<usize>,
     pub values: Vec<f64>,
 }
 
+// Rank-r factors of the gradient laid out as a rows x columns matrix, both column-major
+#[derive(Clone, Debug)]
+pub struct LowRankGradients {
+    // Length of the original gradient, before padding
+    pub len: usize,
+    pub rows: usize,
+    pub columns: usize,
+    pub rank: usize,
+    // rows x rank, orthonormal columns
+    pub p: Vec<f64>,
+    // columns x rank
+    pub q: Vec<f64>,
+}
+
+impl LowRankGradients {
+    pub fn to_dense(&self) -> Vec<f64> {
+        let p = DMatrix::from_column_slice(self.rows, self.rank, &self.p);
+        let q = DMatrix::from_column_slice(self.columns, self.rank, &self.q);
+        let mut dense = (p * q.transpose()).as_slice().to_vec();
+        dense.truncate(self.len);
+        dense
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct HybridCompressedGradients {
     pub method: String,
@@ -591,4 +1090,100 @@ pub fn benchmark_compression_methods(gradients: &[f64]) -> Vec<(String, Compress
     results
 }
 
-use rand::seq::SliceRandom;
\ No newline at end of file
+use rand::seq::SliceRandom;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_feedback_delays_rather_than_drops() {
+        let mut feedback = ErrorFeedback::new(SignCompressor);
+        let gradients = [1.0, -0.1, 0.1, -1.0];
+        let mut sent = vec![0.0; gradients.len()];
+        for _ in 0..50 {
+            let (compressed, _) = feedback.compress("site-a", &gradients);
+            kernels::add_assign(&mut sent, &feedback.compressor().decode(&compressed, gradients.len()));
+        }
+        // What was sent plus what is still owed equals everything that was submitted
+        let residual = feedback.residual("site-a").unwrap();
+        for i in 0..gradients.len() {
+            assert!((sent[i] + residual[i] - 50.0 * gradients[i]).abs() < 1e-9);
+        }
+        assert!(residual.iter().all(|r| r.abs() < 2.0));
+        assert!(feedback.residual("site-b").is_none());
+
+        let mut topk = ErrorFeedback::new(SparsificationCompressor::new(0.75, SparsificationMethod::TopK));
+        let (first, _) = topk.compress("site-a", &[4.0, 3.0, 0.0, 0.0]);
+        assert_eq!(first.indices, vec![0]);
+        let (second, _) = topk.compress("site-a", &[0.0, 1.0, 0.0, 0.0]);
+        assert_eq!((second.indices[0], second.values[0]), (1, 4.0));
+    }
+
+    #[test]
+    fn test_count_sketch_recovers_heavy_coordinates() {
+        let dimension = 10_000;
+        let mut compressor = CountSketchCompressor::new(5, 100, 42, 0.0);
+        let client_gradients: Vec<Vec<f64>> = (0..3)
+            .map(|c| {
+                let mut g: Vec<f64> = (0..dimension).map(|i| ((i * 7 + c) % 13) as f64 * 1e-4).collect();
+                g[17] = 5.0 + c as f64;
+                g[4242] = -8.0;
+                g
+            })
+            .collect();
+        let sketches: Vec<CountSketch> = client_gradients
+            .iter()
+            .map(|g| {
+                let (sketch, stats) = compressor.compress(g);
+                assert!(stats.compressed_size < stats.original_size / 10);
+                sketch
+            })
+            .collect();
+
+        let update = compressor.aggregate(&sketches, 2).unwrap();
+        assert_eq!(update.indices, vec![17, 4242]);
+        assert!((update.values[0] - 6.0).abs() < 0.05);
+        assert!((update.values[1] + 8.0).abs() < 0.05);
+
+        // Sent coordinates leave the error accumulator; nothing heavy remains next round
+        let zeros = compressor.compress(&vec![0.0; dimension]).0;
+        let next = compressor.aggregate(&[zeros], 1).unwrap();
+        assert!(next.values.iter().all(|v| v.abs() < 0.05));
+
+        let other_seed = CountSketchCompressor::new(5, 100, 7, 0.0).compress(&client_gradients[0]).0;
+        assert!(compressor.aggregate(&[sketches[0].clone(), other_seed], 2).is_err());
+    }
+
+    #[test]
+    fn test_powersgd_warm_start_recovers_low_rank_gradient() {
+        // A rank-2 gradient on a 10 x 10 layout, so rank 2 reconstructs it exactly
+        let u = [1.0, -2.0, 0.5, 3.0, 0.0, 1.5, -1.0, 2.5, 0.25, -0.75];
+        let v = [0.3, 0.1, -0.4, 0.2, 0.9, -0.6, 0.05, 0.7, -0.2, 0.15];
+        let gradients: Vec<f64> = (0..100).map(|i| u[i % 10] * v[i / 10] + (i % 10 % 3) as f64 * ((i / 10) as f64 - 4.5) * 0.1).collect();
+
+        let mut compressor = PowerSgdCompressor::new(2);
+        let (compressed, stats) = compressor.compress("site-a", &gradients);
+        assert_eq!((compressed.rows, compressed.columns, compressed.rank), (10, 10, 2));
+        assert_eq!(stats.compressed_size, 40 * 8);
+        let dense = compressor.decompress(&compressed);
+        assert!(dense.iter().zip(&gradients).all(|(d, g)| (d - g).abs() < 1e-9));
+        assert!(stats.accuracy_loss < 1e-9);
+
+        // The next round starts from the Q this client sent
+        let warm_start = compressor.factors("site-a").unwrap().q.clone();
+        compressor.compress("site-a", &gradients);
+        assert_ne!(compressor.factors("site-a").unwrap().q, warm_start);
+        compressor.reset("site-a");
+        assert!(compressor.factors("site-a").is_none());
+
+        // A rank-1 approximation of the same gradient owes the rest to the next round
+        let mut rank_one = PowerSgdCompressor::new(1);
+        let (first, stats) = rank_one.compress("site-a", &gradients);
+        assert!(stats.accuracy_loss > 1e-3);
+        let (second, _) = rank_one.compress("site-a", &vec![0.0; 100]);
+        let sent: Vec<f64> = first.to_dense().iter().zip(second.to_dense()).map(|(a, b)| a + b).collect();
+        let before = kernels::l2_norm(&gradients.iter().zip(&first.to_dense()).map(|(g, s)| g - s).collect::<Vec<_>>());
+        let after = kernels::l2_norm(&gradients.iter().zip(&sent).map(|(g, s)| g - s).collect::<Vec<_>>());
+        assert!(after < before);
+    }
+}
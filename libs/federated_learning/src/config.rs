@@ -0,0 +1,564 @@
+// Construction and validation of federated learning configurations, built in code or
+// loaded from TOML or JSON files
+
+use crate::*;
+pub use medical_data::config_file::ConfigFormat;
+use medical_data::config_file::{load_config_file, parse_config};
+use std::path::Path;
+
+impl FederatedLearningConfig {
+    pub fn builder() -> FederatedLearningConfigBuilder {
+        FederatedLearningConfigBuilder::new()
+    }
+
+    // Every field is required and unknown fields are rejected, so a typo cannot fall
+    // back to a default silently
+    pub fn from_config_str(text: &str, format: ConfigFormat) -> Result<Self, String> {
+        let config: FederatedLearningConfig = parse_config(text, format)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_config_file(path: &Path) -> Result<Self, String> {
+        let config: FederatedLearningConfig = load_config_file(path)?;
+        config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    // Rejects configurations the coordinator cannot execute, so they fail at
+    // construction time rather than in the middle of a training round
+    pub fn validate(&self) -> Result<(), String> {
+        self.algorithm.check_supported()?;
+        self.aggregation_method.check_supported()?;
+        self.compression_method.check_supported()?;
+        self.privacy_method.check_supported()?;
+
+        self.validate_training_parameters()?;
+        self.validate_privacy_budget()?;
+        self.validate_communication_budget()?;
+        self.validate_method_parameters()?;
+
+        if matches!(self.privacy_method, PrivacyMethod::SecureAggregation | PrivacyMethod::MultiPartyComputation { .. })
+            && self.aggregation_method.requires_individual_updates()
+        {
+            return Err(format!(
+                "Aggregation method {:?} needs individual client updates, which secure aggregation and MPC hide",
+                self.aggregation_method
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Checks that a consortium of `total_clients` can satisfy the per-round minimum
+    pub fn validate_participation(&self, total_clients: u32) -> Result<(), String> {
+        let selected = (self.client_fraction * total_clients as f64).round() as u32;
+        if self.min_clients > total_clients {
+            return Err(format!(
+                "min_clients ({}) exceeds the number of participants ({})",
+                self.min_clients, total_clients
+            ));
+        }
+        if self.min_clients > selected {
+            return Err(format!(
+                "client_fraction {} selects {} of {} participants, fewer than min_clients ({})",
+                self.client_fraction, selected, total_clients, self.min_clients
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_training_parameters(&self) -> Result<(), String> {
+        if self.model_dimension == 0 {
+            return Err("model_dimension must be at least 1".to_string());
+        }
+        if !(self.learning_rate.is_finite() && self.learning_rate > 0.0) {
+            return Err("learning_rate must be positive".to_string());
+        }
+        if !(0.0..1.0).contains(&self.momentum) {
+            return Err("momentum must be in [0, 1)".to_string());
+        }
+        if self.weight_decay < 0.0 {
+            return Err("weight_decay must be non-negative".to_string());
+        }
+        if self.local_epochs == 0 {
+            return Err("local_epochs must be at least 1".to_string());
+        }
+        if self.batch_size == 0 {
+            return Err("batch_size must be at least 1".to_string());
+        }
+        if self.client_fraction.is_nan() || self.client_fraction <= 0.0 || self.client_fraction > 1.0 {
+            return Err("client_fraction must be in (0, 1]".to_string());
+        }
+        if self.min_clients == 0 {
+            return Err("min_clients must be at least 1".to_string());
+        }
+        if self.max_rounds == 0 {
+            return Err("max_rounds must be at least 1".to_string());
+        }
+        if self.convergence_threshold < 0.0 {
+            return Err("convergence_threshold must be non-negative".to_string());
+        }
+        self.early_stopping.validate()?;
+        self.lr_schedule.validate(self.learning_rate, self.max_rounds)?;
+        if self.round_deadline_secs.is_some_and(|secs| !(secs.is_finite() && secs > 0.0)) {
+            return Err("round_deadline_secs must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn validate_privacy_budget(&self) -> Result<(), String> {
+        let budget = &self.privacy_budget;
+
+        if budget.total_epsilon.is_nan() || budget.total_epsilon <= 0.0 {
+            return Err("Total privacy budget ε must be positive".to_string());
+        }
+        if !(0.0..1.0).contains(&budget.total_delta) {
+            return Err("Total privacy budget δ must be in [0, 1)".to_string());
+        }
+        if budget.per_round_epsilon <= 0.0 || budget.per_round_epsilon > budget.total_epsilon {
+            return Err(format!(
+                "Per-round ε ({}) must be positive and no larger than the total budget ({})",
+                budget.per_round_epsilon, budget.total_epsilon
+            ));
+        }
+        if budget.per_client_epsilon <= 0.0 || budget.per_client_epsilon > budget.total_epsilon {
+            return Err(format!(
+                "Per-client ε ({}) must be positive and no larger than the total budget ({})",
+                budget.per_client_epsilon, budget.total_epsilon
+            ));
+        }
+        if let CompositionMethod::RenyiDP { alpha } = budget.composition_method {
+            if alpha <= 1.0 {
+                return Err("Rényi DP order α must be greater than 1".to_string());
+            }
+        }
+
+        match self.privacy_method {
+            PrivacyMethod::DifferentialPrivacy { epsilon, .. }
+                if epsilon <= 0.0 || epsilon > budget.per_round_epsilon =>
+            {
+                return Err(format!(
+                    "Mechanism ε ({}) must be positive and within the per-round budget ({})",
+                    epsilon, budget.per_round_epsilon
+                ));
+            }
+            PrivacyMethod::DifferentialPrivacy { delta, .. }
+                if delta <= 0.0 || delta >= 1.0 || delta > budget.total_delta =>
+            {
+                return Err(format!(
+                    "Mechanism δ ({}) must be in (0, 1) and within the total budget ({})",
+                    delta, budget.total_delta
+                ));
+            }
+            PrivacyMethod::LocalDifferentialPrivacy { epsilon }
+                if epsilon <= 0.0 || epsilon > budget.per_round_epsilon =>
+            {
+                return Err(format!(
+                    "Local DP ε ({}) must be positive and within the per-round budget ({})",
+                    epsilon, budget.per_round_epsilon
+                ));
+            }
+            PrivacyMethod::Shuffle { local_epsilon, delta } => {
+                if !(local_epsilon.is_finite() && local_epsilon > 0.0) {
+                    return Err("Shuffle local ε₀ must be positive".to_string());
+                }
+                if delta <= 0.0 || delta >= 1.0 || delta > budget.total_delta {
+                    return Err(format!("Shuffle δ ({}) must be in (0, 1) and within the total budget ({})", delta, budget.total_delta));
+                }
+                // The smallest allowed cohort gets the least amplification
+                let round_epsilon = shuffled_epsilon(local_epsilon, self.min_clients as usize, delta);
+                if round_epsilon > budget.per_round_epsilon {
+                    return Err(format!(
+                        "Shuffled ε ({}) with {} clients exceeds the per-round budget ({})",
+                        round_epsilon, self.min_clients, budget.per_round_epsilon
+                    ));
+                }
+            }
+            PrivacyMethod::GradientObfuscation { noise_scale } if noise_scale <= 0.0 => {
+                return Err("Gradient obfuscation noise_scale must be positive".to_string());
+            }
+            PrivacyMethod::MultiPartyComputation { ref scheme } => scheme.validate()?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn validate_communication_budget(&self) -> Result<(), String> {
+        let budget = &self.communication_budget;
+
+        if budget.max_bytes_per_round == 0 {
+            return Err("max_bytes_per_round must be positive".to_string());
+        }
+        if budget.max_bytes_per_round > budget.max_total_bytes {
+            return Err(format!(
+                "max_bytes_per_round ({}) exceeds max_total_bytes ({})",
+                budget.max_bytes_per_round, budget.max_total_bytes
+            ));
+        }
+        if budget.target_compression_ratio <= 0.0 || budget.target_compression_ratio > 1.0 {
+            return Err("target_compression_ratio must be in (0, 1]".to_string());
+        }
+        Ok(())
+    }
+
+    fn validate_method_parameters(&self) -> Result<(), String> {
+        match self.aggregation_method {
+            AggregationMethod::TrimmedMean { trim_ratio } if !(0.0..0.5).contains(&trim_ratio) => {
+                return Err(format!("trim_ratio must be in [0, 0.5), got {}", trim_ratio));
+            }
+            // Krum scores each update over its n - f - 2 nearest neighbours
+            AggregationMethod::Krum { byzantine_clients } if self.min_clients < 2 * byzantine_clients + 3 => {
+                return Err(format!(
+                    "Krum with f = {} requires min_clients >= 2f + 3 ({})",
+                    byzantine_clients,
+                    2 * byzantine_clients + 3
+                ));
+            }
+            // Multi-Krum averages m updates, so every round must deliver at least m
+            AggregationMethod::MultiKrum { m } if m == 0 || m > self.min_clients => {
+                return Err(format!("Multi-Krum m must be in 1..=min_clients ({}), got {}", self.min_clients, m));
+            }
+            AggregationMethod::Bulyan { f } if self.min_clients < 4 * f + 3 => {
+                return Err(format!("Bulyan with f = {} requires min_clients >= 4f + 3 ({})", f, 4 * f + 3));
+            }
+            AggregationMethod::SignSGD { server_learning_rate }
+                if !(server_learning_rate.is_finite() && server_learning_rate > 0.0) =>
+            {
+                return Err("SignSGD server learning rate must be positive".to_string());
+            }
+            AggregationMethod::GeometricMedian { max_iterations, tolerance }
+                if max_iterations == 0 || !(tolerance.is_finite() && tolerance > 0.0) =>
+            {
+                return Err("Geometric median needs at least one iteration and a positive tolerance".to_string());
+            }
+            _ => {}
+        }
+
+        // Averaging ±1 signs would install them as the new weights; only the vote
+        // knows how to turn them into a step
+        if matches!(self.compression_method, CompressionMethod::SignSGD)
+            && !matches!(self.aggregation_method, AggregationMethod::SignSGD { .. })
+        {
+            return Err("SignSGD compression requires SignSGD aggregation".to_string());
+        }
+
+        match self.compression_method {
+            CompressionMethod::Quantization { bits } if bits == 0 || bits > 16 => {
+                return Err(format!("Quantization bits must be in 1..=16, got {}", bits));
+            }
+            CompressionMethod::Sparsification { sparsity_ratio } if !(0.0..1.0).contains(&sparsity_ratio) => {
+                return Err(format!("sparsity_ratio must be in [0, 1), got {}", sparsity_ratio));
+            }
+            CompressionMethod::TopK { k } | CompressionMethod::RandomK { k } if k == 0 => {
+                return Err("k must be at least 1".to_string());
+            }
+            CompressionMethod::QSGD { levels: l } | CompressionMethod::FedPAQ { quantization_levels: l } if l < 2 => {
+                return Err(format!("Quantization needs at least 2 levels, got {}", l));
+            }
+            CompressionMethod::DeepGradientCompression { compression_ratio: r }
+            | CompressionMethod::AdaptiveCompression { target_ratio: r }
+                if r.is_nan() || r <= 0.0 || r > 1.0 =>
+            {
+                return Err(format!("Compression ratio must be in (0, 1], got {}", r));
+            }
+            _ => {}
+        }
+
+        match self.algorithm {
+            FLAlgorithm::FedProx { mu } if mu < 0.0 => {
+                return Err("FedProx μ must be non-negative".to_string());
+            }
+            FLAlgorithm::FedAdam { beta1, beta2 } if !(0.0..1.0).contains(&beta1) || !(0.0..1.0).contains(&beta2) => {
+                return Err("FedAdam β1 and β2 must be in [0, 1)".to_string());
+            }
+            FLAlgorithm::FedAvgM { momentum } if !(0.0..1.0).contains(&momentum) => {
+                return Err("FedAvgM momentum must be in [0, 1)".to_string());
+            }
+            FLAlgorithm::FedDyn { alpha } if alpha.is_nan() || alpha <= 0.0 => {
+                return Err("FedDyn α must be positive".to_string());
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+// Builder with cross-field validation on `build`
+#[derive(Clone, Debug)]
+pub struct FederatedLearningConfigBuilder {
+    config: FederatedLearningConfig,
+    total_clients: Option<u32>,
+}
+
+impl FederatedLearningConfigBuilder {
+    pub fn new() -> Self {
+        FederatedLearningConfigBuilder {
+            config: FederatedLearningConfig {
+                algorithm: FLAlgorithm::FedAvg,
+                aggregation_method: AggregationMethod::FedAvg,
+                compression_method: CompressionMethod::None,
+                privacy_method: PrivacyMethod::DifferentialPrivacy { epsilon: 0.1, delta: 1e-5 },
+                model_dimension: 1000,
+                learning_rate: 0.01,
+                momentum: 0.9,
+                weight_decay: 1e-4,
+                local_epochs: 5,
+                batch_size: 32,
+                client_fraction: 1.0,
+                min_clients: 3,
+                max_rounds: 100,
+                convergence_threshold: 1e-4,
+                early_stopping: EarlyStoppingStrategy::default(),
+                lr_schedule: LearningRateSchedule::default(),
+                round_deadline_secs: None,
+                privacy_budget: PrivacyBudget {
+                    total_epsilon: 10.0,
+                    total_delta: 1e-3,
+                    per_round_epsilon: 0.1,
+                    per_client_epsilon: 0.1,
+                    composition_method: CompositionMethod::Advanced,
+                },
+                communication_budget: CommunicationBudget {
+                    max_bytes_per_round: 10_000_000,
+                    max_total_bytes: 1_000_000_000,
+                    target_compression_ratio: 1.0,
+                    adaptive_compression: false,
+                },
+            },
+            total_clients: None,
+        }
+    }
+
+    // Strict privacy defaults for consortia handling PHI: robust aggregation,
+    // a small overall ε and local DP so raw updates never leave the hospital
+    pub fn hipaa_strict() -> Self {
+        Self::new()
+            .aggregation_method(AggregationMethod::TrimmedMean { trim_ratio: 0.2 })
+            .privacy_method(PrivacyMethod::LocalDifferentialPrivacy { epsilon: 0.05 })
+            .privacy_budget(PrivacyBudget {
+                total_epsilon: 3.0,
+                total_delta: 1e-6,
+                per_round_epsilon: 0.05,
+                per_client_epsilon: 0.05,
+                composition_method: CompositionMethod::Advanced,
+            })
+            .min_clients(5)
+            .max_rounds(60)
+    }
+
+    // For sites on constrained links: 8-bit quantization and a small per-round cap
+    pub fn bandwidth_constrained() -> Self {
+        Self::new()
+            .compression_method(CompressionMethod::Quantization { bits: 8 })
+            .client_fraction(0.3)
+            .local_epochs(10)
+            .communication_budget(CommunicationBudget {
+                max_bytes_per_round: 1_000_000,
+                max_total_bytes: 100_000_000,
+                target_compression_ratio: 0.25,
+                adaptive_compression: true,
+            })
+    }
+
+    pub fn algorithm(mut self, algorithm: FLAlgorithm) -> Self {
+        self.config.algorithm = algorithm;
+        self
+    }
+
+    pub fn aggregation_method(mut self, method: AggregationMethod) -> Self {
+        self.config.aggregation_method = method;
+        self
+    }
+
+    pub fn compression_method(mut self, method: CompressionMethod) -> Self {
+        self.config.compression_method = method;
+        self
+    }
+
+    pub fn privacy_method(mut self, method: PrivacyMethod) -> Self {
+        self.config.privacy_method = method;
+        self
+    }
+
+    pub fn model_dimension(mut self, model_dimension: usize) -> Self {
+        self.config.model_dimension = model_dimension;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.config.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn momentum(mut self, momentum: f64) -> Self {
+        self.config.momentum = momentum;
+        self
+    }
+
+    pub fn weight_decay(mut self, weight_decay: f64) -> Self {
+        self.config.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn local_epochs(mut self, local_epochs: u32) -> Self {
+        self.config.local_epochs = local_epochs;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.config.batch_size = batch_size;
+        self
+    }
+
+    pub fn client_fraction(mut self, client_fraction: f64) -> Self {
+        self.config.client_fraction = client_fraction;
+        self
+    }
+
+    pub fn min_clients(mut self, min_clients: u32) -> Self {
+        self.config.min_clients = min_clients;
+        self
+    }
+
+    pub fn max_rounds(mut self, max_rounds: u32) -> Self {
+        self.config.max_rounds = max_rounds;
+        self
+    }
+
+    pub fn convergence_threshold(mut self, threshold: f64) -> Self {
+        self.config.convergence_threshold = threshold;
+        self
+    }
+
+    pub fn early_stopping(mut self, strategy: EarlyStoppingStrategy) -> Self {
+        self.config.early_stopping = strategy;
+        self
+    }
+
+    pub fn lr_schedule(mut self, schedule: LearningRateSchedule) -> Self {
+        self.config.lr_schedule = schedule;
+        self
+    }
+
+    pub fn round_deadline_secs(mut self, secs: Option<f64>) -> Self {
+        self.config.round_deadline_secs = secs;
+        self
+    }
+
+    pub fn privacy_budget(mut self, budget: PrivacyBudget) -> Self {
+        self.config.privacy_budget = budget;
+        self
+    }
+
+    pub fn communication_budget(mut self, budget: CommunicationBudget) -> Self {
+        self.config.communication_budget = budget;
+        self
+    }
+
+    // Number of institutions in the consortium, used to check min_clients
+    pub fn total_clients(mut self, total_clients: u32) -> Self {
+        self.total_clients = Some(total_clients);
+        self
+    }
+
+    pub fn build(self) -> Result<FederatedLearningConfig, String> {
+        self.config.validate()?;
+        if let Some(total_clients) = self.total_clients {
+            self.config.validate_participation(total_clients)?;
+        }
+        Ok(self.config)
+    }
+}
+
+impl Default for FederatedLearningConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid() {
+        assert!(FederatedLearningConfigBuilder::new().build().is_ok());
+        assert!(FederatedLearningConfigBuilder::hipaa_strict().total_clients(10).build().is_ok());
+        assert!(FederatedLearningConfigBuilder::bandwidth_constrained().total_clients(20).build().is_ok());
+    }
+
+    #[test]
+    fn test_cross_field_validation() {
+        let too_many_min_clients = FederatedLearningConfig::builder().min_clients(8).total_clients(5).build();
+        assert!(too_many_min_clients.is_err());
+
+        let round_exceeds_total = FederatedLearningConfig::builder()
+            .privacy_budget(PrivacyBudget {
+                total_epsilon: 1.0,
+                total_delta: 1e-5,
+                per_round_epsilon: 2.0,
+                per_client_epsilon: 0.5,
+                composition_method: CompositionMethod::Basic,
+            })
+            .build();
+        assert!(round_exceeds_total.is_err());
+
+        let bad_trim = FederatedLearningConfig::builder()
+            .aggregation_method(AggregationMethod::TrimmedMean { trim_ratio: 0.5 })
+            .build();
+        assert!(bad_trim.is_err());
+
+        let secure_krum = FederatedLearningConfig::builder()
+            .privacy_method(PrivacyMethod::SecureAggregation)
+            .aggregation_method(AggregationMethod::Krum { byzantine_clients: 0 })
+            .build();
+        assert!(secure_krum.is_err());
+    }
+
+    #[test]
+    fn test_coordinator_rejects_unsupported_configurations() {
+        let valid = FederatedLearningConfigBuilder::new().build().unwrap();
+        assert!(FederatedLearningCoordinator::new(valid.clone()).is_ok());
+
+        let no_weights = FederatedLearningConfig { model_dimension: 0, ..valid.clone() };
+        assert!(FederatedLearningCoordinator::new(no_weights).is_err());
+        let signs_averaged = FederatedLearningConfig { compression_method: CompressionMethod::SignSGD, ..valid.clone() };
+        let error = FederatedLearningCoordinator::new(signs_averaged).err().unwrap();
+        assert!(error.contains("SignSGD aggregation"), "{}", error);
+        let masked_median = FederatedLearningConfig {
+            privacy_method: PrivacyMethod::MultiPartyComputation { scheme: MpcScheme::Additive { parties: 3 } },
+            aggregation_method: AggregationMethod::Median,
+            ..valid.clone()
+        };
+        assert!(masked_median.validate().is_err());
+        assert!(FederatedLearningCoordinator::new(masked_median).is_err());
+
+        #[cfg(feature = "experimental")]
+        {
+            let encrypted = FederatedLearningConfig { privacy_method: PrivacyMethod::HomomorphicEncryption, ..valid };
+            let error = FederatedLearningCoordinator::new(encrypted).err().unwrap();
+            assert!(error.contains("experimental"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_config_files_are_checked_like_built_configs() {
+        let config = FederatedLearningConfigBuilder::hipaa_strict().total_clients(10).build().unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded = FederatedLearningConfig::from_config_str(&json, ConfigFormat::Json).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        let typo = json.replacen("\"min_clients\"", "\"min_client\"", 1);
+        let error = FederatedLearningConfig::from_config_str(&typo, ConfigFormat::Json).unwrap_err();
+        assert!(error.contains("unknown field `min_client`"), "{}", error);
+        let mut invalid: serde_json::Value = serde_json::from_str(&json).unwrap();
+        invalid["min_clients"] = 0.into();
+        assert!(FederatedLearningConfig::from_config_str(&invalid.to_string(), ConfigFormat::Json).is_err());
+
+        let error = FederatedLearningConfig::from_config_str("algorithm = \"FedAvg\"\nmodel_dimension = \"large\"", ConfigFormat::Toml).unwrap_err();
+        assert!(error.contains("model_dimension") && error.contains("line 2"), "{}", error);
+    }
+}
@@ -0,0 +1,277 @@
+// Patient-level k-fold cross-validation across sites. Fold membership is derived
+// from the keyed patient pseudonym, so every site assigns a given patient to the
+// same fold without a coordinator ever seeing patient identifiers, and a patient
+// treated at two hospitals never lands in train at one and test at the other.
+
+use crate::*;
+use medical_data::privacy::pseudonymize_patient_id;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CrossValidationPlan {
+    pub k: usize,
+    pub consortium_salt: String,
+}
+
+// Metrics one site reports for one fold, from a binary classifier at a fixed threshold
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FoldMetrics {
+    pub fold: usize,
+    pub site_id: String,
+    pub num_samples: usize,
+    pub loss: f64,
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub true_negatives: u64,
+    pub false_negatives: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FoldSummary {
+    pub fold: usize,
+    pub num_sites: usize,
+    pub num_samples: usize,
+    pub loss: f64,
+    pub accuracy: f64,
+    pub sensitivity: f64,
+    pub specificity: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CrossValidationReport {
+    pub k: usize,
+    pub folds: Vec<FoldSummary>,
+    pub mean_loss: f64,
+    pub std_loss: f64,
+    pub mean_accuracy: f64,
+    pub std_accuracy: f64,
+    pub mean_sensitivity: f64,
+    pub mean_specificity: f64,
+}
+
+impl CrossValidationPlan {
+    pub fn new(k: usize, consortium_salt: &str) -> Result<Self, String> {
+        if k < 2 {
+            return Err(format!("Cross-validation needs at least 2 folds, got {}", k));
+        }
+        if consortium_salt.is_empty() {
+            return Err("Cross-validation requires a consortium salt".to_string());
+        }
+        Ok(CrossValidationPlan { k, consortium_salt: consortium_salt.to_string() })
+    }
+
+    pub fn fold_of(&self, patient_id: &str) -> usize {
+        let pseudonym = pseudonymize_patient_id(patient_id, &self.consortium_salt);
+        // The first 64 bits of a SHA-256 digest are uniform enough for modulo bucketing
+        let prefix = u64::from_str_radix(&pseudonym[..16], 16).unwrap_or(0);
+        (prefix % self.k as u64) as usize
+    }
+
+    pub fn assign(&self, patient_ids: &[String]) -> Vec<usize> {
+        patient_ids.iter().map(|id| self.fold_of(id)).collect()
+    }
+
+    // Row indices for training and held-out evaluation of `fold`
+    pub fn split(&self, patient_ids: &[String], fold: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut train = Vec::new();
+        let mut test = Vec::new();
+        for (i, id) in patient_ids.iter().enumerate() {
+            if self.fold_of(id) == fold {
+                test.push(i);
+            } else {
+                train.push(i);
+            }
+        }
+        (train, test)
+    }
+
+    pub fn split_matrix(&self, matrix: &FeatureMatrix, fold: usize) -> Result<(FeatureMatrix, FeatureMatrix), String> {
+        if fold >= self.k {
+            return Err(format!("Fold {} out of range for {}-fold cross-validation", fold, self.k));
+        }
+        let (train, test) = self.split(&matrix.patient_ids, fold);
+        Ok((select_rows(matrix, &train), select_rows(matrix, &test)))
+    }
+}
+
+fn select_rows(matrix: &FeatureMatrix, indices: &[usize]) -> FeatureMatrix {
+    FeatureMatrix {
+        schema_fingerprint: matrix.schema_fingerprint.clone(),
+        feature_names: matrix.feature_names.clone(),
+        patient_ids: indices.iter().map(|&i| matrix.patient_ids[i].clone()).collect(),
+        rows: indices.iter().map(|&i| matrix.rows[i].clone()).collect(),
+    }
+}
+
+impl FoldMetrics {
+    // `probabilities` are predicted P(label = 1); loss is mean binary cross-entropy
+    pub fn from_predictions(
+        fold: usize,
+        site_id: &str,
+        probabilities: &[f64],
+        labels: &[usize],
+        threshold: f64,
+    ) -> Result<Self, String> {
+        if probabilities.len() != labels.len() {
+            return Err(format!(
+                "Got {} predictions for {} labels",
+                probabilities.len(),
+                labels.len()
+            ));
+        }
+
+        let mut metrics = FoldMetrics {
+            fold,
+            site_id: site_id.to_string(),
+            num_samples: labels.len(),
+            loss: 0.0,
+            true_positives: 0,
+            false_positives: 0,
+            true_negatives: 0,
+            false_negatives: 0,
+        };
+
+        let mut total_loss = 0.0;
+        for (&p, &label) in probabilities.iter().zip(labels.iter()) {
+            let p = p.clamp(1e-12, 1.0 - 1e-12);
+            let positive = label == 1;
+            total_loss -= if positive { p.ln() } else { (1.0 - p).ln() };
+            match (p >= threshold, positive) {
+                (true, true) => metrics.true_positives += 1,
+                (true, false) => metrics.false_positives += 1,
+                (false, false) => metrics.true_negatives += 1,
+                (false, true) => metrics.false_negatives += 1,
+            }
+        }
+        if !labels.is_empty() {
+            metrics.loss = total_loss / labels.len() as f64;
+        }
+
+        Ok(metrics)
+    }
+}
+
+impl CrossValidationReport {
+    // Pools the per-site confusion counts of each fold, so a fold's metrics are those of
+    // the whole consortium's held-out patients rather than an average of site averages
+    pub fn from_site_metrics(k: usize, metrics: &[FoldMetrics]) -> Result<Self, String> {
+        let mut folds = Vec::with_capacity(k);
+
+        for fold in 0..k {
+            let fold_metrics: Vec<&FoldMetrics> = metrics.iter().filter(|m| m.fold == fold).collect();
+            if fold_metrics.is_empty() {
+                return Err(format!("No site reported metrics for fold {}", fold));
+            }
+
+            let num_samples: usize = fold_metrics.iter().map(|m| m.num_samples).sum();
+            let tp: u64 = fold_metrics.iter().map(|m| m.true_positives).sum();
+            let fp: u64 = fold_metrics.iter().map(|m| m.false_positives).sum();
+            let tn: u64 = fold_metrics.iter().map(|m| m.true_negatives).sum();
+            let fn_: u64 = fold_metrics.iter().map(|m| m.false_negatives).sum();
+            let loss = if num_samples > 0 {
+                fold_metrics.iter().map(|m| m.loss * m.num_samples as f64).sum::<f64>() / num_samples as f64
+            } else {
+                0.0
+            };
+
+            folds.push(FoldSummary {
+                fold,
+                num_sites: fold_metrics.len(),
+                num_samples,
+                loss,
+                accuracy: ratio(tp + tn, tp + tn + fp + fn_),
+                sensitivity: ratio(tp, tp + fn_),
+                specificity: ratio(tn, tn + fp),
+            });
+        }
+
+        let losses: Vec<f64> = folds.iter().map(|f| f.loss).collect();
+        let accuracies: Vec<f64> = folds.iter().map(|f| f.accuracy).collect();
+        let (mean_loss, std_loss) = mean_std(&losses);
+        let (mean_accuracy, std_accuracy) = mean_std(&accuracies);
+
+        Ok(CrossValidationReport {
+            k,
+            mean_sensitivity: folds.iter().map(|f| f.sensitivity).sum::<f64>() / k as f64,
+            mean_specificity: folds.iter().map(|f| f.specificity).sum::<f64>() / k as f64,
+            folds,
+            mean_loss,
+            std_loss,
+            mean_accuracy,
+            std_accuracy,
+        })
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 { 0.0 } else { numerator as f64 / denominator as f64 }
+}
+
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sites_agree_on_folds_and_reports_pool_counts() {
+        let plan = CrossValidationPlan::new(5, "consortium-salt").unwrap();
+        assert!(CrossValidationPlan::new(1, "consortium-salt").is_err());
+
+        // Two hospitals share patients 100..150; each holds its rows in its own order
+        let ids = |range: std::ops::Range<usize>| -> Vec<String> { range.map(|i| format!("patient-{}", i)).collect() };
+        let site_a = ids(0..150);
+        let mut site_b = ids(100..300);
+        site_b.reverse();
+        let (folds_a, folds_b) = (plan.assign(&site_a), plan.assign(&site_b));
+        for (i, shared) in site_a.iter().enumerate().skip(100) {
+            let j = site_b.iter().position(|id| id == shared).unwrap();
+            assert_eq!(folds_a[i], folds_b[j]);
+        }
+        // A different salt reshuffles the folds
+        let other = CrossValidationPlan::new(5, "another-salt").unwrap();
+        assert_ne!(folds_a, other.assign(&site_a));
+
+        let matrix = FeatureMatrix {
+            schema_fingerprint: "fp".to_string(),
+            feature_names: vec!["x".to_string()],
+            patient_ids: site_b.clone(),
+            rows: (0..site_b.len()).map(|i| vec![i as f64]).collect(),
+        };
+        let mut held_out = 0;
+        for fold in 0..plan.k {
+            let (train, test) = plan.split_matrix(&matrix, fold).unwrap();
+            assert_eq!(train.rows.len() + test.rows.len(), matrix.rows.len());
+            assert!(test.patient_ids.iter().all(|id| plan.fold_of(id) == fold));
+            assert!(train.patient_ids.iter().all(|id| plan.fold_of(id) != fold));
+            // Roughly a fifth of 200 patients each
+            assert!((20..=60).contains(&test.rows.len()), "fold {} holds {}", fold, test.rows.len());
+            held_out += test.rows.len();
+        }
+        assert_eq!(held_out, matrix.rows.len());
+        assert!(plan.split_matrix(&matrix, 5).is_err());
+
+        // Site a: 1 TP, 1 FN; site b: 1 TP, 1 TN, 1 FP
+        let metrics = vec![
+            FoldMetrics::from_predictions(0, "a", &[0.9, 0.2], &[1, 1], 0.5).unwrap(),
+            FoldMetrics::from_predictions(0, "b", &[0.8, 0.1, 0.7], &[1, 0, 0], 0.5).unwrap(),
+            FoldMetrics::from_predictions(1, "a", &[0.9, 0.1], &[1, 0], 0.5).unwrap(),
+        ];
+        assert!(FoldMetrics::from_predictions(0, "a", &[0.9], &[1, 0], 0.5).is_err());
+        let report = CrossValidationReport::from_site_metrics(2, &metrics).unwrap();
+        let fold = &report.folds[0];
+        assert_eq!((fold.num_sites, fold.num_samples), (2, 5));
+        // Pooled, 2 of 3 positives are caught; averaging site sensitivities would give 0.75
+        assert!((fold.sensitivity - 2.0 / 3.0).abs() < 1e-12);
+        assert!((fold.specificity - 0.5).abs() < 1e-12);
+        assert!((report.mean_accuracy - (0.6 + 1.0) / 2.0).abs() < 1e-12);
+        assert!(CrossValidationReport::from_site_metrics(3, &metrics).is_err());
+    }
+}
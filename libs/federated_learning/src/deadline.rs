@@ -0,0 +1,126 @@
+// Round deadlines. With `round_deadline_secs` set (or a pacing policy, whose
+// deadline takes over), the orchestrator no longer has to wait for every site:
+// `execute_round_with_deadline` takes the round's updates together with when each
+// arrived, measured from the round's start, aggregates those that made the deadline
+// and leaves the rest out as stragglers. A straggler counts as absent from the
+// round, so the dropout policy applies to it as to a site that never reported.
+//
+// The round's deadline, how many updates missed it and by how much, and the running
+// total are kept in `CommunicationMetrics`.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TimedUpdate {
+    pub update: ModelUpdate,
+    // Seconds from the start of the round until the update was received
+    pub arrival_secs: f64,
+}
+
+// Straggler accounting for the round in progress, folded into the metrics
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DeadlineOutcome {
+    pub deadline_secs: Option<f64>,
+    pub on_time: usize,
+    pub late_by_secs: Vec<f64>,
+}
+
+impl FederatedLearningCoordinator {
+    // Deadline for the round in progress: the pacing controller's when pacing is on
+    pub fn round_deadline(&self) -> Option<f64> {
+        match &self.pacing {
+            Some(pacing) => Some(pacing.decision.deadline_secs),
+            None => self.config.round_deadline_secs,
+        }
+    }
+
+    // Runs the round with the updates that arrived by the deadline; without a
+    // deadline every update counts
+    pub fn execute_round_with_deadline(&mut self, updates: Vec<TimedUpdate>) -> Result<GlobalModel, String> {
+        if updates.iter().any(|t| !(t.arrival_secs.is_finite() && t.arrival_secs >= 0.0)) {
+            return Err("Arrival times must be non-negative numbers of seconds".to_string());
+        }
+        let deadline_secs = self.round_deadline();
+        let (on_time, late): (Vec<TimedUpdate>, Vec<TimedUpdate>) =
+            updates.into_iter().partition(|t| deadline_secs.is_none_or(|deadline| t.arrival_secs <= deadline));
+        if let Some(deadline) = deadline_secs.filter(|_| on_time.len() < self.config.min_clients as usize) {
+            return Err(format!(
+                "Only {} updates arrived by the {:.1} s deadline; min_clients is {}",
+                on_time.len(),
+                deadline,
+                self.config.min_clients
+            ));
+        }
+
+        let late_by_secs = late.iter().map(|t| t.arrival_secs - deadline_secs.unwrap_or(t.arrival_secs)).collect();
+        self.deadline_outcome = Some(DeadlineOutcome { deadline_secs, on_time: on_time.len(), late_by_secs });
+        let result = self.execute_round(on_time.into_iter().map(|t| t.update).collect());
+        self.deadline_outcome = None;
+        result
+    }
+
+    // Called while computing the round's metrics; rounds run without a deadline
+    // record no stragglers
+    pub(crate) fn record_straggler_metrics(&mut self) {
+        let outcome = self.deadline_outcome.take().unwrap_or_default();
+        let metrics = &mut self.global_model.communication_metrics;
+        let stragglers = outcome.late_by_secs.len();
+        metrics.round_deadline_secs = outcome.deadline_secs;
+        metrics.round_stragglers = stragglers as u32;
+        metrics.round_straggler_rate = match outcome.on_time + stragglers {
+            0 => 0.0,
+            received => stragglers as f64 / received as f64,
+        };
+        metrics.max_straggler_delay_secs = outcome.late_by_secs.iter().copied().fold(0.0, f64::max);
+        metrics.total_stragglers += stragglers as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed(client_id: &str, arrival_secs: f64) -> TimedUpdate {
+        let update = ModelUpdate {
+            loss: 0.5,
+            accuracy: 0.5,
+            computation_time: arrival_secs,
+            ..ModelUpdate::for_test(client_id, vec![1.0, 1.0], 10)
+        };
+        TimedUpdate { update, arrival_secs }
+    }
+
+    #[test]
+    fn test_late_updates_are_left_out_and_counted() {
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(2)
+            .min_clients(2)
+            .privacy_method(PrivacyMethod::None)
+            .round_deadline_secs(Some(60.0))
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let model = coordinator
+            .execute_round_with_deadline(vec![timed("hospital_a", 20.0), timed("hospital_b", 55.0), timed("hospital_c", 90.0)])
+            .unwrap();
+        assert_eq!(model.participating_clients, vec!["hospital_a".to_string(), "hospital_b".to_string()]);
+        let metrics = &model.communication_metrics;
+        assert_eq!((metrics.round_stragglers, metrics.total_stragglers), (1, 1));
+        assert_eq!(metrics.max_straggler_delay_secs, 30.0);
+        assert!((metrics.round_straggler_rate - 1.0 / 3.0).abs() < 1e-12);
+
+        // Too few in time: the round does not run
+        let mut late = vec![timed("hospital_a", 10.0), timed("hospital_b", 61.0)];
+        late.iter_mut().for_each(|t| t.update.round = 1);
+        assert!(coordinator.execute_round_with_deadline(late).is_err());
+
+        // Rounds run with plain `execute_round` record no stragglers
+        let updates = ["hospital_a", "hospital_b"]
+            .iter()
+            .map(|client_id| ModelUpdate { round: 1, ..timed(client_id, 1.0).update })
+            .collect();
+        let model = coordinator.execute_round(updates).unwrap();
+        assert_eq!((model.communication_metrics.round_stragglers, model.communication_metrics.total_stragglers), (0, 1));
+        assert_eq!(model.communication_metrics.round_deadline_secs, None);
+    }
+}
@@ -0,0 +1,288 @@
+// Ensemble distillation aggregation (FedDF, Lin et al. 2020; FedMD, Li & Wang 2019).
+// Instead of averaging weights, which needs every hospital to run the same
+// architecture, the server publishes an unlabeled proxy dataset. Each client runs its
+// own model on the proxy rows and submits the logits. The server fuses them into
+// soft targets: the data-size weighted mean of the temperature-softened class
+// probabilities.
+//   FedMD: clients fetch the consensus targets and distill them into their models.
+//   FedDF: the server also distills them into its own student model with the loss
+//          T^2 * KL(targets || softmax(student / T)), averaged over proxy rows.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DistillationMode {
+    FedMD,
+    FedDF { epochs: u32, learning_rate: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DistillationConfig {
+    pub mode: DistillationMode,
+    pub temperature: f64,
+    pub min_clients: u32,
+}
+
+impl DistillationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.temperature.is_finite() && self.temperature > 0.0) {
+            return Err("Distillation temperature must be positive".to_string());
+        }
+        if self.min_clients == 0 {
+            return Err("min_clients must be at least 1".to_string());
+        }
+        if let DistillationMode::FedDF { epochs, learning_rate } = self.mode {
+            if epochs == 0 {
+                return Err("FedDF needs at least one distillation epoch".to_string());
+            }
+            if !(learning_rate.is_finite() && learning_rate > 0.0) {
+                return Err("FedDF learning rate must be positive".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Unlabeled rows shared with every client
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProxyDataset {
+    pub rows: Vec<Vec<f64>>,
+    pub fingerprint: String,
+}
+
+impl ProxyDataset {
+    pub fn new(rows: Vec<Vec<f64>>) -> Result<Self, String> {
+        let width = rows.first().map(|r| r.len()).ok_or("Proxy dataset cannot be empty")?;
+        if width == 0 || rows.iter().any(|r| r.len() != width) {
+            return Err("Proxy rows must be non-empty and of equal width".to_string());
+        }
+        let mut hasher = Sha256::new();
+        for row in &rows {
+            for value in row {
+                hasher.update(value.to_le_bytes());
+            }
+        }
+        Ok(ProxyDataset { rows, fingerprint: format!("{:x}", hasher.finalize()) })
+    }
+}
+
+// A client's logits on the proxy rows, row-major (rows x classes)
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProxyLogits {
+    pub client_id: String,
+    pub round: u64,
+    pub proxy_fingerprint: String,
+    pub classes: usize,
+    pub logits: Vec<f64>,
+    pub data_size: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DistillationRound {
+    pub round: u64,
+    // Soft targets per proxy row, row-major
+    pub consensus: Vec<f64>,
+    pub participating_clients: Vec<String>,
+    // Distillation loss of the server student before and after FedDF
+    pub student_loss_before: Option<f64>,
+    pub student_loss_after: Option<f64>,
+}
+
+fn softmax(logits: &[f64], temperature: f64) -> Vec<f64> {
+    let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exp: Vec<f64> = logits.iter().map(|z| ((z - max) / temperature).exp()).collect();
+    let total: f64 = exp.iter().sum();
+    exp.iter().map(|e| e / total).collect()
+}
+
+pub struct DistillationAggregator {
+    config: DistillationConfig,
+    proxy: ProxyDataset,
+    classes: usize,
+    student: Option<LayerStack>,
+    round: u64,
+    pending: HashMap<String, ProxyLogits>,
+    history: Vec<DistillationRound>,
+}
+
+impl DistillationAggregator {
+    pub fn new(config: DistillationConfig, proxy: ProxyDataset, classes: usize) -> Result<Self, String> {
+        config.validate()?;
+        if classes < 2 {
+            return Err("Distillation needs at least 2 classes".to_string());
+        }
+        Ok(DistillationAggregator {
+            config,
+            proxy,
+            classes,
+            student: None,
+            round: 0,
+            pending: HashMap::new(),
+            history: Vec::new(),
+        })
+    }
+
+    // Server model for FedDF; maps proxy rows to class logits
+    pub fn set_student(&mut self, student: LayerStack) -> Result<(), String> {
+        if student.input_width() != self.proxy.rows[0].len() || student.output_width() != self.classes {
+            return Err("Student model does not match the proxy width and class count".to_string());
+        }
+        self.student = Some(student);
+        Ok(())
+    }
+
+    pub fn student(&self) -> Option<&LayerStack> {
+        self.student.as_ref()
+    }
+
+    pub fn proxy(&self) -> &ProxyDataset {
+        &self.proxy
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn get_history(&self) -> &[DistillationRound] {
+        &self.history
+    }
+
+    // Soft targets from the latest aggregated round, for FedMD clients
+    pub fn consensus(&self) -> Option<&[f64]> {
+        self.history.last().map(|r| r.consensus.as_slice())
+    }
+
+    pub fn submit_logits(&mut self, logits: ProxyLogits) -> Result<(), String> {
+        if logits.round != self.round {
+            return Err(format!("Logits are for round {}, current round is {}", logits.round, self.round));
+        }
+        if logits.proxy_fingerprint != self.proxy.fingerprint {
+            return Err("Logits were computed on a different proxy dataset".to_string());
+        }
+        if logits.classes != self.classes || logits.logits.len() != self.proxy.rows.len() * self.classes {
+            return Err(format!("Expected {} x {} logits", self.proxy.rows.len(), self.classes));
+        }
+        if logits.logits.iter().any(|z| !z.is_finite()) {
+            return Err("Logits must be finite".to_string());
+        }
+        if logits.data_size == 0 {
+            return Err("data_size must be positive".to_string());
+        }
+        self.pending.insert(logits.client_id.clone(), logits);
+        Ok(())
+    }
+
+    fn fuse(&self) -> Vec<f64> {
+        let total: f64 = self.pending.values().map(|l| l.data_size as f64).sum();
+        let mut consensus = vec![0.0; self.proxy.rows.len() * self.classes];
+        for submission in self.pending.values() {
+            let weight = submission.data_size as f64 / total;
+            for (row, target) in submission.logits.chunks(self.classes).zip(consensus.chunks_mut(self.classes)) {
+                let probabilities = softmax(row, self.config.temperature);
+                kernels::axpy(weight, &probabilities, target);
+            }
+        }
+        consensus
+    }
+
+    // (loss, gradient w.r.t. the student logits)
+    fn distillation_loss(&self, student_logits: &[f64], targets: &[f64]) -> (f64, Vec<f64>) {
+        let t = self.config.temperature;
+        let rows = self.proxy.rows.len() as f64;
+        let mut loss = 0.0;
+        let mut gradient = Vec::with_capacity(student_logits.len());
+        for (logits, target) in student_logits.chunks(self.classes).zip(targets.chunks(self.classes)) {
+            let q = softmax(logits, t);
+            for (&p, &q) in target.iter().zip(q.iter()) {
+                if p > 0.0 {
+                    loss += t * t * p * (p / q.max(f64::MIN_POSITIVE)).ln() / rows;
+                }
+                gradient.push(t * (q - p) / rows);
+            }
+        }
+        (loss, gradient)
+    }
+
+    pub fn aggregate_round(&mut self) -> Result<DistillationRound, String> {
+        if (self.pending.len() as u32) < self.config.min_clients {
+            return Err(format!("Only {} of {} required clients submitted logits", self.pending.len(), self.config.min_clients));
+        }
+        let consensus = self.fuse();
+
+        let mut losses = (None, None);
+        if let (DistillationMode::FedDF { epochs, learning_rate }, Some(student)) = (&self.config.mode, &self.student) {
+            let mut student = student.clone();
+            let inputs = self.proxy.rows.concat();
+            for epoch in 0..*epochs {
+                let cache = student.forward(inputs.clone(), self.proxy.rows.len());
+                let (loss, grad_logits) = self.distillation_loss(&cache.output, &consensus);
+                if epoch == 0 {
+                    losses.0 = Some(loss);
+                }
+                let (_, grad_params) = student.backward(&cache, &grad_logits);
+                student.step(&grad_params, *learning_rate);
+            }
+            let output = student.forward(inputs, self.proxy.rows.len()).output;
+            losses.1 = Some(self.distillation_loss(&output, &consensus).0);
+            self.student = Some(student);
+        }
+
+        let mut participating_clients: Vec<String> = self.pending.keys().cloned().collect();
+        participating_clients.sort();
+        let summary = DistillationRound {
+            round: self.round,
+            consensus,
+            participating_clients,
+            student_loss_before: losses.0,
+            student_loss_after: losses.1,
+        };
+        self.history.push(summary.clone());
+        self.pending.clear();
+        self.round += 1;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consensus_and_student_distillation() {
+        let proxy = ProxyDataset::new(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]]).unwrap();
+        let config = DistillationConfig {
+            mode: DistillationMode::FedDF { epochs: 200, learning_rate: 0.5 },
+            temperature: 2.0,
+            min_clients: 2,
+        };
+        let mut aggregator = DistillationAggregator::new(config, proxy.clone(), 2).unwrap();
+        let student = LayerStack::new(vec![DenseLayer::from_parameters(2, 2, Activation::Identity, &[0.0; 6]).unwrap()]).unwrap();
+        aggregator.set_student(student).unwrap();
+
+        // Two differently shaped hospital models only have to agree on the output layout
+        let submit = |client: &str, logits: Vec<f64>, data_size: usize| ProxyLogits {
+            client_id: client.to_string(),
+            round: 0,
+            proxy_fingerprint: proxy.fingerprint.clone(),
+            classes: 2,
+            logits,
+            data_size,
+        };
+        aggregator.submit_logits(submit("a", vec![4.0, 0.0, 0.0, 4.0, 0.0, 0.0], 30)).unwrap();
+        assert!(aggregator.aggregate_round().is_err());
+        let mut wrong = submit("b", vec![0.0; 6], 10);
+        wrong.proxy_fingerprint = "other".to_string();
+        assert!(aggregator.submit_logits(wrong).is_err());
+        aggregator.submit_logits(submit("b", vec![2.0, 0.0, 0.0, 2.0, 0.0, 0.0], 10)).unwrap();
+
+        let round = aggregator.aggregate_round().unwrap();
+        let expected = 0.75 * softmax(&[4.0, 0.0], 2.0)[0] + 0.25 * softmax(&[2.0, 0.0], 2.0)[0];
+        assert!((round.consensus[0] - expected).abs() < 1e-12);
+        assert!((round.consensus[4] - 0.5).abs() < 1e-12);
+        assert!(round.student_loss_after.unwrap() < round.student_loss_before.unwrap() / 10.0);
+
+        let prediction = aggregator.student().unwrap().predict(&proxy.rows[..1]);
+        assert!(prediction[0][0] > prediction[0][1]);
+    }
+}
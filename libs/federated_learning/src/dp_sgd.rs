@@ -0,0 +1,220 @@
+// Client-side DP-SGD (Abadi et al., 2016) in the style of Opacus, so every site
+// applies differential privacy the same way. Each local step takes the per-sample
+// gradients of a batch, averages them over micro-batches (of one sample for classic
+// per-sample clipping), clips every micro-batch gradient to `max_grad_norm`, sums
+// them, adds Gaussian noise of std `noise_multiplier * max_grad_norm` and divides by
+// the number of micro-batches.
+//
+// Privacy is tracked with the Rényi DP accountant for the Poisson-subsampled
+// Gaussian mechanism (Mironov et al., 2019), at integer orders, and converted to
+// (ε, δ) with the bound of Balle et al. (2020) that Opacus also uses. The same
+// accountant, run backwards, gives the noise multiplier that meets a target (ε, δ)
+// for a planned number of local steps.
+
+use crate::*;
+use rand_distr::{Distribution, Normal};
+
+const RDP_ORDERS: std::ops::RangeInclusive<u32> = 2..=256;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DpSgdConfig {
+    pub max_grad_norm: f64,
+    pub noise_multiplier: f64,
+    // Samples averaged together before clipping; 1 clips every sample on its own
+    pub micro_batch_size: usize,
+    // Probability that a record is in a given batch: batch size over local dataset size
+    pub sample_rate: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DpSgdStats {
+    pub samples: usize,
+    pub micro_batches: usize,
+    pub micro_batches_clipped: usize,
+    pub noise_std: f64,
+}
+
+pub struct DpSgd {
+    config: DpSgdConfig,
+    steps: u64,
+}
+
+// RDP of one step at order `alpha`
+pub fn rdp_sampled_gaussian(sample_rate: f64, noise_multiplier: f64, alpha: u32) -> f64 {
+    if noise_multiplier <= 0.0 {
+        return f64::INFINITY;
+    }
+    if sample_rate >= 1.0 {
+        return alpha as f64 / (2.0 * noise_multiplier * noise_multiplier);
+    }
+    // The log moment at λ = α - 1 (moments_accountant in differential_privacy)
+    differential_privacy::sampled_gaussian_log_moment(sample_rate, noise_multiplier, alpha - 1) / (alpha as f64 - 1.0)
+}
+
+// ε after `steps` steps, minimised over the RDP orders
+pub fn dp_sgd_epsilon(sample_rate: f64, noise_multiplier: f64, steps: u64, delta: f64) -> f64 {
+    if steps == 0 || sample_rate <= 0.0 {
+        return 0.0;
+    }
+    let orders: Vec<f64> = RDP_ORDERS.map(f64::from).collect();
+    let rdp: Vec<f64> = RDP_ORDERS.map(|alpha| steps as f64 * rdp_sampled_gaussian(sample_rate, noise_multiplier, alpha)).collect();
+    rdp_to_epsilon(&orders, &rdp, delta)
+}
+
+// Smallest noise multiplier, to within 0.01 in ε, whose ε after `steps` steps stays
+// within `target_epsilon`
+pub fn dp_sgd_noise_multiplier(target_epsilon: f64, delta: f64, sample_rate: f64, steps: u64) -> Result<f64, String> {
+    if !(target_epsilon.is_finite() && target_epsilon > 0.0) {
+        return Err("Target epsilon must be positive".to_string());
+    }
+    if !(delta > 0.0 && delta < 1.0) {
+        return Err("Delta must be in (0, 1)".to_string());
+    }
+    if !(sample_rate > 0.0 && sample_rate <= 1.0) || steps == 0 {
+        return Err("Sample rate must be in (0, 1] and steps at least 1".to_string());
+    }
+
+    let (mut low, mut high) = (0.0, 10.0);
+    while dp_sgd_epsilon(sample_rate, high, steps, delta) > target_epsilon {
+        low = high;
+        high *= 2.0;
+        if high > 1e6 {
+            return Err(format!("No noise multiplier reaches epsilon {}", target_epsilon));
+        }
+    }
+    while target_epsilon - dp_sgd_epsilon(sample_rate, high, steps, delta) > 0.01 {
+        let mid = (low + high) / 2.0;
+        if dp_sgd_epsilon(sample_rate, mid, steps, delta) > target_epsilon {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(high)
+}
+
+impl DpSgdConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.max_grad_norm.is_finite() && self.max_grad_norm > 0.0) {
+            return Err("max_grad_norm must be positive".to_string());
+        }
+        if !(self.noise_multiplier.is_finite() && self.noise_multiplier >= 0.0) {
+            return Err("noise_multiplier must be non-negative".to_string());
+        }
+        if self.micro_batch_size == 0 {
+            return Err("micro_batch_size must be at least 1".to_string());
+        }
+        if !(self.sample_rate > 0.0 && self.sample_rate <= 1.0) {
+            return Err("sample_rate must be in (0, 1]".to_string());
+        }
+        Ok(())
+    }
+
+    // Config whose noise meets (target_epsilon, delta) after `steps` local steps
+    pub fn for_target(
+        target_epsilon: f64,
+        delta: f64,
+        steps: u64,
+        sample_rate: f64,
+        max_grad_norm: f64,
+        micro_batch_size: usize,
+    ) -> Result<Self, String> {
+        let noise_multiplier = dp_sgd_noise_multiplier(target_epsilon, delta, sample_rate, steps)?;
+        let config = DpSgdConfig { max_grad_norm, noise_multiplier, micro_batch_size, sample_rate };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl DpSgd {
+    pub fn new(config: DpSgdConfig) -> Result<Self, String> {
+        config.validate()?;
+        Ok(DpSgd { config, steps: 0 })
+    }
+
+    pub fn config(&self) -> &DpSgdConfig {
+        &self.config
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn epsilon_spent(&self, delta: f64) -> f64 {
+        dp_sgd_epsilon(self.config.sample_rate, self.config.noise_multiplier, self.steps, delta)
+    }
+
+    // Privatised gradient of one batch from its per-sample gradients; counts as a step
+    pub fn private_gradient(&mut self, per_sample_gradients: &[Vec<f64>]) -> Result<(Vec<f64>, DpSgdStats), String> {
+        let dimension = per_sample_gradients.first().ok_or("Batch has no samples")?.len();
+        if per_sample_gradients.iter().any(|g| g.len() != dimension) {
+            return Err("Per-sample gradients differ in length".to_string());
+        }
+
+        let mut stats = DpSgdStats { samples: per_sample_gradients.len(), ..Default::default() };
+        let mut sum = vec![0.0; dimension];
+        for micro_batch in per_sample_gradients.chunks(self.config.micro_batch_size) {
+            let mut mean = vec![0.0; dimension];
+            for gradient in micro_batch {
+                kernels::axpy(1.0 / micro_batch.len() as f64, gradient, &mut mean);
+            }
+            let norm = kernels::l2_norm(&mean);
+            let factor = if norm > self.config.max_grad_norm {
+                stats.micro_batches_clipped += 1;
+                self.config.max_grad_norm / norm
+            } else {
+                1.0
+            };
+            kernels::axpy(factor, &mean, &mut sum);
+            stats.micro_batches += 1;
+        }
+
+        stats.noise_std = self.config.noise_multiplier * self.config.max_grad_norm;
+        if stats.noise_std > 0.0 {
+            let normal = Normal::new(0.0, stats.noise_std).map_err(|e| e.to_string())?;
+            let mut rng = rand::thread_rng();
+            sum.iter_mut().for_each(|s| *s += normal.sample(&mut rng));
+        }
+        let scale = 1.0 / stats.micro_batches as f64;
+        sum.iter_mut().for_each(|s| *s *= scale);
+        self.steps += 1;
+        Ok((sum, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_multiplier_meets_target_and_samples_are_clipped() {
+        // Without subsampling each step is the plain Gaussian mechanism, α / (2σ²)
+        assert!((rdp_sampled_gaussian(1.0, 2.0, 8) - 1.0).abs() < 1e-12);
+        // Subsampling only ever helps
+        assert!(rdp_sampled_gaussian(0.01, 1.0, 8) < rdp_sampled_gaussian(1.0, 1.0, 8));
+
+        let (sample_rate, steps, delta) = (256.0 / 60_000.0, 2_000, 1e-5);
+        let sigma = dp_sgd_noise_multiplier(3.0, delta, sample_rate, steps).unwrap();
+        let epsilon = dp_sgd_epsilon(sample_rate, sigma, steps, delta);
+        assert!(epsilon <= 3.0 && epsilon > 2.9);
+        assert!(dp_sgd_noise_multiplier(1.0, delta, sample_rate, steps).unwrap() > sigma);
+        assert!(dp_sgd_noise_multiplier(0.0, delta, sample_rate, steps).is_err());
+
+        let config = DpSgdConfig { max_grad_norm: 1.0, noise_multiplier: 0.0, micro_batch_size: 1, sample_rate };
+        let mut dp_sgd = DpSgd::new(config.clone()).unwrap();
+        let batch = vec![vec![3.0, 4.0], vec![0.5, 0.0], vec![0.0, -0.5], vec![0.0, 0.0]];
+        let (gradient, stats) = dp_sgd.private_gradient(&batch).unwrap();
+        // Only the first sample exceeds the bound; it is scaled to (0.6, 0.8)
+        assert_eq!((stats.micro_batches, stats.micro_batches_clipped), (4, 1));
+        assert!((gradient[0] - 1.1 / 4.0).abs() < 1e-12 && (gradient[1] - 0.3 / 4.0).abs() < 1e-12);
+        assert_eq!(dp_sgd.steps(), 1);
+
+        let mut micro = DpSgd::new(DpSgdConfig { micro_batch_size: 2, ..config }).unwrap();
+        let (gradient, stats) = micro.private_gradient(&batch).unwrap();
+        // Micro-batch means (1.75, 2.0) and (0.0, -0.25); only the first is clipped
+        assert_eq!((stats.micro_batches, stats.micro_batches_clipped), (2, 1));
+        let norm = (1.75f64.powi(2) + 4.0).sqrt();
+        assert!((gradient[1] - (2.0 / norm - 0.25) / 2.0).abs() < 1e-12);
+        assert!(micro.private_gradient(&[vec![1.0], vec![1.0, 2.0]]).is_err());
+    }
+}
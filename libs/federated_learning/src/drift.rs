@@ -0,0 +1,307 @@
+// Per-site drift detection. Each hospital summarises its extracted features (no raw
+// rows leave the site) and the coordinator compares the summary against the
+// consortium baseline before accepting that site's updates.
+
+use crate::*;
+use medical_data::{MedicalDataset, ObservationValue};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureStats {
+    pub name: String,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SiteFeatureProfile {
+    pub site_id: String,
+    pub schema_version: u32,
+    pub schema_fingerprint: String,
+    pub num_samples: usize,
+    pub features: Vec<FeatureStats>,
+    // Units seen locally for each lab in the schema, keyed by LOINC code
+    pub lab_units: HashMap<String, Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ConsortiumBaseline {
+    pub schema: FeatureSchema,
+    pub features: Vec<FeatureStats>,
+    pub lab_units: HashMap<String, Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DriftThresholds {
+    // Shift of the site mean, in baseline standard deviations
+    pub max_mean_shift: f64,
+    // Site std over baseline std, checked in both directions
+    pub max_std_ratio: f64,
+    // Baseline prevalence above which a code absent at the site counts as missing
+    pub min_code_prevalence: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum DriftKind {
+    SchemaMismatch { expected: String, found: String },
+    MeanShift { feature: String, baseline: f64, observed: f64, shift: f64 },
+    VarianceChange { feature: String, baseline: f64, observed: f64 },
+    MissingCode { feature: String, baseline_prevalence: f64 },
+    NewLabUnit { loinc_code: String, unit: String },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DriftReport {
+    pub site_id: String,
+    pub findings: Vec<DriftKind>,
+}
+
+impl Default for DriftThresholds {
+    fn default() -> Self {
+        DriftThresholds {
+            max_mean_shift: 3.0,
+            max_std_ratio: 3.0,
+            min_code_prevalence: 0.05,
+        }
+    }
+}
+
+impl SiteFeatureProfile {
+    pub fn from_matrix(site_id: &str, schema: &FeatureSchema, matrix: &FeatureMatrix, dataset: &MedicalDataset) -> Self {
+        let features = schema
+            .features
+            .iter()
+            .enumerate()
+            .map(|(j, feature)| {
+                let column: Vec<f64> = matrix.rows.iter().filter_map(|row| row.get(j).copied()).collect();
+                let (mean, std_dev) = column_stats(&column);
+                FeatureStats { name: feature.name.clone(), mean, std_dev }
+            })
+            .collect();
+
+        SiteFeatureProfile {
+            site_id: site_id.to_string(),
+            schema_version: schema.version,
+            schema_fingerprint: matrix.schema_fingerprint.clone(),
+            num_samples: matrix.rows.len(),
+            features,
+            lab_units: observed_lab_units(schema, dataset),
+        }
+    }
+}
+
+impl ConsortiumBaseline {
+    // Pools site profiles sharing the schema, weighting each site by its sample count
+    pub fn from_profiles(schema: FeatureSchema, profiles: &[SiteFeatureProfile]) -> Result<Self, String> {
+        let fingerprint = schema.fingerprint();
+        let matching: Vec<&SiteFeatureProfile> =
+            profiles.iter().filter(|p| p.schema_fingerprint == fingerprint).collect();
+        let total: usize = matching.iter().map(|p| p.num_samples).sum();
+        if total == 0 {
+            return Err("No site profiles with samples match the baseline schema".to_string());
+        }
+
+        let features = schema
+            .features
+            .iter()
+            .enumerate()
+            .map(|(j, feature)| {
+                let mean = matching
+                    .iter()
+                    .map(|p| p.features[j].mean * p.num_samples as f64)
+                    .sum::<f64>()
+                    / total as f64;
+                // Pooled variance: within-site variance plus between-site spread of means
+                let variance = matching
+                    .iter()
+                    .map(|p| {
+                        let f = &p.features[j];
+                        p.num_samples as f64 * (f.std_dev.powi(2) + (f.mean - mean).powi(2))
+                    })
+                    .sum::<f64>()
+                    / total as f64;
+                FeatureStats { name: feature.name.clone(), mean, std_dev: variance.sqrt() }
+            })
+            .collect();
+
+        let mut lab_units: HashMap<String, Vec<String>> = HashMap::new();
+        for profile in &matching {
+            for (code, units) in &profile.lab_units {
+                let entry = lab_units.entry(code.clone()).or_default();
+                for unit in units {
+                    if !entry.contains(unit) {
+                        entry.push(unit.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(ConsortiumBaseline { schema, features, lab_units })
+    }
+}
+
+pub struct DriftDetector {
+    baseline: ConsortiumBaseline,
+    thresholds: DriftThresholds,
+}
+
+impl DriftDetector {
+    pub fn new(baseline: ConsortiumBaseline, thresholds: DriftThresholds) -> Self {
+        DriftDetector { baseline, thresholds }
+    }
+
+    pub fn check(&self, profile: &SiteFeatureProfile) -> DriftReport {
+        let mut findings = Vec::new();
+
+        let expected = self.baseline.schema.fingerprint();
+        if profile.schema_version != self.baseline.schema.version || profile.schema_fingerprint != expected {
+            findings.push(DriftKind::SchemaMismatch {
+                expected: format!("v{} {}", self.baseline.schema.version, expected),
+                found: format!("v{} {}", profile.schema_version, profile.schema_fingerprint),
+            });
+            // Feature columns are not comparable under a different schema
+            return DriftReport { site_id: profile.site_id.clone(), findings };
+        }
+
+        for ((definition, base), site) in self
+            .baseline
+            .schema
+            .features
+            .iter()
+            .zip(self.baseline.features.iter())
+            .zip(profile.features.iter())
+        {
+            let is_indicator = matches!(
+                definition.kind,
+                FeatureKind::ConditionCode { .. } | FeatureKind::HpoTerm { .. } | FeatureKind::LabObserved { .. }
+            );
+            if is_indicator && site.mean == 0.0 && base.mean >= self.thresholds.min_code_prevalence {
+                findings.push(DriftKind::MissingCode { feature: base.name.clone(), baseline_prevalence: base.mean });
+                continue;
+            }
+
+            if base.std_dev > 0.0 {
+                let shift = (site.mean - base.mean).abs() / base.std_dev;
+                if shift > self.thresholds.max_mean_shift {
+                    findings.push(DriftKind::MeanShift {
+                        feature: base.name.clone(),
+                        baseline: base.mean,
+                        observed: site.mean,
+                        shift,
+                    });
+                }
+                let ratio = site.std_dev / base.std_dev;
+                if ratio > self.thresholds.max_std_ratio || (ratio > 0.0 && ratio < 1.0 / self.thresholds.max_std_ratio) {
+                    findings.push(DriftKind::VarianceChange {
+                        feature: base.name.clone(),
+                        baseline: base.std_dev,
+                        observed: site.std_dev,
+                    });
+                }
+            }
+        }
+
+        for (code, units) in &profile.lab_units {
+            let known = self.baseline.lab_units.get(code);
+            for unit in units {
+                if !known.is_some_and(|k| k.contains(unit)) {
+                    findings.push(DriftKind::NewLabUnit { loinc_code: code.clone(), unit: unit.clone() });
+                }
+            }
+        }
+
+        DriftReport { site_id: profile.site_id.clone(), findings }
+    }
+
+    pub fn drifted_sites(&self, profiles: &[SiteFeatureProfile]) -> Vec<DriftReport> {
+        profiles
+            .iter()
+            .map(|p| self.check(p))
+            .filter(|r| !r.findings.is_empty())
+            .collect()
+    }
+}
+
+fn column_stats(column: &[f64]) -> (f64, f64) {
+    if column.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = column.len() as f64;
+    let mean = column.iter().sum::<f64>() / n;
+    let variance = column.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+fn observed_lab_units(schema: &FeatureSchema, dataset: &MedicalDataset) -> HashMap<String, Vec<String>> {
+    let mut units: HashMap<String, Vec<String>> = HashMap::new();
+
+    for feature in &schema.features {
+        let FeatureKind::LabValue { loinc_code, .. } = &feature.kind else {
+            continue;
+        };
+        let entry = units.entry(loinc_code.clone()).or_default();
+        for observation in &dataset.observations {
+            let mut values = Vec::new();
+            if observation.code.has_loinc_code(loinc_code) {
+                values.extend(observation.value.iter());
+            }
+            if let Some(component) = observation.component_by_loinc(loinc_code) {
+                values.extend(component.value.iter());
+            }
+            for value in values {
+                if let ObservationValue::Quantity(quantity) = value {
+                    if let Some(unit) = &quantity.unit {
+                        if !entry.contains(unit) {
+                            entry.push(unit.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::*;
+
+    fn site(site_id: &str, glucose_unit: &str, glucose: f64, has_diabetes: bool) -> (FeatureSchema, SiteFeatureProfile) {
+        let schema = FeatureSchema::new(2024).with_condition("E11").with_lab("2345-7", 100.0, 20.0);
+        let mut dataset = MedicalDataset::new(site_id.to_string(), site_id.to_string(), String::new());
+        for i in 0..4 {
+            let id = format!("{}-{}", site_id, i);
+            dataset.patients.push(Patient::new(id.clone()));
+            if has_diabetes && i % 2 == 0 {
+                let mut condition = Condition::new(format!("c{}", i), create_reference(&format!("Patient/{}", id), None));
+                condition.set_code(CodeableConcept::icd10("E11").unwrap());
+                dataset.conditions.push(condition);
+            }
+            let code = CodeableConcept::loinc("2345-7").unwrap();
+            let mut observation = Observation::new(format!("o{}", i), code, create_reference(&format!("Patient/{}", id), None));
+            observation.set_value(ObservationValue::Quantity(create_quantity(glucose + 10.0 * i as f64, glucose_unit, None, None)));
+            dataset.observations.push(observation);
+        }
+        let matrix = FeatureExtractor::new(schema.clone()).extract_dataset(&dataset);
+        let profile = SiteFeatureProfile::from_matrix(site_id, &schema, &matrix, &dataset);
+        (schema, profile)
+    }
+
+    #[test]
+    fn test_unit_and_code_drift_flagged() {
+        let (schema, a) = site("a", "mg/dL", 95.0, true);
+        let (_, b) = site("b", "mg/dL", 105.0, true);
+        let baseline = ConsortiumBaseline::from_profiles(schema, &[a.clone(), b]).unwrap();
+        let detector = DriftDetector::new(baseline, DriftThresholds::default());
+
+        assert!(detector.check(&a).findings.is_empty());
+
+        // Glucose reported in mmol/L and no diabetes codes at all
+        let (_, drifted) = site("c", "mmol/L", 5.5, false);
+        let findings = detector.check(&drifted).findings;
+        assert!(findings.iter().any(|f| matches!(f, DriftKind::NewLabUnit { unit, .. } if unit == "mmol/L")));
+        assert!(findings.iter().any(|f| matches!(f, DriftKind::MissingCode { feature, .. } if feature == "condition_E11")));
+        assert!(findings.iter().any(|f| matches!(f, DriftKind::MeanShift { feature, .. } if feature == "lab_2345-7")));
+    }
+}
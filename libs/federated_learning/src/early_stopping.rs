@@ -0,0 +1,182 @@
+// When to stop training. `FederatedLearningConfig::early_stopping` picks one of the
+// built-in strategies; `set_stopping_criterion` replaces it with any other rule over
+// the round records (see history.rs), which keep every retained round's loss,
+// accuracy and gradient norm even once its snapshot is compacted.
+//
+// - LossVariance: variance of the last `window` losses below
+//   `convergence_threshold`, once `min_rounds` rounds have run (the original check).
+// - LossPatience: the loss has not improved on its best by more than `min_delta` for
+//   `patience` rounds.
+// - AccuracyPlateau: the same for accuracy, which should rise.
+// - GradientNorm: the aggregated update's norm stayed below `threshold` for
+//   `consecutive` rounds in a row.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum EarlyStoppingStrategy {
+    LossVariance { window: usize, min_rounds: u64 },
+    LossPatience { patience: u32, min_delta: f64 },
+    AccuracyPlateau { patience: u32, min_delta: f64 },
+    GradientNorm { threshold: f64, consecutive: u32 },
+}
+
+pub trait StoppingCriterion {
+    // Why training should stop after the latest of `records` (oldest first), if it should
+    fn should_stop(&self, records: &[RoundRecord], config: &FederatedLearningConfig) -> Option<String>;
+}
+
+impl Default for EarlyStoppingStrategy {
+    fn default() -> Self {
+        EarlyStoppingStrategy::LossVariance { window: 5, min_rounds: 10 }
+    }
+}
+
+impl EarlyStoppingStrategy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            EarlyStoppingStrategy::LossVariance { window, .. } if *window < 2 => {
+                Err("Loss variance needs a window of at least 2 rounds".to_string())
+            }
+            EarlyStoppingStrategy::LossPatience { patience, min_delta } | EarlyStoppingStrategy::AccuracyPlateau { patience, min_delta } => {
+                if *patience == 0 {
+                    return Err("Early stopping patience must be at least 1 round".to_string());
+                }
+                if min_delta.is_nan() || *min_delta < 0.0 {
+                    return Err("Early stopping min_delta must be non-negative".to_string());
+                }
+                Ok(())
+            }
+            EarlyStoppingStrategy::GradientNorm { threshold, consecutive } => {
+                if !(threshold.is_finite() && *threshold > 0.0) {
+                    return Err("Gradient norm threshold must be positive".to_string());
+                }
+                if *consecutive == 0 {
+                    return Err("Gradient norm stopping needs at least 1 round".to_string());
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Rounds since `values`, where higher is better, last beat their best by more than
+// `min_delta`
+fn rounds_without_improvement(values: impl Iterator<Item = f64>, min_delta: f64) -> usize {
+    let mut best = f64::NEG_INFINITY;
+    let mut since_best = 0;
+    for value in values.filter(|v| v.is_finite()) {
+        if value > best + min_delta {
+            best = value;
+            since_best = 0;
+        } else {
+            since_best += 1;
+        }
+    }
+    since_best
+}
+
+impl StoppingCriterion for EarlyStoppingStrategy {
+    fn should_stop(&self, records: &[RoundRecord], config: &FederatedLearningConfig) -> Option<String> {
+        match self {
+            EarlyStoppingStrategy::LossVariance { window, min_rounds } => {
+                let latest = records.last()?.round;
+                if latest < *min_rounds || records.len() < *window {
+                    return None;
+                }
+                let losses: Vec<f64> = records[records.len() - window..].iter().map(|r| r.global_loss).collect();
+                let mean = losses.iter().sum::<f64>() / losses.len() as f64;
+                let variance = losses.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / losses.len() as f64;
+                (variance < config.convergence_threshold)
+                    .then(|| format!("Loss variance {:.3e} over the last {} rounds is below {:.3e}", variance, window, config.convergence_threshold))
+            }
+            EarlyStoppingStrategy::LossPatience { patience, min_delta } => {
+                let stalled = rounds_without_improvement(records.iter().map(|r| -r.global_loss), *min_delta);
+                (stalled >= *patience as usize).then(|| format!("Loss has not improved for {} rounds", stalled))
+            }
+            EarlyStoppingStrategy::AccuracyPlateau { patience, min_delta } => {
+                let stalled = rounds_without_improvement(records.iter().map(|r| r.global_accuracy), *min_delta);
+                (stalled >= *patience as usize).then(|| format!("Accuracy has plateaued for {} rounds", stalled))
+            }
+            EarlyStoppingStrategy::GradientNorm { threshold, consecutive } => {
+                let below = records.iter().rev().take_while(|r| r.gradient_norm < *threshold).count();
+                (below >= *consecutive as usize)
+                    .then(|| format!("Update norm has stayed below {} for {} rounds", threshold, below))
+            }
+        }
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Overrides the configured strategy; None goes back to it
+    pub fn set_stopping_criterion(&mut self, criterion: Option<Box<dyn StoppingCriterion>>) {
+        self.stopping_criterion = criterion;
+    }
+
+    pub fn stopping_reason(&self) -> Option<String> {
+        let records = self.get_round_records();
+        match &self.stopping_criterion {
+            Some(criterion) => criterion.should_stop(&records, &self.config),
+            None => self.config.early_stopping.should_stop(&records, &self.config),
+        }
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.stopping_reason().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(values: &[(f64, f64, f64)]) -> Vec<RoundRecord> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &(global_loss, global_accuracy, gradient_norm))| RoundRecord {
+                round: i as u64 + 1,
+                global_loss,
+                global_accuracy,
+                participants: 3,
+                effective_sample_count: 30,
+                gradient_norm,
+                weight_change_norm: gradient_norm,
+                total_epsilon_used: 0.0,
+                effective_round_epsilon: 0.0,
+                total_bytes_received: 0,
+                round_energy_kwh: 0.0,
+                updates_clipped: 0,
+                backdoor_flags: 0,
+                candidate_decision: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies_stop_on_their_own_signal() {
+        let config = FederatedLearningConfigBuilder::new().build().unwrap();
+        // Loss stalls after round 3 while accuracy keeps creeping up and updates shrink
+        let history = records(&[
+            (1.0, 0.50, 2.0),
+            (0.6, 0.60, 1.0),
+            (0.5, 0.70, 0.5),
+            (0.5, 0.72, 0.05),
+            (0.51, 0.74, 0.04),
+            (0.5, 0.76, 0.03),
+        ]);
+
+        let patience = EarlyStoppingStrategy::LossPatience { patience: 3, min_delta: 0.01 };
+        assert!(patience.should_stop(&history, &config).is_some());
+        assert!(patience.should_stop(&history[..5], &config).is_none());
+        let plateau = EarlyStoppingStrategy::AccuracyPlateau { patience: 2, min_delta: 0.01 };
+        assert!(plateau.should_stop(&history, &config).is_none());
+        let norm = EarlyStoppingStrategy::GradientNorm { threshold: 0.1, consecutive: 3 };
+        assert!(norm.should_stop(&history, &config).is_some());
+        assert!(norm.should_stop(&history[..5], &config).is_none());
+        // The default waits for ten rounds
+        assert!(EarlyStoppingStrategy::default().should_stop(&history, &config).is_none());
+        assert!(EarlyStoppingStrategy::AccuracyPlateau { patience: 0, min_delta: 0.0 }.validate().is_err());
+    }
+}
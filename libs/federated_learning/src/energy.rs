@@ -0,0 +1,155 @@
+// Energy and carbon accounting. Funders ask for the footprint of training in project
+// reports, so each round's energy and emissions are estimated from what the round
+// actually did rather than from a flat per-client guess. The default model charges a
+// client its measured wall-clock training time at its device's power draw (TDP times
+// average utilisation) plus the energy of moving its update over the network, and
+// converts energy to CO2-equivalent with the carbon intensity of the site's grid.
+// Other models plug in through `EnergyModel`, e.g. one fed by metered power readings.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub tdp_watts: f64,
+    // Average fraction of TDP drawn while training
+    pub utilization: f64,
+    // Grams CO2e per kWh of the grid the device runs on
+    pub carbon_intensity_g_per_kwh: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct EnergyEstimate {
+    pub energy_kwh: f64,
+    pub carbon_kg_co2e: f64,
+}
+
+pub trait EnergyModel {
+    fn estimate(&self, update: &ModelUpdate) -> EnergyEstimate;
+}
+
+// Measured wall-clock time x device power, plus network transfer
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TdpEnergyModel {
+    // Per-client device profiles; clients without one use `default_profile`
+    pub profiles: HashMap<String, DeviceProfile>,
+    pub default_profile: DeviceProfile,
+    pub network_kwh_per_gb: f64,
+}
+
+impl Default for DeviceProfile {
+    // A single-GPU hospital workstation on a grid near the world average
+    fn default() -> Self {
+        DeviceProfile {
+            name: "workstation-gpu".to_string(),
+            tdp_watts: 300.0,
+            utilization: 0.7,
+            carbon_intensity_g_per_kwh: 475.0,
+        }
+    }
+}
+
+impl Default for TdpEnergyModel {
+    fn default() -> Self {
+        TdpEnergyModel {
+            profiles: HashMap::new(),
+            default_profile: DeviceProfile::default(),
+            network_kwh_per_gb: 0.06,
+        }
+    }
+}
+
+impl DeviceProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.tdp_watts.is_finite() && self.tdp_watts > 0.0) {
+            return Err(format!("Device profile {} needs a positive TDP", self.name));
+        }
+        if !(self.utilization > 0.0 && self.utilization <= 1.0) {
+            return Err(format!("Device profile {} utilization must be in (0, 1]", self.name));
+        }
+        if !(self.carbon_intensity_g_per_kwh.is_finite() && self.carbon_intensity_g_per_kwh >= 0.0) {
+            return Err(format!("Device profile {} carbon intensity must be non-negative", self.name));
+        }
+        Ok(())
+    }
+
+    pub fn compute_energy_kwh(&self, seconds: f64) -> f64 {
+        self.tdp_watts * self.utilization * seconds.max(0.0) / 3_600_000.0
+    }
+
+    pub fn carbon_kg(&self, energy_kwh: f64) -> f64 {
+        energy_kwh * self.carbon_intensity_g_per_kwh / 1000.0
+    }
+}
+
+impl EnergyEstimate {
+    pub fn add(&mut self, other: EnergyEstimate) {
+        self.energy_kwh += other.energy_kwh;
+        self.carbon_kg_co2e += other.carbon_kg_co2e;
+    }
+}
+
+impl TdpEnergyModel {
+    pub fn validate(&self) -> Result<(), String> {
+        self.default_profile.validate()?;
+        self.profiles.values().try_for_each(DeviceProfile::validate)?;
+        if !(self.network_kwh_per_gb.is_finite() && self.network_kwh_per_gb >= 0.0) {
+            return Err("network_kwh_per_gb must be non-negative".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn profile(&self, client_id: &str) -> &DeviceProfile {
+        self.profiles.get(client_id).unwrap_or(&self.default_profile)
+    }
+}
+
+impl EnergyModel for TdpEnergyModel {
+    fn estimate(&self, update: &ModelUpdate) -> EnergyEstimate {
+        let profile = self.profile(&update.client_id);
+        let network_kwh = update.communication_cost.max(0.0) / 1e9 * self.network_kwh_per_gb;
+        let energy_kwh = profile.compute_energy_kwh(update.computation_time) + network_kwh;
+        EnergyEstimate { energy_kwh, carbon_kg_co2e: profile.carbon_kg(energy_kwh) }
+    }
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_energy_model(&mut self, model: Box<dyn EnergyModel>) {
+        self.energy_model = model;
+    }
+
+    pub(crate) fn round_energy(&self, updates: &[ModelUpdate]) -> EnergyEstimate {
+        let mut total = EnergyEstimate::default();
+        for update in updates {
+            total.add(self.energy_model.estimate(update));
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_energy_follows_measured_time_and_site_grid() {
+        let mut model = TdpEnergyModel::default();
+        let clean_grid = DeviceProfile { name: "hydro-site".to_string(), carbon_intensity_g_per_kwh: 20.0, ..DeviceProfile::default() };
+        model.profiles.insert("hospital_b".to_string(), clean_grid);
+        assert!(model.validate().is_ok());
+
+        let mut update = ModelUpdate {
+            // One hour at 210 W, plus 1 GB over the network
+            computation_time: 3600.0,
+            communication_cost: 1e9,
+            ..ModelUpdate::for_test("hospital_a", Vec::new(), 1)
+        };
+        let estimate = model.estimate(&update);
+        assert!((estimate.energy_kwh - 0.27).abs() < 1e-12);
+        assert!((estimate.carbon_kg_co2e - 0.27 * 0.475).abs() < 1e-12);
+
+        update.client_id = "hospital_b".to_string();
+        assert!(model.estimate(&update).carbon_kg_co2e < estimate.carbon_kg_co2e / 20.0);
+        assert!(DeviceProfile { utilization: 1.5, ..DeviceProfile::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+// Experiment manifests for benchmark results. A number in a report is only worth as
+// much as the ability to say how it was produced, so every `PerformanceBenchmark`,
+// and every `CostAnalysis` derived from one, carries the manifest of the run: the
+// source revision, the full configuration, the seed, the dataset statistics and the
+// build environment. The manifest id is a SHA-256 over all of it, so an auditor can
+// check a manifest has not been edited since the run and regenerate the result from it.
+//
+// The source revision is taken from the `GIT_COMMIT` environment variable at build
+// time (CI sets it); local builds without it record "unknown" unless the caller
+// supplies the revision.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DatasetStatistics {
+    pub source: BenchmarkDataset,
+    pub dataset_size: usize,
+    pub num_clients: u32,
+    pub mean_samples_per_client: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExperimentEnvironment {
+    pub crate_version: String,
+    pub target_os: String,
+    pub target_arch: String,
+    pub profile: String,
+    pub features: Vec<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ExperimentManifest {
+    pub manifest_id: String,
+    pub git_commit: String,
+    pub config: FederatedLearningConfig,
+    // Seed for the run's randomness (client sampling, noise, initialisation)
+    pub seed: u64,
+    pub dataset: DatasetStatistics,
+    pub environment: ExperimentEnvironment,
+}
+
+impl DatasetStatistics {
+    pub fn new(source: BenchmarkDataset, dataset_size: usize, num_clients: u32) -> Self {
+        DatasetStatistics {
+            source,
+            dataset_size,
+            num_clients,
+            mean_samples_per_client: dataset_size as f64 / num_clients.max(1) as f64,
+        }
+    }
+}
+
+impl ExperimentEnvironment {
+    // The build this code was compiled in
+    pub fn current() -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "wasm-simd") {
+            features.push("wasm-simd".to_string());
+        }
+        if cfg!(feature = "avx") {
+            features.push("avx".to_string());
+        }
+        if cfg!(feature = "experimental") {
+            features.push("experimental".to_string());
+        }
+        ExperimentEnvironment {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            target_os: std::env::consts::OS.to_string(),
+            target_arch: std::env::consts::ARCH.to_string(),
+            profile: if cfg!(debug_assertions) { "debug" } else { "release" }.to_string(),
+            features,
+        }
+    }
+}
+
+impl ExperimentManifest {
+    pub fn new(config: FederatedLearningConfig, seed: u64, dataset: DatasetStatistics) -> Self {
+        let mut manifest = ExperimentManifest {
+            manifest_id: String::new(),
+            git_commit: option_env!("GIT_COMMIT").unwrap_or("unknown").to_string(),
+            config,
+            seed,
+            dataset,
+            environment: ExperimentEnvironment::current(),
+        };
+        manifest.manifest_id = manifest.compute_id();
+        manifest
+    }
+
+    pub fn with_git_commit(mut self, git_commit: &str) -> Self {
+        self.git_commit = git_commit.to_string();
+        self.manifest_id = self.compute_id();
+        self
+    }
+
+    // SHA-256 over every field except the id itself
+    pub fn compute_id(&self) -> String {
+        let unsigned = ExperimentManifest { manifest_id: String::new(), ..self.clone() };
+        let json = serde_json::to_string(&unsigned).unwrap_or_default();
+        format!("{:x}", Sha256::digest(json.as_bytes()))
+    }
+
+    pub fn verify(&self) -> Result<(), String> {
+        if self.compute_id() != self.manifest_id {
+            return Err(format!("Experiment manifest {} does not match its contents", self.manifest_id));
+        }
+        Ok(())
+    }
+
+    // Reruns the experiment the manifest describes
+    pub fn reproduce(&self) -> Result<PerformanceBenchmark, String> {
+        self.verify()?;
+        simulate_federated_learning(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmarks_are_attributed_and_reproducible() {
+        let algorithms = vec![FLAlgorithm::FedAvg, FLAlgorithm::SCAFFOLD];
+        let benchmarks = benchmark_federated_algorithms(algorithms, BenchmarkDataset::VitalSigns, 1_000, 10, 42).unwrap();
+        let manifest = &benchmarks[1].manifest;
+        assert!(manifest.verify().is_ok());
+        assert_eq!((manifest.seed, manifest.dataset.mean_samples_per_client), (42, 100.0));
+        assert_ne!(benchmarks[0].manifest.manifest_id, manifest.manifest_id);
+
+        let rerun = manifest.reproduce().unwrap();
+        assert_eq!(rerun.rounds_to_convergence, benchmarks[1].rounds_to_convergence);
+        assert_eq!(rerun.final_accuracy, benchmarks[1].final_accuracy);
+        assert!(benchmarks.iter().all(|b| b.final_accuracy > 0.8 && b.rounds_to_convergence < 100), "{:?}", benchmarks);
+        assert_eq!(rerun.manifest.manifest_id, manifest.manifest_id);
+
+        let costs = analyze_federated_learning_costs(&benchmarks[1], 0.1, 1.0, 0.02);
+        assert_eq!(costs.manifest.manifest_id, manifest.manifest_id);
+
+        let mut edited = manifest.clone().with_git_commit("abc123");
+        assert!(edited.verify().is_ok());
+        edited.seed = 7;
+        assert!(edited.reproduce().is_err());
+    }
+}
@@ -0,0 +1,444 @@
+// Conversion of medical records into fixed-length training vectors. The schema is
+// agreed once per consortium and shipped to every hospital, so column i means the
+// same thing at every site regardless of the local data.
+
+use crate::*;
+use chrono::{Datelike, Utc};
+use medical_data::interpretation::InterpretationFlag;
+use medical_data::rare_diseases::RareDiseaseCase;
+use medical_data::{CodeableConcept, Condition, Gender, MedicalDataset, Observation, Patient};
+use std::collections::BTreeSet;
+
+pub const FEATURE_SCHEMA_VERSION: u32 = 1;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureSchema {
+    pub version: u32,
+    // Year used to compute ages; fixed so re-extraction is reproducible
+    pub reference_year: i32,
+    pub features: Vec<FeatureDefinition>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureDefinition {
+    pub name: String,
+    pub kind: FeatureKind,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum FeatureKind {
+    // Age in years divided by `scale`; 0 when the birth date is unknown
+    Age { scale: f64 },
+    // One-hot over "male", "female", "other" and "unknown"
+    Gender { value: String },
+    // 1 if the patient has a condition with this code in any coding system
+    ConditionCode { code: String },
+    // Most recent value, standardised as (x - mean) / std; 0 (the mean) when missing
+    LabValue { loinc_code: String, mean: f64, std_dev: f64 },
+    // 1 if a value for the lab exists, so the model can tell imputed zeros apart
+    LabObserved { loinc_code: String },
+    // One-hot over the most recent result's interpretation flag ("N", "L", "H",
+    // "LL", "HH"); all zero when the result is missing or was never interpreted
+    LabInterpretation { loinc_code: String, flag: String },
+    // 1 if the HPO term is recorded as a condition code or presenting symptom
+    HpoTerm { hpo_id: String },
+}
+
+// Extracted vectors for a set of patients, row i belonging to patient_ids[i]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureMatrix {
+    pub schema_fingerprint: String,
+    pub feature_names: Vec<String>,
+    pub patient_ids: Vec<String>,
+    pub rows: Vec<Vec<f64>>,
+}
+
+impl FeatureSchema {
+    pub fn new(reference_year: i32) -> Self {
+        FeatureSchema {
+            version: FEATURE_SCHEMA_VERSION,
+            reference_year,
+            features: Vec::new(),
+        }
+    }
+
+    pub fn with_demographics(mut self) -> Self {
+        self.push("age", FeatureKind::Age { scale: 100.0 });
+        for value in ["male", "female", "other", "unknown"] {
+            self.push(&format!("gender_{}", value), FeatureKind::Gender { value: value.to_string() });
+        }
+        self
+    }
+
+    pub fn with_condition(mut self, code: &str) -> Self {
+        self.push(&format!("condition_{}", code), FeatureKind::ConditionCode { code: code.to_string() });
+        self
+    }
+
+    pub fn with_lab(mut self, loinc_code: &str, mean: f64, std_dev: f64) -> Self {
+        self.push(
+            &format!("lab_{}", loinc_code),
+            FeatureKind::LabValue { loinc_code: loinc_code.to_string(), mean, std_dev },
+        );
+        self.push(
+            &format!("lab_{}_observed", loinc_code),
+            FeatureKind::LabObserved { loinc_code: loinc_code.to_string() },
+        );
+        self
+    }
+
+    pub fn with_lab_interpretation(mut self, loinc_code: &str) -> Self {
+        for flag in InterpretationFlag::ALL {
+            self.push(
+                &format!("lab_{}_flag_{}", loinc_code, flag.code()),
+                FeatureKind::LabInterpretation { loinc_code: loinc_code.to_string(), flag: flag.code().to_string() },
+            );
+        }
+        self
+    }
+
+    pub fn with_hpo_term(mut self, hpo_id: &str) -> Self {
+        self.push(&format!("hpo_{}", hpo_id), FeatureKind::HpoTerm { hpo_id: hpo_id.to_string() });
+        self
+    }
+
+    fn push(&mut self, name: &str, kind: FeatureKind) {
+        self.features.push(FeatureDefinition { name: name.to_string(), kind });
+    }
+
+    // Derives a schema from a reference dataset: demographics, the `max_conditions`
+    // most frequent condition codes, and standardisation statistics for the given labs
+    pub fn fit(dataset: &MedicalDataset, max_conditions: usize, lab_codes: &[&str]) -> Self {
+        let mut schema = FeatureSchema::new(Utc::now().year()).with_demographics();
+
+        let mut condition_counts: HashMap<String, usize> = HashMap::new();
+        for condition in &dataset.conditions {
+            for code in condition_codes(condition) {
+                *condition_counts.entry(code).or_insert(0) += 1;
+            }
+        }
+        let mut ranked: Vec<(String, usize)> = condition_counts.into_iter().collect();
+        // Ties are broken by code so every site fitting the same data gets the same schema
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (code, _) in ranked.into_iter().take(max_conditions) {
+            schema = schema.with_condition(&code);
+        }
+
+        for &loinc_code in lab_codes {
+            let values: Vec<f64> = dataset
+                .observations
+                .iter()
+                .filter_map(|observation| lab_value(observation, loinc_code))
+                .collect();
+            let (mean, std_dev) = mean_and_std(&values);
+            schema = schema.with_lab(loinc_code, mean, std_dev);
+        }
+
+        schema
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    pub fn feature_names(&self) -> Vec<String> {
+        self.features.iter().map(|f| f.name.clone()).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Failed to serialize feature schema: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let schema: FeatureSchema =
+            serde_json::from_str(json).map_err(|e| format!("Failed to parse feature schema: {}", e))?;
+        if schema.version != FEATURE_SCHEMA_VERSION {
+            return Err(format!(
+                "Unsupported feature schema version {} (expected {})",
+                schema.version, FEATURE_SCHEMA_VERSION
+            ));
+        }
+        Ok(schema)
+    }
+
+    // Stable FNV-1a hash of the serialized schema; sites compare it before training
+    pub fn fingerprint(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in json.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+pub struct FeatureExtractor {
+    schema: FeatureSchema,
+    opt_out: Option<OptOutRegistry>,
+    // LOINC codes of prediction targets, whose observations and derivations are left out
+    target_codes: Vec<String>,
+}
+
+impl FeatureExtractor {
+    pub fn new(schema: FeatureSchema) -> Self {
+        FeatureExtractor { schema, opt_out: None, target_codes: Vec::new() }
+    }
+
+    // Opted-out patients are dropped from every dataset extract
+    pub fn with_opt_out(mut self, registry: OptOutRegistry) -> Self {
+        self.opt_out = Some(registry);
+        self
+    }
+
+    // Observations of the target labs, and everything derived from them (a BMI from a
+    // weight target), are not used as features, so the label cannot leak in
+    pub fn excluding_targets(mut self, loinc_codes: &[&str]) -> Self {
+        self.target_codes = loinc_codes.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn schema(&self) -> &FeatureSchema {
+        &self.schema
+    }
+
+    pub fn opt_out(&self) -> Option<&OptOutRegistry> {
+        self.opt_out.as_ref()
+    }
+
+    pub fn extract_patient(
+        &self,
+        patient: &Patient,
+        conditions: &[&Condition],
+        observations: &[&Observation],
+        hpo_terms: &[String],
+    ) -> Vec<f64> {
+        let codes: Vec<String> = conditions.iter().flat_map(|c| condition_codes(c)).collect();
+
+        self.schema
+            .features
+            .iter()
+            .map(|feature| match &feature.kind {
+                FeatureKind::Age { scale } => patient_age(patient, self.schema.reference_year)
+                    .map(|age| age / scale)
+                    .unwrap_or(0.0),
+                FeatureKind::Gender { value } => indicator(gender_key(patient) == value),
+                FeatureKind::ConditionCode { code } => indicator(codes.iter().any(|c| c == code)),
+                FeatureKind::LabValue { loinc_code, mean, std_dev } => {
+                    match most_recent_lab(observations, loinc_code) {
+                        Some(value) if *std_dev > 0.0 => (value - mean) / std_dev,
+                        Some(value) => value - mean,
+                        None => 0.0,
+                    }
+                }
+                FeatureKind::LabObserved { loinc_code } => {
+                    indicator(most_recent_lab(observations, loinc_code).is_some())
+                }
+                FeatureKind::LabInterpretation { loinc_code, flag } => {
+                    indicator(most_recent_flag(observations, loinc_code).is_some_and(|f| f.code() == flag))
+                }
+                FeatureKind::HpoTerm { hpo_id } => {
+                    indicator(codes.iter().any(|c| c == hpo_id) || hpo_terms.iter().any(|t| t == hpo_id))
+                }
+            })
+            .collect()
+    }
+
+    pub fn extract_dataset(&self, dataset: &MedicalDataset) -> FeatureMatrix {
+        self.extract_dataset_with_stats(dataset).0
+    }
+
+    pub fn extract_dataset_with_stats(&self, dataset: &MedicalDataset) -> (FeatureMatrix, OptOutStats) {
+        match &self.opt_out {
+            Some(registry) => {
+                let (filtered, stats) = registry.filter_dataset(dataset);
+                (self.extract_rows(&filtered), stats)
+            }
+            None => {
+                let stats = OptOutStats { patients_seen: dataset.patients.len() as u64, ..OptOutStats::default() };
+                (self.extract_rows(dataset), stats)
+            }
+        }
+    }
+
+    fn leakage_exclusions(&self, dataset: &MedicalDataset) -> BTreeSet<String> {
+        if self.target_codes.is_empty() {
+            return BTreeSet::new();
+        }
+        let codes: Vec<&str> = self.target_codes.iter().map(|c| c.as_str()).collect();
+        dataset.leakage_exclusions(&codes)
+    }
+
+    fn extract_rows(&self, dataset: &MedicalDataset) -> FeatureMatrix {
+        let excluded = self.leakage_exclusions(dataset);
+        let rows = dataset
+            .patients
+            .iter()
+            .map(|patient| {
+                let conditions: Vec<&Condition> = dataset
+                    .conditions
+                    .iter()
+                    .filter(|c| references_patient(&c.subject.reference, &patient.id))
+                    .collect();
+                let observations: Vec<&Observation> = dataset
+                    .observations
+                    .iter()
+                    .filter(|o| references_patient(&o.subject.reference, &patient.id) && !excluded.contains(&o.id))
+                    .collect();
+                self.extract_patient(patient, &conditions, &observations, &[])
+            })
+            .collect();
+
+        FeatureMatrix {
+            schema_fingerprint: self.schema.fingerprint(),
+            feature_names: self.schema.feature_names(),
+            patient_ids: dataset.patients.iter().map(|p| p.id.clone()).collect(),
+            rows,
+        }
+    }
+
+    // Rare disease cases carry their phenotype as HPO-coded presenting symptoms
+    pub fn extract_case(&self, case: &RareDiseaseCase, dataset: &MedicalDataset) -> Vec<f64> {
+        let conditions: Vec<&Condition> = dataset
+            .conditions
+            .iter()
+            .filter(|c| references_patient(&c.subject.reference, &case.patient.id))
+            .collect();
+        let excluded = self.leakage_exclusions(dataset);
+        let observations: Vec<&Observation> = dataset
+            .observations
+            .iter()
+            .filter(|o| references_patient(&o.subject.reference, &case.patient.id) && !excluded.contains(&o.id))
+            .collect();
+        let hpo_terms: Vec<String> = case.presenting_symptoms.iter().map(|s| s.hpo_id.clone()).collect();
+
+        self.extract_patient(&case.patient, &conditions, &observations, &hpo_terms)
+    }
+}
+
+fn indicator(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+fn concept_codes(concept: &CodeableConcept) -> impl Iterator<Item = String> + '_ {
+    concept.coding.iter().filter_map(|coding| coding.code.clone())
+}
+
+pub(crate) fn condition_codes(condition: &Condition) -> Vec<String> {
+    condition.code.iter().flat_map(concept_codes).collect()
+}
+
+// Accepts both "Patient/<id>" and bare ids
+pub(crate) fn references_patient(reference: &Option<String>, patient_id: &str) -> bool {
+    match reference {
+        Some(reference) => reference == patient_id || reference.strip_prefix("Patient/") == Some(patient_id),
+        None => false,
+    }
+}
+
+fn gender_key(patient: &Patient) -> &'static str {
+    match patient.gender {
+        Some(Gender::Male) => "male",
+        Some(Gender::Female) => "female",
+        Some(Gender::Other) => "other",
+        Some(Gender::Unknown) | None => "unknown",
+    }
+}
+
+pub(crate) fn patient_age(patient: &Patient, reference_year: i32) -> Option<f64> {
+    let birth_date = patient.birth_date.as_ref()?;
+    let birth_year: i32 = birth_date.get(..4)?.parse().ok()?;
+    Some((reference_year - birth_year).max(0) as f64)
+}
+
+// Labs may be reported standalone or as a component of a panel
+fn lab_value(observation: &Observation, loinc_code: &str) -> Option<f64> {
+    if observation.code.has_loinc_code(loinc_code) {
+        if let Some(value) = observation.value.as_ref().and_then(|v| v.as_f64()) {
+            return Some(value);
+        }
+    }
+    observation.component_value(loinc_code)
+}
+
+// ISO 8601 timestamps order lexicographically, so string comparison finds the latest
+fn most_recent_lab(observations: &[&Observation], loinc_code: &str) -> Option<f64> {
+    observations
+        .iter()
+        .filter_map(|o| lab_value(o, loinc_code).map(|value| (o.effective_datetime.as_deref().unwrap_or(""), value)))
+        .max_by(|a, b| a.0.cmp(b.0))
+        .map(|(_, value)| value)
+}
+
+fn lab_flag(observation: &Observation, loinc_code: &str) -> Option<InterpretationFlag> {
+    if observation.code.has_loinc_code(loinc_code) && observation.value.is_some() {
+        return observation.interpretation_flag();
+    }
+    observation.component_by_loinc(loinc_code).and_then(|c| c.interpretation_flag())
+}
+
+// Flag of the most recent result for the lab, so it agrees with LabValue
+fn most_recent_flag(observations: &[&Observation], loinc_code: &str) -> Option<InterpretationFlag> {
+    observations
+        .iter()
+        .filter(|o| lab_value(o, loinc_code).is_some())
+        .max_by(|a, b| a.effective_datetime.as_deref().unwrap_or("").cmp(b.effective_datetime.as_deref().unwrap_or("")))
+        .and_then(|o| lab_flag(o, loinc_code))
+}
+
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 1.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::*;
+
+    #[test]
+    fn test_extraction_follows_schema() {
+        let mut dataset = MedicalDataset::new("ds".to_string(), "test".to_string(), String::new());
+
+        let mut patient = Patient::new("p1".to_string());
+        patient.set_birth_date("1980-05-01".to_string());
+        patient.set_gender(Gender::Female);
+        dataset.patients.push(patient);
+
+        let mut condition = Condition::new("c1".to_string(), create_reference("Patient/p1", None));
+        condition.set_code(CodeableConcept::icd10("E11").unwrap());
+        dataset.conditions.push(condition);
+
+        for (id, date, value) in [("o1", "2024-01-01", 6.0), ("o2", "2024-06-01", 8.0)] {
+            let code = CodeableConcept::loinc("4548-4").unwrap();
+            let mut observation = Observation::new(id.to_string(), code, create_reference("Patient/p1", None));
+            observation.effective_datetime = Some(date.to_string());
+            observation.set_value(ObservationValue::Quantity(create_quantity(value, "%", None, None)));
+            dataset.observations.push(observation);
+        }
+
+        let schema = FeatureSchema::new(2024)
+            .with_demographics()
+            .with_condition("E11")
+            .with_condition("I10")
+            .with_lab("4548-4", 6.0, 1.0)
+            .with_hpo_term("HP:0001250");
+        let restored = FeatureSchema::from_json(&schema.to_json().unwrap()).unwrap();
+        assert_eq!(restored.fingerprint(), schema.fingerprint());
+
+        let matrix = FeatureExtractor::new(restored).extract_dataset(&dataset);
+        assert_eq!(matrix.rows.len(), 1);
+        assert_eq!(matrix.rows[0].len(), schema.len());
+        // age, male, female, other, unknown, E11, I10, HbA1c (latest, standardised), observed, HPO
+        assert_eq!(matrix.rows[0], vec![0.44, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 2.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,107 @@
+// The unit central differential privacy protects. With `Record` (the default) the
+// coordinator's Gaussian noise goes on every clipped update, and the guarantee is
+// stated per patient record: it holds for a single record as long as each site's
+// contribution of that record is bounded, e.g. by training with DP-SGD (dp_sgd.rs).
+//
+// With `Client` the whole contribution of a hospital is the unit, as in DP-FedAvg
+// (McMahan et al., 2018): every update is clipped to the DP bound C, all hospitals
+// count equally in the average (a weight that grew with a site's data would grow its
+// sensitivity), and one Gaussian draw calibrated to C / n is added to the average of
+// the n updates. The guarantee then covers everything a hospital sends, whatever its
+// number of records, at the noise cost of a single aggregate. Only plain averaging
+// has that sensitivity, so client-level DP needs the FedAvg or WeightedAverage
+// aggregation method. `PrivacyMetrics::granularity` records which guarantee the
+// latest DP round gave.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum DpGranularity {
+    #[default]
+    Record,
+    Client,
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_dp_granularity(&mut self, granularity: DpGranularity) -> Result<(), String> {
+        if granularity == DpGranularity::Client
+            && !matches!(self.config.aggregation_method, AggregationMethod::FedAvg | AggregationMethod::WeightedAverage)
+        {
+            return Err(format!(
+                "Client-level DP needs FedAvg or WeightedAverage aggregation, not {:?}",
+                self.config.aggregation_method
+            ));
+        }
+        self.dp_granularity = granularity;
+        Ok(())
+    }
+
+    pub fn get_dp_granularity(&self) -> &DpGranularity {
+        &self.dp_granularity
+    }
+
+    // Gives every clipped update the round's mean data size, so each hospital carries
+    // the same weight and the total sample count is kept
+    pub(crate) fn equalize_client_weights(&self, updates: &mut [ModelUpdate]) {
+        if updates.is_empty() {
+            return;
+        }
+        let total: usize = updates.iter().map(|u| u.data_size).sum();
+        let mean = (total / updates.len()).max(1);
+        updates.iter_mut().for_each(|u| u.data_size = mean);
+    }
+
+    // One Gaussian draw on the averaged update of a client-level DP round; any other
+    // round's aggregate is returned unchanged
+    pub(crate) fn add_client_level_noise(&mut self, mut aggregated: Vec<f64>, updates: &[ModelUpdate]) -> Result<Vec<f64>, String> {
+        let (epsilon, delta) = match (&self.config.privacy_method, &self.dp_granularity) {
+            (PrivacyMethod::DifferentialPrivacy { epsilon, delta }, DpGranularity::Client) => (*epsilon, *delta),
+            _ => return Ok(aggregated),
+        };
+        if updates.is_empty() {
+            return Err("Client-level DP needs at least one update".to_string());
+        }
+        let clip_norm = self.dp_clip_norm()?;
+        let sensitivities = match &self.layer_privacy {
+            Some(policy) => policy.coordinate_sensitivities(updates),
+            None => vec![clip_norm; aggregated.len()],
+        };
+        let clients = updates.len() as f64;
+        for (value, sensitivity) in aggregated.iter_mut().zip(&sensitivities) {
+            let std_dev = sensitivity / clients * gaussian_noise_multiplier(epsilon, delta);
+            *value += self.sample_gaussian_noise(0.0, std_dev);
+        }
+        Ok(aggregated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, data_size: usize) -> ModelUpdate {
+        ModelUpdate { loss: 1.0, accuracy: 0.5, ..ModelUpdate::for_test(client_id, vec![3.0, 4.0], data_size) }
+    }
+
+    #[test]
+    fn test_client_level_dp_weighs_hospitals_equally() {
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(2)
+            .min_clients(2)
+            .aggregation_method(AggregationMethod::Median)
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        assert!(coordinator.set_dp_granularity(DpGranularity::Client).is_err());
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(2).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_clipping_policy(ClippingPolicy { max_norm: Some(1.0) }).unwrap();
+        coordinator.set_dp_granularity(DpGranularity::Client).unwrap();
+        let model = coordinator.execute_round(vec![update("small_clinic", 10), update("teaching_hospital", 990)]).unwrap();
+
+        assert_eq!(model.privacy_metrics.granularity, DpGranularity::Client);
+        assert_eq!(model.effective_sample_count, 1000);
+        assert!(model.weights.iter().all(|w| w.is_finite()));
+    }
+}
@@ -0,0 +1,210 @@
+// Two-tier aggregation: hospitals -> regional coordinator -> global coordinator. A
+// regional coordinator is an ordinary FederatedLearningCoordinator that starts each
+// global round from the broadcast global model, runs its rounds with its hospitals
+// and submits the result with the effective sample count behind it. The global tier
+// averages regional models weighted by those counts, so the result matches a flat
+// FedAvg over every hospital while each region only sends one model upstream.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RegionalModel {
+    pub region_id: String,
+    // Global round this model was trained for
+    pub round: u64,
+    pub weights: Vec<f64>,
+    // Sum of the (effective) data sizes of the hospital updates behind the model
+    pub effective_sample_count: u64,
+    pub participating_clients: Vec<String>,
+    pub loss: f64,
+    pub accuracy: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct HierarchicalRound {
+    pub round: u64,
+    pub weights: Vec<f64>,
+    pub participating_regions: Vec<String>,
+    pub total_samples: u64,
+    pub global_loss: f64,
+    pub global_accuracy: f64,
+}
+
+pub struct HierarchicalCoordinator {
+    round: u64,
+    global_weights: Vec<f64>,
+    min_regions: u32,
+    regions: Vec<String>,
+    // Latest submission per region for the open round
+    pending: HashMap<String, RegionalModel>,
+    history: Vec<HierarchicalRound>,
+}
+
+impl HierarchicalCoordinator {
+    pub fn new(initial_weights: Vec<f64>, min_regions: u32) -> Result<Self, String> {
+        if initial_weights.is_empty() {
+            return Err("Initial weights cannot be empty".to_string());
+        }
+        if min_regions == 0 {
+            return Err("min_regions must be at least 1".to_string());
+        }
+        Ok(HierarchicalCoordinator {
+            round: 0,
+            global_weights: initial_weights,
+            min_regions,
+            regions: Vec::new(),
+            pending: HashMap::new(),
+            history: Vec::new(),
+        })
+    }
+
+    pub fn register_region(&mut self, region_id: &str) {
+        if !self.regions.iter().any(|r| r == region_id) {
+            self.regions.push(region_id.to_string());
+        }
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    // What regional coordinators load before the round
+    pub fn get_global_weights(&self) -> &[f64] {
+        &self.global_weights
+    }
+
+    pub fn get_history(&self) -> &[HierarchicalRound] {
+        &self.history
+    }
+
+    // A region resubmitting within the round replaces its earlier model
+    pub fn submit_regional_model(&mut self, model: RegionalModel) -> Result<(), String> {
+        if !self.regions.contains(&model.region_id) {
+            return Err(format!("Region {} is not registered", model.region_id));
+        }
+        if model.round != self.round {
+            return Err(format!("Regional model is for round {}, current round is {}", model.round, self.round));
+        }
+        if model.weights.len() != self.global_weights.len() {
+            return Err(format!(
+                "Regional model has {} weights, expected {}",
+                model.weights.len(),
+                self.global_weights.len()
+            ));
+        }
+        if model.effective_sample_count == 0 {
+            return Err(format!("Region {} reported no samples", model.region_id));
+        }
+        if model.weights.iter().any(|w| !w.is_finite()) {
+            return Err(format!("Region {} submitted non-finite weights", model.region_id));
+        }
+        self.pending.insert(model.region_id.clone(), model);
+        Ok(())
+    }
+
+    // Sample-weighted average of the submitted regional models
+    pub fn aggregate_round(&mut self) -> Result<HierarchicalRound, String> {
+        if self.pending.len() < self.min_regions as usize {
+            return Err(format!(
+                "Only {} of the required {} regions submitted for round {}",
+                self.pending.len(),
+                self.min_regions,
+                self.round
+            ));
+        }
+
+        let mut models: Vec<RegionalModel> = self.pending.drain().map(|(_, m)| m).collect();
+        models.sort_by(|a, b| a.region_id.cmp(&b.region_id));
+        let total_samples: u64 = models.iter().map(|m| m.effective_sample_count).sum();
+
+        let mut weights = vec![0.0; self.global_weights.len()];
+        let mut loss = 0.0;
+        let mut accuracy = 0.0;
+        for model in &models {
+            let share = model.effective_sample_count as f64 / total_samples as f64;
+            for (w, value) in weights.iter_mut().zip(model.weights.iter()) {
+                *w += share * value;
+            }
+            loss += share * model.loss;
+            accuracy += share * model.accuracy;
+        }
+
+        self.round += 1;
+        self.global_weights = weights.clone();
+        let summary = HierarchicalRound {
+            round: self.round,
+            weights,
+            participating_regions: models.iter().map(|m| m.region_id.clone()).collect(),
+            total_samples,
+            global_loss: loss,
+            global_accuracy: accuracy,
+        };
+        self.history.push(summary.clone());
+        Ok(summary)
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Regional tier: start the next global round from the model the parent broadcast
+    pub fn adopt_parent_weights(&mut self, weights: Vec<f64>) -> Result<(), String> {
+        if weights.is_empty() {
+            return Err("Parent weights cannot be empty".to_string());
+        }
+        self.global_model.weights = weights;
+        Ok(())
+    }
+
+    // Regional tier: the result of the latest round, packaged for the global coordinator
+    pub fn regional_model(&self, region_id: &str, global_round: u64) -> RegionalModel {
+        RegionalModel {
+            region_id: region_id.to_string(),
+            round: global_round,
+            weights: self.global_model.weights.clone(),
+            effective_sample_count: self.global_model.effective_sample_count,
+            participating_clients: self.global_model.participating_clients.clone(),
+            loss: self.global_model.global_loss,
+            accuracy: self.global_model.global_accuracy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regional(region_id: &str, value: f64, samples: u64) -> RegionalModel {
+        RegionalModel {
+            region_id: region_id.to_string(),
+            round: 0,
+            weights: vec![value; 2],
+            effective_sample_count: samples,
+            participating_clients: Vec::new(),
+            loss: value,
+            accuracy: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_regions_weighted_by_effective_samples() {
+        let mut coordinator = HierarchicalCoordinator::new(vec![0.0; 2], 2).unwrap();
+        coordinator.register_region("north");
+        coordinator.register_region("south");
+        assert!(coordinator.submit_regional_model(regional("east", 1.0, 10)).is_err());
+
+        coordinator.submit_regional_model(regional("north", 1.0, 100)).unwrap();
+        assert!(coordinator.aggregate_round().is_err());
+        coordinator.submit_regional_model(regional("south", 4.0, 10)).unwrap();
+        // Resubmission replaces the earlier model
+        coordinator.submit_regional_model(regional("south", 4.0, 300)).unwrap();
+
+        let round = coordinator.aggregate_round().unwrap();
+        assert_eq!(round.round, 1);
+        assert_eq!(round.total_samples, 400);
+        assert!((round.weights[0] - 3.25).abs() < 1e-12);
+        assert_eq!(coordinator.get_global_weights(), round.weights.as_slice());
+
+        let mut stale = regional("north", 1.0, 100);
+        stale.round = 0;
+        assert!(coordinator.submit_regional_model(stale).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// Retention of the per-round history. Every round leaves a GlobalModel snapshot
+// (without weights, which the checkpoint store keeps) that carries the round's
+// participants, per-client privacy loss and accuracy, and backdoor flags, all of
+// which grow with the federation. The newest `full_snapshots` rounds keep their
+// snapshot; older rounds are compacted to a RoundRecord of the round's scalar
+// metrics, which is all the convergence check and long-run reports need.
+// `max_records` optionally bounds the compacted records as well, oldest first.
+//
+// Compaction runs after every round and whenever the policy changes.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryRetentionPolicy {
+    // Rounds kept as full snapshots; 0 keeps every snapshot
+    pub full_snapshots: usize,
+    // Metrics-only records kept beyond those; 0 keeps every record
+    pub max_records: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RoundRecord {
+    pub round: u64,
+    pub global_loss: f64,
+    pub global_accuracy: f64,
+    pub participants: usize,
+    pub effective_sample_count: u64,
+    pub gradient_norm: f64,
+    pub weight_change_norm: f64,
+    pub total_epsilon_used: f64,
+    pub effective_round_epsilon: f64,
+    pub total_bytes_received: u64,
+    pub round_energy_kwh: f64,
+    pub updates_clipped: u32,
+    pub backdoor_flags: usize,
+    pub candidate_decision: Option<CandidateDecision>,
+}
+
+impl Default for HistoryRetentionPolicy {
+    fn default() -> Self {
+        HistoryRetentionPolicy { full_snapshots: 100, max_records: 0 }
+    }
+}
+
+impl From<&GlobalModel> for RoundRecord {
+    fn from(model: &GlobalModel) -> Self {
+        RoundRecord {
+            round: model.round,
+            global_loss: model.global_loss,
+            global_accuracy: model.global_accuracy,
+            participants: model.participating_clients.len(),
+            effective_sample_count: model.effective_sample_count,
+            gradient_norm: model.convergence_metrics.gradient_norm,
+            weight_change_norm: model.convergence_metrics.weight_change_norm,
+            total_epsilon_used: model.privacy_metrics.total_epsilon_used,
+            effective_round_epsilon: model.privacy_metrics.effective_round_epsilon,
+            total_bytes_received: model.communication_metrics.total_bytes_received,
+            round_energy_kwh: model.communication_metrics.round_energy_kwh,
+            updates_clipped: model.clipping_stats.updates_clipped,
+            backdoor_flags: model.backdoor_flags.len(),
+            candidate_decision: model.candidate_evaluation.as_ref().map(|e| e.decision),
+        }
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Returns how many snapshots the new policy compacted right away
+    pub fn set_history_retention(&mut self, policy: HistoryRetentionPolicy) -> usize {
+        self.history_retention = policy;
+        self.compact_history()
+    }
+
+    // Applies the retention policy; returns how many snapshots were compacted
+    pub fn compact_history(&mut self) -> usize {
+        let policy = &self.history_retention;
+        let excess = match policy.full_snapshots {
+            0 => 0,
+            keep => self.round_history.len().saturating_sub(keep),
+        };
+        self.compacted_history.extend(self.round_history.drain(..excess).map(|model| RoundRecord::from(&model)));
+        if policy.max_records > 0 {
+            let dropped = self.compacted_history.len().saturating_sub(policy.max_records);
+            self.compacted_history.drain(..dropped);
+        }
+        excess
+    }
+
+    // Every retained round, oldest first, whether compacted or not
+    pub fn get_round_records(&self) -> Vec<RoundRecord> {
+        self.compacted_history.iter().cloned().chain(self.round_history.iter().map(RoundRecord::from)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_rounds_are_compacted_to_metrics() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).privacy_method(PrivacyMethod::None).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_history_retention(HistoryRetentionPolicy { full_snapshots: 2, max_records: 3 });
+        for round in 0..7 {
+            let update = ModelUpdate {
+                round,
+                loss: 1.0 / (round + 1) as f64,
+                accuracy: 0.5,
+                ..ModelUpdate::for_test("hospital_a", vec![0.1 * round as f64, 0.0], 10)
+            };
+            coordinator.execute_round(vec![update]).unwrap();
+        }
+
+        // Rounds 6 and 7 in full, 3 to 5 as records, 1 and 2 dropped
+        assert_eq!(coordinator.get_round_history().iter().map(|m| m.round).collect::<Vec<_>>(), vec![6, 7]);
+        let records = coordinator.get_round_records();
+        assert_eq!(records.iter().map(|r| r.round).collect::<Vec<_>>(), vec![3, 4, 5, 6, 7]);
+        assert_eq!(records[0].participants, 1);
+        assert!(coordinator.diff_history_rounds(4, 7, None).is_err());
+
+        // Narrowing the policy compacts at once
+        assert_eq!(coordinator.set_history_retention(HistoryRetentionPolicy { full_snapshots: 1, max_records: 0 }), 1);
+        assert_eq!(coordinator.get_round_records().len(), 5);
+    }
+}
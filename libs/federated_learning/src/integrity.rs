@@ -0,0 +1,234 @@
+// Integrity of chunked gradient uploads. Before uploading, a client commits to its
+// gradient with an additive lattice hash (LtHash-style): every chunk hashes to a
+// vector of 32-bit lanes and the hash of the whole update is the lane-wise sum mod
+// 2^32. The client signs the commitment; the aggregator folds chunks into a running
+// hash as they arrive, in any order, and only accepts the reassembled gradient when
+// that sum equals the signed one, so corrupted, missing or truncated chunks are caught
+// without ever hashing the full update in one piece.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+// 256 lanes x 32 bits; far fewer than LtHash's 1024 x 16, which is enough to detect
+// transport faults and tampering against a signed commitment
+pub const LATTICE_LANES: usize = 256;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LatticeHash {
+    pub lanes: Vec<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GradientChunk {
+    pub client_id: String,
+    pub round: u64,
+    pub index: u32,
+    pub values: Vec<f64>,
+}
+
+// What the client signs: the lattice hash plus the shape of the upload
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UpdateCommitment {
+    pub client_id: String,
+    pub round: u64,
+    pub total_len: u64,
+    pub chunk_size: u32,
+    pub hash: LatticeHash,
+}
+
+impl Default for LatticeHash {
+    fn default() -> Self {
+        LatticeHash { lanes: vec![0; LATTICE_LANES] }
+    }
+}
+
+impl LatticeHash {
+    pub fn new() -> Self {
+        LatticeHash::default()
+    }
+
+    // Expands SHA-256 in counter mode over the chunk's position and values. The index
+    // is hashed in so that swapping two chunks changes the sum.
+    pub fn of_chunk(index: u32, values: &[f64]) -> Self {
+        let mut seed = Sha256::new();
+        seed.update(b"fl-gradient-chunk");
+        seed.update(index.to_be_bytes());
+        seed.update((values.len() as u64).to_be_bytes());
+        for value in values {
+            seed.update(value.to_be_bytes());
+        }
+        let seed = seed.finalize();
+
+        let mut lanes = Vec::with_capacity(LATTICE_LANES);
+        let mut counter = 0u32;
+        while lanes.len() < LATTICE_LANES {
+            let block = Sha256::new().chain_update(seed).chain_update(counter.to_be_bytes()).finalize();
+            lanes.extend(block.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+            counter += 1;
+        }
+        LatticeHash { lanes }
+    }
+
+    pub fn add(&mut self, other: &LatticeHash) {
+        for (lane, value) in self.lanes.iter_mut().zip(other.lanes.iter()) {
+            *lane = lane.wrapping_add(*value);
+        }
+    }
+
+    pub fn remove(&mut self, other: &LatticeHash) {
+        for (lane, value) in self.lanes.iter_mut().zip(other.lanes.iter()) {
+            *lane = lane.wrapping_sub(*value);
+        }
+    }
+}
+
+impl UpdateCommitment {
+    pub fn for_gradients(client_id: &str, round: u64, gradients: &[f64], chunk_size: u32) -> Result<Self, String> {
+        if chunk_size == 0 {
+            return Err("chunk_size must be at least 1".to_string());
+        }
+        let mut hash = LatticeHash::new();
+        for (index, values) in gradients.chunks(chunk_size as usize).enumerate() {
+            hash.add(&LatticeHash::of_chunk(index as u32, values));
+        }
+        Ok(UpdateCommitment {
+            client_id: client_id.to_string(),
+            round,
+            total_len: gradients.len() as u64,
+            chunk_size,
+            hash,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.total_len.div_ceil(self.chunk_size as u64) as usize
+    }
+
+    // Hex SHA-256 of the commitment; this is the message the client's signature covers
+    pub fn signing_digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.client_id.as_bytes());
+        hasher.update(self.round.to_be_bytes());
+        hasher.update(self.total_len.to_be_bytes());
+        hasher.update(self.chunk_size.to_be_bytes());
+        for lane in &self.hash.lanes {
+            hasher.update(lane.to_be_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+pub fn split_into_chunks(client_id: &str, round: u64, gradients: &[f64], chunk_size: u32) -> Vec<GradientChunk> {
+    gradients
+        .chunks(chunk_size.max(1) as usize)
+        .enumerate()
+        .map(|(index, values)| GradientChunk {
+            client_id: client_id.to_string(),
+            round,
+            index: index as u32,
+            values: values.to_vec(),
+        })
+        .collect()
+}
+
+// Aggregator-side reassembly of one client's upload against its signed commitment
+#[derive(Clone, Debug)]
+pub struct ChunkedUpload {
+    commitment: UpdateCommitment,
+    chunks: Vec<Option<Vec<f64>>>,
+    hash: LatticeHash,
+}
+
+impl ChunkedUpload {
+    // The caller verifies the client's signature over `signing_digest` first
+    pub fn new(commitment: UpdateCommitment) -> Result<Self, String> {
+        if commitment.chunk_size == 0 {
+            return Err("chunk_size must be at least 1".to_string());
+        }
+        if commitment.hash.lanes.len() != LATTICE_LANES {
+            return Err(format!("Commitment hash must have {} lanes", LATTICE_LANES));
+        }
+        let chunks = vec![None; commitment.chunk_count()];
+        Ok(ChunkedUpload { commitment, chunks, hash: LatticeHash::new() })
+    }
+
+    // A retransmitted chunk replaces the earlier copy; its hash is subtracted back out
+    pub fn add_chunk(&mut self, chunk: GradientChunk) -> Result<(), String> {
+        if chunk.client_id != self.commitment.client_id || chunk.round != self.commitment.round {
+            return Err("Chunk belongs to a different upload".to_string());
+        }
+        let index = chunk.index as usize;
+        if index >= self.chunks.len() {
+            return Err(format!("Chunk {} is beyond the committed {} chunks", index, self.chunks.len()));
+        }
+        let chunk_size = self.commitment.chunk_size as usize;
+        let expected = chunk_size.min(self.commitment.total_len as usize - index * chunk_size);
+        if chunk.values.len() != expected {
+            return Err(format!("Chunk {} has {} values, expected {}", index, chunk.values.len(), expected));
+        }
+
+        if let Some(previous) = &self.chunks[index] {
+            self.hash.remove(&LatticeHash::of_chunk(chunk.index, previous));
+        }
+        self.hash.add(&LatticeHash::of_chunk(chunk.index, &chunk.values));
+        self.chunks[index] = Some(chunk.values);
+        Ok(())
+    }
+
+    pub fn missing_chunks(&self) -> Vec<u32> {
+        self.chunks.iter().enumerate().filter(|(_, c)| c.is_none()).map(|(i, _)| i as u32).collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.chunks.iter().all(|c| c.is_some())
+    }
+
+    // Reassembles the gradient once every chunk is in and the running hash matches
+    pub fn finish(self) -> Result<Vec<f64>, String> {
+        let missing = self.missing_chunks();
+        if !missing.is_empty() {
+            return Err(format!("Upload is truncated; missing chunks {:?}", missing));
+        }
+        if self.hash != self.commitment.hash {
+            return Err("Reassembled chunks do not match the signed commitment".to_string());
+        }
+        let mut gradients = Vec::with_capacity(self.commitment.total_len as usize);
+        for values in self.chunks.into_iter().flatten() {
+            gradients.extend(values);
+        }
+        Ok(gradients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_upload_detects_corruption_and_truncation() {
+        let gradients: Vec<f64> = (0..10).map(|i| i as f64 * 0.1).collect();
+        let commitment = UpdateCommitment::for_gradients("hospital_a", 4, &gradients, 4).unwrap();
+        assert_eq!(commitment.chunk_count(), 3);
+
+        // Out-of-order arrival, with one chunk corrupted and then retransmitted
+        let chunks = split_into_chunks("hospital_a", 4, &gradients, 4);
+        let mut upload = ChunkedUpload::new(commitment.clone()).unwrap();
+        let mut corrupted = chunks[1].clone();
+        corrupted.values[0] += 1e-9;
+        upload.add_chunk(chunks[2].clone()).unwrap();
+        upload.add_chunk(corrupted).unwrap();
+        upload.add_chunk(chunks[0].clone()).unwrap();
+        assert!(upload.clone().finish().is_err());
+        upload.add_chunk(chunks[1].clone()).unwrap();
+        assert_eq!(upload.finish().unwrap(), gradients);
+
+        // Truncated final chunk and a missing chunk
+        let mut upload = ChunkedUpload::new(commitment.clone()).unwrap();
+        let mut short = chunks[2].clone();
+        short.values.pop();
+        assert!(upload.add_chunk(short).is_err());
+        upload.add_chunk(chunks[0].clone()).unwrap();
+        upload.add_chunk(chunks[1].clone()).unwrap();
+        assert!(upload.finish().is_err());
+    }
+}
@@ -0,0 +1,337 @@
+// Vectorised inner loops for quantization, noise addition and weighted averaging.
+// The scalar backend is always available; the `wasm-simd` feature enables simd128
+// kernels when building for wasm32 with `-C target-feature=+simd128`, and the `avx`
+// feature enables AVX kernels on x86_64 selected by runtime CPU detection.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KernelBackend {
+    Scalar,
+    WasmSimd128,
+    Avx,
+}
+
+pub fn active_backend() -> KernelBackend {
+    if cfg!(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd")) {
+        return KernelBackend::WasmSimd128;
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+    {
+        if std::is_x86_feature_detected!("avx") {
+            return KernelBackend::Avx;
+        }
+    }
+
+    KernelBackend::Scalar
+}
+
+// y[i] += alpha * x[i]
+pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    let n = x.len().min(y.len());
+    match active_backend() {
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd"))]
+        KernelBackend::WasmSimd128 => wasm_simd::axpy(alpha, &x[..n], &mut y[..n]),
+        #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+        KernelBackend::Avx => unsafe { avx::axpy(alpha, &x[..n], &mut y[..n]) },
+        _ => scalar::axpy(alpha, &x[..n], &mut y[..n]),
+    }
+}
+
+// y[i] += x[i], used to apply a pre-sampled noise vector
+pub fn add_assign(y: &mut [f64], x: &[f64]) {
+    axpy(1.0, x, y);
+}
+
+// out[i] = a * x[i] + b, used for dequantization
+pub fn affine(x: &[f64], a: f64, b: f64, out: &mut [f64]) {
+    let n = x.len().min(out.len());
+    match active_backend() {
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd"))]
+        KernelBackend::WasmSimd128 => wasm_simd::affine(&x[..n], a, b, &mut out[..n]),
+        #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+        KernelBackend::Avx => unsafe { avx::affine(&x[..n], a, b, &mut out[..n]) },
+        _ => scalar::affine(&x[..n], a, b, &mut out[..n]),
+    }
+}
+
+// out[i] = (|x[i] / divisor| * scale).round(), with f64::round's ties away from zero.
+// The vector paths round as floor(v) + (v - floor(v) >= 0.5), which is exact for
+// v >= 0; floor(v + 0.5) is not, since the addition itself can round up
+pub fn quantize_magnitudes(x: &[f64], divisor: f64, scale: f64, out: &mut [f64]) {
+    let n = x.len().min(out.len());
+    match active_backend() {
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd"))]
+        KernelBackend::WasmSimd128 => wasm_simd::quantize_magnitudes(&x[..n], divisor, scale, &mut out[..n]),
+        #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+        KernelBackend::Avx => unsafe { avx::quantize_magnitudes(&x[..n], divisor, scale, &mut out[..n]) },
+        _ => scalar::quantize_magnitudes(&x[..n], divisor, scale, &mut out[..n]),
+    }
+}
+
+pub fn sum_squares(x: &[f64]) -> f64 {
+    match active_backend() {
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd"))]
+        KernelBackend::WasmSimd128 => wasm_simd::sum_squares(x),
+        #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+        KernelBackend::Avx => unsafe { avx::sum_squares(x) },
+        _ => scalar::sum_squares(x),
+    }
+}
+
+pub fn l2_norm(x: &[f64]) -> f64 {
+    sum_squares(x).sqrt()
+}
+
+// Reference implementations, also used for the remainder of vectorised loops
+pub mod scalar {
+    pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+        for (yi, &xi) in y.iter_mut().zip(x.iter()) {
+            *yi += alpha * xi;
+        }
+    }
+
+    pub fn affine(x: &[f64], a: f64, b: f64, out: &mut [f64]) {
+        for (oi, &xi) in out.iter_mut().zip(x.iter()) {
+            *oi = a * xi + b;
+        }
+    }
+
+    pub fn quantize_magnitudes(x: &[f64], divisor: f64, scale: f64, out: &mut [f64]) {
+        for (oi, &xi) in out.iter_mut().zip(x.iter()) {
+            *oi = ((xi / divisor).abs() * scale).round();
+        }
+    }
+
+    pub fn sum_squares(x: &[f64]) -> f64 {
+        x.iter().map(|&v| v * v).sum()
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "wasm-simd"))]
+mod wasm_simd {
+    use core::arch::wasm32::*;
+
+    const LANES: usize = 2;
+
+    pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let a = f64x2_splat(alpha);
+        for c in 0..chunks {
+            let i = c * LANES;
+            unsafe {
+                let xv = v128_load(x.as_ptr().add(i) as *const v128);
+                let yv = v128_load(y.as_ptr().add(i) as *const v128);
+                let r = f64x2_add(yv, f64x2_mul(a, xv));
+                v128_store(y.as_mut_ptr().add(i) as *mut v128, r);
+            }
+        }
+        let tail = chunks * LANES;
+        super::scalar::axpy(alpha, &x[tail..], &mut y[tail..]);
+    }
+
+    pub fn affine(x: &[f64], a: f64, b: f64, out: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let av = f64x2_splat(a);
+        let bv = f64x2_splat(b);
+        for c in 0..chunks {
+            let i = c * LANES;
+            unsafe {
+                let xv = v128_load(x.as_ptr().add(i) as *const v128);
+                let r = f64x2_add(f64x2_mul(av, xv), bv);
+                v128_store(out.as_mut_ptr().add(i) as *mut v128, r);
+            }
+        }
+        let tail = chunks * LANES;
+        super::scalar::affine(&x[tail..], a, b, &mut out[tail..]);
+    }
+
+    pub fn quantize_magnitudes(x: &[f64], divisor: f64, scale: f64, out: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let d = f64x2_splat(divisor);
+        let s = f64x2_splat(scale);
+        let half = f64x2_splat(0.5);
+        let one = f64x2_splat(1.0);
+        for c in 0..chunks {
+            let i = c * LANES;
+            unsafe {
+                let xv = v128_load(x.as_ptr().add(i) as *const v128);
+                let v = f64x2_mul(f64x2_abs(f64x2_div(xv, d)), s);
+                let t = f64x2_floor(v);
+                let up = v128_and(f64x2_ge(f64x2_sub(v, t), half), one);
+                v128_store(out.as_mut_ptr().add(i) as *mut v128, f64x2_add(t, up));
+            }
+        }
+        let tail = chunks * LANES;
+        super::scalar::quantize_magnitudes(&x[tail..], divisor, scale, &mut out[tail..]);
+    }
+
+    pub fn sum_squares(x: &[f64]) -> f64 {
+        let chunks = x.len() / LANES;
+        let mut acc = f64x2_splat(0.0);
+        for c in 0..chunks {
+            let i = c * LANES;
+            unsafe {
+                let xv = v128_load(x.as_ptr().add(i) as *const v128);
+                acc = f64x2_add(acc, f64x2_mul(xv, xv));
+            }
+        }
+        let tail = chunks * LANES;
+        f64x2_extract_lane::<0>(acc) + f64x2_extract_lane::<1>(acc) + super::scalar::sum_squares(&x[tail..])
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", feature = "avx"))]
+mod avx {
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 4;
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let a = _mm256_set1_pd(alpha);
+        for c in 0..chunks {
+            let i = c * LANES;
+            let xv = _mm256_loadu_pd(x.as_ptr().add(i));
+            let yv = _mm256_loadu_pd(y.as_ptr().add(i));
+            _mm256_storeu_pd(y.as_mut_ptr().add(i), _mm256_add_pd(yv, _mm256_mul_pd(a, xv)));
+        }
+        let tail = chunks * LANES;
+        super::scalar::axpy(alpha, &x[tail..], &mut y[tail..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn affine(x: &[f64], a: f64, b: f64, out: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let av = _mm256_set1_pd(a);
+        let bv = _mm256_set1_pd(b);
+        for c in 0..chunks {
+            let i = c * LANES;
+            let xv = _mm256_loadu_pd(x.as_ptr().add(i));
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_add_pd(_mm256_mul_pd(av, xv), bv));
+        }
+        let tail = chunks * LANES;
+        super::scalar::affine(&x[tail..], a, b, &mut out[tail..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn quantize_magnitudes(x: &[f64], divisor: f64, scale: f64, out: &mut [f64]) {
+        let chunks = x.len() / LANES;
+        let d = _mm256_set1_pd(divisor);
+        let s = _mm256_set1_pd(scale);
+        let half = _mm256_set1_pd(0.5);
+        let one = _mm256_set1_pd(1.0);
+        // Clearing the sign bit gives |x|
+        let abs_mask = _mm256_castsi256_pd(_mm256_set1_epi64x(i64::MAX));
+        for c in 0..chunks {
+            let i = c * LANES;
+            let q = _mm256_and_pd(_mm256_div_pd(_mm256_loadu_pd(x.as_ptr().add(i)), d), abs_mask);
+            let v = _mm256_mul_pd(q, s);
+            let t = _mm256_floor_pd(v);
+            let up = _mm256_and_pd(_mm256_cmp_pd::<_CMP_GE_OQ>(_mm256_sub_pd(v, t), half), one);
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_add_pd(t, up));
+        }
+        let tail = chunks * LANES;
+        super::scalar::quantize_magnitudes(&x[tail..], divisor, scale, &mut out[tail..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn sum_squares(x: &[f64]) -> f64 {
+        let chunks = x.len() / LANES;
+        let mut acc = _mm256_setzero_pd();
+        for c in 0..chunks {
+            let xv = _mm256_loadu_pd(x.as_ptr().add(c * LANES));
+            acc = _mm256_add_pd(acc, _mm256_mul_pd(xv, xv));
+        }
+        let mut lanes = [0.0f64; LANES];
+        _mm256_storeu_pd(lanes.as_mut_ptr(), acc);
+        let tail = chunks * LANES;
+        lanes.iter().sum::<f64>() + super::scalar::sum_squares(&x[tail..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Around the 2-lane (simd128) and 4-lane (AVX) widths, so both the vector body
+    // and the scalar tail run
+    const LENGTHS: [usize; 10] = [0, 1, 2, 3, 4, 5, 7, 8, 9, 13];
+
+    struct Kernels {
+        axpy: fn(f64, &[f64], &mut [f64]),
+        affine: fn(&[f64], f64, f64, &mut [f64]),
+        quantize_magnitudes: fn(&[f64], f64, f64, &mut [f64]),
+        sum_squares: fn(&[f64]) -> f64,
+    }
+
+    // Halved and then doubled by the quantization below: just under a half, and odd
+    // integers above 2^52 where adding 0.5 is inexact
+    const ROUNDING_EDGES: [f64; 3] = [-0.49999999999999994, 4503599627370497.0, 9007199254740991.0];
+
+    // Deterministic QSGD quantization as compression.rs computed it before the kernels
+    fn baseline_quantize(gradient: f64, norm: f64, levels: f64) -> f64 {
+        ((gradient / norm).abs() * levels).round()
+    }
+
+    fn input(n: usize, phase: f64) -> Vec<f64> {
+        (0..n).map(|i| (i as f64 * 0.37 + phase).sin() * 3.0).collect()
+    }
+
+    fn assert_matches_scalar(kernels: &Kernels) {
+        for n in LENGTHS {
+            let x = input(n, 0.0);
+            let y = input(n, 1.0);
+
+            let (mut expected, mut actual) = (y.clone(), y.clone());
+            scalar::axpy(-0.7, &x, &mut expected);
+            (kernels.axpy)(-0.7, &x, &mut actual);
+            assert_eq!(actual, expected, "axpy, length {}", n);
+
+            let (mut expected, mut actual) = (vec![0.0; n], vec![0.0; n]);
+            scalar::affine(&x, 0.25, -1.5, &mut expected);
+            (kernels.affine)(&x, 0.25, -1.5, &mut actual);
+            assert_eq!(actual, expected, "affine, length {}", n);
+
+            // Steps of a quarter land every fourth value on a rounding tie,
+            // and the last values sit where floor(v + 0.5) rounds the wrong way
+            let mut ramp: Vec<f64> = (0..n).map(|i| (i as f64 - 6.0) * 0.25).collect();
+            for (value, edge) in ramp.iter_mut().rev().zip(ROUNDING_EDGES) {
+                *value = edge;
+            }
+            let expected: Vec<f64> = ramp.iter().map(|&g| baseline_quantize(g, 2.0, 2.0)).collect();
+            let mut actual = vec![0.0; n];
+            (kernels.quantize_magnitudes)(&ramp, 2.0, 2.0, &mut actual);
+            assert_eq!(actual, expected, "quantize_magnitudes, length {}", n);
+
+            // Lanes are summed in a different order, so only to rounding
+            let expected = scalar::sum_squares(&x);
+            let actual = (kernels.sum_squares)(&x);
+            assert!((actual - expected).abs() <= 1e-12 * expected.max(1.0), "sum_squares, length {}", n);
+        }
+    }
+
+    #[test]
+    fn test_dispatched_kernels_match_scalar() {
+        assert_matches_scalar(&Kernels { axpy, affine, quantize_magnitudes, sum_squares });
+        // Slices of different lengths are cut to the shorter one
+        let mut y = vec![1.0; 3];
+        axpy(2.0, &[1.0; 6], &mut y);
+        assert_eq!(y, vec![3.0; 3]);
+    }
+
+    #[cfg(all(target_arch = "x86_64", feature = "avx"))]
+    #[test]
+    fn test_avx_kernels_match_scalar() {
+        if !std::is_x86_feature_detected!("avx") {
+            return;
+        }
+        assert_eq!(active_backend(), KernelBackend::Avx);
+        assert_matches_scalar(&Kernels {
+            axpy: |alpha, x, y| unsafe { avx::axpy(alpha, x, y) },
+            affine: |x, a, b, out| unsafe { avx::affine(x, a, b, out) },
+            quantize_magnitudes: |x, divisor, scale, out| unsafe { avx::quantize_magnitudes(x, divisor, scale, out) },
+            sum_squares: |x| unsafe { avx::sum_squares(x) },
+        });
+    }
+}
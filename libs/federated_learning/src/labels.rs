@@ -0,0 +1,251 @@
+// Supervised labels for rare disease models, derived from confirmed diagnoses.
+// Like the feature schema, the label schema is fixed per consortium so class i
+// is the same disease at every site, and the class balance statistics let each
+// participant apply the same loss weighting.
+
+use crate::*;
+use medical_data::condition_status::VerificationStatus;
+use medical_data::rare_diseases::{CaseStatus, RareDiseaseCase};
+use medical_data::{Condition, MedicalDataset};
+
+pub const OTHER_CLASS: &str = "other";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum LabelTask {
+    // Multi-class over ORPHA codes; confirmed codes outside the schema map to OTHER_CLASS
+    OrphaClass,
+    // Binary: 1 for a confirmed rare disease diagnosis, 0 otherwise
+    DiagnosedVsUndiagnosed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LabelSchema {
+    pub task: LabelTask,
+    pub classes: Vec<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClassBalance {
+    pub counts: Vec<u64>,
+    pub total: u64,
+    pub unlabeled: u64,
+    // Largest class count over smallest non-empty class count
+    pub imbalance_ratio: f64,
+    // Inverse-frequency weights total / (k * count), 0 for empty classes
+    pub class_weights: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LabelSet {
+    pub case_ids: Vec<String>,
+    pub labels: Vec<Option<usize>>,
+    pub balance: ClassBalance,
+}
+
+impl LabelSchema {
+    pub fn diagnosed_vs_undiagnosed() -> Self {
+        LabelSchema {
+            task: LabelTask::DiagnosedVsUndiagnosed,
+            classes: vec!["undiagnosed".to_string(), "diagnosed".to_string()],
+        }
+    }
+
+    pub fn orpha_classes(orpha_codes: &[&str]) -> Self {
+        let mut classes: Vec<String> = orpha_codes.iter().map(|c| normalize_orpha_code(c)).collect();
+        classes.sort();
+        classes.dedup();
+        classes.push(OTHER_CLASS.to_string());
+        LabelSchema { task: LabelTask::OrphaClass, classes }
+    }
+
+    // Keeps ORPHA codes confirmed in at least `min_cases` cases, so rare classes
+    // too small to learn from collapse into OTHER_CLASS
+    pub fn fit_orpha_classes(cases: &[RareDiseaseCase], dataset: &MedicalDataset, min_cases: usize) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for case in cases {
+            if let Some(code) = confirmed_orpha_code(case, &patient_conditions(dataset, &case.patient.id)) {
+                *counts.entry(code).or_insert(0) += 1;
+            }
+        }
+        let codes: Vec<&str> = counts
+            .iter()
+            .filter(|(_, &count)| count >= min_cases)
+            .map(|(code, _)| code.as_str())
+            .collect();
+        LabelSchema::orpha_classes(&codes)
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn class_index(&self, class: &str) -> Option<usize> {
+        self.classes.iter().position(|c| c == class)
+    }
+}
+
+pub struct Labeler {
+    schema: LabelSchema,
+}
+
+impl Labeler {
+    pub fn new(schema: LabelSchema) -> Self {
+        Labeler { schema }
+    }
+
+    pub fn schema(&self) -> &LabelSchema {
+        &self.schema
+    }
+
+    // Returns None when the case cannot be labeled for this task, e.g. an
+    // undiagnosed case under OrphaClass or a case still under investigation
+    pub fn label_case(&self, case: &RareDiseaseCase, conditions: &[&Condition]) -> Option<usize> {
+        let orpha_code = confirmed_orpha_code(case, conditions);
+
+        match self.schema.task {
+            LabelTask::OrphaClass => {
+                let code = orpha_code?;
+                self.schema.class_index(&code).or_else(|| self.schema.class_index(OTHER_CLASS))
+            }
+            LabelTask::DiagnosedVsUndiagnosed => {
+                if orpha_code.is_some() {
+                    return Some(1);
+                }
+                match case.outcome.as_ref().map(|o| &o.status) {
+                    Some(CaseStatus::Diagnosed) => Some(1),
+                    Some(CaseStatus::Undiagnosed) => Some(0),
+                    Some(CaseStatus::UnderInvestigation) => None,
+                    // Without an outcome, absence of a diagnosis is treated as undiagnosed
+                    _ => Some(0),
+                }
+            }
+        }
+    }
+
+    pub fn label_cases(&self, cases: &[RareDiseaseCase], dataset: &MedicalDataset) -> LabelSet {
+        let labels: Vec<Option<usize>> = cases
+            .iter()
+            .map(|case| self.label_case(case, &patient_conditions(dataset, &case.patient.id)))
+            .collect();
+
+        LabelSet {
+            case_ids: cases.iter().map(|c| c.case_id.clone()).collect(),
+            balance: self.class_balance(&labels),
+            labels,
+        }
+    }
+
+    pub fn class_balance(&self, labels: &[Option<usize>]) -> ClassBalance {
+        let num_classes = self.schema.num_classes();
+        let mut counts = vec![0u64; num_classes];
+        let mut unlabeled = 0;
+        for label in labels {
+            match label {
+                Some(class) if *class < num_classes => counts[*class] += 1,
+                _ => unlabeled += 1,
+            }
+        }
+
+        let total: u64 = counts.iter().sum();
+        let max = counts.iter().copied().max().unwrap_or(0);
+        let min_non_empty = counts.iter().copied().filter(|&c| c > 0).min().unwrap_or(0);
+        let imbalance_ratio = if min_non_empty > 0 { max as f64 / min_non_empty as f64 } else { 0.0 };
+        let class_weights = counts
+            .iter()
+            .map(|&count| {
+                if count > 0 {
+                    total as f64 / (num_classes as f64 * count as f64)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        ClassBalance { counts, total, unlabeled, imbalance_ratio, class_weights }
+    }
+}
+
+// "399", "ORPHA:399" and "Orphanet_399" all refer to the same disease
+pub fn normalize_orpha_code(code: &str) -> String {
+    let trimmed = code.trim();
+    let number = trimmed
+        .strip_prefix("ORPHA:")
+        .or_else(|| trimmed.strip_prefix("Orphanet_"))
+        .unwrap_or(trimmed);
+    format!("ORPHA:{}", number)
+}
+
+// The case's confirmed diagnosis wins; otherwise a confirmed Orphanet-coded Condition
+fn confirmed_orpha_code(case: &RareDiseaseCase, conditions: &[&Condition]) -> Option<String> {
+    if let Some(disease) = &case.confirmed_diagnosis {
+        return Some(normalize_orpha_code(&disease.orpha_code));
+    }
+
+    conditions
+        .iter()
+        .filter(|condition| is_confirmed(condition))
+        .flat_map(|condition| condition.code.iter().flat_map(|c| c.coding.iter()))
+        .find_map(|coding| {
+            let code = coding.code.as_deref()?;
+            let is_orpha = coding.system.as_deref().is_some_and(|s| s.contains("orpha"))
+                || code.starts_with("ORPHA:");
+            is_orpha.then(|| normalize_orpha_code(code))
+        })
+}
+
+// Unverified conditions count as confirmed; provisional, refuted and erroneous ones do not
+fn is_confirmed(condition: &Condition) -> bool {
+    matches!(condition.verification_status, Some(VerificationStatus::Confirmed) | None)
+}
+
+fn patient_conditions<'a>(dataset: &'a MedicalDataset, patient_id: &str) -> Vec<&'a Condition> {
+    let reference = format!("Patient/{}", patient_id);
+    dataset
+        .conditions
+        .iter()
+        .filter(|c| matches!(c.subject.reference.as_deref(), Some(r) if r == reference || r == patient_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::rare_diseases::initialize_rare_disease_database;
+    use medical_data::*;
+
+    #[test]
+    fn test_orpha_labels_and_balance() {
+        let db = initialize_rare_disease_database();
+        let mut dataset = MedicalDataset::new("ds".to_string(), "test".to_string(), String::new());
+
+        let mut cases = vec![
+            db.generate_synthetic_case("ORPHA:399").unwrap(),
+            db.generate_synthetic_case("ORPHA:399").unwrap(),
+            db.generate_synthetic_case("ORPHA:586").unwrap(),
+        ];
+
+        // Undiagnosed case with an Orphanet-coded condition recorded at the hospital
+        let mut undiagnosed = db.generate_synthetic_case("ORPHA:586").unwrap();
+        undiagnosed.confirmed_diagnosis = None;
+        let mut condition = Condition::new("c1".to_string(), create_reference(&format!("Patient/{}", undiagnosed.patient.id), None));
+        condition.set_code(CodeableConcept::orphanet("586").unwrap());
+        dataset.conditions.push(condition);
+        cases.push(undiagnosed);
+
+        let labeler = Labeler::new(LabelSchema::orpha_classes(&["ORPHA:399"]));
+        let labels = labeler.label_cases(&cases, &dataset);
+        // ORPHA:399 is class 0, anything else falls into OTHER_CLASS
+        assert_eq!(labels.labels, vec![Some(0), Some(0), Some(1), Some(1)]);
+        assert_eq!(labels.balance.counts, vec![2, 2]);
+        assert_eq!(labels.balance.class_weights, vec![1.0, 1.0]);
+
+        let fitted = LabelSchema::fit_orpha_classes(&cases, &dataset, 2);
+        assert_eq!(fitted.classes, vec!["ORPHA:399", "ORPHA:586", OTHER_CLASS]);
+
+        let binary = Labeler::new(LabelSchema::diagnosed_vs_undiagnosed());
+        cases[3].patient.id = "unrelated".to_string();
+        let balance = binary.label_cases(&cases, &dataset).balance;
+        assert_eq!(balance.counts, vec![1, 3]);
+        assert_eq!(balance.imbalance_ratio, 3.0);
+    }
+}
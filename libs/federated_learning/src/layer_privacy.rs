@@ -0,0 +1,163 @@
+// Per-layer clipping and noise calibration for differential privacy. With one global
+// sensitivity, every coordinate gets noise sized for the largest update norm, which
+// drowns small layers (biases, normalisation, output heads) whose values are orders of
+// magnitude smaller. Here the model is split into layers, or fixed-size buckets when
+// layer boundaries are unknown, each with its own L2 sensitivity: a configured bound
+// every update is clipped to, or the largest norm observed for that layer. Only the
+// first gives a valid guarantee, and central DP rounds require it.
+//
+// Layer j of L with sensitivity s_j gets Gaussian noise calibrated to s_j * sqrt(L).
+// The sum over layers of (s_j / sigma_j)^2 then equals that of a single mechanism of
+// sensitivity 1 at the same (epsilon, delta), so the guarantee is unchanged while
+// every layer sees the same signal-to-noise ratio.
+
+use crate::*;
+use std::ops::Range;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum LayerPartition {
+    // Parameter count of each layer, in model order; must sum to the model dimension
+    Layers { sizes: Vec<usize> },
+    // Consecutive buckets of `size` coordinates, the last one possibly shorter
+    Buckets { size: usize },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LayerPrivacyPolicy {
+    pub partition: LayerPartition,
+    // Per-layer L2 clipping bounds, one per layer; None calibrates each layer to the
+    // largest norm observed for it in the round, which DP rounds reject
+    pub max_norms: Option<Vec<f64>>,
+}
+
+impl LayerPrivacyPolicy {
+    pub fn validate(&self, model_dimension: usize) -> Result<(), String> {
+        match &self.partition {
+            LayerPartition::Layers { sizes } => {
+                if sizes.contains(&0) {
+                    return Err("Layer sizes must be positive".to_string());
+                }
+                if sizes.iter().sum::<usize>() != model_dimension {
+                    return Err(format!(
+                        "Layer sizes sum to {}, model_dimension is {}",
+                        sizes.iter().sum::<usize>(),
+                        model_dimension
+                    ));
+                }
+            }
+            LayerPartition::Buckets { size } if *size == 0 => {
+                return Err("Bucket size must be positive".to_string());
+            }
+            LayerPartition::Buckets { .. } => {}
+        }
+        if let Some(max_norms) = &self.max_norms {
+            let layers = self.ranges(model_dimension).len();
+            if max_norms.len() != layers {
+                return Err(format!("{} clipping bounds for {} layers", max_norms.len(), layers));
+            }
+            if max_norms.iter().any(|n| !(n.is_finite() && *n > 0.0)) {
+                return Err("Layer clipping bounds must be positive".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // Coordinate ranges of the layers of a vector of length `len`
+    pub fn ranges(&self, len: usize) -> Vec<Range<usize>> {
+        match &self.partition {
+            LayerPartition::Layers { sizes } => {
+                let mut start = 0;
+                sizes
+                    .iter()
+                    .map(|size| {
+                        let range = start.min(len)..(start + size).min(len);
+                        start += size;
+                        range
+                    })
+                    .collect()
+            }
+            LayerPartition::Buckets { size } => {
+                (0..len.div_ceil(*size)).map(|b| b * size..((b + 1) * size).min(len)).collect()
+            }
+        }
+    }
+
+    // Scales each layer of `gradients` down to its bound, independently of the others
+    pub fn clip(&self, gradients: &mut [f64]) {
+        let max_norms = match &self.max_norms {
+            Some(max_norms) => max_norms,
+            None => return,
+        };
+        for (range, max_norm) in self.ranges(gradients.len()).into_iter().zip(max_norms) {
+            let layer = &mut gradients[range];
+            let norm = kernels::l2_norm(layer);
+            if norm > *max_norm {
+                layer.iter_mut().for_each(|g| *g *= max_norm / norm);
+            }
+        }
+    }
+
+    // L2 sensitivity of each layer over the round's (already clipped) updates
+    pub fn sensitivities(&self, updates: &[ModelUpdate]) -> Vec<f64> {
+        let len = updates.iter().map(|u| u.gradients.len()).max().unwrap_or(0);
+        let ranges = self.ranges(len);
+        match &self.max_norms {
+            Some(max_norms) => max_norms.iter().take(ranges.len()).copied().collect(),
+            None => ranges
+                .iter()
+                .map(|range| {
+                    updates
+                        .iter()
+                        .map(|u| kernels::l2_norm(&u.gradients[range.start.min(u.gradients.len())..range.end.min(u.gradients.len())]))
+                        .fold(0.0, f64::max)
+                })
+                .collect(),
+        }
+    }
+
+    // Sensitivity to calibrate each coordinate's Gaussian noise to, so that the
+    // per-layer mechanisms compose to the configured (epsilon, delta)
+    pub fn coordinate_sensitivities(&self, updates: &[ModelUpdate]) -> Vec<f64> {
+        let len = updates.iter().map(|u| u.gradients.len()).max().unwrap_or(0);
+        let ranges = self.ranges(len);
+        let layers = (ranges.len() as f64).sqrt();
+        let mut coordinates = vec![0.0; len];
+        for (range, sensitivity) in ranges.into_iter().zip(self.sensitivities(updates)) {
+            coordinates[range].iter_mut().for_each(|c| *c = sensitivity * layers);
+        }
+        coordinates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(gradients: Vec<f64>) -> ModelUpdate {
+        ModelUpdate::for_test("", gradients, 1)
+    }
+
+    #[test]
+    fn test_small_layers_get_noise_sized_to_their_own_norm() {
+        let policy = LayerPrivacyPolicy { partition: LayerPartition::Layers { sizes: vec![3, 1] }, max_norms: None };
+        assert!(policy.validate(4).is_ok());
+        assert!(policy.validate(5).is_err());
+        let updates = vec![update(vec![30.0, 40.0, 0.0, 0.01]), update(vec![3.0, 4.0, 0.0, -0.02])];
+
+        assert_eq!(policy.sensitivities(&updates), vec![50.0, 0.02]);
+        let coordinates = policy.coordinate_sensitivities(&updates);
+        // The bias coordinate is calibrated to its own norm, not to the weights'
+        assert!(coordinates[3] < 0.03 && coordinates[0] > 70.0);
+        // Same guarantee as one mechanism of unit sensitivity
+        let composed: f64 = policy.sensitivities(&updates).iter().zip([coordinates[0], coordinates[3]]).map(|(s, c)| (s / c).powi(2)).sum();
+        assert!((composed - 1.0).abs() < 1e-12);
+
+        let clipped = LayerPrivacyPolicy { partition: LayerPartition::Buckets { size: 2 }, max_norms: Some(vec![5.0, 1.0]) };
+        assert!(clipped.validate(4).is_ok());
+        let mut gradients = vec![30.0, 40.0, 0.5, 0.0];
+        clipped.clip(&mut gradients);
+        assert_eq!(gradients, vec![3.0, 4.0, 0.5, 0.0]);
+        assert_eq!(clipped.sensitivities(&updates), vec![5.0, 1.0]);
+        assert!(LayerPrivacyPolicy { partition: LayerPartition::Buckets { size: 0 }, max_norms: None }.validate(4).is_err());
+    }
+}
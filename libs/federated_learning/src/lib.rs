@@ -10,6 +10,63 @@ pub mod compression;
 pub mod aggregation;
 pub mod optimization;
 pub mod communication;
+pub mod kernels;
+pub mod config;
+pub mod features;
+pub mod labels;
+pub mod cross_validation;
+pub mod drift;
+pub mod checkpoint;
+pub mod sketch;
+pub mod clients;
+pub mod buffers;
+pub mod tuning;
+pub mod analytics;
+pub mod attestation;
+pub mod integrity;
+pub mod optout;
+pub mod cohort;
+pub mod clipping;
+pub mod selection;
+pub mod hierarchy;
+pub mod personalization;
+pub mod clustering;
+pub mod split;
+pub mod distillation;
+pub mod multitask;
+pub mod pretrained;
+pub mod manifest;
+pub mod layer_privacy;
+pub mod dp_sgd;
+pub mod scalar;
+pub mod sparse;
+pub mod experiment;
+pub mod energy;
+pub mod secure_aggregation;
+pub mod artifact;
+pub mod round_diff;
+pub mod mpc;
+pub mod rdp_accountant;
+pub mod pacing;
+pub mod amplification;
+pub mod zcdp_accountant;
+pub mod granularity;
+pub mod shuffle;
+pub mod backdoor;
+pub mod inversion;
+pub mod anomaly;
+pub mod membership;
+pub mod partial;
+pub mod proofs;
+pub mod quotes;
+pub mod history;
+pub mod simulation;
+pub mod candidate;
+pub mod snapshot;
+pub mod early_stopping;
+pub mod lr_schedule;
+pub mod deadline;
+pub mod openmetrics;
 
 // Core federated learning types
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -26,6 +83,44 @@ pub struct ModelUpdate {
     pub privacy_budget_used: f64,
     pub compressed: bool,
     pub compression_ratio: Option<f64>,
+    // Present when the client trained inside a trusted execution environment
+    pub attestation: Option<TeeAttestation>,
+    // Accuracy of the client's personalized model on its local validation data
+    pub personalized_accuracy: Option<f64>,
+    // Nonzero coordinates, when the client sent its gradient sparse; `gradients` is then empty
+    pub sparse_gradients: Option<SparseGradients>,
+    // Coordinates of the layers a transfer-learning client fine-tuned; `gradients`
+    // then holds only those values, in order. None covers the whole model
+    pub trained_ranges: Option<Vec<LayerRange>>,
+    // Zero-knowledge proof that the update was computed as required, when the client attached one
+    pub gradient_proof: Option<GradientProof>,
+}
+
+#[cfg(test)]
+impl ModelUpdate {
+    // A plain update for tests: round 0, no loss or accuracy, nothing compressed,
+    // attested, sparse or proven. Tests override the fields they exercise
+    pub(crate) fn for_test(client_id: &str, gradients: Vec<f64>, data_size: usize) -> Self {
+        ModelUpdate {
+            client_id: client_id.to_string(),
+            round: 0,
+            gradients,
+            weights: Vec::new(),
+            loss: 0.0,
+            accuracy: 0.0,
+            data_size,
+            computation_time: 0.0,
+            communication_cost: 0.0,
+            privacy_budget_used: 0.0,
+            compressed: false,
+            compression_ratio: None,
+            attestation: None,
+            personalized_accuracy: None,
+            sparse_gradients: None,
+            trained_ranges: None,
+            gradient_proof: None,
+        }
+    }
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -35,9 +130,23 @@ pub struct GlobalModel {
     pub global_loss: f64,
     pub global_accuracy: f64,
     pub participating_clients: Vec<String>,
+    // Sum of the participating updates' data sizes after weighting adjustments
+    pub effective_sample_count: u64,
     pub convergence_metrics: ConvergenceMetrics,
     pub privacy_metrics: PrivacyMetrics,
     pub communication_metrics: CommunicationMetrics,
+    // Server-side norm clipping applied to this round's updates
+    pub clipping_stats: ClippingStats,
+    // Reported personalized-model accuracy per participating client
+    pub personalized_accuracy: HashMap<String, f64>,
+    // Updates the backdoor screen flagged this round, for audit
+    pub backdoor_flags: Vec<BackdoorFlag>,
+    // Whether this round's model was kept or rolled back (see candidate.rs); None
+    // without a policy or while sites are still evaluating it
+    pub candidate_evaluation: Option<CandidateEvaluation>,
+    // Scheduled server learning rate this round stepped with; None for algorithms
+    // without a server step (see lr_schedule.rs)
+    pub server_learning_rate: Option<f64>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -48,6 +157,8 @@ pub struct ConvergenceMetrics {
     pub accuracy_improvement: f64,
     pub convergence_rate: f64,
     pub stability_score: f64,
+    // L2 norm of the server momentum after the round (FedACG), for debugging
+    pub server_momentum_norm: f64,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -56,7 +167,17 @@ pub struct PrivacyMetrics {
     pub total_delta_used: f64,
     pub privacy_loss_per_client: HashMap<String, f64>,
     pub differential_privacy_guarantee: f64,
+    // From the latest membership inference audit (see membership.rs); 0 until one ran
     pub membership_inference_resistance: f64,
+    // L2 bound every update was clipped to before the latest DP round's noise, which
+    // is calibrated to it; None if no DP noise has been added yet
+    pub clip_norm: Option<f64>,
+    // Chance each selectable client had of being in the latest DP round, and that
+    // round's ε after amplification by subsampling (see amplification.rs)
+    pub sampling_rate: f64,
+    pub effective_round_epsilon: f64,
+    // Whether the latest DP round protected single records or whole clients
+    pub granularity: DpGranularity,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -67,14 +188,28 @@ pub struct CommunicationMetrics {
     pub communication_rounds: u64,
     pub average_round_time: f64,
     pub bandwidth_efficiency: f64,
+    // Estimates from the coordinator's energy model, for the last round and in total
+    pub round_energy_kwh: f64,
+    pub round_carbon_kg_co2e: f64,
+    pub total_energy_kwh: f64,
+    pub total_carbon_kg_co2e: f64,
+    // Latest round's deadline and the updates that missed it (see deadline.rs)
+    pub round_deadline_secs: Option<f64>,
+    pub round_stragglers: u32,
+    pub round_straggler_rate: f64,
+    pub max_straggler_delay_secs: f64,
+    pub total_stragglers: u64,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct FederatedLearningConfig {
     pub algorithm: FLAlgorithm,
     pub aggregation_method: AggregationMethod,
     pub compression_method: CompressionMethod,
     pub privacy_method: PrivacyMethod,
+    // Number of weights in the global model
+    pub model_dimension: usize,
     pub learning_rate: f64,
     pub momentum: f64,
     pub weight_decay: f64,
@@ -83,7 +218,13 @@ pub struct FederatedLearningConfig {
     pub client_fraction: f64,
     pub min_clients: u32,
     pub max_rounds: u32,
+    // Loss variance below which the default early-stopping strategy stops
     pub convergence_threshold: f64,
+    pub early_stopping: EarlyStoppingStrategy,
+    // Warmup and decay of `learning_rate` as FedAdam and FedOpt's server step
+    pub lr_schedule: LearningRateSchedule,
+    // Seconds from a round's start after which updates are left out as stragglers
+    pub round_deadline_secs: Option<f64>,
     pub privacy_budget: PrivacyBudget,
     pub communication_budget: CommunicationBudget,
 }
@@ -111,7 +252,11 @@ pub enum AggregationMethod {
     FoolsGold,
     MultiKrum { m: u32 },
     Bulyan { f: u32 },
-    SignSGD,
+    // Majority vote over client gradient signs; the global model moves by
+    // server_learning_rate against the voted sign
+    SignSGD { server_learning_rate: f64 },
+    // RFA: smoothed Weiszfeld iterations towards the geometric median of the updates
+    GeometricMedian { max_iterations: u32, tolerance: f64 },
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -135,13 +280,20 @@ pub enum PrivacyMethod {
     DifferentialPrivacy { epsilon: f64, delta: f64 },
     LocalDifferentialPrivacy { epsilon: f64 },
     SecureAggregation,
+    #[cfg(feature = "experimental")]
     HomomorphicEncryption,
-    MultiPartyComputation,
+    // Secret shares split across aggregator instances; rounds complete through
+    // `execute_mpc_round`
+    MultiPartyComputation { scheme: MpcScheme },
     TrustedExecutionEnvironment,
     GradientObfuscation { noise_scale: f64 },
+    // Local DP at ε₀ followed by a shuffler that permutes and anonymizes the round's
+    // updates, accounted with amplification by shuffling (see shuffle.rs)
+    Shuffle { local_epsilon: f64, delta: f64 },
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct PrivacyBudget {
     pub total_epsilon: f64,
     pub total_delta: f64,
@@ -159,6 +311,7 @@ pub enum CompositionMethod {
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct CommunicationBudget {
     pub max_bytes_per_round: u64,
     pub max_total_bytes: u64,
@@ -166,12 +319,156 @@ pub struct CommunicationBudget {
     pub adaptive_compression: bool,
 }
 
+impl FLAlgorithm {
+    // Every algorithm is executable now; kept so validation treats all method enums alike
+    pub fn check_supported(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl AggregationMethod {
+    pub fn check_supported(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Robust aggregators need to inspect individual client updates
+    pub fn requires_individual_updates(&self) -> bool {
+        matches!(
+            self,
+            AggregationMethod::Krum { .. }
+                | AggregationMethod::TrimmedMean { .. }
+                | AggregationMethod::Median
+                | AggregationMethod::MultiKrum { .. }
+                | AggregationMethod::Bulyan { .. }
+                | AggregationMethod::FoolsGold
+                | AggregationMethod::GeometricMedian { .. }
+        )
+    }
+}
+
+impl CompressionMethod {
+    pub fn check_supported(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// HomomorphicEncryption is the one variant left behind the `experimental` feature. It
+// has no runtime implementation, so validation rejects it and a coordinator is never
+// built with it
+impl PrivacyMethod {
+    pub fn check_supported(&self) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "experimental")]
+            PrivacyMethod::HomomorphicEncryption => {
+                Err(format!("Privacy method {:?} is experimental and not executable", self))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// State the coordinator carries across rounds, serialized for persistence and restarts
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CoordinatorState {
+    pub global_model: GlobalModel,
+    pub round_history: Vec<GlobalModel>,
+    pub compacted_history: Vec<RoundRecord>,
+    pub weight_history: CheckpointStore,
+    pub scaffold_state: ScaffoldState,
+    pub feddyn_state: FedDynState,
+    pub fedacg_state: FedAcgState,
+    pub foolsgold_state: FoolsGoldState,
+    pub personalization_state: PersonalizationState,
+    pub clustering: Option<ClusteringState>,
+    pub client_registry: ClientRegistry,
+    pub opt_out_reports: Vec<OptOutReport>,
+    pub manifest_log: Vec<ContributionManifest>,
+    pub rdp_accountant: RdpAccountant,
+    pub zcdp_accountant: ZcdpAccountant,
+    pub pacing: Option<PacingController>,
+    pub pending_candidate: Option<PendingCandidate>,
+    pub shuffled_participants: Vec<String>,
+    pub round_diagnostics: RoundDiagnostics,
+    pub membership_reports: Vec<MembershipLossReport>,
+    pub membership_audits: Vec<MembershipAudit>,
+    pub data_commitments: HashMap<String, String>,
+    pub deadline_outcome: Option<DeadlineOutcome>,
+}
+
 // Main federated learning coordinator
 pub struct FederatedLearningCoordinator {
     config: FederatedLearningConfig,
     global_model: GlobalModel,
     client_updates: HashMap<String, ModelUpdate>,
+    // Per-round metadata; weights live in `weight_history`, and rounds beyond the
+    // retention policy's full snapshots in `compacted_history`
     round_history: Vec<GlobalModel>,
+    compacted_history: Vec<RoundRecord>,
+    history_retention: HistoryRetentionPolicy,
+    weight_history: CheckpointStore,
+    scaffold_state: ScaffoldState,
+    feddyn_state: FedDynState,
+    fedacg_state: FedAcgState,
+    // Per-client gradient history for FoolsGold
+    foolsgold_state: FoolsGoldState,
+    // Per-client personalized heads, kept only when personalization is configured
+    personalization: Option<PersonalizationConfig>,
+    personalization_state: PersonalizationState,
+    // IFCA cluster models and assignments, when clustered training is enabled
+    clustering: Option<ClusteringState>,
+    client_registry: ClientRegistry,
+    dropout_policy: DropoutPolicy,
+    attestation_policy: AttestationPolicy,
+    clipping_policy: ClippingPolicy,
+    // Per-layer sensitivity for differential privacy; None uses one global sensitivity
+    layer_privacy: Option<LayerPrivacyPolicy>,
+    // Unit central DP protects: a record (noise per update) or a whole client
+    dp_granularity: DpGranularity,
+    client_selector: Box<dyn ClientSelector>,
+    // Whether cohorts are drawn uniformly at random, which privacy amplification needs
+    uniform_selection: bool,
+    quote_verifier: Option<Box<dyn QuoteVerifier>>,
+    // Opt-out registry version every site must have applied before training
+    required_opt_out_fingerprint: Option<String>,
+    opt_out_reports: Vec<OptOutReport>,
+    manifest_verifier: Option<Box<dyn ManifestVerifier>>,
+    // Signed contribution manifests of every round, in submission order
+    manifest_log: Vec<ContributionManifest>,
+    energy_model: Box<dyn EnergyModel>,
+    // Rényi DP composition of the rounds, used with CompositionMethod::RenyiDP
+    rdp_accountant: RdpAccountant,
+    // ρ-zCDP composition of the rounds, used with CompositionMethod::ZeroConcentratedDP
+    zcdp_accountant: ZcdpAccountant,
+    // Adjusts local epochs and deadlines to a target round time, when enabled
+    pacing: Option<PacingController>,
+    // Real ids behind the latest shuffled round's anonymous updates
+    shuffled_participants: Vec<String>,
+    // Spectral-signature screening of each round's updates, when enabled
+    backdoor_policy: Option<BackdoorPolicy>,
+    // Refuses updates too easy to invert back to patient data, when enabled
+    inversion_policy: Option<InversionRiskPolicy>,
+    inversion_risks: Vec<InversionRisk>,
+    // Statistical screening of updates during validation, when enabled
+    anomaly_policy: Option<AnomalyPolicy>,
+    round_diagnostics: RoundDiagnostics,
+    // Aggregated update of the latest round, the reference direction for screening
+    previous_global_update: Vec<f64>,
+    // Periodic membership inference audits of the global model, when enabled
+    membership_policy: Option<MembershipAuditPolicy>,
+    membership_reports: Vec<MembershipLossReport>,
+    membership_audits: Vec<MembershipAudit>,
+    // Checks of clients' gradient proofs, when enabled
+    proof_policy: Option<ProofPolicy>,
+    proof_verifiers: Vec<Box<dyn ProofVerifier>>,
+    // Commitment to each client's training data, which training proofs must name
+    data_commitments: HashMap<String, String>,
+    // Evaluation of each round's model against the previous one, when enabled
+    candidate_policy: Option<CandidateEvaluationPolicy>,
+    model_evaluator: Option<Box<dyn ModelEvaluator>>,
+    pending_candidate: Option<PendingCandidate>,
+    // Replaces the configured early-stopping strategy, when set
+    stopping_criterion: Option<Box<dyn StoppingCriterion>>,
+    deadline_outcome: Option<DeadlineOutcome>,
     privacy_engine: DifferentialPrivacy,
     compression_engine: CompressionEngine,
     aggregation_engine: AggregationEngine,
@@ -179,15 +476,23 @@ pub struct FederatedLearningCoordinator {
 }
 
 impl FederatedLearningCoordinator {
-    pub fn new(config: FederatedLearningConfig) -> Self {
-        let initial_weights = vec![0.0; 1000]; // Placeholder size
-        
+    pub fn new(config: FederatedLearningConfig) -> Result<Self, String> {
+        let initial_weights = vec![0.0; config.model_dimension];
+        Self::new_with_weights(config, initial_weights)
+    }
+
+    // Warm start from a centrally pretrained model
+    pub fn new_with_weights(config: FederatedLearningConfig, initial_weights: Vec<f64>) -> Result<Self, String> {
+        config.validate()?;
+        validate_initial_weights(&config, &initial_weights)?;
+
         let global_model = GlobalModel {
             round: 0,
             weights: initial_weights,
             global_loss: f64::INFINITY,
             global_accuracy: 0.0,
             participating_clients: Vec::new(),
+            effective_sample_count: 0,
             convergence_metrics: ConvergenceMetrics {
                 gradient_norm: 0.0,
                 weight_change_norm: 0.0,
@@ -195,6 +500,7 @@ impl FederatedLearningCoordinator {
                 accuracy_improvement: 0.0,
                 convergence_rate: 0.0,
                 stability_score: 0.0,
+                server_momentum_norm: 0.0,
             },
             privacy_metrics: PrivacyMetrics {
                 total_epsilon_used: 0.0,
@@ -202,6 +508,10 @@ impl FederatedLearningCoordinator {
                 privacy_loss_per_client: HashMap::new(),
                 differential_privacy_guarantee: 0.0,
                 membership_inference_resistance: 0.0,
+                clip_norm: None,
+                sampling_rate: 1.0,
+                effective_round_epsilon: 0.0,
+                granularity: DpGranularity::Record,
             },
             communication_metrics: CommunicationMetrics {
                 total_bytes_sent: 0,
@@ -210,70 +520,270 @@ impl FederatedLearningCoordinator {
                 communication_rounds: 0,
                 average_round_time: 0.0,
                 bandwidth_efficiency: 0.0,
+                round_energy_kwh: 0.0,
+                round_carbon_kg_co2e: 0.0,
+                total_energy_kwh: 0.0,
+                total_carbon_kg_co2e: 0.0,
+                round_deadline_secs: None,
+                round_stragglers: 0,
+                round_straggler_rate: 0.0,
+                max_straggler_delay_secs: 0.0,
+                total_stragglers: 0,
             },
+            clipping_stats: ClippingStats::default(),
+            personalized_accuracy: HashMap::new(),
+            backdoor_flags: Vec::new(),
+            candidate_evaluation: None,
+            server_learning_rate: None,
         };
 
-        FederatedLearningCoordinator {
+        let rdp_accountant = RdpAccountant::for_composition(&config.privacy_budget.composition_method);
+        Ok(FederatedLearningCoordinator {
             config,
             global_model,
             client_updates: HashMap::new(),
             round_history: Vec::new(),
+            compacted_history: Vec::new(),
+            history_retention: HistoryRetentionPolicy::default(),
+            weight_history: CheckpointStore::new(CheckpointConfig::default())?,
+            scaffold_state: ScaffoldState::new(),
+            feddyn_state: FedDynState::new(),
+            fedacg_state: FedAcgState::new(),
+            foolsgold_state: FoolsGoldState::new(),
+            personalization: None,
+            personalization_state: PersonalizationState::new(),
+            clustering: None,
+            client_registry: ClientRegistry::new(),
+            dropout_policy: DropoutPolicy::default(),
+            attestation_policy: AttestationPolicy::default(),
+            clipping_policy: ClippingPolicy::default(),
+            layer_privacy: None,
+            dp_granularity: DpGranularity::Record,
+            client_selector: Box::new(SelectionStrategy::default()),
+            uniform_selection: true,
+            quote_verifier: None,
+            required_opt_out_fingerprint: None,
+            opt_out_reports: Vec::new(),
+            manifest_verifier: None,
+            manifest_log: Vec::new(),
+            energy_model: Box::new(TdpEnergyModel::default()),
+            rdp_accountant,
+            zcdp_accountant: ZcdpAccountant::default(),
+            pacing: None,
+            shuffled_participants: Vec::new(),
+            backdoor_policy: None,
+            inversion_policy: None,
+            inversion_risks: Vec::new(),
+            anomaly_policy: None,
+            round_diagnostics: RoundDiagnostics::default(),
+            previous_global_update: Vec::new(),
+            membership_policy: None,
+            membership_reports: Vec::new(),
+            membership_audits: Vec::new(),
+            proof_policy: None,
+            proof_verifiers: Vec::new(),
+            data_commitments: HashMap::new(),
+            candidate_policy: None,
+            model_evaluator: None,
+            pending_candidate: None,
+            stopping_criterion: None,
+            deadline_outcome: None,
             privacy_engine: DifferentialPrivacy::new(),
             compression_engine: CompressionEngine::new(),
             aggregation_engine: AggregationEngine::new(),
             optimization_engine: OptimizationEngine::new(),
-        }
+        })
     }
 
     // Core federated learning round
     pub fn execute_round(&mut self, client_updates: Vec<ModelUpdate>) -> Result<GlobalModel, String> {
+        self.check_no_pending_candidate()?;
+        
         // 1. Validate and filter client updates
         let valid_updates = self.validate_client_updates(client_updates)?;
         
+        // Suspected backdoors are flagged, or dropped, before noise can mask them
+        let valid_updates = self.screen_for_backdoors(valid_updates)?;
+        
+        // Absent clients' expected weight is handled per the dropout policy
+        let valid_updates = self.client_registry.redistribute_weights(valid_updates, &self.dropout_policy);
+        
+        // Sparse updates stay sparse only if every later stage accepts them
+        let valid_updates = self.densify_unsupported_sparse(valid_updates);
+        
         // 2. Apply privacy mechanisms
         let private_updates = self.apply_privacy_mechanisms(valid_updates)?;
         
         // 3. Decompress updates if needed
         let decompressed_updates = self.decompress_updates(private_updates)?;
         
+        // FedNova rescales each client's update by its number of local steps
+        let decompressed_updates = self.normalize_local_steps(decompressed_updates);
+        
         // 4. Aggregate updates using selected method
         let aggregated_weights = self.aggregate_updates(&decompressed_updates)?;
         
+        // Client-level DP noises the average once instead of every update
+        let aggregated_weights = self.add_client_level_noise(aggregated_weights, &decompressed_updates)?;
+        let aggregated_weights = self.client_registry.retain_global_weight(
+            aggregated_weights,
+            &self.global_model.weights,
+            &decompressed_updates,
+            &self.dropout_policy,
+        );
+        
+        // SCAFFOLD refreshes control variates against the pre-round global model
+        if matches!(self.config.algorithm, FLAlgorithm::SCAFFOLD) {
+            self.update_scaffold_controls(&decompressed_updates)?;
+        }
+        
+        // Each cluster model averages only its own members' updates
+        if let Some(clustering) = &mut self.clustering {
+            clustering.aggregate(self.global_model.round, &decompressed_updates, &self.aggregation_engine)?;
+        }
+        
+        // Personal heads move relative to the pre-round global model
+        if let Some(personalization) = &self.personalization {
+            self.personalization_state.update(personalization, &self.global_model.weights, &decompressed_updates);
+        }
+        
+        self.finish_round(aggregated_weights, &decompressed_updates)
+    }
+
+    // Steps shared by plain and secure rounds once the updates are aggregated
+    fn finish_round(&mut self, aggregated_weights: Vec<f64>, updates: &[ModelUpdate]) -> Result<GlobalModel, String> {
+        self.previous_global_update = aggregated_weights.clone();
+        // Losses reported for the previous model say nothing about the next one
+        self.membership_reports.clear();
+        let previous_weights = self.global_model.weights.clone();
+        // Norm of the round's pseudo-gradient, before the server optimizer acts on it
+        self.global_model.convergence_metrics.gradient_norm =
+            self.compute_l2_norm_difference(&aggregated_weights, &previous_weights);
+        
         // 5. Apply optimization algorithm
-        let optimized_weights = self.apply_optimization(aggregated_weights)?;
+        let optimized_weights = self.apply_optimization(aggregated_weights, updates)?;
         
         // 6. Update global model
-        self.update_global_model(optimized_weights, &decompressed_updates)?;
+        self.update_global_model(optimized_weights, updates)?;
         
         // 7. Compute convergence and privacy metrics
-        self.compute_metrics(&decompressed_updates)?;
+        self.compute_metrics(updates)?;
+        
+        // Keep the previous weights if the new model scores worse on validation
+        self.evaluate_candidate(previous_weights)?;
         
-        // 8. Store round history
-        self.round_history.push(self.global_model.clone());
+        // 8. Store round history, keeping weights as keyframes and deltas
+        self.weight_history.push(self.global_model.round, &self.global_model.weights)?;
+        let mut history_entry = self.global_model.clone();
+        history_entry.weights = Vec::new();
+        self.round_history.push(history_entry);
+        self.compact_history();
+        
+        // 9. Advance client lifecycles: mark dropouts and implicit rejoins
+        self.client_registry.record_round(self.global_model.round, updates, &self.dropout_policy);
         
         Ok(self.global_model.clone())
     }
 
-    fn validate_client_updates(&self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
-        let mut valid_updates = Vec::new();
+    fn validate_client_updates(&mut self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
+        let mut candidates = Vec::new();
+        self.inversion_risks.clear();
+        self.round_diagnostics = RoundDiagnostics { round: self.global_model.round, received: updates.len(), ..RoundDiagnostics::default() };
         
-        for update in updates {
+        for mut update in updates {
             // Check if client is authorized
             if !self.is_client_authorized(&update.client_id) {
+                self.reject_update(&update.client_id, RejectionReason::Unauthorized);
                 continue;
             }
             
             // Check if update is from current round
             if update.round != self.global_model.round {
+                self.reject_update(&update.client_id, RejectionReason::WrongRound { round: update.round });
+                continue;
+            }
+            
+            // Only the cohort selected for this round may contribute
+            if !self.client_registry.in_cohort(&update.client_id, update.round) {
+                self.reject_update(&update.client_id, RejectionReason::NotInCohort);
+                continue;
+            }
+            
+            // Sites must have filtered their extract against the current opt-out registry
+            if self.required_opt_out_fingerprint.is_some()
+                && !self.has_opt_out_report(&update.client_id, update.round)
+            {
+                self.reject_update(&update.client_id, RejectionReason::MissingOptOutReport);
+                continue;
+            }
+            
+            // Check TEE attestation; verified enclaves may carry extra weight
+            match self.check_attestation(&update) {
+                AttestationStatus::Rejected { reason } => {
+                    self.reject_update(&update.client_id, RejectionReason::AttestationRejected { reason });
+                    continue;
+                }
+                AttestationStatus::Verified => {
+                    let weighted = update.data_size as f64 * self.attestation_policy.trusted_weight_multiplier;
+                    update.data_size = weighted.round() as usize;
+                }
+                AttestationStatus::Unattested => {}
+            }
+            
+            // Proofs are checked against the update exactly as the client sent it
+            if let Err(reason) = self.check_gradient_proof(&update) {
+                self.reject_update(&update.client_id, RejectionReason::ProofRejected { reason });
                 continue;
             }
             
             // Check gradient bounds (Byzantine fault tolerance)
-            if self.is_gradient_valid(&update.gradients) {
-                valid_updates.push(update);
+            if !self.is_gradient_valid(update.gradient_values()) {
+                self.reject_update(&update.client_id, RejectionReason::InvalidGradients);
+                continue;
+            }
+            if let Some(sparse) = &update.sparse_gradients {
+                if sparse.validate(self.global_model.weights.len()).is_err() {
+                    self.reject_update(&update.client_id, RejectionReason::InvalidSparseGradients);
+                    continue;
+                }
+            }
+            // Layer-subset updates are filled out to the full model from the global weights
+            if let Err(reason) = self.expand_partial_update(&mut update) {
+                self.reject_update(&update.client_id, RejectionReason::InvalidPartialUpdate { reason });
+                continue;
+            }
+            // SCAFFOLD refreshes the client's control variate from its local model
+            if matches!(self.config.algorithm, FLAlgorithm::SCAFFOLD) && update.weights.len() != self.global_model.weights.len() {
+                self.reject_update(&update.client_id, RejectionReason::InvalidLocalWeights { received: update.weights.len() });
+                continue;
+            }
+            
+            // Updates that would give away their examples never reach aggregation
+            if !self.check_inversion_risk(&update) {
+                let score = self.inversion_risks.last().map_or(0.0, |risk| risk.score);
+                self.reject_update(&update.client_id, RejectionReason::InversionRisk { score });
+                continue;
+            }
+            
+            // Updates pointing away from where the model has been heading
+            if let Some(reason) = self.direction_anomaly(&update) {
+                self.reject_update(&update.client_id, reason);
+                continue;
             }
+            candidates.push(update);
         }
         
+        // Coordinate and loss outliers are judged against the round's other updates
+        let mut valid_updates = self.filter_statistical_anomalies(candidates);
+        
+        // Bound each update's L2 norm so no single client dominates the aggregate
+        let mut clipping_stats = ClippingStats::default();
+        for update in &mut valid_updates {
+            self.clipping_policy.clip(update.gradient_values_mut(), &mut clipping_stats);
+        }
+        self.global_model.clipping_stats = clipping_stats;
+        self.round_diagnostics.accepted = valid_updates.len();
+        
         if valid_updates.len() < self.config.min_clients as usize {
             return Err("Insufficient valid client updates".to_string());
         }
@@ -290,31 +800,66 @@ impl FederatedLearningCoordinator {
             PrivacyMethod::LocalDifferentialPrivacy { epsilon } => {
                 self.apply_local_differential_privacy(updates, *epsilon)
             }
-            PrivacyMethod::SecureAggregation => {
+            PrivacyMethod::SecureAggregation | PrivacyMethod::MultiPartyComputation { .. } => {
                 self.apply_secure_aggregation(updates)
             }
             PrivacyMethod::GradientObfuscation { noise_scale } => {
                 self.apply_gradient_obfuscation(updates, *noise_scale)
             }
+            PrivacyMethod::Shuffle { local_epsilon, delta } => {
+                self.apply_shuffle(updates, *local_epsilon, *delta)
+            }
+            // Confidentiality comes from the enclave; validation already required
+            // every update to carry a verified attestation
+            PrivacyMethod::TrustedExecutionEnvironment => Ok(updates),
+            #[cfg(feature = "experimental")]
             _ => Err("Privacy method not implemented".to_string()),
         }
     }
 
     fn apply_differential_privacy(&mut self, mut updates: Vec<ModelUpdate>, epsilon: f64, delta: f64) -> Result<Vec<ModelUpdate>, String> {
-        let sensitivity = self.compute_gradient_sensitivity(&updates);
+        // Noise calibrated to the round's largest observed norm would leak it, so the
+        // sensitivity must be a bound fixed before any update arrives
+        let clip_norm = self.dp_clip_norm()?;
+        // Each client is charged the round's ε amplified by the cohort's sampling rate
+        let sampling_rate = self.round_sampling_rate();
+        let round_epsilon = amplified_epsilon(epsilon, sampling_rate);
+        let sensitivities = match &self.layer_privacy {
+            Some(policy) => {
+                for update in &mut updates {
+                    policy.clip(&mut update.gradients);
+                }
+                policy.coordinate_sensitivities(&updates)
+            }
+            None => {
+                // Updates were clipped to `clip_norm` during validation
+                let len = updates.iter().map(|u| u.gradients.len()).max().unwrap_or(0);
+                vec![clip_norm; len]
+            }
+        };
         
+        if self.dp_granularity == DpGranularity::Client {
+            // The aggregate is noised after averaging (see granularity.rs)
+            self.equalize_client_weights(&mut updates);
+        } else {
+            for update in &mut updates {
+                // Add calibrated noise to gradients
+                let noise: Vec<f64> = sensitivities[..update.gradients.len()]
+                    .iter()
+                    .map(|&sensitivity| self.privacy_engine.add_gaussian_noise(sensitivity, epsilon, delta))
+                    .collect();
+                
+                kernels::add_assign(&mut update.gradients, &noise);
+            }
+        }
         for update in &mut updates {
-            // Add calibrated noise to gradients
-            let noisy_gradients: Vec<f64> = update.gradients
-                .iter()
-                .map(|&gradient| {
-                    gradient + self.privacy_engine.add_gaussian_noise(sensitivity, epsilon, delta)
-                })
-                .collect();
-            
-            update.gradients = noisy_gradients;
-            update.privacy_budget_used = epsilon;
+            update.privacy_budget_used = round_epsilon;
         }
+        let metrics = &mut self.global_model.privacy_metrics;
+        metrics.granularity = self.dp_granularity.clone();
+        metrics.clip_norm = Some(clip_norm);
+        metrics.sampling_rate = sampling_rate;
+        metrics.effective_round_epsilon = round_epsilon;
         
         Ok(updates)
     }
@@ -324,36 +869,29 @@ impl FederatedLearningCoordinator {
         for update in &mut updates {
             let noise_scale = 2.0 / epsilon; // Laplace mechanism
             
-            let noisy_gradients: Vec<f64> = update.gradients
-                .iter()
-                .map(|&gradient| {
-                    gradient + self.sample_laplace_noise(0.0, noise_scale)
-                })
+            let noise: Vec<f64> = (0..update.gradients.len())
+                .map(|_| self.sample_laplace_noise(0.0, noise_scale))
                 .collect();
             
-            update.gradients = noisy_gradients;
+            kernels::add_assign(&mut update.gradients, &noise);
             update.privacy_budget_used = epsilon;
         }
         
         Ok(updates)
     }
 
-    fn apply_secure_aggregation(&self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
-        // Simplified secure aggregation - in practice would use cryptographic protocols
-        // This is a placeholder for the actual secure aggregation implementation
-        Ok(updates)
+    fn apply_secure_aggregation(&self, _updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
+        // Individual updates must never reach the coordinator in the clear
+        Err("Secure aggregation and MPC rounds complete through execute_secure_round or execute_mpc_round".to_string())
     }
 
     fn apply_gradient_obfuscation(&self, mut updates: Vec<ModelUpdate>, noise_scale: f64) -> Result<Vec<ModelUpdate>, String> {
         for update in &mut updates {
-            let obfuscated_gradients: Vec<f64> = update.gradients
-                .iter()
-                .map(|&gradient| {
-                    gradient + self.sample_gaussian_noise(0.0, noise_scale)
-                })
+            let noise: Vec<f64> = (0..update.gradients.len())
+                .map(|_| self.sample_gaussian_noise(0.0, noise_scale))
                 .collect();
             
-            update.gradients = obfuscated_gradients;
+            kernels::add_assign(&mut update.gradients, &noise);
         }
         
         Ok(updates)
@@ -368,15 +906,43 @@ impl FederatedLearningCoordinator {
             CompressionMethod::Sparsification { sparsity_ratio } => {
                 self.compression_engine.desparsify_updates(updates, *sparsity_ratio)
             }
-            CompressionMethod::TopK { k } => {
+            CompressionMethod::TopK { k } | CompressionMethod::RandomK { k } => {
                 self.compression_engine.decompress_topk_updates(updates, *k)
             }
-            _ => Err("Compression method not implemented".to_string()),
+            CompressionMethod::DeepGradientCompression { compression_ratio } => {
+                self.compression_engine.desparsify_updates(updates, 1.0 - compression_ratio)
+            }
+            CompressionMethod::QSGD { levels } => {
+                self.compression_engine.dequantize_levels(updates, *levels)
+            }
+            CompressionMethod::FedPAQ { quantization_levels } => {
+                self.compression_engine.dequantize_levels(updates, *quantization_levels)
+            }
+            CompressionMethod::TernGrad => {
+                self.compression_engine.decompress_ternary_updates(updates)
+            }
+            CompressionMethod::SignSGD => {
+                self.compression_engine.decompress_sign_updates(updates)
+            }
+            CompressionMethod::AdaptiveCompression { .. } => {
+                // Clients pick the scheme per round and send reconstructed dense values
+                Ok(updates)
+            }
         }
     }
 
-    fn aggregate_updates(&self, updates: &[ModelUpdate]) -> Result<Vec<f64>, String> {
+    fn aggregate_updates(&mut self, updates: &[ModelUpdate]) -> Result<Vec<f64>, String> {
         match &self.config.aggregation_method {
+            AggregationMethod::WeightedAverage | AggregationMethod::FedAvg
+                if updates.iter().any(|u| u.trained_ranges.is_some()) =>
+            {
+                self.aggregation_engine.partial_weighted_average(updates, &self.global_model.weights)
+            }
+            AggregationMethod::WeightedAverage | AggregationMethod::FedAvg
+                if updates.iter().any(|u| u.sparse_gradients.is_some()) =>
+            {
+                self.aggregation_engine.sparse_weighted_average(updates, self.global_model.weights.len())
+            }
             AggregationMethod::WeightedAverage => {
                 self.aggregation_engine.weighted_average(updates)
             }
@@ -392,27 +958,128 @@ impl FederatedLearningCoordinator {
             AggregationMethod::Median => {
                 self.aggregation_engine.median_aggregation(updates)
             }
-            _ => Err("Aggregation method not implemented".to_string()),
+            AggregationMethod::MultiKrum { m } => {
+                self.aggregation_engine.multi_krum_aggregation(updates, *m)
+            }
+            AggregationMethod::FoolsGold => {
+                self.aggregation_engine.foolsgold_aggregation(&mut self.foolsgold_state, updates)
+            }
+            AggregationMethod::Bulyan { f } => {
+                self.aggregation_engine.bulyan_aggregation(updates, *f)
+            }
+            AggregationMethod::SignSGD { server_learning_rate } => {
+                self.aggregation_engine.sign_majority_vote(updates, *server_learning_rate, &self.global_model.weights)
+            }
+            AggregationMethod::GeometricMedian { max_iterations, tolerance } => {
+                self.aggregation_engine.geometric_median_aggregation(updates, *max_iterations, *tolerance)
+            }
         }
     }
 
-    fn apply_optimization(&mut self, weights: Vec<f64>) -> Result<Vec<f64>, String> {
+    fn apply_optimization(&mut self, weights: Vec<f64>, updates: &[ModelUpdate]) -> Result<Vec<f64>, String> {
+        let server_learning_rate = self.server_learning_rate();
+        self.global_model.server_learning_rate = matches!(self.config.algorithm, FLAlgorithm::FedAdam { .. } | FLAlgorithm::FedOpt)
+            .then_some(server_learning_rate);
         match &self.config.algorithm {
             FLAlgorithm::FedAvg => Ok(weights),
             FLAlgorithm::FedProx { mu } => {
                 self.optimization_engine.fedprox_optimization(weights, *mu, &self.global_model.weights)
             }
             FLAlgorithm::FedAdam { beta1, beta2 } => {
-                self.optimization_engine.fedadam_optimization(weights, *beta1, *beta2, self.config.learning_rate)
+                self.optimization_engine.fedadam_optimization(weights, *beta1, *beta2, server_learning_rate)
             }
             FLAlgorithm::FedAvgM { momentum } => {
                 self.optimization_engine.fedavgm_optimization(weights, *momentum, &self.global_model.weights)
             }
             FLAlgorithm::SCAFFOLD => {
-                self.optimization_engine.scaffold_optimization(weights, &self.global_model.weights)
+                // Global step size η_g = 1, as in the paper's experiments
+                self.optimization_engine.scaffold_optimization(weights, 1.0, &self.global_model.weights)
+            }
+            FLAlgorithm::FedDyn { alpha } => {
+                let total_clients = self.client_registry.population(updates);
+                self.optimization_engine.feddyn_optimization(
+                    &mut self.feddyn_state,
+                    weights,
+                    *alpha,
+                    &self.global_model.weights,
+                    updates.len(),
+                    total_clients,
+                )
+            }
+            FLAlgorithm::FedACG { lookahead_steps } => {
+                // Server momentum λ is the configured momentum
+                self.optimization_engine.fedacg_optimization(
+                    &mut self.fedacg_state,
+                    weights,
+                    self.config.momentum,
+                    *lookahead_steps,
+                    &self.global_model.weights,
+                )
+            }
+            // The normalized averaging already happened before aggregation
+            FLAlgorithm::FedNova => Ok(weights),
+            FLAlgorithm::FedOpt => {
+                self.optimization_engine.fedopt_optimization(weights, server_learning_rate, &self.global_model.weights)
+            }
+        }
+    }
+
+    fn normalize_local_steps(&self, mut updates: Vec<ModelUpdate>) -> Vec<ModelUpdate> {
+        if !matches!(self.config.algorithm, FLAlgorithm::FedNova) {
+            return updates;
+        }
+
+        let local_steps: Vec<f64> = updates.iter().map(|u| self.local_steps(u)).collect();
+
+        let total_data: f64 = updates.iter().map(|u| u.data_size as f64).sum();
+        if total_data == 0.0 {
+            return updates;
+        }
+
+        let effective_steps: f64 = updates
+            .iter()
+            .zip(local_steps.iter())
+            .map(|(u, &tau)| u.data_size as f64 / total_data * tau)
+            .sum();
+
+        for (update, &tau) in updates.iter_mut().zip(local_steps.iter()) {
+            let scale = effective_steps / tau;
+            for gradient in update.gradient_values_mut() {
+                *gradient *= scale;
             }
-            _ => Err("Optimization algorithm not implemented".to_string()),
         }
+
+        updates
+    }
+
+    // Local steps per client: epochs x batches over the client's data
+    fn local_steps(&self, update: &ModelUpdate) -> f64 {
+        let batches = (update.data_size as f64 / self.config.batch_size.max(1) as f64).ceil().max(1.0);
+        self.config.local_epochs.max(1) as f64 * batches
+    }
+
+    fn update_scaffold_controls(&mut self, updates: &[ModelUpdate]) -> Result<(), String> {
+        let results: Vec<ScaffoldClientResult> = updates
+            .iter()
+            .map(|u| ScaffoldClientResult {
+                client_id: &u.client_id,
+                local_weights: &u.weights,
+                local_steps: self.local_steps(u),
+            })
+            .collect();
+        let total_clients = updates
+            .iter()
+            .filter(|u| !self.scaffold_state.client_controls.contains_key(&u.client_id))
+            .count()
+            + self.scaffold_state.client_controls.len();
+
+        self.optimization_engine.scaffold_update_controls(
+            &mut self.scaffold_state,
+            &self.global_model.weights,
+            &results,
+            self.config.learning_rate,
+            total_clients,
+        )
     }
 
     fn update_global_model(&mut self, new_weights: Vec<f64>, updates: &[ModelUpdate]) -> Result<(), String> {
@@ -421,6 +1088,11 @@ impl FederatedLearningCoordinator {
         self.global_model.round += 1;
         self.global_model.weights = new_weights;
         self.global_model.participating_clients = updates.iter().map(|u| u.client_id.clone()).collect();
+        self.global_model.effective_sample_count = updates.iter().map(|u| u.data_size as u64).sum();
+        self.global_model.personalized_accuracy = updates
+            .iter()
+            .filter_map(|u| u.personalized_accuracy.map(|accuracy| (u.client_id.clone(), accuracy)))
+            .collect();
         
         // Compute global metrics
         self.global_model.global_loss = self.compute_weighted_average_loss(updates);
@@ -429,19 +1101,26 @@ impl FederatedLearningCoordinator {
         // Update convergence metrics
         self.global_model.convergence_metrics.weight_change_norm = 
             self.compute_l2_norm_difference(&self.global_model.weights, &previous_weights);
+        self.global_model.convergence_metrics.server_momentum_norm =
+            self.compute_l2_norm(&self.fedacg_state.momentum);
         
         Ok(())
     }
 
     fn compute_metrics(&mut self, updates: &[ModelUpdate]) -> Result<(), String> {
-        // Update privacy metrics
-        let total_epsilon: f64 = updates.iter().map(|u| u.privacy_budget_used).sum();
-        self.global_model.privacy_metrics.total_epsilon_used += total_epsilon;
-        
-        for update in updates {
-            *self.global_model.privacy_metrics.privacy_loss_per_client
-                .entry(update.client_id.clone())
-                .or_insert(0.0) += update.privacy_budget_used;
+        // Update privacy metrics, composing rounds in RDP or zCDP when configured
+        if !self.record_rdp_round(updates) && !self.record_zcdp_round(updates) {
+            let total_epsilon: f64 = updates.iter().map(|u| u.privacy_budget_used).sum();
+            self.global_model.privacy_metrics.total_epsilon_used += total_epsilon;
+            
+            // With amplification, clients left out of the cohort pay the round's ε too
+            let round_epsilon = self.global_model.privacy_metrics.effective_round_epsilon;
+            for client_id in self.privacy_accounted_clients(updates) {
+                let used = updates.iter().find(|u| u.client_id == client_id).map_or(round_epsilon, |u| u.privacy_budget_used);
+                *self.global_model.privacy_metrics.privacy_loss_per_client
+                    .entry(client_id)
+                    .or_insert(0.0) += used;
+            }
         }
         
         // Update communication metrics
@@ -449,6 +1128,18 @@ impl FederatedLearningCoordinator {
         self.global_model.communication_metrics.total_bytes_sent += total_communication_cost as u64;
         self.global_model.communication_metrics.communication_rounds += 1;
         
+        // Energy and carbon of the round's local training and transfers
+        let energy = self.round_energy(updates);
+        let metrics = &mut self.global_model.communication_metrics;
+        metrics.round_energy_kwh = energy.energy_kwh;
+        metrics.round_carbon_kg_co2e = energy.carbon_kg_co2e;
+        metrics.total_energy_kwh += energy.energy_kwh;
+        metrics.total_carbon_kg_co2e += energy.carbon_kg_co2e;
+        
+        // Local training times feed the pacing controller once the round's duration is known
+        self.record_pacing_computation(updates);
+        self.record_straggler_metrics();
+        
         // Compute compression savings
         let compressed_updates: Vec<&ModelUpdate> = updates.iter().filter(|u| u.compressed).collect();
         if !compressed_updates.is_empty() {
@@ -474,22 +1165,8 @@ impl FederatedLearningCoordinator {
         gradients.iter().all(|&g| g.is_finite() && g.abs() < 1e6)
     }
 
-    fn compute_gradient_sensitivity(&self, updates: &[ModelUpdate]) -> f64 {
-        // Compute L2 sensitivity for differential privacy
-        let mut max_norm = 0.0;
-        
-        for update in updates {
-            let norm = self.compute_l2_norm(&update.gradients);
-            if norm > max_norm {
-                max_norm = norm;
-            }
-        }
-        
-        max_norm
-    }
-
     fn compute_l2_norm(&self, vector: &[f64]) -> f64 {
-        vector.iter().map(|&x| x * x).sum::<f64>().sqrt()
+        kernels::l2_norm(vector)
     }
 
     fn compute_l2_norm_difference(&self, vec1: &[f64], vec2: &[f64]) -> f64 {
@@ -540,37 +1217,320 @@ impl FederatedLearningCoordinator {
         &self.global_model
     }
 
+    pub fn get_config(&self) -> &FederatedLearningConfig {
+        &self.config
+    }
+
+    // Rounds still kept as full snapshots (see history.rs); weights are not included,
+    // use `get_round_weights` to rebuild them
     pub fn get_round_history(&self) -> &[GlobalModel] {
         &self.round_history
     }
 
-    pub fn is_converged(&self) -> bool {
-        if self.global_model.round < 10 {
-            return false;
+    pub fn get_round_weights(&self, round: u64) -> Option<Vec<f64>> {
+        self.weight_history.reconstruct(round)
+    }
+
+    // Replaces the weight history storage; previously stored rounds are discarded
+    pub fn set_checkpoint_config(&mut self, config: CheckpointConfig) -> Result<(), String> {
+        self.weight_history = CheckpointStore::new(config)?;
+        Ok(())
+    }
+
+    pub fn get_checkpoint_memory_report(&self) -> CheckpointMemoryReport {
+        self.weight_history.memory_report()
+    }
+
+    // Model clients should start the next round from: the FedACG lookahead point when
+    // that algorithm is selected, otherwise the global model itself
+    pub fn get_broadcast_weights(&self) -> Vec<f64> {
+        match &self.config.algorithm {
+            FLAlgorithm::FedACG { lookahead_steps } => self.optimization_engine.fedacg_lookahead(
+                &self.fedacg_state,
+                &self.global_model.weights,
+                self.config.momentum,
+                *lookahead_steps,
+            ),
+            _ => self.global_model.weights.clone(),
         }
-        
-        // Check convergence based on loss improvement
-        let recent_losses: Vec<f64> = self.round_history
-            .iter()
-            .rev()
-            .take(5)
-            .map(|m| m.global_loss)
-            .collect();
-        
-        if recent_losses.len() < 5 {
-            return false;
+    }
+
+    pub fn get_fedacg_momentum(&self) -> &[f64] {
+        &self.fedacg_state.momentum
+    }
+
+    // SCAFFOLD controls (c, c_i) to send to a client with the next round's model
+    pub fn get_scaffold_controls(&self, client_id: &str) -> (Vec<f64>, Vec<f64>) {
+        self.scaffold_state.controls_for(client_id)
+    }
+
+    pub fn set_dropout_policy(&mut self, policy: DropoutPolicy) -> Result<(), String> {
+        policy.validate()?;
+        self.dropout_policy = policy;
+        Ok(())
+    }
+
+    // Registers a client ahead of its first round with the weight it is expected to carry
+    pub fn register_client(&mut self, client_id: &str, expected_data_size: usize) {
+        self.client_registry.register(client_id, expected_data_size as f64);
+    }
+
+    pub fn set_attestation_policy(&mut self, policy: AttestationPolicy) -> Result<(), String> {
+        policy.validate()?;
+        self.attestation_policy = policy;
+        Ok(())
+    }
+
+    pub fn set_clipping_policy(&mut self, policy: ClippingPolicy) -> Result<(), String> {
+        policy.validate()?;
+        self.clipping_policy = policy;
+        Ok(())
+    }
+
+    pub fn set_layer_privacy(&mut self, policy: Option<LayerPrivacyPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate(self.config.model_dimension)?;
         }
-        
-        let loss_variance = self.compute_variance(&recent_losses);
-        loss_variance < self.config.convergence_threshold
+        self.layer_privacy = policy;
+        Ok(())
+    }
+
+    pub fn set_selection_strategy(&mut self, strategy: SelectionStrategy) -> Result<(), String> {
+        strategy.validate()?;
+        self.uniform_selection = strategy == SelectionStrategy::UniformRandom;
+        self.client_selector = Box::new(strategy);
+        Ok(())
+    }
+
+    pub fn set_client_selector(&mut self, selector: Box<dyn ClientSelector>) {
+        self.client_selector = selector;
+        self.uniform_selection = false;
+    }
+
+    pub fn report_client_resources(&mut self, client_id: &str, resources: ClientResources) -> Result<(), String> {
+        self.client_registry.set_resources(client_id, resources)
+    }
+
+    // Draws the cohort for the current round from the registered, non-dropped clients.
+    // Without a selection every registered client may contribute, as before.
+    pub fn select_clients(&mut self) -> Result<Vec<String>, String> {
+        let round = self.global_model.round;
+        let candidates: Vec<ClientRecord> =
+            self.client_registry.records().into_iter().filter(|r| r.status != ClientStatus::Dropped).collect();
+        let count = cohort_size(&self.config, candidates.len());
+        let selected = self.client_selector.select(&candidates, count, round)?;
+
+        if let Some(unknown) = selected.iter().find(|id| !candidates.iter().any(|r| &r.client_id == *id)) {
+            return Err(format!("Selector picked {}, which is not a selectable client", unknown));
+        }
+        if selected.len() < self.config.min_clients as usize {
+            return Err(format!(
+                "Only {} clients selectable for round {}, {} required",
+                selected.len(),
+                round,
+                self.config.min_clients
+            ));
+        }
+        let sampling_rate = Some(selected.len() as f64 / candidates.len() as f64);
+        self.client_registry.set_cohort(RoundCohort { round, clients: selected.clone(), sampling_rate });
+        Ok(selected)
+    }
+
+    pub fn get_selected_clients(&self) -> Option<Vec<String>> {
+        self.client_registry.cohort().filter(|c| c.round == self.global_model.round).map(|c| c.clients.clone())
     }
 
-    fn compute_variance(&self, values: &[f64]) -> f64 {
-        let mean = values.iter().sum::<f64>() / values.len() as f64;
-        let variance = values.iter()
-            .map(|&x| (x - mean).powi(2))
-            .sum::<f64>() / values.len() as f64;
-        variance
+    pub fn set_personalization(&mut self, config: Option<PersonalizationConfig>) -> Result<(), String> {
+        if let Some(config) = &config {
+            config.validate()?;
+        }
+        self.personalization = config;
+        Ok(())
+    }
+
+    // The model a client should evaluate and fine-tune locally: the global model with
+    // its personalized head when personalization is on
+    pub fn get_personalized_weights(&self, client_id: &str) -> Vec<f64> {
+        match &self.personalization {
+            Some(config) => self.personalization_state.personalized_weights(config, client_id, &self.global_model.weights),
+            None => self.global_model.weights.clone(),
+        }
+    }
+
+    // Enables IFCA with the given initial cluster models (see ClusteringState::perturbed);
+    // None returns to a single global model
+    pub fn set_clustering(&mut self, clustering: Option<ClusteringState>) {
+        self.clustering = clustering;
+    }
+
+    // Clients report their local loss on every cluster model before training a round
+    pub fn submit_cluster_losses(&mut self, report: ClusterLossReport) -> Result<usize, String> {
+        if report.round != self.global_model.round {
+            return Err(format!("Cluster losses are for round {}, current round is {}", report.round, self.global_model.round));
+        }
+        match &mut self.clustering {
+            Some(clustering) => clustering.assign(&report),
+            None => Err("Clustered training is not enabled".to_string()),
+        }
+    }
+
+    // The model a client should train: its cluster's model, or the global model
+    pub fn get_cluster_weights(&self, client_id: &str) -> Vec<f64> {
+        self.clustering
+            .as_ref()
+            .and_then(|c| c.assignment(client_id).map(|cluster| c.models()[cluster].clone()))
+            .unwrap_or_else(|| self.global_model.weights.clone())
+    }
+
+    pub fn get_cluster_models(&self) -> Vec<Vec<f64>> {
+        self.clustering.as_ref().map(|c| c.models().to_vec()).unwrap_or_default()
+    }
+
+    pub fn set_quote_verifier(&mut self, verifier: Box<dyn QuoteVerifier>) {
+        self.quote_verifier = Some(verifier);
+    }
+
+    // With a fingerprint set, updates are only accepted from sites that reported
+    // applying exactly that opt-out registry in the update's round
+    pub fn require_opt_out_registry(&mut self, fingerprint: Option<String>) {
+        self.required_opt_out_fingerprint = fingerprint;
+    }
+
+    pub fn submit_opt_out_report(&mut self, report: OptOutReport) -> Result<(), String> {
+        if report.round != self.global_model.round {
+            return Err(format!("Opt-out report is for round {}, current round is {}", report.round, self.global_model.round));
+        }
+        if let Some(required) = &self.required_opt_out_fingerprint {
+            if &report.stats.registry_fingerprint != required {
+                return Err(format!("Site {} applied an outdated opt-out registry", report.site_id));
+            }
+        }
+        self.opt_out_reports.retain(|r| !(r.site_id == report.site_id && r.round == report.round));
+        self.opt_out_reports.push(report);
+        Ok(())
+    }
+
+    fn has_opt_out_report(&self, client_id: &str, round: u64) -> bool {
+        self.opt_out_reports.iter().any(|r| {
+            r.site_id == client_id
+                && r.round == round
+                && Some(&r.stats.registry_fingerprint) == self.required_opt_out_fingerprint.as_ref()
+        })
+    }
+
+    // Exclusion statistics per site for one round, for ethics reporting
+    pub fn get_opt_out_reports(&self, round: u64) -> Vec<OptOutReport> {
+        self.opt_out_reports.iter().filter(|r| r.round == round).cloned().collect()
+    }
+
+    // Policy checks plus platform quote verification. Without a verifier a quote
+    // cannot be trusted, so the update counts as unattested unless attestation is required.
+    pub fn check_attestation(&self, update: &ModelUpdate) -> AttestationStatus {
+        let required = matches!(self.config.privacy_method, PrivacyMethod::TrustedExecutionEnvironment)
+            || self.attestation_policy.require_attestation;
+        let status = self.attestation_policy.check(update, required);
+        let attestation = match (&status, &update.attestation) {
+            (AttestationStatus::Verified, Some(attestation)) => attestation,
+            _ => return status,
+        };
+        match &self.quote_verifier {
+            Some(verifier) => match verifier.verify_quote(attestation) {
+                Ok(()) => AttestationStatus::Verified,
+                Err(reason) => AttestationStatus::Rejected { reason },
+            },
+            None if required => AttestationStatus::Rejected { reason: "No quote verifier configured".to_string() },
+            None => AttestationStatus::Unattested,
+        }
+    }
+
+    pub fn get_client_status(&self, client_id: &str) -> Option<ClientStatus> {
+        self.client_registry.status(client_id)
+    }
+
+    pub fn get_client_records(&self) -> Vec<ClientRecord> {
+        self.client_registry.records()
+    }
+
+    // Brings a dropped client back: applies the policy's resets and returns the latest
+    // model and control variates it must load before training the next round
+    pub fn rejoin_client(&mut self, client_id: &str) -> Result<ClientResync, String> {
+        if !self.client_registry.mark_rejoined(client_id) {
+            return Err(format!("Client {} is not currently dropped", client_id));
+        }
+        if self.dropout_policy.reset_control_variate {
+            self.scaffold_state.client_controls.remove(client_id);
+        }
+        Ok(self.get_client_resync(client_id))
+    }
+
+    pub fn get_client_resync(&self, client_id: &str) -> ClientResync {
+        let (server_control, client_control) = self.scaffold_state.controls_for(client_id);
+        let rejoining = self.client_registry.status(client_id) == Some(ClientStatus::Rejoined);
+        ClientResync {
+            client_id: client_id.to_string(),
+            round: self.global_model.round,
+            weights: self.get_broadcast_weights(),
+            server_control,
+            client_control,
+            reset_error_feedback: rejoining && self.dropout_policy.reset_error_feedback,
+        }
+    }
+
+    pub fn export_state(&self) -> CoordinatorState {
+        CoordinatorState {
+            global_model: self.global_model.clone(),
+            round_history: self.round_history.clone(),
+            compacted_history: self.compacted_history.clone(),
+            weight_history: self.weight_history.clone(),
+            scaffold_state: self.scaffold_state.clone(),
+            feddyn_state: self.feddyn_state.clone(),
+            fedacg_state: self.fedacg_state.clone(),
+            foolsgold_state: self.foolsgold_state.clone(),
+            personalization_state: self.personalization_state.clone(),
+            clustering: self.clustering.clone(),
+            client_registry: self.client_registry.clone(),
+            opt_out_reports: self.opt_out_reports.clone(),
+            manifest_log: self.manifest_log.clone(),
+            rdp_accountant: self.rdp_accountant.clone(),
+            zcdp_accountant: self.zcdp_accountant.clone(),
+            pacing: self.pacing.clone(),
+            pending_candidate: self.pending_candidate.clone(),
+            shuffled_participants: self.shuffled_participants.clone(),
+            round_diagnostics: self.round_diagnostics.clone(),
+            membership_reports: self.membership_reports.clone(),
+            membership_audits: self.membership_audits.clone(),
+            data_commitments: self.data_commitments.clone(),
+            deadline_outcome: self.deadline_outcome.clone(),
+        }
+    }
+
+    pub fn restore_state(&mut self, state: CoordinatorState) {
+        self.global_model = state.global_model;
+        self.round_history = state.round_history;
+        self.compacted_history = state.compacted_history;
+        self.weight_history = state.weight_history;
+        self.scaffold_state = state.scaffold_state;
+        self.feddyn_state = state.feddyn_state;
+        self.fedacg_state = state.fedacg_state;
+        self.foolsgold_state = state.foolsgold_state;
+        self.personalization_state = state.personalization_state;
+        self.clustering = state.clustering;
+        self.client_registry = state.client_registry;
+        self.opt_out_reports = state.opt_out_reports;
+        self.manifest_log = state.manifest_log;
+        self.rdp_accountant = state.rdp_accountant;
+        self.zcdp_accountant = state.zcdp_accountant;
+        self.pacing = state.pacing;
+        self.pending_candidate = state.pending_candidate;
+        self.shuffled_participants = state.shuffled_participants;
+        self.round_diagnostics = state.round_diagnostics;
+        self.membership_reports = state.membership_reports;
+        self.membership_audits = state.membership_audits;
+        self.data_commitments = state.data_commitments;
+        self.deadline_outcome = state.deadline_outcome;
+        if let Some(pacing) = &self.pacing {
+            self.config.local_epochs = pacing.decision.local_epochs;
+        }
     }
 
     pub fn get_privacy_report(&self) -> PrivacyReport {
@@ -580,14 +1540,19 @@ impl FederatedLearningCoordinator {
             privacy_budget_remaining: self.config.privacy_budget.total_epsilon - self.global_model.privacy_metrics.total_epsilon_used,
             client_privacy_usage: self.global_model.privacy_metrics.privacy_loss_per_client.clone(),
             rounds_remaining: self.estimate_remaining_rounds(),
+            sampling_rate: self.global_model.privacy_metrics.sampling_rate,
+            effective_epsilon_per_round: self.global_model.privacy_metrics.effective_round_epsilon,
         }
     }
 
     fn estimate_remaining_rounds(&self) -> u32 {
+        if let Some(rounds) = self.rdp_remaining_rounds().or_else(|| self.zcdp_remaining_rounds()) {
+            return rounds;
+        }
         let epsilon_per_round = if self.global_model.round > 0 {
             self.global_model.privacy_metrics.total_epsilon_used / self.global_model.round as f64
         } else {
-            self.config.privacy_budget.per_round_epsilon
+            amplified_epsilon(self.config.privacy_budget.per_round_epsilon, self.round_sampling_rate())
         };
         
         let remaining_epsilon = self.config.privacy_budget.total_epsilon - self.global_model.privacy_metrics.total_epsilon_used;
@@ -607,6 +1572,9 @@ pub struct PrivacyReport {
     pub privacy_budget_remaining: f64,
     pub client_privacy_usage: HashMap<String, f64>,
     pub rounds_remaining: u32,
+    // Sampling rate of the latest DP round and the ε it cost each client after amplification
+    pub sampling_rate: f64,
+    pub effective_epsilon_per_round: f64,
 }
 
 // Compression engine for communication efficiency
@@ -617,19 +1585,25 @@ impl CompressionEngine {
         CompressionEngine
     }
 
-    pub fn dequantize_updates(&self, mut updates: Vec<ModelUpdate>, bits: u8) -> Result<Vec<ModelUpdate>, String> {
-        let levels = 2_u32.pow(bits as u32) as f64;
+    pub fn dequantize_updates(&self, updates: Vec<ModelUpdate>, bits: u8) -> Result<Vec<ModelUpdate>, String> {
+        if bits == 0 || bits > 31 {
+            return Err(format!("Unsupported quantization width: {} bits", bits));
+        }
+        self.dequantize_levels(updates, 2_u32.pow(bits as u32))
+    }
+
+    pub fn dequantize_levels(&self, mut updates: Vec<ModelUpdate>, levels: u32) -> Result<Vec<ModelUpdate>, String> {
+        if levels < 2 {
+            return Err("Quantization needs at least 2 levels".to_string());
+        }
+        let levels = levels as f64;
         
         for update in &mut updates {
-            update.gradients = update.gradients
-                .iter()
-                .map(|&quantized| {
-                    // Dequantize from [0, levels-1] back to original range
-                    let normalized = quantized / (levels - 1.0);
-                    // Assuming original range was [-1, 1] for simplicity
-                    2.0 * normalized - 1.0
-                })
-                .collect();
+            // Dequantize from [0, levels-1] back to original range
+            // Assuming original range was [-1, 1] for simplicity
+            let mut dequantized = vec![0.0; update.gradients.len()];
+            kernels::affine(&update.gradients, 2.0 / (levels - 1.0), -1.0, &mut dequantized);
+            update.gradients = dequantized;
         }
         
         Ok(updates)
@@ -646,6 +1620,29 @@ impl CompressionEngine {
         // The rest are implicitly zero
         Ok(updates)
     }
+
+    pub fn decompress_ternary_updates(&self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
+        // TernGrad sends values in {-s, 0, s} with a per-update scale s, so the
+        // server only needs to check the update really is ternary
+        for update in &updates {
+            let scale = update.gradients.iter().fold(0.0_f64, |acc, &g| acc.max(g.abs()));
+            let ternary = update.gradients.iter().all(|&g| g == 0.0 || (g.abs() - scale).abs() <= 1e-9 * scale.max(1.0));
+            if !ternary {
+                return Err(format!("Update from {} is not ternary", update.client_id));
+            }
+        }
+        Ok(updates)
+    }
+
+    pub fn decompress_sign_updates(&self, updates: Vec<ModelUpdate>) -> Result<Vec<ModelUpdate>, String> {
+        // Clients send SignCompressor output unpacked to ±1
+        for update in &updates {
+            if update.gradients.iter().any(|&g| g != 1.0 && g != -1.0) {
+                return Err(format!("Update from {} is not sign-compressed", update.client_id));
+            }
+        }
+        Ok(updates)
+    }
 }
 
 // Aggregation engine for robust model updates
@@ -668,9 +1665,7 @@ impl AggregationEngine {
         
         for update in updates {
             let weight = update.data_size as f64 / total_weight;
-            for (i, &gradient) in update.gradients.iter().enumerate() {
-                aggregated[i] += weight * gradient;
-            }
+            kernels::axpy(weight, &update.gradients, &mut aggregated);
         }
         
         Ok(aggregated)
@@ -767,6 +1762,7 @@ impl AggregationEngine {
 }
 
 // Optimization engine for advanced FL algorithms
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct OptimizationEngine {
     momentum_buffer: HashMap<String, Vec<f64>>,
     adam_m: Vec<f64>,
@@ -854,10 +1850,23 @@ impl OptimizationEngine {
         Ok(optimized_weights)
     }
 
-    pub fn scaffold_optimization(&self, weights: Vec<f64>, _global_weights: &[f64]) -> Result<Vec<f64>, String> {
-        // SCAFFOLD requires client-side control variates
-        // This is a simplified version - full implementation requires more state
-        Ok(weights)
+    pub fn fedopt_optimization(&self, weights: Vec<f64>, server_learning_rate: f64, global_weights: &[f64]) -> Result<Vec<f64>, String> {
+        // Server SGD on the pseudo-gradient (aggregated weights - global weights)
+        if global_weights.len() != weights.len() {
+            return Ok(weights);
+        }
+
+        Ok(global_weights
+            .iter()
+            .zip(weights.iter())
+            .map(|(&global, &aggregated)| global + server_learning_rate * (aggregated - global))
+            .collect())
+    }
+
+    pub fn scaffold_optimization(&self, weights: Vec<f64>, global_learning_rate: f64, global_weights: &[f64]) -> Result<Vec<f64>, String> {
+        // x <- x + η_g * (mean(y_i) - x); drift correction happens in the clients'
+        // local steps using the control variates kept in ScaffoldState
+        self.fedopt_optimization(weights, global_learning_rate, global_weights)
     }
 }
 
@@ -874,22 +1883,30 @@ pub struct PerformanceBenchmark {
     pub privacy_budget_used: f64,
     pub compression_ratio: f64,
     pub memory_usage: u64,
+    // kWh, from the default device profile
     pub energy_consumption: f64,
+    pub carbon_kg_co2e: f64,
+    // How the result was produced, for audit and regeneration
+    pub manifest: ExperimentManifest,
 }
 
 pub fn benchmark_federated_algorithms(
     algorithms: Vec<FLAlgorithm>,
+    dataset: BenchmarkDataset,
     dataset_size: usize,
     num_clients: u32,
-) -> Vec<PerformanceBenchmark> {
+    seed: u64,
+) -> Result<Vec<PerformanceBenchmark>, String> {
     let mut benchmarks = Vec::new();
     
     for algorithm in algorithms {
         let config = FederatedLearningConfig {
             algorithm: algorithm.clone(),
             aggregation_method: AggregationMethod::FedAvg,
-            compression_method: CompressionMethod::Quantization { bits: 8 },
-            privacy_method: PrivacyMethod::DifferentialPrivacy { epsilon: 1.0, delta: 1e-5 },
+            compression_method: CompressionMethod::None,
+            privacy_method: PrivacyMethod::None,
+            // A logistic regression over the dataset's features
+            model_dimension: dataset.feature_count() + 1,
             learning_rate: 0.01,
             momentum: 0.9,
             weight_decay: 1e-4,
@@ -899,6 +1916,9 @@ pub fn benchmark_federated_algorithms(
             min_clients: 10,
             max_rounds: 100,
             convergence_threshold: 1e-4,
+            early_stopping: EarlyStoppingStrategy::default(),
+            lr_schedule: LearningRateSchedule::default(),
+            round_deadline_secs: None,
             privacy_budget: PrivacyBudget {
                 total_epsilon: 10.0,
                 total_delta: 1e-3,
@@ -914,48 +1934,54 @@ pub fn benchmark_federated_algorithms(
             },
         };
         
-        let benchmark = simulate_federated_learning(config, dataset_size, num_clients);
-        benchmarks.push(benchmark);
+        let manifest = ExperimentManifest::new(config, seed, DatasetStatistics::new(dataset.clone(), dataset_size, num_clients));
+        benchmarks.push(simulate_federated_learning(manifest)?);
     }
     
-    benchmarks
+    Ok(benchmarks)
 }
 
-fn simulate_federated_learning(
-    config: FederatedLearningConfig,
-    dataset_size: usize,
-    num_clients: u32,
-) -> PerformanceBenchmark {
-    // This is a simplified simulation - in practice would run actual FL training
-    let algorithm_name = format!("{:?}", config.algorithm);
-    
-    // Simulate performance based on algorithm characteristics
-    let (rounds_to_convergence, final_accuracy, communication_cost) = match config.algorithm {
-        FLAlgorithm::FedAvg => (50, 0.85, 1_000_000),
-        FLAlgorithm::FedProx { .. } => (45, 0.87, 1_100_000),
-        FLAlgorithm::FedAdam { .. } => (40, 0.89, 1_200_000),
-        FLAlgorithm::FedAvgM { .. } => (42, 0.88, 1_050_000),
-        FLAlgorithm::SCAFFOLD => (35, 0.91, 800_000),
-        _ => (50, 0.85, 1_000_000),
-    };
-    
-    PerformanceBenchmark {
-        algorithm: algorithm_name,
+// Trains the manifest's configuration on its dataset (see simulation.rs): a logistic
+// regression over the dataset's features, with the records split over the clients
+// with Dirichlet(0.5) label skew, until the early-stopping strategy fires or
+// `max_rounds` have run
+fn simulate_federated_learning(manifest: ExperimentManifest) -> Result<PerformanceBenchmark, String> {
+    let config = manifest.config.clone();
+    let (dataset_size, num_clients) = (manifest.dataset.dataset_size, manifest.dataset.num_clients);
+    if num_clients == 0 {
+        return Err("Simulation needs at least one client".to_string());
+    }
+    let simulation = manifest.dataset.source.simulation(SimulationConfig {
+        clients: num_clients as usize,
+        records_per_client: (dataset_size / num_clients as usize).max(1),
+        rounds: config.max_rounds as u64,
+        partition: DataPartition::Dirichlet { alpha: 0.5 },
+        model: SimulatedModel::Linear,
+        stop_when_converged: true,
+        seed: manifest.seed,
+        ..SimulationConfig::default()
+    })?;
+    let report = simulation.run(config.clone(), None)?;
+
+    Ok(PerformanceBenchmark {
+        algorithm: format!("{:?}", config.algorithm),
         dataset_size,
         num_clients,
-        rounds_to_convergence,
-        final_accuracy,
-        total_communication_cost: communication_cost,
-        total_computation_time: rounds_to_convergence as f64 * 10.0, // 10 seconds per round
-        privacy_budget_used: config.privacy_budget.total_epsilon * 0.8,
+        rounds_to_convergence: report.rounds_run as u32,
+        final_accuracy: report.final_accuracy,
+        total_communication_cost: report.bytes_transferred,
+        total_computation_time: report.computation_secs,
+        privacy_budget_used: report.epsilon_spent,
         compression_ratio: match config.compression_method {
             CompressionMethod::Quantization { bits } => 32.0 / bits as f64,
             CompressionMethod::Sparsification { sparsity_ratio } => 1.0 / (1.0 - sparsity_ratio),
             _ => 1.0,
         },
-        memory_usage: dataset_size as u64 * 4, // 4 bytes per float
-        energy_consumption: rounds_to_convergence as f64 * num_clients as f64 * 0.1, // 0.1 kWh per client per round
-    }
+        memory_usage: simulation.dataset_bytes(),
+        energy_consumption: report.energy_kwh,
+        carbon_kg_co2e: report.carbon_kg_co2e,
+        manifest,
+    })
 }
 
 // Cost analysis and optimization
@@ -968,6 +1994,10 @@ pub struct CostAnalysis {
     pub total_cost: f64,
     pub cost_per_accuracy_point: f64,
     pub cost_savings_vs_centralized: f64,
+    pub energy_kwh: f64,
+    pub carbon_kg_co2e: f64,
+    // Manifest of the benchmark the costs were derived from
+    pub manifest: ExperimentManifest,
 }
 
 pub fn analyze_federated_learning_costs(
@@ -1003,6 +2033,9 @@ pub fn analyze_federated_learning_costs(
         total_cost,
         cost_per_accuracy_point,
         cost_savings_vs_centralized,
+        energy_kwh: benchmark.energy_consumption,
+        carbon_kg_co2e: benchmark.carbon_kg_co2e,
+        manifest: benchmark.manifest.clone(),
     }
 }
 
@@ -1010,4 +2043,59 @@ pub fn analyze_federated_learning_costs(
 pub use compression::*;
 pub use aggregation::*;
 pub use optimization::*;
-pub use communication::*;
\ No newline at end of file
+pub use communication::*;
+pub use config::*;
+pub use features::*;
+pub use labels::*;
+pub use cross_validation::*;
+pub use drift::*;
+pub use checkpoint::*;
+pub use sketch::*;
+pub use clients::*;
+pub use buffers::*;
+pub use tuning::*;
+pub use analytics::*;
+pub use attestation::*;
+pub use integrity::*;
+pub use optout::*;
+pub use cohort::*;
+pub use clipping::*;
+pub use selection::*;
+pub use hierarchy::*;
+pub use personalization::*;
+pub use clustering::*;
+pub use split::*;
+pub use distillation::*;
+pub use multitask::*;
+pub use pretrained::*;
+pub use manifest::*;
+pub use layer_privacy::*;
+pub use dp_sgd::*;
+pub use scalar::*;
+pub use sparse::*;
+pub use experiment::*;
+pub use energy::*;
+pub use secure_aggregation::*;
+pub use artifact::*;
+pub use round_diff::*;
+pub use mpc::*;
+pub use rdp_accountant::*;
+pub use pacing::*;
+pub use amplification::*;
+pub use zcdp_accountant::*;
+pub use granularity::*;
+pub use shuffle::*;
+pub use backdoor::*;
+pub use inversion::*;
+pub use anomaly::*;
+pub use membership::*;
+pub use partial::*;
+pub use proofs::*;
+pub use quotes::*;
+pub use history::*;
+pub use simulation::*;
+pub use candidate::*;
+pub use early_stopping::*;
+pub use lr_schedule::*;
+pub use deadline::*;
+pub use openmetrics::*;
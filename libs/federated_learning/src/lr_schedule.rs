@@ -0,0 +1,118 @@
+// Schedules for the server learning rate, the step FedAdam and FedOpt take along the
+// round's pseudo-gradient. `learning_rate` in the configuration is the peak rate:
+// the first `warmup_rounds` rounds ramp up to it linearly, and later rounds follow
+// the decay, counted from the end of the warmup.
+//
+// - Constant: stays at the peak.
+// - Step: multiplies by `gamma` every `every_rounds` rounds.
+// - Cosine: anneals from the peak to `min_rate` over the rest of `max_rounds`, and
+//   stays at `min_rate` if training runs longer.
+//
+// The rate each round used is kept as `GlobalModel::server_learning_rate`.
+
+use crate::*;
+use std::f64::consts::PI;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum LearningRateDecay {
+    Constant,
+    Step { every_rounds: u64, gamma: f64 },
+    Cosine { min_rate: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LearningRateSchedule {
+    pub warmup_rounds: u64,
+    pub decay: LearningRateDecay,
+}
+
+impl Default for LearningRateSchedule {
+    fn default() -> Self {
+        LearningRateSchedule { warmup_rounds: 0, decay: LearningRateDecay::Constant }
+    }
+}
+
+impl LearningRateSchedule {
+    pub fn validate(&self, peak_rate: f64, max_rounds: u32) -> Result<(), String> {
+        if self.warmup_rounds > 0 && self.warmup_rounds >= max_rounds as u64 {
+            return Err(format!("Warmup of {} rounds leaves none of the {} rounds", self.warmup_rounds, max_rounds));
+        }
+        match self.decay {
+            LearningRateDecay::Constant => Ok(()),
+            LearningRateDecay::Step { every_rounds, gamma } => {
+                if every_rounds == 0 {
+                    return Err("Step decay needs a positive interval".to_string());
+                }
+                if !(gamma > 0.0 && gamma <= 1.0) {
+                    return Err("Step decay gamma must be in (0, 1]".to_string());
+                }
+                Ok(())
+            }
+            LearningRateDecay::Cosine { min_rate } => {
+                if !(0.0..=peak_rate).contains(&min_rate) {
+                    return Err(format!("Cosine min_rate must be in [0, {}]", peak_rate));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Rate for the round that starts after `round` completed rounds
+    pub fn rate(&self, peak_rate: f64, round: u64, max_rounds: u32) -> f64 {
+        if round < self.warmup_rounds {
+            return peak_rate * (round + 1) as f64 / self.warmup_rounds as f64;
+        }
+        let elapsed = round - self.warmup_rounds;
+        match self.decay {
+            LearningRateDecay::Constant => peak_rate,
+            LearningRateDecay::Step { every_rounds, gamma } => peak_rate * gamma.powi((elapsed / every_rounds) as i32),
+            LearningRateDecay::Cosine { min_rate } => {
+                let horizon = (max_rounds as u64).saturating_sub(self.warmup_rounds).max(1);
+                let progress = elapsed.min(horizon) as f64 / horizon as f64;
+                min_rate + (peak_rate - min_rate) * (1.0 + (PI * progress).cos()) / 2.0
+            }
+        }
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Server learning rate for the round in progress
+    pub fn server_learning_rate(&self) -> f64 {
+        self.config.lr_schedule.rate(self.config.learning_rate, self.global_model.round, self.config.max_rounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_then_decay() {
+        let step = LearningRateSchedule { warmup_rounds: 4, decay: LearningRateDecay::Step { every_rounds: 10, gamma: 0.5 } };
+        let rates: Vec<f64> = [0, 3, 4, 13, 14, 24].iter().map(|&round| step.rate(1.0, round, 100)).collect();
+        assert_eq!(rates, vec![0.25, 1.0, 1.0, 1.0, 0.5, 0.25]);
+
+        let cosine = LearningRateSchedule { warmup_rounds: 0, decay: LearningRateDecay::Cosine { min_rate: 0.1 } };
+        assert_eq!(cosine.rate(1.0, 0, 10), 1.0);
+        assert!((cosine.rate(1.0, 5, 10) - 0.55).abs() < 1e-12);
+        assert_eq!(cosine.rate(1.0, 50, 10), 0.1);
+        assert!(cosine.validate(0.05, 10).is_err());
+
+        // FedOpt steps with the scheduled rate and records it
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(1)
+            .min_clients(1)
+            .privacy_method(PrivacyMethod::None)
+            .algorithm(FLAlgorithm::FedOpt)
+            .learning_rate(1.0)
+            .lr_schedule(LearningRateSchedule { warmup_rounds: 2, decay: LearningRateDecay::Constant })
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let update = ModelUpdate { loss: 0.5, accuracy: 0.5, ..ModelUpdate::for_test("hospital_a", vec![1.0], 10) };
+        let model = coordinator.execute_round(vec![update]).unwrap();
+        assert_eq!(model.weights, vec![0.5]);
+        assert_eq!(coordinator.get_round_history()[0].server_learning_rate, Some(0.5));
+        assert_eq!(coordinator.server_learning_rate(), 1.0);
+    }
+}
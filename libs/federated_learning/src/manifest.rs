@@ -0,0 +1,191 @@
+// Data contribution manifests. Alongside each training extract a site produces a
+// manifest stating what went into it: how many records of each resource survived the
+// opt-out filter, a hash of the cohort definition (feature schema, label schema,
+// opt-out registry and deceased exclusion), the version of the de-identification
+// pipeline the data passed through and a quality score. The site signs the manifest
+// digest; the coordinator checks the signature with a pluggable verifier and keeps
+// accepted manifests in its audit log next to the round they were submitted for.
+
+use crate::*;
+use chrono::Utc;
+use medical_data::MedicalDataset;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ContributionManifest {
+    pub site_id: String,
+    pub round: u64,
+    // Resource type ("Patient", "Condition", ...) to records in the extract
+    pub record_counts: BTreeMap<String, u64>,
+    pub cohort_definition_hash: String,
+    pub deidentification_pipeline_version: String,
+    // Site-assessed data quality in [0, 1]
+    pub quality_score: f64,
+    pub generated_at: String,
+    // Site signature over `signing_digest`; empty until signed
+    pub signature: Vec<u8>,
+}
+
+// Site key management lives outside the crate: the verifier resolves the site's key
+// and checks the signature against the manifest's signing digest
+pub trait ManifestVerifier {
+    fn verify_manifest(&self, manifest: &ContributionManifest) -> Result<(), String>;
+}
+
+impl ContributionManifest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.site_id.is_empty() {
+            return Err("Manifest has no site".to_string());
+        }
+        if !(self.quality_score.is_finite() && (0.0..=1.0).contains(&self.quality_score)) {
+            return Err(format!("Manifest quality score {} is outside [0, 1]", self.quality_score));
+        }
+        if self.deidentification_pipeline_version.is_empty() {
+            return Err("Manifest does not name a de-identification pipeline version".to_string());
+        }
+        Ok(())
+    }
+
+    // Hex SHA-256 over every field except the signature, in a fixed order
+    pub fn signing_digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"fl-contribution-manifest");
+        for field in [&self.site_id, &self.cohort_definition_hash, &self.deidentification_pipeline_version, &self.generated_at] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update(self.round.to_be_bytes());
+        hasher.update(self.quality_score.to_be_bytes());
+        hasher.update((self.record_counts.len() as u64).to_be_bytes());
+        for (resource, count) in &self.record_counts {
+            hasher.update((resource.len() as u64).to_be_bytes());
+            hasher.update(resource.as_bytes());
+            hasher.update(count.to_be_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = signature;
+        self
+    }
+}
+
+impl CohortBuilder {
+    // Unsigned manifest for a cohort this builder produced from `dataset`
+    pub fn manifest(
+        &self,
+        site_id: &str,
+        round: u64,
+        dataset: &MedicalDataset,
+        cohort: &TrainingCohort,
+        deidentification_pipeline_version: &str,
+        quality_score: f64,
+    ) -> Result<ContributionManifest, String> {
+        let stats = &cohort.opt_out;
+        let record_counts = BTreeMap::from([
+            ("Patient".to_string(), (dataset.patients.len() as u64).saturating_sub(stats.patients_excluded)),
+            ("Condition".to_string(), (dataset.conditions.len() as u64).saturating_sub(stats.conditions_excluded)),
+            ("Observation".to_string(), (dataset.observations.len() as u64).saturating_sub(stats.observations_excluded)),
+            (
+                "DiagnosticReport".to_string(),
+                (dataset.diagnostic_reports.len() as u64).saturating_sub(stats.diagnostic_reports_excluded),
+            ),
+            ("RareDiseaseCase".to_string(), cohort.features.rows.len() as u64),
+        ]);
+        let manifest = ContributionManifest {
+            site_id: site_id.to_string(),
+            round,
+            record_counts,
+            cohort_definition_hash: self.definition_hash(),
+            deidentification_pipeline_version: deidentification_pipeline_version.to_string(),
+            quality_score,
+            generated_at: Utc::now().to_rfc3339(),
+            signature: Vec::new(),
+        };
+        manifest.validate()?;
+        Ok(manifest)
+    }
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_manifest_verifier(&mut self, verifier: Box<dyn ManifestVerifier>) {
+        self.manifest_verifier = Some(verifier);
+    }
+
+    // Manifests are audit records: one per site and round, never replaced, and only
+    // accepted with a signature the verifier vouches for
+    pub fn submit_manifest(&mut self, manifest: ContributionManifest) -> Result<(), String> {
+        if manifest.round != self.global_model.round {
+            return Err(format!("Manifest is for round {}, current round is {}", manifest.round, self.global_model.round));
+        }
+        manifest.validate()?;
+        if self.manifest_log.iter().any(|m| m.site_id == manifest.site_id && m.round == manifest.round) {
+            return Err(format!("Site {} already submitted a manifest for round {}", manifest.site_id, manifest.round));
+        }
+        if manifest.signature.is_empty() {
+            return Err(format!("Manifest from site {} is not signed", manifest.site_id));
+        }
+        match &self.manifest_verifier {
+            Some(verifier) => verifier.verify_manifest(&manifest)?,
+            None => return Err("No manifest verifier configured".to_string()),
+        }
+        self.manifest_log.push(manifest);
+        Ok(())
+    }
+
+    pub fn get_manifests(&self, round: u64) -> Vec<ContributionManifest> {
+        self.manifest_log.iter().filter(|m| m.round == round).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::*;
+
+    // Stands in for a real signature scheme: the signature is the digest itself
+    struct DigestVerifier;
+
+    impl ManifestVerifier for DigestVerifier {
+        fn verify_manifest(&self, manifest: &ContributionManifest) -> Result<(), String> {
+            if manifest.signature == manifest.signing_digest().into_bytes() {
+                Ok(())
+            } else {
+                Err(format!("Bad signature from site {}", manifest.site_id))
+            }
+        }
+    }
+
+    #[test]
+    fn test_signed_manifest_is_verified_and_logged() {
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        for id in ["p1", "p2", "p3"] {
+            dataset.patients.push(Patient::new(id.to_string()));
+        }
+        let mut registry = OptOutRegistry::new("salt");
+        registry.insert_patient("p2");
+        let builder = CohortBuilder::new(FeatureSchema::new(2024), LabelSchema::diagnosed_vs_undiagnosed(), registry);
+        let cohort = builder.build(&[], &dataset).unwrap();
+        let manifest = builder.manifest("site-a", 0, &dataset, &cohort, "deid-2.1", 0.9).unwrap();
+        assert_eq!(manifest.record_counts["Patient"], 2);
+        assert!(builder.manifest("site-a", 0, &dataset, &cohort, "deid-2.1", 1.5).is_err());
+        let other = CohortBuilder::new(FeatureSchema::new(2024), LabelSchema::diagnosed_vs_undiagnosed(), OptOutRegistry::new("salt"));
+        assert_ne!(other.definition_hash(), manifest.cohort_definition_hash);
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let signed = manifest.clone().with_signature(manifest.signing_digest().into_bytes());
+        assert!(coordinator.submit_manifest(signed.clone()).is_err());
+        coordinator.set_manifest_verifier(Box::new(DigestVerifier));
+
+        let mut tampered = signed.clone();
+        tampered.quality_score = 1.0;
+        assert!(coordinator.submit_manifest(tampered).is_err());
+        coordinator.submit_manifest(signed.clone()).unwrap();
+        assert!(coordinator.submit_manifest(signed.clone()).is_err());
+        assert_eq!(coordinator.get_manifests(0), vec![signed]);
+        assert_eq!(coordinator.export_state().manifest_log.len(), 1);
+    }
+}
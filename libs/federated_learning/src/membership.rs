@@ -0,0 +1,185 @@
+// Membership inference audit of the released model. A model leaks who was in its
+// training data when it fits its training records noticeably better than unseen
+// ones: the loss-threshold attack (Yeom et al., 2018) guesses "member" whenever a
+// record's loss is below a threshold, and shadow-model attacks (Shokri et al., 2017)
+// in effect learn that threshold from models trained on data of known membership.
+// The coordinator holds no records, so every `every_rounds` rounds the sites evaluate
+// the current global model on records it was trained on and on held-out records of
+// the same population, and report the per-record losses; no records leave the site.
+//
+// Over the pooled losses, the attack's AUC across all thresholds is the chance that a
+// random member has a lower loss than a random non-member (Mann-Whitney, ties count
+// half), and its advantage is the best TPR - FPR of any single threshold, which is
+// what a shadow-calibrated threshold can at most achieve. The resistance published in
+// `PrivacyMetrics::membership_inference_resistance` is 1 - 2·|AUC - 0.5|: 1 when the
+// attack does no better than chance, 0 when it separates members perfectly. It stays
+// at its last measured value between audits.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MembershipAuditPolicy {
+    pub every_rounds: u64,
+    // Members and non-members each needed before an audit is published
+    pub min_records: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MembershipLossReport {
+    pub client_id: String,
+    // The global model round that was evaluated
+    pub round: u64,
+    pub member_losses: Vec<f64>,
+    pub non_member_losses: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MembershipAudit {
+    pub round: u64,
+    pub sites: usize,
+    pub members: usize,
+    pub non_members: usize,
+    pub auc: f64,
+    pub advantage: f64,
+    pub resistance: f64,
+}
+
+impl Default for MembershipAuditPolicy {
+    fn default() -> Self {
+        MembershipAuditPolicy { every_rounds: 5, min_records: 100 }
+    }
+}
+
+impl MembershipAuditPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.every_rounds == 0 {
+            return Err("Membership audits need a positive interval".to_string());
+        }
+        if self.min_records == 0 {
+            return Err("Membership audits need at least one record of each kind".to_string());
+        }
+        Ok(())
+    }
+}
+
+// AUC and advantage of the loss-threshold attack; lower loss means "member"
+pub fn loss_threshold_attack(member_losses: &[f64], non_member_losses: &[f64]) -> (f64, f64) {
+    if member_losses.is_empty() || non_member_losses.is_empty() {
+        return (0.5, 0.0);
+    }
+    let mut records: Vec<(f64, bool)> =
+        member_losses.iter().map(|&l| (l, true)).chain(non_member_losses.iter().map(|&l| (l, false))).collect();
+    records.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let (members, non_members) = (member_losses.len() as f64, non_member_losses.len() as f64);
+    let (mut below_members, mut below_non_members) = (0.0, 0.0);
+    let (mut pairs_won, mut advantage) = (0.0, 0.0f64);
+    let mut start = 0;
+    while start < records.len() {
+        // A group of equal losses falls on the same side of every threshold
+        let end = start + records[start..].iter().take_while(|r| r.0 == records[start].0).count();
+        let tied_members = records[start..end].iter().filter(|r| r.1).count() as f64;
+        let tied_non_members = (end - start) as f64 - tied_members;
+        pairs_won += tied_members * (non_members - below_non_members - tied_non_members / 2.0);
+        below_members += tied_members;
+        below_non_members += tied_non_members;
+        advantage = advantage.max(below_members / members - below_non_members / non_members);
+        start = end;
+    }
+    (pairs_won / (members * non_members), advantage)
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_membership_audit_policy(&mut self, policy: Option<MembershipAuditPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.membership_policy = policy;
+        self.membership_reports.clear();
+        Ok(())
+    }
+
+    // Whether sites should evaluate the current global model for an audit
+    pub fn membership_audit_due(&self) -> bool {
+        let round = self.global_model.round;
+        self.membership_policy.as_ref().is_some_and(|p| round > 0 && round.is_multiple_of(p.every_rounds))
+    }
+
+    pub fn get_membership_audits(&self) -> &[MembershipAudit] {
+        &self.membership_audits
+    }
+
+    // Pools a site's losses; once both kinds reach `min_records` the audit for the
+    // round is (re)computed over everything reported so far and published
+    pub fn submit_membership_losses(&mut self, report: MembershipLossReport) -> Result<Option<MembershipAudit>, String> {
+        if !self.membership_audit_due() {
+            return Err(format!("No membership audit is due for round {}", self.global_model.round));
+        }
+        if report.round != self.global_model.round {
+            return Err(format!("Losses are for round {}, current round is {}", report.round, self.global_model.round));
+        }
+        if !self.is_client_authorized(&report.client_id) {
+            return Err(format!("Client {} is not authorized", report.client_id));
+        }
+        if report.member_losses.iter().chain(&report.non_member_losses).any(|l| !l.is_finite() || *l < 0.0) {
+            return Err("Losses must be finite and non-negative".to_string());
+        }
+        self.membership_reports.retain(|r| r.client_id != report.client_id);
+        self.membership_reports.push(report);
+
+        let members: Vec<f64> = self.membership_reports.iter().flat_map(|r| r.member_losses.iter().copied()).collect();
+        let non_members: Vec<f64> = self.membership_reports.iter().flat_map(|r| r.non_member_losses.iter().copied()).collect();
+        let min_records = self.membership_policy.as_ref().map_or(0, |p| p.min_records);
+        if members.len() < min_records || non_members.len() < min_records {
+            return Ok(None);
+        }
+
+        let (auc, advantage) = loss_threshold_attack(&members, &non_members);
+        let audit = MembershipAudit {
+            round: self.global_model.round,
+            sites: self.membership_reports.len(),
+            members: members.len(),
+            non_members: non_members.len(),
+            auc,
+            advantage,
+            resistance: 1.0 - 2.0 * (auc - 0.5).abs(),
+        };
+        self.membership_audits.retain(|a| a.round != audit.round);
+        self.membership_audits.push(audit.clone());
+        self.global_model.privacy_metrics.membership_inference_resistance = audit.resistance;
+        Ok(Some(audit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loss_threshold_attack_is_measured_and_published() {
+        // Perfect separation, no separation, and ties counting half
+        assert_eq!(loss_threshold_attack(&[0.1, 0.2], &[0.5, 0.9]), (1.0, 1.0));
+        assert_eq!(loss_threshold_attack(&[0.3, 0.3], &[0.3, 0.3]), (0.5, 0.0));
+        let (auc, advantage) = loss_threshold_attack(&[0.1, 0.4, 0.6], &[0.2, 0.5, 0.8]);
+        assert!((auc - 6.0 / 9.0).abs() < 1e-12 && (advantage - 1.0 / 3.0).abs() < 1e-12);
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.set_membership_audit_policy(Some(MembershipAuditPolicy { every_rounds: 1, min_records: 4 })).unwrap();
+        coordinator.global_model.round = 3;
+        let report = |client_id: &str, member_losses: Vec<f64>, non_member_losses: Vec<f64>| MembershipLossReport {
+            client_id: client_id.to_string(),
+            round: 3,
+            member_losses,
+            non_member_losses,
+        };
+
+        // One site is not enough records; the second completes the pool
+        assert_eq!(coordinator.submit_membership_losses(report("site_a", vec![0.1, 0.2], vec![0.9, 1.1])).unwrap(), None);
+        let audit = coordinator.submit_membership_losses(report("site_b", vec![0.3, 1.0], vec![0.4, 1.2])).unwrap().unwrap();
+        assert_eq!((audit.sites, audit.members, audit.non_members), (2, 4, 4));
+        assert!(audit.auc > 0.5 && audit.resistance < 1.0);
+        assert_eq!(coordinator.get_global_model().privacy_metrics.membership_inference_resistance, audit.resistance);
+        assert!(coordinator.submit_membership_losses(MembershipLossReport { round: 2, ..report("site_a", vec![], vec![]) }).is_err());
+    }
+}
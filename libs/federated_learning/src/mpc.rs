@@ -0,0 +1,389 @@
+// Multi-party computation aggregation: each client splits its weighted update into
+// secret shares, one per aggregator instance, and the instances only ever add shares.
+// No instance sees anything but uniformly random field elements; the coordinator
+// recombines the instances' sums into the aggregate and nothing else, since no
+// individual client's shares ever leave the instances.
+//
+// Two schemes are supported. Additive shares sum to the input and need every
+// aggregator to answer; Shamir shares are points on a random polynomial of degree
+// t - 1, so any t of n aggregators reconstruct the sum and up to t - 1 colluding
+// instances learn nothing.
+//
+// Verification happens twice. A client publishes a salted SHA-256 commitment to each
+// of its shares through the coordinator, and an aggregator rejects a share that does
+// not open its commitment, so neither a client nor a relay can swap a share after the
+// fact. When more than t Shamir aggregate shares arrive, the extra ones must lie on the
+// polynomial through the first t; a share that does not points to a faulty aggregator
+// or an inconsistently shared input, and the round is refused rather than skewed.
+//
+// Arithmetic is in the prime field of 2^61 - 1, with values in fixed point with 20
+// fractional bits, so sums must stay below 2^40 in magnitude.
+
+use crate::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+const FIELD_PRIME: u64 = (1 << 61) - 1;
+const FIXED_POINT_SCALE: f64 = (1u64 << 20) as f64;
+const MAX_INPUT_MAGNITUDE: f64 = (1u64 << 36) as f64;
+const SALT_BYTES: usize = 16;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum MpcScheme {
+    Additive { parties: u32 },
+    Shamir { parties: u32, threshold: u32 },
+}
+
+// One aggregator's share of one client's input, x = party
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MpcShare {
+    pub client_id: String,
+    pub round: u64,
+    pub party: u32,
+    pub data_size: usize,
+    pub salt: Vec<u8>,
+    pub values: Vec<u64>,
+}
+
+// Published by the client through the coordinator; one digest per party
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShareCommitment {
+    pub client_id: String,
+    pub round: u64,
+    pub data_size: usize,
+    pub digests: Vec<Vec<u8>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MpcSharedInput {
+    pub commitment: ShareCommitment,
+    pub shares: Vec<MpcShare>,
+}
+
+// An aggregator's share of the sum over the announced participants
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MpcAggregateShare {
+    pub party: u32,
+    pub round: u64,
+    pub participants: Vec<SecureParticipant>,
+    pub values: Vec<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MpcAggregator {
+    party: u32,
+    round: u64,
+    dimension: usize,
+    received: BTreeMap<String, MpcShare>,
+}
+
+fn field_add(a: u64, b: u64) -> u64 {
+    (a + b) % FIELD_PRIME
+}
+
+fn field_sub(a: u64, b: u64) -> u64 {
+    (a + FIELD_PRIME - b) % FIELD_PRIME
+}
+
+fn field_mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % FIELD_PRIME as u128) as u64
+}
+
+fn field_inv(a: u64) -> u64 {
+    // Fermat: a^(p - 2)
+    let (mut result, mut base, mut exponent) = (1, a, FIELD_PRIME - 2);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = field_mul(result, base);
+        }
+        base = field_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn encode(value: f64) -> Result<u64, String> {
+    if !(value.is_finite() && value.abs() < MAX_INPUT_MAGNITUDE) {
+        return Err(format!("Value {} cannot be encoded for MPC aggregation", value));
+    }
+    Ok(((value * FIXED_POINT_SCALE).round() as i64).rem_euclid(FIELD_PRIME as i64) as u64)
+}
+
+fn decode(value: u64) -> f64 {
+    // The upper half of the field holds negative values
+    let signed = if value > FIELD_PRIME / 2 { value as i64 - FIELD_PRIME as i64 } else { value as i64 };
+    signed as f64 / FIXED_POINT_SCALE
+}
+
+// Lagrange interpolation of the points (x_i, y_i) at `at`
+fn interpolate(points: &[(u64, &[u64])], at: u64, len: usize) -> Vec<u64> {
+    let mut result = vec![0; len];
+    for (i, (xi, values)) in points.iter().enumerate() {
+        let (mut numerator, mut denominator) = (1, 1);
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i != j {
+                numerator = field_mul(numerator, field_sub(at, *xj));
+                denominator = field_mul(denominator, field_sub(*xi, *xj));
+            }
+        }
+        let basis = field_mul(numerator, field_inv(denominator));
+        result.iter_mut().zip(values.iter()).for_each(|(r, &v)| *r = field_add(*r, field_mul(basis, v)));
+    }
+    result
+}
+
+fn share_digest(share: &MpcShare) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(share.client_id.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(share.round.to_le_bytes());
+    hasher.update(share.party.to_le_bytes());
+    hasher.update((share.data_size as u64).to_le_bytes());
+    hasher.update(&share.salt);
+    share.values.iter().for_each(|v| hasher.update(v.to_le_bytes()));
+    hasher.finalize().to_vec()
+}
+
+impl MpcScheme {
+    pub fn parties(&self) -> u32 {
+        match self {
+            MpcScheme::Additive { parties } | MpcScheme::Shamir { parties, .. } => *parties,
+        }
+    }
+
+    // Aggregate shares needed to reconstruct the sum
+    pub fn threshold(&self) -> u32 {
+        match self {
+            MpcScheme::Additive { parties } => *parties,
+            MpcScheme::Shamir { threshold, .. } => *threshold,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.parties() < 2 {
+            return Err("MPC aggregation needs at least two aggregators".to_string());
+        }
+        if let MpcScheme::Shamir { parties, threshold } = self {
+            if *threshold < 2 || threshold > parties {
+                return Err(format!("Shamir threshold {} must be in [2, {}]", threshold, parties));
+            }
+        }
+        Ok(())
+    }
+
+    // Splits `values` into one share per party and commits to each
+    pub fn share_input(&self, client_id: &str, round: u64, values: &[f64], data_size: usize) -> Result<MpcSharedInput, String> {
+        self.validate()?;
+        let encoded = values.iter().map(|&v| encode(v)).collect::<Result<Vec<u64>, String>>()?;
+        let mut rng = rand::thread_rng();
+        let parties = self.parties() as usize;
+        let mut party_values = vec![Vec::with_capacity(encoded.len()); parties];
+        for &secret in &encoded {
+            match self {
+                MpcScheme::Additive { .. } => {
+                    let mut remainder = secret;
+                    for shares in party_values.iter_mut().take(parties - 1) {
+                        let share = rng.gen_range(0..FIELD_PRIME);
+                        remainder = field_sub(remainder, share);
+                        shares.push(share);
+                    }
+                    party_values[parties - 1].push(remainder);
+                }
+                MpcScheme::Shamir { threshold, .. } => {
+                    let coefficients: Vec<u64> = (1..*threshold).map(|_| rng.gen_range(0..FIELD_PRIME)).collect();
+                    for (x, shares) in party_values.iter_mut().enumerate() {
+                        // Horner's rule, highest coefficient first
+                        let x = x as u64 + 1;
+                        let value = coefficients.iter().rev().fold(0, |acc, &c| field_add(field_mul(acc, x), c));
+                        shares.push(field_add(field_mul(value, x), secret));
+                    }
+                }
+            }
+        }
+
+        let shares: Vec<MpcShare> = party_values
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let mut salt = vec![0u8; SALT_BYTES];
+                rng.fill(salt.as_mut_slice());
+                MpcShare { client_id: client_id.to_string(), round, party: i as u32 + 1, data_size, salt, values }
+            })
+            .collect();
+        let commitment = ShareCommitment {
+            client_id: client_id.to_string(),
+            round,
+            data_size,
+            digests: shares.iter().map(share_digest).collect(),
+        };
+        Ok(MpcSharedInput { commitment, shares })
+    }
+
+    // Weighted gradients, loss and accuracy of `update`, in the layout
+    // `execute_secure_round` expects
+    pub fn share_update(&self, update: &ModelUpdate) -> Result<MpcSharedInput, String> {
+        let weight = update.data_size as f64;
+        let mut values: Vec<f64> = update.gradients.iter().map(|g| g * weight).collect();
+        values.push(update.loss * weight);
+        values.push(update.accuracy * weight);
+        self.share_input(&update.client_id, update.round, &values, update.data_size)
+    }
+
+    // Recombines aggregator shares into the sum they share, checking any shares beyond
+    // the threshold against it
+    pub fn reconstruct(&self, round: u64, shares: &[MpcAggregateShare]) -> Result<SecureAggregate, String> {
+        self.validate()?;
+        let first = shares.first().ok_or("No aggregate shares to reconstruct")?;
+        let parties: BTreeSet<u32> = shares.iter().map(|s| s.party).collect();
+        if parties.len() != shares.len() || parties.iter().any(|p| *p == 0 || *p > self.parties()) {
+            return Err("Aggregate shares must come from distinct aggregators of this scheme".to_string());
+        }
+        if shares.len() < self.threshold() as usize {
+            return Err(format!("{} aggregate shares, {} needed", shares.len(), self.threshold()));
+        }
+        let ids = |s: &MpcAggregateShare| s.participants.iter().map(|p| (p.client_id.clone(), p.data_size)).collect::<Vec<_>>();
+        if shares.iter().any(|s| s.round != round || ids(s) != ids(first) || s.values.len() != first.values.len()) {
+            return Err("Aggregate shares disagree on the round, participants or dimension".to_string());
+        }
+
+        let len = first.values.len();
+        let sum = match self {
+            MpcScheme::Additive { .. } => shares.iter().fold(vec![0; len], |mut acc, s| {
+                acc.iter_mut().zip(&s.values).for_each(|(a, &v)| *a = field_add(*a, v));
+                acc
+            }),
+            MpcScheme::Shamir { threshold, .. } => {
+                let points: Vec<(u64, &[u64])> = shares.iter().map(|s| (s.party as u64, s.values.as_slice())).collect();
+                let (basis, extra) = points.split_at(*threshold as usize);
+                for (x, values) in extra {
+                    if interpolate(basis, *x, len) != *values {
+                        return Err(format!("Aggregate share of aggregator {} is inconsistent with the others", x));
+                    }
+                }
+                interpolate(basis, 0, len)
+            }
+        };
+        Ok(SecureAggregate { round, participants: first.participants.clone(), sum: sum.into_iter().map(decode).collect() })
+    }
+}
+
+impl MpcAggregator {
+    pub fn new(party: u32, round: u64, dimension: usize) -> Self {
+        MpcAggregator { party, round, dimension, received: BTreeMap::new() }
+    }
+
+    // Accepts a client's share if it opens the client's published commitment
+    pub fn receive(&mut self, share: MpcShare, commitment: &ShareCommitment) -> Result<(), String> {
+        if share.party != self.party || share.round != self.round {
+            return Err(format!("Share is for aggregator {} in round {}", share.party, share.round));
+        }
+        if share.values.len() != self.dimension {
+            return Err(format!("Share has {} values, expected {}", share.values.len(), self.dimension));
+        }
+        if commitment.client_id != share.client_id || commitment.round != share.round || commitment.data_size != share.data_size {
+            return Err(format!("Commitment does not belong to the share from {}", share.client_id));
+        }
+        let expected = commitment.digests.get(self.party as usize - 1).ok_or("Commitment has no digest for this aggregator")?;
+        if share_digest(&share) != *expected {
+            return Err(format!("Share from {} does not match its commitment", share.client_id));
+        }
+        if self.received.contains_key(&share.client_id) {
+            return Err(format!("Duplicate share from {}", share.client_id));
+        }
+        self.received.insert(share.client_id.clone(), share);
+        Ok(())
+    }
+
+    pub fn received_clients(&self) -> Vec<String> {
+        self.received.keys().cloned().collect()
+    }
+
+    // Sum of the shares of `participants`, the clients every answering aggregator
+    // received; no single client's share is ever released
+    pub fn aggregate(&self, participants: &[String]) -> Result<MpcAggregateShare, String> {
+        if participants.len() < 2 {
+            return Err("An aggregate of fewer than two clients would reveal an input".to_string());
+        }
+        let mut values = vec![0; self.dimension];
+        let mut included = Vec::with_capacity(participants.len());
+        for client_id in participants.iter().collect::<BTreeSet<_>>() {
+            let share = self.received.get(client_id).ok_or_else(|| format!("Aggregator {} has no share from {}", self.party, client_id))?;
+            values.iter_mut().zip(&share.values).for_each(|(v, &s)| *v = field_add(*v, s));
+            included.push(SecureParticipant { client_id: client_id.clone(), data_size: share.data_size });
+        }
+        Ok(MpcAggregateShare { party: self.party, round: self.round, participants: included, values })
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Completes a round from the aggregators' shares of the sum of `share_update` inputs
+    pub fn execute_mpc_round(&mut self, shares: Vec<MpcAggregateShare>) -> Result<GlobalModel, String> {
+        let scheme = match &self.config.privacy_method {
+            PrivacyMethod::MultiPartyComputation { scheme } => scheme.clone(),
+            _ => return Err("MPC rounds need the MultiPartyComputation privacy method".to_string()),
+        };
+        let aggregate = scheme.reconstruct(self.global_model.round, &shares)?;
+        self.execute_secure_round(aggregate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(scheme: &MpcScheme, inputs: &[(&str, Vec<f64>, usize)], answering: &[u32]) -> Vec<MpcAggregateShare> {
+        let mut aggregators: Vec<MpcAggregator> = answering.iter().map(|&p| MpcAggregator::new(p, 0, inputs[0].1.len())).collect();
+        for (client_id, values, data_size) in inputs {
+            let shared = scheme.share_input(client_id, 0, values, *data_size).unwrap();
+            for aggregator in &mut aggregators {
+                let share = shared.shares[aggregator.party as usize - 1].clone();
+                aggregator.receive(share, &shared.commitment).unwrap();
+            }
+        }
+        let participants = aggregators[0].received_clients();
+        aggregators.iter().map(|a| a.aggregate(&participants).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_aggregators_reconstruct_only_the_sum() {
+        let inputs = [("hospital_a", vec![1.5, -2.0], 1), ("hospital_b", vec![0.25, 4.0], 3), ("hospital_c", vec![-3.0, 1.0], 2)];
+        let close = |sum: &[f64]| (sum[0] - -1.25).abs() < 1e-5 && (sum[1] - 3.0).abs() < 1e-5;
+
+        let additive = MpcScheme::Additive { parties: 3 };
+        let shares = run(&additive, &inputs, &[1, 2, 3]);
+        assert!(close(&additive.reconstruct(0, &shares).unwrap().sum));
+        assert!(additive.reconstruct(0, &shares[..2]).is_err());
+
+        // Any 3 of 5 Shamir aggregators suffice; a fourth is checked against them
+        let shamir = MpcScheme::Shamir { parties: 5, threshold: 3 };
+        let mut shares = run(&shamir, &inputs, &[2, 3, 4, 5]);
+        let aggregate = shamir.reconstruct(0, &shares[1..]).unwrap();
+        assert!(close(&aggregate.sum) && aggregate.participants.len() == 3);
+        assert!(close(&shamir.reconstruct(0, &shares).unwrap().sum));
+        shares[3].values[0] = field_add(shares[3].values[0], 1);
+        assert!(shamir.reconstruct(0, &shares).is_err());
+        assert!(MpcScheme::Shamir { parties: 3, threshold: 1 }.validate().is_err());
+
+        // A share swapped after commitment is rejected
+        let shared = shamir.share_input("hospital_a", 0, &[1.0, 2.0], 1).unwrap();
+        let mut tampered = shared.shares[0].clone();
+        tampered.values[1] = field_add(tampered.values[1], 1);
+        assert!(MpcAggregator::new(1, 0, 2).receive(tampered, &shared.commitment).is_err());
+
+        let config = FederatedLearningConfig::builder()
+            .privacy_method(PrivacyMethod::MultiPartyComputation { scheme: MpcScheme::Shamir { parties: 3, threshold: 2 } })
+            .model_dimension(1)
+            .min_clients(2)
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let scheme = MpcScheme::Shamir { parties: 3, threshold: 2 };
+        // n * (gradient, loss, accuracy) per client
+        let inputs = [("hospital_a", vec![2.0, 0.5, 0.8], 1), ("hospital_b", vec![-6.0, 1.5, 2.4], 3)];
+        let shares = run(&scheme, &inputs, &[1, 3]);
+        let model = coordinator.execute_mpc_round(shares).unwrap();
+        assert!((model.weights[0] - -1.0).abs() < 1e-5);
+        assert!((model.global_accuracy - 0.8).abs() < 1e-5);
+    }
+}
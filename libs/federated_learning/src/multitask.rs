@@ -0,0 +1,245 @@
+// Multi-task federated learning for the rare disease classifiers. Every task's model
+// is a shared representation (the leading weights) followed by a task-specific head.
+// A site trains the tasks it has labels for and submits one update per task. The
+// shared representation is averaged over every update of the round, whatever the
+// task, weighted by data size; each head is averaged only over updates for its own
+// task. Convergence is tracked per task, since a task with few sites converges on
+// its own schedule.
+
+use crate::*;
+use std::collections::BTreeMap;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TaskUpdate {
+    pub client_id: String,
+    pub task_id: String,
+    pub round: u64,
+    pub shared: Vec<f64>,
+    pub head: Vec<f64>,
+    pub data_size: usize,
+    pub loss: f64,
+    pub accuracy: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TaskMetrics {
+    pub task_id: String,
+    pub round: u64,
+    pub participating_clients: Vec<String>,
+    pub samples: u64,
+    pub loss: f64,
+    pub accuracy: f64,
+    // Relative to the task's previous aggregated round; 0 on its first
+    pub loss_improvement: f64,
+    pub accuracy_improvement: f64,
+    pub head_change_norm: f64,
+    pub converged: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MultiTaskRound {
+    pub round: u64,
+    pub shared_change_norm: f64,
+    // Tasks that received updates this round
+    pub tasks: Vec<TaskMetrics>,
+}
+
+pub struct MultiTaskCoordinator {
+    round: u64,
+    shared: Vec<f64>,
+    heads: BTreeMap<String, Vec<f64>>,
+    // A task has converged once its loss moves by less than this between rounds
+    convergence_threshold: f64,
+    // Latest update per (client, task) for the open round
+    pending: HashMap<(String, String), TaskUpdate>,
+    metrics: HashMap<String, TaskMetrics>,
+    history: Vec<MultiTaskRound>,
+}
+
+fn weighted_mean<'a>(vectors: impl Iterator<Item = (&'a [f64], f64)>, len: usize) -> Vec<f64> {
+    let mut mean = vec![0.0; len];
+    let mut total = 0.0;
+    for (vector, weight) in vectors {
+        kernels::axpy(weight, vector, &mut mean);
+        total += weight;
+    }
+    mean.iter_mut().for_each(|m| *m /= total);
+    mean
+}
+
+fn change_norm(before: &[f64], after: &[f64]) -> f64 {
+    let diff: Vec<f64> = after.iter().zip(before.iter()).map(|(a, b)| a - b).collect();
+    kernels::l2_norm(&diff)
+}
+
+impl MultiTaskCoordinator {
+    pub fn new(shared: Vec<f64>, convergence_threshold: f64) -> Result<Self, String> {
+        if shared.is_empty() {
+            return Err("Shared representation cannot be empty".to_string());
+        }
+        if !(convergence_threshold.is_finite() && convergence_threshold > 0.0) {
+            return Err("Convergence threshold must be positive".to_string());
+        }
+        Ok(MultiTaskCoordinator {
+            round: 0,
+            shared,
+            heads: BTreeMap::new(),
+            convergence_threshold,
+            pending: HashMap::new(),
+            metrics: HashMap::new(),
+            history: Vec::new(),
+        })
+    }
+
+    pub fn add_task(&mut self, task_id: &str, head: Vec<f64>) -> Result<(), String> {
+        if head.is_empty() {
+            return Err(format!("Task {} head cannot be empty", task_id));
+        }
+        if self.heads.contains_key(task_id) {
+            return Err(format!("Task {} already exists", task_id));
+        }
+        self.heads.insert(task_id.to_string(), head);
+        Ok(())
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn tasks(&self) -> Vec<String> {
+        self.heads.keys().cloned().collect()
+    }
+
+    pub fn shared_weights(&self) -> &[f64] {
+        &self.shared
+    }
+
+    // Shared representation followed by the task's head
+    pub fn task_weights(&self, task_id: &str) -> Option<Vec<f64>> {
+        self.heads.get(task_id).map(|head| [self.shared.as_slice(), head.as_slice()].concat())
+    }
+
+    pub fn task_metrics(&self, task_id: &str) -> Option<&TaskMetrics> {
+        self.metrics.get(task_id)
+    }
+
+    pub fn all_converged(&self) -> bool {
+        self.heads.keys().all(|t| self.metrics.get(t).is_some_and(|m| m.converged))
+    }
+
+    pub fn get_history(&self) -> &[MultiTaskRound] {
+        &self.history
+    }
+
+    pub fn submit_update(&mut self, update: TaskUpdate) -> Result<(), String> {
+        let head = self.heads.get(&update.task_id).ok_or(format!("Unknown task {}", update.task_id))?;
+        if update.round != self.round {
+            return Err(format!("Update is for round {}, current round is {}", update.round, self.round));
+        }
+        if update.shared.len() != self.shared.len() || update.head.len() != head.len() {
+            return Err(format!("Update shape does not match task {}", update.task_id));
+        }
+        if update.shared.iter().chain(update.head.iter()).any(|w| !w.is_finite()) {
+            return Err("Update weights must be finite".to_string());
+        }
+        if update.data_size == 0 {
+            return Err("data_size must be positive".to_string());
+        }
+        self.pending.insert((update.client_id.clone(), update.task_id.clone()), update);
+        Ok(())
+    }
+
+    pub fn aggregate_round(&mut self) -> Result<MultiTaskRound, String> {
+        if self.pending.is_empty() {
+            return Err(format!("No task updates received for round {}", self.round));
+        }
+        let mut updates: Vec<TaskUpdate> = self.pending.drain().map(|(_, u)| u).collect();
+        updates.sort_by(|a, b| (&a.task_id, &a.client_id).cmp(&(&b.task_id, &b.client_id)));
+
+        let shared = weighted_mean(updates.iter().map(|u| (u.shared.as_slice(), u.data_size as f64)), self.shared.len());
+        let shared_change_norm = change_norm(&self.shared, &shared);
+        self.shared = shared;
+
+        let mut tasks = Vec::new();
+        for (task_id, head) in self.heads.iter_mut() {
+            let members: Vec<&TaskUpdate> = updates.iter().filter(|u| &u.task_id == task_id).collect();
+            if members.is_empty() {
+                continue;
+            }
+            let samples: u64 = members.iter().map(|u| u.data_size as u64).sum();
+            let new_head = weighted_mean(members.iter().map(|u| (u.head.as_slice(), u.data_size as f64)), head.len());
+            let head_change_norm = change_norm(head, &new_head);
+            *head = new_head;
+
+            let share = |u: &TaskUpdate| u.data_size as f64 / samples as f64;
+            let loss: f64 = members.iter().map(|u| share(u) * u.loss).sum();
+            let accuracy: f64 = members.iter().map(|u| share(u) * u.accuracy).sum();
+            let previous = self.metrics.get(task_id);
+            let loss_improvement = previous.map(|p| p.loss - loss).unwrap_or(0.0);
+            let metrics = TaskMetrics {
+                task_id: task_id.clone(),
+                round: self.round,
+                participating_clients: members.iter().map(|u| u.client_id.clone()).collect(),
+                samples,
+                loss,
+                accuracy,
+                loss_improvement,
+                accuracy_improvement: previous.map(|p| accuracy - p.accuracy).unwrap_or(0.0),
+                head_change_norm,
+                converged: previous.is_some() && loss_improvement.abs() < self.convergence_threshold,
+            };
+            self.metrics.insert(task_id.clone(), metrics.clone());
+            tasks.push(metrics);
+        }
+
+        let summary = MultiTaskRound { round: self.round, shared_change_norm, tasks };
+        self.history.push(summary.clone());
+        self.round += 1;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client: &str, task: &str, round: u64, shared: f64, head: f64, data_size: usize, loss: f64) -> TaskUpdate {
+        TaskUpdate {
+            client_id: client.to_string(),
+            task_id: task.to_string(),
+            round,
+            shared: vec![shared; 2],
+            head: vec![head],
+            data_size,
+            loss,
+            accuracy: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_shared_layer_spans_tasks_and_heads_stay_separate() {
+        let mut coordinator = MultiTaskCoordinator::new(vec![0.0; 2], 0.01).unwrap();
+        coordinator.add_task("pku", vec![0.0]).unwrap();
+        coordinator.add_task("cf", vec![0.0]).unwrap();
+        assert!(coordinator.submit_update(update("a", "hd", 0, 1.0, 1.0, 10, 1.0)).is_err());
+
+        coordinator.submit_update(update("a", "pku", 0, 1.0, 2.0, 10, 0.9)).unwrap();
+        coordinator.submit_update(update("b", "pku", 0, 1.0, 4.0, 30, 0.7)).unwrap();
+        coordinator.submit_update(update("a", "cf", 0, 5.0, -1.0, 40, 0.5)).unwrap();
+        let round = coordinator.aggregate_round().unwrap();
+
+        assert!((coordinator.shared_weights()[0] - 3.0).abs() < 1e-12);
+        assert_eq!(coordinator.task_weights("pku").unwrap(), vec![3.0, 3.0, 3.5]);
+        assert_eq!(coordinator.task_weights("cf").unwrap(), vec![3.0, 3.0, -1.0]);
+        assert_eq!(round.tasks.len(), 2);
+        assert!((coordinator.task_metrics("pku").unwrap().loss - 0.75).abs() < 1e-12);
+
+        // Only cf trains in round 1; its loss barely moves, so it has converged while pku has not
+        coordinator.submit_update(update("a", "cf", 1, 3.0, -1.0, 40, 0.495)).unwrap();
+        let round = coordinator.aggregate_round().unwrap();
+        assert_eq!(round.tasks.len(), 1);
+        assert!(coordinator.task_metrics("cf").unwrap().converged);
+        assert!(!coordinator.task_metrics("pku").unwrap().converged);
+        assert!(!coordinator.all_converged());
+    }
+}
@@ -0,0 +1,219 @@
+// Coordinator metrics as OpenMetrics text, the exposition format Prometheus scrapes,
+// so a campaign can be watched on the dashboards operators already run. Each export
+// is a snapshot of the latest round's `ConvergenceMetrics`, `PrivacyMetrics` and
+// `CommunicationMetrics`, plus the round, loss and accuracy.
+//
+// Running totals (bytes, rounds, stragglers, energy) are counters; everything else is
+// a gauge. The privacy budget is a gauge too: with Rényi or zCDP accounting the
+// reported total is not a sum of per-round charges. Optional values that are unset
+// (no deadline, no DP round yet) are left out rather than exported as zero.
+//
+// Names are `<prefix>_<metric>`, with the prefix `federated_learning` unless the
+// operator sets another; constant labels such as the campaign or site are added to
+// every sample.
+
+use crate::*;
+use std::fmt::Write;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OpenMetricsExporter {
+    pub prefix: String,
+    pub labels: Vec<(String, String)>,
+}
+
+impl Default for OpenMetricsExporter {
+    fn default() -> Self {
+        OpenMetricsExporter { prefix: "federated_learning".to_string(), labels: Vec::new() }
+    }
+}
+
+fn is_valid_name(name: &str, allow_colon: bool) -> bool {
+    let mut chars = name.chars();
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':');
+    chars.next().is_some_and(|c| !c.is_ascii_digit() && valid(c)) && chars.all(valid)
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// One metric family being written: its descriptor lines and then its samples
+struct Family<'a> {
+    exporter: &'a OpenMetricsExporter,
+    out: &'a mut String,
+    sample_name: String,
+}
+
+impl Family<'_> {
+    fn sample(&mut self, labels: &[(&str, &str)], value: f64) {
+        let pairs: Vec<String> = self
+            .exporter
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .chain(labels.iter().copied())
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+            .collect();
+        let labels = if pairs.is_empty() { String::new() } else { format!("{{{}}}", pairs.join(",")) };
+        let _ = writeln!(self.out, "{}{} {}", self.sample_name, labels, format_value(value));
+    }
+}
+
+impl OpenMetricsExporter {
+    pub fn new(prefix: &str) -> Result<Self, String> {
+        if !is_valid_name(prefix, true) {
+            return Err(format!("'{}' is not a valid metric name prefix", prefix));
+        }
+        Ok(OpenMetricsExporter { prefix: prefix.to_string(), labels: Vec::new() })
+    }
+
+    pub fn with_label(mut self, name: &str, value: &str) -> Result<Self, String> {
+        if !is_valid_name(name, false) || name.starts_with("__") {
+            return Err(format!("'{}' is not a valid label name", name));
+        }
+        if self.labels.iter().any(|(k, _)| k == name) {
+            return Err(format!("Label '{}' is already set", name));
+        }
+        self.labels.push((name.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    fn family<'a>(&'a self, out: &'a mut String, metric: &str, kind: &str, unit: Option<&str>, help: &str) -> Family<'a> {
+        let name = format!("{}_{}", self.prefix, metric);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        if let Some(unit) = unit {
+            let _ = writeln!(out, "# UNIT {} {}", name, unit);
+        }
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let sample_name = if kind == "counter" { format!("{}_total", name) } else { name.clone() };
+        Family { exporter: self, out, sample_name }
+    }
+
+    fn gauge(&self, out: &mut String, metric: &str, help: &str, value: f64) {
+        self.family(out, metric, "gauge", None, help).sample(&[], value);
+    }
+
+    fn counter(&self, out: &mut String, metric: &str, unit: Option<&str>, help: &str, value: f64) {
+        self.family(out, metric, "counter", unit, help).sample(&[], value);
+    }
+
+    pub fn render(&self, model: &GlobalModel) -> String {
+        let mut out = String::new();
+        self.gauge(&mut out, "round", "Completed training rounds", model.round as f64);
+        self.gauge(&mut out, "global_loss", "Loss of the global model", model.global_loss);
+        self.gauge(&mut out, "global_accuracy", "Accuracy of the global model", model.global_accuracy);
+        self.gauge(&mut out, "participants", "Clients aggregated in the latest round", model.participating_clients.len() as f64);
+
+        let convergence = &model.convergence_metrics;
+        self.gauge(&mut out, "gradient_norm", "L2 norm of the latest aggregated update", convergence.gradient_norm);
+        self.gauge(&mut out, "weight_change_norm", "L2 norm of the latest change to the weights", convergence.weight_change_norm);
+        self.gauge(&mut out, "loss_improvement", "Loss decrease in the latest round", convergence.loss_improvement);
+        self.gauge(&mut out, "accuracy_improvement", "Accuracy increase in the latest round", convergence.accuracy_improvement);
+        self.gauge(&mut out, "convergence_rate", "Relative loss improvement in the latest round", convergence.convergence_rate);
+        self.gauge(&mut out, "stability_score", "Stability of the latest update", convergence.stability_score);
+        self.gauge(&mut out, "server_momentum_norm", "L2 norm of the server momentum", convergence.server_momentum_norm);
+
+        let privacy = &model.privacy_metrics;
+        self.gauge(&mut out, "privacy_epsilon_used", "Privacy budget spent, epsilon", privacy.total_epsilon_used);
+        self.gauge(&mut out, "privacy_delta_used", "Privacy budget spent, delta", privacy.total_delta_used);
+        self.gauge(&mut out, "privacy_guarantee", "Differential privacy guarantee", privacy.differential_privacy_guarantee);
+        self.gauge(
+            &mut out,
+            "membership_inference_resistance",
+            "Resistance in the latest membership inference audit",
+            privacy.membership_inference_resistance,
+        );
+        if let Some(clip_norm) = privacy.clip_norm {
+            self.gauge(&mut out, "privacy_clip_norm", "L2 bound updates were clipped to before noise", clip_norm);
+        }
+        self.gauge(&mut out, "privacy_sampling_rate", "Sampling rate of the latest DP round", privacy.sampling_rate);
+        self.gauge(&mut out, "privacy_round_epsilon", "Epsilon of the latest DP round after amplification", privacy.effective_round_epsilon);
+        let granularity = format!("{:?}", privacy.granularity).to_lowercase();
+        self.family(&mut out, "privacy_granularity", "gauge", None, "Unit the latest DP round protected")
+            .sample(&[("granularity", &granularity)], 1.0);
+        // Sorted, so consecutive scrapes list clients in the same order
+        let mut per_client: Vec<(&String, &f64)> = privacy.privacy_loss_per_client.iter().collect();
+        per_client.sort_by(|a, b| a.0.cmp(b.0));
+        if !per_client.is_empty() {
+            let mut family = self.family(&mut out, "privacy_client_epsilon", "gauge", None, "Privacy budget spent per client, epsilon");
+            for (client_id, epsilon) in per_client {
+                family.sample(&[("client_id", client_id)], *epsilon);
+            }
+        }
+
+        let communication = &model.communication_metrics;
+        self.counter(&mut out, "sent_bytes", Some("bytes"), "Bytes sent to clients", communication.total_bytes_sent as f64);
+        self.counter(&mut out, "received_bytes", Some("bytes"), "Bytes received from clients", communication.total_bytes_received as f64);
+        self.gauge(&mut out, "compression_savings", "Share of bytes saved by compression", communication.compression_savings);
+        self.counter(&mut out, "communication_rounds", None, "Rounds of communication", communication.communication_rounds as f64);
+        self.family(&mut out, "average_round_time_seconds", "gauge", Some("seconds"), "Mean round duration")
+            .sample(&[], communication.average_round_time);
+        self.gauge(&mut out, "bandwidth_efficiency", "Bandwidth efficiency", communication.bandwidth_efficiency);
+        self.gauge(&mut out, "round_energy_kwh", "Estimated energy of the latest round", communication.round_energy_kwh);
+        self.gauge(&mut out, "round_carbon_kg_co2e", "Estimated emissions of the latest round", communication.round_carbon_kg_co2e);
+        self.counter(&mut out, "energy_kwh", None, "Estimated energy of all rounds", communication.total_energy_kwh);
+        self.counter(&mut out, "carbon_kg_co2e", None, "Estimated emissions of all rounds", communication.total_carbon_kg_co2e);
+        if let Some(deadline) = communication.round_deadline_secs {
+            self.family(&mut out, "round_deadline_seconds", "gauge", Some("seconds"), "Deadline of the latest round")
+                .sample(&[], deadline);
+        }
+        self.gauge(&mut out, "round_stragglers", "Updates that missed the latest round's deadline", communication.round_stragglers as f64);
+        self.gauge(&mut out, "round_straggler_rate", "Share of the latest round's updates that were late", communication.round_straggler_rate);
+        self.family(&mut out, "max_straggler_delay_seconds", "gauge", Some("seconds"), "Longest delay past the latest deadline")
+            .sample(&[], communication.max_straggler_delay_secs);
+        self.counter(&mut out, "stragglers", None, "Updates that missed their round's deadline", communication.total_stragglers as f64);
+
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+impl FederatedLearningCoordinator {
+    pub fn export_openmetrics(&self, exporter: &OpenMetricsExporter) -> String {
+        exporter.render(&self.global_model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposition_is_well_formed() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).privacy_method(PrivacyMethod::None).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let update = ModelUpdate {
+            loss: 0.4,
+            accuracy: 0.9,
+            computation_time: 1.0,
+            privacy_budget_used: 0.2,
+            ..ModelUpdate::for_test("hospital \"a\"", vec![0.5, -0.5], 10)
+        };
+        coordinator.execute_round(vec![update]).unwrap();
+        coordinator.global_model.privacy_metrics.privacy_loss_per_client.insert("hospital \"a\"".to_string(), 0.2);
+
+        let exporter = OpenMetricsExporter::default().with_label("campaign", "sepsis-2024").unwrap();
+        let text = coordinator.export_openmetrics(&exporter);
+        assert!(text.ends_with("# EOF\n"));
+        assert!(text.contains("# TYPE federated_learning_received_bytes counter\n# UNIT federated_learning_received_bytes bytes\n"));
+        assert!(text.contains("federated_learning_round{campaign=\"sepsis-2024\"} 1\n"));
+        assert!(text.contains("federated_learning_privacy_client_epsilon{campaign=\"sepsis-2024\",client_id=\"hospital \\\"a\\\"\"} 0.2\n"));
+        // No deadline was set, so none is exported
+        assert!(!text.contains("round_deadline_seconds"));
+        // Counter samples carry the _total suffix
+        assert!(text.lines().any(|l| l.starts_with("federated_learning_communication_rounds_total{")));
+
+        assert!(OpenMetricsExporter::new("9lives").is_err());
+        assert!(exporter.with_label("campaign", "again").is_err());
+    }
+}
@@ -1,2 +1,351 @@
-// Placeholder for optimization module
-pub struct OptimizationPlaceholder;
\ No newline at end of file
+// Server-side state for optimizers that carry information across rounds
+
+use crate::*;
+
+// SCAFFOLD control variates (Karimireddy et al., 2020). The server control c estimates
+// the global update direction and each client control c_i that client's own; clients
+// correct every local step by (c - c_i) so their models drift less toward local optima.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ScaffoldState {
+    pub server_control: Vec<f64>,
+    pub client_controls: HashMap<String, Vec<f64>>,
+}
+
+// What the server needs from one participant to refresh its control variate
+pub struct ScaffoldClientResult<'a> {
+    pub client_id: &'a str,
+    pub local_weights: &'a [f64],
+    pub local_steps: f64,
+}
+
+// FedDyn server state (Acar et al., 2021). h accumulates the participants' drift from
+// the global model, and the server model is corrected by -h / α so the fixed point of
+// the clients' dynamically regularized objectives matches the global optimum.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FedDynState {
+    pub linear_penalty: Vec<f64>,
+}
+
+impl FedDynState {
+    pub fn new() -> Self {
+        FedDynState::default()
+    }
+}
+
+// FedACG server momentum (Kim et al., 2024). Clients start each round from a lookahead
+// point along the momentum, so local training already anticipates the next global step.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FedAcgState {
+    pub momentum: Vec<f64>,
+}
+
+impl FedAcgState {
+    pub fn new() -> Self {
+        FedAcgState::default()
+    }
+}
+
+impl ScaffoldState {
+    pub fn new() -> Self {
+        ScaffoldState::default()
+    }
+
+    // Controls a client should train the next round with: (c, c_i)
+    pub fn controls_for(&self, client_id: &str) -> (Vec<f64>, Vec<f64>) {
+        let client_control = self
+            .client_controls
+            .get(client_id)
+            .cloned()
+            .unwrap_or_else(|| vec![0.0; self.server_control.len()]);
+        (self.server_control.clone(), client_control)
+    }
+
+    // A change of model size invalidates every control variate
+    fn ensure_dimension(&mut self, dimension: usize) {
+        if self.server_control.len() != dimension {
+            self.server_control = vec![0.0; dimension];
+            self.client_controls.clear();
+        }
+    }
+}
+
+impl OptimizationEngine {
+    // Client side: one local SGD step corrected for drift, y <- y - lr * (g - c_i + c)
+    pub fn scaffold_local_step(
+        &self,
+        weights: &mut [f64],
+        gradient: &[f64],
+        learning_rate: f64,
+        server_control: &[f64],
+        client_control: &[f64],
+    ) -> Result<(), String> {
+        let n = weights.len();
+        if gradient.len() != n || server_control.len() != n || client_control.len() != n {
+            return Err("SCAFFOLD step needs gradient and controls matching the model size".to_string());
+        }
+        for i in 0..n {
+            weights[i] -= learning_rate * (gradient[i] - client_control[i] + server_control[i]);
+        }
+        Ok(())
+    }
+
+    // Server side: refreshes each participant's control with option II of the paper,
+    // c_i+ = c_i - c + (x - y_i) / (K * lr), then moves the server control by the
+    // participating fraction of the mean change, c <- c + |S| / N * mean(c_i+ - c_i)
+    pub fn scaffold_update_controls(
+        &self,
+        state: &mut ScaffoldState,
+        global_weights: &[f64],
+        results: &[ScaffoldClientResult],
+        local_learning_rate: f64,
+        total_clients: usize,
+    ) -> Result<(), String> {
+        if results.is_empty() {
+            return Ok(());
+        }
+        if local_learning_rate <= 0.0 {
+            return Err("SCAFFOLD requires a positive local learning rate".to_string());
+        }
+
+        let dimension = global_weights.len();
+        state.ensure_dimension(dimension);
+
+        let mut mean_delta = vec![0.0; dimension];
+        for result in results {
+            if result.local_weights.len() != dimension {
+                return Err(format!(
+                    "Client {} sent {} weights, expected {}",
+                    result.client_id,
+                    result.local_weights.len(),
+                    dimension
+                ));
+            }
+
+            let steps = result.local_steps.max(1.0);
+            let (server_control, old_control) = state.controls_for(result.client_id);
+            let new_control: Vec<f64> = (0..dimension)
+                .map(|i| {
+                    old_control[i] - server_control[i]
+                        + (global_weights[i] - result.local_weights[i]) / (steps * local_learning_rate)
+                })
+                .collect();
+
+            for i in 0..dimension {
+                mean_delta[i] += (new_control[i] - old_control[i]) / results.len() as f64;
+            }
+            state.client_controls.insert(result.client_id.to_string(), new_control);
+        }
+
+        let participation = results.len() as f64 / total_clients.max(results.len()) as f64;
+        kernels::axpy(participation, &mean_delta, &mut state.server_control);
+
+        Ok(())
+    }
+
+    // Client side: gradient of the FedDyn local objective
+    // L_k(θ) - <g_k, θ> + α/2 ||θ - θ_global||², where g_k is the client's linear term.
+    // After local training the client refreshes g_k <- g_k - α (θ_k - θ_global).
+    pub fn feddyn_local_gradient(
+        &self,
+        weights: &[f64],
+        gradient: &[f64],
+        alpha: f64,
+        global_weights: &[f64],
+        linear_term: &[f64],
+    ) -> Result<Vec<f64>, String> {
+        let n = weights.len();
+        if gradient.len() != n || global_weights.len() != n || linear_term.len() != n {
+            return Err("FedDyn step needs gradient, global weights and linear term matching the model size".to_string());
+        }
+        Ok((0..n)
+            .map(|i| gradient[i] - linear_term[i] + alpha * (weights[i] - global_weights[i]))
+            .collect())
+    }
+
+    // Server side, with `weights` the mean of the participants' models:
+    // h <- h - α |P| / m * (mean(θ_k) - θ), then θ <- mean(θ_k) - h / α
+    pub fn feddyn_optimization(
+        &self,
+        state: &mut FedDynState,
+        weights: Vec<f64>,
+        alpha: f64,
+        global_weights: &[f64],
+        participants: usize,
+        total_clients: usize,
+    ) -> Result<Vec<f64>, String> {
+        if alpha <= 0.0 {
+            return Err("FedDyn requires a positive α".to_string());
+        }
+        let dimension = weights.len();
+        if global_weights.len() != dimension {
+            return Err(format!("FedDyn got {} aggregated weights for a model of size {}", dimension, global_weights.len()));
+        }
+        // A change of model size invalidates the accumulated penalty
+        if state.linear_penalty.len() != dimension {
+            state.linear_penalty = vec![0.0; dimension];
+        }
+
+        let participation = participants as f64 / total_clients.max(participants).max(1) as f64;
+        for i in 0..dimension {
+            state.linear_penalty[i] -= alpha * participation * (weights[i] - global_weights[i]);
+        }
+
+        Ok(weights
+            .iter()
+            .zip(state.linear_penalty.iter())
+            .map(|(w, h)| w - h / alpha)
+            .collect())
+    }
+
+    // x + (λ + λ² + ... + λ^k) m: the model after k further momentum-only steps.
+    // One step is the paper's lookahead; zero broadcasts the global model.
+    pub fn fedacg_lookahead(&self, state: &FedAcgState, global_weights: &[f64], lambda: f64, lookahead_steps: u32) -> Vec<f64> {
+        let mut lookahead = global_weights.to_vec();
+        if state.momentum.len() != global_weights.len() {
+            return lookahead;
+        }
+        let coefficient: f64 = (1..=lookahead_steps).map(|j| lambda.powi(j as i32)).sum();
+        kernels::axpy(coefficient, &state.momentum, &mut lookahead);
+        lookahead
+    }
+
+    // Server side, with `weights` the mean of models trained from the lookahead point:
+    // m <- λ m + (mean(y_i) - lookahead), then x <- x + m
+    pub fn fedacg_optimization(
+        &self,
+        state: &mut FedAcgState,
+        weights: Vec<f64>,
+        lambda: f64,
+        lookahead_steps: u32,
+        global_weights: &[f64],
+    ) -> Result<Vec<f64>, String> {
+        let dimension = weights.len();
+        if global_weights.len() != dimension {
+            return Err(format!("FedACG got {} aggregated weights for a model of size {}", dimension, global_weights.len()));
+        }
+        // A change of model size invalidates the momentum
+        if state.momentum.len() != dimension {
+            state.momentum = vec![0.0; dimension];
+        }
+
+        let lookahead = self.fedacg_lookahead(state, global_weights, lambda, lookahead_steps);
+        for i in 0..dimension {
+            state.momentum[i] = lambda * state.momentum[i] + (weights[i] - lookahead[i]);
+        }
+
+        let mut updated = global_weights.to_vec();
+        kernels::axpy(1.0, &state.momentum, &mut updated);
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feddyn_penalty_accumulates_drift() {
+        let engine = OptimizationEngine::new();
+        let mut state = FedDynState::new();
+
+        // Half the clients moved the mean model from 0 to 1
+        let weights = engine.feddyn_optimization(&mut state, vec![1.0], 0.1, &[0.0], 2, 4).unwrap();
+        // h = -0.1 * 0.5 * 1 = -0.05, θ = 1 + 0.05 / 0.1
+        assert!((state.linear_penalty[0] + 0.05).abs() < 1e-12);
+        assert!((weights[0] - 1.5).abs() < 1e-12);
+
+        // No movement leaves h unchanged, so the correction persists across rounds
+        let weights = engine.feddyn_optimization(&mut state, vec![1.5], 0.1, &[1.5], 2, 4).unwrap();
+        assert!((weights[0] - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fedacg_momentum_and_lookahead() {
+        let engine = OptimizationEngine::new();
+        let mut state = FedAcgState::new();
+
+        // First round: no momentum yet, so clients moved the global model 0 -> 1
+        let weights = engine.fedacg_optimization(&mut state, vec![1.0], 0.5, 2, &[0.0]).unwrap();
+        assert_eq!(state.momentum, vec![1.0]);
+        assert_eq!(weights, vec![1.0]);
+
+        // Two lookahead steps broadcast x + (0.5 + 0.25) m
+        assert_eq!(engine.fedacg_lookahead(&state, &weights, 0.5, 2), vec![1.75]);
+
+        // Clients ended 0.5 past the lookahead: m = 0.5 * 1 + 0.5
+        let weights = engine.fedacg_optimization(&mut state, vec![2.25], 0.5, 2, &weights).unwrap();
+        assert_eq!(state.momentum, vec![1.0]);
+        assert_eq!(weights, vec![2.0]);
+    }
+    #[test]
+    fn test_scaffold_step_and_option_ii_control_update() {
+        let engine = OptimizationEngine::new();
+        // y <- y - lr (g - c_i + c)
+        let mut weights = vec![1.0, 1.0];
+        engine.scaffold_local_step(&mut weights, &[0.5, 0.5], 0.1, &[0.25, -0.75], &[1.0, -1.0]).unwrap();
+        assert!((weights[0] - 1.025).abs() < 1e-12 && (weights[1] - 0.925).abs() < 1e-12);
+        assert!(engine.scaffold_local_step(&mut weights, &[0.5], 0.1, &[0.0, 0.0], &[0.0, 0.0]).is_err());
+
+        // Two of four clients, K = 2 steps at lr 0.1: c_i+ = 0 - 0 + (x - y_i) / 0.2
+        let mut state = ScaffoldState::new();
+        let global = [1.0, 0.0];
+        let results = [
+            ScaffoldClientResult { client_id: "a", local_weights: &[0.8, 0.2], local_steps: 2.0 },
+            ScaffoldClientResult { client_id: "b", local_weights: &[1.0, 0.4], local_steps: 2.0 },
+        ];
+        engine.scaffold_update_controls(&mut state, &global, &results, 0.1, 4).unwrap();
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12);
+        assert!(close(&state.client_controls["a"], &[1.0, -1.0]));
+        assert!(close(&state.client_controls["b"], &[0.0, -2.0]));
+        // c moves by |S| / N = 1/2 of the mean change [0.5, -1.5]
+        assert!(close(&state.server_control, &[0.25, -0.75]));
+
+        // a alone, back at x: c_a+ = c_a - c, so c moves by 1/4 of [-0.25, 0.75]
+        let results = [ScaffoldClientResult { client_id: "a", local_weights: &global, local_steps: 2.0 }];
+        engine.scaffold_update_controls(&mut state, &global, &results, 0.1, 4).unwrap();
+        assert!(close(&state.client_controls["a"], &[0.75, -0.25]));
+        assert!(close(&state.client_controls["b"], &[0.0, -2.0]));
+        assert!(close(&state.server_control, &[0.1875, -0.5625]));
+    }
+
+    #[test]
+    fn test_scaffold_controls_survive_rounds_and_snapshots() {
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(2)
+            .min_clients(1)
+            .privacy_method(PrivacyMethod::None)
+            .algorithm(FLAlgorithm::SCAFFOLD)
+            .learning_rate(0.1)
+            .local_epochs(1)
+            .batch_size(32)
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let update = |client_id: &str, round: u64, weights: Vec<f64>| ModelUpdate {
+            round,
+            weights,
+            ..ModelUpdate::for_test(client_id, vec![0.1, -0.1], 10)
+        };
+
+        // An update without its local model is turned away rather than failing the round
+        let global = coordinator.global_model.weights.clone();
+        let local_a = vec![global[0] - 0.1, global[1] + 0.1];
+        coordinator.execute_round(vec![update("a", 0, local_a.clone()), update("b", 0, Vec::new())]).unwrap();
+        let diagnostics = coordinator.get_round_diagnostics();
+        assert_eq!(diagnostics.accepted, 1);
+        assert_eq!(diagnostics.rejections[0].reason, RejectionReason::InvalidLocalWeights { received: 0 });
+        // One local step at lr 0.1: c_a = (x - y_a) / 0.1
+        let (_, control_a) = coordinator.get_scaffold_controls("a");
+        assert!((control_a[0] - 1.0).abs() < 1e-9 && (control_a[1] + 1.0).abs() < 1e-9);
+
+        // a sits the next round out and keeps its control
+        let global = coordinator.global_model.weights.clone();
+        coordinator.execute_round(vec![update("c", 1, global)]).unwrap();
+        assert_eq!(coordinator.get_scaffold_controls("a").1, control_a);
+
+        let restored = FederatedLearningCoordinator::restore(&coordinator.snapshot()).unwrap();
+        for client_id in ["a", "c"] {
+            assert_eq!(restored.get_scaffold_controls(client_id), coordinator.get_scaffold_controls(client_id));
+        }
+    }
+}
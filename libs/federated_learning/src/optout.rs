@@ -0,0 +1,207 @@
+// Patient-level opt-out from research use. The consortium distributes the keyed
+// pseudonyms of every patient who has withdrawn consent, never the raw identifiers.
+// Each site drops matching patients and everything recorded about them before
+// extracting features or building a cohort, and reports what it excluded against
+// which registry version, so the coordinator can show an ethics board that every
+// round trained on extracts filtered against the current registry.
+
+use crate::*;
+use medical_data::linkage::patient_id_from_reference;
+use medical_data::privacy::pseudonymize_patient_id;
+use medical_data::rare_diseases::RareDiseaseCase;
+use medical_data::MedicalDataset;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OptOutRegistry {
+    consortium_salt: String,
+    pseudonyms: BTreeSet<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OptOutStats {
+    // Identifies the registry version that was applied
+    pub registry_fingerprint: String,
+    pub patients_seen: u64,
+    pub patients_excluded: u64,
+    pub conditions_excluded: u64,
+    pub observations_excluded: u64,
+    pub diagnostic_reports_excluded: u64,
+    pub cases_excluded: u64,
+}
+
+// What one site applied before training in one round
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OptOutReport {
+    pub site_id: String,
+    pub round: u64,
+    pub stats: OptOutStats,
+}
+
+impl OptOutRegistry {
+    pub fn new(consortium_salt: &str) -> Self {
+        OptOutRegistry { consortium_salt: consortium_salt.to_string(), pseudonyms: BTreeSet::new() }
+    }
+
+    // Pseudonym as distributed by the consortium
+    pub fn insert_pseudonym(&mut self, pseudonym: &str) {
+        self.pseudonyms.insert(pseudonym.to_ascii_lowercase());
+    }
+
+    // For the site where the patient withdrew consent
+    pub fn insert_patient(&mut self, patient_id: &str) -> String {
+        let pseudonym = pseudonymize_patient_id(patient_id, &self.consortium_salt);
+        self.pseudonyms.insert(pseudonym.clone());
+        pseudonym
+    }
+
+    pub fn len(&self) -> usize {
+        self.pseudonyms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pseudonyms.is_empty()
+    }
+
+    pub fn is_opted_out(&self, patient_id: &str) -> bool {
+        let patient_id = patient_id.strip_prefix("Patient/").unwrap_or(patient_id);
+        self.pseudonyms.contains(&pseudonymize_patient_id(patient_id, &self.consortium_salt))
+    }
+
+    // Hex SHA-256 over the sorted pseudonyms; equal at two sites iff they hold the same list
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for pseudonym in &self.pseudonyms {
+            hasher.update(pseudonym.as_bytes());
+            hasher.update(b"\n");
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn empty_stats(&self) -> OptOutStats {
+        OptOutStats { registry_fingerprint: self.fingerprint(), ..OptOutStats::default() }
+    }
+
+    fn references_opted_out(&self, reference: &Option<String>) -> bool {
+        reference.as_deref().is_some_and(|reference| self.is_opted_out(reference))
+    }
+
+    // Copy of the dataset without opted-out patients and their resources
+    pub fn filter_dataset(&self, dataset: &MedicalDataset) -> (MedicalDataset, OptOutStats) {
+        let mut stats = self.empty_stats();
+        let mut filtered = dataset.clone();
+        stats.patients_seen = dataset.patients.len() as u64;
+        if self.is_empty() {
+            return (filtered, stats);
+        }
+
+        // A patient who opted out under a record since merged away is excluded through
+        // the survivor that replaced it
+        let merged: BTreeSet<String> = dataset
+            .patients
+            .iter()
+            .filter(|p| p.replaced_ids().iter().any(|id| self.is_opted_out(id)))
+            .map(|p| p.id.clone())
+            .collect();
+        let excluded = |reference: &Option<String>| {
+            self.references_opted_out(reference)
+                || reference.as_deref().is_some_and(|r| merged.contains(patient_id_from_reference(r)))
+        };
+
+        filtered.patients.retain(|p| !self.is_opted_out(&p.id) && !merged.contains(&p.id));
+        filtered.conditions.retain(|c| !excluded(&c.subject.reference));
+        filtered.observations.retain(|o| !excluded(&o.subject.reference));
+        filtered.diagnostic_reports.retain(|r| !excluded(&r.subject.reference));
+
+        stats.patients_excluded = (dataset.patients.len() - filtered.patients.len()) as u64;
+        stats.conditions_excluded = (dataset.conditions.len() - filtered.conditions.len()) as u64;
+        stats.observations_excluded = (dataset.observations.len() - filtered.observations.len()) as u64;
+        stats.diagnostic_reports_excluded =
+            (dataset.diagnostic_reports.len() - filtered.diagnostic_reports.len()) as u64;
+        (filtered, stats)
+    }
+
+    pub fn filter_cases(&self, cases: &[RareDiseaseCase]) -> (Vec<RareDiseaseCase>, OptOutStats) {
+        let mut stats = self.empty_stats();
+        let kept: Vec<RareDiseaseCase> = cases.iter().filter(|c| !self.is_opted_out(&c.patient.id)).cloned().collect();
+        stats.cases_excluded = (cases.len() - kept.len()) as u64;
+        (kept, stats)
+    }
+}
+
+impl OptOutStats {
+    // Combines the dataset and cohort passes of one site
+    pub fn merge(&mut self, other: &OptOutStats) -> Result<(), String> {
+        if self.registry_fingerprint != other.registry_fingerprint {
+            return Err("Opt-out statistics come from different registry versions".to_string());
+        }
+        self.patients_seen += other.patients_seen;
+        self.patients_excluded += other.patients_excluded;
+        self.conditions_excluded += other.conditions_excluded;
+        self.observations_excluded += other.observations_excluded;
+        self.diagnostic_reports_excluded += other.diagnostic_reports_excluded;
+        self.cases_excluded += other.cases_excluded;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use medical_data::{create_reference, CodeableConcept, Condition, Observation, Patient};
+
+    fn dataset() -> MedicalDataset {
+        let mut dataset = MedicalDataset::new("ward".to_string(), "Ward".to_string(), String::new());
+        for id in ["p1", "p2", "p3", "p4"] {
+            let subject = create_reference(&format!("Patient/{}", id), None);
+            dataset.patients.push(Patient::new(id.to_string()));
+            dataset.conditions.push(Condition::new(format!("{}-condition", id), subject.clone()));
+            dataset.observations.push(Observation::new(format!("{}-hr", id), CodeableConcept::loinc("8867-4").unwrap(), subject));
+        }
+        // p4 is a duplicate record of p3
+        dataset.merge_patients("p3", "p4").unwrap();
+        dataset
+    }
+
+    #[test]
+    fn test_sites_drop_opted_out_patients_and_report_against_the_registry() {
+        // p1 and p4 withdrew at the site treating them; other sites receive only the pseudonyms
+        let mut origin = OptOutRegistry::new("consortium-salt");
+        let pseudonyms = vec![origin.insert_patient("p1"), origin.insert_patient("p4")];
+        let mut site = OptOutRegistry::new("consortium-salt");
+        for pseudonym in &pseudonyms {
+            site.insert_pseudonym(&pseudonym.to_ascii_uppercase());
+        }
+        assert_eq!(site.fingerprint(), origin.fingerprint());
+        assert!(site.is_opted_out("Patient/p1") && !site.is_opted_out("p2"));
+
+        // p4 was merged into p3, so p3 and everything now recorded under it goes too
+        let (filtered, stats) = site.filter_dataset(&dataset());
+        assert_eq!(filtered.patients.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["p2"]);
+        assert!(filtered.conditions.iter().all(|c| c.subject.reference.as_deref() == Some("Patient/p2")));
+        assert_eq!((stats.patients_seen, stats.patients_excluded), (3, 2));
+        assert_eq!((stats.conditions_excluded, stats.observations_excluded), (3, 3));
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).privacy_method(PrivacyMethod::None).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.require_opt_out_registry(Some(site.fingerprint()));
+        let report = |site_id: &str, stats: OptOutStats| OptOutReport { site_id: site_id.to_string(), round: 0, stats };
+        // A site still holding the registry without p4 is turned away
+        let mut outdated = OptOutRegistry::new("consortium-salt");
+        outdated.insert_patient("p1");
+        assert!(coordinator.submit_opt_out_report(report("hospital_b", outdated.filter_dataset(&dataset()).1)).is_err());
+        coordinator.submit_opt_out_report(report("hospital_a", stats.clone())).unwrap();
+
+        let updates = vec![
+            ModelUpdate::for_test("hospital_a", vec![0.1, 0.1], 10),
+            ModelUpdate::for_test("hospital_b", vec![0.1, 0.1], 10),
+        ];
+        coordinator.execute_round(updates).unwrap();
+        let diagnostics = coordinator.get_round_diagnostics();
+        assert_eq!(diagnostics.accepted, 1);
+        assert_eq!(diagnostics.rejections[0].client_id, "hospital_b");
+        assert_eq!(diagnostics.rejections[0].reason, RejectionReason::MissingOptOutReport);
+        assert_eq!(coordinator.get_opt_out_reports(0)[0].stats, stats);
+    }
+}
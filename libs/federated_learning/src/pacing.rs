@@ -0,0 +1,206 @@
+// Back-pressure aware round pacing. A round lasts as long as its slowest counted
+// client, so when sites get busier (or a heavier model is deployed) rounds stretch
+// far past the schedule. The pacing controller watches how long rounds and local
+// training actually take and, after each round, picks the local epochs and the
+// upload deadline for the next one so that it fits `target_round_secs`.
+//
+// Local training time is taken to scale linearly with the number of epochs. The
+// controller estimates the time per epoch of a slow client (the `straggler_quantile`
+// of the round's reported computation times) and the fixed overhead of a round
+// (broadcast, upload and aggregation: whatever of the round the slow client's
+// training does not explain), smooths both across rounds, and runs as many epochs as
+// fit in what the overhead leaves of the target. The deadline is the expected round
+// time with some headroom, never beyond the target; clients slower than that are cut
+// off as stragglers instead of holding the round up.
+//
+// The orchestrator reports each round's wall-clock duration with
+// `record_round_duration`, which also maintains `average_round_time`; the paced
+// epochs replace `local_epochs` in the coordinator's configuration, so FedNova keeps
+// normalizing by the epochs clients were actually asked to run.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PacingPolicy {
+    pub target_round_secs: f64,
+    pub min_local_epochs: u32,
+    pub max_local_epochs: u32,
+    // Computation-time quantile that sizes the round; 1.0 waits for the slowest client
+    pub straggler_quantile: f64,
+    // Deadline as a multiple of the expected round time, capped at the target
+    pub deadline_headroom: f64,
+    // Weight of the newest round in the smoothed estimates, in (0, 1]
+    pub smoothing: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PacingDecision {
+    pub local_epochs: u32,
+    pub deadline_secs: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PacingController {
+    pub policy: PacingPolicy,
+    pub decision: PacingDecision,
+    // Smoothed estimates; None until a round with computation times has been seen
+    pub secs_per_epoch: Option<f64>,
+    pub overhead_secs: Option<f64>,
+    // Computation times of the latest round, waiting for its duration
+    pub pending_computation_times: Vec<f64>,
+}
+
+impl Default for PacingPolicy {
+    fn default() -> Self {
+        PacingPolicy {
+            target_round_secs: 600.0,
+            min_local_epochs: 1,
+            max_local_epochs: 10,
+            straggler_quantile: 0.9,
+            deadline_headroom: 1.2,
+            smoothing: 0.5,
+        }
+    }
+}
+
+impl PacingPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.target_round_secs.is_finite() && self.target_round_secs > 0.0) {
+            return Err("Pacing target_round_secs must be positive".to_string());
+        }
+        if self.min_local_epochs == 0 || self.min_local_epochs > self.max_local_epochs {
+            return Err("Pacing needs 1 <= min_local_epochs <= max_local_epochs".to_string());
+        }
+        if !(self.straggler_quantile > 0.0 && self.straggler_quantile <= 1.0) {
+            return Err("Pacing straggler_quantile must be in (0, 1]".to_string());
+        }
+        if !(self.deadline_headroom.is_finite() && self.deadline_headroom >= 1.0) {
+            return Err("Pacing deadline_headroom must be at least 1".to_string());
+        }
+        if !(self.smoothing > 0.0 && self.smoothing <= 1.0) {
+            return Err("Pacing smoothing must be in (0, 1]".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl PacingController {
+    // Starts from the configured epochs and the full target as deadline
+    pub fn new(policy: PacingPolicy, local_epochs: u32) -> Result<Self, String> {
+        policy.validate()?;
+        let decision = PacingDecision {
+            local_epochs: local_epochs.clamp(policy.min_local_epochs, policy.max_local_epochs),
+            deadline_secs: policy.target_round_secs,
+        };
+        Ok(PacingController { policy, decision, secs_per_epoch: None, overhead_secs: None, pending_computation_times: Vec::new() })
+    }
+
+    // Folds in a round run with the current decision and returns the next one
+    pub fn observe(&mut self, round_secs: f64, computation_times: &[f64]) -> PacingDecision {
+        let mut times: Vec<f64> = computation_times.iter().copied().filter(|t| t.is_finite() && *t >= 0.0).collect();
+        if times.is_empty() || !(round_secs.is_finite() && round_secs >= 0.0) {
+            return self.decision.clone();
+        }
+        times.sort_by(|a, b| a.total_cmp(b));
+        let index = ((times.len() as f64 * self.policy.straggler_quantile).ceil() as usize).clamp(1, times.len()) - 1;
+        let slow = times[index];
+
+        let smooth = |previous: Option<f64>, observed: f64| match previous {
+            Some(previous) => previous + self.policy.smoothing * (observed - previous),
+            None => observed,
+        };
+        let secs_per_epoch = smooth(self.secs_per_epoch, slow / self.decision.local_epochs as f64);
+        let overhead_secs = smooth(self.overhead_secs, (round_secs - slow).max(0.0));
+        self.secs_per_epoch = Some(secs_per_epoch);
+        self.overhead_secs = Some(overhead_secs);
+
+        let policy = &self.policy;
+        let training_budget = policy.target_round_secs - overhead_secs;
+        let local_epochs = if secs_per_epoch > 0.0 {
+            (training_budget / secs_per_epoch).floor().clamp(policy.min_local_epochs as f64, policy.max_local_epochs as f64) as u32
+        } else {
+            policy.max_local_epochs
+        };
+        let expected_secs = overhead_secs + secs_per_epoch * local_epochs as f64;
+        self.decision = PacingDecision {
+            local_epochs,
+            deadline_secs: (expected_secs * policy.deadline_headroom).min(policy.target_round_secs),
+        };
+        self.decision.clone()
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // None turns pacing off and keeps the current local epochs
+    pub fn set_pacing_policy(&mut self, policy: Option<PacingPolicy>) -> Result<(), String> {
+        self.pacing = match policy {
+            Some(policy) => Some(PacingController::new(policy, self.config.local_epochs)?),
+            None => None,
+        };
+        if let Some(pacing) = &self.pacing {
+            self.config.local_epochs = pacing.decision.local_epochs;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record_pacing_computation(&mut self, updates: &[ModelUpdate]) {
+        if let Some(pacing) = &mut self.pacing {
+            pacing.pending_computation_times = updates.iter().map(|u| u.computation_time).collect();
+        }
+    }
+
+    // Reports the wall-clock duration of the round that just finished; returns the
+    // epochs and deadline for the next round when pacing is on
+    pub fn record_round_duration(&mut self, round_secs: f64) -> Result<Option<PacingDecision>, String> {
+        if !(round_secs.is_finite() && round_secs >= 0.0) {
+            return Err("Round duration must be a non-negative number of seconds".to_string());
+        }
+        let metrics = &mut self.global_model.communication_metrics;
+        let rounds = metrics.communication_rounds.max(1) as f64;
+        metrics.average_round_time += (round_secs - metrics.average_round_time) / rounds;
+
+        let pacing = match &mut self.pacing {
+            Some(pacing) => pacing,
+            None => return Ok(None),
+        };
+        let computation_times = std::mem::take(&mut pacing.pending_computation_times);
+        let decision = pacing.observe(round_secs, &computation_times);
+        self.config.local_epochs = decision.local_epochs;
+        Ok(Some(decision))
+    }
+
+    pub fn get_pacing_decision(&self) -> Option<PacingDecision> {
+        self.pacing.as_ref().map(|pacing| pacing.decision.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pacing_trades_epochs_for_round_time() {
+        let policy = PacingPolicy { target_round_secs: 100.0, smoothing: 1.0, ..PacingPolicy::default() };
+        let mut pacing = PacingController::new(policy, 5).unwrap();
+
+        // 5 epochs at 30 s each for the slowest client, plus 10 s overhead: 160 s
+        let decision = pacing.observe(160.0, &[60.0, 90.0, 150.0]);
+        assert_eq!(decision.local_epochs, 3);
+        assert!((decision.deadline_secs - 100.0).abs() < 1e-9);
+
+        // Sites freed up: 3 epochs now take 30 s, so there is room for more
+        let decision = pacing.observe(40.0, &[20.0, 30.0]);
+        assert_eq!(decision.local_epochs, 9);
+        assert!((decision.deadline_secs - 100.0).abs() < 1e-9);
+        let decision = pacing.observe(10.0, &[1.0]);
+        assert_eq!(decision.local_epochs, 10);
+        assert!(decision.deadline_secs < 100.0);
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        assert_eq!(coordinator.record_round_duration(30.0).unwrap(), None);
+        assert!(coordinator.set_pacing_policy(Some(PacingPolicy { smoothing: 0.0, ..PacingPolicy::default() })).is_err());
+        coordinator.set_pacing_policy(Some(PacingPolicy::default())).unwrap();
+        assert_eq!(coordinator.get_pacing_decision().unwrap().local_epochs, 5);
+    }
+}
@@ -0,0 +1,188 @@
+// Partial updates for transfer-learning clients. A small clinic often fine-tunes only
+// the final layers of the pretrained model and cannot train or send the rest. Its
+// update declares the coordinate ranges it trained (from `ModelSpec::tensor_ranges`
+// for named layers) and carries only their values, or the full vector with the
+// frozen coordinates left as received.
+//
+// During validation a partial update is expanded to the full model, with the frozen
+// coordinates taken from the current global model, so every later stage sees an
+// ordinary dense update. Weighted averaging then merges per coordinate: each
+// coordinate averages, by data size, only the updates that trained it, and keeps its
+// global value when none did. Counting a clinic's frozen layers as "unchanged" would
+// instead drag every layer it did not train back toward the old model in proportion
+// to its data. Robust aggregators, which rank or compare whole updates, see the
+// expanded updates as they are.
+
+use crate::*;
+use std::ops::Range;
+
+// Coordinates [start, end) of the flat model
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LayerRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LayerRange {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ModelSpec {
+    // Coordinate ranges of the named tensors, in model order
+    pub fn tensor_ranges(&self, names: &[&str]) -> Result<Vec<LayerRange>, String> {
+        if let Some(unknown) = names.iter().find(|name| !self.tensors.iter().any(|t| t.name == **name)) {
+            return Err(format!("Model spec has no tensor {}", unknown));
+        }
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for tensor in &self.tensors {
+            if names.contains(&tensor.name.as_str()) {
+                ranges.push(LayerRange { start, end: start + tensor.len() });
+            }
+            start += tensor.len();
+        }
+        Ok(ranges)
+    }
+}
+
+// Non-empty, ordered, non-overlapping and inside the model; returns the coordinates covered
+pub fn validate_trained_ranges(ranges: &[LayerRange], dimension: usize) -> Result<usize, String> {
+    if ranges.is_empty() || ranges.iter().any(LayerRange::is_empty) {
+        return Err("Trained ranges must be non-empty".to_string());
+    }
+    if ranges.windows(2).any(|pair| pair[0].end > pair[1].start) {
+        return Err("Trained ranges must be ordered and must not overlap".to_string());
+    }
+    match ranges.last() {
+        Some(last) if last.end > dimension => Err(format!("Trained range ends at {}, model has {}", last.end, dimension)),
+        _ => Ok(ranges.iter().map(LayerRange::len).sum()),
+    }
+}
+
+// The values a client sends for the layers it trained
+pub fn trained_values(weights: &[f64], ranges: &[LayerRange]) -> Vec<f64> {
+    ranges.iter().flat_map(|r| weights[r.start.min(weights.len())..r.end.min(weights.len())].iter().copied()).collect()
+}
+
+impl AggregationEngine {
+    // Weighted average by data size per coordinate, over the updates that trained it
+    pub fn partial_weighted_average(&self, updates: &[ModelUpdate], global_weights: &[f64]) -> Result<Vec<f64>, String> {
+        if updates.is_empty() {
+            return Err("No updates to aggregate".to_string());
+        }
+        let dimension = global_weights.len();
+        let full = [LayerRange { start: 0, end: dimension }];
+        let mut sums = vec![0.0; dimension];
+        let mut weights = vec![0.0; dimension];
+        for update in updates {
+            let dense;
+            let values = match update.payload() {
+                GradientPayload::Dense(gradients) => gradients,
+                GradientPayload::Sparse(sparse) => {
+                    dense = sparse.to_dense(dimension);
+                    &dense
+                }
+            };
+            if values.len() != dimension {
+                return Err(format!("Update from {} has {} values, model has {}", update.client_id, values.len(), dimension));
+            }
+            let weight = update.data_size as f64;
+            for range in update.trained_ranges.as_deref().unwrap_or(&full) {
+                for i in range.range() {
+                    sums[i] += weight * values[i];
+                    weights[i] += weight;
+                }
+            }
+        }
+        Ok((0..dimension).map(|i| if weights[i] > 0.0 { sums[i] / weights[i] } else { global_weights[i] }).collect())
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Expands a partial update to the full model, frozen coordinates from the global model
+    pub(crate) fn expand_partial_update(&self, update: &mut ModelUpdate) -> Result<(), String> {
+        let ranges = match &update.trained_ranges {
+            Some(ranges) => ranges,
+            None => return Ok(()),
+        };
+        if update.sparse_gradients.is_some() {
+            return Err("Partial updates must be dense".to_string());
+        }
+        let dimension = self.global_model.weights.len();
+        let covered = validate_trained_ranges(ranges, dimension)?;
+        if update.gradients.len() == dimension {
+            return Ok(());
+        }
+        if update.gradients.len() != covered {
+            return Err(format!("{} values for {} trained coordinates", update.gradients.len(), covered));
+        }
+        let mut full = self.global_model.weights.clone();
+        let mut values = update.gradients.iter();
+        for range in ranges {
+            full[range.range()].iter_mut().zip(values.by_ref()).for_each(|(slot, value)| *slot = *value);
+        }
+        update.gradients = full;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, gradients: Vec<f64>, data_size: usize, trained_ranges: Option<Vec<LayerRange>>) -> ModelUpdate {
+        ModelUpdate {
+            loss: 1.0,
+            accuracy: 0.5,
+            trained_ranges,
+            ..ModelUpdate::for_test(client_id, gradients, data_size)
+        }
+    }
+
+    #[test]
+    fn test_partial_updates_merge_per_trained_coordinate() {
+        let spec = ModelSpec {
+            architecture: "mlp".to_string(),
+            tensors: vec![
+                TensorSpec { name: "backbone".to_string(), shape: vec![3] },
+                TensorSpec { name: "head.weight".to_string(), shape: vec![2] },
+                TensorSpec { name: "head.bias".to_string(), shape: vec![1] },
+            ],
+        };
+        let head = spec.tensor_ranges(&["head.weight", "head.bias"]).unwrap();
+        assert_eq!(head, vec![LayerRange { start: 3, end: 5 }, LayerRange { start: 5, end: 6 }]);
+        assert!(spec.tensor_ranges(&["decoder"]).is_err());
+        assert!(validate_trained_ranges(&[LayerRange { start: 2, end: 4 }, LayerRange { start: 3, end: 5 }], 6).is_err());
+        assert!(validate_trained_ranges(&[LayerRange { start: 4, end: 7 }], 6).is_err());
+
+        let config = FederatedLearningConfigBuilder::new().model_dimension(6).min_clients(2).privacy_method(PrivacyMethod::None).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new_with_weights(config, vec![1.0; 6]).unwrap();
+        let clinic_weights = [1.0, 1.0, 1.0, 4.0, 4.0, 4.0];
+        let mut clinic = update("clinic", trained_values(&clinic_weights, &head), 300, Some(head.clone()));
+        coordinator.expand_partial_update(&mut clinic).unwrap();
+        assert_eq!(clinic.gradients, clinic_weights.to_vec());
+
+        // The hospital trains everything; only the clinic's head counts toward the head
+        let hospital = update("hospital", vec![2.0; 6], 100, None);
+        let engine = AggregationEngine::new();
+        let merged = engine.partial_weighted_average(&[hospital.clone(), clinic], &[1.0; 6]).unwrap();
+        assert_eq!(merged, vec![2.0, 2.0, 2.0, 3.5, 3.5, 3.5]);
+
+        // Through a round: the backbone is the hospital's alone, not pulled back by the clinic
+        let compact = update("clinic", vec![4.0; 3], 300, Some(head));
+        let model = coordinator.execute_round(vec![hospital, compact]).unwrap();
+        assert_eq!(model.weights[..3], [2.0, 2.0, 2.0]);
+        let mut short = update("clinic", vec![4.0; 2], 300, Some(vec![LayerRange { start: 3, end: 6 }]));
+        assert!(coordinator.expand_partial_update(&mut short).is_err());
+    }
+}
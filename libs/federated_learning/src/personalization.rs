@@ -0,0 +1,141 @@
+// Personalized federated learning. The global model trains as usual; alongside it the
+// coordinator keeps a personalized head per client: the trailing `head_size` weights,
+// or the whole model when `head_size` is 0. Following the aggregation convention a
+// client's update carries its locally trained model w_k, so w_k - v stands in for a
+// descent step on the client's own loss. With w the global model before the round:
+//   Ditto:      v <- v + lr * ((w_k - v) - lambda * (v - w))
+//   Per-FedAvg: v <- w + alpha * (w_k - w)
+// Clients evaluate their personalized model on local data and report its accuracy
+// with their update; the round records it per client in the global model.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum PersonalizationMethod {
+    // Larger lambda keeps personal heads closer to the global model
+    Ditto { lambda: f64, learning_rate: f64 },
+    // One adaptation step of size alpha from the global model
+    PerFedAvg { alpha: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PersonalizationConfig {
+    pub method: PersonalizationMethod,
+    pub head_size: usize,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PersonalizationState {
+    heads: HashMap<String, Vec<f64>>,
+}
+
+impl PersonalizationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.method {
+            PersonalizationMethod::Ditto { lambda, learning_rate } => {
+                if !(lambda.is_finite() && *lambda >= 0.0) {
+                    return Err("Ditto lambda must be non-negative".to_string());
+                }
+                if !(*learning_rate > 0.0 && *learning_rate <= 1.0) {
+                    return Err("Ditto learning_rate must be in (0, 1]".to_string());
+                }
+            }
+            PersonalizationMethod::PerFedAvg { alpha } => {
+                if !(*alpha > 0.0 && *alpha <= 1.0) {
+                    return Err("Per-FedAvg alpha must be in (0, 1]".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Index where the head starts in a model of `len` weights
+    fn head_start(&self, len: usize) -> usize {
+        if self.head_size == 0 {
+            0
+        } else {
+            len.saturating_sub(self.head_size)
+        }
+    }
+}
+
+impl PersonalizationState {
+    pub fn new() -> Self {
+        PersonalizationState::default()
+    }
+
+    pub fn head(&self, client_id: &str) -> Option<&[f64]> {
+        self.heads.get(client_id).map(|h| h.as_slice())
+    }
+
+    pub fn update(&mut self, config: &PersonalizationConfig, global_weights: &[f64], updates: &[ModelUpdate]) {
+        let start = config.head_start(global_weights.len());
+        let global_head = &global_weights[start..];
+
+        for update in updates {
+            if update.gradients.len() != global_weights.len() {
+                continue;
+            }
+            let local_head = &update.gradients[start..];
+            let head = match &config.method {
+                PersonalizationMethod::Ditto { lambda, learning_rate } => {
+                    let current = self.heads.get(&update.client_id).map(|h| h.as_slice()).unwrap_or(global_head);
+                    current
+                        .iter()
+                        .zip(local_head.iter().zip(global_head.iter()))
+                        .map(|(&v, (&local, &global))| v + learning_rate * ((local - v) - lambda * (v - global)))
+                        .collect()
+                }
+                PersonalizationMethod::PerFedAvg { alpha } => global_head
+                    .iter()
+                    .zip(local_head.iter())
+                    .map(|(&global, &local)| global + alpha * (local - global))
+                    .collect(),
+            };
+            self.heads.insert(update.client_id.clone(), head);
+        }
+    }
+
+    // Global body with the client's head; the global head if it has none yet
+    pub fn personalized_weights(&self, config: &PersonalizationConfig, client_id: &str, global_weights: &[f64]) -> Vec<f64> {
+        let mut weights = global_weights.to_vec();
+        let start = config.head_start(weights.len());
+        if let Some(head) = self.heads.get(client_id) {
+            if head.len() == weights.len() - start {
+                weights[start..].copy_from_slice(head);
+            }
+        }
+        weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(client_id: &str, gradients: Vec<f64>) -> ModelUpdate {
+        ModelUpdate::for_test(client_id, gradients, 1)
+    }
+
+    #[test]
+    fn test_personal_heads_track_clients_and_keep_global_body() {
+        let global = vec![0.0, 0.0, 0.0];
+        let per_fedavg = PersonalizationConfig { method: PersonalizationMethod::PerFedAvg { alpha: 0.5 }, head_size: 1 };
+        let mut state = PersonalizationState::new();
+        state.update(&per_fedavg, &global, &[update("a", vec![1.0, 1.0, 4.0]), update("b", vec![1.0, 1.0, -2.0])]);
+        assert_eq!(state.personalized_weights(&per_fedavg, "a", &global), vec![0.0, 0.0, 2.0]);
+        assert_eq!(state.personalized_weights(&per_fedavg, "b", &global), vec![0.0, 0.0, -1.0]);
+        assert_eq!(state.personalized_weights(&per_fedavg, "c", &global), global);
+
+        // Without regularization Ditto follows the local model; a large lambda holds it near global
+        let loose = PersonalizationConfig { method: PersonalizationMethod::Ditto { lambda: 0.0, learning_rate: 1.0 }, head_size: 0 };
+        let mut state = PersonalizationState::new();
+        state.update(&loose, &global, &[update("a", vec![1.0, 2.0, 3.0])]);
+        assert_eq!(state.head("a").unwrap(), &[1.0, 2.0, 3.0]);
+
+        let tight = PersonalizationConfig { method: PersonalizationMethod::Ditto { lambda: 1.0, learning_rate: 0.5 }, ..loose };
+        assert!(tight.validate().is_ok());
+        state.update(&tight, &global, &[update("a", vec![1.0, 2.0, 3.0])]);
+        assert_eq!(state.head("a").unwrap(), &[0.5, 1.0, 1.5]);
+    }
+}
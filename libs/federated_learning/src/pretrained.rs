@@ -0,0 +1,96 @@
+// Warm start from a centrally pretrained model. Weights travel in a small binary
+// format: the magic "FLPM", a format version byte, the weight count as a
+// little-endian u64, the weights as little-endian f64, and a SHA-256 over everything
+// before it, so a truncated or corrupted upload is rejected rather than loaded.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 4] = b"FLPM";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 8;
+const DIGEST_LEN: usize = 32;
+
+pub fn encode_pretrained(weights: &[f64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + weights.len() * 8 + DIGEST_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(FORMAT_VERSION);
+    bytes.extend_from_slice(&(weights.len() as u64).to_le_bytes());
+    for weight in weights {
+        bytes.extend_from_slice(&weight.to_le_bytes());
+    }
+    let digest = Sha256::digest(&bytes);
+    bytes.extend_from_slice(&digest);
+    bytes
+}
+
+pub fn decode_pretrained(bytes: &[u8]) -> Result<Vec<f64>, String> {
+    if bytes.len() < HEADER_LEN + DIGEST_LEN || &bytes[..4] != MAGIC {
+        return Err("Not a pretrained model file".to_string());
+    }
+    if bytes[4] != FORMAT_VERSION {
+        return Err(format!("Unsupported pretrained model format version {}", bytes[4]));
+    }
+    let (body, digest) = bytes.split_at(bytes.len() - DIGEST_LEN);
+    if Sha256::digest(body).as_slice() != digest {
+        return Err("Pretrained model checksum mismatch".to_string());
+    }
+
+    let count = u64::from_le_bytes(body[5..HEADER_LEN].try_into().map_err(|_| "Malformed header")?) as usize;
+    let payload = &body[HEADER_LEN..];
+    if count.checked_mul(8) != Some(payload.len()) {
+        return Err(format!("Pretrained model declares {} weights but carries {} bytes", count, payload.len()));
+    }
+    Ok(payload.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap_or([0; 8]))).collect())
+}
+
+pub(crate) fn validate_initial_weights(config: &FederatedLearningConfig, weights: &[f64]) -> Result<(), String> {
+    if weights.len() != config.model_dimension {
+        return Err(format!(
+            "Initial model has {} weights, config model_dimension is {}",
+            weights.len(),
+            config.model_dimension
+        ));
+    }
+    if weights.iter().any(|w| !w.is_finite()) {
+        return Err("Initial model weights must be finite".to_string());
+    }
+    Ok(())
+}
+
+impl FederatedLearningCoordinator {
+    // Replaces the initial global model before the first round
+    pub fn load_pretrained(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if self.global_model.round > 0 || !self.round_history.is_empty() {
+            return Err("A pretrained model can only be loaded before the first round".to_string());
+        }
+        let weights = decode_pretrained(bytes)?;
+        validate_initial_weights(&self.config, &weights)?;
+        self.global_model.weights = weights;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_start_from_pretrained_bytes() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(4).build().unwrap();
+        let pretrained = vec![0.5, -1.25, 3.0, 0.0];
+        let bytes = encode_pretrained(&pretrained);
+        assert_eq!(decode_pretrained(&bytes).unwrap(), pretrained);
+
+        let mut coordinator = FederatedLearningCoordinator::new(config.clone()).unwrap();
+        assert_eq!(coordinator.get_global_model().weights, vec![0.0; 4]);
+        coordinator.load_pretrained(&bytes).unwrap();
+        assert_eq!(coordinator.get_global_model().weights, pretrained);
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN] ^= 0x01;
+        assert!(coordinator.load_pretrained(&corrupted).is_err());
+        assert!(coordinator.load_pretrained(&encode_pretrained(&[1.0; 3])).is_err());
+        assert!(FederatedLearningCoordinator::new_with_weights(config, vec![0.0; 5]).is_err());
+    }
+}
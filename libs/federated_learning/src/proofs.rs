@@ -0,0 +1,207 @@
+// Zero-knowledge proofs that a client computed its update as the protocol requires.
+// Two schemes are recognised: a range proof that the update's L2 norm is within the
+// clipping bound (a Bulletproofs-style proof over a commitment to the update), and a
+// zk-SNARK that the update is the result of training the announced global model on
+// the data the client committed to before the round. The first matters once the
+// coordinator cannot inspect updates itself; the second is the only evidence that an
+// update came from the client's real records at all.
+//
+// As with TEE quotes, the crate checks the proof's public statement and leaves the
+// cryptography to a verifier per scheme: the statement must bind this exact update
+// (its `update_digest`), claim a norm bound no looser than the clipping policy's, and
+// for a training proof name the client's registered data commitment and the current
+// global model. Proofs are optional unless the policy requires them; without a policy
+// they are ignored.
+
+use crate::*;
+use sha2::{Digest, Sha256};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ProofScheme {
+    ClippedNormRange,
+    TrainingSnark,
+}
+
+// Public inputs the proof is verified against
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProofStatement {
+    pub update_digest: String,
+    // L2 norm the update is proven not to exceed
+    pub norm_bound: f64,
+    // Hex commitment (e.g. a Merkle root) to the local training records
+    pub data_commitment: Option<String>,
+    // `model_digest` of the global weights training started from
+    pub model_digest: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GradientProof {
+    pub scheme: ProofScheme,
+    pub statement: ProofStatement,
+    pub proof: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProofPolicy {
+    // Reject updates that carry no proof
+    pub require_proof: bool,
+    pub accepted_schemes: Vec<ProofScheme>,
+}
+
+// Proof-system verification (pairing checks, inner-product arguments) for one scheme
+pub trait ProofVerifier {
+    fn scheme(&self) -> ProofScheme;
+    fn verify_proof(&self, statement: &ProofStatement, proof: &[u8]) -> Result<(), String>;
+}
+
+impl Default for ProofPolicy {
+    fn default() -> Self {
+        ProofPolicy { require_proof: false, accepted_schemes: vec![ProofScheme::ClippedNormRange, ProofScheme::TrainingSnark] }
+    }
+}
+
+impl ProofPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.accepted_schemes.is_empty() {
+            return Err("Proof policy accepts no scheme".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Hex SHA-256 of the model weights, so a training proof names the model it started from
+pub fn model_digest(weights: &[f64]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"fl-model-weights");
+    for value in weights {
+        hasher.update(value.to_be_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+impl FederatedLearningCoordinator {
+    pub fn set_proof_policy(&mut self, policy: Option<ProofPolicy>) -> Result<(), String> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        self.proof_policy = policy;
+        Ok(())
+    }
+
+    // Replaces any verifier registered for the same scheme
+    pub fn set_proof_verifier(&mut self, verifier: Box<dyn ProofVerifier>) {
+        self.proof_verifiers.retain(|v| v.scheme() != verifier.scheme());
+        self.proof_verifiers.push(verifier);
+    }
+
+    // A client commits to its training data before the round it proves training on
+    pub fn register_data_commitment(&mut self, client_id: &str, commitment: String) -> Result<(), String> {
+        if !self.is_client_authorized(client_id) {
+            return Err(format!("Client {} is not authorized", client_id));
+        }
+        self.data_commitments.insert(client_id.to_string(), commitment);
+        Ok(())
+    }
+
+    pub fn check_gradient_proof(&self, update: &ModelUpdate) -> Result<(), String> {
+        let policy = match &self.proof_policy {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        let proof = match &update.gradient_proof {
+            Some(proof) => proof,
+            None if policy.require_proof => return Err("Proof required".to_string()),
+            None => return Ok(()),
+        };
+        if !policy.accepted_schemes.contains(&proof.scheme) {
+            return Err(format!("Proof scheme {:?} is not accepted", proof.scheme));
+        }
+
+        let statement = &proof.statement;
+        if !statement.update_digest.eq_ignore_ascii_case(&update_digest(update)) {
+            return Err("Proof does not bind this update".to_string());
+        }
+        if let Some(max_norm) = self.clipping_policy.max_norm {
+            if statement.norm_bound.is_nan() || statement.norm_bound > max_norm {
+                return Err(format!("Proven norm bound {} exceeds the clipping bound {}", statement.norm_bound, max_norm));
+            }
+        }
+        if proof.scheme == ProofScheme::TrainingSnark {
+            let registered = self.data_commitments.get(&update.client_id);
+            if registered.is_none() || statement.data_commitment.as_ref() != registered {
+                return Err("Proof does not name the client's registered data commitment".to_string());
+            }
+            if statement.model_digest.as_deref() != Some(model_digest(&self.global_model.weights).as_str()) {
+                return Err("Proof does not start from the current global model".to_string());
+            }
+        }
+
+        match self.proof_verifiers.iter().find(|v| v.scheme() == proof.scheme) {
+            Some(verifier) => verifier.verify_proof(statement, &proof.proof),
+            None => Err(format!("No verifier configured for {:?}", proof.scheme)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for a real proof system: accepts the proof bytes "valid"
+    struct FixedVerifier(ProofScheme);
+
+    impl ProofVerifier for FixedVerifier {
+        fn scheme(&self) -> ProofScheme {
+            self.0
+        }
+
+        fn verify_proof(&self, _statement: &ProofStatement, proof: &[u8]) -> Result<(), String> {
+            if proof == b"valid" {
+                Ok(())
+            } else {
+                Err("Proof does not verify".to_string())
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_statement_binds_update_bound_and_data() {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(2).min_clients(1).build().unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        coordinator.register_client("hospital_a", 50);
+        coordinator.set_clipping_policy(ClippingPolicy { max_norm: Some(1.0) }).unwrap();
+        coordinator.set_proof_policy(Some(ProofPolicy { require_proof: true, ..ProofPolicy::default() })).unwrap();
+        coordinator.set_proof_verifier(Box::new(FixedVerifier(ProofScheme::TrainingSnark)));
+        coordinator.register_data_commitment("hospital_a", "c0ffee".to_string()).unwrap();
+
+        let mut update = ModelUpdate::for_test("hospital_a", vec![0.3, -0.4], 50);
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+
+        let statement = ProofStatement {
+            update_digest: update_digest(&update),
+            norm_bound: 1.0,
+            data_commitment: Some("c0ffee".to_string()),
+            model_digest: Some(model_digest(&[0.0, 0.0])),
+        };
+        let proof = |scheme, statement: &ProofStatement, bytes: &[u8]| Some(GradientProof { scheme, statement: statement.clone(), proof: bytes.to_vec() });
+        update.gradient_proof = proof(ProofScheme::TrainingSnark, &statement, b"valid");
+        assert_eq!(coordinator.check_gradient_proof(&update), Ok(()));
+
+        // A bad proof, a looser bound, other data, or no verifier for the scheme
+        update.gradient_proof = proof(ProofScheme::TrainingSnark, &statement, b"forged");
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+        let loose = ProofStatement { norm_bound: 5.0, ..statement.clone() };
+        update.gradient_proof = proof(ProofScheme::TrainingSnark, &loose, b"valid");
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+        let other_data = ProofStatement { data_commitment: Some("beef".to_string()), ..statement.clone() };
+        update.gradient_proof = proof(ProofScheme::TrainingSnark, &other_data, b"valid");
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+        update.gradient_proof = proof(ProofScheme::ClippedNormRange, &statement, b"valid");
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+
+        // Changing the update after proving breaks the binding
+        update.gradient_proof = proof(ProofScheme::TrainingSnark, &statement, b"valid");
+        update.gradients[0] = 0.2;
+        assert!(coordinator.check_gradient_proof(&update).is_err());
+    }
+}
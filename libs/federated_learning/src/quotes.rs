@@ -0,0 +1,224 @@
+// Quote verification for the TrustedExecutionEnvironment privacy method: the
+// `QuoteVerifier` the coordinator runs after the attestation policy checks, for
+// Intel SGX DCAP quotes and AMD SEV-SNP attestation reports. The policy has only
+// compared the measurement and report data the client declared; here both are read
+// out of the signed quote itself, so a client cannot claim an allowlisted enclave it
+// did not run.
+//
+// SGX (ECDSA quote v3): the enclave report is signed by an attestation key, which the
+// quoting enclave vouches for by placing SHA-256(attestation key || QE auth data) in
+// its own report, signed by the platform's PCK key. SEV-SNP: the report is signed by
+// the chip's VCEK with ECDSA P-384 over SHA-384. Both trust roots are keys the
+// consortium enrols per platform, after checking the PCK or VCEK certificate chain
+// and TCB status against Intel's or AMD's collateral; that happens when a hospital's
+// hardware is onboarded, not per update. Debug enclaves and guests, whose memory the
+// host can read, are refused unless `allow_debug` is set for testing.
+
+use crate::*;
+use p256::ecdsa::signature::Verifier;
+use sha2::{Digest, Sha256};
+
+const SGX_HEADER_LEN: usize = 48;
+const SGX_REPORT_LEN: usize = 384;
+const SGX_SIGNED_LEN: usize = SGX_HEADER_LEN + SGX_REPORT_LEN;
+const SGX_ATTESTATION_KEY_TYPE_P256: u16 = 2;
+const SGX_ATTRIBUTE_DEBUG: u64 = 1 << 1;
+// Offsets within an SGX report body
+const SGX_ATTRIBUTES: usize = 48;
+const SGX_MR_ENCLAVE: usize = 64;
+const SGX_REPORT_DATA: usize = 320;
+
+const SNP_REPORT_LEN: usize = 0x4a0;
+const SNP_SIGNED_LEN: usize = 0x2a0;
+const SNP_SIGNATURE_ALGO_P384: u32 = 1;
+const SNP_POLICY_DEBUG: u64 = 1 << 19;
+const SNP_REPORT_DATA: usize = 0x50;
+const SNP_MEASUREMENT: usize = 0x90;
+// r and s are little-endian, zero-padded to 72 bytes each
+const SNP_SIGNATURE_COMPONENT_LEN: usize = 72;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PlatformQuoteVerifier {
+    // SEC1-encoded P-256 PCK public keys of enrolled SGX platforms
+    pub sgx_pck_keys: Vec<Vec<u8>>,
+    // SEC1-encoded P-384 VCEK public keys of enrolled SEV-SNP chips
+    pub snp_vcek_keys: Vec<Vec<u8>>,
+    pub allow_debug: bool,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap_or_default())
+}
+
+// Splits `len` bytes off the front of `rest`
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if rest.len() < len {
+        return Err("Quote is truncated".to_string());
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
+
+// The quote must carry what the client declared: its measurement, and the update
+// digest in the first half of the report data
+fn check_binding(attestation: &TeeAttestation, measurement: &[u8], report_data: &[u8]) -> Result<(), String> {
+    if !attestation.measurement.eq_ignore_ascii_case(&to_hex(measurement)) {
+        return Err("Quote measurement differs from the declared one".to_string());
+    }
+    if !attestation.report_data.eq_ignore_ascii_case(&to_hex(&report_data[..32])) {
+        return Err("Quote report data differs from the declared one".to_string());
+    }
+    Ok(())
+}
+
+impl PlatformQuoteVerifier {
+    fn verify_sgx(&self, attestation: &TeeAttestation) -> Result<(), String> {
+        let quote = attestation.quote.as_slice();
+        let mut rest = quote;
+        let header = take(&mut rest, SGX_HEADER_LEN)?;
+        let report = take(&mut rest, SGX_REPORT_LEN)?;
+        if read_u16(header, 0) != 3 || read_u16(header, 2) != SGX_ATTESTATION_KEY_TYPE_P256 {
+            return Err("Only ECDSA P-256 SGX quotes of version 3 are supported".to_string());
+        }
+        let signature_len = read_u32(take(&mut rest, 4)?, 0) as usize;
+        let mut signature_data = take(&mut rest, signature_len)?;
+        let report_signature = take(&mut signature_data, 64)?;
+        let attestation_key_bytes = take(&mut signature_data, 64)?;
+        let qe_report = take(&mut signature_data, SGX_REPORT_LEN)?;
+        let qe_report_signature = take(&mut signature_data, 64)?;
+        let auth_len = read_u16(take(&mut signature_data, 2)?, 0) as usize;
+        let qe_auth_data = take(&mut signature_data, auth_len)?;
+
+        // Enclave report, signed by the attestation key
+        let attestation_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&[&[0x04], attestation_key_bytes].concat())
+            .map_err(|_| "Invalid SGX attestation key".to_string())?;
+        let signature = p256::ecdsa::Signature::from_slice(report_signature).map_err(|_| "Malformed SGX quote signature".to_string())?;
+        attestation_key.verify(&quote[..SGX_SIGNED_LEN], &signature).map_err(|_| "SGX quote signature does not verify".to_string())?;
+
+        // Attestation key, vouched for by the quoting enclave's PCK-signed report
+        let expected = Sha256::new().chain_update(attestation_key_bytes).chain_update(qe_auth_data).finalize();
+        if qe_report[SGX_REPORT_DATA..SGX_REPORT_DATA + 32] != expected[..] {
+            return Err("Quoting enclave does not vouch for the attestation key".to_string());
+        }
+        let qe_signature = p256::ecdsa::Signature::from_slice(qe_report_signature).map_err(|_| "Malformed QE report signature".to_string())?;
+        let vouched = self.sgx_pck_keys.iter().any(|key| {
+            p256::ecdsa::VerifyingKey::from_sec1_bytes(key).is_ok_and(|key| key.verify(qe_report, &qe_signature).is_ok())
+        });
+        if !vouched {
+            return Err("QE report is not signed by an enrolled PCK key".to_string());
+        }
+
+        if !self.allow_debug && read_u64(report, SGX_ATTRIBUTES) & SGX_ATTRIBUTE_DEBUG != 0 {
+            return Err("Enclave runs in debug mode".to_string());
+        }
+        check_binding(attestation, &report[SGX_MR_ENCLAVE..SGX_MR_ENCLAVE + 32], &report[SGX_REPORT_DATA..SGX_REPORT_DATA + 64])
+    }
+
+    fn verify_snp(&self, attestation: &TeeAttestation) -> Result<(), String> {
+        let report = attestation.quote.as_slice();
+        if report.len() != SNP_REPORT_LEN {
+            return Err(format!("SEV-SNP report has {} bytes, expected {}", report.len(), SNP_REPORT_LEN));
+        }
+        if read_u32(report, 0x34) != SNP_SIGNATURE_ALGO_P384 {
+            return Err("Only ECDSA P-384 SEV-SNP reports are supported".to_string());
+        }
+
+        let big_endian = |at: usize| -> Vec<u8> { report[at..at + 48].iter().rev().copied().collect() };
+        let r = big_endian(SNP_SIGNED_LEN);
+        let s = big_endian(SNP_SIGNED_LEN + SNP_SIGNATURE_COMPONENT_LEN);
+        let signature = p384::ecdsa::Signature::from_slice(&[r, s].concat()).map_err(|_| "Malformed SEV-SNP report signature".to_string())?;
+        let signed = self.snp_vcek_keys.iter().any(|key| {
+            p384::ecdsa::VerifyingKey::from_sec1_bytes(key).is_ok_and(|key| key.verify(&report[..SNP_SIGNED_LEN], &signature).is_ok())
+        });
+        if !signed {
+            return Err("SEV-SNP report is not signed by an enrolled VCEK".to_string());
+        }
+
+        if !self.allow_debug && read_u64(report, 0x08) & SNP_POLICY_DEBUG != 0 {
+            return Err("Guest policy allows debugging".to_string());
+        }
+        check_binding(attestation, &report[SNP_MEASUREMENT..SNP_MEASUREMENT + 48], &report[SNP_REPORT_DATA..SNP_REPORT_DATA + 64])
+    }
+}
+
+impl QuoteVerifier for PlatformQuoteVerifier {
+    fn verify_quote(&self, attestation: &TeeAttestation) -> Result<(), String> {
+        match attestation.platform {
+            TeePlatform::IntelSgx => self.verify_sgx(attestation),
+            TeePlatform::AmdSevSnp => self.verify_snp(attestation),
+            TeePlatform::IntelTdx | TeePlatform::AwsNitro => {
+                Err(format!("No quote verification for {:?}", attestation.platform))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+
+    fn sgx_quote(pck: &p256::ecdsa::SigningKey, mr_enclave: [u8; 32], report_data: &[u8]) -> Vec<u8> {
+        let attestation_key = p256::ecdsa::SigningKey::from_slice(&[5; 32]).unwrap();
+        let public = attestation_key.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec();
+        let mut quote = vec![0; SGX_SIGNED_LEN];
+        quote[0] = 3;
+        quote[2] = SGX_ATTESTATION_KEY_TYPE_P256 as u8;
+        quote[SGX_HEADER_LEN + SGX_MR_ENCLAVE..][..32].copy_from_slice(&mr_enclave);
+        quote[SGX_HEADER_LEN + SGX_REPORT_DATA..][..32].copy_from_slice(report_data);
+        let report_signature: p256::ecdsa::Signature = attestation_key.sign(&quote);
+
+        let qe_auth_data = b"qe-auth";
+        let mut qe_report = vec![0; SGX_REPORT_LEN];
+        let vouch = Sha256::new().chain_update(&public).chain_update(qe_auth_data).finalize();
+        qe_report[SGX_REPORT_DATA..][..32].copy_from_slice(&vouch);
+        let qe_signature: p256::ecdsa::Signature = pck.sign(&qe_report);
+
+        let signature_data =
+            [&report_signature.to_bytes()[..], &public, &qe_report, &qe_signature.to_bytes(), &(qe_auth_data.len() as u16).to_le_bytes(), qe_auth_data]
+                .concat();
+        quote.extend_from_slice(&(signature_data.len() as u32).to_le_bytes());
+        quote.extend_from_slice(&signature_data);
+        quote
+    }
+
+    #[test]
+    fn test_sgx_quote_is_verified_and_bound_to_the_declared_update() {
+        let pck = p256::ecdsa::SigningKey::from_slice(&[9; 32]).unwrap();
+        let verifier = PlatformQuoteVerifier {
+            sgx_pck_keys: vec![pck.verifying_key().to_encoded_point(true).as_bytes().to_vec()],
+            ..PlatformQuoteVerifier::default()
+        };
+        let digest = Sha256::digest(b"update");
+        let attestation = TeeAttestation {
+            platform: TeePlatform::IntelSgx,
+            quote: sgx_quote(&pck, [0xab; 32], &digest),
+            measurement: to_hex(&[0xab; 32]),
+            report_data: to_hex(&digest),
+        };
+        assert_eq!(verifier.verify_quote(&attestation), Ok(()));
+
+        // Declaring another enclave, altering the quote, or a platform key nobody enrolled
+        let claimed = TeeAttestation { measurement: to_hex(&[0xcd; 32]), ..attestation.clone() };
+        assert!(verifier.verify_quote(&claimed).is_err());
+        let mut tampered = attestation.clone();
+        tampered.quote[SGX_HEADER_LEN + SGX_MR_ENCLAVE] ^= 1;
+        assert!(verifier.verify_quote(&tampered).is_err());
+        let rogue = p256::ecdsa::SigningKey::from_slice(&[3; 32]).unwrap();
+        let unenrolled = TeeAttestation { quote: sgx_quote(&rogue, [0xab; 32], &digest), ..attestation };
+        assert!(verifier.verify_quote(&unenrolled).is_err());
+    }
+}
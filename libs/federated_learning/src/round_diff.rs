@@ -0,0 +1,171 @@
+// What changed between two rounds. When accuracy drops suddenly, the first questions
+// from operations are who was in the round that was not before, which part of the
+// model moved, and how much privacy budget went with it. `diff_rounds` answers them
+// from two `GlobalModel` snapshots: clients that joined or left, the L2 norm of the
+// weight change per layer, metric deltas, and the privacy spent in between.
+//
+// Layers come from a `ModelSpec`; without one the whole model is a single layer.
+// Round history stores metadata without weights, so the coordinator method rebuilds
+// both rounds' weights from the checkpoint store before diffing.
+
+use crate::*;
+use std::collections::HashSet;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LayerChange {
+    pub name: String,
+    pub change_norm: f64,
+    // Change norm over the layer's norm in the earlier round; 0 when that is zero
+    pub relative_change: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoundDiff {
+    pub from_round: u64,
+    pub to_round: u64,
+    pub joined_clients: Vec<String>,
+    pub left_clients: Vec<String>,
+    pub weight_change_norm: f64,
+    pub layer_changes: Vec<LayerChange>,
+    pub loss_delta: f64,
+    pub accuracy_delta: f64,
+    pub gradient_norm_delta: f64,
+    pub effective_sample_count_delta: i64,
+    pub updates_clipped_delta: i64,
+    pub epsilon_spent: f64,
+    pub delta_spent: f64,
+    // Per-client epsilon spent between the rounds, for clients that spent any
+    pub client_epsilon_spent: HashMap<String, f64>,
+}
+
+pub fn diff_rounds(a: &GlobalModel, b: &GlobalModel) -> Result<RoundDiff, String> {
+    diff_rounds_with_spec(a, b, &ModelSpec::single_tensor("model", b.weights.len()))
+}
+
+pub fn diff_rounds_with_spec(a: &GlobalModel, b: &GlobalModel, spec: &ModelSpec) -> Result<RoundDiff, String> {
+    if a.weights.len() != b.weights.len() {
+        return Err(format!("Round {} has {} weights, round {} has {}", a.round, a.weights.len(), b.round, b.weights.len()));
+    }
+    spec.validate(b.weights.len())?;
+
+    let change: Vec<f64> = b.weights.iter().zip(&a.weights).map(|(after, before)| after - before).collect();
+    let mut layer_changes = Vec::with_capacity(spec.tensors.len());
+    let mut offset = 0;
+    for tensor in &spec.tensors {
+        let range = offset..offset + tensor.len();
+        let change_norm = kernels::l2_norm(&change[range.clone()]);
+        let before_norm = kernels::l2_norm(&a.weights[range]);
+        layer_changes.push(LayerChange {
+            name: tensor.name.clone(),
+            change_norm,
+            relative_change: if before_norm > 0.0 { change_norm / before_norm } else { 0.0 },
+        });
+        offset += tensor.len();
+    }
+
+    let before: HashSet<&String> = a.participating_clients.iter().collect();
+    let after: HashSet<&String> = b.participating_clients.iter().collect();
+    let mut joined_clients: Vec<String> = after.difference(&before).map(|c| c.to_string()).collect();
+    let mut left_clients: Vec<String> = before.difference(&after).map(|c| c.to_string()).collect();
+    joined_clients.sort();
+    left_clients.sort();
+
+    let client_epsilon_spent = b
+        .privacy_metrics
+        .privacy_loss_per_client
+        .iter()
+        .map(|(client, epsilon)| (client.clone(), epsilon - a.privacy_metrics.privacy_loss_per_client.get(client).copied().unwrap_or(0.0)))
+        .filter(|(_, spent)| *spent > 0.0)
+        .collect();
+
+    Ok(RoundDiff {
+        from_round: a.round,
+        to_round: b.round,
+        joined_clients,
+        left_clients,
+        weight_change_norm: kernels::l2_norm(&change),
+        layer_changes,
+        loss_delta: b.global_loss - a.global_loss,
+        accuracy_delta: b.global_accuracy - a.global_accuracy,
+        gradient_norm_delta: b.convergence_metrics.gradient_norm - a.convergence_metrics.gradient_norm,
+        effective_sample_count_delta: b.effective_sample_count as i64 - a.effective_sample_count as i64,
+        updates_clipped_delta: b.clipping_stats.updates_clipped as i64 - a.clipping_stats.updates_clipped as i64,
+        epsilon_spent: b.privacy_metrics.total_epsilon_used - a.privacy_metrics.total_epsilon_used,
+        delta_spent: b.privacy_metrics.total_delta_used - a.privacy_metrics.total_delta_used,
+        client_epsilon_spent,
+    })
+}
+
+impl RoundDiff {
+    // Layers ordered by how far they moved, largest first
+    pub fn largest_changes(&self, count: usize) -> Vec<&LayerChange> {
+        let mut layers: Vec<&LayerChange> = self.layer_changes.iter().collect();
+        layers.sort_by(|x, y| y.change_norm.total_cmp(&x.change_norm));
+        layers.truncate(count);
+        layers
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Diff of two rounds from the history, with their weights rebuilt from checkpoints
+    pub fn diff_history_rounds(&self, from_round: u64, to_round: u64, spec: Option<&ModelSpec>) -> Result<RoundDiff, String> {
+        let snapshot = |round: u64| -> Result<GlobalModel, String> {
+            let mut model = self
+                .round_history
+                .iter()
+                .find(|m| m.round == round)
+                .cloned()
+                .ok_or_else(|| format!("Round {} is not in the history as a full snapshot", round))?;
+            model.weights = self
+                .get_round_weights(round)
+                .ok_or_else(|| format!("Weights for round {} are no longer stored", round))?;
+            Ok(model)
+        };
+        let (a, b) = (snapshot(from_round)?, snapshot(to_round)?);
+        match spec {
+            Some(spec) => diff_rounds_with_spec(&a, &b, spec),
+            None => diff_rounds(&a, &b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(round: u64, weights: Vec<f64>, clients: &[&str], accuracy: f64, epsilon: f64) -> GlobalModel {
+        let config = FederatedLearningConfigBuilder::new().model_dimension(weights.len()).build().unwrap();
+        let mut model = FederatedLearningCoordinator::new_with_weights(config, weights).unwrap().get_global_model().clone();
+        model.round = round;
+        model.participating_clients = clients.iter().map(|c| c.to_string()).collect();
+        model.global_accuracy = accuracy;
+        model.privacy_metrics.total_epsilon_used = epsilon;
+        model.privacy_metrics.privacy_loss_per_client = clients.iter().map(|c| (c.to_string(), epsilon / 2.0)).collect();
+        model
+    }
+
+    #[test]
+    fn test_diff_names_new_clients_and_the_layer_that_moved() {
+        let a = snapshot(3, vec![1.0, 1.0, 1.0, 0.5], &["hospital_a", "hospital_b"], 0.9, 1.0);
+        let b = snapshot(4, vec![1.0, 1.0, 1.0, 4.5], &["hospital_b", "hospital_c"], 0.6, 1.6);
+        let spec = ModelSpec {
+            architecture: "mlp".to_string(),
+            tensors: vec![
+                TensorSpec { name: "fc.weight".to_string(), shape: vec![3] },
+                TensorSpec { name: "fc.bias".to_string(), shape: vec![1] },
+            ],
+        };
+
+        let diff = diff_rounds_with_spec(&a, &b, &spec).unwrap();
+        assert_eq!((diff.joined_clients.clone(), diff.left_clients.clone()), (vec!["hospital_c".to_string()], vec!["hospital_a".to_string()]));
+        assert_eq!(diff.largest_changes(1)[0], &LayerChange { name: "fc.bias".to_string(), change_norm: 4.0, relative_change: 8.0 });
+        assert_eq!(diff.layer_changes[0].change_norm, 0.0);
+        assert!((diff.accuracy_delta + 0.3).abs() < 1e-12 && (diff.epsilon_spent - 0.6).abs() < 1e-12);
+        // hospital_b spent 0.3 more, hospital_c its first 0.8; hospital_a spent nothing
+        assert_eq!(diff.client_epsilon_spent.len(), 2);
+        assert!((diff.client_epsilon_spent["hospital_b"] - 0.3).abs() < 1e-12);
+
+        assert_eq!(diff_rounds(&a, &b).unwrap().weight_change_norm, 4.0);
+        assert!(diff_rounds(&a, &snapshot(4, vec![0.0; 3], &[], 0.0, 0.0)).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+// Reduced-precision gradients and weights. The coordinator computes in f64, but
+// updates and broadcasts need not travel or sit in memory at that width: f32 halves
+// them and IEEE half precision quarters them, which is ample for gradients that are
+// noised and clipped anyway. `Scalar` lets aggregation run over any of the three
+// widths, accumulating in f64 so that summing many clients does not lose precision;
+// `PackedVector` is the wire form, and `CompactModelUpdate` a model update carrying
+// its gradients and weights packed.
+
+use crate::*;
+
+pub trait Scalar: Copy + Default + PartialOrd + Send + Sync + 'static {
+    const BYTES: usize;
+
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+// IEEE 754 binary16, stored as its bit pattern
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct F16(pub u16);
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    F64,
+    F32,
+    F16,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PackedVector {
+    F64(Vec<f64>),
+    F32(Vec<f32>),
+    // Bit patterns of binary16 values
+    F16(Vec<u16>),
+}
+
+// A model update whose gradients and weights travel packed; the other fields are
+// those of `update`, whose own vectors are left empty
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CompactModelUpdate {
+    pub update: ModelUpdate,
+    pub gradients: PackedVector,
+    pub weights: PackedVector,
+}
+
+impl Scalar for f64 {
+    const BYTES: usize = 8;
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl Scalar for f32 {
+    const BYTES: usize = 4;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Scalar for F16 {
+    const BYTES: usize = 2;
+
+    fn to_f64(self) -> f64 {
+        self.to_f32() as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        F16::from_f32(value as f32)
+    }
+}
+
+impl F16 {
+    // Round to nearest, ties to even; out-of-range values become infinite
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+
+        if exponent == 0xff {
+            let nan = if mantissa != 0 { 0x200 } else { 0 };
+            return F16(sign | 0x7c00 | nan);
+        }
+        let half_exponent = exponent - 127 + 15;
+        if half_exponent >= 0x1f {
+            return F16(sign | 0x7c00);
+        }
+        if half_exponent <= 0 {
+            // Subnormal in half precision, or too small even for that
+            if half_exponent < -10 {
+                return F16(sign);
+            }
+            let mantissa = mantissa | 0x80_0000;
+            let shift = (14 - half_exponent) as u32;
+            let mut half = mantissa >> shift;
+            let remainder = mantissa & ((1 << shift) - 1);
+            let halfway = 1 << (shift - 1);
+            if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+                half += 1;
+            }
+            return F16(sign | half as u16);
+        }
+
+        let mut half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+        let remainder = mantissa & 0x1fff;
+        // A carry out of the mantissa correctly bumps the exponent
+        if remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1) {
+            half += 1;
+        }
+        F16(sign | half as u16)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x3ff) as u32;
+        let bits = match exponent {
+            0 if mantissa == 0 => sign,
+            0 => {
+                let magnitude = mantissa as f32 * 2f32.powi(-24);
+                return if sign != 0 { -magnitude } else { magnitude };
+            }
+            0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+pub fn to_scalars<T: Scalar>(values: &[f64]) -> Vec<T> {
+    values.iter().map(|&v| T::from_f64(v)).collect()
+}
+
+pub fn to_f64s<T: Scalar>(values: &[T]) -> Vec<f64> {
+    values.iter().map(|v| v.to_f64()).collect()
+}
+
+impl Precision {
+    pub fn bytes(&self) -> usize {
+        match self {
+            Precision::F64 => f64::BYTES,
+            Precision::F32 => f32::BYTES,
+            Precision::F16 => F16::BYTES,
+        }
+    }
+}
+
+impl PackedVector {
+    pub fn pack(values: &[f64], precision: Precision) -> Self {
+        match precision {
+            Precision::F64 => PackedVector::F64(values.to_vec()),
+            Precision::F32 => PackedVector::F32(to_scalars(values)),
+            Precision::F16 => PackedVector::F16(to_scalars::<F16>(values).into_iter().map(|h| h.0).collect()),
+        }
+    }
+
+    pub fn unpack(&self) -> Vec<f64> {
+        match self {
+            PackedVector::F64(values) => values.clone(),
+            PackedVector::F32(values) => to_f64s(values),
+            PackedVector::F16(values) => values.iter().map(|&bits| F16(bits).to_f64()).collect(),
+        }
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self {
+            PackedVector::F64(_) => Precision::F64,
+            PackedVector::F32(_) => Precision::F32,
+            PackedVector::F16(_) => Precision::F16,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            PackedVector::F64(values) => values.len(),
+            PackedVector::F32(values) => values.len(),
+            PackedVector::F16(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn byte_size(&self) -> usize {
+        self.len() * self.precision().bytes()
+    }
+}
+
+impl CompactModelUpdate {
+    pub fn pack(mut update: ModelUpdate, precision: Precision) -> Self {
+        let gradients = PackedVector::pack(&std::mem::take(&mut update.gradients), precision);
+        let weights = PackedVector::pack(&std::mem::take(&mut update.weights), precision);
+        CompactModelUpdate { update, gradients, weights }
+    }
+
+    pub fn unpack(self) -> ModelUpdate {
+        ModelUpdate { gradients: self.gradients.unpack(), weights: self.weights.unpack(), ..self.update }
+    }
+}
+
+impl AggregationEngine {
+    // Weighted average at any precision, accumulated in f64
+    pub fn weighted_average_of<T: Scalar>(&self, vectors: &[&[T]], weights: &[f64]) -> Result<Vec<T>, String> {
+        if vectors.is_empty() || vectors.len() != weights.len() {
+            return Err("Need one weight per vector to aggregate".to_string());
+        }
+        let dimension = vectors[0].len();
+        if vectors.iter().any(|v| v.len() != dimension) {
+            return Err("Vectors to aggregate differ in length".to_string());
+        }
+        let total_weight: f64 = weights.iter().sum();
+        if !(total_weight.is_finite() && total_weight > 0.0) {
+            return Err("Aggregation weights must sum to a positive value".to_string());
+        }
+
+        let mut aggregated = vec![0.0; dimension];
+        let mut widened = vec![0.0; dimension];
+        for (vector, weight) in vectors.iter().zip(weights) {
+            widened.iter_mut().zip(vector.iter()).for_each(|(w, v)| *w = v.to_f64());
+            kernels::axpy(weight / total_weight, &widened, &mut aggregated);
+        }
+        Ok(to_scalars(&aggregated))
+    }
+}
+
+impl FederatedLearningCoordinator {
+    pub fn execute_compact_round(&mut self, client_updates: Vec<CompactModelUpdate>) -> Result<GlobalModel, String> {
+        self.execute_round(client_updates.into_iter().map(CompactModelUpdate::unpack).collect())
+    }
+
+    pub fn get_compact_broadcast(&self, precision: Precision) -> PackedVector {
+        PackedVector::pack(&self.get_broadcast_weights(), precision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduced_precision_round_trips_and_aggregates() {
+        assert_eq!(F16::from_f32(1.0), F16(0x3c00));
+        assert_eq!(F16::from_f32(-2.5).to_f32(), -2.5);
+        assert_eq!(F16::from_f32(65504.0).to_f32(), 65504.0);
+        assert!(F16::from_f32(70000.0).to_f32().is_infinite());
+        // Smallest subnormal, and ties rounding to even
+        assert_eq!(F16::from_f32(2f32.powi(-24)), F16(0x0001));
+        assert_eq!(F16::from_f32(1.0 + 2f32.powi(-11)), F16(0x3c00));
+        assert_eq!(F16::from_f32(1.0 + 3.0 * 2f32.powi(-11)), F16(0x3c02));
+
+        let gradients = vec![0.1, -0.25, 3.0e-3, 12.5];
+        let packed = PackedVector::pack(&gradients, Precision::F16);
+        assert_eq!(packed.byte_size(), 8);
+        assert!(packed.unpack().iter().zip(&gradients).all(|(p, g)| (p - g).abs() <= g.abs() * 1e-3));
+        assert_eq!(PackedVector::pack(&gradients, Precision::F32).byte_size(), 16);
+
+        let engine = AggregationEngine::new();
+        let a: Vec<f32> = vec![1.0, 2.0];
+        let b: Vec<f32> = vec![3.0, 6.0];
+        assert_eq!(engine.weighted_average_of(&[&a, &b], &[1.0, 3.0]).unwrap(), vec![2.5f32, 5.0]);
+        assert!(engine.weighted_average_of::<f32>(&[&a, &b[..1]], &[1.0, 1.0]).is_err());
+    }
+}
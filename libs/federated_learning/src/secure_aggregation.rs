@@ -0,0 +1,973 @@
+// Secure aggregation after Bonawitz et al. (CCS 2017), so the coordinator learns the
+// sum of the hospitals' updates and nothing about any single one. A round runs in
+// four steps, the coordinator only relaying messages between them:
+//
+// 1. Each client advertises two X25519 public keys, one for encrypting shares to its
+//    peers and one for agreeing pairwise masks.
+// 2. Each client Shamir-shares its masking secret key and a fresh self-mask seed among
+//    all clients, t of n, and sends every peer its shares encrypted under their
+//    pairwise key.
+// 3. Each client sends its input in the ring of 64-bit integers plus the stream of its
+//    self-mask seed, plus (for each peer v) the stream of their pairwise secret, added
+//    when its id sorts before v's and subtracted otherwise. Pairwise masks cancel in
+//    the sum.
+// 4. The coordinator announces who sent an input. Each survivor reveals its shares of
+//    the survivors' self-mask seeds and of the dropped clients' masking keys, never
+//    both for the same client. From t shares each the coordinator removes the
+//    survivors' self-masks and the dropped clients' leftover pairwise masks.
+//
+// The threshold must be a majority of the clients, so a coordinator that lies about
+// who dropped cannot gather both secrets of one client. Clients clip and weight their
+// update before masking; the coordinator cannot inspect it afterwards.
+//
+// Values are encoded in fixed point with 20 fractional bits. Streams come from
+// SHA-256 in counter mode, and shares are encrypted with a SHA-256 keystream and
+// authenticated with HMAC-SHA256 over the sender, recipient and round.
+
+use crate::*;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+const FIXED_POINT_SCALE: f64 = (1u64 << 20) as f64;
+// Inputs beyond this could overflow the 64-bit sum
+const MAX_INPUT_MAGNITUDE: f64 = (1u64 << 40) as f64;
+const KEY_BYTES: usize = 32;
+const HMAC_BLOCK_SIZE: usize = 64;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AdvertisedKeys {
+    pub client_id: String,
+    pub round: u64,
+    pub encryption_public_key: Vec<u8>,
+    pub masking_public_key: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EncryptedShare {
+    pub from: String,
+    pub to: String,
+    pub round: u64,
+    pub ciphertext: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MaskedInput {
+    pub client_id: String,
+    pub round: u64,
+    // Revealed in the clear: the coordinator needs it to weight the sum
+    pub data_size: usize,
+    pub values: Vec<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RevealedShare {
+    pub owner: String,
+    pub x: u8,
+    pub share: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UnmaskingResponse {
+    pub client_id: String,
+    pub round: u64,
+    // Shares of surviving clients' self-mask seeds
+    pub self_mask_shares: Vec<RevealedShare>,
+    // Shares of dropped clients' masking secret keys
+    pub masking_key_shares: Vec<RevealedShare>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SecureParticipant {
+    pub client_id: String,
+    pub data_size: usize,
+}
+
+// What the coordinator learns: who took part and the sum of their inputs
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SecureAggregate {
+    pub round: u64,
+    pub participants: Vec<SecureParticipant>,
+    pub sum: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecureAggregationPhase {
+    AdvertiseKeys,
+    ShareKeys,
+    MaskedInput,
+    Unmasking,
+}
+
+// Shares a client holds for one owner
+#[derive(Clone, Debug)]
+struct HeldShare {
+    x: u8,
+    masking_key: Vec<u8>,
+    self_mask: Vec<u8>,
+}
+
+pub struct SecureAggregationClient {
+    client_id: String,
+    round: u64,
+    encryption_secret: [u8; KEY_BYTES],
+    masking_secret: [u8; KEY_BYTES],
+    self_mask_seed: [u8; KEY_BYTES],
+    threshold: usize,
+    roster: BTreeMap<String, AdvertisedKeys>,
+    // Keyed by owner, including this client's own shares
+    held_shares: BTreeMap<String, HeldShare>,
+    unmasked: bool,
+}
+
+pub struct SecureAggregationServer {
+    round: u64,
+    threshold: usize,
+    dimension: usize,
+    phase: SecureAggregationPhase,
+    keys: BTreeMap<String, AdvertisedKeys>,
+    // Keyed by sender; the senders are the clients whose masks are in play
+    shares: BTreeMap<String, Vec<EncryptedShare>>,
+    inputs: BTreeMap<String, MaskedInput>,
+    responses: BTreeMap<String, UnmaskingResponse>,
+}
+
+// X25519 (RFC 7748) over field elements of five 51-bit limbs
+mod x25519 {
+    type Fe = [u64; 5];
+
+    const MASK: u64 = (1 << 51) - 1;
+    const BASE_POINT: [u8; 32] = {
+        let mut base = [0u8; 32];
+        base[0] = 9;
+        base
+    };
+
+    fn load(bytes: &[u8; 32]) -> Fe {
+        let mut fe = [0u64; 5];
+        let (mut acc, mut bits, mut limb) = (0u128, 0u32, 0usize);
+        for (i, &byte) in bytes.iter().enumerate() {
+            let byte = if i == 31 { byte & 0x7f } else { byte };
+            acc |= (byte as u128) << bits;
+            bits += 8;
+            if bits >= 51 && limb < 5 {
+                fe[limb] = acc as u64 & MASK;
+                acc >>= 51;
+                bits -= 51;
+                limb += 1;
+            }
+        }
+        fe
+    }
+
+    fn store(fe: &Fe) -> [u8; 32] {
+        let mut t = *fe;
+        for _ in 0..2 {
+            for i in 0..4 {
+                t[i + 1] += t[i] >> 51;
+                t[i] &= MASK;
+            }
+            t[0] += 19 * (t[4] >> 51);
+            t[4] &= MASK;
+        }
+        // Subtract p once if t >= p
+        let mut q = (t[0] + 19) >> 51;
+        for limb in &t[1..] {
+            q = (limb + q) >> 51;
+        }
+        t[0] += 19 * q;
+        for i in 0..4 {
+            t[i + 1] += t[i] >> 51;
+            t[i] &= MASK;
+        }
+        t[4] &= MASK;
+
+        let mut bytes = [0u8; 32];
+        let (mut acc, mut bits, mut out) = (0u128, 0u32, 0usize);
+        for limb in t {
+            acc |= (limb as u128) << bits;
+            bits += 51;
+            while bits >= 8 {
+                bytes[out] = acc as u8;
+                acc >>= 8;
+                bits -= 8;
+                out += 1;
+            }
+        }
+        bytes[out] = acc as u8;
+        bytes
+    }
+
+    fn carry(mut r: Fe) -> Fe {
+        for i in 0..4 {
+            r[i + 1] += r[i] >> 51;
+            r[i] &= MASK;
+        }
+        r[0] += 19 * (r[4] >> 51);
+        r[4] &= MASK;
+        r[1] += r[0] >> 51;
+        r[0] &= MASK;
+        r
+    }
+
+    fn add(a: &Fe, b: &Fe) -> Fe {
+        carry([a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3], a[4] + b[4]])
+    }
+
+    // a + 4p - b, so limbs never underflow
+    fn sub(a: &Fe, b: &Fe) -> Fe {
+        const FOUR_P0: u64 = 0x1f_ffff_ffff_ffb4;
+        const FOUR_P: u64 = 0x1f_ffff_ffff_fffc;
+        carry([
+            a[0] + FOUR_P0 - b[0],
+            a[1] + FOUR_P - b[1],
+            a[2] + FOUR_P - b[2],
+            a[3] + FOUR_P - b[3],
+            a[4] + FOUR_P - b[4],
+        ])
+    }
+
+    fn mul(a: &Fe, b: &Fe) -> Fe {
+        let m = |x: u64, y: u64| x as u128 * y as u128;
+        let (b1, b2, b3, b4) = (b[1] * 19, b[2] * 19, b[3] * 19, b[4] * 19);
+        let mut r = [
+            m(a[0], b[0]) + m(a[1], b4) + m(a[2], b3) + m(a[3], b2) + m(a[4], b1),
+            m(a[0], b[1]) + m(a[1], b[0]) + m(a[2], b4) + m(a[3], b3) + m(a[4], b2),
+            m(a[0], b[2]) + m(a[1], b[1]) + m(a[2], b[0]) + m(a[3], b4) + m(a[4], b3),
+            m(a[0], b[3]) + m(a[1], b[2]) + m(a[2], b[1]) + m(a[3], b[0]) + m(a[4], b4),
+            m(a[0], b[4]) + m(a[1], b[3]) + m(a[2], b[2]) + m(a[3], b[1]) + m(a[4], b[0]),
+        ];
+        let mask = MASK as u128;
+        for i in 0..4 {
+            r[i + 1] += r[i] >> 51;
+            r[i] &= mask;
+        }
+        r[0] += 19 * (r[4] >> 51);
+        r[4] &= mask;
+        r[1] += r[0] >> 51;
+        r[0] &= mask;
+        [r[0] as u64, r[1] as u64, r[2] as u64, r[3] as u64, r[4] as u64]
+    }
+
+    // a^(p - 2)
+    fn invert(a: &Fe) -> Fe {
+        let mut exponent = [0xffu8; 32];
+        exponent[0] = 0xeb;
+        exponent[31] = 0x7f;
+        let mut result = [1, 0, 0, 0, 0];
+        for bit in (0..255).rev() {
+            result = mul(&result, &result);
+            if (exponent[bit / 8] >> (bit % 8)) & 1 == 1 {
+                result = mul(&result, a);
+            }
+        }
+        result
+    }
+
+    fn conditional_swap(swap: u64, a: &mut Fe, b: &mut Fe) {
+        let mask = 0u64.wrapping_sub(swap);
+        for i in 0..5 {
+            let t = mask & (a[i] ^ b[i]);
+            a[i] ^= t;
+            b[i] ^= t;
+        }
+    }
+
+    pub fn scalar_mult(scalar: &[u8; 32], point: &[u8; 32]) -> [u8; 32] {
+        let mut k = *scalar;
+        k[0] &= 248;
+        k[31] &= 127;
+        k[31] |= 64;
+
+        let x1 = load(point);
+        let (mut x2, mut z2, mut x3, mut z3) = ([1, 0, 0, 0, 0], [0; 5], x1, [1, 0, 0, 0, 0]);
+        let a24 = [121_665, 0, 0, 0, 0];
+        let mut swap = 0;
+        for t in (0..255).rev() {
+            let bit = ((k[t / 8] >> (t % 8)) & 1) as u64;
+            swap ^= bit;
+            conditional_swap(swap, &mut x2, &mut x3);
+            conditional_swap(swap, &mut z2, &mut z3);
+            swap = bit;
+
+            let a = add(&x2, &z2);
+            let aa = mul(&a, &a);
+            let b = sub(&x2, &z2);
+            let bb = mul(&b, &b);
+            let e = sub(&aa, &bb);
+            let c = add(&x3, &z3);
+            let d = sub(&x3, &z3);
+            let da = mul(&d, &a);
+            let cb = mul(&c, &b);
+            let sum = add(&da, &cb);
+            x3 = mul(&sum, &sum);
+            let difference = sub(&da, &cb);
+            z3 = mul(&x1, &mul(&difference, &difference));
+            x2 = mul(&aa, &bb);
+            z2 = mul(&e, &add(&aa, &mul(&a24, &e)));
+        }
+        conditional_swap(swap, &mut x2, &mut x3);
+        conditional_swap(swap, &mut z2, &mut z3);
+        store(&mul(&x2, &invert(&z2)))
+    }
+
+    pub fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+        scalar_mult(secret, &BASE_POINT)
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn length_prefixed(parts: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for part in parts {
+        bytes.extend_from_slice(&(part.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(part);
+    }
+    bytes
+}
+
+fn key_bytes(key: &[u8]) -> Result<[u8; KEY_BYTES], String> {
+    key.try_into().map_err(|_| format!("Keys must be {} bytes", KEY_BYTES))
+}
+
+fn agree(secret: &[u8; KEY_BYTES], public_key: &[u8]) -> Result<[u8; KEY_BYTES], String> {
+    let shared = x25519::scalar_mult(secret, &key_bytes(public_key)?);
+    // A low-order public key yields the all-zero secret
+    if shared.iter().all(|&b| b == 0) {
+        return Err("Key agreement produced a degenerate secret".to_string());
+    }
+    Ok(shared)
+}
+
+// Pseudorandom stream of `len` ring elements
+fn expand(seed: &[u8], len: usize) -> Vec<u64> {
+    let mut stream = Vec::with_capacity(len);
+    let mut counter = 0u64;
+    while stream.len() < len {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        for word in hasher.finalize().chunks(8) {
+            if stream.len() < len {
+                stream.push(u64::from_le_bytes(word.try_into().unwrap_or_default()));
+            }
+        }
+        counter += 1;
+    }
+    stream
+}
+
+fn pairwise_seed(shared: &[u8; KEY_BYTES], round: u64, a: &str, b: &str) -> Vec<u8> {
+    let (first, second) = if a < b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(b"secagg-pairwise-mask");
+    hasher.update(length_prefixed(&[&round.to_be_bytes(), first.as_bytes(), second.as_bytes(), shared]));
+    hasher.finalize().to_vec()
+}
+
+fn share_key(shared: &[u8; KEY_BYTES]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"secagg-share-key");
+    hasher.update(shared);
+    hasher.finalize().to_vec()
+}
+
+fn share_associated_data(from: &str, to: &str, round: u64) -> Vec<u8> {
+    length_prefixed(&[from.as_bytes(), to.as_bytes(), &round.to_be_bytes()])
+}
+
+fn apply_keystream(key: &[u8], associated_data: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    for (counter, block) in data.chunks(32).enumerate() {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        hasher.update(associated_data);
+        hasher.update((counter as u64).to_be_bytes());
+        output.extend(block.iter().zip(hasher.finalize()).map(|(d, k)| d ^ k));
+    }
+    output
+}
+
+// Shamir's scheme over GF(2^8) with the AES polynomial, byte by byte
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+fn gf_inv(a: u8) -> u8 {
+    // a^254
+    let mut result = 1;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+// Shares at x = 1..=shares, any `threshold` of which recover `secret`
+pub fn shamir_split(secret: &[u8], threshold: usize, shares: usize) -> Result<Vec<(u8, Vec<u8>)>, String> {
+    if threshold == 0 || threshold > shares || shares > 255 {
+        return Err(format!("Cannot split {} of {} shares", threshold, shares));
+    }
+    let mut rng = rand::thread_rng();
+    let mut coefficients = vec![0u8; threshold - 1];
+    let mut split: Vec<(u8, Vec<u8>)> = (1..=shares as u8).map(|x| (x, Vec::with_capacity(secret.len()))).collect();
+    for &byte in secret {
+        rng.fill_bytes(&mut coefficients);
+        for (x, share) in &mut split {
+            // Horner's rule, highest coefficient first
+            let value = coefficients.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, *x) ^ c);
+            share.push(gf_mul(value, *x) ^ byte);
+        }
+    }
+    Ok(split)
+}
+
+pub fn shamir_combine(shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let len = shares.first().ok_or("No shares to combine")?.1.len();
+    let xs: BTreeSet<u8> = shares.iter().map(|(x, _)| *x).collect();
+    if xs.len() != shares.len() || xs.contains(&0) || shares.iter().any(|(_, s)| s.len() != len) {
+        return Err("Shares must have distinct nonzero x and equal length".to_string());
+    }
+    let mut secret = vec![0u8; len];
+    for (i, (xi, share)) in shares.iter().enumerate() {
+        // Lagrange basis polynomial of share i at zero
+        let basis = shares.iter().enumerate().filter(|(j, _)| *j != i).fold(1u8, |acc, (_, (xj, _))| {
+            gf_mul(acc, gf_mul(*xj, gf_inv(xj ^ xi)))
+        });
+        secret.iter_mut().zip(share).for_each(|(s, &b)| *s ^= gf_mul(basis, b));
+    }
+    Ok(secret)
+}
+
+fn encode(value: f64) -> Result<u64, String> {
+    if !(value.is_finite() && value.abs() < MAX_INPUT_MAGNITUDE) {
+        return Err(format!("Value {} cannot be encoded for secure aggregation", value));
+    }
+    Ok((value * FIXED_POINT_SCALE).round() as i64 as u64)
+}
+
+fn decode(value: u64) -> f64 {
+    value as i64 as f64 / FIXED_POINT_SCALE
+}
+
+fn random_key() -> [u8; KEY_BYTES] {
+    let mut key = [0u8; KEY_BYTES];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+impl SecureAggregationClient {
+    pub fn new(client_id: &str, round: u64) -> Self {
+        SecureAggregationClient {
+            client_id: client_id.to_string(),
+            round,
+            encryption_secret: random_key(),
+            masking_secret: random_key(),
+            self_mask_seed: random_key(),
+            threshold: 0,
+            roster: BTreeMap::new(),
+            held_shares: BTreeMap::new(),
+            unmasked: false,
+        }
+    }
+
+    pub fn advertise(&self) -> AdvertisedKeys {
+        AdvertisedKeys {
+            client_id: self.client_id.clone(),
+            round: self.round,
+            encryption_public_key: x25519::public_key(&self.encryption_secret).to_vec(),
+            masking_public_key: x25519::public_key(&self.masking_secret).to_vec(),
+        }
+    }
+
+    // Shares this client's secrets among the roster; one encrypted share per peer
+    pub fn share_keys(&mut self, roster: &[AdvertisedKeys], threshold: usize) -> Result<Vec<EncryptedShare>, String> {
+        let own_keys = self.advertise();
+        let mut by_id = BTreeMap::new();
+        for keys in roster {
+            if keys.round != self.round {
+                return Err(format!("Keys of {} are for round {}", keys.client_id, keys.round));
+            }
+            key_bytes(&keys.encryption_public_key)?;
+            key_bytes(&keys.masking_public_key)?;
+            if by_id.insert(keys.client_id.clone(), keys.clone()).is_some() {
+                return Err(format!("{} appears twice in the roster", keys.client_id));
+            }
+        }
+        if by_id.get(&self.client_id) != Some(&own_keys) {
+            return Err("Roster does not carry this client's keys".to_string());
+        }
+        if threshold < 2 || threshold * 2 <= by_id.len() || threshold > by_id.len() {
+            return Err(format!("Threshold {} must be a majority of {} clients", threshold, by_id.len()));
+        }
+
+        let masking_shares = shamir_split(&self.masking_secret, threshold, by_id.len())?;
+        let self_mask_shares = shamir_split(&self.self_mask_seed, threshold, by_id.len())?;
+        let mut encrypted = Vec::new();
+        for (index, peer) in by_id.keys().enumerate() {
+            let (x, masking_key) = &masking_shares[index];
+            let self_mask = &self_mask_shares[index].1;
+            if *peer == self.client_id {
+                self.held_shares.insert(peer.clone(), HeldShare { x: *x, masking_key: masking_key.clone(), self_mask: self_mask.clone() });
+                continue;
+            }
+            let key = share_key(&agree(&self.encryption_secret, &by_id[peer].encryption_public_key)?);
+            let associated_data = share_associated_data(&self.client_id, peer, self.round);
+            let mut plaintext = vec![*x];
+            plaintext.extend_from_slice(masking_key);
+            plaintext.extend_from_slice(self_mask);
+            let ciphertext = apply_keystream(&key, &associated_data, &plaintext);
+            let tag = hmac_sha256(&key, &[associated_data, ciphertext.clone()].concat());
+            encrypted.push(EncryptedShare { from: self.client_id.clone(), to: peer.clone(), round: self.round, ciphertext, tag });
+        }
+        self.roster = by_id;
+        self.threshold = threshold;
+        Ok(encrypted)
+    }
+
+    // Decrypts the shares peers sent this client
+    pub fn receive_shares(&mut self, shares: &[EncryptedShare]) -> Result<(), String> {
+        let own_x = self.held_shares.get(&self.client_id).ok_or("Keys have not been shared yet")?.x;
+        for share in shares.iter().filter(|s| s.to == self.client_id) {
+            let sender = self.roster.get(&share.from).ok_or(format!("Share from {} who is not in the roster", share.from))?;
+            if share.round != self.round || share.from == self.client_id {
+                return Err(format!("Share from {} does not belong to this round", share.from));
+            }
+            let key = share_key(&agree(&self.encryption_secret, &sender.encryption_public_key)?);
+            let associated_data = share_associated_data(&share.from, &share.to, share.round);
+            let expected_tag = hmac_sha256(&key, &[associated_data.clone(), share.ciphertext.clone()].concat());
+            if !constant_time_eq(&expected_tag, &share.tag) {
+                return Err(format!("Share from {} failed authentication", share.from));
+            }
+            let plaintext = apply_keystream(&key, &associated_data, &share.ciphertext);
+            if plaintext.len() != 1 + 2 * KEY_BYTES || plaintext[0] != own_x {
+                return Err(format!("Share from {} is malformed", share.from));
+            }
+            self.held_shares.insert(
+                share.from.clone(),
+                HeldShare {
+                    x: plaintext[0],
+                    masking_key: plaintext[1..1 + KEY_BYTES].to_vec(),
+                    self_mask: plaintext[1 + KEY_BYTES..].to_vec(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // Masks `values` against every client whose shares this client holds
+    pub fn mask_input(&self, values: &[f64], data_size: usize) -> Result<MaskedInput, String> {
+        if self.held_shares.len() < self.threshold.max(2) {
+            return Err("Too few peers shared keys to mask the input".to_string());
+        }
+        let mut masked = values.iter().map(|&v| encode(v)).collect::<Result<Vec<u64>, String>>()?;
+        let add_stream = |masked: &mut [u64], stream: Vec<u64>, subtract: bool| {
+            for (m, s) in masked.iter_mut().zip(stream) {
+                *m = if subtract { m.wrapping_sub(s) } else { m.wrapping_add(s) };
+            }
+        };
+        add_stream(&mut masked, expand(&self.self_mask_seed, values.len()), false);
+        for peer in self.held_shares.keys().filter(|p| **p != self.client_id) {
+            let shared = agree(&self.masking_secret, &self.roster[peer].masking_public_key)?;
+            let stream = expand(&pairwise_seed(&shared, self.round, &self.client_id, peer), values.len());
+            add_stream(&mut masked, stream, self.client_id > *peer);
+        }
+        Ok(MaskedInput { client_id: self.client_id.clone(), round: self.round, data_size, values: masked })
+    }
+
+    // Weighted gradients, loss and accuracy of `update`, masked for the coordinator
+    pub fn mask_update(&self, update: &ModelUpdate) -> Result<MaskedInput, String> {
+        let weight = update.data_size as f64;
+        let mut values: Vec<f64> = update.gradients.iter().map(|g| g * weight).collect();
+        values.push(update.loss * weight);
+        values.push(update.accuracy * weight);
+        self.mask_input(&values, update.data_size)
+    }
+
+    // Reveals self-mask shares of the survivors and masking-key shares of the rest.
+    // Answers once, so a coordinator cannot ask again with a different survivor set.
+    pub fn unmask(&mut self, survivors: &[String]) -> Result<UnmaskingResponse, String> {
+        if self.unmasked {
+            return Err("Unmasking shares were already revealed".to_string());
+        }
+        let survivors: BTreeSet<&String> = survivors.iter().collect();
+        if survivors.len() < self.threshold || !survivors.contains(&self.client_id) {
+            return Err(format!("{} survivors is below the threshold of {}", survivors.len(), self.threshold));
+        }
+        if survivors.iter().any(|s| !self.held_shares.contains_key(*s)) {
+            return Err("Survivor set names a client that never shared keys".to_string());
+        }
+
+        let mut response = UnmaskingResponse {
+            client_id: self.client_id.clone(),
+            round: self.round,
+            self_mask_shares: Vec::new(),
+            masking_key_shares: Vec::new(),
+        };
+        for (owner, held) in &self.held_shares {
+            if survivors.contains(owner) {
+                response.self_mask_shares.push(RevealedShare { owner: owner.clone(), x: held.x, share: held.self_mask.clone() });
+            } else {
+                response.masking_key_shares.push(RevealedShare { owner: owner.clone(), x: held.x, share: held.masking_key.clone() });
+            }
+        }
+        self.unmasked = true;
+        Ok(response)
+    }
+}
+
+impl SecureAggregationServer {
+    pub fn new(round: u64, threshold: usize, dimension: usize) -> Result<Self, String> {
+        if threshold < 2 {
+            return Err("Secure aggregation needs a threshold of at least 2".to_string());
+        }
+        Ok(SecureAggregationServer {
+            round,
+            threshold,
+            dimension,
+            phase: SecureAggregationPhase::AdvertiseKeys,
+            keys: BTreeMap::new(),
+            shares: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            responses: BTreeMap::new(),
+        })
+    }
+
+    pub fn phase(&self) -> SecureAggregationPhase {
+        self.phase
+    }
+
+    fn expect_phase(&self, phase: SecureAggregationPhase) -> Result<(), String> {
+        if self.phase != phase {
+            return Err(format!("Secure aggregation is in phase {:?}, not {:?}", self.phase, phase));
+        }
+        Ok(())
+    }
+
+    pub fn advertise_keys(&mut self, keys: AdvertisedKeys) -> Result<(), String> {
+        self.expect_phase(SecureAggregationPhase::AdvertiseKeys)?;
+        if keys.round != self.round {
+            return Err(format!("Keys are for round {}, current round is {}", keys.round, self.round));
+        }
+        key_bytes(&keys.encryption_public_key)?;
+        key_bytes(&keys.masking_public_key)?;
+        if self.keys.contains_key(&keys.client_id) {
+            return Err(format!("{} already advertised keys", keys.client_id));
+        }
+        self.keys.insert(keys.client_id.clone(), keys);
+        Ok(())
+    }
+
+    // Closes advertisement; every client shares its keys against this roster
+    pub fn roster(&mut self) -> Result<Vec<AdvertisedKeys>, String> {
+        if self.phase == SecureAggregationPhase::AdvertiseKeys {
+            if self.keys.len() < self.threshold || self.threshold * 2 <= self.keys.len() {
+                return Err(format!("Threshold {} must be a majority of the {} clients", self.threshold, self.keys.len()));
+            }
+            self.phase = SecureAggregationPhase::ShareKeys;
+        }
+        self.expect_phase(SecureAggregationPhase::ShareKeys)?;
+        Ok(self.keys.values().cloned().collect())
+    }
+
+    pub fn submit_shares(&mut self, client_id: &str, shares: Vec<EncryptedShare>) -> Result<(), String> {
+        self.expect_phase(SecureAggregationPhase::ShareKeys)?;
+        if !self.keys.contains_key(client_id) || self.shares.contains_key(client_id) {
+            return Err(format!("{} is not in the roster or already shared keys", client_id));
+        }
+        let recipients: BTreeSet<&String> = shares.iter().map(|s| &s.to).collect();
+        let valid = recipients.len() == shares.len()
+            && shares.iter().all(|s| s.from == client_id && s.round == self.round && s.to != client_id && self.keys.contains_key(&s.to));
+        if !valid {
+            return Err(format!("Shares from {} are not one per roster peer", client_id));
+        }
+        self.shares.insert(client_id.to_string(), shares);
+        Ok(())
+    }
+
+    // Closes sharing; the shares addressed to `client_id` from every client that shared
+    pub fn shares_for(&mut self, client_id: &str) -> Result<Vec<EncryptedShare>, String> {
+        if self.phase == SecureAggregationPhase::ShareKeys {
+            if self.shares.len() < self.threshold {
+                return Err(format!("Only {} clients shared keys, threshold is {}", self.shares.len(), self.threshold));
+            }
+            self.phase = SecureAggregationPhase::MaskedInput;
+        }
+        self.expect_phase(SecureAggregationPhase::MaskedInput)?;
+        if !self.shares.contains_key(client_id) {
+            return Err(format!("{} did not share keys this round", client_id));
+        }
+        Ok(self.shares.values().flatten().filter(|s| s.to == client_id).cloned().collect())
+    }
+
+    pub fn submit_masked_input(&mut self, input: MaskedInput) -> Result<(), String> {
+        self.expect_phase(SecureAggregationPhase::MaskedInput)?;
+        if input.round != self.round || !self.shares.contains_key(&input.client_id) {
+            return Err(format!("{} may not submit an input this round", input.client_id));
+        }
+        if input.values.len() != self.dimension {
+            return Err(format!("Masked input has {} values, expected {}", input.values.len(), self.dimension));
+        }
+        if self.inputs.contains_key(&input.client_id) {
+            return Err(format!("{} already submitted an input", input.client_id));
+        }
+        self.inputs.insert(input.client_id.clone(), input);
+        Ok(())
+    }
+
+    // Closes input collection; the clients whose inputs are in the sum
+    pub fn survivors(&mut self) -> Result<Vec<String>, String> {
+        if self.phase == SecureAggregationPhase::MaskedInput {
+            if self.inputs.len() < self.threshold {
+                return Err(format!("Only {} clients sent inputs, threshold is {}", self.inputs.len(), self.threshold));
+            }
+            self.phase = SecureAggregationPhase::Unmasking;
+        }
+        self.expect_phase(SecureAggregationPhase::Unmasking)?;
+        Ok(self.inputs.keys().cloned().collect())
+    }
+
+    pub fn submit_unmasking(&mut self, response: UnmaskingResponse) -> Result<(), String> {
+        self.expect_phase(SecureAggregationPhase::Unmasking)?;
+        if response.round != self.round || !self.inputs.contains_key(&response.client_id) {
+            return Err(format!("{} is not a survivor of this round", response.client_id));
+        }
+        if self.responses.contains_key(&response.client_id) {
+            return Err(format!("{} already answered the unmasking request", response.client_id));
+        }
+        self.responses.insert(response.client_id.clone(), response);
+        Ok(())
+    }
+
+    fn reconstruct(&self, owner: &str, masking_key: bool) -> Result<[u8; KEY_BYTES], String> {
+        let shares: Vec<(u8, Vec<u8>)> = self
+            .responses
+            .values()
+            .flat_map(|r| if masking_key { &r.masking_key_shares } else { &r.self_mask_shares })
+            .filter(|s| s.owner == owner)
+            .map(|s| (s.x, s.share.clone()))
+            .collect();
+        if shares.len() < self.threshold {
+            return Err(format!("Only {} shares for {}, threshold is {}", shares.len(), owner, self.threshold));
+        }
+        key_bytes(&shamir_combine(&shares[..self.threshold])?)
+    }
+
+    // Removes the remaining masks and returns the sum of the survivors' inputs
+    pub fn finish(&self) -> Result<SecureAggregate, String> {
+        self.expect_phase(SecureAggregationPhase::Unmasking)?;
+        if self.responses.len() < self.threshold {
+            return Err(format!("Only {} unmasking responses, threshold is {}", self.responses.len(), self.threshold));
+        }
+
+        let mut sum = vec![0u64; self.dimension];
+        for input in self.inputs.values() {
+            sum.iter_mut().zip(&input.values).for_each(|(s, v)| *s = s.wrapping_add(*v));
+        }
+        for survivor in self.inputs.keys() {
+            let seed = self.reconstruct(survivor, false)?;
+            sum.iter_mut().zip(expand(&seed, self.dimension)).for_each(|(s, m)| *s = s.wrapping_sub(m));
+        }
+        for dropped in self.shares.keys().filter(|c| !self.inputs.contains_key(*c)) {
+            let secret = self.reconstruct(dropped, true)?;
+            if x25519::public_key(&secret).as_slice() != self.keys[dropped].masking_public_key.as_slice() {
+                return Err(format!("Reconstructed masking key of {} does not match its public key", dropped));
+            }
+            for survivor in self.inputs.keys() {
+                let shared = agree(&secret, &self.keys[survivor].masking_public_key)?;
+                let stream = expand(&pairwise_seed(&shared, self.round, survivor, dropped), self.dimension);
+                // The survivor added this mask if its id sorts first, else subtracted it
+                let added = survivor < dropped;
+                for (s, m) in sum.iter_mut().zip(stream) {
+                    *s = if added { s.wrapping_sub(m) } else { s.wrapping_add(m) };
+                }
+            }
+        }
+
+        Ok(SecureAggregate {
+            round: self.round,
+            participants: self
+                .inputs
+                .values()
+                .map(|i| SecureParticipant { client_id: i.client_id.clone(), data_size: i.data_size })
+                .collect(),
+            sum: sum.into_iter().map(decode).collect(),
+        })
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Completes a round from a secure aggregate of `SecureAggregationClient::mask_update`
+    // or `MpcScheme::share_update` inputs. Every participant must be one the coordinator
+    // would have accepted an update from, since its contribution cannot be taken back
+    // out of the sum.
+    pub fn execute_secure_round(&mut self, aggregate: SecureAggregate) -> Result<GlobalModel, String> {
+        if !matches!(self.config.privacy_method, PrivacyMethod::SecureAggregation | PrivacyMethod::MultiPartyComputation { .. }) {
+            return Err("Secure rounds need the SecureAggregation or MultiPartyComputation privacy method".to_string());
+        }
+        self.check_no_pending_candidate()?;
+        if aggregate.round != self.global_model.round {
+            return Err(format!("Aggregate is for round {}, current round is {}", aggregate.round, self.global_model.round));
+        }
+        if matches!(self.config.algorithm, FLAlgorithm::SCAFFOLD) || self.clustering.is_some() || self.personalization.is_some() {
+            return Err("SCAFFOLD, clustering and personalization need individual updates".to_string());
+        }
+        let dimension = self.global_model.weights.len();
+        if aggregate.sum.len() != dimension + 2 {
+            return Err(format!("Aggregate has {} values, expected {}", aggregate.sum.len(), dimension + 2));
+        }
+        let total: f64 = aggregate.participants.iter().map(|p| p.data_size as f64).sum();
+        if total <= 0.0 {
+            return Err("Participants report no data".to_string());
+        }
+
+        let (loss, accuracy) = (aggregate.sum[dimension] / total, aggregate.sum[dimension + 1] / total);
+        let participants: Vec<ModelUpdate> = aggregate
+            .participants
+            .iter()
+            .map(|p| ModelUpdate {
+                client_id: p.client_id.clone(),
+                round: aggregate.round,
+                gradients: Vec::new(),
+                weights: Vec::new(),
+                loss,
+                accuracy,
+                data_size: p.data_size,
+                computation_time: 0.0,
+                communication_cost: 0.0,
+                privacy_budget_used: 0.0,
+                compressed: false,
+                compression_ratio: None,
+                attestation: None,
+                personalized_accuracy: None,
+                sparse_gradients: None,
+                trained_ranges: None,
+                gradient_proof: None,
+            })
+            .collect();
+        let accepted = self.validate_client_updates(participants.clone())?;
+        if accepted.len() != participants.len() {
+            return Err("The aggregate includes clients not eligible for this round".to_string());
+        }
+
+        let aggregated_weights = aggregate.sum[..dimension].iter().map(|s| s / total).collect();
+        self.finish_round(aggregated_weights, &accepted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> [u8; 32] {
+        let bytes: Vec<u8> = (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect();
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_coordinator_learns_only_the_sum_despite_dropout() {
+        // RFC 7748 section 5.2
+        let scalar = hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let point = hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        assert_eq!(x25519::scalar_mult(&scalar, &point), hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552"));
+        let secret = hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        assert_eq!(x25519::public_key(&secret), hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"));
+
+        let ids = ["hospital_a", "hospital_b", "hospital_c", "hospital_d"];
+        let inputs = [vec![1.5, -2.0], vec![0.25, 4.0], vec![-3.0, 1.0], vec![10.0, 10.0]];
+        let mut clients: Vec<SecureAggregationClient> = ids.iter().map(|id| SecureAggregationClient::new(id, 3)).collect();
+        let mut server = SecureAggregationServer::new(3, 3, 2).unwrap();
+        for client in &clients {
+            server.advertise_keys(client.advertise()).unwrap();
+        }
+        let roster = server.roster().unwrap();
+        for client in &mut clients {
+            let shares = client.share_keys(&roster, 3).unwrap();
+            server.submit_shares(&client.client_id, shares).unwrap();
+        }
+        for client in &mut clients {
+            let shares = server.shares_for(&client.client_id).unwrap();
+            client.receive_shares(&shares).unwrap();
+        }
+
+        // hospital_d drops out after sharing its keys; the others' inputs stay masked
+        for (client, input) in clients.iter().zip(&inputs).take(3) {
+            let masked = client.mask_input(input, 1).unwrap();
+            assert_ne!(masked.values[0], encode(input[0]).unwrap());
+            server.submit_masked_input(masked).unwrap();
+        }
+        let survivors = server.survivors().unwrap();
+        assert_eq!(survivors.len(), 3);
+        assert!(server.finish().is_err());
+        for client in clients.iter_mut().take(3) {
+            let response = client.unmask(&survivors).unwrap();
+            server.submit_unmasking(response).unwrap();
+        }
+        assert!(clients[0].unmask(&survivors).is_err());
+
+        let aggregate = server.finish().unwrap();
+        assert!((aggregate.sum[0] - -1.25).abs() < 1e-5 && (aggregate.sum[1] - 3.0).abs() < 1e-5);
+        assert_eq!(aggregate.participants.len(), 3);
+
+        // A threshold short of a majority could reveal both secrets of one client
+        let mut minority = SecureAggregationServer::new(3, 2, 2).unwrap();
+        roster.iter().for_each(|keys| minority.advertise_keys(keys.clone()).unwrap());
+        assert!(minority.roster().is_err());
+        let (x, shares) = (b"secret", shamir_split(b"secret", 2, 3).unwrap());
+        assert_eq!(shamir_combine(&shares[1..]).unwrap(), x.to_vec());
+
+        // The coordinator only ever handles the weighted sum
+        let config = FederatedLearningConfig::builder()
+            .privacy_method(PrivacyMethod::SecureAggregation)
+            .model_dimension(2)
+            .min_clients(2)
+            .build()
+            .unwrap();
+        let mut coordinator = FederatedLearningCoordinator::new(config).unwrap();
+        let participants = vec![
+            SecureParticipant { client_id: "hospital_a".to_string(), data_size: 1 },
+            SecureParticipant { client_id: "hospital_b".to_string(), data_size: 3 },
+        ];
+        let aggregate = SecureAggregate { round: 0, participants, sum: vec![4.0, -8.0, 2.0, 3.2] };
+        let model = coordinator.execute_secure_round(aggregate).unwrap();
+        assert_eq!(model.weights, vec![1.0, -2.0]);
+        assert!((model.global_loss - 0.5).abs() < 1e-12 && (model.global_accuracy - 0.8).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,138 @@
+// Client selection. Before each round the coordinator draws a cohort of
+// max(min_clients, ceil(client_fraction * N)) clients out of the N that are not
+// dropped and only accepts updates from that cohort. Strategies other than uniform
+// sampling draw without replacement with per-client weights (Efraimidis-Spirakis
+// keys u^(1/w)), so a heavier client is more likely, but never certain, to be picked.
+
+use crate::*;
+use rand::Rng;
+
+// Self-reported by clients between rounds
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ClientResources {
+    // Relative training throughput; 1.0 is the reference device
+    pub compute_score: f64,
+    pub bandwidth_mbps: f64,
+    pub available: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum SelectionStrategy {
+    #[default]
+    UniformRandom,
+    // Weighted by last training loss, so sites the model fits worst train more often
+    LossWeighted,
+    // Weighted by expected data size
+    DataSizeWeighted,
+    // Only available clients above both thresholds, weighted by compute score
+    ResourceAware { min_compute_score: f64, min_bandwidth_mbps: f64 },
+}
+
+pub trait ClientSelector {
+    // Picks up to `count` of `candidates` for `round`
+    fn select(&mut self, candidates: &[ClientRecord], count: usize, round: u64) -> Result<Vec<String>, String>;
+}
+
+impl SelectionStrategy {
+    pub fn validate(&self) -> Result<(), String> {
+        if let SelectionStrategy::ResourceAware { min_compute_score, min_bandwidth_mbps } = self {
+            if *min_compute_score < 0.0 || *min_bandwidth_mbps < 0.0 {
+                return Err("Resource thresholds must be non-negative".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // None for clients the strategy never selects
+    fn weight(&self, record: &ClientRecord, mean_loss: f64) -> Option<f64> {
+        match self {
+            SelectionStrategy::UniformRandom => Some(1.0),
+            // Clients without a reported loss yet count as average
+            SelectionStrategy::LossWeighted => Some(record.last_loss.filter(|l| l.is_finite()).unwrap_or(mean_loss)),
+            SelectionStrategy::DataSizeWeighted => Some(record.expected_weight),
+            SelectionStrategy::ResourceAware { min_compute_score, min_bandwidth_mbps } => {
+                let resources = record.resources.as_ref()?;
+                let eligible = resources.available
+                    && resources.compute_score >= *min_compute_score
+                    && resources.bandwidth_mbps >= *min_bandwidth_mbps;
+                eligible.then_some(resources.compute_score)
+            }
+        }
+    }
+}
+
+impl ClientSelector for SelectionStrategy {
+    fn select(&mut self, candidates: &[ClientRecord], count: usize, _round: u64) -> Result<Vec<String>, String> {
+        let losses: Vec<f64> = candidates.iter().filter_map(|r| r.last_loss).filter(|l| l.is_finite()).collect();
+        let mean_loss = if losses.is_empty() { 1.0 } else { losses.iter().sum::<f64>() / losses.len() as f64 };
+
+        let mut rng = rand::thread_rng();
+        let mut keyed: Vec<(f64, &str)> = Vec::new();
+        for record in candidates {
+            let weight = match self.weight(record, mean_loss) {
+                Some(weight) => weight,
+                None => continue,
+            };
+            // Zero-weight clients stay selectable, behind every positively weighted one
+            let weight = weight.max(f64::MIN_POSITIVE);
+            let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+            keyed.push((u.ln() / weight, record.client_id.as_str()));
+        }
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut selected: Vec<String> = keyed.into_iter().take(count).map(|(_, id)| id.to_string()).collect();
+        selected.sort();
+        Ok(selected)
+    }
+}
+
+// Cohort size for `eligible` selectable clients
+pub fn cohort_size(config: &FederatedLearningConfig, eligible: usize) -> usize {
+    let fraction = (config.client_fraction * eligible as f64).ceil() as usize;
+    fraction.max(config.min_clients as usize).min(eligible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, weight: f64, loss: Option<f64>, resources: Option<ClientResources>) -> ClientRecord {
+        let mut record = ClientRecord::new(id, weight);
+        record.last_loss = loss;
+        record.resources = resources;
+        record
+    }
+
+    #[test]
+    fn test_strategies_pick_cohort_of_requested_size() {
+        let fast = ClientResources { compute_score: 2.0, bandwidth_mbps: 100.0, available: true };
+        let slow = ClientResources { compute_score: 0.2, bandwidth_mbps: 100.0, available: true };
+        let offline = ClientResources { available: false, ..fast.clone() };
+        let candidates = vec![
+            record("a", 10.0, Some(0.1), Some(fast.clone())),
+            record("b", 1000.0, Some(5.0), Some(slow)),
+            record("c", 10.0, None, Some(offline)),
+            record("d", 0.0, Some(0.0), Some(fast)),
+            record("e", 10.0, Some(0.1), None),
+        ];
+
+        let mut uniform = SelectionStrategy::UniformRandom;
+        let cohort = uniform.select(&candidates, 3, 0).unwrap();
+        assert_eq!(cohort.len(), 3);
+        assert!(cohort.windows(2).all(|w| w[0] < w[1]));
+
+        // Zero-weight clients only make the cohort once everyone else is in
+        let mut by_size = SelectionStrategy::DataSizeWeighted;
+        assert!(!by_size.select(&candidates, 4, 0).unwrap().contains(&"d".to_string()));
+        let mut by_loss = SelectionStrategy::LossWeighted;
+        assert!(!by_loss.select(&candidates, 4, 0).unwrap().contains(&"d".to_string()));
+
+        let mut resource_aware = SelectionStrategy::ResourceAware { min_compute_score: 1.0, min_bandwidth_mbps: 10.0 };
+        assert_eq!(resource_aware.select(&candidates, 5, 0).unwrap(), vec!["a".to_string(), "d".to_string()]);
+
+        let config = FederatedLearningConfig::builder().client_fraction(0.3).min_clients(2).build().unwrap();
+        assert_eq!(cohort_size(&config, 10), 3);
+        assert_eq!(cohort_size(&config, 4), 2);
+        assert_eq!(cohort_size(&config, 1), 1);
+    }
+}
@@ -0,0 +1,494 @@
+// Simulated federations, for measuring the coordinator without a consortium's data.
+// The task is binary classification, by default on standard normal features with
+// labels from a fixed true weight vector and a little label noise; `datasets` has
+// other sources, such as toy vital signs built from medical records. The records are
+// split over the clients either evenly at random or with Dirichlet label skew, the
+// usual way to make federated data non-IID (Hsu et al., 2019): each class is spread
+// over the clients in Dirichlet(alpha) proportions, so a small alpha leaves most
+// clients with mostly one class and uneven sizes.
+//
+// Clients train a logistic regression or a one-hidden-layer MLP (the dense layers of
+// split.rs) with minibatch SGD for the configured local epochs and batch size,
+// starting from the broadcast model, and submit their local model as FedAvg clients
+// do. Rounds run through a real coordinator, so validation, screening, compression,
+// the configured AggregationMethod and the server optimizer all apply, and the
+// report's numbers come from the trained models. `attacks` turns some clients
+// malicious.
+
+use crate::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rand_distr::{Distribution, Gamma};
+use std::time::Instant;
+
+pub mod attacks;
+pub mod datasets;
+pub use attacks::*;
+pub use datasets::*;
+
+#[derive(Clone, Debug)]
+pub struct ClientData {
+    pub features: Vec<Vec<f64>>,
+    // 0 or 1
+    pub labels: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DataPartition {
+    Iid,
+    Dirichlet { alpha: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SimulatedModel {
+    // Logistic regression with a bias
+    Linear,
+    // One tanh hidden layer, then a logistic output
+    Mlp { hidden: usize },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SimulationConfig {
+    pub clients: usize,
+    pub dimension: usize,
+    // Records per client on average; Dirichlet partitions make sizes uneven
+    pub records_per_client: usize,
+    pub test_records: usize,
+    pub rounds: u64,
+    // Clients' local SGD step; epochs and batch size come from the FL configuration
+    pub learning_rate: f64,
+    // Share of labels flipped at random, so honest clients disagree a little
+    pub label_noise: f64,
+    pub partition: DataPartition,
+    pub model: SimulatedModel,
+    // Ends the run early once the coordinator's early-stopping strategy fires
+    pub stop_when_converged: bool,
+    pub seed: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SimulationReport {
+    // Test accuracy of the global model after each round
+    pub accuracy_per_round: Vec<f64>,
+    pub final_accuracy: f64,
+    pub final_loss: f64,
+    pub rounds_run: u64,
+    pub converged: bool,
+    // Share of triggered test records classified as the backdoor's target; None
+    // unless the attack is a backdoor
+    pub backdoor_success_rate: Option<f64>,
+    // Updates the coordinator rejected during validation, over all rounds
+    pub rejected_updates: usize,
+    // Broadcasts plus uploads as encoded for the configured compression
+    pub bytes_transferred: u64,
+    // Wall-clock seconds of local training, summed over clients and rounds
+    pub computation_secs: f64,
+    pub energy_kwh: f64,
+    pub carbon_kg_co2e: f64,
+    pub epsilon_spent: f64,
+}
+
+pub struct Simulation {
+    config: SimulationConfig,
+    clients: Vec<ClientData>,
+    test: ClientData,
+    model: LayerStack,
+    initial_weights: Vec<f64>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            clients: 20,
+            dimension: 10,
+            records_per_client: 100,
+            test_records: 1_000,
+            rounds: 10,
+            learning_rate: 0.5,
+            label_noise: 0.05,
+            partition: DataPartition::Iid,
+            model: SimulatedModel::Linear,
+            stop_when_converged: false,
+            seed: 7,
+        }
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+impl SimulatedModel {
+    pub fn parameter_count(&self, dimension: usize) -> usize {
+        match self {
+            SimulatedModel::Linear => dimension + 1,
+            SimulatedModel::Mlp { hidden } => (dimension + 1) * hidden + hidden + 1,
+        }
+    }
+
+    // The layers, with zero parameters
+    fn layers(&self, dimension: usize) -> Result<LayerStack, String> {
+        let dense = |inputs, outputs, activation| {
+            DenseLayer::from_parameters(inputs, outputs, activation, &vec![0.0; (inputs + 1) * outputs])
+        };
+        match self {
+            SimulatedModel::Linear => LayerStack::new(vec![dense(dimension, 1, Activation::Identity)?]),
+            SimulatedModel::Mlp { hidden } => {
+                LayerStack::new(vec![dense(dimension, *hidden, Activation::Tanh)?, dense(*hidden, 1, Activation::Identity)?])
+            }
+        }
+    }
+
+    // The linear model starts at zero; the MLP from seeded Glorot-uniform weights,
+    // since hidden units that start equal stay equal
+    fn initial_parameters(&self, dimension: usize, rng: &mut StdRng) -> Vec<f64> {
+        let mut glorot = |inputs: usize, outputs: usize| {
+            let limit = (6.0 / (inputs + outputs) as f64).sqrt();
+            let weights = (0..inputs * outputs).map(|_| rng.gen_range(-limit..=limit));
+            weights.chain(std::iter::repeat_n(0.0, outputs)).collect::<Vec<f64>>()
+        };
+        match self {
+            SimulatedModel::Linear => vec![0.0; dimension + 1],
+            SimulatedModel::Mlp { hidden } => [glorot(dimension, *hidden), glorot(*hidden, 1)].concat(),
+        }
+    }
+}
+
+// Probability of class 1 for each record
+fn probabilities(model: &LayerStack, data: &ClientData) -> Vec<f64> {
+    if data.labels.is_empty() {
+        return Vec::new();
+    }
+    model.forward(data.features.concat(), data.labels.len()).output.into_iter().map(sigmoid).collect()
+}
+
+pub fn predict(model: &LayerStack, features: &[f64]) -> f64 {
+    sigmoid(model.forward(features.to_vec(), 1).output[0])
+}
+
+pub fn accuracy(model: &LayerStack, data: &ClientData) -> f64 {
+    if data.labels.is_empty() {
+        return 0.0;
+    }
+    let correct = probabilities(model, data).iter().zip(&data.labels).filter(|(p, &y)| (**p >= 0.5) == (y >= 0.5)).count();
+    correct as f64 / data.labels.len() as f64
+}
+
+pub fn log_loss(model: &LayerStack, data: &ClientData) -> f64 {
+    let total: f64 = probabilities(model, data)
+        .iter()
+        .zip(&data.labels)
+        .map(|(p, &y)| {
+            let p = p.clamp(1e-12, 1.0 - 1e-12);
+            -(y * p.ln() + (1.0 - y) * (1.0 - p).ln())
+        })
+        .sum();
+    total / data.labels.len().max(1) as f64
+}
+
+// Minibatch SGD on the log loss, reshuffling every epoch
+pub fn local_sgd(model: &mut LayerStack, data: &ClientData, epochs: u32, batch_size: usize, learning_rate: f64, rng: &mut StdRng) {
+    let mut order: Vec<usize> = (0..data.labels.len()).collect();
+    for _ in 0..epochs {
+        order.shuffle(rng);
+        for batch in order.chunks(batch_size.max(1)) {
+            let input: Vec<f64> = batch.iter().flat_map(|&i| data.features[i].iter().copied()).collect();
+            let cache = model.forward(input, batch.len());
+            // Gradient of the mean log loss with respect to the logits
+            let grad_output: Vec<f64> =
+                cache.output.iter().zip(batch).map(|(z, &i)| (sigmoid(*z) - data.labels[i]) / batch.len() as f64).collect();
+            let (_, gradient) = model.backward(&cache, &grad_output);
+            model.step(&gradient, learning_rate);
+        }
+    }
+}
+
+// The update as the coordinator's decompression expects it, and its size in bytes.
+// Quantized updates carry levels over [-1, 1], so larger weights are clipped.
+fn encode_update(method: &CompressionMethod, weights: &[f64]) -> Result<(Vec<f64>, u64), String> {
+    let levels = match method {
+        CompressionMethod::None => return Ok((weights.to_vec(), weights.len() as u64 * 8)),
+        CompressionMethod::Quantization { bits } if (1..=31).contains(bits) => 2_u64.pow(*bits as u32),
+        CompressionMethod::QSGD { levels } | CompressionMethod::FedPAQ { quantization_levels: levels } if *levels >= 2 => *levels as u64,
+        other => return Err(format!("Simulated clients cannot produce {:?} updates", other)),
+    };
+    let top = (levels - 1) as f64;
+    let encoded = weights.iter().map(|w| ((w.clamp(-1.0, 1.0) + 1.0) / 2.0 * top).round()).collect();
+    let bits = 64 - (levels - 1).leading_zeros() as u64;
+    Ok((encoded, (weights.len() as u64 * bits).div_ceil(8)))
+}
+
+// Splits the pooled records over `clients`
+fn partition(pool: ClientData, clients: usize, partition: &DataPartition, rng: &mut StdRng) -> Result<Vec<ClientData>, String> {
+    let mut assignment: Vec<Vec<usize>> = vec![Vec::new(); clients];
+    match partition {
+        DataPartition::Iid => {
+            let mut order: Vec<usize> = (0..pool.labels.len()).collect();
+            order.shuffle(rng);
+            for (position, index) in order.into_iter().enumerate() {
+                assignment[position % clients].push(index);
+            }
+        }
+        DataPartition::Dirichlet { alpha } => {
+            let gamma = Gamma::new(*alpha, 1.0).map_err(|e| format!("Invalid Dirichlet alpha: {}", e))?;
+            for class in [0.0, 1.0] {
+                let mut members: Vec<usize> = (0..pool.labels.len()).filter(|&i| pool.labels[i] == class).collect();
+                members.shuffle(rng);
+                let shares: Vec<f64> = (0..clients).map(|_| gamma.sample(rng)).collect();
+                let total: f64 = shares.iter().sum();
+                // Cumulative shares as cut points, so every record is assigned once
+                let mut cumulative = 0.0;
+                let mut start = 0;
+                for (client, share) in shares.iter().enumerate() {
+                    cumulative += share / total;
+                    let end = if client + 1 == clients { members.len() } else { ((cumulative * members.len() as f64).round() as usize).min(members.len()) };
+                    assignment[client].extend(&members[start..end.max(start)]);
+                    start = end.max(start);
+                }
+            }
+        }
+    }
+    Ok(assignment
+        .into_iter()
+        .map(|indices| ClientData {
+            features: indices.iter().map(|&i| pool.features[i].clone()).collect(),
+            labels: indices.iter().map(|&i| pool.labels[i]).collect(),
+        })
+        .collect())
+}
+
+impl SimulationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.clients == 0 || self.dimension == 0 || self.records_per_client == 0 || self.test_records == 0 {
+            return Err("Simulation needs clients, features and records".to_string());
+        }
+        if !(self.learning_rate.is_finite() && self.learning_rate > 0.0) {
+            return Err("Learning rate must be positive".to_string());
+        }
+        if !(0.0..0.5).contains(&self.label_noise) {
+            return Err("Label noise must be within [0, 0.5)".to_string());
+        }
+        if let DataPartition::Dirichlet { alpha } = self.partition {
+            if !(alpha.is_finite() && alpha > 0.0) {
+                return Err("Dirichlet alpha must be positive".to_string());
+            }
+        }
+        if self.model == (SimulatedModel::Mlp { hidden: 0 }) {
+            return Err("An MLP needs at least one hidden unit".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Result<Self, String> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let task = SyntheticTask::new(config.dimension, config.label_noise, &mut rng);
+        Self::from_source(config, &task, rng)
+    }
+
+    fn from_source(config: SimulationConfig, source: &dyn DatasetSource, mut rng: StdRng) -> Result<Self, String> {
+        config.validate()?;
+        let pool = source.sample(config.clients * config.records_per_client, false, &mut rng)?;
+        let clients = partition(pool, config.clients, &config.partition, &mut rng)?;
+        let test = source.sample(config.test_records, true, &mut rng)?;
+        let model = config.model.layers(config.dimension)?;
+        let initial_weights = config.model.initial_parameters(config.dimension, &mut rng);
+        Ok(Simulation { config, clients, test, model, initial_weights })
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn client_data(&self) -> &[ClientData] {
+        &self.clients
+    }
+
+    // The FL configuration's model_dimension must equal this
+    pub fn model_dimension(&self) -> usize {
+        self.initial_weights.len()
+    }
+
+    // Bytes the records of every client and the test set take as f64
+    pub fn dataset_bytes(&self) -> u64 {
+        let records: usize = self.clients.iter().map(|c| c.labels.len()).sum::<usize>() + self.test.labels.len();
+        (records * (self.config.dimension + 1) * std::mem::size_of::<f64>()) as u64
+    }
+
+    // The model with the given parameters
+    pub fn model_with(&self, weights: &[f64]) -> Result<LayerStack, String> {
+        let mut model = self.model.clone();
+        model.set_parameters(weights)?;
+        Ok(model)
+    }
+
+    // Runs the configured rounds from the initial model on a fresh coordinator
+    pub fn run(&self, fl_config: FederatedLearningConfig, scenario: Option<&AttackScenario>) -> Result<SimulationReport, String> {
+        let mut coordinator = FederatedLearningCoordinator::new_with_weights(fl_config, self.initial_weights.clone())?;
+        self.run_on(&mut coordinator, scenario)
+    }
+
+    // Runs on a coordinator the caller has already configured (clipping, screening,
+    // privacy policies); the first clients are the scenario's attackers
+    pub fn run_on(&self, coordinator: &mut FederatedLearningCoordinator, scenario: Option<&AttackScenario>) -> Result<SimulationReport, String> {
+        let fl_config = coordinator.get_config().clone();
+        if fl_config.model_dimension != self.model_dimension() {
+            return Err(format!("Model dimension {} differs from the simulated model's {}", fl_config.model_dimension, self.model_dimension()));
+        }
+        if let Some(scenario) = scenario {
+            scenario.validate(self.config.dimension)?;
+        }
+        let malicious = scenario.map_or(0, |s| s.malicious_clients(self.config.clients));
+        // Attackers poison their extract once, before training starts
+        let datasets: Vec<ClientData> = self
+            .clients
+            .iter()
+            .enumerate()
+            .map(|(i, data)| match scenario {
+                Some(scenario) if i < malicious => scenario.attack.poison_data(data),
+                _ => data.clone(),
+            })
+            .collect();
+        // Clients a Dirichlet split left without records never report
+        let active: Vec<usize> = (0..datasets.len()).filter(|&i| !datasets[i].labels.is_empty()).collect();
+        let cohort_size = ((fl_config.client_fraction * active.len() as f64).ceil() as usize)
+            .max(fl_config.min_clients as usize)
+            .min(active.len());
+
+        let mut rng = StdRng::seed_from_u64(self.config.seed.wrapping_add(1));
+        let mut report = SimulationReport {
+            accuracy_per_round: Vec::new(),
+            final_accuracy: 0.0,
+            final_loss: 0.0,
+            rounds_run: 0,
+            converged: false,
+            backdoor_success_rate: None,
+            rejected_updates: 0,
+            bytes_transferred: 0,
+            computation_secs: 0.0,
+            energy_kwh: 0.0,
+            carbon_kg_co2e: 0.0,
+            epsilon_spent: 0.0,
+        };
+        for _ in 0..self.config.rounds {
+            let global = coordinator.get_broadcast_weights();
+            let round = coordinator.get_global_model().round;
+            let mut cohort: Vec<usize> = active.choose_multiple(&mut rng, cohort_size).copied().collect();
+            cohort.sort_unstable();
+
+            let mut updates = Vec::with_capacity(cohort.len());
+            for i in cohort {
+                let data = &datasets[i];
+                let mut local = self.model_with(&global)?;
+                let started = Instant::now();
+                local_sgd(&mut local, data, fl_config.local_epochs, fl_config.batch_size as usize, self.config.learning_rate, &mut rng);
+                let computation_time = started.elapsed().as_secs_f64();
+                let mut weights = local.parameters();
+                if let Some(scenario) = scenario.filter(|_| i < malicious) {
+                    weights = scenario.attack.poison_update(&global, weights);
+                    local.set_parameters(&weights)?;
+                }
+                let (encoded, upload_bytes) = encode_update(&fl_config.compression_method, &weights)?;
+                let dense_bytes = weights.len() as u64 * 8;
+                report.bytes_transferred += dense_bytes + upload_bytes;
+                report.computation_secs += computation_time;
+                updates.push(ModelUpdate {
+                    client_id: format!("client_{}", i),
+                    round,
+                    loss: log_loss(&local, data),
+                    accuracy: accuracy(&local, data),
+                    gradients: encoded,
+                    weights,
+                    data_size: data.labels.len(),
+                    computation_time,
+                    communication_cost: upload_bytes as f64,
+                    privacy_budget_used: 0.0,
+                    compressed: upload_bytes < dense_bytes,
+                    compression_ratio: (upload_bytes < dense_bytes).then(|| upload_bytes as f64 / dense_bytes as f64),
+                    attestation: None,
+                    personalized_accuracy: None,
+                    sparse_gradients: None,
+                    trained_ranges: None,
+                    gradient_proof: None,
+                });
+            }
+            let model = coordinator.execute_round(updates)?;
+            report.rejected_updates += coordinator.get_round_diagnostics().rejections.len();
+            report.accuracy_per_round.push(accuracy(&self.model_with(&model.weights)?, &self.test));
+            report.rounds_run += 1;
+            if self.config.stop_when_converged && coordinator.is_converged() {
+                report.converged = true;
+                break;
+            }
+        }
+
+        let global_model = coordinator.get_global_model();
+        let final_model = self.model_with(&global_model.weights)?;
+        report.final_accuracy = accuracy(&final_model, &self.test);
+        report.final_loss = log_loss(&final_model, &self.test);
+        report.backdoor_success_rate = scenario.and_then(|s| s.attack.backdoor_success_rate(&final_model, &self.test));
+        report.energy_kwh = global_model.communication_metrics.total_energy_kwh;
+        report.carbon_kg_co2e = global_model.communication_metrics.total_carbon_kg_co2e;
+        report.epsilon_spent = global_model.privacy_metrics.total_epsilon_used;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(method: AggregationMethod, model_dimension: usize) -> FederatedLearningConfig {
+        FederatedLearningConfigBuilder::new()
+            .model_dimension(model_dimension)
+            .min_clients(1)
+            .privacy_method(PrivacyMethod::None)
+            .aggregation_method(method)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_median_withstands_an_attack_that_breaks_fedavg() {
+        let simulation = Simulation::new(SimulationConfig { clients: 10, rounds: 8, ..SimulationConfig::default() }).unwrap();
+        let dimension = simulation.model_dimension();
+        let clean = simulation.run(config(AggregationMethod::FedAvg, dimension), None).unwrap();
+        assert!(clean.final_accuracy > 0.85, "clean accuracy {}", clean.final_accuracy);
+
+        // Three of ten clients push the model backwards, five times as hard
+        let scenario = AttackScenario { attack: Attack::Scaled { factor: -5.0 }, malicious_fraction: 0.3 };
+        let fedavg = simulation.run(config(AggregationMethod::FedAvg, dimension), Some(&scenario)).unwrap();
+        let median = simulation.run(config(AggregationMethod::Median, dimension), Some(&scenario)).unwrap();
+        assert!(fedavg.final_accuracy < 0.6, "FedAvg under attack {}", fedavg.final_accuracy);
+        assert!(median.final_accuracy > 0.8, "median under attack {}", median.final_accuracy);
+    }
+
+    #[test]
+    fn test_dirichlet_partition_skews_labels_and_fedavg_still_learns() {
+        let skewed = SimulationConfig {
+            clients: 10,
+            rounds: 15,
+            partition: DataPartition::Dirichlet { alpha: 0.1 },
+            model: SimulatedModel::Mlp { hidden: 4 },
+            ..SimulationConfig::default()
+        };
+        let simulation = Simulation::new(skewed).unwrap();
+        let records: usize = simulation.client_data().iter().map(|c| c.labels.len()).sum();
+        assert_eq!(records, 1_000);
+        // Most clients hold mostly one class
+        let lopsided = simulation
+            .client_data()
+            .iter()
+            .filter(|c| c.labels.is_empty() || {
+                let positive = c.labels.iter().sum::<f64>() / c.labels.len() as f64;
+                !(0.2..=0.8).contains(&positive)
+            })
+            .count();
+        assert!(lopsided >= 6, "{} lopsided clients", lopsided);
+
+        let report = simulation.run(config(AggregationMethod::FedAvg, simulation.model_dimension()), None).unwrap();
+        assert_eq!(report.rounds_run, 15);
+        assert!(report.final_accuracy > 0.8, "non-IID accuracy {}", report.final_accuracy);
+        assert!(report.bytes_transferred > 0 && report.final_loss.is_finite());
+    }
+}
@@ -0,0 +1,182 @@
+// Poisoning attackers for simulated rounds, after the threat models robust
+// aggregation is usually evaluated against:
+//
+// - LabelFlip: trains honestly on its records with every label inverted.
+// - SignFlip: submits its honest update reversed, global - (local - global).
+// - Scaled: submits its honest update multiplied by `factor`; a large factor is the
+//   boosting step of model replacement, a negative one drags the model backwards.
+// - Backdoor: adds a copy of its records carrying the trigger (one feature set to
+//   `trigger_value`) labelled `target_label`, trains on both, and boosts its update
+//   by `boost` so it survives averaging (Bagdasaryan et al., 2020).
+//
+// Attackers poison their data once and their update every round; they collude only
+// in attacking the same way. `Simulation::benchmark_attacks` runs every aggregation
+// method against every scenario.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Attack {
+    LabelFlip,
+    SignFlip,
+    Scaled { factor: f64 },
+    Backdoor { trigger_feature: usize, trigger_value: f64, target_label: f64, boost: f64 },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AttackScenario {
+    pub attack: Attack,
+    // Share of the clients that are attackers
+    pub malicious_fraction: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AttackBenchmark {
+    pub aggregation_method: AggregationMethod,
+    // None for the run without attackers
+    pub attack: Option<Attack>,
+    pub malicious_clients: usize,
+    pub final_accuracy: f64,
+    pub backdoor_success_rate: Option<f64>,
+    pub rejected_updates: usize,
+}
+
+impl AttackScenario {
+    pub fn validate(&self, dimension: usize) -> Result<(), String> {
+        if !(0.0..1.0).contains(&self.malicious_fraction) {
+            return Err("Malicious fraction must be within [0, 1)".to_string());
+        }
+        match self.attack {
+            Attack::Scaled { factor } if !factor.is_finite() => Err("Scale factor must be finite".to_string()),
+            Attack::Backdoor { trigger_feature, .. } if trigger_feature >= dimension => {
+                Err(format!("Trigger feature {} is outside the {} features", trigger_feature, dimension))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn malicious_clients(&self, clients: usize) -> usize {
+        (self.malicious_fraction * clients as f64).round() as usize
+    }
+}
+
+impl Attack {
+    pub fn poison_data(&self, data: &ClientData) -> ClientData {
+        match self {
+            Attack::LabelFlip => ClientData { features: data.features.clone(), labels: data.labels.iter().map(|y| 1.0 - y).collect() },
+            Attack::Backdoor { target_label, .. } => {
+                let mut poisoned = data.clone();
+                poisoned.features.extend(data.features.iter().map(|x| self.triggered(x)));
+                poisoned.labels.extend(data.labels.iter().map(|_| *target_label));
+                poisoned
+            }
+            Attack::SignFlip | Attack::Scaled { .. } => data.clone(),
+        }
+    }
+
+    pub fn poison_update(&self, global: &[f64], local: Vec<f64>) -> Vec<f64> {
+        let scale = match self {
+            Attack::SignFlip => -1.0,
+            Attack::Scaled { factor } => *factor,
+            Attack::Backdoor { boost, .. } => *boost,
+            Attack::LabelFlip => return local,
+        };
+        global.iter().zip(&local).map(|(g, w)| g + scale * (w - g)).collect()
+    }
+
+    // The record with the trigger applied; unchanged for attacks without one
+    pub fn triggered(&self, features: &[f64]) -> Vec<f64> {
+        let mut triggered = features.to_vec();
+        if let Attack::Backdoor { trigger_feature, trigger_value, .. } = self {
+            if let Some(value) = triggered.get_mut(*trigger_feature) {
+                *value = *trigger_value;
+            }
+        }
+        triggered
+    }
+
+    // Over test records not already of the target class, the share the model assigns
+    // to the target once triggered
+    pub fn backdoor_success_rate(&self, model: &LayerStack, test: &ClientData) -> Option<f64> {
+        let target_label = match self {
+            Attack::Backdoor { target_label, .. } => *target_label,
+            _ => return None,
+        };
+        let others: Vec<&Vec<f64>> = test.features.iter().zip(&test.labels).filter(|(_, &y)| y != target_label).map(|(x, _)| x).collect();
+        if others.is_empty() {
+            return Some(0.0);
+        }
+        let hits = others.iter().filter(|x| (predict(model, &self.triggered(x)) >= 0.5) == (target_label >= 0.5)).count();
+        Some(hits as f64 / others.len() as f64)
+    }
+}
+
+impl Simulation {
+    // Every method without attackers and against each scenario; `base_config`
+    // supplies everything but the aggregation method
+    pub fn benchmark_attacks(
+        &self,
+        base_config: &FederatedLearningConfig,
+        methods: &[AggregationMethod],
+        scenarios: &[AttackScenario],
+    ) -> Result<Vec<AttackBenchmark>, String> {
+        let mut results = Vec::new();
+        for method in methods {
+            let config = FederatedLearningConfig { aggregation_method: method.clone(), ..base_config.clone() };
+            let runs = std::iter::once(None).chain(scenarios.iter().map(Some));
+            for scenario in runs {
+                let report = self.run(config.clone(), scenario)?;
+                results.push(AttackBenchmark {
+                    aggregation_method: method.clone(),
+                    attack: scenario.map(|s| s.attack.clone()),
+                    malicious_clients: scenario.map_or(0, |s| s.malicious_clients(self.config().clients)),
+                    final_accuracy: report.final_accuracy,
+                    backdoor_success_rate: report.backdoor_success_rate,
+                    rejected_updates: report.rejected_updates,
+                });
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backdoor_survives_fedavg_but_not_median() {
+        let backdoor = Attack::Backdoor { trigger_feature: 0, trigger_value: 6.0, target_label: 1.0, boost: 10.0 };
+        let data = ClientData { features: vec![vec![0.5, -1.0], vec![-0.5, 2.0]], labels: vec![0.0, 1.0] };
+        // The poisoned copy carries the trigger and the target label
+        let poisoned = backdoor.poison_data(&data);
+        assert_eq!(poisoned.features[2..], [vec![6.0, -1.0], vec![6.0, 2.0]]);
+        assert_eq!(poisoned.labels, vec![0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(Attack::LabelFlip.poison_data(&data).labels, vec![1.0, 0.0]);
+        assert_eq!(Attack::SignFlip.poison_update(&[1.0, 1.0], vec![1.5, 0.0]), vec![0.5, 2.0]);
+        assert_eq!(backdoor.poison_update(&[1.0, 1.0], vec![1.5, 1.0]), vec![6.0, 1.0]);
+
+        let scenario = AttackScenario { attack: backdoor, malicious_fraction: 0.2 };
+        assert!(scenario.validate(10).is_ok());
+        assert!(AttackScenario { malicious_fraction: 1.0, ..scenario.clone() }.validate(10).is_err());
+
+        let simulation = Simulation::new(SimulationConfig { clients: 10, rounds: 8, ..SimulationConfig::default() }).unwrap();
+        let config = FederatedLearningConfigBuilder::new()
+            .model_dimension(simulation.model_dimension())
+            .min_clients(1)
+            .privacy_method(PrivacyMethod::None)
+            .build()
+            .unwrap();
+        let methods = [AggregationMethod::FedAvg, AggregationMethod::Median];
+        let results = simulation.benchmark_attacks(&config, &methods, &[scenario]).unwrap();
+        // A clean run and the attacked run for each method
+        assert_eq!(results.len(), 4);
+        let (clean, fedavg, median) = (&results[0], &results[1], &results[3]);
+        assert_eq!((clean.malicious_clients, clean.backdoor_success_rate), (0, None));
+        assert_eq!(fedavg.malicious_clients, 2);
+        // Two boosted attackers take over the average; the median ignores them
+        assert!(fedavg.backdoor_success_rate.unwrap() > 0.9, "FedAvg backdoor {:?}", fedavg.backdoor_success_rate);
+        assert!(median.backdoor_success_rate.unwrap() < 0.1, "median backdoor {:?}", median.backdoor_success_rate);
+        assert!(median.final_accuracy > 0.9, "median under attack {}", median.final_accuracy);
+    }
+}
@@ -0,0 +1,218 @@
+// Mergeable quantile sketch in the style of KLL (Karnin, Lang & Liberty, 2016) with a
+// fixed capacity per level. Level h holds items standing for 2^h inputs; when a level
+// overflows it is sorted and every other item is promoted, alternating the kept half
+// between compactions. Compaction is deterministic, so every replica of the
+// aggregator produces bit-identical quantiles from the same updates.
+//
+// Each compaction at level h moves any rank by at most 2^h, so with capacity k over
+// n inputs the rank error is at most (levels / k) * n. With n <= k nothing is ever
+// compacted and the sketch is exact.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QuantileSketch {
+    capacity: usize,
+    levels: Vec<Vec<f64>>,
+    count: u64,
+    // Sum of 2^h over all compactions, an upper bound on the absolute rank error
+    rank_error: u64,
+    compactions: u64,
+}
+
+impl QuantileSketch {
+    pub fn new(capacity: usize) -> Self {
+        // An even capacity keeps both halves of a compaction the same size
+        let capacity = (capacity.max(2) + 1) & !1;
+        QuantileSketch {
+            capacity,
+            levels: vec![Vec::with_capacity(capacity + 1)],
+            count: 0,
+            rank_error: 0,
+            compactions: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for level in &mut self.levels {
+            level.clear();
+        }
+        self.count = 0;
+        self.rank_error = 0;
+        self.compactions = 0;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    // Items held, at most about capacity per level
+    pub fn retained(&self) -> usize {
+        self.levels.iter().map(Vec::len).sum()
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.levels[0].push(value);
+        self.count += 1;
+        if self.levels[0].len() > self.capacity {
+            self.compress();
+        }
+    }
+
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        while self.levels.len() < other.levels.len() {
+            self.levels.push(Vec::with_capacity(self.capacity + 1));
+        }
+        for (level, items) in other.levels.iter().enumerate() {
+            self.levels[level].extend_from_slice(items);
+        }
+        self.count += other.count;
+        self.rank_error += other.rank_error;
+        self.compactions += other.compactions;
+        self.compress();
+    }
+
+    fn compress(&mut self) {
+        let mut level = 0;
+        while level < self.levels.len() {
+            if self.levels[level].len() > self.capacity {
+                self.compact(level);
+            }
+            level += 1;
+        }
+    }
+
+    fn compact(&mut self, level: usize) {
+        if level + 1 == self.levels.len() {
+            self.levels.push(Vec::with_capacity(self.capacity + 1));
+        }
+
+        let mut items = std::mem::take(&mut self.levels[level]);
+        items.sort_unstable_by(|a, b| a.total_cmp(b));
+        // An odd item out stays behind so weights are conserved exactly
+        let leftover = if items.len() % 2 == 1 { items.pop() } else { None };
+        let offset = (self.compactions % 2) as usize;
+        self.compactions += 1;
+        self.rank_error += 1u64 << level;
+
+        let promoted = items.iter().skip(offset).step_by(2).copied();
+        self.levels[level + 1].extend(promoted);
+        items.clear();
+        items.extend(leftover);
+        self.levels[level] = items;
+    }
+
+    // Sorted (value, weight) pairs; the weights sum to `count`
+    fn weighted_items(&self) -> Vec<(f64, u64)> {
+        let mut items: Vec<(f64, u64)> = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, values)| values.iter().map(move |&v| (v, 1u64 << level)))
+            .collect();
+        items.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        items
+    }
+
+    // Value of the input at zero-based `rank` in sorted order
+    fn value_at_rank(items: &[(f64, u64)], rank: u64) -> Option<f64> {
+        let mut cumulative = 0;
+        for &(value, weight) in items {
+            cumulative += weight;
+            if cumulative > rank {
+                return Some(value);
+            }
+        }
+        items.last().map(|&(value, _)| value)
+    }
+
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        Self::value_at_rank(&self.weighted_items(), rank)
+    }
+
+    // Median with the same even-count convention as exact median aggregation
+    pub fn median(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let items = self.weighted_items();
+        let upper = Self::value_at_rank(&items, self.count / 2)?;
+        if self.count.is_multiple_of(2) {
+            let lower = Self::value_at_rank(&items, self.count / 2 - 1)?;
+            Some((lower + upper) / 2.0)
+        } else {
+            Some(upper)
+        }
+    }
+
+    // Mean of the inputs ranked in [trim, count - trim), weighting each sketch item
+    // by how much of its weight falls inside that rank window
+    pub fn trimmed_mean(&self, trim: u64) -> Option<f64> {
+        if self.count <= 2 * trim {
+            return None;
+        }
+        let (low, high) = (trim, self.count - trim);
+        let mut sum = 0.0;
+        let mut rank = 0;
+        for (value, weight) in self.weighted_items() {
+            let start = rank.max(low);
+            let end = (rank + weight).min(high);
+            if end > start {
+                sum += value * (end - start) as f64;
+            }
+            rank += weight;
+        }
+        Some(sum / (high - low) as f64)
+    }
+
+    // Upper bound on the rank error as a fraction of the inputs seen
+    pub fn rank_error_bound(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.rank_error as f64 / self.count as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_below_capacity() {
+        let mut sketch = QuantileSketch::new(64);
+        for v in [5.0, 1.0, 4.0, 2.0, 3.0, 6.0] {
+            sketch.insert(v);
+        }
+        assert_eq!(sketch.median(), Some(3.5));
+        assert_eq!(sketch.trimmed_mean(1), Some(3.5));
+        assert_eq!(sketch.rank_error_bound(), 0.0);
+    }
+
+    #[test]
+    fn test_rank_error_within_bound() {
+        let n = 10_000u64;
+        let mut sketch = QuantileSketch::new(128);
+        // Deterministic permutation of 0..n
+        for i in 0..n {
+            sketch.insert(((i * 7919) % n) as f64);
+        }
+
+        let bound = sketch.rank_error_bound();
+        assert!(bound > 0.0 && bound < 0.1);
+        for q in [0.01, 0.25, 0.5, 0.75, 0.99] {
+            let estimate = sketch.quantile(q).unwrap();
+            let true_rank = q * (n - 1) as f64;
+            // Values equal their rank in 0..n
+            assert!((estimate - true_rank).abs() <= bound * n as f64 + 1.0);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Sparse gradients as a first-class update payload. Top-k and DGC clients send only
+// the coordinates they kept, often 1% or fewer of the model; expanding every such
+// update to a dense vector on arrival costs the coordinator a full model's memory per
+// client. A `ModelUpdate` carrying `sparse_gradients` stays sparse through validation,
+// clipping and FedNova scaling, and weighted averaging scatters its values straight
+// into the aggregate.
+//
+// Stages that need every coordinate of every update (robust aggregators, per-update
+// noise, quantized decompression, clustering, personalization, SCAFFOLD) still see
+// dense updates: when the round's configuration uses any of them, sparse updates are
+// expanded once, right after validation.
+
+use crate::*;
+
+#[derive(Clone, Copy, Debug)]
+pub enum GradientPayload<'a> {
+    Dense(&'a [f64]),
+    Sparse(&'a SparseGradients),
+}
+
+impl SparseGradients {
+    // Indices must be strictly increasing and within the model
+    pub fn validate(&self, dimension: usize) -> Result<(), String> {
+        if self.indices.len() != self.values.len() {
+            return Err(format!("{} sparse indices for {} values", self.indices.len(), self.values.len()));
+        }
+        if self.indices.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("Sparse indices must be strictly increasing".to_string());
+        }
+        match self.indices.last() {
+            Some(&last) if last >= dimension => Err(format!("Sparse index {} is outside the model of {}", last, dimension)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_dense(&self, dimension: usize) -> Vec<f64> {
+        let mut dense = vec![0.0; dimension];
+        for (&index, &value) in self.indices.iter().zip(&self.values) {
+            if index < dimension {
+                dense[index] = value;
+            }
+        }
+        dense
+    }
+
+    pub fn density(&self, dimension: usize) -> f64 {
+        if dimension == 0 {
+            return 0.0;
+        }
+        self.values.len() as f64 / dimension as f64
+    }
+}
+
+impl ModelUpdate {
+    pub fn payload(&self) -> GradientPayload<'_> {
+        match &self.sparse_gradients {
+            Some(sparse) => GradientPayload::Sparse(sparse),
+            None => GradientPayload::Dense(&self.gradients),
+        }
+    }
+
+    // The transmitted gradient values; scaling or clipping them is the same for both
+    // payloads, since the omitted coordinates are zero
+    pub fn gradient_values(&self) -> &[f64] {
+        match &self.sparse_gradients {
+            Some(sparse) => &sparse.values,
+            None => &self.gradients,
+        }
+    }
+
+    pub fn gradient_values_mut(&mut self) -> &mut [f64] {
+        match &mut self.sparse_gradients {
+            Some(sparse) => &mut sparse.values,
+            None => &mut self.gradients,
+        }
+    }
+
+    pub fn densify(&mut self, dimension: usize) {
+        if let Some(sparse) = self.sparse_gradients.take() {
+            self.gradients = sparse.to_dense(dimension);
+        }
+    }
+}
+
+impl AggregationEngine {
+    // Weighted average by data size over a mix of dense and sparse updates, without
+    // expanding the sparse ones
+    pub fn sparse_weighted_average(&self, updates: &[ModelUpdate], dimension: usize) -> Result<Vec<f64>, String> {
+        if updates.is_empty() {
+            return Err("No updates to aggregate".to_string());
+        }
+
+        let total_weight: f64 = updates.iter().map(|u| u.data_size as f64).sum();
+        let mut aggregated = vec![0.0; dimension];
+        for update in updates {
+            let weight = update.data_size as f64 / total_weight;
+            match update.payload() {
+                GradientPayload::Dense(gradients) => {
+                    if gradients.len() != dimension {
+                        return Err(format!("Update from {} has {} gradients, model has {}", update.client_id, gradients.len(), dimension));
+                    }
+                    kernels::axpy(weight, gradients, &mut aggregated);
+                }
+                GradientPayload::Sparse(sparse) => {
+                    sparse.validate(dimension)?;
+                    for (&index, &value) in sparse.indices.iter().zip(&sparse.values) {
+                        aggregated[index] += weight * value;
+                    }
+                }
+            }
+        }
+        Ok(aggregated)
+    }
+}
+
+impl FederatedLearningCoordinator {
+    // Whether every stage of a round under the current configuration accepts sparse updates
+    pub fn supports_sparse_updates(&self) -> bool {
+        matches!(self.config.aggregation_method, AggregationMethod::WeightedAverage | AggregationMethod::FedAvg)
+            && matches!(
+                self.config.privacy_method,
+                PrivacyMethod::None | PrivacyMethod::TrustedExecutionEnvironment
+            )
+            && matches!(
+                self.config.compression_method,
+                CompressionMethod::None
+                    | CompressionMethod::Sparsification { .. }
+                    | CompressionMethod::TopK { .. }
+                    | CompressionMethod::RandomK { .. }
+                    | CompressionMethod::DeepGradientCompression { .. }
+                    | CompressionMethod::AdaptiveCompression { .. }
+            )
+            && !matches!(self.config.algorithm, FLAlgorithm::SCAFFOLD)
+            && self.clustering.is_none()
+            && self.personalization.is_none()
+    }
+
+    pub(crate) fn densify_unsupported_sparse(&self, mut updates: Vec<ModelUpdate>) -> Vec<ModelUpdate> {
+        if !self.supports_sparse_updates() {
+            let dimension = self.global_model.weights.len();
+            updates.iter_mut().for_each(|u| u.densify(dimension));
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(gradients: Vec<f64>, sparse_gradients: Option<SparseGradients>, data_size: usize) -> ModelUpdate {
+        ModelUpdate { compressed: true, sparse_gradients, ..ModelUpdate::for_test("", gradients, data_size) }
+    }
+
+    #[test]
+    fn test_sparse_updates_aggregate_like_their_dense_form() {
+        let sparse = SparseGradients { indices: vec![1, 4], values: vec![2.0, -4.0] };
+        let updates = vec![update(vec![1.0, 1.0, 1.0, 1.0, 1.0], None, 1), update(Vec::new(), Some(sparse.clone()), 3)];
+        let engine = AggregationEngine::new();
+        let aggregated = engine.sparse_weighted_average(&updates, 5).unwrap();
+
+        let mut dense = updates.clone();
+        dense.iter_mut().for_each(|u| u.densify(5));
+        assert_eq!(dense[1].gradients, vec![0.0, 2.0, 0.0, 0.0, -4.0]);
+        assert_eq!(aggregated, engine.weighted_average(&dense).unwrap());
+        assert_eq!(aggregated, vec![0.25, 1.75, 0.25, 0.25, -2.75]);
+
+        assert!(sparse.validate(4).is_err());
+        assert!(SparseGradients { indices: vec![2, 1], values: vec![1.0, 1.0] }.validate(5).is_err());
+        assert!(engine.sparse_weighted_average(&updates, 6).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+// Split learning (SplitFed). The network is a stack of dense layers cut in two: each
+// client runs the layers up to the cut on its own records and sends the cut-layer
+// activations with their labels; the server runs the remaining layers, computes the
+// softmax cross-entropy loss and returns the gradient with respect to the
+// activations, which the client backpropagates through its own layers. Raw features
+// never leave the site, though activations are not private by themselves and should
+// be combined with clipping or noise where that matters.
+//
+// The server does not step after every batch. It accumulates its parameter gradients
+// over all activation batches of a round, weighted by batch size, and applies the
+// average in `aggregate_round`, so every client in a round trains against the same
+// server model. The client-side layers are averaged separately, as an ordinary
+// ModelUpdate through the FedAvg path.
+
+use crate::*;
+use rand::Rng;
+use std::collections::BTreeSet;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Identity,
+    Relu,
+    Sigmoid,
+    Tanh,
+}
+
+impl Activation {
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            Activation::Identity => x,
+            Activation::Relu => x.max(0.0),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Tanh => x.tanh(),
+        }
+    }
+
+    // Derivative from the pre-activation and the output
+    fn derivative(&self, pre: f64, out: f64) -> f64 {
+        match self {
+            Activation::Identity => 1.0,
+            Activation::Relu => {
+                if pre > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Sigmoid => out * (1.0 - out),
+            Activation::Tanh => 1.0 - out * out,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DenseLayer {
+    pub inputs: usize,
+    pub outputs: usize,
+    // outputs x inputs, row-major
+    weights: Vec<f64>,
+    bias: Vec<f64>,
+    pub activation: Activation,
+}
+
+impl DenseLayer {
+    // Glorot-uniform weights, zero bias
+    pub fn new(inputs: usize, outputs: usize, activation: Activation) -> Self {
+        let limit = (6.0 / (inputs + outputs).max(1) as f64).sqrt();
+        let mut rng = rand::thread_rng();
+        DenseLayer {
+            inputs,
+            outputs,
+            weights: (0..inputs * outputs).map(|_| rng.gen_range(-limit..=limit)).collect(),
+            bias: vec![0.0; outputs],
+            activation,
+        }
+    }
+
+    // Parameters are the weights followed by the bias
+    pub fn from_parameters(inputs: usize, outputs: usize, activation: Activation, parameters: &[f64]) -> Result<Self, String> {
+        let mut layer = DenseLayer { inputs, outputs, weights: vec![0.0; inputs * outputs], bias: vec![0.0; outputs], activation };
+        layer.set_parameters(parameters)?;
+        Ok(layer)
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        self.weights.len() + self.bias.len()
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        self.weights.iter().chain(self.bias.iter()).copied().collect()
+    }
+
+    pub fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), String> {
+        if parameters.len() != self.parameter_count() {
+            return Err(format!("Layer expects {} parameters, got {}", self.parameter_count(), parameters.len()));
+        }
+        let (weights, bias) = parameters.split_at(self.weights.len());
+        self.weights.copy_from_slice(weights);
+        self.bias.copy_from_slice(bias);
+        Ok(())
+    }
+
+    // (pre-activations, outputs) for a row-major batch
+    fn forward(&self, input: &[f64], batch: usize) -> (Vec<f64>, Vec<f64>) {
+        let mut pre = vec![0.0; batch * self.outputs];
+        for b in 0..batch {
+            let row = &input[b * self.inputs..(b + 1) * self.inputs];
+            for o in 0..self.outputs {
+                let w = &self.weights[o * self.inputs..(o + 1) * self.inputs];
+                pre[b * self.outputs + o] = self.bias[o] + w.iter().zip(row).map(|(w, x)| w * x).sum::<f64>();
+            }
+        }
+        let out = pre.iter().map(|&z| self.activation.apply(z)).collect();
+        (pre, out)
+    }
+
+    // (gradient w.r.t. the input, gradient w.r.t. the parameters)
+    fn backward(&self, input: &[f64], pre: &[f64], out: &[f64], grad_out: &[f64], batch: usize) -> (Vec<f64>, Vec<f64>) {
+        let delta: Vec<f64> =
+            (0..grad_out.len()).map(|i| grad_out[i] * self.activation.derivative(pre[i], out[i])).collect();
+        let mut grad_input = vec![0.0; batch * self.inputs];
+        let mut grad_params = vec![0.0; self.parameter_count()];
+        let (grad_weights, grad_bias) = grad_params.split_at_mut(self.weights.len());
+        for b in 0..batch {
+            let row = &input[b * self.inputs..(b + 1) * self.inputs];
+            for o in 0..self.outputs {
+                let d = delta[b * self.outputs + o];
+                if d == 0.0 {
+                    continue;
+                }
+                grad_bias[o] += d;
+                for i in 0..self.inputs {
+                    grad_weights[o * self.inputs + i] += d * row[i];
+                    grad_input[b * self.inputs + i] += d * self.weights[o * self.inputs + i];
+                }
+            }
+        }
+        (grad_input, grad_params)
+    }
+}
+
+// Inputs and pre-activations of each layer from one forward pass
+pub(crate) struct ForwardCache {
+    batch: usize,
+    inputs: Vec<Vec<f64>>,
+    pre: Vec<Vec<f64>>,
+    pub(crate) output: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LayerStack {
+    layers: Vec<DenseLayer>,
+}
+
+impl LayerStack {
+    pub fn new(layers: Vec<DenseLayer>) -> Result<Self, String> {
+        if layers.is_empty() {
+            return Err("A layer stack needs at least one layer".to_string());
+        }
+        for pair in layers.windows(2) {
+            if pair[0].outputs != pair[1].inputs {
+                return Err(format!("Layer widths do not chain: {} outputs into {} inputs", pair[0].outputs, pair[1].inputs));
+            }
+        }
+        Ok(LayerStack { layers })
+    }
+
+    pub fn input_width(&self) -> usize {
+        self.layers[0].inputs
+    }
+
+    pub fn output_width(&self) -> usize {
+        self.layers[self.layers.len() - 1].outputs
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        self.layers.iter().map(|l| l.parameter_count()).sum()
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    }
+
+    // Outputs for each row
+    pub fn predict(&self, rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let output = self.forward(rows.concat(), rows.len()).output;
+        output.chunks(self.output_width()).map(|c| c.to_vec()).collect()
+    }
+
+    pub fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), String> {
+        if parameters.len() != self.parameter_count() {
+            return Err(format!("Model expects {} parameters, got {}", self.parameter_count(), parameters.len()));
+        }
+        let mut offset = 0;
+        for layer in &mut self.layers {
+            let count = layer.parameter_count();
+            layer.set_parameters(&parameters[offset..offset + count])?;
+            offset += count;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn forward(&self, input: Vec<f64>, batch: usize) -> ForwardCache {
+        let mut cache = ForwardCache { batch, inputs: Vec::new(), pre: Vec::new(), output: input };
+        for layer in &self.layers {
+            let (pre, out) = layer.forward(&cache.output, batch);
+            cache.inputs.push(std::mem::replace(&mut cache.output, out));
+            cache.pre.push(pre);
+        }
+        cache
+    }
+
+    // (gradient w.r.t. the stack's input, flattened parameter gradient)
+    pub(crate) fn backward(&self, cache: &ForwardCache, grad_output: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        let mut grad = grad_output.to_vec();
+        let mut grads_per_layer = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate().rev() {
+            let out = if i + 1 < self.layers.len() { &cache.inputs[i + 1] } else { &cache.output };
+            let (grad_input, grad_params) = layer.backward(&cache.inputs[i], &cache.pre[i], out, &grad, cache.batch);
+            grads_per_layer.push(grad_params);
+            grad = grad_input;
+        }
+        grads_per_layer.reverse();
+        (grad, grads_per_layer.concat())
+    }
+
+    pub(crate) fn step(&mut self, gradient: &[f64], learning_rate: f64) {
+        let mut parameters = self.parameters();
+        kernels::axpy(-learning_rate, gradient, &mut parameters);
+        // Lengths match by construction
+        let _ = self.set_parameters(&parameters);
+    }
+}
+
+// Client -> server: cut-layer activations for one batch, row-major
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ActivationBatch {
+    pub client_id: String,
+    pub round: u64,
+    pub batch_id: u64,
+    pub batch_size: usize,
+    pub width: usize,
+    pub activations: Vec<f64>,
+    pub labels: Vec<usize>,
+}
+
+// Server -> client: gradient of the batch loss w.r.t. the activations
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GradientBatch {
+    pub client_id: String,
+    pub round: u64,
+    pub batch_id: u64,
+    pub gradients: Vec<f64>,
+    pub loss: f64,
+    pub accuracy: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SplitRoundSummary {
+    pub round: u64,
+    pub batches: u32,
+    pub samples: u64,
+    pub participating_clients: Vec<String>,
+    pub loss: f64,
+    pub accuracy: f64,
+}
+
+pub struct SplitLearningServer {
+    round: u64,
+    layers: LayerStack,
+    learning_rate: f64,
+    // Sum over batches of batch_size * mean parameter gradient
+    accumulated: Vec<f64>,
+    samples: u64,
+    batches: u32,
+    loss_sum: f64,
+    correct: u64,
+    clients: BTreeSet<String>,
+    history: Vec<SplitRoundSummary>,
+}
+
+impl SplitLearningServer {
+    // `layers` are the server's layers; the last one's outputs are the class logits
+    pub fn new(layers: LayerStack, learning_rate: f64) -> Result<Self, String> {
+        if layers.output_width() < 2 {
+            return Err("The server's last layer needs at least 2 outputs (classes)".to_string());
+        }
+        if !(learning_rate.is_finite() && learning_rate > 0.0) {
+            return Err("Learning rate must be positive".to_string());
+        }
+        let accumulated = vec![0.0; layers.parameter_count()];
+        Ok(SplitLearningServer {
+            round: 0,
+            layers,
+            learning_rate,
+            accumulated,
+            samples: 0,
+            batches: 0,
+            loss_sum: 0.0,
+            correct: 0,
+            clients: BTreeSet::new(),
+            history: Vec::new(),
+        })
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn server_parameters(&self) -> Vec<f64> {
+        self.layers.parameters()
+    }
+
+    pub fn get_history(&self) -> &[SplitRoundSummary] {
+        &self.history
+    }
+
+    fn validate_batch(&self, batch: &ActivationBatch) -> Result<(), String> {
+        if batch.round != self.round {
+            return Err(format!("Activation batch is for round {}, current round is {}", batch.round, self.round));
+        }
+        if batch.batch_size == 0 {
+            return Err("Activation batch is empty".to_string());
+        }
+        if batch.width != self.layers.input_width() {
+            return Err(format!("Activation width {} does not match the cut layer width {}", batch.width, self.layers.input_width()));
+        }
+        if batch.activations.len() != batch.batch_size * batch.width || batch.labels.len() != batch.batch_size {
+            return Err("Activation batch size does not match its contents".to_string());
+        }
+        if batch.activations.iter().any(|a| !a.is_finite()) {
+            return Err("Activations must be finite".to_string());
+        }
+        if batch.labels.iter().any(|&l| l >= self.layers.output_width()) {
+            return Err("Activation batch has a label outside the model's classes".to_string());
+        }
+        Ok(())
+    }
+
+    // Runs the server layers on a batch and returns the cut-layer gradient. The
+    // server's own gradient is accumulated until the round is aggregated.
+    pub fn process_activations(&mut self, batch: &ActivationBatch) -> Result<GradientBatch, String> {
+        self.validate_batch(batch)?;
+        let n = batch.batch_size;
+        let classes = self.layers.output_width();
+        let cache = self.layers.forward(batch.activations.clone(), n);
+
+        // Softmax cross-entropy, averaged over the batch
+        let mut grad_logits = vec![0.0; n * classes];
+        let mut loss = 0.0;
+        let mut correct = 0u64;
+        for (b, &label) in batch.labels.iter().enumerate() {
+            let logits = &cache.output[b * classes..(b + 1) * classes];
+            let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let exp: Vec<f64> = logits.iter().map(|z| (z - max).exp()).collect();
+            let total: f64 = exp.iter().sum();
+            loss -= (exp[label] / total).ln();
+            let predicted = (0..classes).max_by(|&i, &j| logits[i].partial_cmp(&logits[j]).unwrap_or(std::cmp::Ordering::Equal));
+            if predicted == Some(label) {
+                correct += 1;
+            }
+            for c in 0..classes {
+                let target = if c == label { 1.0 } else { 0.0 };
+                grad_logits[b * classes + c] = (exp[c] / total - target) / n as f64;
+            }
+        }
+        let (grad_activations, grad_params) = self.layers.backward(&cache, &grad_logits);
+
+        kernels::axpy(n as f64, &grad_params, &mut self.accumulated);
+        self.samples += n as u64;
+        self.batches += 1;
+        self.loss_sum += loss;
+        self.correct += correct;
+        self.clients.insert(batch.client_id.clone());
+
+        Ok(GradientBatch {
+            client_id: batch.client_id.clone(),
+            round: batch.round,
+            batch_id: batch.batch_id,
+            gradients: grad_activations,
+            loss: loss / n as f64,
+            accuracy: correct as f64 / n as f64,
+        })
+    }
+
+    // Applies the sample-weighted mean of the round's server gradients
+    pub fn aggregate_round(&mut self) -> Result<SplitRoundSummary, String> {
+        if self.samples == 0 {
+            return Err(format!("No activation batches received for round {}", self.round));
+        }
+        let samples = self.samples as f64;
+        let gradient: Vec<f64> = self.accumulated.iter().map(|g| g / samples).collect();
+        self.layers.step(&gradient, self.learning_rate);
+
+        let summary = SplitRoundSummary {
+            round: self.round,
+            batches: self.batches,
+            samples: self.samples,
+            participating_clients: std::mem::take(&mut self.clients).into_iter().collect(),
+            loss: self.loss_sum / samples,
+            accuracy: self.correct as f64 / samples,
+        };
+        self.history.push(summary.clone());
+
+        self.accumulated.iter_mut().for_each(|g| *g = 0.0);
+        self.samples = 0;
+        self.batches = 0;
+        self.loss_sum = 0.0;
+        self.correct = 0;
+        self.round += 1;
+        Ok(summary)
+    }
+}
+
+pub struct SplitClient {
+    client_id: String,
+    layers: LayerStack,
+    next_batch: u64,
+    // Forward passes waiting for their gradient, by batch id
+    pending: HashMap<u64, ForwardCache>,
+    samples_trained: usize,
+}
+
+impl SplitClient {
+    pub fn new(client_id: &str, layers: LayerStack) -> Self {
+        SplitClient { client_id: client_id.to_string(), layers, next_batch: 0, pending: HashMap::new(), samples_trained: 0 }
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        self.layers.parameters()
+    }
+
+    // Adopts the aggregated client-side model
+    pub fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), String> {
+        self.layers.set_parameters(parameters)
+    }
+
+    pub fn forward(&mut self, round: u64, rows: &[Vec<f64>], labels: &[usize]) -> Result<ActivationBatch, String> {
+        if rows.is_empty() || rows.len() != labels.len() {
+            return Err("Batch needs one label per row".to_string());
+        }
+        if rows.iter().any(|r| r.len() != self.layers.input_width()) {
+            return Err(format!("Rows must have {} features", self.layers.input_width()));
+        }
+        let batch_id = self.next_batch;
+        self.next_batch += 1;
+        let cache = self.layers.forward(rows.concat(), rows.len());
+        let batch = ActivationBatch {
+            client_id: self.client_id.clone(),
+            round,
+            batch_id,
+            batch_size: rows.len(),
+            width: self.layers.output_width(),
+            activations: cache.output.clone(),
+            labels: labels.to_vec(),
+        };
+        self.pending.insert(batch_id, cache);
+        Ok(batch)
+    }
+
+    // Finishes backpropagation for a batch and takes a local SGD step
+    pub fn backward(&mut self, gradients: &GradientBatch, learning_rate: f64) -> Result<(), String> {
+        let cache = self.pending.remove(&gradients.batch_id).ok_or(format!("No pending batch {}", gradients.batch_id))?;
+        if gradients.gradients.len() != cache.output.len() {
+            return Err("Gradient batch does not match the activations sent".to_string());
+        }
+        let (_, grad_params) = self.layers.backward(&cache, &gradients.gradients);
+        self.layers.step(&grad_params, learning_rate);
+        self.samples_trained += cache.batch;
+        Ok(())
+    }
+
+    // Client-side layers for FedAvg; `gradients` carries the trained weights as in
+    // every other update
+    pub fn model_update(&mut self, round: u64, loss: f64, accuracy: f64) -> ModelUpdate {
+        let data_size = std::mem::take(&mut self.samples_trained);
+        ModelUpdate {
+            client_id: self.client_id.clone(),
+            round,
+            gradients: self.layers.parameters(),
+            weights: Vec::new(),
+            loss,
+            accuracy,
+            data_size,
+            computation_time: 0.0,
+            communication_cost: 0.0,
+            privacy_budget_used: 0.0,
+            compressed: false,
+            compression_ratio: None,
+            attestation: None,
+            personalized_accuracy: None,
+            sparse_gradients: None,
+            trained_ranges: None,
+            gradient_proof: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(inputs: usize, outputs: usize, activation: Activation, seed: f64) -> DenseLayer {
+        let count = inputs * outputs + outputs;
+        let parameters: Vec<f64> = (0..count).map(|i| 0.5 * (seed + i as f64 * 0.77).sin()).collect();
+        DenseLayer::from_parameters(inputs, outputs, activation, &parameters).unwrap()
+    }
+
+    #[test]
+    fn test_split_training_reduces_loss() {
+        let client_layers = LayerStack::new(vec![layer(2, 4, Activation::Tanh, 0.1)]).unwrap();
+        let server_layers = LayerStack::new(vec![layer(4, 2, Activation::Identity, 0.7)]).unwrap();
+        let mut server = SplitLearningServer::new(server_layers, 0.5).unwrap();
+        let mut clients = vec![SplitClient::new("a", client_layers.clone()), SplitClient::new("b", client_layers)];
+
+        // Class 1 when the first feature is larger
+        let rows = [vec![1.0, 0.0], vec![0.0, 1.0], vec![0.8, 0.2], vec![0.1, 0.9]];
+        let labels = [1, 0, 1, 0];
+        let engine = AggregationEngine::new();
+        for round in 0..60 {
+            let mut updates = Vec::new();
+            for (k, client) in clients.iter_mut().enumerate() {
+                let batch = client.forward(round, &rows[2 * k..2 * k + 2], &labels[2 * k..2 * k + 2]).unwrap();
+                let gradients = server.process_activations(&batch).unwrap();
+                client.backward(&gradients, 0.5).unwrap();
+                updates.push(client.model_update(round, gradients.loss, gradients.accuracy));
+            }
+            server.aggregate_round().unwrap();
+            let averaged = engine.weighted_average(&updates).unwrap();
+            for client in &mut clients {
+                client.set_parameters(&averaged).unwrap();
+            }
+        }
+
+        let history = server.get_history();
+        assert_eq!(history[0].participating_clients, vec!["a".to_string(), "b".to_string()]);
+        assert!(history[59].loss < history[0].loss / 2.0);
+        assert_eq!(history[59].accuracy, 1.0);
+
+        // Stale rounds and mismatched cut widths are rejected
+        let mut batch = clients[0].forward(0, &rows[..1], &labels[..1]).unwrap();
+        assert!(server.process_activations(&batch).is_err());
+        batch.round = server.round();
+        batch.width = 3;
+        assert!(server.process_activations(&batch).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+// Budget-aware hyperparameter search. Every candidate in the grid is first costed
+// against the base configuration's privacy and communication budgets, which fixes how
+// many rounds it may run; the affordable candidates then go through successive
+// halving, each rung training the survivors for η times more rounds through a
+// caller-supplied simulation and keeping the best 1/η.
+
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchSpace {
+    pub learning_rates: Vec<f64>,
+    pub local_epochs: Vec<u32>,
+    pub clipping_norms: Vec<f64>,
+    pub noise_multipliers: Vec<f64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrialParams {
+    pub learning_rate: f64,
+    pub local_epochs: u32,
+    // Per-client L2 clipping bound; the Gaussian noise std is clipping_norm * noise_multiplier
+    pub clipping_norm: f64,
+    pub noise_multiplier: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TunerSettings {
+    // Consortium size and model size, for the per-round communication cost
+    pub num_clients: u32,
+    pub model_dimension: usize,
+    // Rounds given to every candidate in the first rung
+    pub min_rounds: u32,
+    // Survivors per rung are 1 / eta of the candidates; rounds grow by eta
+    pub eta: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrialRecord {
+    pub params: TrialParams,
+    pub rung: u32,
+    pub rounds: u32,
+    pub loss: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TuningResult {
+    pub config: FederatedLearningConfig,
+    // Clipping happens client-side, so the clipping norm travels with the config
+    pub params: TrialParams,
+    pub loss: f64,
+    pub trials: Vec<TrialRecord>,
+    // Candidates the budgets could not afford for even `min_rounds`
+    pub infeasible: Vec<TrialParams>,
+}
+
+// Runs a candidate configuration for a number of rounds and reports its validation loss
+pub trait TrialEvaluator {
+    fn evaluate(&mut self, config: &FederatedLearningConfig, params: &TrialParams, rounds: u32) -> Result<f64, String>;
+}
+
+struct Candidate {
+    params: TrialParams,
+    config: FederatedLearningConfig,
+    max_rounds: u32,
+    loss: f64,
+}
+
+impl SearchSpace {
+    pub fn grid(&self) -> Vec<TrialParams> {
+        let mut grid = Vec::new();
+        for &learning_rate in &self.learning_rates {
+            for &local_epochs in &self.local_epochs {
+                for &clipping_norm in &self.clipping_norms {
+                    for &noise_multiplier in &self.noise_multipliers {
+                        grid.push(TrialParams { learning_rate, local_epochs, clipping_norm, noise_multiplier });
+                    }
+                }
+            }
+        }
+        grid
+    }
+}
+
+impl Default for TunerSettings {
+    fn default() -> Self {
+        TunerSettings {
+            num_clients: 10,
+            model_dimension: 1000,
+            min_rounds: 5,
+            eta: 3,
+        }
+    }
+}
+
+pub struct HyperparameterTuner {
+    base: FederatedLearningConfig,
+    settings: TunerSettings,
+}
+
+impl HyperparameterTuner {
+    pub fn new(base: FederatedLearningConfig, settings: TunerSettings) -> Result<Self, String> {
+        base.validate()?;
+        if settings.eta < 2 {
+            return Err("Successive halving needs eta of at least 2".to_string());
+        }
+        if settings.min_rounds == 0 || settings.num_clients == 0 {
+            return Err("min_rounds and num_clients must be at least 1".to_string());
+        }
+        Ok(HyperparameterTuner { base, settings })
+    }
+
+    // Per-round δ, leaving half of the total δ for advanced composition's slack
+    fn round_delta(&self) -> f64 {
+        self.base.privacy_budget.total_delta / (2.0 * self.base.max_rounds as f64)
+    }
+
+    // ε of one Gaussian-mechanism round with the given noise multiplier
+    pub fn round_epsilon(&self, noise_multiplier: f64) -> f64 {
+        (2.0 * (1.25 / self.round_delta()).ln()).sqrt() / noise_multiplier
+    }
+
+    // Total ε of `rounds` rounds under the base configuration's composition method
+    pub fn total_epsilon(&self, noise_multiplier: f64, rounds: u32) -> f64 {
+        let k = rounds as f64;
+        let budget = &self.base.privacy_budget;
+        match budget.composition_method {
+            CompositionMethod::Basic => k * self.round_epsilon(noise_multiplier),
+            CompositionMethod::Advanced => {
+                let eps = self.round_epsilon(noise_multiplier);
+                let slack = budget.total_delta / 2.0;
+                (2.0 * k * (1.0 / slack).ln()).sqrt() * eps + k * eps * (eps.exp() - 1.0)
+            }
+            // The Gaussian mechanism is ρ = 1 / (2σ²)-zCDP per round; ρ adds up over rounds
+            CompositionMethod::RenyiDP { .. } | CompositionMethod::ZeroConcentratedDP => {
+                zcdp_to_epsilon(k * gaussian_rho(noise_multiplier), budget.total_delta)
+            }
+        }
+    }
+
+    pub fn bytes_per_round(&self) -> u64 {
+        let participants = (self.base.client_fraction * self.settings.num_clients as f64).ceil();
+        // Model down and update up, at the targeted compression
+        let bytes = participants
+            * self.settings.model_dimension as f64
+            * std::mem::size_of::<f64>() as f64
+            * 2.0
+            * self.base.communication_budget.target_compression_ratio;
+        bytes.ceil() as u64
+    }
+
+    // Most rounds a candidate can run within both budgets, capped at the base max_rounds
+    pub fn affordable_rounds(&self, params: &TrialParams) -> u32 {
+        let communication = &self.base.communication_budget;
+        let bytes = self.bytes_per_round();
+        if bytes > communication.max_bytes_per_round {
+            return 0;
+        }
+        let by_bytes = (communication.max_total_bytes / bytes.max(1)).min(u32::MAX as u64) as u32;
+        let cap = self.base.max_rounds.min(by_bytes);
+
+        // Total ε grows with the round count, so binary search the largest that fits
+        let total_epsilon = self.base.privacy_budget.total_epsilon;
+        let (mut low, mut high) = (0, cap);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if self.total_epsilon(params.noise_multiplier, mid) <= total_epsilon {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        low
+    }
+
+    fn candidate_config(&self, params: &TrialParams, rounds: u32) -> Result<FederatedLearningConfig, String> {
+        let round_epsilon = self.round_epsilon(params.noise_multiplier);
+        let mut budget = self.base.privacy_budget.clone();
+        budget.per_round_epsilon = round_epsilon;
+        budget.per_client_epsilon = budget.per_client_epsilon.max(round_epsilon).min(budget.total_epsilon);
+
+        let mut config = self.base.clone();
+        config.learning_rate = params.learning_rate;
+        config.local_epochs = params.local_epochs;
+        config.max_rounds = rounds;
+        config.privacy_method = PrivacyMethod::DifferentialPrivacy { epsilon: round_epsilon, delta: self.round_delta() };
+        config.privacy_budget = budget;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn tune(&self, space: &SearchSpace, evaluator: &mut dyn TrialEvaluator) -> Result<TuningResult, String> {
+        let mut candidates = Vec::new();
+        let mut infeasible = Vec::new();
+        for params in space.grid() {
+            let is_valid = params.learning_rate > 0.0
+                && params.local_epochs > 0
+                && params.clipping_norm > 0.0
+                && params.noise_multiplier > 0.0;
+            let max_rounds = if is_valid { self.affordable_rounds(&params) } else { 0 };
+            match self.candidate_config(&params, max_rounds.max(1)) {
+                Ok(config) if max_rounds >= self.settings.min_rounds => {
+                    candidates.push(Candidate { params, config, max_rounds, loss: f64::INFINITY })
+                }
+                _ => infeasible.push(params),
+            }
+        }
+        if candidates.is_empty() {
+            return Err("No candidate fits the privacy and communication budgets".to_string());
+        }
+
+        let eta = self.settings.eta as usize;
+        let mut trials = Vec::new();
+        let mut rung = 0;
+        let mut rounds = self.settings.min_rounds;
+        loop {
+            for candidate in &mut candidates {
+                let budgeted = rounds.min(candidate.max_rounds);
+                let mut config = candidate.config.clone();
+                config.max_rounds = budgeted;
+                let loss = evaluator.evaluate(&config, &candidate.params, budgeted)?;
+                // A diverged run must never survive a rung
+                candidate.loss = if loss.is_finite() { loss } else { f64::INFINITY };
+                trials.push(TrialRecord { params: candidate.params.clone(), rung, rounds: budgeted, loss: candidate.loss });
+            }
+
+            candidates.sort_by(|a, b| a.loss.total_cmp(&b.loss));
+            let all_at_budget = candidates.iter().all(|c| rounds >= c.max_rounds);
+            if candidates.len() == 1 || all_at_budget {
+                break;
+            }
+            candidates.truncate(candidates.len().div_ceil(eta));
+            rung += 1;
+            rounds = rounds.saturating_mul(self.settings.eta);
+        }
+
+        let best = candidates.swap_remove(0);
+        if !best.loss.is_finite() {
+            return Err("Every candidate diverged".to_string());
+        }
+        Ok(TuningResult { config: best.config, params: best.params, loss: best.loss, trials, infeasible })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loss is lowest at lr = 0.1 and 5 epochs; more noise and tighter clipping cost accuracy
+    struct Quadratic;
+
+    impl TrialEvaluator for Quadratic {
+        fn evaluate(&mut self, _config: &FederatedLearningConfig, p: &TrialParams, rounds: u32) -> Result<f64, String> {
+            let noise = 1.0 / (p.clipping_norm * p.noise_multiplier).max(1e-9);
+            Ok((p.learning_rate.log10() + 1.0).powi(2)
+                + (p.local_epochs as f64 - 5.0).powi(2) * 0.01
+                + 0.01 * p.noise_multiplier
+                + 0.001 * noise
+                + 1.0 / rounds as f64)
+        }
+    }
+
+    #[test]
+    fn test_successive_halving_respects_budgets() {
+        let base = FederatedLearningConfig::builder().max_rounds(50).build().unwrap();
+        let tuner = HyperparameterTuner::new(base.clone(), TunerSettings::default()).unwrap();
+        let space = SearchSpace {
+            learning_rates: vec![0.001, 0.01, 0.1, 1.0],
+            local_epochs: vec![1, 5, 10],
+            clipping_norms: vec![1.0],
+            // σ = 0.5 cannot afford five rounds within ε = 10
+            noise_multipliers: vec![0.5, 8.0, 16.0],
+        };
+
+        let result = tuner.tune(&space, &mut Quadratic).unwrap();
+        assert_eq!(result.params.learning_rate, 0.1);
+        assert_eq!(result.params.local_epochs, 5);
+        assert!(result.infeasible.iter().all(|p| p.noise_multiplier == 0.5));
+        assert!(!result.infeasible.is_empty());
+
+        let config = &result.config;
+        let spent = tuner.total_epsilon(result.params.noise_multiplier, config.max_rounds);
+        assert!(spent <= base.privacy_budget.total_epsilon);
+        assert!(config.max_rounds as u64 * tuner.bytes_per_round() <= base.communication_budget.max_total_bytes);
+        // Later rungs ran fewer candidates for more rounds
+        assert!(result.trials.iter().filter(|t| t.rung == 0).count() > result.trials.iter().filter(|t| t.rung == 1).count());
+    }
+}
@@ -0,0 +1,323 @@
+// K-of-N approval for destructive admin operations. Resetting a privacy budget,
+// rolling a model back and pausing a canister are operational decisions that do not
+// go through consortium governance, but none of them should rest on a single admin
+// key. Each canister keeps an admin policy (the admin principals, how many of them
+// must approve, and how long a proposal stays open) and runs operations through a
+// proposal:
+//
+//   propose -> approve (until `threshold` admins have, the proposer included) -> execute
+//
+// Executing is calling the operation's own endpoint, which consumes the approved
+// proposal for exactly that operation, like a governance approval. A proposal not
+// executed within `proposal_ttl_ns` lapses. Until a policy is configured, operations
+// that existed before keep working without approval so deployments can bootstrap;
+// pausing and rollback always need a policy. The first policy is set by the deployer;
+// replacing it is itself an operation, and discards the proposals still open under
+// the old admin set.
+//
+// Settings that are not operations of their own but can still discard data or stall
+// a canister, such as retention and maintenance schedules, may be changed by a
+// controller or by any single admin.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::ShadowPolicy;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AdminPolicy {
+    pub admins: Vec<Principal>,
+    pub threshold: u32,
+    pub proposal_ttl_ns: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AdminOperation {
+    ResetPrivacyBudget { hospital_id: Principal },
+    RollbackModel { version: String },
+    EmergencyPause,
+    Resume,
+    ChangeAdminPolicy { policy: AdminPolicy },
+    ChangeShadowPolicy { policy: ShadowPolicy },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AdminProposal {
+    pub id: u64,
+    pub operation: AdminOperation,
+    pub proposer: Principal,
+    // Admins who approved, the proposer first
+    pub approvals: Vec<Principal>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub executed_at: Option<u64>,
+    pub executed_by: Option<Principal>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AdminApprovals {
+    policy: Option<AdminPolicy>,
+    proposals: BTreeMap<u64, AdminProposal>,
+    next_id: u64,
+    paused: bool,
+}
+
+impl AdminPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.admins.is_empty() {
+            return Err("Admin policy needs at least one admin".to_string());
+        }
+        if self.admins.contains(&Principal::anonymous()) {
+            return Err("The anonymous principal cannot be an admin".to_string());
+        }
+        let mut admins = self.admins.clone();
+        admins.sort();
+        admins.dedup();
+        if admins.len() != self.admins.len() {
+            return Err("Admin policy lists an admin twice".to_string());
+        }
+        if self.threshold == 0 || self.threshold as usize > self.admins.len() {
+            return Err(format!("Threshold must be between 1 and {}", self.admins.len()));
+        }
+        if self.proposal_ttl_ns == 0 {
+            return Err("Proposal lifetime must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl AdminProposal {
+    pub fn is_open(&self, now: u64) -> bool {
+        self.executed_at.is_none() && now <= self.expires_at
+    }
+}
+
+impl AdminApprovals {
+    pub fn new() -> Self {
+        AdminApprovals::default()
+    }
+
+    pub fn policy(&self) -> Option<&AdminPolicy> {
+        self.policy.as_ref()
+    }
+
+    pub fn is_enforced(&self) -> bool {
+        self.policy.is_some()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn get(&self, id: u64) -> Option<&AdminProposal> {
+        self.proposals.get(&id)
+    }
+
+    pub fn open_proposals(&self, now: u64) -> Vec<AdminProposal> {
+        self.proposals.values().filter(|p| p.is_open(now)).cloned().collect()
+    }
+
+    pub fn is_admin(&self, caller: Principal) -> bool {
+        self.policy.as_ref().is_some_and(|policy| policy.admins.contains(&caller))
+    }
+
+    pub fn require_controller_or_admin(&self, caller: Principal, caller_is_controller: bool) -> Result<(), String> {
+        if !caller_is_controller && !self.is_admin(caller) {
+            return Err("Only a controller or an admin can change this setting".to_string());
+        }
+        Ok(())
+    }
+
+    fn require_admin(&self, caller: Principal) -> Result<&AdminPolicy, String> {
+        let policy = self.policy.as_ref().ok_or("No admin policy configured")?;
+        if !policy.admins.contains(&caller) {
+            return Err("Caller is not an admin".to_string());
+        }
+        Ok(policy)
+    }
+
+    // Sets the first policy, which only a controller of the canister may do, or
+    // replaces the current one with an approved proposal
+    pub fn configure(&mut self, policy: AdminPolicy, caller: Principal, caller_is_controller: bool, now: u64) -> Result<(), String> {
+        policy.validate()?;
+        if self.policy.is_some() {
+            self.authorize(&AdminOperation::ChangeAdminPolicy { policy: policy.clone() }, caller, now)?;
+            self.proposals.retain(|_, p| p.executed_at.is_some());
+        } else if !caller_is_controller {
+            return Err("Only a controller can set the first admin policy".to_string());
+        }
+        self.policy = Some(policy);
+        Ok(())
+    }
+
+    pub fn propose(&mut self, operation: AdminOperation, caller: Principal, now: u64) -> Result<u64, String> {
+        let ttl = self.require_admin(caller)?.proposal_ttl_ns;
+        if let AdminOperation::ChangeAdminPolicy { policy } = &operation {
+            policy.validate()?;
+        }
+        if let Some(open) = self.proposals.values().find(|p| p.is_open(now) && p.operation == operation) {
+            return Err(format!("Proposal {} for this operation is already open", open.id));
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.proposals.insert(
+            id,
+            AdminProposal {
+                id,
+                operation,
+                proposer: caller,
+                approvals: vec![caller],
+                created_at: now,
+                expires_at: now.saturating_add(ttl),
+                executed_at: None,
+                executed_by: None,
+            },
+        );
+        Ok(id)
+    }
+
+    // Returns the number of approvals the proposal now has
+    pub fn approve(&mut self, id: u64, caller: Principal, now: u64) -> Result<u32, String> {
+        self.require_admin(caller)?;
+        let proposal = self.proposals.get_mut(&id).ok_or_else(|| format!("No admin proposal {}", id))?;
+        if !proposal.is_open(now) {
+            return Err(format!("Admin proposal {} is no longer open", id));
+        }
+        if proposal.approvals.contains(&caller) {
+            return Err(format!("Caller already approved proposal {}", id));
+        }
+        proposal.approvals.push(caller);
+        Ok(proposal.approvals.len() as u32)
+    }
+
+    // Only the proposer can withdraw an open proposal
+    pub fn cancel(&mut self, id: u64, caller: Principal, now: u64) -> Result<(), String> {
+        match self.proposals.get(&id) {
+            Some(proposal) if proposal.proposer != caller => Err("Only the proposer can cancel a proposal".to_string()),
+            Some(proposal) if !proposal.is_open(now) => Err(format!("Admin proposal {} is no longer open", id)),
+            Some(_) => {
+                self.proposals.remove(&id);
+                Ok(())
+            }
+            None => Err(format!("No admin proposal {}", id)),
+        }
+    }
+
+    // Consumes the approved proposal for `operation` and returns its id; None when no
+    // policy is configured yet
+    pub fn authorize(&mut self, operation: &AdminOperation, caller: Principal, now: u64) -> Result<Option<u64>, String> {
+        if self.policy.is_none() {
+            return Ok(None);
+        }
+        let threshold = self.require_admin(caller)?.threshold as usize;
+        let proposal = self
+            .proposals
+            .values_mut()
+            .find(|p| p.is_open(now) && &p.operation == operation)
+            .ok_or("Operation has no open admin proposal")?;
+        if proposal.approvals.len() < threshold {
+            return Err(format!(
+                "Admin proposal {} has {} of {} approvals",
+                proposal.id,
+                proposal.approvals.len(),
+                threshold
+            ));
+        }
+        proposal.executed_at = Some(now);
+        proposal.executed_by = Some(caller);
+        Ok(Some(proposal.id))
+    }
+
+    // Like `authorize`, but refuses to run without a policy
+    pub fn authorize_required(&mut self, operation: &AdminOperation, caller: Principal, now: u64) -> Result<u64, String> {
+        self.authorize(operation, caller, now)?.ok_or_else(|| "No admin policy configured".to_string())
+    }
+
+    pub fn pause(&mut self, caller: Principal, now: u64) -> Result<u64, String> {
+        if self.paused {
+            return Err("Already paused".to_string());
+        }
+        let id = self.authorize_required(&AdminOperation::EmergencyPause, caller, now)?;
+        self.paused = true;
+        Ok(id)
+    }
+
+    pub fn resume(&mut self, caller: Principal, now: u64) -> Result<u64, String> {
+        if !self.paused {
+            return Err("Not paused".to_string());
+        }
+        let id = self.authorize_required(&AdminOperation::Resume, caller, now)?;
+        self.paused = false;
+        Ok(id)
+    }
+
+    pub fn require_running(&self) -> Result<(), String> {
+        if self.paused {
+            return Err("Canister is paused by its admins".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_need_threshold_approvals_before_expiry() {
+        let admins: Vec<Principal> = (1..=3).map(|i| Principal::from_slice(&[i])).collect();
+        let outsider = Principal::from_slice(&[9]);
+        let reset = AdminOperation::ResetPrivacyBudget { hospital_id: outsider };
+
+        let mut approvals = AdminApprovals::new();
+        assert_eq!(approvals.authorize(&reset, outsider, 0), Ok(None));
+        assert!(approvals.pause(admins[0], 0).is_err());
+
+        let policy = AdminPolicy { admins: admins.clone(), threshold: 2, proposal_ttl_ns: 100 };
+        assert!(approvals.configure(policy.clone(), outsider, false, 0).is_err());
+        approvals.configure(policy.clone(), admins[0], true, 0).unwrap();
+        assert!(approvals.propose(reset.clone(), outsider, 0).is_err());
+
+        let id = approvals.propose(reset.clone(), admins[0], 0).unwrap();
+        assert!(approvals.propose(reset.clone(), admins[1], 0).is_err());
+        assert!(approvals.authorize(&reset, admins[0], 10).unwrap_err().contains("1 of 2"));
+        assert!(approvals.approve(id, admins[0], 10).is_err());
+        assert_eq!(approvals.approve(id, admins[1], 20), Ok(2));
+        assert_eq!(approvals.authorize(&reset, admins[2], 30), Ok(Some(id)));
+        assert!(approvals.authorize(&reset, admins[2], 40).is_err());
+
+        // Proposals lapse, and pausing follows the same path
+        let id = approvals.propose(AdminOperation::EmergencyPause, admins[0], 0).unwrap();
+        assert!(approvals.approve(id, admins[1], 101).is_err());
+        let id = approvals.propose(AdminOperation::EmergencyPause, admins[0], 200).unwrap();
+        approvals.approve(id, admins[2], 210).unwrap();
+        approvals.pause(admins[1], 220).unwrap();
+        assert!(approvals.require_running().is_err());
+
+        // Replacing the policy needs approval too, and drops what was open
+        let solo = AdminPolicy { threshold: 1, ..policy };
+        assert!(approvals.configure(solo.clone(), admins[0], false, 300).is_err());
+        let change = AdminOperation::ChangeAdminPolicy { policy: solo.clone() };
+        let id = approvals.propose(change, admins[0], 300).unwrap();
+        approvals.propose(AdminOperation::Resume, admins[0], 300).unwrap();
+        approvals.approve(id, admins[1], 310).unwrap();
+        approvals.configure(solo, admins[0], false, 320).unwrap();
+        assert!(approvals.open_proposals(320).is_empty());
+    }
+    #[test]
+    fn test_settings_need_a_controller_or_an_admin() {
+        let admin = Principal::from_slice(&[1]);
+        let outsider = Principal::from_slice(&[9]);
+        let mut approvals = AdminApprovals::new();
+        assert!(approvals.require_controller_or_admin(outsider, false).is_err());
+        assert!(approvals.require_controller_or_admin(admin, false).is_err());
+        assert!(approvals.require_controller_or_admin(outsider, true).is_ok());
+
+        let policy = AdminPolicy { admins: vec![admin], threshold: 1, proposal_ttl_ns: 100 };
+        approvals.configure(policy, outsider, true, 0).unwrap();
+        assert!(approvals.require_controller_or_admin(admin, false).is_ok());
+        assert!(approvals.require_controller_or_admin(outsider, false).is_err());
+        assert!(approvals.require_controller_or_admin(Principal::anonymous(), false).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+// Correlation ids for tracing one piece of work across canisters. The entry point (a
+// hospital client submitting to a round, the frontend requesting a diagnosis, an
+// operator launching a task) generates the id and passes it to every canister call
+// the work involves; each canister stamps it on the audit and event records it writes,
+// so the records of one federated round or one diagnosis can be joined afterwards.
+//
+// Calls that arrive without an id get one derived from the receiving canister, its
+// clock and a per-canister sequence number, so every record still has one; clients
+// should supply their own to link calls made to different canisters.
+
+pub const MAX_CORRELATION_ID_LEN: usize = 64;
+
+pub fn validate_correlation_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > MAX_CORRELATION_ID_LEN {
+        return Err(format!("Correlation id must be 1 to {} characters", MAX_CORRELATION_ID_LEN));
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')) {
+        return Err("Correlation id may only contain ASCII letters, digits, '-', '_', '.' and ':'".to_string());
+    }
+    Ok(())
+}
+
+// An id for work that starts inside a canister, such as a round opened by a timer
+pub fn new_correlation_id(origin: &str, now: u64, sequence: u64) -> String {
+    format!("{}:{:x}:{:x}", origin, now, sequence)
+}
+
+// The supplied id, validated, or a fresh one for a call that came without
+pub fn correlation_id_or_new(supplied: Option<String>, origin: &str, now: u64, sequence: u64) -> Result<String, String> {
+    match supplied {
+        Some(id) => validate_correlation_id(&id).map(|_| id),
+        None => Ok(new_correlation_id(origin, now, sequence)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correlation_ids_are_validated_or_generated() {
+        let supplied = "3f2b9c1e-7a4d-4e8f-9b0a-1c2d3e4f5a6b".to_string();
+        assert_eq!(correlation_id_or_new(Some(supplied.clone()), "aggregator", 1, 0).unwrap(), supplied);
+        assert!(correlation_id_or_new(Some("round 7; drop".to_string()), "aggregator", 1, 0).is_err());
+        assert!(correlation_id_or_new(Some("x".repeat(65)), "aggregator", 1, 0).is_err());
+
+        let generated = correlation_id_or_new(None, "privacy_engine", 255, 1).unwrap();
+        assert_eq!(generated, "privacy_engine:ff:1");
+        assert!(validate_correlation_id(&generated).is_ok());
+    }
+}
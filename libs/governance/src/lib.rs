@@ -0,0 +1,224 @@
+// Consortium governance hooks. Admitting an institution, launching a federated
+// learning task and changing privacy policy are decided by the consortium's
+// governance process (a governance canister running proposals and member votes),
+// not by whichever principal happens to call the aggregator or privacy engine. The
+// governance canister pushes each approved decision to the canisters it concerns;
+// they keep the decisions in an approval registry and consult it before acting,
+// consuming one approval per action.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub mod session;
+pub use session::*;
+pub mod onboarding;
+pub use onboarding::*;
+pub mod admin;
+pub use admin::*;
+pub mod correlation;
+pub use correlation::*;
+pub mod metrics;
+pub use metrics::*;
+pub mod shadow;
+pub use shadow::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum GovernanceAction {
+    AdmitInstitution { institution_id: String },
+    // Approval covers these exact round parameters
+    LaunchTask { task_id: String, target_participants: u32, privacy_epsilon: f64 },
+    ChangePrivacyPolicy { change: PrivacyPolicyChange },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PrivacyPolicyChange {
+    // Allocate or replace a hospital's total privacy budget
+    SetBudget { hospital_id: Principal, epsilon_total: f64, delta_total: f64 },
+    ResetBudget { hospital_id: Principal },
+    // Name the canister that opens privacy sessions (normally the aggregator)
+    SetSessionCoordinator { coordinator: Principal },
+}
+
+// A passed proposal as delivered by the governance canister
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApprovalDecision {
+    pub proposal_id: u64,
+    pub action: GovernanceAction,
+    // Members whose votes carried the proposal, kept for the audit trail
+    pub approvers: Vec<Principal>,
+    pub decided_at: u64,
+    // Time (ns) after which an unused approval lapses
+    pub expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApprovalReceipt {
+    pub proposal_id: u64,
+    pub action: GovernanceAction,
+    pub executed_by: Principal,
+    pub executed_at: u64,
+}
+
+pub trait ApprovalRegistry {
+    // False until a governance canister is configured; canisters keep their
+    // previous behaviour until then so existing deployments can bootstrap
+    fn is_enforced(&self) -> bool;
+
+    // Consumes the approval for `action` and returns its proposal id
+    fn consume_approval(&mut self, action: &GovernanceAction, caller: Principal, now: u64) -> Result<u64, String>;
+
+    // None when governance is not enforced yet
+    fn authorize(&mut self, action: &GovernanceAction, caller: Principal, now: u64) -> Result<Option<u64>, String> {
+        if !self.is_enforced() {
+            return Ok(None);
+        }
+        self.consume_approval(action, caller, now).map(Some)
+    }
+}
+
+impl GovernanceAction {
+    // Registry key; two actions share a key only if they are the same action
+    pub fn key(&self) -> String {
+        match self {
+            GovernanceAction::AdmitInstitution { institution_id } => format!("admit_institution:{}", institution_id),
+            GovernanceAction::LaunchTask { task_id, target_participants, privacy_epsilon } => {
+                format!("launch_task:{}:{}:{}", task_id, target_participants, privacy_epsilon)
+            }
+            GovernanceAction::ChangePrivacyPolicy { change } => match change {
+                PrivacyPolicyChange::SetBudget { hospital_id, epsilon_total, delta_total } => {
+                    format!("set_budget:{}:{}:{}", hospital_id, epsilon_total, delta_total)
+                }
+                PrivacyPolicyChange::ResetBudget { hospital_id } => format!("reset_budget:{}", hospital_id),
+                PrivacyPolicyChange::SetSessionCoordinator { coordinator } => format!("set_session_coordinator:{}", coordinator),
+            },
+        }
+    }
+}
+
+// Approval registry held by each governed canister
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LocalApprovalRegistry {
+    governance_canister: Option<Principal>,
+    pending: BTreeMap<String, ApprovalDecision>,
+    receipts: Vec<ApprovalReceipt>,
+}
+
+impl LocalApprovalRegistry {
+    pub fn new() -> Self {
+        LocalApprovalRegistry::default()
+    }
+
+    pub fn governance_canister(&self) -> Option<Principal> {
+        self.governance_canister
+    }
+
+    // The first configuration is reserved to the canister's controllers (the
+    // deployer); after that only the current governance canister can hand over to a
+    // new one
+    pub fn set_governance_canister(&mut self, caller: Principal, caller_is_controller: bool, canister: Principal) -> Result<(), String> {
+        match self.governance_canister {
+            Some(current) if caller != current => return Err("Only the governance canister can replace itself".to_string()),
+            None if !caller_is_controller => return Err("Only a controller can configure the governance canister".to_string()),
+            _ => {}
+        }
+        self.governance_canister = Some(canister);
+        Ok(())
+    }
+
+    fn require_governance(&self, caller: Principal) -> Result<(), String> {
+        match self.governance_canister {
+            Some(governance) if governance == caller => Ok(()),
+            Some(_) => Err("Only the governance canister can record decisions".to_string()),
+            None => Err("No governance canister configured".to_string()),
+        }
+    }
+
+    pub fn record_decision(&mut self, caller: Principal, decision: ApprovalDecision) -> Result<(), String> {
+        self.require_governance(caller)?;
+        if self.receipts.iter().any(|r| r.proposal_id == decision.proposal_id) {
+            return Err(format!("Proposal {} was already executed", decision.proposal_id));
+        }
+        self.pending.insert(decision.action.key(), decision);
+        Ok(())
+    }
+
+    pub fn revoke_decision(&mut self, caller: Principal, proposal_id: u64) -> Result<(), String> {
+        self.require_governance(caller)?;
+        let before = self.pending.len();
+        self.pending.retain(|_, decision| decision.proposal_id != proposal_id);
+        if self.pending.len() == before {
+            return Err(format!("No pending approval for proposal {}", proposal_id));
+        }
+        Ok(())
+    }
+
+    pub fn pending_approvals(&self) -> Vec<ApprovalDecision> {
+        self.pending.values().cloned().collect()
+    }
+
+    pub fn receipts(&self) -> &[ApprovalReceipt] {
+        &self.receipts
+    }
+}
+
+impl ApprovalRegistry for LocalApprovalRegistry {
+    fn is_enforced(&self) -> bool {
+        self.governance_canister.is_some()
+    }
+
+    fn consume_approval(&mut self, action: &GovernanceAction, caller: Principal, now: u64) -> Result<u64, String> {
+        let key = action.key();
+        let decision = match self.pending.get(&key) {
+            Some(decision) => decision,
+            None => return Err(format!("Action {} has not been approved by governance", key)),
+        };
+        if decision.expires_at.is_some_and(|expires_at| now > expires_at) {
+            self.pending.remove(&key);
+            return Err(format!("Governance approval for {} has expired", key));
+        }
+
+        let proposal_id = decision.proposal_id;
+        self.pending.remove(&key);
+        self.receipts.push(ApprovalReceipt { proposal_id, action: action.clone(), executed_by: caller, executed_at: now });
+        Ok(proposal_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approvals_are_single_use_and_governance_only() {
+        let deployer = Principal::from_slice(&[1]);
+        let governance = Principal::from_slice(&[2]);
+        let admin = Principal::from_slice(&[3]);
+        let admit = GovernanceAction::AdmitInstitution { institution_id: "hospital_a".to_string() };
+
+        let mut registry = LocalApprovalRegistry::new();
+        assert_eq!(registry.authorize(&admit, admin, 0), Ok(None));
+
+        // Whoever calls first does not win: the first configuration needs a controller
+        assert!(registry.set_governance_canister(admin, false, admin).is_err());
+        registry.set_governance_canister(deployer, true, governance).unwrap();
+        assert!(registry.set_governance_canister(admin, false, admin).is_err());
+        assert!(registry.set_governance_canister(deployer, true, admin).is_err());
+        assert!(registry.authorize(&admit, admin, 0).is_err());
+
+        let decision =
+            ApprovalDecision { proposal_id: 7, action: admit.clone(), approvers: vec![admin], decided_at: 0, expires_at: Some(100) };
+        assert!(registry.record_decision(admin, decision.clone()).is_err());
+        registry.record_decision(governance, decision.clone()).unwrap();
+
+        assert_eq!(registry.authorize(&admit, admin, 50), Ok(Some(7)));
+        assert!(registry.authorize(&admit, admin, 60).is_err());
+        assert!(registry.record_decision(governance, decision.clone()).is_err());
+
+        // Approvals lapse after their expiry
+        let decision = ApprovalDecision { proposal_id: 8, ..decision };
+        registry.record_decision(governance, decision).unwrap();
+        assert!(registry.authorize(&admit, admin, 101).is_err());
+        assert_eq!(registry.receipts().len(), 1);
+    }
+}
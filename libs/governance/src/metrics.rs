@@ -0,0 +1,79 @@
+// Call metrics for capacity testing. A canister records the instructions each call of
+// an instrumented method used (the call-context performance counter, so an async
+// method's awaits are included) and reports them per method together with its heap
+// and stable memory size. The load generator in tools/load_generator reads these
+// before and after a run to attribute instruction cost and memory growth to the
+// load it applied; latency is measured on the client side.
+//
+// The counts live on the heap and restart from zero on upgrade.
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const WASM_PAGE_BYTES: u64 = 64 * 1024;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CallStats {
+    pub calls: u64,
+    pub total_instructions: u64,
+    pub max_instructions: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CanisterMetrics {
+    pub methods: Vec<(String, CallStats)>,
+    pub heap_bytes: u64,
+    pub stable_bytes: u64,
+}
+
+#[derive(Default)]
+pub struct CallMetrics {
+    methods: BTreeMap<String, CallStats>,
+}
+
+impl CallStats {
+    pub fn mean_instructions(&self) -> u64 {
+        self.total_instructions.checked_div(self.calls).unwrap_or(0)
+    }
+}
+
+impl CallMetrics {
+    pub fn new() -> Self {
+        CallMetrics::default()
+    }
+
+    pub fn record(&mut self, method: &str, instructions: u64) {
+        let stats = self.methods.entry(method.to_string()).or_default();
+        stats.calls += 1;
+        stats.total_instructions = stats.total_instructions.saturating_add(instructions);
+        stats.max_instructions = stats.max_instructions.max(instructions);
+    }
+
+    pub fn snapshot(&self, heap_bytes: u64, stable_bytes: u64) -> CanisterMetrics {
+        CanisterMetrics {
+            methods: self.methods.iter().map(|(method, stats)| (method.clone(), stats.clone())).collect(),
+            heap_bytes,
+            stable_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calls_are_counted_per_method() {
+        let mut metrics = CallMetrics::new();
+        metrics.record("submit_gradient_update", 1_000);
+        metrics.record("submit_gradient_update", 3_000);
+        metrics.record("diagnose", 500);
+
+        let snapshot = metrics.snapshot(2 * WASM_PAGE_BYTES, 0);
+        assert_eq!(snapshot.methods[0].0, "diagnose");
+        let (_, submit) = &snapshot.methods[1];
+        assert_eq!((submit.calls, submit.max_instructions, submit.mean_instructions()), (2, 3_000, 2_000));
+        assert_eq!(CallStats::default().mean_instructions(), 0);
+    }
+}
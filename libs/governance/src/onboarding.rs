@@ -0,0 +1,304 @@
+// Hospital onboarding. Joining the consortium used to be a single registration call;
+// it is now a per-institution state machine that every canister can consult:
+//
+//   Applied -> DocumentsSubmitted -> Approved -> KeysRegistered -> TrialPassed -> Active
+//
+// The applicant applies, submits SHA-256 hashes of its credentials (accreditation,
+// data-processing agreement, ethics approval; the documents themselves stay off
+// chain), the consortium approves the admission through governance, the institution
+// registers the public key its updates are signed with, takes part in a trial round
+// whose update is checked but never aggregated, and is then activated. Every step
+// past the application is taken by the applicant's principal, except the approval.
+// An applicant can withdraw at any point before activation and apply again later.
+// An active institution that the aggregator expires for inactivity goes back to
+// KeysRegistered and has to pass a new trial round before it is reactivated.
+//
+// Each transition is kept with who made it and when, so the path an institution took
+// into the consortium can be audited.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum OnboardingStage {
+    Applied,
+    DocumentsSubmitted,
+    Approved,
+    KeysRegistered,
+    TrialPassed,
+    Active,
+    Withdrawn,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CredentialDocument {
+    // e.g. "accreditation", "data_processing_agreement", "ethics_approval"
+    pub kind: String,
+    // Lowercase hex SHA-256 of the document
+    pub sha256: String,
+    pub submitted_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrialResult {
+    pub submitted_at: u64,
+    pub dimension: u64,
+    pub sample_count: u32,
+    pub gradient_norm: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StageTransition {
+    pub from: Option<OnboardingStage>,
+    pub to: OnboardingStage,
+    pub actor: Principal,
+    pub at: u64,
+    pub note: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OnboardingRecord {
+    pub institution_id: String,
+    pub name: String,
+    pub applicant: Principal,
+    pub stage: OnboardingStage,
+    pub documents: Vec<CredentialDocument>,
+    // Governance proposal that approved the admission and the members who carried it;
+    // None when governance was not enforced yet
+    pub approval_proposal: Option<u64>,
+    pub approvers: Vec<Principal>,
+    // SEC1-encoded public key the institution signs its updates with
+    pub public_key: Option<Vec<u8>>,
+    pub trial: Option<TrialResult>,
+    pub history: Vec<StageTransition>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct OnboardingRegistry {
+    records: BTreeMap<String, OnboardingRecord>,
+}
+
+impl OnboardingRecord {
+    fn transition(&mut self, to: OnboardingStage, actor: Principal, at: u64, note: Option<String>) {
+        self.history.push(StageTransition { from: Some(self.stage.clone()), to: to.clone(), actor, at, note });
+        self.stage = to;
+    }
+
+    fn require_applicant(&self, caller: Principal) -> Result<(), String> {
+        if caller != self.applicant {
+            return Err(format!("Only the applicant of {} can take this step", self.institution_id));
+        }
+        Ok(())
+    }
+
+    fn require_stage(&self, allowed: &[OnboardingStage]) -> Result<(), String> {
+        if !allowed.contains(&self.stage) {
+            return Err(format!("{} is {:?}; this step needs {:?}", self.institution_id, self.stage, allowed));
+        }
+        Ok(())
+    }
+}
+
+impl OnboardingRegistry {
+    pub fn new() -> Self {
+        OnboardingRegistry::default()
+    }
+
+    pub fn get(&self, institution_id: &str) -> Option<&OnboardingRecord> {
+        self.records.get(institution_id)
+    }
+
+    // All records, or those at `stage`, ordered by institution id
+    pub fn list(&self, stage: Option<&OnboardingStage>) -> Vec<OnboardingRecord> {
+        self.records.values().filter(|r| stage.is_none_or(|s| &r.stage == s)).cloned().collect()
+    }
+
+    pub fn is_active(&self, institution_id: &str) -> bool {
+        self.get(institution_id).is_some_and(|r| r.stage == OnboardingStage::Active)
+    }
+
+    fn record_mut(&mut self, institution_id: &str) -> Result<&mut OnboardingRecord, String> {
+        self.records.get_mut(institution_id).ok_or_else(|| format!("{} has not applied", institution_id))
+    }
+
+    pub fn apply(&mut self, institution_id: &str, name: &str, applicant: Principal, now: u64) -> Result<&OnboardingRecord, String> {
+        if institution_id.is_empty() {
+            return Err("Institution ID cannot be empty".to_string());
+        }
+        if applicant == Principal::anonymous() {
+            return Err("Anonymous caller not allowed".to_string());
+        }
+        let mut history = Vec::new();
+        if let Some(existing) = self.records.get(institution_id) {
+            if existing.stage != OnboardingStage::Withdrawn {
+                return Err(format!("{} is already {:?}", institution_id, existing.stage));
+            }
+            // A renewed application keeps the earlier attempt's trail
+            history = existing.history.clone();
+        }
+        history.push(StageTransition { from: None, to: OnboardingStage::Applied, actor: applicant, at: now, note: None });
+        let record = OnboardingRecord {
+            institution_id: institution_id.to_string(),
+            name: name.to_string(),
+            applicant,
+            stage: OnboardingStage::Applied,
+            documents: Vec::new(),
+            approval_proposal: None,
+            approvers: Vec::new(),
+            public_key: None,
+            trial: None,
+            history,
+        };
+        self.records.insert(institution_id.to_string(), record);
+        Ok(&self.records[institution_id])
+    }
+
+    // Documents can be added until the admission is approved; a later hash of the same
+    // kind replaces the earlier one
+    pub fn submit_documents(
+        &mut self,
+        institution_id: &str,
+        caller: Principal,
+        documents: Vec<(String, String)>,
+        now: u64,
+    ) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_applicant(caller)?;
+        record.require_stage(&[OnboardingStage::Applied, OnboardingStage::DocumentsSubmitted])?;
+        if documents.is_empty() {
+            return Err("No documents submitted".to_string());
+        }
+        for (kind, sha256) in &documents {
+            if kind.is_empty() {
+                return Err("Document kind cannot be empty".to_string());
+            }
+            if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+                return Err(format!("Hash of {} must be 64 lowercase hex characters", kind));
+            }
+        }
+        for (kind, sha256) in documents {
+            record.documents.retain(|d| d.kind != kind);
+            record.documents.push(CredentialDocument { kind, sha256, submitted_at: now });
+        }
+        if record.stage == OnboardingStage::Applied {
+            record.transition(OnboardingStage::DocumentsSubmitted, caller, now, None);
+        }
+        Ok(())
+    }
+
+    // Called once governance has authorized the admission
+    pub fn approve(
+        &mut self,
+        institution_id: &str,
+        caller: Principal,
+        proposal_id: Option<u64>,
+        approvers: Vec<Principal>,
+        now: u64,
+    ) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_stage(&[OnboardingStage::DocumentsSubmitted])?;
+        record.approval_proposal = proposal_id;
+        record.approvers = approvers;
+        record.transition(OnboardingStage::Approved, caller, now, proposal_id.map(|id| format!("proposal {}", id)));
+        Ok(())
+    }
+
+    // The key can be rotated until the trial round has been passed
+    pub fn register_key(&mut self, institution_id: &str, caller: Principal, public_key: Vec<u8>, now: u64) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_applicant(caller)?;
+        record.require_stage(&[OnboardingStage::Approved, OnboardingStage::KeysRegistered])?;
+        if public_key.is_empty() {
+            return Err("Public key cannot be empty".to_string());
+        }
+        record.public_key = Some(public_key);
+        if record.stage == OnboardingStage::Approved {
+            record.transition(OnboardingStage::KeysRegistered, caller, now, None);
+        }
+        Ok(())
+    }
+
+    pub fn record_trial(&mut self, institution_id: &str, caller: Principal, trial: TrialResult, now: u64) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_applicant(caller)?;
+        record.require_stage(&[OnboardingStage::KeysRegistered])?;
+        record.trial = Some(trial);
+        record.transition(OnboardingStage::TrialPassed, caller, now, None);
+        Ok(())
+    }
+
+    pub fn activate(&mut self, institution_id: &str, caller: Principal, now: u64) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_applicant(caller)?;
+        record.require_stage(&[OnboardingStage::TrialPassed])?;
+        record.transition(OnboardingStage::Active, caller, now, None);
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, institution_id: &str, caller: Principal, now: u64) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_applicant(caller)?;
+        if matches!(record.stage, OnboardingStage::Active | OnboardingStage::Withdrawn) {
+            return Err(format!("{} is {:?} and cannot withdraw", institution_id, record.stage));
+        }
+        record.transition(OnboardingStage::Withdrawn, caller, now, None);
+        Ok(())
+    }
+
+    // An expired institution keeps its approval and key but must pass a new trial
+    pub fn return_to_trial(&mut self, institution_id: &str, actor: Principal, now: u64, reason: &str) -> Result<(), String> {
+        let record = self.record_mut(institution_id)?;
+        record.require_stage(&[OnboardingStage::Active])?;
+        record.trial = None;
+        record.transition(OnboardingStage::KeysRegistered, actor, now, Some(reason.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_institutions_move_through_every_stage_in_order() {
+        let applicant = Principal::from_slice(&[1]);
+        let other = Principal::from_slice(&[2]);
+        let aggregator = Principal::from_slice(&[3]);
+        let hash = "ab".repeat(32);
+        let trial = TrialResult { submitted_at: 5, dimension: 4, sample_count: 100, gradient_norm: 1.0 };
+
+        let mut registry = OnboardingRegistry::new();
+        registry.apply("hospital_a", "Hospital A", applicant, 0).unwrap();
+        assert!(registry.apply("hospital_a", "Hospital A", other, 0).is_err());
+
+        // Steps are the applicant's and only run in order
+        assert!(registry.register_key("hospital_a", applicant, vec![2; 33], 1).is_err());
+        assert!(registry.submit_documents("hospital_a", other, vec![("accreditation".to_string(), hash.clone())], 1).is_err());
+        assert!(registry.submit_documents("hospital_a", applicant, vec![("accreditation".to_string(), "not-a-hash".to_string())], 1).is_err());
+        registry.submit_documents("hospital_a", applicant, vec![("accreditation".to_string(), hash.clone())], 1).unwrap();
+        registry.approve("hospital_a", aggregator, Some(9), vec![other], 2).unwrap();
+        registry.register_key("hospital_a", applicant, vec![2; 33], 3).unwrap();
+        assert!(registry.activate("hospital_a", applicant, 4).is_err());
+        registry.record_trial("hospital_a", applicant, trial.clone(), 5).unwrap();
+        registry.activate("hospital_a", applicant, 6).unwrap();
+        assert!(registry.is_active("hospital_a"));
+        assert!(registry.withdraw("hospital_a", applicant, 7).is_err());
+
+        let record = registry.get("hospital_a").unwrap();
+        assert_eq!(record.approval_proposal, Some(9));
+        assert_eq!(record.history.len(), 6);
+        assert_eq!(registry.list(Some(&OnboardingStage::Active)).len(), 1);
+
+        // Expiry sends the institution back to a trial round
+        registry.return_to_trial("hospital_a", aggregator, 8, "idle for 10 rounds").unwrap();
+        assert_eq!(registry.get("hospital_a").unwrap().stage, OnboardingStage::KeysRegistered);
+        registry.record_trial("hospital_a", applicant, trial, 9).unwrap();
+        registry.activate("hospital_a", applicant, 10).unwrap();
+
+        registry.apply("hospital_b", "Hospital B", other, 0).unwrap();
+        registry.withdraw("hospital_b", other, 1).unwrap();
+        registry.apply("hospital_b", "Hospital B", other, 2).unwrap();
+        assert_eq!(registry.get("hospital_b").unwrap().history.len(), 3);
+    }
+}
@@ -0,0 +1,207 @@
+// Privacy session tokens. When a coordination session starts, the privacy engine
+// issues each canister taking part (aggregator, inference) a short-lived token bound
+// to the session and to that canister's principal. Budget-consuming calls must carry
+// the token, so a whitelisted canister can only spend the budgets of hospitals in a
+// session it was admitted to, and only until the token expires.
+//
+// Tokens are minted when a session opens, so opening one is restricted too: only the
+// session coordinator governance named (the aggregator) or an admin under the admin
+// policy may. Before governance is configured the coordinator is named by a
+// controller of the privacy engine; until it is named, only admins can open sessions.
+//
+// The privacy engine both issues and checks tokens, so they are authenticated with
+// HMAC-SHA256 under a secret only it holds rather than with a public-key signature.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::{AdminApprovals, ApprovalRegistry, GovernanceAction, PrivacyPolicyChange};
+
+// Upper bound on a token's lifetime: one hour, in nanoseconds
+pub const MAX_SESSION_TOKEN_TTL_NS: u64 = 3_600_000_000_000;
+
+const HMAC_BLOCK_SIZE: usize = 64;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionToken {
+    pub session_id: String,
+    // Only this principal may present the token
+    pub holder: Principal,
+    pub issued_at: u64,
+    pub expires_at: u64,
+    // HMAC-SHA256 over the fields above
+    pub mac: Vec<u8>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionCoordinator {
+    coordinator: Option<Principal>,
+}
+
+fn hmac_sha256(secret: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut key = [0u8; HMAC_BLOCK_SIZE];
+    if secret.len() > HMAC_BLOCK_SIZE {
+        key[..32].copy_from_slice(&Sha256::digest(secret));
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+    let mut inner = Sha256::new();
+    inner.update(key.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(key.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+// Compares every byte so the time taken does not reveal how much of a MAC matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl SessionToken {
+    pub fn issue(secret: &[u8], session_id: &str, holder: Principal, now: u64, ttl_ns: u64) -> Result<Self, String> {
+        if secret.len() < 32 {
+            return Err("Session secret must be at least 32 bytes".to_string());
+        }
+        if ttl_ns == 0 || ttl_ns > MAX_SESSION_TOKEN_TTL_NS {
+            return Err(format!("Session token lifetime must be between 1 and {} ns", MAX_SESSION_TOKEN_TTL_NS));
+        }
+        let mut token = SessionToken {
+            session_id: session_id.to_string(),
+            holder,
+            issued_at: now,
+            expires_at: now.saturating_add(ttl_ns),
+            mac: Vec::new(),
+        };
+        token.mac = hmac_sha256(secret, &token.signing_bytes());
+        Ok(token)
+    }
+
+    // Length-prefixed fields, so no two tokens share the same bytes
+    fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"privacy-session-token".to_vec();
+        for field in [self.session_id.as_bytes(), self.holder.as_slice()] {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field);
+        }
+        bytes.extend_from_slice(&self.issued_at.to_be_bytes());
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes
+    }
+
+    pub fn verify(&self, secret: &[u8], caller: Principal, now: u64) -> Result<(), String> {
+        if !constant_time_eq(&hmac_sha256(secret, &self.signing_bytes()), &self.mac) {
+            return Err("Invalid session token".to_string());
+        }
+        if self.holder != caller {
+            return Err(format!("Session token for {} was not issued to the caller", self.session_id));
+        }
+        if now >= self.expires_at {
+            return Err(format!("Session token for {} has expired", self.session_id));
+        }
+        Ok(())
+    }
+}
+
+impl SessionCoordinator {
+    pub fn new() -> Self {
+        SessionCoordinator::default()
+    }
+
+    pub fn coordinator(&self) -> Option<Principal> {
+        self.coordinator
+    }
+
+    // Needs a governance approval once governance is enforced, and a controller
+    // before then
+    pub fn set(
+        &mut self,
+        coordinator: Principal,
+        caller: Principal,
+        caller_is_controller: bool,
+        governance: &mut impl ApprovalRegistry,
+        now: u64,
+    ) -> Result<Option<u64>, String> {
+        if coordinator == Principal::anonymous() {
+            return Err("The anonymous principal cannot coordinate sessions".to_string());
+        }
+        if !governance.is_enforced() && !caller_is_controller {
+            return Err("Only a controller can name the session coordinator before governance is configured".to_string());
+        }
+        let change = PrivacyPolicyChange::SetSessionCoordinator { coordinator };
+        let proposal_id = governance.authorize(&GovernanceAction::ChangePrivacyPolicy { change }, caller, now)?;
+        self.coordinator = Some(coordinator);
+        Ok(proposal_id)
+    }
+
+    pub fn authorize_opening(&self, caller: Principal, admin: &AdminApprovals) -> Result<(), String> {
+        if self.coordinator == Some(caller) || admin.is_admin(caller) {
+            return Ok(());
+        }
+        Err("Only the session coordinator or an admin can open a privacy session".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AdminPolicy, LocalApprovalRegistry};
+
+    #[test]
+    fn test_tokens_are_bound_to_holder_and_lifetime() {
+        let secret = [7u8; 32];
+        let aggregator = Principal::from_slice(&[1]);
+        let other = Principal::from_slice(&[2]);
+        let token = SessionToken::issue(&secret, "session-1", aggregator, 1_000, 500).unwrap();
+
+        assert!(token.verify(&secret, aggregator, 1_200).is_ok());
+        assert!(token.verify(&secret, other, 1_200).is_err());
+        assert!(token.verify(&secret, aggregator, 1_500).is_err());
+        assert!(token.verify(&[8u8; 32], aggregator, 1_200).is_err());
+
+        // Stretching the lifetime or moving the token to another session breaks the MAC
+        let extended = SessionToken { expires_at: u64::MAX, ..token.clone() };
+        assert!(extended.verify(&secret, aggregator, 1_200).is_err());
+        let moved = SessionToken { session_id: "session-2".to_string(), ..token };
+        assert!(moved.verify(&secret, aggregator, 1_200).is_err());
+
+        assert!(SessionToken::issue(&secret, "session-1", aggregator, 0, MAX_SESSION_TOKEN_TTL_NS + 1).is_err());
+        assert!(SessionToken::issue(&[0u8; 16], "session-1", aggregator, 0, 500).is_err());
+    }
+
+    #[test]
+    fn test_only_the_coordinator_or_an_admin_opens_sessions() {
+        let aggregator = Principal::from_slice(&[1]);
+        let outsider = Principal::from_slice(&[2]);
+        let admin = Principal::from_slice(&[3]);
+        let mut governance = LocalApprovalRegistry::new();
+        let mut admins = AdminApprovals::new();
+        let mut sessions = SessionCoordinator::new();
+
+        // Nobody can open a session on a fresh deployment, and only a controller can
+        // name the coordinator
+        assert!(sessions.authorize_opening(outsider, &admins).is_err());
+        assert!(sessions.set(outsider, outsider, false, &mut governance, 0).is_err());
+        assert_eq!(sessions.set(aggregator, admin, true, &mut governance, 0), Ok(None));
+        assert!(sessions.authorize_opening(aggregator, &admins).is_ok());
+        assert!(sessions.authorize_opening(outsider, &admins).is_err());
+
+        let policy = AdminPolicy { admins: vec![admin], threshold: 1, proposal_ttl_ns: 100 };
+        admins.configure(policy, admin, true, 0).unwrap();
+        assert!(sessions.authorize_opening(admin, &admins).is_ok());
+
+        // An outsider cannot spend with the coordinator's token, nor with one minted
+        // under a secret of its own
+        let secret = [7u8; 32];
+        let token = SessionToken::issue(&secret, "session-1", aggregator, 0, 500).unwrap();
+        assert!(token.verify(&secret, outsider, 10).is_err());
+        let forged = SessionToken::issue(&[9u8; 32], "session-1", outsider, 0, 500).unwrap();
+        assert!(forged.verify(&secret, outsider, 10).is_err());
+
+        // Once governance is enforced even a controller needs an approval
+        governance.set_governance_canister(admin, true, admin).unwrap();
+        assert!(sessions.set(outsider, admin, true, &mut governance, 0).is_err());
+        assert_eq!(sessions.coordinator(), Some(aggregator));
+    }
+}
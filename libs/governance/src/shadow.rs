@@ -0,0 +1,224 @@
+// Shadow deployment of a newly aggregated model. Before a model replaces the one
+// serving diagnoses, it runs in shadow on the inference canister: every query is
+// scored by both, the caller only ever sees the production result, and the canister
+// compares the two. A disagreement (a different top diagnosis) is logged with the
+// query's correlation id and both answers, never the patient's data, and the
+// running confidence delta between the models is kept.
+//
+// The shadow model is at parity while, over everything it has scored so far, it has
+// seen at least `min_queries` queries, disagreed on at most `max_disagreement_rate`
+// of them and moved the mean confidence by at most `max_confidence_delta`. Promotion
+// needs parity to have held without a break for `parity_period_ns`; a comparison that
+// breaks parity restarts the period. With `require_shadow` set, a model can only
+// replace a loaded one this way.
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowPolicy {
+    // Loaded models may only be replaced by promoting a shadow model
+    pub require_shadow: bool,
+    pub parity_period_ns: u64,
+    pub min_queries: u64,
+    pub max_disagreement_rate: f64,
+    pub max_confidence_delta: f64,
+    // Most recent disagreements kept for review
+    pub max_logged_disagreements: usize,
+}
+
+// What one model answered for a query
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowScore {
+    pub model_version: String,
+    pub diagnosis: String,
+    pub confidence: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowDisagreement {
+    pub correlation_id: String,
+    pub at: u64,
+    pub production: ShadowScore,
+    pub shadow: ShadowScore,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowStatus {
+    pub shadow_version: String,
+    pub started_at: u64,
+    pub queries: u64,
+    pub disagreements: u64,
+    pub disagreement_rate: f64,
+    // Shadow minus production, averaged over the queries
+    pub mean_confidence_delta: f64,
+    pub parity_since: Option<u64>,
+    pub promotable: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ShadowDeployment {
+    policy: ShadowPolicy,
+    shadow_version: String,
+    started_at: u64,
+    queries: u64,
+    disagreements: u64,
+    confidence_delta_sum: f64,
+    parity_since: Option<u64>,
+    log: Vec<ShadowDisagreement>,
+}
+
+impl Default for ShadowPolicy {
+    fn default() -> Self {
+        ShadowPolicy {
+            require_shadow: false,
+            parity_period_ns: 7 * 24 * 60 * 60 * 1_000_000_000,
+            min_queries: 200,
+            max_disagreement_rate: 0.05,
+            max_confidence_delta: 0.05,
+            max_logged_disagreements: 100,
+        }
+    }
+}
+
+impl ShadowPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.max_disagreement_rate) {
+            return Err("max_disagreement_rate must be within [0, 1]".to_string());
+        }
+        if !(self.max_confidence_delta.is_finite() && self.max_confidence_delta >= 0.0) {
+            return Err("max_confidence_delta must be non-negative".to_string());
+        }
+        if self.min_queries == 0 {
+            return Err("Parity needs at least one query".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ShadowDeployment {
+    pub fn new(policy: ShadowPolicy, shadow_version: String, now: u64) -> Result<Self, String> {
+        policy.validate()?;
+        Ok(ShadowDeployment {
+            policy,
+            shadow_version,
+            started_at: now,
+            queries: 0,
+            disagreements: 0,
+            confidence_delta_sum: 0.0,
+            parity_since: None,
+            log: Vec::new(),
+        })
+    }
+
+    // Applies to the comparisons from now on; the parity period restarts
+    pub fn set_policy(&mut self, policy: ShadowPolicy) -> Result<(), String> {
+        policy.validate()?;
+        self.policy = policy;
+        self.parity_since = None;
+        Ok(())
+    }
+
+    pub fn record(&mut self, correlation_id: &str, production: ShadowScore, shadow: ShadowScore, now: u64) {
+        self.queries += 1;
+        self.confidence_delta_sum += shadow.confidence - production.confidence;
+        if production.diagnosis != shadow.diagnosis {
+            self.disagreements += 1;
+            self.log.push(ShadowDisagreement { correlation_id: correlation_id.to_string(), at: now, production, shadow });
+            let excess = self.log.len().saturating_sub(self.policy.max_logged_disagreements);
+            self.log.drain(..excess);
+        }
+
+        if self.at_parity() {
+            self.parity_since.get_or_insert(now);
+        } else {
+            self.parity_since = None;
+        }
+    }
+
+    fn disagreement_rate(&self) -> f64 {
+        if self.queries == 0 {
+            return 0.0;
+        }
+        self.disagreements as f64 / self.queries as f64
+    }
+
+    fn mean_confidence_delta(&self) -> f64 {
+        if self.queries == 0 {
+            return 0.0;
+        }
+        self.confidence_delta_sum / self.queries as f64
+    }
+
+    fn at_parity(&self) -> bool {
+        self.queries >= self.policy.min_queries
+            && self.disagreement_rate() <= self.policy.max_disagreement_rate
+            && self.mean_confidence_delta().abs() <= self.policy.max_confidence_delta
+    }
+
+    pub fn disagreements(&self) -> &[ShadowDisagreement] {
+        &self.log
+    }
+
+    pub fn status(&self, now: u64) -> ShadowStatus {
+        ShadowStatus {
+            shadow_version: self.shadow_version.clone(),
+            started_at: self.started_at,
+            queries: self.queries,
+            disagreements: self.disagreements,
+            disagreement_rate: self.disagreement_rate(),
+            mean_confidence_delta: self.mean_confidence_delta(),
+            parity_since: self.parity_since,
+            promotable: self.check_promotion(now).is_ok(),
+        }
+    }
+
+    pub fn check_promotion(&self, now: u64) -> Result<(), String> {
+        match self.parity_since {
+            None => Err(format!(
+                "Shadow model {} is not at parity: {} queries, {:.1}% disagreement, confidence delta {:+.3}",
+                self.shadow_version,
+                self.queries,
+                self.disagreement_rate() * 100.0,
+                self.mean_confidence_delta()
+            )),
+            Some(since) if now.saturating_sub(since) < self.policy.parity_period_ns => Err(format!(
+                "Shadow model {} has been at parity for {}s of the required {}s",
+                self.shadow_version,
+                now.saturating_sub(since) / 1_000_000_000,
+                self.policy.parity_period_ns / 1_000_000_000
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(version: &str, diagnosis: &str, confidence: f64) -> ShadowScore {
+        ShadowScore { model_version: version.to_string(), diagnosis: diagnosis.to_string(), confidence }
+    }
+
+    #[test]
+    fn test_promotion_needs_an_unbroken_parity_period() {
+        let policy = ShadowPolicy { parity_period_ns: 100, min_queries: 10, max_logged_disagreements: 2, ..ShadowPolicy::default() };
+        let mut shadow = ShadowDeployment::new(policy, "v2".to_string(), 0).unwrap();
+        for t in 0..10 {
+            shadow.record("q", score("v1", "Fabry disease", 0.80), score("v2", "Fabry disease", 0.82), t);
+        }
+        assert_eq!(shadow.status(9).parity_since, Some(9));
+        assert!(shadow.check_promotion(50).is_err());
+        assert!(shadow.status(109).promotable);
+
+        // Three disagreements in thirteen queries break parity and restart the period
+        for t in 110..113 {
+            shadow.record(&format!("q{}", t), score("v1", "Fabry disease", 0.8), score("v2", "Pompe disease", 0.8), t);
+        }
+        let status = shadow.status(300);
+        assert_eq!((status.disagreements, status.parity_since, status.promotable), (3, None, false));
+        assert_eq!(shadow.disagreements().len(), 2);
+        assert_eq!(shadow.disagreements()[1].correlation_id, "q112");
+    }
+}
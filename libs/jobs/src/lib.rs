@@ -0,0 +1,244 @@
+// Long-running jobs for canisters. A single message is capped at a few billion
+// instructions, which aggregating many large updates, anonymizing a big dataset or
+// validating a model can exceed. Such work is written as a `ChunkedJob` that does a
+// bounded amount of it per call. The canister submits the job to its `JobQueue`,
+// hands the caller the job id straight away, and drives the queue from a zero-delay
+// timer: each timer message runs one chunk and schedules the next until nothing is
+// left to run. Callers poll the job's info for progress.
+//
+// Jobs run one at a time in submission order. Cancelling marks the job before its
+// next chunk and gives it a chance to undo partial state. The queue holds no timers
+// itself, so it can be exercised off-chain; `run_next` is what the canister's timer
+// callback calls. Finished jobs are kept, up to `max_finished`, so their outcome can
+// still be queried.
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed { error: String },
+    Cancelled,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: String,
+    pub submitted_by: Principal,
+    pub status: JobStatus,
+    // Units are job-specific (updates, records, ...)
+    pub units_done: u64,
+    pub total_units: u64,
+    pub chunks_run: u64,
+    pub submitted_at: u64,
+    pub updated_at: u64,
+    pub result: Option<String>,
+}
+
+pub enum ChunkOutcome {
+    Continue { units: u64 },
+    Done { units: u64, result: Option<String> },
+}
+
+pub trait ChunkedJob {
+    fn kind(&self) -> String;
+
+    fn total_units(&self) -> u64;
+
+    // Does the next bounded piece of work
+    fn run_chunk(&mut self) -> Result<ChunkOutcome, String>;
+
+    // Called once when the job is cancelled or fails, to undo partial state
+    fn abort(&mut self) {}
+}
+
+pub struct JobQueue {
+    jobs: BTreeMap<u64, (JobInfo, Option<Box<dyn ChunkedJob>>)>,
+    next_id: u64,
+    max_finished: usize,
+}
+
+impl JobInfo {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Completed | JobStatus::Failed { .. } | JobStatus::Cancelled)
+    }
+
+    // Fraction of the units done, in [0, 1]
+    pub fn progress(&self) -> f64 {
+        match self.status {
+            JobStatus::Completed => 1.0,
+            _ if self.total_units == 0 => 0.0,
+            _ => (self.units_done as f64 / self.total_units as f64).min(1.0),
+        }
+    }
+}
+
+impl JobQueue {
+    pub fn new(max_finished: usize) -> Self {
+        JobQueue { jobs: BTreeMap::new(), next_id: 0, max_finished }
+    }
+
+    pub fn submit(&mut self, job: Box<dyn ChunkedJob>, submitted_by: Principal, now: u64) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let info = JobInfo {
+            id,
+            kind: job.kind(),
+            submitted_by,
+            status: JobStatus::Queued,
+            units_done: 0,
+            total_units: job.total_units(),
+            chunks_run: 0,
+            submitted_at: now,
+            updated_at: now,
+            result: None,
+        };
+        self.jobs.insert(id, (info, Some(job)));
+        id
+    }
+
+    pub fn has_runnable(&self) -> bool {
+        self.jobs.values().any(|(info, _)| !info.is_finished())
+    }
+
+    pub fn info(&self, id: u64) -> Option<JobInfo> {
+        self.jobs.get(&id).map(|(info, _)| info.clone())
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.jobs.values().map(|(info, _)| info.clone()).collect()
+    }
+
+    // Runs one chunk of the oldest unfinished job and returns its id
+    pub fn run_next(&mut self, now: u64) -> Option<u64> {
+        let (info, job) = self.jobs.values_mut().find(|(info, _)| !info.is_finished())?;
+        let id = info.id;
+        let task = job.as_mut()?;
+        info.status = JobStatus::Running;
+        info.chunks_run += 1;
+        info.updated_at = now;
+
+        match task.run_chunk() {
+            Ok(ChunkOutcome::Continue { units }) => info.units_done += units,
+            Ok(ChunkOutcome::Done { units, result }) => {
+                info.units_done += units;
+                info.status = JobStatus::Completed;
+                info.result = result;
+                *job = None;
+            }
+            Err(error) => {
+                task.abort();
+                info.status = JobStatus::Failed { error };
+                *job = None;
+            }
+        }
+        self.prune();
+        Some(id)
+    }
+
+    pub fn cancel(&mut self, id: u64, now: u64) -> Result<(), String> {
+        let (info, job) = self.jobs.get_mut(&id).ok_or_else(|| format!("No job {}", id))?;
+        if info.is_finished() {
+            return Err(format!("Job {} has already finished", id));
+        }
+        if let Some(task) = job.as_mut() {
+            task.abort();
+        }
+        info.status = JobStatus::Cancelled;
+        info.updated_at = now;
+        *job = None;
+        self.prune();
+        Ok(())
+    }
+
+    // Drops the jobs that finished longest ago beyond `max_finished`
+    fn prune(&mut self) {
+        let mut finished: Vec<(u64, u64)> =
+            self.jobs.values().filter(|(info, _)| info.is_finished()).map(|(info, _)| (info.updated_at, info.id)).collect();
+        finished.sort();
+        for (_, id) in finished.iter().take(finished.len().saturating_sub(self.max_finished)) {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FINISHED_JOBS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Sum {
+        values: Vec<u64>,
+        next: usize,
+        total: u64,
+        aborted: Rc<Cell<bool>>,
+    }
+
+    impl ChunkedJob for Sum {
+        fn kind(&self) -> String {
+            "sum".to_string()
+        }
+
+        fn total_units(&self) -> u64 {
+            self.values.len() as u64
+        }
+
+        fn run_chunk(&mut self) -> Result<ChunkOutcome, String> {
+            let end = (self.next + 2).min(self.values.len());
+            self.total += self.values[self.next..end].iter().sum::<u64>();
+            let units = (end - self.next) as u64;
+            self.next = end;
+            if end < self.values.len() {
+                Ok(ChunkOutcome::Continue { units })
+            } else {
+                Ok(ChunkOutcome::Done { units, result: Some(self.total.to_string()) })
+            }
+        }
+
+        fn abort(&mut self) {
+            self.aborted.set(true);
+        }
+    }
+
+    #[test]
+    fn test_jobs_run_in_chunks_and_can_be_cancelled() {
+        let caller = Principal::from_slice(&[1]);
+        let sum = |values: Vec<u64>, aborted: &Rc<Cell<bool>>| {
+            Box::new(Sum { values, next: 0, total: 0, aborted: aborted.clone() })
+        };
+        let (first_aborted, second_aborted) = (Rc::new(Cell::new(false)), Rc::new(Cell::new(false)));
+
+        let mut queue = JobQueue::new(1);
+        let first = queue.submit(sum(vec![1, 2, 3, 4, 5], &first_aborted), caller, 0);
+        let second = queue.submit(sum(vec![10, 20, 30], &second_aborted), caller, 0);
+
+        assert_eq!(queue.run_next(1), Some(first));
+        let info = queue.info(first).unwrap();
+        assert_eq!((info.progress(), info.status), (0.4, JobStatus::Running));
+
+        queue.cancel(second, 2).unwrap();
+        assert!(second_aborted.get() && queue.cancel(second, 2).is_err());
+        while queue.run_next(3).is_some() {}
+        let info = queue.info(first).unwrap();
+        assert_eq!((info.progress(), info.chunks_run), (1.0, 3));
+        assert_eq!((info.status, info.result), (JobStatus::Completed, Some("15".to_string())));
+        assert!(!first_aborted.get() && !queue.has_runnable());
+
+        // Only the newest finished job is kept
+        assert!(queue.info(second).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+// Attachment checks for DiagnosticReport.presented_form and similar fields. Inline
+// data is base64 and checked against the declared size, the SHA-256 in `hash`
+// (base64 as in FHIR, hex also accepted) and a content-type whitelist. Attachments
+// that only carry a URL can be dereferenced from a canister with an HTTPS outcall
+// (feature "canister"); fetched content must match a declared hash, since the hash
+// is the only thing tying a remote document to the signed report.
+
+use crate::*;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AttachmentPolicy {
+    pub max_size_bytes: u64,
+    // Media types without parameters, compared case-insensitively
+    pub allowed_content_types: Vec<String>,
+    pub require_hash: bool,
+    pub allow_external_urls: bool,
+}
+
+impl Default for AttachmentPolicy {
+    fn default() -> Self {
+        AttachmentPolicy {
+            max_size_bytes: 10 * 1024 * 1024,
+            allowed_content_types: ["application/pdf", "application/dicom", "image/png", "image/jpeg", "text/plain"]
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            require_hash: false,
+            allow_external_urls: false,
+        }
+    }
+}
+
+// SHA-256 of the content, base64 encoded as FHIR carries it
+pub fn attachment_hash(content: &[u8]) -> String {
+    STANDARD.encode(Sha256::digest(content))
+}
+
+impl Attachment {
+    // Inline attachment with size and hash filled in
+    pub fn from_bytes(content_type: &str, content: &[u8]) -> Self {
+        Attachment {
+            content_type: Some(content_type.to_string()),
+            language: None,
+            data: Some(STANDARD.encode(content)),
+            url: None,
+            size: Some(content.len() as u64),
+            hash: Some(attachment_hash(content)),
+            title: None,
+            creation: None,
+        }
+    }
+
+    pub fn decoded_data(&self) -> Result<Option<Vec<u8>>, String> {
+        match &self.data {
+            Some(data) => STANDARD.decode(data.trim()).map(Some).map_err(|e| format!("Attachment data is not valid base64: {}", e)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl AttachmentPolicy {
+    fn check_content_type(&self, content_type: Option<&str>) -> Result<(), String> {
+        let content_type = content_type.ok_or("Attachment content type is required")?;
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+        if !self.allowed_content_types.iter().any(|t| t.eq_ignore_ascii_case(media_type)) {
+            return Err(format!("Attachment content type {} is not allowed", media_type));
+        }
+        Ok(())
+    }
+
+    // Checks content against the attachment's declared size and hash
+    pub fn verify_content(&self, attachment: &Attachment, content: &[u8]) -> Result<(), String> {
+        if content.len() as u64 > self.max_size_bytes {
+            return Err(format!("Attachment is {} bytes, limit is {}", content.len(), self.max_size_bytes));
+        }
+        if let Some(size) = attachment.size {
+            if size != content.len() as u64 {
+                return Err(format!("Attachment declares {} bytes but has {}", size, content.len()));
+            }
+        }
+        match &attachment.hash {
+            Some(hash) => {
+                let digest = Sha256::digest(content);
+                let matches = STANDARD.decode(hash.trim()).is_ok_and(|h| h == digest.as_slice())
+                    || hash.trim().eq_ignore_ascii_case(&format!("{:x}", digest));
+                if !matches {
+                    return Err("Attachment content does not match its SHA-256 hash".to_string());
+                }
+            }
+            None if self.require_hash => return Err("Attachment hash is required".to_string()),
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, attachment: &Attachment) -> Result<(), String> {
+        self.check_content_type(attachment.content_type.as_deref())?;
+        if let Some(size) = attachment.size {
+            if size > self.max_size_bytes {
+                return Err(format!("Attachment is {} bytes, limit is {}", size, self.max_size_bytes));
+            }
+        }
+
+        if let Some(content) = attachment.decoded_data()? {
+            return self.verify_content(attachment, &content);
+        }
+        match &attachment.url {
+            Some(url) => {
+                if !self.allow_external_urls {
+                    return Err("External attachment URLs are not allowed".to_string());
+                }
+                if !url.starts_with("https://") {
+                    return Err("External attachments must use HTTPS".to_string());
+                }
+                if attachment.hash.is_none() {
+                    return Err("External attachments must declare a hash".to_string());
+                }
+                Ok(())
+            }
+            None => Err("Attachment has neither data nor url".to_string()),
+        }
+    }
+}
+
+impl DiagnosticReport {
+    pub fn validate_attachments(&self, policy: &AttachmentPolicy) -> Result<(), String> {
+        for (i, attachment) in self.presented_form.iter().enumerate() {
+            policy.validate(attachment).map_err(|e| format!("presented_form[{}]: {}", i, e))?;
+        }
+        Ok(())
+    }
+}
+
+// HTTPS outcalls from a canister
+#[cfg(feature = "canister")]
+pub mod https {
+    use super::*;
+    use ic_cdk::api::management_canister::http_request::{
+        http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+        TransformContext,
+    };
+
+    // Headers beyond this allowance count against the response size limit
+    const HEADER_ALLOWANCE_BYTES: u64 = 8 * 1024;
+
+    // Body of the canister's transform query: replicas must agree on the response, so
+    // everything but the status, content type and body is dropped
+    pub fn normalize_response(args: TransformArgs) -> HttpResponse {
+        let headers = args
+            .response
+            .headers
+            .into_iter()
+            .filter(|h| h.name.eq_ignore_ascii_case("content-type"))
+            .map(|h| HttpHeader { name: "content-type".to_string(), value: h.value })
+            .collect();
+        HttpResponse { status: args.response.status, headers, body: args.response.body }
+    }
+
+    // Fetches a URL-only attachment and verifies it; `transform` names a query that
+    // calls `normalize_response`
+    pub async fn fetch_attachment(
+        attachment: &Attachment,
+        policy: &AttachmentPolicy,
+        transform: TransformContext,
+        cycles: u128,
+    ) -> Result<Vec<u8>, String> {
+        policy.validate(attachment)?;
+        let url = attachment.url.clone().ok_or("Attachment has no url")?;
+        let request = CanisterHttpRequestArgument {
+            url,
+            method: HttpMethod::GET,
+            body: None,
+            max_response_bytes: Some(policy.max_size_bytes + HEADER_ALLOWANCE_BYTES),
+            transform: Some(transform),
+            headers: Vec::new(),
+        };
+
+        let (response,) = http_request(request, cycles)
+            .await
+            .map_err(|(code, message)| format!("Attachment fetch failed: {:?} {}", code, message))?;
+        if response.status != candid::Nat::from(200u32) {
+            return Err(format!("Attachment fetch returned status {}", response.status));
+        }
+        if let Some(header) = response.headers.iter().find(|h| h.name == "content-type") {
+            policy.check_content_type(Some(&header.value))?;
+        }
+        policy.verify_content(attachment, &response.body)?;
+        Ok(response.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_size_hash_and_type_checks() {
+        let policy = AttachmentPolicy::default();
+        let report = b"%PDF-1.7 pathology report";
+        let attachment = Attachment::from_bytes("application/pdf", report);
+        assert!(policy.validate(&attachment).is_ok());
+
+        let mut hex_hash = attachment.clone();
+        hex_hash.hash = Some(format!("{:x}", Sha256::digest(report)));
+        assert!(policy.validate(&hex_hash).is_ok());
+
+        let mut tampered = attachment.clone();
+        tampered.data = Some(STANDARD.encode(b"%PDF-1.7 altered report!"));
+        assert!(policy.validate(&tampered).is_err());
+
+        let mut wrong_type = attachment.clone();
+        wrong_type.content_type = Some("application/x-msdownload".to_string());
+        assert!(policy.validate(&wrong_type).is_err());
+
+        let small = AttachmentPolicy { max_size_bytes: 8, ..AttachmentPolicy::default() };
+        assert!(small.validate(&attachment).is_err());
+
+        let mut remote = attachment.clone();
+        remote.data = None;
+        remote.url = Some("https://pacs.example.org/report.pdf".to_string());
+        assert!(policy.validate(&remote).is_err());
+        let external = AttachmentPolicy { allow_external_urls: true, ..AttachmentPolicy::default() };
+        assert!(external.validate(&remote).is_ok());
+        remote.hash = None;
+        assert!(external.validate(&remote).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+// Condition clinical and verification status as a lifecycle. Both statuses are typed
+// and move only along allowed transitions, each recorded with the date it took
+// effect, so a condition's history can be replayed and a resolved diagnosis cannot
+// silently flip back to provisional. The codes are those of the FHIR
+// condition-clinical and condition-ver-status code systems.
+//
+// Clinical: active (or its recurrence/relapse forms) may become inactive, remission
+// or resolved; inactive and remission may progress to resolved or come back as a
+// recurrence or relapse; a resolved condition can only come back as a recurrence.
+// Verification: unconfirmed, provisional and differential may be confirmed or
+// refuted; a confirmed diagnosis may still be refuted; refuted is final. Anything may
+// be marked entered-in-error, which is final and clears the clinical status.
+
+use crate::mortality::parse_day;
+use crate::*;
+
+pub const CLINICAL_STATUS_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/condition-clinical";
+pub const VERIFICATION_STATUS_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/condition-ver-status";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClinicalStatus {
+    Active,
+    Recurrence,
+    Relapse,
+    Inactive,
+    Remission,
+    Resolved,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationStatus {
+    Unconfirmed,
+    Provisional,
+    Differential,
+    Confirmed,
+    Refuted,
+    EnteredInError,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum StatusChange {
+    Clinical { from: Option<ClinicalStatus>, to: Option<ClinicalStatus> },
+    Verification { from: Option<VerificationStatus>, to: VerificationStatus },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ConditionStatusChange {
+    pub change: StatusChange,
+    // "YYYY-MM-DD" or a dateTime starting with it
+    pub effective_date: String,
+}
+
+impl ClinicalStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ClinicalStatus::Active => "active",
+            ClinicalStatus::Recurrence => "recurrence",
+            ClinicalStatus::Relapse => "relapse",
+            ClinicalStatus::Inactive => "inactive",
+            ClinicalStatus::Remission => "remission",
+            ClinicalStatus::Resolved => "resolved",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "active" => Some(ClinicalStatus::Active),
+            "recurrence" => Some(ClinicalStatus::Recurrence),
+            "relapse" => Some(ClinicalStatus::Relapse),
+            "inactive" => Some(ClinicalStatus::Inactive),
+            "remission" => Some(ClinicalStatus::Remission),
+            "resolved" => Some(ClinicalStatus::Resolved),
+            _ => None,
+        }
+    }
+
+    // Recurrence and relapse are kinds of active
+    pub fn is_active(&self) -> bool {
+        matches!(self, ClinicalStatus::Active | ClinicalStatus::Recurrence | ClinicalStatus::Relapse)
+    }
+
+    pub fn can_transition_to(&self, next: ClinicalStatus) -> bool {
+        use ClinicalStatus::*;
+        match self {
+            Active | Recurrence | Relapse => matches!(next, Inactive | Remission | Resolved),
+            Inactive => matches!(next, Remission | Resolved | Recurrence | Relapse),
+            Remission => matches!(next, Resolved | Recurrence | Relapse),
+            Resolved => next == Recurrence,
+        }
+    }
+
+    pub fn to_concept(&self) -> CodeableConcept {
+        create_codeable_concept(create_coding(CLINICAL_STATUS_SYSTEM, self.code(), self.code()), None)
+    }
+}
+
+impl VerificationStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerificationStatus::Unconfirmed => "unconfirmed",
+            VerificationStatus::Provisional => "provisional",
+            VerificationStatus::Differential => "differential",
+            VerificationStatus::Confirmed => "confirmed",
+            VerificationStatus::Refuted => "refuted",
+            VerificationStatus::EnteredInError => "entered-in-error",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "unconfirmed" => Some(VerificationStatus::Unconfirmed),
+            "provisional" => Some(VerificationStatus::Provisional),
+            "differential" => Some(VerificationStatus::Differential),
+            "confirmed" => Some(VerificationStatus::Confirmed),
+            "refuted" => Some(VerificationStatus::Refuted),
+            "entered-in-error" => Some(VerificationStatus::EnteredInError),
+            _ => None,
+        }
+    }
+
+    pub fn can_transition_to(&self, next: VerificationStatus) -> bool {
+        use VerificationStatus::*;
+        match self {
+            _ if next == EnteredInError => *self != EnteredInError,
+            Unconfirmed => matches!(next, Provisional | Differential | Confirmed | Refuted),
+            Provisional => matches!(next, Differential | Confirmed | Refuted),
+            Differential => matches!(next, Provisional | Confirmed | Refuted),
+            Confirmed => next == Refuted,
+            Refuted | EnteredInError => false,
+        }
+    }
+
+    pub fn to_concept(&self) -> CodeableConcept {
+        create_codeable_concept(create_coding(VERIFICATION_STATUS_SYSTEM, self.code(), self.code()), None)
+    }
+}
+
+fn check_effective_date(condition: &Condition, effective_date: &str) -> Result<(), String> {
+    let day = parse_day(effective_date).ok_or(format!("Invalid status effective date: {}", effective_date))?;
+    if let Some(ConditionOnset::DateTime(onset)) = &condition.onset {
+        if parse_day(onset).is_some_and(|onset| day < onset) {
+            return Err(format!("Condition {} status change on {} precedes onset {}", condition.id, effective_date, onset));
+        }
+    }
+    if let Some(last) = condition.status_history.last().and_then(|c| parse_day(&c.effective_date)) {
+        if day < last {
+            return Err(format!("Condition {} status change on {} precedes the previous change", condition.id, effective_date));
+        }
+    }
+    Ok(())
+}
+
+impl Condition {
+    // The first clinical status may be anything; later ones must follow the lifecycle.
+    // Moving to remission or resolved records the abatement date; a recurrence or
+    // relapse clears it.
+    pub fn update_clinical_status(&mut self, status: ClinicalStatus, effective_date: &str) -> Result<(), String> {
+        if self.verification_status == Some(VerificationStatus::EnteredInError) {
+            return Err(format!("Condition {} was entered in error", self.id));
+        }
+        if let Some(current) = self.clinical_status {
+            if !current.can_transition_to(status) {
+                return Err(format!(
+                    "Condition {} cannot move from {} to {}",
+                    self.id,
+                    current.code(),
+                    status.code()
+                ));
+            }
+        }
+        check_effective_date(self, effective_date)?;
+
+        match status {
+            ClinicalStatus::Remission | ClinicalStatus::Resolved if self.abatement.is_none() => {
+                self.abatement = Some(ConditionAbatement::DateTime(effective_date.to_string()));
+            }
+            ClinicalStatus::Recurrence | ClinicalStatus::Relapse => self.abatement = None,
+            _ => {}
+        }
+        self.status_history.push(ConditionStatusChange {
+            change: StatusChange::Clinical { from: self.clinical_status, to: Some(status) },
+            effective_date: effective_date.to_string(),
+        });
+        self.clinical_status = Some(status);
+        Ok(())
+    }
+
+    pub fn update_verification_status(&mut self, status: VerificationStatus, effective_date: &str) -> Result<(), String> {
+        if let Some(current) = self.verification_status {
+            if !current.can_transition_to(status) {
+                return Err(format!(
+                    "Condition {} cannot move from {} to {}",
+                    self.id,
+                    current.code(),
+                    status.code()
+                ));
+            }
+        }
+        check_effective_date(self, effective_date)?;
+
+        self.status_history.push(ConditionStatusChange {
+            change: StatusChange::Verification { from: self.verification_status, to: status },
+            effective_date: effective_date.to_string(),
+        });
+        self.verification_status = Some(status);
+        if status == VerificationStatus::EnteredInError && self.clinical_status.is_some() {
+            self.status_history.push(ConditionStatusChange {
+                change: StatusChange::Clinical { from: self.clinical_status, to: None },
+                effective_date: effective_date.to_string(),
+            });
+            self.clinical_status = None;
+        }
+        Ok(())
+    }
+
+    // Clinical status in effect on a day, replayed from the history
+    pub fn clinical_status_on(&self, date: &str) -> Option<ClinicalStatus> {
+        let day = parse_day(date)?;
+        self.status_history
+            .iter()
+            .rev()
+            .filter(|c| parse_day(&c.effective_date).is_some_and(|d| d <= day))
+            .find_map(|c| match c.change {
+                StatusChange::Clinical { to, .. } => Some(to),
+                StatusChange::Verification { .. } => None,
+            })
+            .flatten()
+    }
+
+    // FHIR con-4 and con-5: an abated condition is not active, and an entry made in
+    // error carries no clinical status
+    pub(crate) fn validate_status(&self) -> Result<(), String> {
+        if self.verification_status == Some(VerificationStatus::EnteredInError) && self.clinical_status.is_some() {
+            return Err(format!("Condition {} entered in error cannot have a clinical status", self.id));
+        }
+        if self.abatement.is_some() && self.clinical_status.is_some_and(|s| s.is_active()) {
+            return Err(format!("Condition {} has an abatement but is still active", self.id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_lifecycle_follows_allowed_transitions() {
+        let mut condition = Condition::new("c1".to_string(), create_reference("Patient/p1", None));
+        condition.set_onset(ConditionOnset::DateTime("2023-01-10".to_string()));
+        assert!(condition.update_clinical_status(ClinicalStatus::Active, "2022-12-31").is_err());
+
+        condition.update_clinical_status(ClinicalStatus::Active, "2023-01-10").unwrap();
+        condition.update_verification_status(VerificationStatus::Provisional, "2023-01-10").unwrap();
+        condition.update_verification_status(VerificationStatus::Confirmed, "2023-02-01").unwrap();
+        assert!(condition.update_verification_status(VerificationStatus::Provisional, "2023-02-02").is_err());
+
+        condition.update_clinical_status(ClinicalStatus::Remission, "2023-06-01").unwrap();
+        assert!(matches!(&condition.abatement, Some(ConditionAbatement::DateTime(d)) if d == "2023-06-01"));
+        assert!(condition.validate().is_ok());
+        condition.update_clinical_status(ClinicalStatus::Resolved, "2023-12-01").unwrap();
+        assert!(condition.update_clinical_status(ClinicalStatus::Remission, "2024-01-01").is_err());
+        assert!(condition.update_clinical_status(ClinicalStatus::Recurrence, "2023-11-01").is_err());
+        condition.update_clinical_status(ClinicalStatus::Recurrence, "2024-03-01").unwrap();
+        assert!(condition.abatement.is_none());
+
+        assert_eq!(condition.clinical_status_on("2023-07-15"), Some(ClinicalStatus::Remission));
+        assert_eq!(condition.clinical_status_on("2022-01-01"), None);
+
+        condition.update_verification_status(VerificationStatus::EnteredInError, "2024-04-01").unwrap();
+        assert!(condition.clinical_status.is_none());
+        assert!(condition.update_clinical_status(ClinicalStatus::Active, "2024-04-02").is_err());
+    }
+}
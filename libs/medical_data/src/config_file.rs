@@ -0,0 +1,115 @@
+// Loading configuration from TOML or JSON, so deployments describe their pipelines and
+// policies in files instead of Rust. Parsing is strict: a value of the wrong type, a
+// missing field or (where the type denies them) an unknown field fails with the path
+// of the offending field and, for TOML, its line; the loaded value then goes through
+// the same validation as one built in code.
+//
+// Files are recognised by extension. `load_config_file` is for native tooling;
+// canisters receive configuration text over their interface and call `parse_config`.
+
+use crate::deidentification::{DeidentificationPipeline, DeidentificationPipelineBuilder, DeidentificationStep};
+use crate::*;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+// A de-identification pipeline as written in a config file: its steps, in order
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DeidentificationConfig {
+    pub steps: Vec<DeidentificationStep>,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("json") => Ok(ConfigFormat::Json),
+            _ => Err(format!("{}: config files must end in .toml or .json", path.display())),
+        }
+    }
+}
+
+pub fn parse_config<T: DeserializeOwned>(text: &str, format: ConfigFormat) -> Result<T, String> {
+    match format {
+        ConfigFormat::Toml => {
+            let deserializer = toml::Deserializer::new(text);
+            serde_path_to_error::deserialize(deserializer).map_err(|e| describe_error(e.path(), e.inner().message(), e.inner().span().map(|s| line_of(text, s.start))))
+        }
+        ConfigFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(text);
+            serde_path_to_error::deserialize(&mut deserializer).map_err(|e| describe_error(e.path(), &e.inner().to_string(), None))
+        }
+    }
+}
+
+pub fn load_config_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let format = ConfigFormat::from_path(path)?;
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_config(&text, format).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+fn describe_error(path: &serde_path_to_error::Path, message: &str, line: Option<usize>) -> String {
+    let location = match line {
+        Some(line) => format!(" (line {})", line),
+        None => String::new(),
+    };
+    // serde_json already appends its own line and column
+    match path.to_string().as_str() {
+        "." => format!("{}{}", message.trim(), location),
+        field => format!("{}: {}{}", field, message.trim(), location),
+    }
+}
+
+impl DeidentificationConfig {
+    pub fn into_pipeline(self) -> Result<DeidentificationPipeline, String> {
+        self.steps.into_iter().fold(DeidentificationPipelineBuilder::new(), |builder, step| builder.step(step)).build()
+    }
+}
+
+impl DeidentificationPipeline {
+    pub fn from_config_str(text: &str, format: ConfigFormat) -> Result<Self, String> {
+        parse_config::<DeidentificationConfig>(text, format)?.into_pipeline()
+    }
+
+    pub fn from_config_file(path: &Path) -> Result<Self, String> {
+        load_config_file::<DeidentificationConfig>(path)?.into_pipeline().map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipelines_load_from_toml_and_json_with_located_errors() {
+        let toml = r#"
+steps = [
+    { DateShift = { max_days = 30, key = "site-secret" } },
+    { KAnonymity = { k = 5 } },
+    { LDiversity = { l = 2 } },
+]
+"#;
+        let pipeline = DeidentificationPipeline::from_config_str(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(pipeline.steps().len(), 3);
+        let json = r#"{"steps": ["SafeHarbor", {"KAnonymity": {"k": 3}}]}"#;
+        assert_eq!(DeidentificationPipeline::from_config_str(json, ConfigFormat::Json).unwrap().steps()[0], DeidentificationStep::SafeHarbor);
+
+        // Type errors name the field and line; pipeline rules still apply
+        let error = DeidentificationPipeline::from_config_str("steps = [\n  { KAnonymity = { k = \"five\" } },\n]", ConfigFormat::Toml).unwrap_err();
+        assert!(error.contains("steps[0].KAnonymity.k") && error.contains("line 2"), "{}", error);
+        let error = DeidentificationPipeline::from_config_str(r#"{"steps": [], "stepz": []}"#, ConfigFormat::Json).unwrap_err();
+        assert!(error.contains("unknown field `stepz`"), "{}", error);
+        assert!(DeidentificationPipeline::from_config_str(r#"steps = [{ LDiversity = { l = 2 } }]"#, ConfigFormat::Toml).is_err());
+        assert!(ConfigFormat::from_path(Path::new("pipeline.yaml")).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+// De-identification pipeline. Steps run in the order they were declared; the builder
+// rejects combinations that defeat each other (Safe Harbor truncates the dates that
+// date shifting is meant to keep usable) or that lack their prerequisite (l-diversity
+// and t-closeness refine the equivalence classes k-anonymity produces). Every run
+// records the steps it applied in a Provenance entry and measures the result once.
+
+use crate::privacy::{MedicalDataPrivacy, PrivacyMetrics};
+use crate::*;
+use chrono::{Duration, NaiveDate};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DeidentificationStep {
+    SafeHarbor,
+    // Shifts each patient's dates by a keyed offset in [-max_days, max_days], the same
+    // for all of that patient's resources so intervals between events are preserved
+    DateShift { max_days: u32, key: String },
+    KAnonymity { k: u32 },
+    LDiversity { l: u32 },
+    TCloseness { t: f64 },
+    DifferentialPrivacy { epsilon: f64 },
+}
+
+// FHIR-style provenance of one pipeline run
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Provenance {
+    // Dataset the activity produced
+    pub target: String,
+    pub activity: String,
+    pub recorded: String,
+    pub steps: Vec<ProvenanceStep>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProvenanceStep {
+    pub order: u32,
+    pub step: String,
+    // Never includes secrets such as the date-shift key
+    pub parameters: String,
+    pub completed_at: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DeidentificationReport {
+    pub provenance: Provenance,
+    pub metrics: PrivacyMetrics,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeidentificationPipeline {
+    steps: Vec<DeidentificationStep>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeidentificationPipelineBuilder {
+    steps: Vec<DeidentificationStep>,
+}
+
+impl DeidentificationStep {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeidentificationStep::SafeHarbor => "safe_harbor",
+            DeidentificationStep::DateShift { .. } => "date_shift",
+            DeidentificationStep::KAnonymity { .. } => "k_anonymity",
+            DeidentificationStep::LDiversity { .. } => "l_diversity",
+            DeidentificationStep::TCloseness { .. } => "t_closeness",
+            DeidentificationStep::DifferentialPrivacy { .. } => "differential_privacy",
+        }
+    }
+
+    fn parameters(&self) -> String {
+        match self {
+            DeidentificationStep::SafeHarbor => String::new(),
+            DeidentificationStep::DateShift { max_days, .. } => format!("max_days={}", max_days),
+            DeidentificationStep::KAnonymity { k } => format!("k={}", k),
+            DeidentificationStep::LDiversity { l } => format!("l={}", l),
+            DeidentificationStep::TCloseness { t } => format!("t={}", t),
+            DeidentificationStep::DifferentialPrivacy { epsilon } => format!("epsilon={}", epsilon),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            DeidentificationStep::DateShift { max_days, key } => {
+                if *max_days == 0 {
+                    return Err("Date shift max_days must be at least 1".to_string());
+                }
+                if key.is_empty() {
+                    return Err("Date shift key cannot be empty".to_string());
+                }
+            }
+            DeidentificationStep::KAnonymity { k } if *k < 2 => {
+                return Err("k-anonymity requires k >= 2".to_string());
+            }
+            DeidentificationStep::LDiversity { l } if *l < 1 => {
+                return Err("l-diversity requires l >= 1".to_string());
+            }
+            DeidentificationStep::TCloseness { t } if !(*t > 0.0 && *t <= 1.0) => {
+                return Err("t-closeness threshold must be in (0, 1]".to_string());
+            }
+            DeidentificationStep::DifferentialPrivacy { epsilon } if !(epsilon.is_finite() && *epsilon > 0.0) => {
+                return Err("Differential privacy epsilon must be positive".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl DeidentificationPipelineBuilder {
+    pub fn new() -> Self {
+        DeidentificationPipelineBuilder::default()
+    }
+
+    pub fn safe_harbor(mut self) -> Self {
+        self.steps.push(DeidentificationStep::SafeHarbor);
+        self
+    }
+
+    pub fn date_shift(mut self, max_days: u32, key: &str) -> Self {
+        self.steps.push(DeidentificationStep::DateShift { max_days, key: key.to_string() });
+        self
+    }
+
+    pub fn k_anonymity(mut self, k: u32) -> Self {
+        self.steps.push(DeidentificationStep::KAnonymity { k });
+        self
+    }
+
+    pub fn l_diversity(mut self, l: u32) -> Self {
+        self.steps.push(DeidentificationStep::LDiversity { l });
+        self
+    }
+
+    pub fn t_closeness(mut self, t: f64) -> Self {
+        self.steps.push(DeidentificationStep::TCloseness { t });
+        self
+    }
+
+    pub fn differential_privacy(mut self, epsilon: f64) -> Self {
+        self.steps.push(DeidentificationStep::DifferentialPrivacy { epsilon });
+        self
+    }
+
+    pub fn step(mut self, step: DeidentificationStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn build(self) -> Result<DeidentificationPipeline, String> {
+        if self.steps.is_empty() {
+            return Err("De-identification pipeline has no steps".to_string());
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            step.validate()?;
+            if self.steps[..i].iter().any(|s| s.name() == step.name()) {
+                return Err(format!("Step {} is declared more than once", step.name()));
+            }
+        }
+
+        let position = |name: &str| self.steps.iter().position(|s| s.name() == name);
+        if position("safe_harbor").is_some() && position("date_shift").is_some() {
+            return Err("Safe Harbor reduces dates to the year, which defeats date shifting".to_string());
+        }
+        for refinement in ["l_diversity", "t_closeness"] {
+            if let Some(at) = position(refinement) {
+                match position("k_anonymity") {
+                    Some(k) if k < at => {}
+                    _ => return Err(format!("{} must come after k_anonymity", refinement)),
+                }
+            }
+        }
+        Ok(DeidentificationPipeline { steps: self.steps })
+    }
+}
+
+impl DeidentificationPipeline {
+    pub fn builder() -> DeidentificationPipelineBuilder {
+        DeidentificationPipelineBuilder::new()
+    }
+
+    pub fn steps(&self) -> &[DeidentificationStep] {
+        &self.steps
+    }
+
+    pub fn run(&self, dataset: &mut MedicalDataset) -> Result<DeidentificationReport, String> {
+        let k = self.steps.iter().find_map(|s| match s {
+            DeidentificationStep::KAnonymity { k } => Some(*k),
+            _ => None,
+        });
+        let l = self.steps.iter().find_map(|s| match s {
+            DeidentificationStep::LDiversity { l } => Some(*l),
+            _ => None,
+        });
+        let mut privacy = MedicalDataPrivacy::new(k.unwrap_or(1), l.unwrap_or(1));
+
+        let mut provenance = Provenance {
+            target: dataset.id.clone(),
+            activity: "deidentification".to_string(),
+            recorded: Utc::now().to_rfc3339(),
+            steps: Vec::new(),
+        };
+        let mut epsilon = 0.0;
+        let mut t_threshold = None;
+
+        for (order, step) in self.steps.iter().enumerate() {
+            match step {
+                DeidentificationStep::SafeHarbor => privacy.apply_safe_harbor_deidentification(dataset)?,
+                DeidentificationStep::DateShift { max_days, key } => shift_dates(dataset, *max_days, key),
+                DeidentificationStep::KAnonymity { .. } => privacy.apply_k_anonymity(dataset)?,
+                DeidentificationStep::LDiversity { .. } => privacy.apply_l_diversity(dataset)?,
+                DeidentificationStep::TCloseness { t } => {
+                    privacy.apply_t_closeness(dataset, *t)?;
+                    t_threshold = Some(*t);
+                }
+                DeidentificationStep::DifferentialPrivacy { epsilon: step_epsilon } => {
+                    privacy.apply_differential_privacy(dataset, *step_epsilon)?;
+                    epsilon = *step_epsilon;
+                }
+            }
+            provenance.steps.push(ProvenanceStep {
+                order: order as u32,
+                step: step.name().to_string(),
+                parameters: step.parameters(),
+                completed_at: Utc::now().to_rfc3339(),
+            });
+        }
+
+        let step_names: Vec<&str> = self.steps.iter().map(|s| s.name()).collect();
+        dataset.metadata.insert("deidentification".to_string(), step_names.join(","));
+        dataset.updated_at = Utc::now().to_rfc3339();
+
+        // Measured properties, with the guarantees the enforcing steps declared
+        let mut metrics = PrivacyMetrics::calculate_for_dataset(dataset);
+        metrics.differential_privacy_epsilon = epsilon;
+        if let Some(l) = l {
+            metrics.l_diversity_level = metrics.l_diversity_level.max(l);
+        }
+        if let Some(t) = t_threshold {
+            metrics.t_closeness_threshold = t;
+        }
+        Ok(DeidentificationReport { provenance, metrics })
+    }
+}
+
+// Keyed offset in [-max_days, max_days]; stable per patient so it can be re-derived
+fn date_offset(patient_id: &str, max_days: u32, key: &str) -> i64 {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    hasher.update(b":");
+    hasher.update(patient_id.as_bytes());
+    let digest = hasher.finalize();
+    let value = u64::from_be_bytes([digest[0], digest[1], digest[2], digest[3], digest[4], digest[5], digest[6], digest[7]]);
+    (value % (2 * max_days as u64 + 1)) as i64 - max_days as i64
+}
+
+// Shifts the YYYY-MM-DD prefix and keeps any time part. Dates that cannot be parsed
+// are dropped rather than left unshifted; year-only dates are already generalized.
+fn shift_date(date: &mut Option<String>, days: i64) {
+    let value = match date {
+        Some(value) => value,
+        None => return,
+    };
+    if value.len() == 4 {
+        return;
+    }
+    let shifted = value
+        .get(..10)
+        .and_then(|prefix| NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok())
+        .and_then(|day| day.checked_add_signed(Duration::days(days)))
+        .map(|day| format!("{}{}", day.format("%Y-%m-%d"), &value[10..]));
+    *date = shifted;
+}
+
+fn shift_dates(dataset: &mut MedicalDataset, max_days: u32, key: &str) {
+    let offset_for = |reference: &Option<String>| {
+        let patient_id = reference.as_deref().map(|r| r.strip_prefix("Patient/").unwrap_or(r)).unwrap_or("");
+        date_offset(patient_id, max_days, key)
+    };
+
+    for patient in &mut dataset.patients {
+        let days = date_offset(&patient.id, max_days, key);
+        shift_date(&mut patient.birth_date, days);
+        shift_date(&mut patient.deceased_datetime, days);
+    }
+    for observation in &mut dataset.observations {
+        let days = offset_for(&observation.subject.reference);
+        shift_date(&mut observation.effective_datetime, days);
+        shift_date(&mut observation.issued, days);
+    }
+    for condition in &mut dataset.conditions {
+        let days = offset_for(&condition.subject.reference);
+        shift_date(&mut condition.recorded_date, days);
+        if let Some(ConditionOnset::DateTime(onset)) = &mut condition.onset {
+            let mut shifted = Some(onset.clone());
+            shift_date(&mut shifted, days);
+            match shifted {
+                Some(value) => *onset = value,
+                None => condition.onset = None,
+            }
+        }
+        if let Some(ConditionAbatement::DateTime(abatement)) = &mut condition.abatement {
+            let mut shifted = Some(abatement.clone());
+            shift_date(&mut shifted, days);
+            match shifted {
+                Some(value) => *abatement = value,
+                None => condition.abatement = None,
+            }
+        }
+        for change in &mut condition.status_history {
+            let mut shifted = Some(change.effective_date.clone());
+            shift_date(&mut shifted, days);
+            change.effective_date = shifted.unwrap_or_default();
+        }
+    }
+    for report in &mut dataset.diagnostic_reports {
+        let days = offset_for(&report.subject.reference);
+        shift_date(&mut report.effective_datetime, days);
+        shift_date(&mut report.issued, days);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_validation_and_provenance() {
+        assert!(DeidentificationPipeline::builder().build().is_err());
+        assert!(DeidentificationPipeline::builder().safe_harbor().date_shift(30, "key").build().is_err());
+        assert!(DeidentificationPipeline::builder().l_diversity(2).k_anonymity(3).build().is_err());
+        assert!(DeidentificationPipeline::builder().k_anonymity(3).k_anonymity(5).build().is_err());
+        assert!(DeidentificationPipeline::builder().differential_privacy(0.0).build().is_err());
+
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        let mut patient = Patient::new("p1".to_string());
+        patient.birth_date = Some("1980-06-15".to_string());
+        dataset.patients.push(patient);
+        let mut condition = Condition::new("c1".to_string(), create_reference("Patient/p1", None));
+        condition.recorded_date = Some("2020-03-01".to_string());
+        condition.onset = Some(ConditionOnset::DateTime("2020-02-20T08:00:00Z".to_string()));
+        dataset.conditions.push(condition);
+
+        let pipeline = DeidentificationPipeline::builder()
+            .date_shift(30, "secret")
+            .k_anonymity(2)
+            .l_diversity(2)
+            .differential_privacy(1.0)
+            .build()
+            .unwrap();
+        let report = pipeline.run(&mut dataset).unwrap();
+
+        let steps: Vec<&str> = report.provenance.steps.iter().map(|s| s.step.as_str()).collect();
+        assert_eq!(steps, vec!["date_shift", "k_anonymity", "l_diversity", "differential_privacy"]);
+        assert!(report.provenance.steps.iter().all(|s| !s.parameters.contains("secret")));
+        assert_eq!(report.metrics.differential_privacy_epsilon, 1.0);
+        assert_eq!(report.metrics.l_diversity_level, 2);
+
+        // Intervals between one patient's events survive the shift
+        let days = date_offset("p1", 30, "secret");
+        let recorded = NaiveDate::parse_from_str(dataset.conditions[0].recorded_date.as_deref().unwrap(), "%Y-%m-%d").unwrap();
+        assert_eq!(recorded, NaiveDate::from_ymd_opt(2020, 3, 1).unwrap() + Duration::days(days));
+        match &dataset.conditions[0].onset {
+            Some(ConditionOnset::DateTime(onset)) => assert!(onset.ends_with("T08:00:00Z")),
+            other => panic!("onset was not shifted in place: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// Interpretation flags from reference ranges. A numeric observation is compared
+// against the reference ranges that apply to the patient: a range restricted by
+// `applies_to` to one sex only applies to patients of that sex, and a range with an
+// `age` only applies when the patient's age on the observation date falls inside it.
+// Ranges typed "critical" (or "panic") give the critical flags LL and HH; all other
+// ranges are the normal range, giving L, H or N. The flag is written to
+// `interpretation` using the HL7 v3 ObservationInterpretation codes, replacing any
+// earlier flag from that system and leaving flags from other systems alone.
+
+use crate::linkage::patient_id_from_reference;
+use crate::mortality::parse_day;
+use crate::*;
+use chrono::NaiveDate;
+
+pub const INTERPRETATION_SYSTEM: &str = "http://terminology.hl7.org/CodeSystem/v3-ObservationInterpretation";
+
+// SNOMED CT sex codes used in applies_to, alongside the plain FHIR gender codes
+const SNOMED_MALE: &str = "248153007";
+const SNOMED_FEMALE: &str = "248152002";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterpretationFlag {
+    Normal,
+    Low,
+    High,
+    CriticalLow,
+    CriticalHigh,
+}
+
+impl InterpretationFlag {
+    pub const ALL: [InterpretationFlag; 5] = [
+        InterpretationFlag::Normal,
+        InterpretationFlag::Low,
+        InterpretationFlag::High,
+        InterpretationFlag::CriticalLow,
+        InterpretationFlag::CriticalHigh,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            InterpretationFlag::Normal => "N",
+            InterpretationFlag::Low => "L",
+            InterpretationFlag::High => "H",
+            InterpretationFlag::CriticalLow => "LL",
+            InterpretationFlag::CriticalHigh => "HH",
+        }
+    }
+
+    pub fn display(&self) -> &'static str {
+        match self {
+            InterpretationFlag::Normal => "Normal",
+            InterpretationFlag::Low => "Low",
+            InterpretationFlag::High => "High",
+            InterpretationFlag::CriticalLow => "Critical low",
+            InterpretationFlag::CriticalHigh => "Critical high",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.code() == code)
+    }
+
+    // The flag carried by a set of interpretations, if any is from the v3 system
+    pub fn from_concepts(concepts: &[CodeableConcept]) -> Option<Self> {
+        concepts
+            .iter()
+            .flat_map(|c| c.coding.iter())
+            .filter(|coding| coding.system.as_deref() == Some(INTERPRETATION_SYSTEM))
+            .find_map(|coding| coding.code.as_deref().and_then(Self::from_code))
+    }
+
+    pub fn to_concept(&self) -> CodeableConcept {
+        create_codeable_concept(create_coding(INTERPRETATION_SYSTEM, self.code(), self.display()), None)
+    }
+}
+
+fn is_critical(range: &ReferenceRange) -> bool {
+    range.range_type.as_ref().is_some_and(|t| {
+        t.coding.iter().any(|c| matches!(c.code.as_deref(), Some("critical") | Some("panic")))
+    })
+}
+
+fn sex_of(concept: &CodeableConcept) -> Option<Gender> {
+    concept.coding.iter().find_map(|coding| match coding.code.as_deref() {
+        Some("male") | Some(SNOMED_MALE) => Some(Gender::Male),
+        Some("female") | Some(SNOMED_FEMALE) => Some(Gender::Female),
+        _ => None,
+    })
+}
+
+// Age in years, with the fraction, so neonatal and paediatric ranges can be told apart
+fn age_in_years(patient: &Patient, on: NaiveDate) -> Option<f64> {
+    let birth_date = patient.birth_date.as_deref()?;
+    let birth = match birth_date.len() {
+        4 => birth_date.parse().ok().and_then(|year| NaiveDate::from_ymd_opt(year, 1, 1))?,
+        _ => parse_day(birth_date)?,
+    };
+    Some((on - birth).num_days() as f64 / 365.25)
+}
+
+// Age bounds in years; UCUM "mo", "wk" and "d" are converted
+fn age_bound(quantity: &Option<Quantity>) -> Option<f64> {
+    let quantity = quantity.as_ref()?;
+    let value = quantity.value?;
+    Some(match quantity.code.as_deref().or(quantity.unit.as_deref()) {
+        Some("mo") => value / 12.0,
+        Some("wk") => value * 7.0 / 365.25,
+        Some("d") => value / 365.25,
+        _ => value,
+    })
+}
+
+pub fn range_applies(range: &ReferenceRange, patient: &Patient, on: NaiveDate) -> bool {
+    let sexes: Vec<Gender> = range.applies_to.iter().filter_map(sex_of).collect();
+    if !sexes.is_empty() {
+        let matches = match &patient.gender {
+            Some(Gender::Male) => sexes.iter().any(|s| matches!(s, Gender::Male)),
+            Some(Gender::Female) => sexes.iter().any(|s| matches!(s, Gender::Female)),
+            _ => false,
+        };
+        if !matches {
+            return false;
+        }
+    }
+    if let Some(age_range) = &range.age {
+        let age = match age_in_years(patient, on) {
+            Some(age) => age,
+            None => return false,
+        };
+        if age_bound(&age_range.low).is_some_and(|low| age < low) || age_bound(&age_range.high).is_some_and(|high| age >= high) {
+            return false;
+        }
+    }
+    true
+}
+
+fn below(value: f64, range: &ReferenceRange) -> bool {
+    range.low.as_ref().and_then(|q| q.value).is_some_and(|low| value < low)
+}
+
+fn above(value: f64, range: &ReferenceRange) -> bool {
+    range.high.as_ref().and_then(|q| q.value).is_some_and(|high| value > high)
+}
+
+// None when no normal or critical range applies to the patient
+pub fn evaluate_value(value: f64, ranges: &[ReferenceRange], patient: &Patient, on: NaiveDate) -> Option<InterpretationFlag> {
+    let applicable: Vec<&ReferenceRange> = ranges.iter().filter(|r| range_applies(r, patient, on)).collect();
+    let critical = applicable.iter().find(|r| is_critical(r));
+    let normal = applicable.iter().find(|r| !is_critical(r));
+    if critical.is_some_and(|r| below(value, r)) {
+        return Some(InterpretationFlag::CriticalLow);
+    }
+    if critical.is_some_and(|r| above(value, r)) {
+        return Some(InterpretationFlag::CriticalHigh);
+    }
+    match normal {
+        Some(r) if below(value, r) => Some(InterpretationFlag::Low),
+        Some(r) if above(value, r) => Some(InterpretationFlag::High),
+        Some(_) => Some(InterpretationFlag::Normal),
+        None if critical.is_some() => Some(InterpretationFlag::Normal),
+        None => None,
+    }
+}
+
+fn set_flag(interpretation: &mut Vec<CodeableConcept>, flag: Option<InterpretationFlag>) {
+    let flag = match flag {
+        Some(flag) => flag,
+        None => return,
+    };
+    interpretation.retain(|c| !c.coding.iter().any(|coding| coding.system.as_deref() == Some(INTERPRETATION_SYSTEM)));
+    interpretation.push(flag.to_concept());
+}
+
+impl Observation {
+    pub fn interpretation_flag(&self) -> Option<InterpretationFlag> {
+        InterpretationFlag::from_concepts(&self.interpretation)
+    }
+
+    // Flags the value and every component against their own reference ranges. The
+    // patient's age is taken on the effective date, or `reference_date` without one.
+    // Returns the flag of the observation's own value.
+    pub fn evaluate_interpretation(&mut self, patient: &Patient, reference_date: NaiveDate) -> Option<InterpretationFlag> {
+        let on = self.effective_datetime.as_deref().and_then(parse_day).unwrap_or(reference_date);
+        for component in &mut self.component {
+            let flag = component
+                .value
+                .as_ref()
+                .and_then(|v| v.as_f64())
+                .and_then(|value| evaluate_value(value, &component.reference_range, patient, on));
+            set_flag(&mut component.interpretation, flag);
+        }
+        let flag = self
+            .value
+            .as_ref()
+            .and_then(|v| v.as_f64())
+            .and_then(|value| evaluate_value(value, &self.reference_range, patient, on));
+        set_flag(&mut self.interpretation, flag);
+        flag
+    }
+}
+
+impl ObservationComponent {
+    pub fn interpretation_flag(&self) -> Option<InterpretationFlag> {
+        InterpretationFlag::from_concepts(&self.interpretation)
+    }
+}
+
+impl MedicalDataset {
+    // Evaluates every observation whose subject is a patient in the dataset; returns
+    // how many observations were flagged abnormal
+    pub fn apply_interpretations(&mut self, reference_date: NaiveDate) -> usize {
+        let patients = &self.patients;
+        let mut abnormal = 0;
+        for observation in &mut self.observations {
+            let patient = match observation.subject.reference.as_deref().map(patient_id_from_reference) {
+                Some(id) => patients.iter().find(|p| p.id == id),
+                None => None,
+            };
+            if let Some(patient) = patient {
+                let flag = observation.evaluate_interpretation(patient, reference_date);
+                if flag.is_some_and(|f| f != InterpretationFlag::Normal) {
+                    abnormal += 1;
+                }
+            }
+        }
+        if abnormal > 0 {
+            self.updated_at = Utc::now().to_rfc3339();
+        }
+        abnormal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(low: f64, high: f64, range_type: Option<&str>, sex: Option<&str>, age: Option<(f64, f64)>) -> ReferenceRange {
+        ReferenceRange {
+            low: Some(create_quantity(low, "g/dL", None, None)),
+            high: Some(create_quantity(high, "g/dL", None, None)),
+            range_type: range_type.map(|t| create_codeable_concept(create_coding("http://terminology.hl7.org/CodeSystem/referencerange-meaning", t, t), None)),
+            applies_to: sex.map(|s| create_codeable_concept(create_coding("http://hl7.org/fhir/administrative-gender", s, s), None)).into_iter().collect(),
+            age: age.map(|(low, high)| Range {
+                low: Some(create_quantity(low, "a", None, Some("a"))),
+                high: Some(create_quantity(high, "a", None, Some("a"))),
+            }),
+            text: None,
+        }
+    }
+
+    #[test]
+    fn test_hemoglobin_flags_follow_sex_and_age() {
+        let mut woman = Patient::new("p1".to_string());
+        woman.gender = Some(Gender::Female);
+        woman.set_birth_date("1980-05-01".to_string());
+        let mut child = Patient::new("p2".to_string());
+        child.gender = Some(Gender::Male);
+        child.set_birth_date("2020-01-01".to_string());
+
+        let ranges = vec![
+            range(13.5, 17.5, Some("normal"), Some("male"), Some((18.0, 150.0))),
+            range(12.0, 15.5, Some("normal"), Some("female"), Some((18.0, 150.0))),
+            range(11.0, 14.5, Some("normal"), None, Some((1.0, 18.0))),
+            range(7.0, 20.0, Some("critical"), None, None),
+        ];
+        let on = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        // 13.0 is normal for an adult woman but would be low for an adult man
+        assert_eq!(evaluate_value(13.0, &ranges, &woman, on), Some(InterpretationFlag::Normal));
+        assert_eq!(evaluate_value(11.5, &ranges, &woman, on), Some(InterpretationFlag::Low));
+        assert_eq!(evaluate_value(6.5, &ranges, &woman, on), Some(InterpretationFlag::CriticalLow));
+        assert_eq!(evaluate_value(15.0, &ranges, &child, on), Some(InterpretationFlag::High));
+
+        let mut observation = Observation::new("o1".to_string(), CodeableConcept::loinc("718-7").unwrap(), create_reference("Patient/p1", None));
+        observation.set_value(ObservationValue::Quantity(create_quantity(16.0, "g/dL", None, None)));
+        observation.reference_range = ranges;
+        let mut dataset = MedicalDataset::new("ds".to_string(), "test".to_string(), String::new());
+        dataset.patients.push(woman);
+        dataset.observations.push(observation);
+        assert_eq!(dataset.apply_interpretations(on), 1);
+        assert_eq!(dataset.observations[0].interpretation_flag(), Some(InterpretationFlag::High));
+        assert_eq!(dataset.observations[0].interpretation.len(), 1);
+    }
+}
@@ -7,6 +7,22 @@ use sha2::{Digest, Sha256};
 pub mod rare_diseases;
 pub mod validation;
 pub mod privacy;
+pub mod panels;
+pub mod deidentification;
+pub mod waveform;
+pub mod attachments;
+pub mod linkage;
+pub mod mortality;
+pub mod practitioners;
+pub mod terminology;
+pub mod interpretation;
+pub mod condition_status;
+pub mod synthetic;
+pub mod registry_statistics;
+pub mod suppression;
+pub mod quarantine;
+pub mod config_file;
+pub mod lineage;
 
 // Core patient data structure
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -19,6 +35,8 @@ pub struct Patient {
     pub address: Vec<Address>,
     pub contact: Vec<ContactPoint>,
     pub deceased: Option<bool>,
+    // FHIR deceasedDateTime; implies deceased
+    pub deceased_datetime: Option<String>,
     pub marital_status: Option<CodeableConcept>,
     pub communication: Vec<Communication>,
     pub general_practitioner: Vec<Reference>,
@@ -202,8 +220,8 @@ pub struct DiagnosticReportMedia {
 pub struct Condition {
     pub id: String,
     pub identifier: Vec<Identifier>,
-    pub clinical_status: Option<CodeableConcept>,
-    pub verification_status: Option<CodeableConcept>,
+    pub clinical_status: Option<condition_status::ClinicalStatus>,
+    pub verification_status: Option<condition_status::VerificationStatus>,
     pub category: Vec<CodeableConcept>,
     pub severity: Option<CodeableConcept>,
     pub code: Option<CodeableConcept>,
@@ -218,6 +236,8 @@ pub struct Condition {
     pub stage: Vec<ConditionStage>,
     pub evidence: Vec<ConditionEvidence>,
     pub note: Vec<Annotation>,
+    // Every status change, oldest first
+    pub status_history: Vec<condition_status::ConditionStatusChange>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -259,7 +279,7 @@ pub struct CodeableConcept {
     pub text: Option<String>,
 }
 
-#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Coding {
     pub system: Option<String>,
     pub version: Option<String>,
@@ -361,6 +381,7 @@ impl Patient {
             address: Vec::new(),
             contact: Vec::new(),
             deceased: None,
+            deceased_datetime: None,
             marital_status: None,
             communication: Vec::new(),
             general_practitioner: Vec::new(),
@@ -385,6 +406,11 @@ impl Patient {
         self.birth_date = Some(birth_date);
     }
 
+    pub fn set_deceased(&mut self, deceased_datetime: Option<String>) {
+        self.deceased = Some(true);
+        self.deceased_datetime = deceased_datetime;
+    }
+
     pub fn add_address(&mut self, address: Address) {
         self.address.push(address);
     }
@@ -409,14 +435,26 @@ impl Patient {
             }
         }
 
+        if self.deceased_datetime.is_some() {
+            if self.date_of_death().is_none() {
+                return Err("Invalid deceased date format".to_string());
+            }
+            if self.deceased == Some(false) {
+                return Err("Patient has a date of death but is marked as not deceased".to_string());
+            }
+            if let (Some(birth), Some(death)) = (self.birth_date.as_deref().and_then(mortality::parse_day), self.date_of_death()) {
+                if death < birth {
+                    return Err("Date of death precedes birth date".to_string());
+                }
+            }
+        }
+
         Ok(())
     }
 
     pub fn anonymize(&mut self) -> String {
         // Generate a hash-based anonymous ID
-        let mut hasher = Sha256::new();
-        hasher.update(&self.id);
-        let anonymous_id = format!("{:x}", hasher.finalize())[..16].to_string();
+        let anonymous_id = anonymous_patient_id(&self.id);
 
         // Clear identifying information
         self.id = anonymous_id.clone();
@@ -500,8 +538,57 @@ impl Observation {
             return Err("Observation subject is required".to_string());
         }
 
+        for waveform in self.sampled_data() {
+            waveform.validate()?;
+        }
+
         Ok(())
     }
+
+    pub fn add_component(&mut self, component: ObservationComponent) {
+        self.component.push(component);
+    }
+
+    pub fn component_by_loinc(&self, loinc_code: &str) -> Option<&ObservationComponent> {
+        self.component.iter().find(|c| c.code.has_loinc_code(loinc_code))
+    }
+
+    pub fn component_value(&self, loinc_code: &str) -> Option<f64> {
+        self.component_by_loinc(loinc_code)
+            .and_then(|c| c.value.as_ref())
+            .and_then(|v| v.as_f64())
+    }
+
+    // Blood pressure panels (LOINC 85354-9) carry systolic and diastolic as components
+    pub fn systolic(&self) -> Option<f64> {
+        self.component_value(panels::LOINC_SYSTOLIC_BP)
+    }
+
+    pub fn diastolic(&self) -> Option<f64> {
+        self.component_value(panels::LOINC_DIASTOLIC_BP)
+    }
+}
+
+impl ObservationValue {
+    // Numeric value usable as a model feature, if the value has one
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ObservationValue::Quantity(quantity) => quantity.value,
+            ObservationValue::Integer(value) => Some(*value as f64),
+            ObservationValue::Boolean(value) => Some(if *value { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+}
+
+impl CodeableConcept {
+    // Codings without a system are accepted, as many lab feeds omit it
+    pub fn has_loinc_code(&self, loinc_code: &str) -> bool {
+        self.coding.iter().any(|coding| {
+            coding.code.as_deref() == Some(loinc_code)
+                && coding.system.as_deref().is_none_or(|s| s == panels::LOINC_SYSTEM)
+        })
+    }
 }
 
 impl Condition {
@@ -525,6 +612,7 @@ impl Condition {
             stage: Vec::new(),
             evidence: Vec::new(),
             note: Vec::new(),
+            status_history: Vec::new(),
         }
     }
 
@@ -532,14 +620,6 @@ impl Condition {
         self.code = Some(code);
     }
 
-    pub fn set_clinical_status(&mut self, status: CodeableConcept) {
-        self.clinical_status = Some(status);
-    }
-
-    pub fn set_verification_status(&mut self, status: CodeableConcept) {
-        self.verification_status = Some(status);
-    }
-
     pub fn add_category(&mut self, category: CodeableConcept) {
         self.category.push(category);
     }
@@ -569,7 +649,7 @@ impl Condition {
             return Err("Condition subject is required".to_string());
         }
 
-        Ok(())
+        self.validate_status()
     }
 }
 
@@ -591,6 +671,13 @@ pub fn create_codeable_concept(coding: Coding, text: Option<&str>) -> CodeableCo
     }
 }
 
+// Pseudonym used for a patient id when anonymizing
+pub fn anonymous_patient_id(id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(id);
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
 pub fn create_reference(reference: &str, display: Option<&str>) -> Reference {
     Reference {
         reference: Some(reference.to_string()),
@@ -620,10 +707,14 @@ pub struct MedicalDataset {
     pub observations: Vec<Observation>,
     pub conditions: Vec<Condition>,
     pub diagnostic_reports: Vec<DiagnosticReport>,
+    pub practitioners: Vec<practitioners::Practitioner>,
+    pub organizations: Vec<practitioners::Organization>,
     pub created_at: String,
     pub updated_at: String,
     pub version: String,
     pub metadata: HashMap<String, String>,
+    // Records refused by add_patient, add_observation or add_condition, kept for review
+    pub quarantine: Vec<quarantine::QuarantineEntry>,
 }
 
 impl MedicalDataset {
@@ -637,32 +728,28 @@ impl MedicalDataset {
             observations: Vec::new(),
             conditions: Vec::new(),
             diagnostic_reports: Vec::new(),
+            practitioners: Vec::new(),
+            organizations: Vec::new(),
             created_at: now.clone(),
             updated_at: now,
             version: "1.0.0".to_string(),
             metadata: HashMap::new(),
+            quarantine: Vec::new(),
         }
     }
 
+    // Records that fail validation or consistency checks are quarantined, not dropped;
+    // the error names the quarantine entry
     pub fn add_patient(&mut self, patient: Patient) -> Result<(), String> {
-        patient.validate()?;
-        self.patients.push(patient);
-        self.updated_at = Utc::now().to_rfc3339();
-        Ok(())
+        self.admit_or_quarantine(quarantine::QuarantinedRecord::Patient(patient))
     }
 
     pub fn add_observation(&mut self, observation: Observation) -> Result<(), String> {
-        observation.validate()?;
-        self.observations.push(observation);
-        self.updated_at = Utc::now().to_rfc3339();
-        Ok(())
+        self.admit_or_quarantine(quarantine::QuarantinedRecord::Observation(observation))
     }
 
     pub fn add_condition(&mut self, condition: Condition) -> Result<(), String> {
-        condition.validate()?;
-        self.conditions.push(condition);
-        self.updated_at = Utc::now().to_rfc3339();
-        Ok(())
+        self.admit_or_quarantine(quarantine::QuarantinedRecord::Condition(condition))
     }
 
     pub fn add_diagnostic_report(&mut self, report: DiagnosticReport) {
@@ -692,21 +779,29 @@ impl MedicalDataset {
             id_mapping.insert(original_id, anonymous_id);
         }
 
-        // Update references in observations
-        for observation in &mut self.observations {
-            if let Some(ref mut subject_ref) = observation.subject.reference {
-                if let Some(anonymous_id) = id_mapping.get(subject_ref) {
-                    *subject_ref = format!("Patient/{}", anonymous_id);
-                }
+        // Update patient references; ids of records no longer in the dataset (merged
+        // away) are hashed the same way so no raw id survives
+        let pseudonymize = |reference: &mut Option<String>| {
+            if let Some(subject_ref) = reference {
+                let original_id = linkage::patient_id_from_reference(subject_ref);
+                let anonymous_id = id_mapping.get(original_id).cloned().unwrap_or_else(|| anonymous_patient_id(original_id));
+                *subject_ref = format!("Patient/{}", anonymous_id);
             }
+        };
+        for observation in &mut self.observations {
+            pseudonymize(&mut observation.subject.reference);
         }
-
-        // Update references in conditions
         for condition in &mut self.conditions {
-            if let Some(ref mut subject_ref) = condition.subject.reference {
-                if let Some(anonymous_id) = id_mapping.get(subject_ref) {
-                    *subject_ref = format!("Patient/{}", anonymous_id);
-                }
+            pseudonymize(&mut condition.subject.reference);
+        }
+        for report in &mut self.diagnostic_reports {
+            pseudonymize(&mut report.subject.reference);
+        }
+        for patient in &mut self.patients {
+            for link in &mut patient.link {
+                pseudonymize(&mut link.other.reference);
+                link.other.identifier = None;
+                link.other.display = None;
             }
         }
 
@@ -730,19 +825,32 @@ impl MedicalDataset {
             condition.validate()?;
         }
 
+        for practitioner in &self.practitioners {
+            practitioner.validate()?;
+        }
+        for organization in &self.organizations {
+            organization.validate()?;
+        }
+
         Ok(())
     }
 
+    // Counts below suppression::SMALL_CELL_THRESHOLD are reported as "<5"
     pub fn get_statistics(&self) -> HashMap<String, serde_json::Value> {
+        self.get_statistics_with_threshold(suppression::SMALL_CELL_THRESHOLD)
+    }
+
+    pub fn get_statistics_with_threshold(&self, threshold: u64) -> HashMap<String, serde_json::Value> {
+        use suppression::{suppress_count, suppress_distribution};
         let mut stats = HashMap::new();
         
-        stats.insert("patient_count".to_string(), serde_json::Value::Number(self.patients.len().into()));
-        stats.insert("observation_count".to_string(), serde_json::Value::Number(self.observations.len().into()));
-        stats.insert("condition_count".to_string(), serde_json::Value::Number(self.conditions.len().into()));
-        stats.insert("diagnostic_report_count".to_string(), serde_json::Value::Number(self.diagnostic_reports.len().into()));
+        stats.insert("patient_count".to_string(), suppress_count(self.patients.len() as u64, threshold));
+        stats.insert("observation_count".to_string(), suppress_count(self.observations.len() as u64, threshold));
+        stats.insert("condition_count".to_string(), suppress_count(self.conditions.len() as u64, threshold));
+        stats.insert("diagnostic_report_count".to_string(), suppress_count(self.diagnostic_reports.len() as u64, threshold));
         
         // Gender distribution
-        let mut gender_counts = HashMap::new();
+        let mut gender_counts: HashMap<String, u64> = HashMap::new();
         for patient in &self.patients {
             let gender_key = match &patient.gender {
                 Some(Gender::Male) => "male",
@@ -751,12 +859,12 @@ impl MedicalDataset {
                 Some(Gender::Unknown) => "unknown",
                 None => "not_specified",
             };
-            *gender_counts.entry(gender_key).or_insert(0) += 1;
+            *gender_counts.entry(gender_key.to_string()).or_insert(0) += 1;
         }
-        stats.insert("gender_distribution".to_string(), serde_json::to_value(gender_counts).unwrap());
+        stats.insert("gender_distribution".to_string(), serde_json::Value::Object(suppress_distribution(&gender_counts, threshold)));
 
         // Age distribution (if birth dates are available)
-        let mut age_groups = HashMap::new();
+        let mut age_groups: HashMap<String, u64> = HashMap::new();
         let current_year = Utc::now().year();
         for patient in &self.patients {
             if let Some(ref birth_date) = patient.birth_date {
@@ -769,11 +877,11 @@ impl MedicalDataset {
                         55..=74 => "55-74",
                         _ => "75+",
                     };
-                    *age_groups.entry(age_group).or_insert(0) += 1;
+                    *age_groups.entry(age_group.to_string()).or_insert(0) += 1;
                 }
             }
         }
-        stats.insert("age_distribution".to_string(), serde_json::to_value(age_groups).unwrap());
+        stats.insert("age_distribution".to_string(), serde_json::Value::Object(suppress_distribution(&age_groups, threshold)));
 
         stats
     }
@@ -0,0 +1,291 @@
+// Observation lineage: which observations were computed from or group which others.
+// `derived_from` (a BMI computed from a height and a weight) and `has_member` (a
+// panel grouping its results) both make one observation depend on others; this
+// module resolves those references within a dataset into a graph.
+//
+// The graph answers two questions. When a source is corrected, which derived values
+// are now stale, and in what order can they be recomputed (inputs first)? And when
+// a model learns to predict some observation, which features quietly contain it? A
+// BMI feature leaks a weight label, so `leakage_exclusions` returns the label's
+// observations together with everything derived from them, transitively.
+//
+// Only references to observations held in the dataset are followed ("Observation/id"
+// or a bare id); other resource types are ignored and dangling observation references
+// are reported. Cycles are data errors; they are detected and reported rather than
+// followed forever, and recomputation refuses to run while any remain.
+
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+pub const LOINC_BODY_WEIGHT: &str = "29463-7";
+pub const LOINC_BODY_HEIGHT: &str = "8302-2";
+pub const LOINC_BMI: &str = "39156-5";
+pub const LOINC_BODY_SURFACE_AREA: &str = "8277-6";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LineageKind {
+    DerivedFrom,
+    HasMember,
+}
+
+// A value computed from other observations, identified by the LOINC codes involved
+pub struct Derivation {
+    pub loinc_code: &'static str,
+    pub unit: &'static str,
+    // Input codes with the unit each must be reported in, in the order `compute` takes them
+    pub inputs: &'static [(&'static str, &'static str)],
+    pub compute: fn(&[f64]) -> Option<f64>,
+}
+
+pub static DERIVATIONS: &[Derivation] = &[
+    Derivation {
+        loinc_code: LOINC_BMI,
+        unit: "kg/m2",
+        inputs: &[(LOINC_BODY_WEIGHT, "kg"), (LOINC_BODY_HEIGHT, "cm")],
+        compute: |v| (v[1] > 0.0).then(|| v[0] / (v[1] / 100.0).powi(2)),
+    },
+    // Mosteller formula
+    Derivation {
+        loinc_code: LOINC_BODY_SURFACE_AREA,
+        unit: "m2",
+        inputs: &[(LOINC_BODY_WEIGHT, "kg"), (LOINC_BODY_HEIGHT, "cm")],
+        compute: |v| (v[0] >= 0.0 && v[1] >= 0.0).then(|| (v[0] * v[1] / 3600.0).sqrt()),
+    },
+];
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ObservationLineage {
+    // Observation id -> the observations it depends on, sorted
+    pub inputs: BTreeMap<String, Vec<(String, LineageKind)>>,
+    // (observation id, reference) pairs naming an observation the dataset lacks
+    pub unresolved: Vec<(String, String)>,
+}
+
+pub fn derivation_for(observation: &Observation) -> Option<&'static Derivation> {
+    DERIVATIONS.iter().find(|d| observation.code.has_loinc_code(d.loinc_code))
+}
+
+fn observation_id(reference: &Reference) -> Option<&str> {
+    let reference = reference.reference.as_deref()?;
+    match reference.split_once('/') {
+        Some(("Observation", id)) => Some(id),
+        Some(_) => None,
+        None => Some(reference),
+    }
+}
+
+impl ObservationLineage {
+    pub fn build(observations: &[Observation]) -> Self {
+        let ids: HashSet<&str> = observations.iter().map(|o| o.id.as_str()).collect();
+        let mut lineage = ObservationLineage::default();
+        for observation in observations {
+            let references = observation
+                .derived_from
+                .iter()
+                .map(|r| (r, LineageKind::DerivedFrom))
+                .chain(observation.has_member.iter().map(|r| (r, LineageKind::HasMember)));
+            let mut inputs = Vec::new();
+            for (reference, kind) in references {
+                match observation_id(reference) {
+                    Some(id) if ids.contains(id) => inputs.push((id.to_string(), kind)),
+                    // Unmatched bare ids may name some other resource type
+                    Some(_) if reference.reference.as_deref().is_some_and(|r| r.starts_with("Observation/")) => {
+                        lineage.unresolved.push((observation.id.clone(), reference.reference.clone().unwrap_or_default()))
+                    }
+                    _ => {}
+                }
+            }
+            inputs.sort();
+            inputs.dedup();
+            lineage.inputs.insert(observation.id.clone(), inputs);
+        }
+        lineage
+    }
+
+    pub fn inputs_of(&self, id: &str) -> &[(String, LineageKind)] {
+        self.inputs.get(id).map_or(&[], |inputs| inputs.as_slice())
+    }
+
+    // Every observation `id` depends on, directly or not
+    pub fn ancestors(&self, id: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(current) = stack.pop() {
+            for (input, _) in self.inputs_of(&current) {
+                if found.insert(input.clone()) {
+                    stack.push(input.clone());
+                }
+            }
+        }
+        found.remove(id);
+        found
+    }
+
+    // Every observation that depends on `id`, directly or not
+    pub fn descendants(&self, id: &str) -> BTreeSet<String> {
+        self.inputs.keys().filter(|candidate| *candidate != id && self.ancestors(candidate).contains(id)).cloned().collect()
+    }
+
+    // Each cycle once, as the ids along it starting from its smallest id
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        fn visit(
+            lineage: &ObservationLineage,
+            id: &str,
+            path: &mut Vec<String>,
+            done: &mut HashSet<String>,
+            cycles: &mut BTreeSet<Vec<String>>,
+        ) {
+            if let Some(start) = path.iter().position(|p| p == id) {
+                let mut cycle = path[start..].to_vec();
+                let smallest = cycle.iter().enumerate().min_by(|a, b| a.1.cmp(b.1)).map_or(0, |(i, _)| i);
+                cycle.rotate_left(smallest);
+                cycles.insert(cycle);
+                return;
+            }
+            if done.contains(id) {
+                return;
+            }
+            path.push(id.to_string());
+            for (input, _) in lineage.inputs_of(id) {
+                visit(lineage, input, path, done, cycles);
+            }
+            path.pop();
+            done.insert(id.to_string());
+        }
+
+        let mut cycles = BTreeSet::new();
+        let mut done = HashSet::new();
+        for id in self.inputs.keys() {
+            visit(self, id, &mut Vec::new(), &mut done, &mut cycles);
+        }
+        cycles.into_iter().collect()
+    }
+
+    // All observations, each after everything it depends on
+    pub fn recompute_order(&self) -> Result<Vec<String>, String> {
+        if let Some(cycle) = self.cycles().first() {
+            return Err(format!("Observation lineage has a cycle: {} -> {}", cycle.join(" -> "), cycle[0]));
+        }
+        let mut order = Vec::new();
+        let mut placed = HashSet::new();
+        while order.len() < self.inputs.len() {
+            for (id, inputs) in &self.inputs {
+                if !placed.contains(id) && inputs.iter().all(|(input, _)| placed.contains(input)) {
+                    placed.insert(id.clone());
+                    order.push(id.clone());
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+impl MedicalDataset {
+    pub fn observation_lineage(&self) -> ObservationLineage {
+        ObservationLineage::build(&self.observations)
+    }
+
+    // Recomputes every observation with a known derivation from its `derived_from`
+    // inputs, inputs first, and returns the ids whose value changed
+    pub fn recompute_derived_observations(&mut self) -> Result<Vec<String>, String> {
+        let lineage = self.observation_lineage();
+        let mut changed = Vec::new();
+        for id in lineage.recompute_order()? {
+            let index = match self.observations.iter().position(|o| o.id == id) {
+                Some(index) => index,
+                None => continue,
+            };
+            let derivation = match derivation_for(&self.observations[index]) {
+                Some(derivation) => derivation,
+                None => continue,
+            };
+            let sources: Vec<&Observation> = lineage
+                .inputs_of(&id)
+                .iter()
+                .filter(|(_, kind)| *kind == LineageKind::DerivedFrom)
+                .filter_map(|(input, _)| self.observations.iter().find(|o| &o.id == input))
+                .collect();
+
+            let mut values = Vec::new();
+            for (code, unit) in derivation.inputs {
+                let source = sources
+                    .iter()
+                    .find(|o| o.code.has_loinc_code(code))
+                    .ok_or_else(|| format!("Observation {} is not derived from a {} observation", id, code))?;
+                match &source.value {
+                    Some(ObservationValue::Quantity(Quantity { value: Some(value), unit: source_unit, .. }))
+                        if source_unit.as_deref() == Some(*unit) =>
+                    {
+                        values.push(*value)
+                    }
+                    _ => return Err(format!("Observation {} needs a value in {} to recompute {}", source.id, unit, id)),
+                }
+            }
+            let value = (derivation.compute)(&values).ok_or_else(|| format!("Inputs of observation {} are out of range", id))?;
+
+            let observation = &mut self.observations[index];
+            if observation.value.as_ref().and_then(|v| v.as_f64()) != Some(value) {
+                observation.value = Some(ObservationValue::Quantity(create_quantity(value, derivation.unit, Some("http://unitsofmeasure.org"), Some(derivation.unit))));
+                changed.push(id);
+            }
+        }
+        if !changed.is_empty() {
+            self.updated_at = Utc::now().to_rfc3339();
+        }
+        Ok(changed)
+    }
+
+    // Observations coded with any of `loinc_codes` plus every observation derived from
+    // them, which must stay out of the features of a model predicting those codes
+    pub fn leakage_exclusions(&self, loinc_codes: &[&str]) -> BTreeSet<String> {
+        let lineage = self.observation_lineage();
+        let mut excluded = BTreeSet::new();
+        for observation in self.observations.iter().filter(|o| loinc_codes.iter().any(|code| o.code.has_loinc_code(code))) {
+            excluded.insert(observation.id.clone());
+            excluded.extend(lineage.descendants(&observation.id));
+        }
+        excluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(id: &str, code: &str, value: f64, unit: &str, derived_from: &[&str]) -> Observation {
+        let mut observation = Observation::new(
+            id.to_string(),
+            create_codeable_concept(create_coding("http://loinc.org", code, code), None),
+            create_reference("Patient/p1", None),
+        );
+        observation.value = Some(ObservationValue::Quantity(create_quantity(value, unit, None, None)));
+        observation.derived_from = derived_from.iter().map(|r| create_reference(r, None)).collect();
+        observation
+    }
+
+    #[test]
+    fn test_lineage_recomputes_derived_values_and_finds_leaks() {
+        let mut dataset = MedicalDataset::new("d1".to_string(), "Site".to_string(), String::new());
+        dataset.observations = vec![
+            measurement("bmi", LOINC_BMI, 0.0, "kg/m2", &["Observation/weight", "height", "Observation/missing"]),
+            measurement("weight", LOINC_BODY_WEIGHT, 81.0, "kg", &[]),
+            measurement("height", LOINC_BODY_HEIGHT, 180.0, "cm", &[]),
+        ];
+
+        let lineage = dataset.observation_lineage();
+        assert_eq!(lineage.ancestors("bmi").into_iter().collect::<Vec<_>>(), vec!["height", "weight"]);
+        assert_eq!(lineage.unresolved, vec![("bmi".to_string(), "Observation/missing".to_string())]);
+        assert_eq!(dataset.recompute_derived_observations().unwrap(), vec!["bmi"]);
+        let bmi = dataset.observations[0].value.as_ref().and_then(|v| v.as_f64()).unwrap();
+        assert!((bmi - 25.0).abs() < 1e-9);
+        assert!(dataset.recompute_derived_observations().unwrap().is_empty());
+
+        // Predicting weight must not see the BMI computed from it
+        let excluded = dataset.leakage_exclusions(&[LOINC_BODY_WEIGHT]);
+        assert_eq!(excluded.into_iter().collect::<Vec<_>>(), vec!["bmi", "weight"]);
+
+        dataset.observations[1].derived_from = vec![create_reference("Observation/bmi", None)];
+        assert_eq!(dataset.observation_lineage().cycles(), vec![vec!["bmi".to_string(), "weight".to_string()]]);
+        assert!(dataset.recompute_derived_observations().unwrap_err().contains("cycle"));
+    }
+}
@@ -0,0 +1,234 @@
+// Patient links (FHIR Patient.link). Merging a duplicate record folds it into the
+// surviving record: every reference to the source is rewritten to the survivor, the
+// source's identifiers are kept on the survivor as "old", the source record is removed
+// and the survivor records a "replaces" link to it. Anonymization pseudonymizes link
+// targets like any other patient reference, and erasure follows "replaces" and
+// "refer" links, since both name the same person.
+
+use crate::*;
+use std::collections::HashSet;
+
+pub const LINK_REPLACED_BY: &str = "replaced-by";
+pub const LINK_REPLACES: &str = "replaces";
+pub const LINK_REFER: &str = "refer";
+pub const LINK_SEEALSO: &str = "seealso";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MergeReport {
+    pub survivor: String,
+    pub source: String,
+    pub references_rewritten: u32,
+    pub identifiers_moved: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ErasureReport {
+    pub patients_removed: Vec<String>,
+    pub observations_removed: u32,
+    pub conditions_removed: u32,
+    pub diagnostic_reports_removed: u32,
+}
+
+// Bare patient id from "Patient/<id>" or "<id>"
+pub fn patient_id_from_reference(reference: &str) -> &str {
+    reference.strip_prefix("Patient/").unwrap_or(reference)
+}
+
+fn points_to(reference: &Option<String>, patient_id: &str) -> bool {
+    reference.as_deref().is_some_and(|r| patient_id_from_reference(r) == patient_id)
+}
+
+impl PatientLink {
+    pub fn new(patient_id: &str, link_type: &str) -> Self {
+        PatientLink { other: create_reference(&format!("Patient/{}", patient_id), None), link_type: link_type.to_string() }
+    }
+
+    pub fn target_id(&self) -> Option<&str> {
+        self.other.reference.as_deref().map(patient_id_from_reference)
+    }
+}
+
+impl Patient {
+    // Ids of records folded into this one
+    pub fn replaced_ids(&self) -> Vec<String> {
+        self.link
+            .iter()
+            .filter(|l| l.link_type == LINK_REPLACES)
+            .filter_map(|l| l.target_id().map(|id| id.to_string()))
+            .collect()
+    }
+}
+
+impl MedicalDataset {
+    pub fn merge_patients(&mut self, survivor: &str, source: &str) -> Result<MergeReport, String> {
+        if survivor == source {
+            return Err("Cannot merge a patient into itself".to_string());
+        }
+        if !self.patients.iter().any(|p| p.id == survivor) {
+            return Err(format!("Survivor patient {} not found", survivor));
+        }
+        let position = self
+            .patients
+            .iter()
+            .position(|p| p.id == source)
+            .ok_or(format!("Source patient {} not found", source))?;
+        let source_record = self.patients.remove(position);
+
+        let mut rewritten = 0u32;
+        let survivor_reference = format!("Patient/{}", survivor);
+        let mut rewrite = |reference: &mut Option<String>| {
+            if points_to(reference, source) {
+                *reference = Some(survivor_reference.clone());
+                rewritten += 1;
+            }
+        };
+        for observation in &mut self.observations {
+            rewrite(&mut observation.subject.reference);
+        }
+        for condition in &mut self.conditions {
+            rewrite(&mut condition.subject.reference);
+        }
+        for report in &mut self.diagnostic_reports {
+            rewrite(&mut report.subject.reference);
+        }
+        for patient in &mut self.patients {
+            for link in &mut patient.link {
+                rewrite(&mut link.other.reference);
+            }
+        }
+
+        let survivor_record = self.patients.iter_mut().find(|p| p.id == survivor).ok_or("Survivor patient vanished")?;
+        let mut moved = 0u32;
+        for mut identifier in source_record.identifier {
+            let known = survivor_record.identifier.iter().any(|i| i.system == identifier.system && i.value == identifier.value);
+            if !known {
+                identifier.use_type = Some("old".to_string());
+                survivor_record.identifier.push(identifier);
+                moved += 1;
+            }
+        }
+        // Links the source carried now belong to the survivor, minus any to itself
+        for link in source_record.link {
+            if link.target_id() != Some(survivor) && link.link_type != LINK_REPLACED_BY {
+                survivor_record.link.push(link);
+            }
+        }
+        survivor_record.link.retain(|l| l.target_id() != Some(survivor));
+        survivor_record.link.push(PatientLink::new(source, LINK_REPLACES));
+
+        self.updated_at = Utc::now().to_rfc3339();
+        Ok(MergeReport {
+            survivor: survivor.to_string(),
+            source: source.to_string(),
+            references_rewritten: rewritten,
+            identifiers_moved: moved,
+        })
+    }
+
+    // Current record for an id, following merges
+    pub fn resolve_patient(&self, patient_id: &str) -> Option<&Patient> {
+        self.patients
+            .iter()
+            .find(|p| p.id == patient_id)
+            .or_else(|| self.patients.iter().find(|p| p.replaced_ids().iter().any(|id| id == patient_id)))
+    }
+
+    // Removes the patient, every record linked to them by "replaces" or "refer", and
+    // all resources about any of them. "seealso" links may name a different person, so
+    // those records stay; only the dangling links to the erased records are dropped.
+    pub fn erase_patient(&mut self, patient_id: &str) -> Result<ErasureReport, String> {
+        let start = self.resolve_patient(patient_id).ok_or(format!("Patient {} not found", patient_id))?.id.clone();
+
+        let mut erased: HashSet<String> = HashSet::new();
+        let mut pending = vec![start];
+        while let Some(id) = pending.pop() {
+            if !erased.insert(id.clone()) {
+                continue;
+            }
+            for patient in &self.patients {
+                let same_person = |l: &PatientLink| l.link_type == LINK_REPLACES || l.link_type == LINK_REFER;
+                if patient.id == id {
+                    pending.extend(patient.link.iter().filter(|l| same_person(l)).filter_map(|l| l.target_id().map(|t| t.to_string())));
+                } else if patient.link.iter().any(|l| same_person(l) && l.target_id() == Some(id.as_str())) {
+                    pending.push(patient.id.clone());
+                }
+            }
+        }
+
+        let is_erased = |reference: &Option<String>| {
+            reference.as_deref().is_some_and(|r| erased.contains(patient_id_from_reference(r)))
+        };
+        let mut report = ErasureReport::default();
+        let before = (self.observations.len(), self.conditions.len(), self.diagnostic_reports.len());
+        self.observations.retain(|o| !is_erased(&o.subject.reference));
+        self.conditions.retain(|c| !is_erased(&c.subject.reference));
+        self.diagnostic_reports.retain(|r| !is_erased(&r.subject.reference));
+        report.observations_removed = (before.0 - self.observations.len()) as u32;
+        report.conditions_removed = (before.1 - self.conditions.len()) as u32;
+        report.diagnostic_reports_removed = (before.2 - self.diagnostic_reports.len()) as u32;
+
+        let mut removed: Vec<String> = self.patients.iter().filter(|p| erased.contains(&p.id)).map(|p| p.id.clone()).collect();
+        removed.sort();
+        self.patients.retain(|p| !erased.contains(&p.id));
+        for patient in &mut self.patients {
+            patient.link.retain(|l| !is_erased(&l.other.reference));
+        }
+        report.patients_removed = removed;
+
+        self.updated_at = Utc::now().to_rfc3339();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(id: &str, patient_id: &str) -> Observation {
+        let code = CodeableConcept::loinc("718-7").unwrap();
+        Observation::new(id.to_string(), code, create_reference(&format!("Patient/{}", patient_id), None))
+    }
+
+    #[test]
+    fn test_merge_rewrites_references_and_erasure_follows_links() {
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        for id in ["p1", "p2", "p3", "p4"] {
+            dataset.patients.push(Patient::new(id.to_string()));
+        }
+        dataset.patients[1].identifier.push(Identifier {
+            use_type: Some("usual".to_string()),
+            type_code: None,
+            system: Some("urn:mrn".to_string()),
+            value: "MRN-2".to_string(),
+            period: None,
+            assigner: None,
+        });
+        dataset.patients[2].link.push(PatientLink::new("p2", LINK_SEEALSO));
+        dataset.patients[3].link.push(PatientLink::new("p1", LINK_REFER));
+        dataset.observations.push(observation("o1", "p1"));
+        dataset.observations.push(observation("o2", "p2"));
+        dataset.observations.push(observation("o3", "p3"));
+
+        let report = dataset.merge_patients("p1", "p2").unwrap();
+        assert_eq!(report.references_rewritten, 2);
+        assert_eq!(report.identifiers_moved, 1);
+        assert!(dataset.patients.iter().all(|p| p.id != "p2"));
+        assert_eq!(dataset.resolve_patient("p2").unwrap().id, "p1");
+        assert_eq!(dataset.observations[1].subject.reference.as_deref(), Some("Patient/p1"));
+        assert!(dataset.merge_patients("p1", "p2").is_err());
+
+        // Anonymization must not leave the merged-away id in a link
+        let mut anonymized = dataset.clone();
+        anonymized.anonymize_dataset();
+        let links: Vec<String> = anonymized.patients.iter().flat_map(|p| p.link.iter()).filter_map(|l| l.other.reference.clone()).collect();
+        assert!(links.iter().all(|l| !l.ends_with("/p1") && !l.ends_with("/p2")));
+        assert_eq!(anonymized.observations[0].subject.reference, anonymized.observations[1].subject.reference);
+
+        // Erasing by the old id removes the survivor and the "refer" record, not the "seealso" one
+        let erasure = dataset.erase_patient("p2").unwrap();
+        assert_eq!(erasure.patients_removed, vec!["p1".to_string(), "p4".to_string()]);
+        assert_eq!(erasure.observations_removed, 2);
+        assert_eq!(dataset.patients.len(), 1);
+        assert!(dataset.patients[0].link.is_empty());
+    }
+}
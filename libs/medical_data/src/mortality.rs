@@ -0,0 +1,176 @@
+// Deceased status. A patient is deceased if `deceased` is true or a date of death is
+// recorded; rare disease cases also count an outcome of Deceased. Clinical activity
+// dated after death is almost always a data error (wrong patient, wrong date, a
+// merged record), so it is reported rather than silently used: observations and
+// reports by their effective date, conditions by onset, and the encounters of a rare
+// disease case (tests, referrals, treatments) by the date they took place. Dates are
+// compared by day, so activity on the day of death is fine. Results issued later,
+// such as autopsy reports, are not flagged.
+
+use crate::rare_diseases::{CaseStatus, RareDiseaseCase};
+use crate::*;
+use chrono::NaiveDate;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PostMortemFinding {
+    pub patient_id: String,
+    pub resource_type: String,
+    pub resource_id: String,
+    pub date: String,
+    pub date_of_death: String,
+}
+
+// Day part of "YYYY-MM-DD" or a dateTime starting with it
+pub fn parse_day(value: &str) -> Option<NaiveDate> {
+    value.get(..10).and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+}
+
+impl Patient {
+    pub fn is_deceased(&self) -> bool {
+        self.deceased == Some(true) || self.deceased_datetime.is_some()
+    }
+
+    // A year-only date (as left by Safe Harbor) counts as the last day of that year,
+    // so nothing within the year is flagged
+    pub fn date_of_death(&self) -> Option<NaiveDate> {
+        let value = self.deceased_datetime.as_deref()?;
+        match value.len() {
+            4 => value.parse().ok().and_then(|year| NaiveDate::from_ymd_opt(year, 12, 31)),
+            _ => parse_day(value),
+        }
+    }
+}
+
+fn check(
+    findings: &mut Vec<PostMortemFinding>,
+    patient: &Patient,
+    death: NaiveDate,
+    resource_type: &str,
+    resource_id: &str,
+    date: Option<&str>,
+) {
+    if let Some(date) = date {
+        if parse_day(date).is_some_and(|day| day > death) {
+            findings.push(PostMortemFinding {
+                patient_id: patient.id.clone(),
+                resource_type: resource_type.to_string(),
+                resource_id: resource_id.to_string(),
+                date: date.to_string(),
+                date_of_death: death.format("%Y-%m-%d").to_string(),
+            });
+        }
+    }
+}
+
+impl MedicalDataset {
+    pub fn post_mortem_findings(&self) -> Vec<PostMortemFinding> {
+        let deaths: HashMap<&str, (&Patient, NaiveDate)> =
+            self.patients.iter().filter_map(|p| p.date_of_death().map(|d| (p.id.as_str(), (p, d)))).collect();
+        let died = |reference: &Option<String>| {
+            reference.as_deref().and_then(|r| deaths.get(linkage::patient_id_from_reference(r))).copied()
+        };
+
+        let mut findings = Vec::new();
+        for observation in &self.observations {
+            if let Some((patient, death)) = died(&observation.subject.reference) {
+                check(&mut findings, patient, death, "Observation", &observation.id, observation.effective_datetime.as_deref());
+            }
+        }
+        for report in &self.diagnostic_reports {
+            if let Some((patient, death)) = died(&report.subject.reference) {
+                check(&mut findings, patient, death, "DiagnosticReport", &report.id, report.effective_datetime.as_deref());
+            }
+        }
+        for condition in &self.conditions {
+            if let (Some((patient, death)), Some(ConditionOnset::DateTime(onset))) = (died(&condition.subject.reference), &condition.onset) {
+                check(&mut findings, patient, death, "Condition", &condition.id, Some(onset));
+            }
+        }
+        findings
+    }
+}
+
+impl RareDiseaseCase {
+    pub fn is_deceased(&self) -> bool {
+        self.patient.is_deceased() || self.outcome.as_ref().is_some_and(|o| matches!(o.status, CaseStatus::Deceased))
+    }
+
+    pub fn post_mortem_findings(&self) -> Vec<PostMortemFinding> {
+        let mut findings = Vec::new();
+        let death = match self.patient.date_of_death() {
+            Some(death) => death,
+            None => return findings,
+        };
+        let patient = &self.patient;
+        let journey = &self.diagnostic_journey;
+        for test in &journey.diagnostic_tests {
+            check(&mut findings, patient, death, "DiagnosticTest", &test.test_name, Some(&test.date_performed));
+        }
+        for referral in &journey.referrals {
+            check(&mut findings, patient, death, "Referral", &referral.specialty, Some(&referral.date));
+        }
+        for treatment in &self.treatment_history {
+            let name = treatment.medication.clone().unwrap_or_else(|| format!("{:?}", treatment.treatment_type));
+            check(&mut findings, patient, death, "Treatment", &name, Some(&treatment.start_date));
+        }
+        findings
+    }
+
+    // Outcome and patient record must agree on whether the patient died
+    pub fn validate_deceased_status(&self) -> Result<(), String> {
+        let outcome_deceased = self.outcome.as_ref().is_some_and(|o| matches!(o.status, CaseStatus::Deceased));
+        if self.patient.is_deceased() && self.outcome.is_some() && !outcome_deceased {
+            return Err(format!("Case {} patient is deceased but the outcome is not", self.case_id));
+        }
+        if outcome_deceased && self.patient.deceased == Some(false) {
+            return Err(format!("Case {} outcome is Deceased but the patient is marked alive", self.case_id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_after_death_is_flagged() {
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        let mut patient = Patient::new("p1".to_string());
+        patient.set_deceased(Some("2024-03-10T08:00:00Z".to_string()));
+        dataset.patients.push(patient);
+        dataset.patients.push(Patient::new("p2".to_string()));
+
+        let code = CodeableConcept::loinc("718-7").unwrap();
+        for (id, patient_id, date) in [("o1", "p1", "2024-03-10"), ("o2", "p1", "2024-03-11T09:00:00Z"), ("o3", "p2", "2025-01-01")] {
+            let mut observation = Observation::new(id.to_string(), code.clone(), create_reference(&format!("Patient/{}", patient_id), None));
+            observation.effective_datetime = Some(date.to_string());
+            dataset.observations.push(observation);
+        }
+        let findings = dataset.post_mortem_findings();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].resource_id.as_str(), findings[0].date_of_death.as_str()), ("o2", "2024-03-10"));
+
+        let mut generalized = dataset.patients[0].clone();
+        generalized.deceased_datetime = Some("2024".to_string());
+        assert_eq!(generalized.date_of_death(), NaiveDate::from_ymd_opt(2024, 12, 31));
+        generalized.add_name(HumanName {
+            use_type: None,
+            text: Some("Test Patient".to_string()),
+            family: Some("Patient".to_string()),
+            given: vec!["Test".to_string()],
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+            period: None,
+        });
+        assert!(generalized.validate().is_ok());
+        generalized.birth_date = Some("2025-01-01".to_string());
+        assert_eq!(generalized.validate().unwrap_err(), "Date of death precedes birth date");
+
+        // Censored at 30 days: the second death takes survival from 0.75 to 0.375
+        let curve = crate::rare_diseases::kaplan_meier(&[(10, true), (30, false), (40, true), (50, false)]);
+        assert_eq!((curve.events, curve.censored), (2, 2));
+        assert!((curve.points[1].survival - 0.375).abs() < 1e-12);
+        assert_eq!(curve.median_survival_days, Some(40));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+
+pub const LOINC_SYSTEM: &str = "http://loinc.org";
+
+// Blood pressure panel and components
+pub const LOINC_BP_PANEL: &str = "85354-9";
+pub const LOINC_SYSTOLIC_BP: &str = "8480-6";
+pub const LOINC_DIASTOLIC_BP: &str = "8462-4";
+
+// Complete blood count panel and components
+pub const LOINC_CBC_PANEL: &str = "58410-2";
+pub const LOINC_WBC: &str = "6690-2";
+pub const LOINC_RBC: &str = "789-8";
+pub const LOINC_HEMOGLOBIN: &str = "718-7";
+pub const LOINC_HEMATOCRIT: &str = "4544-3";
+pub const LOINC_MCV: &str = "787-2";
+pub const LOINC_MCH: &str = "785-6";
+pub const LOINC_MCHC: &str = "786-4";
+pub const LOINC_PLATELETS: &str = "777-3";
+
+// Layout of a multi-component panel; the component order fixes the feature order
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ObservationPanel {
+    pub loinc_code: String,
+    pub name: String,
+    pub components: Vec<PanelComponent>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PanelComponent {
+    pub loinc_code: String,
+    pub name: String,
+    pub unit: String,
+}
+
+// One flattened panel observation; missing components are None
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PanelFeatureVector {
+    pub observation_id: String,
+    pub subject: Option<String>,
+    pub effective_datetime: Option<String>,
+    pub values: Vec<Option<f64>>,
+}
+
+impl PanelFeatureVector {
+    pub fn is_complete(&self) -> bool {
+        self.values.iter().all(|v| v.is_some())
+    }
+
+    pub fn to_dense(&self, fill_value: f64) -> Vec<f64> {
+        self.values.iter().map(|v| v.unwrap_or(fill_value)).collect()
+    }
+}
+
+impl ObservationPanel {
+    pub fn new(loinc_code: &str, name: &str, components: &[(&str, &str, &str)]) -> Self {
+        ObservationPanel {
+            loinc_code: loinc_code.to_string(),
+            name: name.to_string(),
+            components: components
+                .iter()
+                .map(|(code, name, unit)| PanelComponent {
+                    loinc_code: code.to_string(),
+                    name: name.to_string(),
+                    unit: unit.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn blood_pressure() -> Self {
+        ObservationPanel::new(
+            LOINC_BP_PANEL,
+            "Blood pressure panel",
+            &[
+                (LOINC_SYSTOLIC_BP, "systolic_bp", "mm[Hg]"),
+                (LOINC_DIASTOLIC_BP, "diastolic_bp", "mm[Hg]"),
+            ],
+        )
+    }
+
+    pub fn complete_blood_count() -> Self {
+        ObservationPanel::new(
+            LOINC_CBC_PANEL,
+            "Complete blood count panel",
+            &[
+                (LOINC_WBC, "wbc", "10*3/uL"),
+                (LOINC_RBC, "rbc", "10*6/uL"),
+                (LOINC_HEMOGLOBIN, "hemoglobin", "g/dL"),
+                (LOINC_HEMATOCRIT, "hematocrit", "%"),
+                (LOINC_MCV, "mcv", "fL"),
+                (LOINC_MCH, "mch", "pg"),
+                (LOINC_MCHC, "mchc", "g/dL"),
+                (LOINC_PLATELETS, "platelets", "10*3/uL"),
+            ],
+        )
+    }
+
+    pub fn feature_names(&self) -> Vec<String> {
+        self.components.iter().map(|c| c.name.clone()).collect()
+    }
+
+    // Matches on the panel code, or on any component code for feeds that omit it
+    pub fn matches(&self, observation: &Observation) -> bool {
+        observation.code.has_loinc_code(&self.loinc_code)
+            || self
+                .components
+                .iter()
+                .any(|c| observation.component_by_loinc(&c.loinc_code).is_some())
+    }
+
+    pub fn flatten(&self, observation: &Observation) -> Option<PanelFeatureVector> {
+        if !self.matches(observation) {
+            return None;
+        }
+
+        let values = self
+            .components
+            .iter()
+            .map(|c| observation.component_value(&c.loinc_code))
+            .collect();
+
+        Some(PanelFeatureVector {
+            observation_id: observation.id.clone(),
+            subject: observation.subject.reference.clone(),
+            effective_datetime: observation.effective_datetime.clone(),
+            values,
+        })
+    }
+}
+
+impl MedicalDataset {
+    // Flattens every observation of the given panel into a feature vector for training
+    pub fn panel_features(&self, panel: &ObservationPanel) -> Vec<PanelFeatureVector> {
+        self.observations
+            .iter()
+            .filter_map(|observation| panel.flatten(observation))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(loinc_code: &str, value: f64, unit: &str) -> ObservationComponent {
+        ObservationComponent {
+            code: CodeableConcept::loinc(loinc_code).unwrap(),
+            value: Some(ObservationValue::Quantity(create_quantity(value, unit, None, None))),
+            data_absent_reason: None,
+            interpretation: Vec::new(),
+            reference_range: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_blood_pressure_components() {
+        let code = CodeableConcept::loinc(LOINC_BP_PANEL).unwrap();
+        let mut observation = Observation::new("bp-1".to_string(), code, create_reference("Patient/1", None));
+        observation.add_component(component(LOINC_SYSTOLIC_BP, 128.0, "mm[Hg]"));
+        observation.add_component(component(LOINC_DIASTOLIC_BP, 82.0, "mm[Hg]"));
+
+        assert_eq!(observation.systolic(), Some(128.0));
+        assert_eq!(observation.diastolic(), Some(82.0));
+        assert!(observation.component_by_loinc(LOINC_HEMOGLOBIN).is_none());
+
+        let features = ObservationPanel::blood_pressure().flatten(&observation).unwrap();
+        assert_eq!(features.values, vec![Some(128.0), Some(82.0)]);
+        assert!(ObservationPanel::complete_blood_count().flatten(&observation).is_none());
+    }
+
+    #[test]
+    fn test_partial_cbc_flattening() {
+        let code = CodeableConcept::loinc(LOINC_CBC_PANEL).unwrap();
+        let mut observation = Observation::new("cbc-1".to_string(), code, create_reference("Patient/1", None));
+        observation.add_component(component(LOINC_HEMOGLOBIN, 13.5, "g/dL"));
+        observation.add_component(component(LOINC_PLATELETS, 250.0, "10*3/uL"));
+
+        let panel = ObservationPanel::complete_blood_count();
+        let features = panel.flatten(&observation).unwrap();
+        assert_eq!(features.values.len(), panel.feature_names().len());
+        assert_eq!(features.values[2], Some(13.5));
+        assert_eq!(features.values[0], None);
+        assert!(!features.is_complete());
+        assert_eq!(features.to_dense(0.0)[7], 250.0);
+    }
+}
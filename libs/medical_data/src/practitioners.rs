@@ -0,0 +1,293 @@
+// Practitioner and Organization resources. Clinical resources point at them through
+// performer, recorder, asserter, general_practitioner and managing_organization.
+// A reference resolves either literally ("Practitioner/<id>", "Organization/<id>")
+// or logically through an identifier, typically the NPI. NPIs, on the resources and
+// on logical references, must pass the NPI check digit.
+
+use crate::validation::validate_medical_identifier_checksum;
+use crate::*;
+
+pub const NPI_SYSTEM: &str = "http://hl7.org/fhir/sid/us-npi";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Practitioner {
+    pub id: String,
+    pub identifier: Vec<Identifier>,
+    pub active: Option<bool>,
+    pub name: Vec<HumanName>,
+    pub telecom: Vec<ContactPoint>,
+    pub address: Vec<Address>,
+    pub gender: Option<Gender>,
+    pub qualification: Vec<PractitionerQualification>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PractitionerQualification {
+    pub identifier: Vec<Identifier>,
+    pub code: CodeableConcept,
+    pub period: Option<Period>,
+    pub issuer: Option<Reference>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Organization {
+    pub id: String,
+    pub identifier: Vec<Identifier>,
+    pub active: Option<bool>,
+    pub organization_type: Vec<CodeableConcept>,
+    pub name: Option<String>,
+    pub telecom: Vec<ContactPoint>,
+    pub address: Vec<Address>,
+    pub part_of: Option<Reference>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReferenceIssue {
+    pub resource_type: String,
+    pub resource_id: String,
+    pub field: String,
+    pub message: String,
+}
+
+pub fn npi_identifier(npi: &str) -> Identifier {
+    Identifier {
+        use_type: Some("official".to_string()),
+        type_code: None,
+        system: Some(NPI_SYSTEM.to_string()),
+        value: npi.to_string(),
+        period: None,
+        assigner: None,
+    }
+}
+
+fn validate_identifiers(identifiers: &[Identifier]) -> Result<(), String> {
+    for identifier in identifiers {
+        if identifier.system.as_deref() == Some(NPI_SYSTEM) {
+            validate_medical_identifier_checksum("npi", &identifier.value)
+                .map_err(|e| format!("NPI {}: {}", identifier.value, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn npi_of(identifiers: &[Identifier]) -> Option<&str> {
+    identifiers.iter().find(|i| i.system.as_deref() == Some(NPI_SYSTEM)).map(|i| i.value.as_str())
+}
+
+fn has_identifier(identifiers: &[Identifier], wanted: &Identifier) -> bool {
+    identifiers.iter().any(|i| i.system == wanted.system && i.value == wanted.value)
+}
+
+impl Practitioner {
+    pub fn new(id: String) -> Self {
+        Practitioner {
+            id,
+            identifier: Vec::new(),
+            active: None,
+            name: Vec::new(),
+            telecom: Vec::new(),
+            address: Vec::new(),
+            gender: None,
+            qualification: Vec::new(),
+        }
+    }
+
+    pub fn npi(&self) -> Option<&str> {
+        npi_of(&self.identifier)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() {
+            return Err("Practitioner ID is required".to_string());
+        }
+        if self.name.is_empty() && self.identifier.is_empty() {
+            return Err(format!("Practitioner {} needs a name or an identifier", self.id));
+        }
+        validate_identifiers(&self.identifier).map_err(|e| format!("Practitioner {}: {}", self.id, e))
+    }
+}
+
+impl Organization {
+    pub fn new(id: String, name: Option<String>) -> Self {
+        Organization {
+            id,
+            identifier: Vec::new(),
+            active: None,
+            organization_type: Vec::new(),
+            name,
+            telecom: Vec::new(),
+            address: Vec::new(),
+            part_of: None,
+        }
+    }
+
+    pub fn npi(&self) -> Option<&str> {
+        npi_of(&self.identifier)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() {
+            return Err("Organization ID is required".to_string());
+        }
+        if self.name.is_none() && self.identifier.is_empty() {
+            return Err(format!("Organization {} needs a name or an identifier", self.id));
+        }
+        validate_identifiers(&self.identifier).map_err(|e| format!("Organization {}: {}", self.id, e))
+    }
+}
+
+// Target type of a reference: the literal prefix, else reference_type
+fn target_type(reference: &Reference) -> Option<&str> {
+    reference
+        .reference
+        .as_deref()
+        .and_then(|r| r.split_once('/').map(|(t, _)| t))
+        .or(reference.reference_type.as_deref())
+}
+
+fn literal_id<'a>(reference: &'a Reference, resource_type: &str) -> Option<&'a str> {
+    reference.reference.as_deref().and_then(|r| r.strip_prefix(resource_type)).and_then(|r| r.strip_prefix('/'))
+}
+
+impl MedicalDataset {
+    pub fn add_practitioner(&mut self, practitioner: Practitioner) -> Result<(), String> {
+        practitioner.validate()?;
+        self.practitioners.push(practitioner);
+        self.updated_at = Utc::now().to_rfc3339();
+        Ok(())
+    }
+
+    pub fn add_organization(&mut self, organization: Organization) -> Result<(), String> {
+        organization.validate()?;
+        self.organizations.push(organization);
+        self.updated_at = Utc::now().to_rfc3339();
+        Ok(())
+    }
+
+    pub fn resolve_practitioner(&self, reference: &Reference) -> Option<&Practitioner> {
+        if let Some(id) = literal_id(reference, "Practitioner") {
+            return self.practitioners.iter().find(|p| p.id == id);
+        }
+        let identifier = reference.identifier.as_ref()?;
+        self.practitioners.iter().find(|p| has_identifier(&p.identifier, identifier))
+    }
+
+    pub fn resolve_organization(&self, reference: &Reference) -> Option<&Organization> {
+        if let Some(id) = literal_id(reference, "Organization") {
+            return self.organizations.iter().find(|o| o.id == id);
+        }
+        let identifier = reference.identifier.as_ref()?;
+        self.organizations.iter().find(|o| has_identifier(&o.identifier, identifier))
+    }
+
+    fn check_reference(&self, issues: &mut Vec<ReferenceIssue>, resource_type: &str, resource_id: &str, field: &str, reference: &Reference) {
+        let mut issue = |message: String| {
+            issues.push(ReferenceIssue {
+                resource_type: resource_type.to_string(),
+                resource_id: resource_id.to_string(),
+                field: field.to_string(),
+                message,
+            })
+        };
+        if let Some(identifier) = &reference.identifier {
+            if let Err(e) = validate_identifiers(std::slice::from_ref(identifier)) {
+                issue(e);
+                return;
+            }
+        }
+        match target_type(reference) {
+            Some("Practitioner") if self.resolve_practitioner(reference).is_none() => {
+                issue("Practitioner reference does not resolve".to_string())
+            }
+            Some("Organization") if self.resolve_organization(reference).is_none() => {
+                issue("Organization reference does not resolve".to_string())
+            }
+            // Patients, devices, roles and the like are resolved elsewhere
+            _ => {}
+        }
+    }
+
+    // Practitioner and Organization references that do not resolve, or that carry
+    // an invalid NPI
+    pub fn reference_issues(&self) -> Vec<ReferenceIssue> {
+        let mut issues = Vec::new();
+        for patient in &self.patients {
+            for reference in &patient.general_practitioner {
+                self.check_reference(&mut issues, "Patient", &patient.id, "general_practitioner", reference);
+            }
+            if let Some(reference) = &patient.managing_organization {
+                self.check_reference(&mut issues, "Patient", &patient.id, "managing_organization", reference);
+            }
+        }
+        for observation in &self.observations {
+            for reference in &observation.performer {
+                self.check_reference(&mut issues, "Observation", &observation.id, "performer", reference);
+            }
+        }
+        for condition in &self.conditions {
+            if let Some(reference) = &condition.recorder {
+                self.check_reference(&mut issues, "Condition", &condition.id, "recorder", reference);
+            }
+            if let Some(reference) = &condition.asserter {
+                self.check_reference(&mut issues, "Condition", &condition.id, "asserter", reference);
+            }
+        }
+        for report in &self.diagnostic_reports {
+            for reference in report.performer.iter().chain(report.results_interpreter.iter()) {
+                self.check_reference(&mut issues, "DiagnosticReport", &report.id, "performer", reference);
+            }
+        }
+        for organization in &self.organizations {
+            if let Some(reference) = &organization.part_of {
+                self.check_reference(&mut issues, "Organization", &organization.id, "part_of", reference);
+            }
+        }
+        issues
+    }
+
+    pub fn validate_references(&self) -> Result<(), String> {
+        match self.reference_issues().first() {
+            Some(issue) => Err(format!("{} {} {}: {}", issue.resource_type, issue.resource_id, issue.field, issue.message)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_performer_references_resolve_by_id_and_npi() {
+        let mut dataset = MedicalDataset::new("site".to_string(), "site".to_string(), String::new());
+        let mut practitioner = Practitioner::new("dr-1".to_string());
+        practitioner.identifier.push(npi_identifier("1234567893"));
+        dataset.add_practitioner(practitioner).unwrap();
+        dataset.add_organization(Organization::new("org-1".to_string(), Some("General Hospital".to_string()))).unwrap();
+
+        let mut bad_npi = Practitioner::new("dr-2".to_string());
+        bad_npi.identifier.push(npi_identifier("1234567890"));
+        assert!(dataset.add_practitioner(bad_npi).is_err());
+
+        let code = CodeableConcept::loinc("718-7").unwrap();
+        let mut observation = Observation::new("o1".to_string(), code, create_reference("Patient/p1", None));
+        observation.performer.push(create_reference("Practitioner/dr-1", None));
+        observation.performer.push(create_reference("Organization/org-1", None));
+        observation.performer.push(Reference {
+            reference: None,
+            reference_type: Some("Practitioner".to_string()),
+            identifier: Some(npi_identifier("1234567893")),
+            display: None,
+        });
+        dataset.observations.push(observation);
+        assert!(dataset.validate_references().is_ok());
+        assert_eq!(dataset.resolve_practitioner(&dataset.observations[0].performer[2]).unwrap().id, "dr-1");
+
+        let mut condition = Condition::new("c1".to_string(), create_reference("Patient/p1", None));
+        condition.recorder = Some(create_reference("Practitioner/dr-9", None));
+        dataset.conditions.push(condition);
+        let issues = dataset.reference_issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].resource_id.as_str(), issues[0].field.as_str()), ("c1", "recorder"));
+    }
+}
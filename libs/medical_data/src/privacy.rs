@@ -121,6 +121,9 @@ impl MedicalDataPrivacy {
                     patient.birth_date = Some(format!("{}-01-01", &birth_date[..4]));
                 }
             }
+            if let Some(ref deceased_datetime) = patient.deceased_datetime {
+                patient.deceased_datetime = deceased_datetime.get(..4).map(|year| year.to_string());
+            }
             
             // 4. Telephone numbers
             // 5. Fax numbers  
@@ -157,6 +160,11 @@ impl MedicalDataPrivacy {
                     condition.recorded_date = Some(format!("{}-01-01", &date[..4]));
                 }
             }
+            for change in &mut condition.status_history {
+                if change.effective_date.len() >= 4 {
+                    change.effective_date = format!("{}-01-01", &change.effective_date[..4]);
+                }
+            }
         }
         
         Ok(())
@@ -479,6 +487,16 @@ impl MedicalDataPrivacy {
     }
 }
 
+// Keyed pseudonym for a patient ID. Sites sharing the consortium salt derive the
+// same pseudonym for the same patient without exchanging the raw identifier.
+pub fn pseudonymize_patient_id(patient_id: &str, consortium_salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(consortium_salt.as_bytes());
+    hasher.update(b":");
+    hasher.update(patient_id.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
 // Privacy metrics and reporting
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct PrivacyMetrics {
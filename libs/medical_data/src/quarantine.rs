@@ -0,0 +1,244 @@
+// Quarantine for records that fail validation or consistency checks. A record a site
+// extract gets wrong is usually still a real patient's data, so instead of dropping
+// it with an error, `add_patient`, `add_observation` and `add_condition` park it here
+// with every reason it was refused. A data steward then reviews it, corrects it, and
+// re-admits it once it passes the same checks, or rejects it for good. Entries stay
+// in the quarantine after either decision, so the review trail is kept.
+//
+// Consistency checks run against what the dataset already holds: a duplicate id, or
+// an observation whose value conflicts with one for the same patient, code and time.
+
+use crate::validation::validate_clinical_data_consistency;
+use crate::*;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum QuarantinedRecord {
+    Patient(Patient),
+    Observation(Observation),
+    Condition(Condition),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum QuarantineStatus {
+    Pending,
+    Readmitted,
+    Rejected,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReviewNote {
+    pub reviewer: String,
+    pub note: String,
+    pub recorded_at: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct QuarantineEntry {
+    pub id: u64,
+    pub record: QuarantinedRecord,
+    // Reasons from the latest check of `record`
+    pub reasons: Vec<String>,
+    pub status: QuarantineStatus,
+    pub quarantined_at: String,
+    pub review_notes: Vec<ReviewNote>,
+}
+
+impl QuarantinedRecord {
+    pub fn resource_type(&self) -> &'static str {
+        match self {
+            QuarantinedRecord::Patient(_) => "Patient",
+            QuarantinedRecord::Observation(_) => "Observation",
+            QuarantinedRecord::Condition(_) => "Condition",
+        }
+    }
+
+    pub fn resource_id(&self) -> &str {
+        match self {
+            QuarantinedRecord::Patient(patient) => &patient.id,
+            QuarantinedRecord::Observation(observation) => &observation.id,
+            QuarantinedRecord::Condition(condition) => &condition.id,
+        }
+    }
+}
+
+impl MedicalDataset {
+    // Every reason `record` cannot be admitted; empty if it can
+    pub fn admission_issues(&self, record: &QuarantinedRecord) -> Vec<String> {
+        let mut issues = Vec::new();
+        let (validation, duplicate) = match record {
+            QuarantinedRecord::Patient(patient) => (patient.validate(), self.patients.iter().any(|p| p.id == patient.id)),
+            QuarantinedRecord::Observation(observation) => {
+                (observation.validate(), self.observations.iter().any(|o| o.id == observation.id))
+            }
+            QuarantinedRecord::Condition(condition) => (condition.validate(), self.conditions.iter().any(|c| c.id == condition.id)),
+        };
+        if let Err(reason) = validation {
+            issues.push(reason);
+        }
+        if duplicate {
+            issues.push(format!("{} {} is already in the dataset", record.resource_type(), record.resource_id()));
+        }
+
+        if let QuarantinedRecord::Observation(observation) = record {
+            let same_measurement = self.observations.iter().filter(|o| {
+                o.subject.reference == observation.subject.reference
+                    && o.effective_datetime == observation.effective_datetime
+                    && o.code.coding == observation.code.coding
+            });
+            for existing in same_measurement {
+                let pair = [existing.clone(), observation.clone()];
+                if let Ok(warnings) = validate_clinical_data_consistency(&pair, &[]) {
+                    // The check reports each conflicting pair in both orders
+                    issues.extend(warnings.into_iter().take(1).map(|w| format!("{} (observation {})", w, existing.id)));
+                }
+            }
+        }
+        issues
+    }
+
+    // Admits `record`, or quarantines it and returns the reasons with the entry id
+    pub fn admit_or_quarantine(&mut self, record: QuarantinedRecord) -> Result<(), String> {
+        let issues = self.admission_issues(&record);
+        if issues.is_empty() {
+            self.insert_record(record);
+            return Ok(());
+        }
+        let id = self.quarantine.iter().map(|e| e.id + 1).max().unwrap_or(1);
+        let message = format!(
+            "{} {} quarantined as entry {}: {}",
+            record.resource_type(),
+            record.resource_id(),
+            id,
+            issues.join("; ")
+        );
+        self.quarantine.push(QuarantineEntry {
+            id,
+            record,
+            reasons: issues,
+            status: QuarantineStatus::Pending,
+            quarantined_at: Utc::now().to_rfc3339(),
+            review_notes: Vec::new(),
+        });
+        self.updated_at = Utc::now().to_rfc3339();
+        Err(message)
+    }
+
+    fn insert_record(&mut self, record: QuarantinedRecord) {
+        match record {
+            QuarantinedRecord::Patient(patient) => self.patients.push(patient),
+            QuarantinedRecord::Observation(observation) => self.observations.push(observation),
+            QuarantinedRecord::Condition(condition) => self.conditions.push(condition),
+        }
+        self.updated_at = Utc::now().to_rfc3339();
+    }
+
+    pub fn quarantine_entry(&self, id: u64) -> Option<&QuarantineEntry> {
+        self.quarantine.iter().find(|e| e.id == id)
+    }
+
+    pub fn pending_quarantine(&self) -> Vec<&QuarantineEntry> {
+        self.quarantine.iter().filter(|e| e.status == QuarantineStatus::Pending).collect()
+    }
+
+    fn pending_entry_mut(&mut self, id: u64) -> Result<&mut QuarantineEntry, String> {
+        let entry = self.quarantine.iter_mut().find(|e| e.id == id).ok_or_else(|| format!("No quarantine entry {}", id))?;
+        if entry.status != QuarantineStatus::Pending {
+            return Err(format!("Quarantine entry {} was already {:?}", id, entry.status));
+        }
+        Ok(entry)
+    }
+
+    pub fn review_quarantined(&mut self, id: u64, reviewer: &str, note: &str) -> Result<(), String> {
+        let entry = self.pending_entry_mut(id)?;
+        entry.review_notes.push(ReviewNote { reviewer: reviewer.to_string(), note: note.to_string(), recorded_at: Utc::now().to_rfc3339() });
+        Ok(())
+    }
+
+    // Replaces the parked record with a corrected one of the same resource type and
+    // returns the issues that remain
+    pub fn correct_quarantined(&mut self, id: u64, corrected: QuarantinedRecord, reviewer: &str) -> Result<Vec<String>, String> {
+        let issues = self.admission_issues(&corrected);
+        let entry = self.pending_entry_mut(id)?;
+        if entry.record.resource_type() != corrected.resource_type() {
+            return Err(format!("Entry {} holds a {}, not a {}", id, entry.record.resource_type(), corrected.resource_type()));
+        }
+        entry.review_notes.push(ReviewNote {
+            reviewer: reviewer.to_string(),
+            note: format!("Corrected; {} issue(s) remain", issues.len()),
+            recorded_at: Utc::now().to_rfc3339(),
+        });
+        entry.record = corrected;
+        entry.reasons = issues.clone();
+        Ok(issues)
+    }
+
+    // Moves the record into the dataset if it now passes every check
+    pub fn readmit_quarantined(&mut self, id: u64, reviewer: &str) -> Result<(), String> {
+        let record = self.pending_entry_mut(id)?.record.clone();
+        let issues = self.admission_issues(&record);
+        let entry = self.pending_entry_mut(id)?;
+        entry.reasons = issues.clone();
+        if !issues.is_empty() {
+            return Err(format!("Entry {} still fails: {}", id, issues.join("; ")));
+        }
+        entry.status = QuarantineStatus::Readmitted;
+        entry.review_notes.push(ReviewNote { reviewer: reviewer.to_string(), note: "Re-admitted".to_string(), recorded_at: Utc::now().to_rfc3339() });
+        self.insert_record(record);
+        Ok(())
+    }
+
+    pub fn reject_quarantined(&mut self, id: u64, reviewer: &str, reason: &str) -> Result<(), String> {
+        let entry = self.pending_entry_mut(id)?;
+        entry.status = QuarantineStatus::Rejected;
+        entry.review_notes.push(ReviewNote { reviewer: reviewer.to_string(), note: reason.to_string(), recorded_at: Utc::now().to_rfc3339() });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glucose(id: &str, value: f64) -> Observation {
+        let code = create_codeable_concept(create_coding("http://loinc.org", "2345-7", "Glucose"), None);
+        let mut observation = Observation::new(id.to_string(), code, create_reference("Patient/p1", None));
+        observation.effective_datetime = Some("2024-03-01T08:00:00Z".to_string());
+        observation.value = Some(ObservationValue::Quantity(Quantity {
+            value: Some(value),
+            comparator: None,
+            unit: Some("mg/dL".to_string()),
+            system: None,
+            code: None,
+        }));
+        observation
+    }
+
+    #[test]
+    fn test_refused_records_are_parked_corrected_and_readmitted() {
+        let mut dataset = MedicalDataset::new("d1".to_string(), "Site".to_string(), String::new());
+        dataset.add_observation(glucose("o1", 100.0)).unwrap();
+
+        // A conflicting value is kept for review instead of being lost
+        let error = dataset.add_observation(glucose("o2", 300.0)).unwrap_err();
+        assert!(error.contains("quarantined as entry 1"));
+        assert_eq!(dataset.get_observation_count(), 1);
+        let entry = &dataset.pending_quarantine()[0];
+        assert!(entry.reasons[0].contains("Conflicting values") && entry.reasons[0].contains("o1"));
+
+        dataset.review_quarantined(1, "steward", "Checked against the LIS; 110 was keyed as 300").unwrap();
+        assert!(!dataset.correct_quarantined(1, QuarantinedRecord::Observation(glucose("o2", 300.0)), "steward").unwrap().is_empty());
+        assert!(dataset.readmit_quarantined(1, "steward").is_err());
+        assert!(dataset.correct_quarantined(1, QuarantinedRecord::Observation(glucose("o2", 110.0)), "steward").unwrap().is_empty());
+        dataset.readmit_quarantined(1, "steward").unwrap();
+        assert_eq!(dataset.get_observation_count(), 2);
+        assert_eq!(dataset.quarantine_entry(1).unwrap().status, QuarantineStatus::Readmitted);
+        assert!(dataset.reject_quarantined(1, "steward", "duplicate").is_err());
+
+        // Invalid and duplicate records are quarantined too
+        assert!(dataset.add_patient(Patient::new("p1".to_string())).is_err());
+        assert!(dataset.add_observation(glucose("o1", 100.0)).is_err());
+        dataset.reject_quarantined(3, "steward", "Re-sent by the extract").unwrap();
+        assert_eq!(dataset.pending_quarantine().len(), 1);
+        assert!(dataset.pending_quarantine()[0].reasons[0].contains("name"));
+    }
+}
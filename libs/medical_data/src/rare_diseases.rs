@@ -391,10 +391,84 @@ pub enum NoteType {
     Research,
 }
 
+// Kaplan-Meier estimate of survival from diagnosis (or first presentation when the
+// diagnosis date is unknown). Patients alive at their last follow-up are censored
+// there; cases with no usable dates are left out and counted.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SurvivalPoint {
+    pub time_days: i64,
+    pub at_risk: u32,
+    pub events: u32,
+    pub survival: f64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SurvivalCurve {
+    pub points: Vec<SurvivalPoint>,
+    pub subjects: u32,
+    pub events: u32,
+    pub censored: u32,
+    pub excluded: u32,
+    pub median_survival_days: Option<i64>,
+}
+
+// (days, died) per subject
+pub fn kaplan_meier(observations: &[(i64, bool)]) -> SurvivalCurve {
+    let mut sorted = observations.to_vec();
+    sorted.sort();
+    let mut curve = SurvivalCurve { subjects: sorted.len() as u32, ..SurvivalCurve::default() };
+    let mut survival = 1.0;
+    let mut at_risk = sorted.len() as u32;
+    let mut i = 0;
+    while i < sorted.len() {
+        let time = sorted[i].0;
+        let (mut events, mut censored) = (0, 0);
+        while i < sorted.len() && sorted[i].0 == time {
+            if sorted[i].1 {
+                events += 1;
+            } else {
+                censored += 1;
+            }
+            i += 1;
+        }
+        if events > 0 {
+            survival *= 1.0 - events as f64 / at_risk as f64;
+            curve.points.push(SurvivalPoint { time_days: time, at_risk, events, survival });
+            if curve.median_survival_days.is_none() && survival <= 0.5 {
+                curve.median_survival_days = Some(time);
+            }
+        }
+        curve.events += events;
+        curve.censored += censored;
+        at_risk -= events + censored;
+    }
+    curve
+}
+
+impl RareDiseaseCase {
+    // (days from diagnosis, died); None without a start date or an end point
+    fn survival_observation(&self) -> Option<(i64, bool)> {
+        let journey = &self.diagnostic_journey;
+        let start = journey
+            .diagnosis_date
+            .as_deref()
+            .and_then(mortality::parse_day)
+            .or_else(|| mortality::parse_day(&journey.initial_presentation_date))?;
+        let last_follow_up = self.outcome.as_ref().and_then(|o| o.last_follow_up.as_deref()).and_then(mortality::parse_day);
+        let (end, died) = match self.patient.date_of_death() {
+            Some(death) => (death, true),
+            None if self.is_deceased() => (last_follow_up?, true),
+            None => (last_follow_up?, false),
+        };
+        let days = (end - start).num_days();
+        (days >= 0).then_some((days, died))
+    }
+}
+
 // Rare disease database and utilities
 pub struct RareDiseaseDatabase {
     diseases: HashMap<String, RareDisease>,
-    cases: HashMap<String, RareDiseaseCase>,
+    pub(crate) cases: HashMap<String, RareDiseaseCase>,
 }
 
 impl RareDiseaseDatabase {
@@ -444,96 +518,28 @@ impl RareDiseaseDatabase {
             .collect()
     }
 
-    pub fn get_diagnostic_statistics(&self) -> HashMap<String, f64> {
-        let mut stats = HashMap::new();
-        
-        let total_cases = self.cases.len() as f64;
-        if total_cases == 0.0 {
-            return stats;
-        }
-
-        // Calculate average time to diagnosis
-        let mut total_time_to_diagnosis = 0.0;
-        let mut diagnosed_cases = 0.0;
-        
-        for case in self.cases.values() {
-            if let Some(days) = case.diagnostic_journey.time_to_diagnosis_days {
-                total_time_to_diagnosis += days as f64;
-                diagnosed_cases += 1.0;
+    // Survival of all cases, or of cases confirmed with one disease
+    pub fn survival_analysis(&self, orpha_code: Option<&str>) -> SurvivalCurve {
+        let cases = self.cases.values().filter(|case| match orpha_code {
+            Some(code) => case.confirmed_diagnosis.as_ref().is_some_and(|d| d.orpha_code == code),
+            None => true,
+        });
+        let mut observations = Vec::new();
+        let mut excluded = 0;
+        for case in cases {
+            match case.survival_observation() {
+                Some(observation) => observations.push(observation),
+                None => excluded += 1,
             }
         }
-
-        if diagnosed_cases > 0.0 {
-            stats.insert("average_time_to_diagnosis_days".to_string(), total_time_to_diagnosis / diagnosed_cases);
-        }
-
-        // Calculate diagnosis rate
-        let diagnosed_count = self.cases.values()
-            .filter(|case| case.confirmed_diagnosis.is_some())
-            .count() as f64;
-        
-        stats.insert("diagnosis_rate".to_string(), diagnosed_count / total_cases);
-
-        // Calculate average physicians consulted
-        let total_physicians: u32 = self.cases.values()
-            .map(|case| case.diagnostic_journey.physicians_consulted)
-            .sum();
-        
-        stats.insert("average_physicians_consulted".to_string(), total_physicians as f64 / total_cases);
-
-        stats
+        let mut curve = kaplan_meier(&observations);
+        curve.excluded = excluded;
+        curve
     }
 
+    // A fresh random case; use generate_synthetic_case_seeded for reproducible sets
     pub fn generate_synthetic_case(&self, disease_orpha_code: &str) -> Option<RareDiseaseCase> {
-        let disease = self.get_disease(disease_orpha_code)?;
-        
-        // Create synthetic patient
-        let mut patient = Patient::new(format!("synthetic_{}", uuid::Uuid::new_v4()));
-        patient.set_gender(Gender::Unknown);
-        
-        // Add synthetic name
-        let mut name = HumanName {
-            use_type: Some("official".to_string()),
-            text: Some("Synthetic Patient".to_string()),
-            family: Some("Patient".to_string()),
-            given: vec!["Synthetic".to_string()],
-            prefix: Vec::new(),
-            suffix: Vec::new(),
-            period: None,
-        };
-        patient.add_name(name);
-
-        // Create presenting symptoms based on disease features
-        let presenting_symptoms: Vec<ClinicalFeature> = disease.clinical_features
-            .iter()
-            .filter(|feature| matches!(feature.frequency, Frequency::Obligate | Frequency::VeryFrequent | Frequency::Frequent))
-            .cloned()
-            .collect();
-
-        // Create diagnostic journey
-        let diagnostic_journey = DiagnosticJourney {
-            initial_presentation_date: "2024-01-01".to_string(),
-            diagnosis_date: Some("2024-06-01".to_string()),
-            time_to_diagnosis_days: Some(150), // ~5 months
-            physicians_consulted: 5,
-            misdiagnoses: vec!["Common condition".to_string()],
-            diagnostic_tests: Vec::new(),
-            referrals: Vec::new(),
-        };
-
-        Some(RareDiseaseCase {
-            case_id: format!("case_{}", uuid::Uuid::new_v4()),
-            patient,
-            presenting_symptoms,
-            family_history: Vec::new(),
-            diagnostic_journey,
-            confirmed_diagnosis: Some(disease.clone()),
-            differential_diagnoses: Vec::new(),
-            genetic_testing: Vec::new(),
-            treatment_history: Vec::new(),
-            outcome: None,
-            case_notes: Vec::new(),
-        })
+        self.generate_synthetic_case_seeded(disease_orpha_code, uuid::Uuid::new_v4().as_u64_pair().0)
     }
 }
 
@@ -0,0 +1,162 @@
+// Differentially private statistics of the rare disease registry. A rare disease may
+// have a handful of cases, and an exact average over five patients moves visibly
+// when one is added, so every statistic is released through the Laplace count and
+// mean of `differential_privacy` and each call is charged to a privacy accountant.
+//
+// A call releases four registry-wide statistics (mean time to diagnosis, diagnosis
+// rate, mean physicians consulted, mortality rate) and, for every disease, its case
+// count and the same three means over its confirmed cases. A case falls in the
+// registry group and at most one disease group, so one case affects at most eight
+// statistics and the call's ε is split eight ways. Means are clamped to configured
+// ranges, which fixes their sensitivity.
+//
+// A group with fewer than `min_cases` cases is suppressed outright, as a second line
+// of defence on top of the noise, as for counts in `suppression`; the number of
+// suppressed diseases is reported as "suppressed_diseases".
+
+use crate::rare_diseases::*;
+use crate::*;
+use differential_privacy::{noisy_count, noisy_mean, PrivacyAccountant};
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::BTreeMap;
+
+// Statistics released per group; a case is in two groups
+const STATISTICS_PER_GROUP: f64 = 4.0;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RegistryStatisticsConfig {
+    // Charged per call and split over the statistics it releases
+    pub epsilon_per_query: f64,
+    pub min_cases: u64,
+    pub max_time_to_diagnosis_days: f64,
+    pub max_physicians_consulted: f64,
+}
+
+impl Default for RegistryStatisticsConfig {
+    fn default() -> Self {
+        RegistryStatisticsConfig {
+            epsilon_per_query: 1.0,
+            min_cases: suppression::SMALL_CELL_THRESHOLD,
+            max_time_to_diagnosis_days: 7_300.0,
+            max_physicians_consulted: 30.0,
+        }
+    }
+}
+
+impl RegistryStatisticsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.epsilon_per_query.is_finite() && self.epsilon_per_query > 0.0) {
+            return Err("Epsilon per query must be positive".to_string());
+        }
+        if !(self.max_time_to_diagnosis_days > 0.0 && self.max_physicians_consulted > 0.0) {
+            return Err("Clamping bounds must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn indicator(condition: bool) -> f64 {
+    if condition {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl RareDiseaseDatabase {
+    pub fn get_diagnostic_statistics(
+        &self,
+        accountant: &mut PrivacyAccountant,
+        config: &RegistryStatisticsConfig,
+    ) -> Result<HashMap<String, f64>, String> {
+        self.get_diagnostic_statistics_seeded(accountant, config, uuid::Uuid::new_v4().as_u64_pair().0)
+    }
+
+    pub fn get_diagnostic_statistics_seeded(
+        &self,
+        accountant: &mut PrivacyAccountant,
+        config: &RegistryStatisticsConfig,
+        seed: u64,
+    ) -> Result<HashMap<String, f64>, String> {
+        config.validate()?;
+        let query_id = format!("registry_statistics_{}", accountant.queries.len());
+        accountant.spend_budget(query_id, config.epsilon_per_query, 0.0, "laplace".to_string())?;
+
+        let epsilon = config.epsilon_per_query / (2.0 * STATISTICS_PER_GROUP);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut stats = HashMap::new();
+        let means = |rng: &mut StdRng, stats: &mut HashMap<String, f64>, prefix: &str, cases: &[&RareDiseaseCase]| {
+            let times: Vec<f64> =
+                cases.iter().filter_map(|c| c.diagnostic_journey.time_to_diagnosis_days).map(f64::from).collect();
+            let physicians: Vec<f64> = cases.iter().map(|c| c.diagnostic_journey.physicians_consulted as f64).collect();
+            let deceased: Vec<f64> = cases.iter().map(|c| indicator(c.is_deceased())).collect();
+            let mut release = |key: &str, value: f64| stats.insert(format!("{}{}", prefix, key), value);
+            release("average_time_to_diagnosis_days", noisy_mean(rng, &times, 0.0, config.max_time_to_diagnosis_days, epsilon));
+            release("average_physicians_consulted", noisy_mean(rng, &physicians, 0.0, config.max_physicians_consulted, epsilon));
+            release("mortality_rate", noisy_mean(rng, &deceased, 0.0, 1.0, epsilon));
+        };
+
+        // Sorted so the same seed draws the same noise for the same statistic
+        let mut cases: Vec<&RareDiseaseCase> = self.cases.values().collect();
+        cases.sort_by(|a, b| a.case_id.cmp(&b.case_id));
+        if (cases.len() as u64) < config.min_cases {
+            return Ok(stats);
+        }
+        means(&mut rng, &mut stats, "", &cases);
+        let diagnosed: Vec<f64> = cases.iter().map(|c| indicator(c.confirmed_diagnosis.is_some())).collect();
+        stats.insert("diagnosis_rate".to_string(), noisy_mean(&mut rng, &diagnosed, 0.0, 1.0, epsilon));
+
+        let mut by_disease: BTreeMap<&str, Vec<&RareDiseaseCase>> = BTreeMap::new();
+        for case in &cases {
+            if let Some(disease) = &case.confirmed_diagnosis {
+                by_disease.entry(disease.orpha_code.as_str()).or_default().push(case);
+            }
+        }
+        let mut suppressed = 0;
+        for (orpha_code, cases) in by_disease {
+            if (cases.len() as u64) < config.min_cases {
+                suppressed += 1;
+                continue;
+            }
+            let prefix = format!("{}/", orpha_code);
+            stats.insert(format!("{}case_count", prefix), noisy_count(&mut rng, cases.len() as u64, epsilon));
+            means(&mut rng, &mut stats, &prefix, &cases);
+        }
+        if suppressed > 0 {
+            stats.insert("suppressed_diseases".to_string(), suppressed as f64);
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_statistics_are_noisy_budgeted_and_suppressed() {
+        let mut db = initialize_rare_disease_database();
+        for seed in 0..30 {
+            db.add_case(db.generate_synthetic_case_seeded("ORPHA:399", seed).unwrap());
+        }
+        for seed in 100..102 {
+            db.add_case(db.generate_synthetic_case_seeded("ORPHA:586", seed).unwrap());
+        }
+
+        let mut accountant = PrivacyAccountant::new(100.0, 1e-5);
+        let precise = RegistryStatisticsConfig { epsilon_per_query: 80.0, ..RegistryStatisticsConfig::default() };
+        let stats = db.get_diagnostic_statistics_seeded(&mut accountant, &precise, 1).unwrap();
+        assert!((stats["ORPHA:399/case_count"] - 30.0).abs() <= 1.0);
+        assert!((stats["diagnosis_rate"] - 1.0).abs() < 0.1);
+        assert!((0.0..=30.0).contains(&stats["ORPHA:399/average_physicians_consulted"]));
+        // Two cystic fibrosis cases are below the minimum
+        assert!(!stats.keys().any(|k| k.starts_with("ORPHA:586")));
+        assert_eq!(stats["suppressed_diseases"], 1.0);
+
+        // Noise differs between calls, and each call is charged until the budget runs out
+        let again = db.get_diagnostic_statistics_seeded(&mut accountant, &RegistryStatisticsConfig::default(), 2).unwrap();
+        assert_ne!(again["average_time_to_diagnosis_days"], stats["average_time_to_diagnosis_days"]);
+        assert_eq!(accountant.queries.len(), 2);
+        assert!(db.get_diagnostic_statistics_seeded(&mut accountant, &precise, 3).is_err());
+    }
+}
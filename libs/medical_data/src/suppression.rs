@@ -0,0 +1,80 @@
+// Small-cell suppression for every published count. A count of 1 to threshold - 1
+// patients is reported as "<threshold" instead of its value, since a cell that small
+// can single out an individual. This is applied whether or not DP noise was added,
+// as a second line of defence. Zero is reported as is: it describes nobody.
+//
+// Suppressing one cell of a distribution is not enough when the total is published
+// too, since the missing cell is the total minus the rest. When exactly one cell is
+// suppressed, the next smallest non-zero cell is suppressed with it.
+
+use crate::*;
+
+pub const SMALL_CELL_THRESHOLD: u64 = 5;
+
+pub fn is_small_cell(count: u64, threshold: u64) -> bool {
+    count > 0 && count < threshold
+}
+
+pub fn suppressed_label(threshold: u64) -> String {
+    format!("<{}", threshold)
+}
+
+pub fn suppress_count(count: u64, threshold: u64) -> serde_json::Value {
+    if is_small_cell(count, threshold) {
+        serde_json::Value::String(suppressed_label(threshold))
+    } else {
+        serde_json::Value::Number(count.into())
+    }
+}
+
+pub fn suppress_distribution(counts: &HashMap<String, u64>, threshold: u64) -> serde_json::Map<String, serde_json::Value> {
+    let mut suppressed: Vec<&String> = counts.iter().filter(|(_, &c)| is_small_cell(c, threshold)).map(|(k, _)| k).collect();
+    if suppressed.len() == 1 {
+        // Ties broken by key so the same data always suppresses the same cell
+        let secondary = counts
+            .iter()
+            .filter(|(k, &c)| c > 0 && !suppressed.contains(k))
+            .min_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)));
+        suppressed.extend(secondary.map(|(k, _)| k));
+    }
+    counts
+        .iter()
+        .map(|(key, &count)| {
+            let value = if suppressed.contains(&key) {
+                serde_json::Value::String(suppressed_label(threshold))
+            } else {
+                serde_json::Value::Number(count.into())
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_cells_and_their_complement_are_suppressed() {
+        assert_eq!(suppress_count(3, 5), serde_json::json!("<5"));
+        assert_eq!(suppress_count(0, 5), serde_json::json!(0));
+        assert_eq!(suppress_count(5, 5), serde_json::json!(5));
+
+        let counts: HashMap<String, u64> =
+            [("male", 40), ("female", 12), ("other", 2), ("unknown", 0)].iter().map(|(k, c)| (k.to_string(), *c)).collect();
+        let cells = suppress_distribution(&counts, 5);
+        assert_eq!(cells["other"], serde_json::json!("<5"));
+        // Otherwise other = total - male - female
+        assert_eq!(cells["female"], serde_json::json!("<5"));
+        assert_eq!(cells["male"], serde_json::json!(40));
+        assert_eq!(cells["unknown"], serde_json::json!(0));
+
+        let mut dataset = MedicalDataset::new("ds".to_string(), "test".to_string(), String::new());
+        for i in 0..3 {
+            dataset.patients.push(Patient::new(format!("p{}", i)));
+        }
+        let stats = dataset.get_statistics();
+        assert_eq!(stats["patient_count"], serde_json::json!("<5"));
+        assert_eq!(stats["observation_count"], serde_json::json!(0));
+    }
+}
@@ -0,0 +1,288 @@
+// Synthetic rare disease cases for benchmarking. Each clinical feature is present with
+// the probability its HPO frequency class stands for, the birth date is placed so
+// that onset falls in one of the disease's age-of-onset bands, the genetic findings
+// fit the disease's genes and inheritance, and the diagnostic journey carries the
+// misdiagnoses typical of a diagnostic odyssey. Generation is driven by a seed, so a
+// benchmark set can be regenerated exactly.
+
+use crate::rare_diseases::*;
+use crate::*;
+use chrono::{Duration, NaiveDate};
+
+// SplitMix64; small, seedable and identical on every platform
+struct CaseRng(u64);
+
+impl CaseRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    // Uniform in [low, high]
+    fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next_u64() % (high - low + 1) as u64) as i64
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.range(0, items.len() as i64 - 1) as usize)
+    }
+}
+
+impl Frequency {
+    // Midpoint of the HPO frequency class; unknown frequencies are taken as even odds
+    pub fn probability(&self) -> f64 {
+        match self {
+            Frequency::Obligate => 1.0,
+            Frequency::VeryFrequent => 0.895,
+            Frequency::Frequent => 0.545,
+            Frequency::Occasional => 0.17,
+            Frequency::VeryRare => 0.025,
+            Frequency::Excluded => 0.0,
+            Frequency::Unknown => 0.5,
+        }
+    }
+}
+
+impl AgeOfOnset {
+    // Age band in days
+    fn day_range(&self) -> (i64, i64) {
+        const YEAR: i64 = 365;
+        match self {
+            AgeOfOnset::Antenatal | AgeOfOnset::Neonatal => (0, 27),
+            AgeOfOnset::Infancy => (28, 2 * YEAR - 1),
+            AgeOfOnset::Childhood => (2 * YEAR, 11 * YEAR - 1),
+            AgeOfOnset::Adolescent => (11 * YEAR, 19 * YEAR - 1),
+            AgeOfOnset::Adult => (19 * YEAR, 65 * YEAR - 1),
+            AgeOfOnset::Elderly => (65 * YEAR, 90 * YEAR),
+            AgeOfOnset::AllAges => (0, 90 * YEAR),
+        }
+    }
+}
+
+// Well-known pathogenic variants with their population allele frequency; genes not
+// listed get a generic missense change
+static KNOWN_VARIANTS: &[(&str, &str, f64)] = &[
+    ("CFTR", "c.1521_1523del (p.Phe508del)", 0.0136),
+    ("CFTR", "c.1624G>T (p.Gly542Ter)", 0.0004),
+    ("CFTR", "c.1652G>A (p.Gly551Asp)", 0.0003),
+];
+
+static COMMON_MISDIAGNOSES: &[&str] = &[
+    "Anxiety disorder",
+    "Viral infection",
+    "Asthma",
+    "Gastroenteritis",
+    "Failure to thrive",
+    "Depression",
+];
+
+fn sample_variant(rng: &mut CaseRng, gene: &Gene) -> (String, Option<f64>) {
+    if matches!(gene.disease_mechanism, DiseaseMechanism::Triplet) || gene.symbol == "HTT" {
+        // Fully penetrant CAG expansion
+        return (format!("c.52CAG[{}]", rng.range(40, 55)), None);
+    }
+    let known: Vec<&(&str, &str, f64)> = KNOWN_VARIANTS.iter().filter(|(g, _, _)| *g == gene.symbol).collect();
+    if let Some((_, variant, frequency)) = rng.pick(&known) {
+        return (variant.to_string(), Some(*frequency));
+    }
+    let bases = ["A", "C", "G", "T"];
+    let reference = rng.range(0, 3) as usize;
+    let alternate = (reference + rng.range(1, 3) as usize) % 4;
+    (format!("c.{}{}>{}", rng.range(100, 4000), bases[reference], bases[alternate]), Some(1e-5))
+}
+
+fn sample_variants(rng: &mut CaseRng, disease: &RareDisease, gender: &Gender) -> Vec<GeneticVariant> {
+    let inheritance = disease.inheritance_pattern.first().cloned().unwrap_or(InheritancePattern::Unknown);
+    let mut variants = Vec::new();
+    for gene in &disease.genes {
+        let variant = |name: String, frequency: Option<f64>, zygosity: Zygosity| GeneticVariant {
+            gene: gene.symbol.clone(),
+            variant: name,
+            zygosity,
+            classification: VariantClassification::Pathogenic,
+            inheritance: Some(inheritance.clone()),
+            population_frequency: frequency,
+            pathogenicity_score: Some(0.9),
+        };
+        match inheritance {
+            // Biallelic: the same variant twice, or two different ones in trans
+            InheritancePattern::AutosomalRecessive => {
+                let (first, first_frequency) = sample_variant(rng, gene);
+                let (second, second_frequency) = sample_variant(rng, gene);
+                if first == second {
+                    variants.push(variant(first, first_frequency, Zygosity::Homozygous));
+                } else {
+                    variants.push(variant(first, first_frequency, Zygosity::Compound));
+                    variants.push(variant(second, second_frequency, Zygosity::Compound));
+                }
+            }
+            InheritancePattern::XLinkedDominant | InheritancePattern::XLinkedRecessive
+                if matches!(gender, Gender::Male) =>
+            {
+                let (name, frequency) = sample_variant(rng, gene);
+                variants.push(variant(name, frequency, Zygosity::Hemizygous));
+            }
+            // Homoplasmic mitochondrial variants are reported as homozygous
+            InheritancePattern::Mitochondrial => {
+                let (name, frequency) = sample_variant(rng, gene);
+                variants.push(variant(name, frequency, Zygosity::Homozygous));
+            }
+            _ => {
+                let (name, frequency) = sample_variant(rng, gene);
+                variants.push(variant(name, frequency, Zygosity::Heterozygous));
+            }
+        }
+    }
+    variants
+}
+
+fn day_string(day: NaiveDate) -> String {
+    day.format("%Y-%m-%d").to_string()
+}
+
+impl RareDiseaseDatabase {
+    pub fn generate_synthetic_case_seeded(&self, disease_orpha_code: &str, seed: u64) -> Option<RareDiseaseCase> {
+        let disease = self.get_disease(disease_orpha_code)?;
+        let mut rng = CaseRng(seed);
+
+        let mut patient = Patient::new(format!("synthetic_{:016x}", rng.next_u64()));
+        // X-linked recessive disease is seen almost only in males
+        let male_probability = match disease.inheritance_pattern.first() {
+            Some(InheritancePattern::XLinkedRecessive) => 0.95,
+            _ => 0.5,
+        };
+        patient.set_gender(if rng.chance(male_probability) { Gender::Male } else { Gender::Female });
+        patient.add_name(HumanName {
+            use_type: Some("official".to_string()),
+            text: Some("Synthetic Patient".to_string()),
+            family: Some("Patient".to_string()),
+            given: vec!["Synthetic".to_string()],
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+            period: None,
+        });
+
+        let mut presenting_symptoms: Vec<ClinicalFeature> = disease
+            .clinical_features
+            .iter()
+            .filter(|feature| rng.chance(feature.frequency.probability()))
+            .cloned()
+            .collect();
+        // A case presents with something: fall back to the most frequent feature
+        if presenting_symptoms.is_empty() {
+            let most_frequent = disease
+                .clinical_features
+                .iter()
+                .max_by(|a, b| a.frequency.probability().total_cmp(&b.frequency.probability()));
+            presenting_symptoms.extend(most_frequent.cloned());
+        }
+
+        // Presentation within 2015-2019, onset up to a year earlier, and a diagnostic
+        // delay of one month to five years
+        let epoch = NaiveDate::from_ymd_opt(2015, 1, 1)?;
+        let presentation = epoch + Duration::days(rng.range(0, 5 * 365 - 1));
+        let onset_band = rng.pick(&disease.age_of_onset).cloned().unwrap_or(AgeOfOnset::AllAges);
+        let (youngest, oldest) = onset_band.day_range();
+        let onset = presentation - Duration::days(rng.range(0, 365));
+        let birth = onset - Duration::days(rng.range(youngest, oldest));
+        patient.set_birth_date(day_string(birth));
+        let delay = rng.range(30, 5 * 365);
+        let diagnosis = presentation + Duration::days(delay);
+
+        // Each misdiagnosis is one more physician consulted before the right one
+        let mut candidates: Vec<&str> = disease.differential_diagnosis.iter().map(|d| d.as_str()).collect();
+        candidates.extend(COMMON_MISDIAGNOSES.iter().copied());
+        let mut misdiagnoses: Vec<String> = Vec::new();
+        for _ in 0..rng.range(0, 3) {
+            if let Some(candidate) = rng.pick(&candidates) {
+                if !misdiagnoses.iter().any(|m| m == *candidate) {
+                    misdiagnoses.push(candidate.to_string());
+                }
+            }
+        }
+        let physicians_consulted = misdiagnoses.len() as u32 + rng.range(1, 4) as u32;
+
+        let variants = sample_variants(&mut rng, disease, &patient.gender.clone().unwrap_or(Gender::Unknown));
+        let genetic_testing = if variants.is_empty() {
+            Vec::new()
+        } else {
+            vec![GeneticTest {
+                test_type: if disease.genes.len() == 1 { GeneticTestType::SingleGene } else { GeneticTestType::GenePanel },
+                genes_tested: disease.genes.iter().map(|g| g.symbol.clone()).collect(),
+                results: variants,
+                interpretation: format!("Pathogenic findings consistent with {}", disease.name),
+                date_performed: day_string(diagnosis),
+                laboratory: "Synthetic Genomics Laboratory".to_string(),
+            }]
+        };
+
+        Some(RareDiseaseCase {
+            case_id: format!("case_{:016x}", rng.next_u64()),
+            patient,
+            presenting_symptoms,
+            family_history: Vec::new(),
+            diagnostic_journey: DiagnosticJourney {
+                initial_presentation_date: day_string(presentation),
+                diagnosis_date: Some(day_string(diagnosis)),
+                time_to_diagnosis_days: Some(delay as u32),
+                physicians_consulted,
+                misdiagnoses,
+                diagnostic_tests: Vec::new(),
+                referrals: Vec::new(),
+            },
+            confirmed_diagnosis: Some(disease.clone()),
+            differential_diagnoses: Vec::new(),
+            genetic_testing,
+            treatment_history: Vec::new(),
+            outcome: None,
+            case_notes: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_cases_are_reproducible_and_consistent() {
+        let db = initialize_rare_disease_database();
+        let case = db.generate_synthetic_case_seeded("ORPHA:586", 7).unwrap();
+        let again = db.generate_synthetic_case_seeded("ORPHA:586", 7).unwrap();
+        assert_eq!(case.case_id, again.case_id);
+        assert_eq!(case.patient.birth_date, again.patient.birth_date);
+
+        // Cystic fibrosis starts in the neonatal period or infancy
+        let birth = mortality::parse_day(case.patient.birth_date.as_deref().unwrap()).unwrap();
+        let presentation = mortality::parse_day(&case.diagnostic_journey.initial_presentation_date).unwrap();
+        assert!((presentation - birth).num_days() <= 2 * 365 + 365);
+        let variants = &case.genetic_testing[0].results;
+        assert!(variants.iter().all(|v| v.gene == "CFTR"));
+        assert!(matches!(variants[0].zygosity, Zygosity::Homozygous | Zygosity::Compound));
+        assert!(!case.presenting_symptoms.is_empty());
+
+        // Over many cases a Frequent (30-79%) feature is sometimes absent
+        let with_feature = (0..200)
+            .filter_map(|seed| db.generate_synthetic_case_seeded("ORPHA:399", seed))
+            .filter(|c| c.presenting_symptoms.iter().any(|s| s.hpo_id == "HP:0000726"))
+            .count();
+        assert!((60..180).contains(&with_feature));
+        let hd = db.generate_synthetic_case_seeded("ORPHA:399", 1).unwrap();
+        assert!(hd.genetic_testing[0].results[0].variant.starts_with("c.52CAG["));
+    }
+}
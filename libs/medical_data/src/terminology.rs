@@ -0,0 +1,170 @@
+// Terminology-bound codes. The constructors below only accept codes that are well
+// formed for their system and present in the embedded tables, and fill in the
+// system URI and display name, so a typo in a code or system fails at construction
+// instead of silently never matching a feature or panel. The tables cover the codes
+// the feature schemas, panels and rare disease database use; extend them as those grow.
+
+use crate::panels::LOINC_SYSTEM;
+use crate::validation::{is_valid_icd10_code, is_valid_loinc_code, is_valid_snomed_code};
+use crate::*;
+
+pub const SNOMED_SYSTEM: &str = "http://snomed.info/sct";
+pub const ICD10_SYSTEM: &str = "http://hl7.org/fhir/sid/icd-10";
+pub const ORPHANET_SYSTEM: &str = "http://www.orpha.net";
+
+pub static LOINC_CODES: &[(&str, &str)] = &[
+    ("85354-9", "Blood pressure panel with all children optional"),
+    ("8480-6", "Systolic blood pressure"),
+    ("8462-4", "Diastolic blood pressure"),
+    ("58410-2", "CBC panel - Blood by Automated count"),
+    ("6690-2", "Leukocytes [#/volume] in Blood by Automated count"),
+    ("789-8", "Erythrocytes [#/volume] in Blood by Automated count"),
+    ("718-7", "Hemoglobin [Mass/volume] in Blood"),
+    ("4544-3", "Hematocrit [Volume Fraction] of Blood by Automated count"),
+    ("787-2", "MCV [Entitic volume] by Automated count"),
+    ("785-6", "MCH [Entitic mass] by Automated count"),
+    ("786-4", "MCHC [Mass/volume] by Automated count"),
+    ("777-3", "Platelets [#/volume] in Blood by Automated count"),
+    ("2345-7", "Glucose [Mass/volume] in Serum or Plasma"),
+    ("4548-4", "Hemoglobin A1c/Hemoglobin.total in Blood"),
+    ("2160-0", "Creatinine [Mass/volume] in Serum or Plasma"),
+    ("2093-3", "Cholesterol [Mass/volume] in Serum or Plasma"),
+    ("8867-4", "Heart rate"),
+    ("9279-1", "Respiratory rate"),
+    ("8310-5", "Body temperature"),
+    ("59408-5", "Oxygen saturation in Arterial blood by Pulse oximetry"),
+    ("29463-7", "Body weight"),
+    ("8302-2", "Body height"),
+    ("39156-5", "Body mass index (BMI) [Ratio]"),
+];
+
+pub static SNOMED_CODES: &[(&str, &str)] = &[
+    ("73211009", "Diabetes mellitus"),
+    ("46635009", "Diabetes mellitus type 1"),
+    ("44054006", "Diabetes mellitus type 2"),
+    ("38341003", "Hypertensive disorder"),
+    ("22298006", "Myocardial infarction"),
+    ("195967001", "Asthma"),
+    ("13645005", "Chronic obstructive lung disease"),
+    ("190905008", "Cystic fibrosis"),
+    ("58756001", "Huntington's chorea"),
+    ("7573000", "Classical phenylketonuria"),
+];
+
+pub static ICD10_CODES: &[(&str, &str)] = &[
+    ("E10", "Type 1 diabetes mellitus"),
+    ("E11", "Type 2 diabetes mellitus"),
+    ("E11.9", "Type 2 diabetes mellitus without complications"),
+    ("I10", "Essential (primary) hypertension"),
+    ("I21", "Acute myocardial infarction"),
+    ("J44", "Other chronic obstructive pulmonary disease"),
+    ("J45", "Asthma"),
+    ("E84", "Cystic fibrosis"),
+    ("E84.0", "Cystic fibrosis with pulmonary manifestations"),
+    ("E70.0", "Classical phenylketonuria"),
+    ("G10", "Huntington disease"),
+];
+
+// Codes without the "ORPHA:" prefix used in the rare disease database
+pub static ORPHANET_CODES: &[(&str, &str)] = &[("399", "Huntington disease"), ("586", "Cystic fibrosis")];
+
+// LOINC check digit: Luhn over the code without the dash
+fn loinc_check_digit_valid(code: &str) -> bool {
+    let (body, check) = match code.split_once('-') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let sum: u32 = body
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| {
+            let d = if i % 2 == 0 { d * 2 } else { d };
+            d / 10 + d % 10
+        })
+        .sum();
+    check.parse::<u32>().is_ok_and(|c| (10 - sum % 10) % 10 == c)
+}
+
+pub fn lookup_display(system: &str, code: &str) -> Option<&'static str> {
+    let table = match system {
+        LOINC_SYSTEM => LOINC_CODES,
+        SNOMED_SYSTEM => SNOMED_CODES,
+        ICD10_SYSTEM => ICD10_CODES,
+        ORPHANET_SYSTEM => ORPHANET_CODES,
+        _ => return None,
+    };
+    table.iter().find(|(c, _)| *c == code).map(|(_, display)| *display)
+}
+
+fn bound_coding(system: &str, name: &str, code: &str, well_formed: bool) -> Result<Coding, String> {
+    if !well_formed {
+        return Err(format!("'{}' is not a valid {} code", code, name));
+    }
+    let display = lookup_display(system, code).ok_or(format!("{} code {} is not in the terminology table", name, code))?;
+    Ok(create_coding(system, code, display))
+}
+
+impl Coding {
+    pub fn loinc(code: &str) -> Result<Self, String> {
+        bound_coding(LOINC_SYSTEM, "LOINC", code, is_valid_loinc_code(code) && loinc_check_digit_valid(code))
+    }
+
+    pub fn snomed(code: &str) -> Result<Self, String> {
+        bound_coding(SNOMED_SYSTEM, "SNOMED CT", code, is_valid_snomed_code(code))
+    }
+
+    pub fn icd10(code: &str) -> Result<Self, String> {
+        bound_coding(ICD10_SYSTEM, "ICD-10", code, is_valid_icd10_code(code))
+    }
+
+    pub fn orphanet(code: &str) -> Result<Self, String> {
+        let code = code.strip_prefix("ORPHA:").unwrap_or(code);
+        bound_coding(ORPHANET_SYSTEM, "Orphanet", code, !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()))
+    }
+}
+
+impl CodeableConcept {
+    pub fn loinc(code: &str) -> Result<Self, String> {
+        Ok(create_codeable_concept(Coding::loinc(code)?, None))
+    }
+
+    pub fn snomed(code: &str) -> Result<Self, String> {
+        Ok(create_codeable_concept(Coding::snomed(code)?, None))
+    }
+
+    pub fn icd10(code: &str) -> Result<Self, String> {
+        Ok(create_codeable_concept(Coding::icd10(code)?, None))
+    }
+
+    pub fn orphanet(code: &str) -> Result<Self, String> {
+        Ok(create_codeable_concept(Coding::orphanet(code)?, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bound_constructors_fill_system_and_display() {
+        let hemoglobin = CodeableConcept::loinc("718-7").unwrap();
+        assert_eq!(hemoglobin.coding[0].system.as_deref(), Some(LOINC_SYSTEM));
+        assert_eq!(hemoglobin.coding[0].display.as_deref(), Some("Hemoglobin [Mass/volume] in Blood"));
+        assert!(hemoglobin.has_loinc_code("718-7"));
+        // Wrong check digit, unknown code, malformed code
+        assert!(CodeableConcept::loinc("718-6").is_err());
+        assert!(CodeableConcept::loinc("1234-5").is_err());
+        assert!(CodeableConcept::icd10("e11").is_err());
+
+        assert_eq!(CodeableConcept::icd10("E11").unwrap().coding[0].display.as_deref(), Some("Type 2 diabetes mellitus"));
+        assert_eq!(CodeableConcept::snomed("44054006").unwrap().coding[0].system.as_deref(), Some(SNOMED_SYSTEM));
+        assert_eq!(Coding::orphanet("ORPHA:586").unwrap().code.as_deref(), Some("586"));
+
+        // Every table entry is accepted by its own constructor
+        assert!(LOINC_CODES.iter().all(|(code, _)| Coding::loinc(code).is_ok()));
+        assert!(SNOMED_CODES.iter().all(|(code, _)| Coding::snomed(code).is_ok()));
+        assert!(ICD10_CODES.iter().all(|(code, _)| Coding::icd10(code).is_ok()));
+    }
+}
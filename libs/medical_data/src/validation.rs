@@ -48,8 +48,8 @@ pub fn is_valid_icd10_code(code: &str) -> bool {
 }
 
 pub fn is_valid_loinc_code(code: &str) -> bool {
-    // LOINC codes: 5-6 digits followed by dash and check digit
-    let loinc_regex = Regex::new(r"^[0-9]{5,6}-[0-9]$").unwrap();
+    // LOINC codes: 1-7 digits followed by dash and check digit
+    let loinc_regex = Regex::new(r"^[0-9]{1,7}-[0-9]$").unwrap();
     loinc_regex.is_match(code)
 }
 
@@ -252,15 +252,16 @@ fn validate_npi_checksum(npi: &str) -> Result<(), String> {
         return Err("NPI must contain only digits".to_string());
     }
     
-    // Luhn algorithm for NPI validation
+    // Luhn check over the NPI with its "80840" card-issuer prefix; the prefix
+    // contributes a constant 24, and the doubled digits are the even positions
     let digits: Vec<u32> = npi.chars()
         .map(|c| c.to_digit(10).unwrap())
         .collect();
     
-    let mut sum = 0;
+    let mut sum = 24;
     for (i, &digit) in digits.iter().enumerate() {
         let mut d = digit;
-        if i % 2 == 1 {
+        if i % 2 == 0 && i < 9 {
             d *= 2;
             if d > 9 {
                 d = d / 10 + d % 10;
@@ -0,0 +1,238 @@
+// FHIR SampledData waveforms (ECG, EEG). `data` is a space-separated list of
+// decimals, or the markers "E" (error), "L" (below lower_limit) and "U" (above
+// upper_limit); the physical value is origin + factor * decimal. With more than one
+// dimension the samples are interleaved, one value per channel per time point, and
+// `period` is the time in milliseconds between time points.
+
+use crate::*;
+
+impl SampledData {
+    pub fn new(origin: Quantity, period: f64, dimensions: u32) -> Self {
+        SampledData {
+            origin,
+            period,
+            factor: None,
+            lower_limit: None,
+            upper_limit: None,
+            dimensions,
+            data: None,
+        }
+    }
+
+    // Interleaved physical values; NaN is written as "E"
+    pub fn encode(origin: Quantity, period: f64, dimensions: u32, factor: Option<f64>, values: &[f64]) -> Result<Self, String> {
+        let mut sampled = SampledData::new(origin, period, dimensions);
+        sampled.factor = factor;
+        sampled.validate_header()?;
+        if !values.len().is_multiple_of(dimensions as usize) {
+            return Err(format!("{} values do not split evenly into {} dimensions", values.len(), dimensions));
+        }
+
+        let offset = sampled.origin_value();
+        let factor = sampled.factor_value();
+        let tokens: Vec<String> = values
+            .iter()
+            .map(|v| if v.is_finite() { format!("{}", (v - offset) / factor) } else { "E".to_string() })
+            .collect();
+        sampled.data = Some(tokens.join(" "));
+        Ok(sampled)
+    }
+
+    fn origin_value(&self) -> f64 {
+        self.origin.value.unwrap_or(0.0)
+    }
+
+    fn factor_value(&self) -> f64 {
+        self.factor.unwrap_or(1.0)
+    }
+
+    fn validate_header(&self) -> Result<(), String> {
+        if !(self.period.is_finite() && self.period > 0.0) {
+            return Err("SampledData period must be positive".to_string());
+        }
+        if self.dimensions == 0 {
+            return Err("SampledData dimensions must be at least 1".to_string());
+        }
+        if self.factor.is_some_and(|f| !f.is_finite() || f == 0.0) {
+            return Err("SampledData factor must be finite and non-zero".to_string());
+        }
+        if let (Some(lower), Some(upper)) = (self.lower_limit, self.upper_limit) {
+            if lower > upper {
+                return Err("SampledData lower_limit exceeds upper_limit".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.decode().map(|_| ())
+    }
+
+    // Physical values, interleaved. "E" decodes to NaN; "L" and "U" decode to the
+    // lower and upper limit, the tightest bound the device reported.
+    pub fn decode(&self) -> Result<Vec<f64>, String> {
+        self.validate_header()?;
+        let data = match &self.data {
+            Some(data) => data,
+            None => return Ok(Vec::new()),
+        };
+
+        let offset = self.origin_value();
+        let factor = self.factor_value();
+        let mut values = Vec::new();
+        for token in data.split_whitespace() {
+            let value = match token {
+                "E" => f64::NAN,
+                "L" => self.lower_limit.ok_or("SampledData has an L marker but no lower_limit")?,
+                "U" => self.upper_limit.ok_or("SampledData has a U marker but no upper_limit")?,
+                _ => {
+                    let raw: f64 = token.parse().map_err(|_| format!("Invalid SampledData value '{}'", token))?;
+                    offset + factor * raw
+                }
+            };
+            values.push(value);
+        }
+        if !values.len().is_multiple_of(self.dimensions as usize) {
+            return Err(format!("{} values do not split evenly into {} dimensions", values.len(), self.dimensions));
+        }
+        Ok(values)
+    }
+
+    // One vector per channel
+    pub fn channels(&self) -> Result<Vec<Vec<f64>>, String> {
+        let values = self.decode()?;
+        let dimensions = self.dimensions as usize;
+        let mut channels = vec![Vec::with_capacity(values.len() / dimensions); dimensions];
+        for (i, value) in values.into_iter().enumerate() {
+            channels[i % dimensions].push(value);
+        }
+        Ok(channels)
+    }
+
+    // Time points per channel
+    pub fn sample_count(&self) -> Result<usize, String> {
+        Ok(self.decode()?.len() / self.dimensions as usize)
+    }
+
+    pub fn duration_ms(&self) -> Result<f64, String> {
+        Ok(self.sample_count()? as f64 * self.period)
+    }
+
+    // Averages each run of `factor` time points (a box filter, so the result does not
+    // alias) and stretches the period to match. Error samples are left out of the
+    // averages; a run with nothing but errors stays an error.
+    pub fn downsample(&self, factor: usize) -> Result<SampledData, String> {
+        if factor == 0 {
+            return Err("Downsampling factor must be at least 1".to_string());
+        }
+        let channels = self.channels()?;
+        let reduced: Vec<Vec<f64>> = channels.iter().map(|c| c.chunks(factor).map(finite_mean).collect()).collect();
+
+        let points = reduced.first().map(|c| c.len()).unwrap_or(0);
+        let mut interleaved = Vec::with_capacity(points * reduced.len());
+        for t in 0..points {
+            interleaved.extend(reduced.iter().map(|c| c[t]));
+        }
+
+        let mut result = SampledData::encode(
+            self.origin.clone(),
+            self.period * factor as f64,
+            self.dimensions,
+            self.factor,
+            &interleaved,
+        )?;
+        result.lower_limit = self.lower_limit;
+        result.upper_limit = self.upper_limit;
+        Ok(result)
+    }
+
+    // Fixed-length model features: each channel split into `points_per_channel` equal
+    // spans and averaged, concatenated channel by channel
+    pub fn to_feature_vector(&self, points_per_channel: usize) -> Result<Vec<f64>, String> {
+        if points_per_channel == 0 {
+            return Err("points_per_channel must be at least 1".to_string());
+        }
+        let channels = self.channels()?;
+        let samples = channels.first().map(|c| c.len()).unwrap_or(0);
+        if samples < points_per_channel {
+            return Err(format!("Waveform has {} samples per channel, fewer than {} features", samples, points_per_channel));
+        }
+
+        let mut features = Vec::with_capacity(points_per_channel * channels.len());
+        for channel in &channels {
+            for point in 0..points_per_channel {
+                let start = point * samples / points_per_channel;
+                let end = (point + 1) * samples / points_per_channel;
+                features.push(finite_mean(&channel[start..end]));
+            }
+        }
+        Ok(features)
+    }
+}
+
+fn finite_mean(values: &[f64]) -> f64 {
+    let finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if finite.is_empty() {
+        return f64::NAN;
+    }
+    finite.iter().sum::<f64>() / finite.len() as f64
+}
+
+impl Observation {
+    // Waveforms carried by the observation: its value, then one per component (ECG leads)
+    pub fn sampled_data(&self) -> Vec<&SampledData> {
+        let mut waveforms = Vec::new();
+        if let Some(ObservationValue::SampledData(sampled)) = &self.value {
+            waveforms.push(sampled);
+        }
+        for component in &self.component {
+            if let Some(ObservationValue::SampledData(sampled)) = &component.value {
+                waveforms.push(sampled);
+            }
+        }
+        waveforms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampled_data_round_trip_and_downsampling() {
+        let origin = create_quantity(0.0, "mV", None, None);
+        // Two interleaved leads at 2 ms
+        let values = [0.1, -0.1, 0.3, -0.3, f64::NAN, -0.5, 0.7, -0.7];
+        let sampled = SampledData::encode(origin.clone(), 2.0, 2, Some(0.1), &values).unwrap();
+        let decoded = sampled.decode().unwrap();
+        assert!(decoded[4].is_nan());
+        assert!((decoded[6] - 0.7).abs() < 1e-9);
+        assert_eq!(sampled.sample_count().unwrap(), 4);
+        assert_eq!(sampled.duration_ms().unwrap(), 8.0);
+
+        let channels = sampled.channels().unwrap();
+        assert!((channels[1][3] + 0.7).abs() < 1e-9);
+
+        let halved = sampled.downsample(2).unwrap();
+        assert_eq!(halved.period, 4.0);
+        let lead_one = &halved.channels().unwrap()[0];
+        assert!((lead_one[0] - 0.2).abs() < 1e-9);
+        assert!((lead_one[1] - 0.7).abs() < 1e-9);
+
+        assert_eq!(sampled.to_feature_vector(2).unwrap().len(), 4);
+        assert!(sampled.to_feature_vector(5).is_err());
+
+        // Markers, limits and malformed payloads
+        let mut clipped = SampledData::new(origin, 1.0, 1);
+        clipped.data = Some("1 L U 2".to_string());
+        assert!(clipped.decode().is_err());
+        clipped.lower_limit = Some(-5.0);
+        clipped.upper_limit = Some(5.0);
+        assert_eq!(clipped.decode().unwrap(), vec![1.0, -5.0, 5.0, 2.0]);
+        clipped.dimensions = 3;
+        assert!(clipped.validate().is_err());
+        clipped.dimensions = 1;
+        clipped.data = Some("1 x".to_string());
+        assert!(clipped.validate().is_err());
+    }
+}
@@ -0,0 +1,367 @@
+// Synthetic load for capacity testing. Simulates a number of hospitals against a
+// local replica: each is onboarded with its own secp256k1 identity, then submits
+// gradient updates of the model's size and diagnosis queries at the configured
+// rates until the run ends. Requests go out on schedule whether or not earlier ones
+// have returned, so a canister that falls behind shows up as latency rather than as
+// a lower offered rate.
+//
+// The report gives client-side latency percentiles per request kind and, from each
+// canister's `get_canister_metrics` before and after the run, the instructions every
+// instrumented method used and how far heap and stable memory grew. The canisters
+// must run without a governance canister configured so onboarding and the task
+// launch go through unapproved; the aggregator's privacy budget caps each hospital
+// at 10 / --privacy-budget updates.
+//
+//   cargo run --release -p load_generator -- --aggregator <canister id> \
+//       --inference <canister id> --hospitals 50 --update-rate 2 --query-rate 30 --duration 300
+
+mod report;
+
+use candid::{CandidType, Decode, Deserialize, Encode, Principal, Reserved};
+use governance::CanisterMetrics;
+use ic_agent::identity::Secp256k1Identity;
+use ic_agent::Agent;
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use report::{Kind, Sample};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const MODEL_VERSION: &str = "load-test";
+
+// Symptoms the inference canister's knowledge base recognizes
+const SYMPTOMS: &[&str] = &[
+    "involuntary_movements",
+    "chorea",
+    "cognitive_decline",
+    "chronic_cough",
+    "thick_mucus",
+    "muscle_weakness",
+    "double_vision",
+    "drooping_eyelids",
+    "muscle_atrophy",
+    "fasciculations",
+    "liver_problems",
+    "tremor",
+];
+
+// Mirrors of the canisters' argument types
+#[derive(CandidType)]
+struct GradientUpdate {
+    institution_id: String,
+    model_version: String,
+    gradients: Vec<f32>,
+    sample_count: u32,
+    privacy_budget: f64,
+    timestamp: u64,
+    signature: Vec<u8>,
+    correlation_id: Option<String>,
+}
+
+#[derive(CandidType)]
+struct MedicalQuery {
+    patient_id: String,
+    symptoms: Vec<String>,
+    medical_history: Vec<String>,
+    timestamp: u64,
+    correlation_id: Option<String>,
+}
+
+#[derive(CandidType)]
+struct ModelWeights {
+    version: String,
+    weights: Vec<f32>,
+    metadata: HashMap<String, String>,
+    threshold_signature: Vec<u8>,
+    correlation_id: Option<String>,
+}
+
+struct Options {
+    replica: String,
+    aggregator: Principal,
+    inference: Option<Principal>,
+    hospitals: usize,
+    // Per hospital, per minute
+    update_rate: f64,
+    query_rate: f64,
+    duration: Duration,
+    gradient_size: usize,
+    privacy_budget: f64,
+    // Updates before the round aggregates; the default keeps it open for the run
+    round_target: u32,
+}
+
+struct Hospital {
+    institution_id: String,
+    agent: Agent,
+    key: SigningKey,
+}
+
+fn option<T: FromStr>(values: &mut HashMap<String, String>, name: &str) -> Result<Option<T>, String> {
+    values
+        .remove(name)
+        .map(|value| value.parse().map_err(|_| format!("Invalid value for --{}: {}", name, value)))
+        .transpose()
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        while let Some(flag) = args.next() {
+            let name = flag.strip_prefix("--").ok_or_else(|| format!("Unexpected argument {}", flag))?;
+            let value = args.next().ok_or_else(|| format!("--{} needs a value", name))?;
+            values.insert(name.to_string(), value);
+        }
+
+        let options = Options {
+            replica: option(&mut values, "replica")?.unwrap_or_else(|| "http://127.0.0.1:4943".to_string()),
+            aggregator: option(&mut values, "aggregator")?.ok_or("--aggregator is required")?,
+            inference: option(&mut values, "inference")?,
+            hospitals: option(&mut values, "hospitals")?.unwrap_or(10),
+            update_rate: option(&mut values, "update-rate")?.unwrap_or(1.0),
+            query_rate: option(&mut values, "query-rate")?.unwrap_or(10.0),
+            duration: Duration::from_secs(option(&mut values, "duration")?.unwrap_or(60)),
+            gradient_size: option(&mut values, "gradient-size")?.unwrap_or(100_000),
+            privacy_budget: option(&mut values, "privacy-budget")?.unwrap_or(0.001),
+            round_target: option(&mut values, "round-target")?.unwrap_or(1_000_000),
+        };
+        if let Some(unknown) = values.keys().next() {
+            return Err(format!("Unknown option --{}", unknown));
+        }
+        if options.hospitals == 0 || options.gradient_size == 0 {
+            return Err("--hospitals and --gradient-size must be positive".to_string());
+        }
+        if [options.update_rate, options.query_rate].iter().any(|rate| !(rate.is_finite() && *rate >= 0.0)) {
+            return Err("Rates must be non-negative requests per minute".to_string());
+        }
+        Ok(options)
+    }
+
+    fn rate(&self, kind: Kind) -> f64 {
+        match kind {
+            Kind::Update => self.update_rate,
+            Kind::Query if self.inference.is_some() => self.query_rate,
+            Kind::Query => 0.0,
+        }
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+fn new_key() -> SigningKey {
+    SigningKey::from(&k256::SecretKey::random(&mut rand::thread_rng()))
+}
+
+async fn agent(replica: &str, key: &SigningKey) -> Result<Agent, String> {
+    let identity = Secp256k1Identity::from_private_key(k256::SecretKey::from(key));
+    let agent = Agent::builder().with_url(replica).with_identity(identity).build().map_err(|e| e.to_string())?;
+    // A local replica's root key is not the mainnet one
+    agent.fetch_root_key().await.map_err(|e| format!("Cannot reach replica at {}: {}", replica, e))?;
+    Ok(agent)
+}
+
+// An update call to a method returning Result<R, String>
+async fn call<R: CandidType + for<'de> Deserialize<'de>>(
+    agent: &Agent,
+    canister: Principal,
+    method: &str,
+    arg: Vec<u8>,
+) -> Result<R, String> {
+    let reply = agent.update(&canister, method).with_arg(arg).call_and_wait().await.map_err(|e| format!("{}: {}", method, e))?;
+    Decode!(&reply, Result<R, String>).map_err(|e| format!("{}: {}", method, e))?.map_err(|e| format!("{}: {}", method, e))
+}
+
+async fn query<R: CandidType + for<'de> Deserialize<'de>>(
+    agent: &Agent,
+    canister: Principal,
+    method: &str,
+    arg: Vec<u8>,
+) -> Result<R, String> {
+    let reply = agent.query(&canister, method).with_arg(arg).call().await.map_err(|e| format!("{}: {}", method, e))?;
+    Decode!(&reply, R).map_err(|e| format!("{}: {}", method, e))
+}
+
+async fn canister_metrics(agent: &Agent, canister: Principal) -> Result<CanisterMetrics, String> {
+    query(agent, canister, "get_canister_metrics", Encode!().unwrap()).await
+}
+
+impl Hospital {
+    // Application through activation, with the operator approving the admission
+    async fn onboard(options: Arc<Options>, operator: Agent, institution_id: String) -> Result<Hospital, String> {
+        let key = new_key();
+        let hospital = Hospital { agent: agent(&options.replica, &key).await?, institution_id, key };
+        let id = &hospital.institution_id;
+        let aggregator = options.aggregator;
+
+        let name = format!("Load test hospital {}", id);
+        call::<Reserved>(&hospital.agent, aggregator, "apply_for_onboarding", Encode!(id, &name).unwrap()).await?;
+        let documents = vec![("operating_license".to_string(), format!("{:x}", Sha256::digest(id.as_bytes())))];
+        call::<Reserved>(&hospital.agent, aggregator, "submit_onboarding_documents", Encode!(id, &documents).unwrap()).await?;
+        call::<Reserved>(&operator, aggregator, "approve_onboarding", Encode!(id).unwrap()).await?;
+        let public_key = hospital.key.verifying_key().to_sec1_bytes().to_vec();
+        call::<Reserved>(&hospital.agent, aggregator, "register_institution_key", Encode!(id, &public_key).unwrap()).await?;
+        call::<Reserved>(&hospital.agent, aggregator, "submit_trial_update", Encode!(&hospital.gradient_update(&options)).unwrap())
+            .await?;
+        call::<Reserved>(&hospital.agent, aggregator, "activate_institution", Encode!(id).unwrap()).await?;
+        Ok(hospital)
+    }
+
+    fn gradient_update(&self, options: &Options) -> GradientUpdate {
+        let mut rng = rand::thread_rng();
+        let gradients: Vec<f32> = (0..options.gradient_size).map(|_| rng.gen_range(-0.01..0.01)).collect();
+        let mut digest = Sha256::new();
+        digest.update(self.institution_id.as_bytes());
+        gradients.iter().for_each(|g| digest.update(g.to_le_bytes()));
+        let signature: Signature = self.key.sign(&digest.finalize());
+        GradientUpdate {
+            institution_id: self.institution_id.clone(),
+            model_version: MODEL_VERSION.to_string(),
+            gradients,
+            sample_count: rng.gen_range(100..5_000),
+            privacy_budget: options.privacy_budget,
+            timestamp: now_nanos(),
+            signature: signature.to_bytes().to_vec(),
+            correlation_id: None,
+        }
+    }
+
+    fn medical_query(&self) -> MedicalQuery {
+        let mut rng = rand::thread_rng();
+        let count = rng.gen_range(2..5);
+        let symptoms = SYMPTOMS.choose_multiple(&mut rng, count).map(|s| s.to_string()).collect();
+        MedicalQuery {
+            patient_id: format!("{}-patient-{}", self.institution_id, rng.gen_range(0..10_000)),
+            symptoms,
+            medical_history: Vec::new(),
+            timestamp: now_nanos(),
+            correlation_id: Some(format!("load:{:x}", rng.gen::<u64>())),
+        }
+    }
+
+    async fn send(&self, kind: Kind, options: &Options) -> Result<(), String> {
+        match (kind, options.inference) {
+            (Kind::Update, _) => {
+                let update = Encode!(&self.gradient_update(options)).unwrap();
+                call::<String>(&self.agent, options.aggregator, "submit_gradient_update", update).await.map(|_| ())
+            }
+            (Kind::Query, Some(inference)) => {
+                call::<Reserved>(&self.agent, inference, "diagnose", Encode!(&self.medical_query()).unwrap()).await.map(|_| ())
+            }
+            (Kind::Query, None) => Err("No inference canister given".to_string()),
+        }
+    }
+}
+
+// Diagnosis needs a model; a fresh replica gets a zero model of the gradient size
+async fn ensure_model_loaded(operator: &Agent, inference: Principal, options: &Options) -> Result<(), String> {
+    let version: Option<String> = query(operator, inference, "get_model_version", Encode!().unwrap()).await?;
+    if version.is_some() {
+        return Ok(());
+    }
+    let weights = ModelWeights {
+        version: MODEL_VERSION.to_string(),
+        weights: vec![0.0; options.gradient_size],
+        metadata: HashMap::new(),
+        threshold_signature: vec![1],
+        correlation_id: None,
+    };
+    call::<String>(operator, inference, "update_model_weights", Encode!(&weights).unwrap()).await.map(|_| ())
+}
+
+// Sends one kind of request for one hospital every `period` until the deadline
+async fn schedule(hospital: Arc<Hospital>, kind: Kind, options: Arc<Options>, deadline: Instant, samples: Arc<Mutex<Vec<Sample>>>) {
+    let period = Duration::from_secs_f64(60.0 / options.rate(kind));
+    // Spread the hospitals over the first period so they do not fire in lockstep
+    tokio::time::sleep(period.mul_f64(rand::random::<f64>())).await;
+    let mut ticks = tokio::time::interval(period);
+    let mut in_flight = Vec::new();
+    loop {
+        ticks.tick().await;
+        if Instant::now() >= deadline {
+            break;
+        }
+        let (hospital, options, samples) = (hospital.clone(), options.clone(), samples.clone());
+        in_flight.push(tokio::spawn(async move {
+            let started = Instant::now();
+            let result = hospital.send(kind, &options).await;
+            samples.lock().unwrap().push(Sample { kind, latency: started.elapsed(), error: result.err() });
+        }));
+    }
+    for request in in_flight {
+        let _ = request.await;
+    }
+}
+
+async fn run() -> Result<(), String> {
+    let options = Arc::new(Options::parse(std::env::args().skip(1))?);
+    let operator = agent(&options.replica, &new_key()).await?;
+    let run_id = format!("{:x}", now_nanos());
+
+    if let Some(inference) = options.inference {
+        ensure_model_loaded(&operator, inference, &options).await?;
+    }
+    let task_id = format!("load-test-{}", run_id);
+    let launched: String =
+        call(&operator, options.aggregator, "launch_task", Encode!(&task_id, &options.round_target, &1.0f64, &None::<String>).unwrap())
+            .await?;
+    println!("{}", launched);
+
+    let started = Instant::now();
+    let onboarding: Vec<_> = (0..options.hospitals)
+        .map(|i| tokio::spawn(Hospital::onboard(options.clone(), operator.clone(), format!("load-{}-{}", run_id, i))))
+        .collect();
+    let mut hospitals = Vec::new();
+    for hospital in onboarding {
+        hospitals.push(Arc::new(hospital.await.map_err(|e| e.to_string())??));
+    }
+    println!("Onboarded {} hospitals in {:.1}s", hospitals.len(), started.elapsed().as_secs_f64());
+
+    let canisters: Vec<(&str, Principal)> =
+        [Some(("federated_aggregator", options.aggregator)), options.inference.map(|id| ("ai_inference", id))].into_iter().flatten().collect();
+    let mut before = Vec::new();
+    for (_, canister) in &canisters {
+        before.push(canister_metrics(&operator, *canister).await?);
+    }
+
+    println!(
+        "Running {}s: {} updates/min and {} queries/min per hospital",
+        options.duration.as_secs(),
+        options.rate(Kind::Update),
+        options.rate(Kind::Query)
+    );
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let deadline = Instant::now() + options.duration;
+    let mut schedules = Vec::new();
+    for hospital in &hospitals {
+        for kind in [Kind::Update, Kind::Query].into_iter().filter(|&kind| options.rate(kind) > 0.0) {
+            schedules.push(tokio::spawn(schedule(hospital.clone(), kind, options.clone(), deadline, samples.clone())));
+        }
+    }
+    for schedule in schedules {
+        schedule.await.map_err(|e| e.to_string())?;
+    }
+
+    let samples = samples.lock().unwrap().clone();
+    report::print_latency(&samples, options.duration);
+    let accepted_updates = samples.iter().filter(|s| s.kind == Kind::Update && s.error.is_none()).count();
+    for ((name, canister), before) in canisters.iter().zip(before) {
+        let after = canister_metrics(&operator, *canister).await?;
+        let accepted = if *name == "federated_aggregator" { Some(accepted_updates) } else { None };
+        report::print_canister(name, &before, &after, accepted);
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("load_generator: {}", e);
+        std::process::exit(1);
+    }
+}
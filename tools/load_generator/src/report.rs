@@ -0,0 +1,123 @@
+// Run report: latency percentiles per request kind, and the change in each
+// canister's call metrics and memory over the run.
+
+use governance::{CallStats, CanisterMetrics};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Update,
+    Query,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub kind: Kind,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+// Nearest-rank percentile of sorted latencies
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+pub fn print_latency(samples: &[Sample], duration: Duration) {
+    for (kind, label) in [(Kind::Update, "gradient updates"), (Kind::Query, "diagnosis queries")] {
+        let of_kind: Vec<&Sample> = samples.iter().filter(|s| s.kind == kind).collect();
+        if of_kind.is_empty() {
+            continue;
+        }
+        let mut latencies: Vec<Duration> = of_kind.iter().filter(|s| s.error.is_none()).map(|s| s.latency).collect();
+        latencies.sort();
+        println!();
+        println!(
+            "{}: {} sent, {} ok, {} failed, {:.2} ok/s",
+            label,
+            of_kind.len(),
+            latencies.len(),
+            of_kind.len() - latencies.len(),
+            latencies.len() as f64 / duration.as_secs_f64()
+        );
+        println!(
+            "  latency ms  p50 {:.0}  p90 {:.0}  p99 {:.0}  max {:.0}",
+            millis(percentile(&latencies, 50.0)),
+            millis(percentile(&latencies, 90.0)),
+            millis(percentile(&latencies, 99.0)),
+            millis(latencies.last().copied().unwrap_or_default())
+        );
+
+        let mut errors: HashMap<&str, usize> = HashMap::new();
+        of_kind.iter().filter_map(|s| s.error.as_deref()).for_each(|e| *errors.entry(e).or_default() += 1);
+        let mut errors: Vec<(&str, usize)> = errors.into_iter().collect();
+        errors.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        for (error, count) in errors.iter().take(3) {
+            println!("  {} × {}", count, error);
+        }
+    }
+}
+
+// `accepted` is the number of updates the run got into the canister, to put its
+// memory growth per update
+pub fn print_canister(name: &str, before: &CanisterMetrics, after: &CanisterMetrics, accepted: Option<usize>) {
+    println!();
+    println!("{}:", name);
+    for (method, stats) in &after.methods {
+        let earlier = before.methods.iter().find(|(m, _)| m == method).map(|(_, s)| s.clone()).unwrap_or_default();
+        let run = CallStats {
+            calls: stats.calls - earlier.calls,
+            total_instructions: stats.total_instructions - earlier.total_instructions,
+            max_instructions: stats.max_instructions,
+        };
+        if run.calls == 0 {
+            continue;
+        }
+        println!(
+            "  {}: {} calls, {:.1}M instructions mean, {:.1}M max since install",
+            method,
+            run.calls,
+            run.mean_instructions() as f64 / 1e6,
+            run.max_instructions as f64 / 1e6
+        );
+    }
+
+    let heap_growth = after.heap_bytes.saturating_sub(before.heap_bytes);
+    let stable_growth = after.stable_bytes.saturating_sub(before.stable_bytes);
+    println!(
+        "  heap {:.1} -> {:.1} MiB, stable {:.1} -> {:.1} MiB",
+        mib(before.heap_bytes),
+        mib(after.heap_bytes),
+        mib(before.stable_bytes),
+        mib(after.stable_bytes)
+    );
+    if let Some(accepted) = accepted.filter(|&accepted| accepted > 0) {
+        println!("  {:.1} KiB of memory growth per accepted update", (heap_growth + stable_growth) as f64 / 1024.0 / accepted as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_use_the_nearest_rank() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+}